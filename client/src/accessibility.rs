@@ -0,0 +1,170 @@
+use crate::{
+	config,
+	palette::{self, Palette},
+	theme::{self, ThemeName},
+};
+use egui::{style::ScrollAnimation, Context};
+use serde::{Deserialize, Serialize};
+
+/// Accessibility settings, kept between sessions.
+#[derive(Clone, Copy, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Accessibility {
+	/// Multiplies the size of the UI, on top of the display's own scaling.
+	pub ui_scale: f32,
+	/// Use [`Palette::ColorblindSafe`] instead of [`Palette::Standard`].
+	pub colorblind_palette: bool,
+	/// Turns off UI animations.
+	pub reduced_motion: bool,
+	pub theme: ThemeName,
+}
+
+impl Accessibility {
+	/// UI scales offered in settings, others can still be set in the file.
+	pub const UI_SCALE_PRESETS: &'static [f32] = &[0.75, 1.0, 1.25, 1.5, 1.75, 2.0];
+	const MIN_UI_SCALE: f32 = 0.5;
+	const MAX_UI_SCALE: f32 = 3.0;
+	const FILE: &'static str = "accessibility.json";
+
+	pub fn load() -> Self {
+		config::load(Self::FILE)
+	}
+
+	pub fn save(&self) {
+		config::save(Self::FILE, self);
+	}
+
+	/// Applies the settings to `context`, the [`palette`], and the [`theme`], call whenever they change.
+	pub fn apply(&self, context: &Context) {
+		let ui_scale = match self.ui_scale.is_finite() {
+			true => self.ui_scale.clamp(Self::MIN_UI_SCALE, Self::MAX_UI_SCALE),
+			false => 1.0,
+		};
+
+		context.set_zoom_factor(ui_scale);
+
+		let palette = match self.colorblind_palette {
+			true => Palette::ColorblindSafe,
+			false => Palette::Standard,
+		};
+
+		palette::select(palette);
+		theme::select(self.theme);
+
+		// Only the selected theme is used, rather than following the system's
+		context.set_theme(match self.theme {
+			ThemeName::Dark => egui::Theme::Dark,
+			ThemeName::Light => egui::Theme::Light,
+		});
+
+		let theme = self.theme.theme(palette);
+
+		context.all_styles_mut(|style| {
+			theme.apply(style);
+
+			match self.reduced_motion {
+				true => {
+					style.animation_time = 0.0;
+					style.scroll_animation = ScrollAnimation::none();
+				}
+				false => {
+					style.animation_time = egui::Style::default().animation_time;
+					style.scroll_animation = ScrollAnimation::default();
+				}
+			}
+		});
+	}
+}
+
+impl Default for Accessibility {
+	fn default() -> Self {
+		Self {
+			ui_scale: 1.0,
+			colorblind_palette: false,
+			reduced_motion: false,
+			theme: ThemeName::Dark,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn ui_scale_is_clamped() {
+		let context = Context::default();
+
+		for (ui_scale, applied) in [
+			(1.25, 1.25),
+			(0.1, Accessibility::MIN_UI_SCALE),
+			(10.0, Accessibility::MAX_UI_SCALE),
+			(f32::NAN, 1.0),
+			(f32::INFINITY, 1.0),
+		] {
+			Accessibility {
+				ui_scale,
+				..Default::default()
+			}
+			.apply(&context);
+
+			// Takes effect from the next frame
+			let _ = context.run(Default::default(), |_| {});
+			assert_eq!(context.zoom_factor(), applied, "{ui_scale}");
+		}
+	}
+
+	#[test]
+	fn reduced_motion_turns_off_animations() {
+		let context = Context::default();
+		let animation_time = |context: &Context| context.style().animation_time;
+
+		Accessibility {
+			reduced_motion: true,
+			..Default::default()
+		}
+		.apply(&context);
+		assert_eq!(animation_time(&context), 0.0);
+
+		Accessibility::default().apply(&context);
+		assert_eq!(
+			animation_time(&context),
+			egui::Style::default().animation_time
+		);
+	}
+
+	#[test]
+	fn missing_settings_are_defaults() {
+		let accessibility: Accessibility = serde_json::from_str(r#"{ "ui_scale": 1.5 }"#).unwrap();
+
+		assert!(
+			accessibility
+				== Accessibility {
+					ui_scale: 1.5,
+					..Default::default()
+				}
+		);
+	}
+
+	#[test]
+	fn the_theme_is_applied_and_kept() {
+		let context = Context::default();
+
+		let accessibility = Accessibility {
+			theme: ThemeName::Light,
+			..Default::default()
+		};
+		accessibility.apply(&context);
+		assert!(!context.style().visuals.dark_mode);
+
+		let json = serde_json::to_string(&accessibility).unwrap();
+		assert!(serde_json::from_str::<Accessibility>(&json).unwrap() == accessibility);
+
+		Accessibility::default().apply(&context);
+		assert!(context.style().visuals.dark_mode);
+
+		// Files from before there were themes
+		let accessibility: Accessibility = serde_json::from_str(r#"{ "ui_scale": 1.5 }"#).unwrap();
+		assert_eq!(accessibility.theme, ThemeName::Dark);
+	}
+}
@@ -0,0 +1,114 @@
+use clap::ValueEnum;
+use log::info;
+use std::fmt::{self, Display, Formatter};
+use thiserror::Error;
+use wgpu::{Adapter, AdapterInfo, Backends, DeviceType, Instance, Surface};
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Backend {
+	Vulkan,
+	Gl,
+	Metal,
+	Dx12,
+}
+
+impl Backend {
+	/// Every backend we support, wgpu only compiles in the ones available on the current platform.
+	pub const ALL: Backends = Backends::VULKAN
+		.union(Backends::GL)
+		.union(Backends::METAL)
+		.union(Backends::DX12);
+
+	pub const fn backends(self) -> Backends {
+		match self {
+			Self::Vulkan => Backends::VULKAN,
+			Self::Gl => Backends::GL,
+			Self::Metal => Backends::METAL,
+			Self::Dx12 => Backends::DX12,
+		}
+	}
+}
+
+/// Overrides for which adapter is picked, from the command line.
+#[derive(Clone, Default)]
+pub struct AdapterPreference {
+	/// Only adapters with a name containing this (case insensitive) will be used.
+	pub gpu: Option<String>,
+	pub backend: Option<Backend>,
+}
+
+impl AdapterPreference {
+	pub fn backends(&self) -> Backends {
+		self.backend.map_or(Backend::ALL, Backend::backends)
+	}
+}
+
+/// Picks the adapter matching the [`AdapterPreference`], preferring discrete GPUs, then integrated ones. Every
+/// adapter found is logged to make it easier to work out why the wrong one was picked.
+pub fn select_adapter(
+	instance: &Instance,
+	surface: &Surface,
+	preference: &AdapterPreference,
+) -> Result<Adapter, AdapterError> {
+	let adapters = instance
+		.enumerate_adapters(preference.backends())
+		.into_iter()
+		.filter(|adapter| adapter.is_surface_supported(surface))
+		.collect::<Vec<_>>();
+
+	for adapter in &adapters {
+		info!("Found adapter: {}", Described(&adapter.get_info()));
+	}
+
+	let available = adapters
+		.iter()
+		.map(|adapter| Described(&adapter.get_info()).to_string())
+		.collect::<Vec<_>>();
+
+	adapters
+		.into_iter()
+		.filter(|adapter| match &preference.gpu {
+			Some(gpu) => adapter
+				.get_info()
+				.name
+				.to_lowercase()
+				.contains(&gpu.to_lowercase()),
+			None => true,
+		})
+		.min_by_key(|adapter| match adapter.get_info().device_type {
+			DeviceType::DiscreteGpu => 0,
+			DeviceType::IntegratedGpu => 1,
+			DeviceType::VirtualGpu => 2,
+			DeviceType::Other => 3,
+			DeviceType::Cpu => 4,
+		})
+		.ok_or_else(|| match preference.gpu {
+			Some(ref gpu) => AdapterError::NotFound {
+				gpu: gpu.clone(),
+				available,
+			},
+			None => AdapterError::NoAdapter,
+		})
+}
+
+/// Displays an adapter as `name (backend, device type)`.
+pub struct Described<'a>(pub &'a AdapterInfo);
+
+impl Display for Described<'_> {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		write!(
+			formatter,
+			"{} ({:?}, {:?})",
+			self.0.name, self.0.backend, self.0.device_type
+		)
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum AdapterError {
+	#[error("unable to find suitable adapter")]
+	NoAdapter,
+
+	#[error("unable to find an adapter matching `{gpu}`, available adapters:\n  {}", available.join("\n  "))]
+	NotFound { gpu: String, available: Vec<String> },
+}
@@ -0,0 +1,222 @@
+use nalgebra::{vector, Vector3};
+use solarscape_shared::message::clientbound::{Ambient, AmbientKeyframe};
+use std::f32::consts::TAU;
+use wgpu::Color;
+
+/// The client's copy of the sector's ambient cycle, which is advanced locally between updates from the server.
+pub struct AmbientCycle {
+	keyframes: Vec<AmbientKeyframe>,
+	phase: f32,
+	rate: f32,
+}
+
+impl AmbientCycle {
+	pub fn new(keyframes: Vec<AmbientKeyframe>, Ambient { phase, rate }: Ambient) -> Self {
+		Self {
+			keyframes,
+			phase,
+			rate,
+		}
+	}
+
+	pub fn update(&mut self, Ambient { phase, rate }: Ambient) {
+		self.phase = phase;
+		self.rate = rate;
+	}
+
+	pub fn tick(&mut self, delta: f32) {
+		self.phase = (self.phase + self.rate * delta).rem_euclid(1.0);
+	}
+
+	pub fn phase(&self) -> f32 {
+		self.phase
+	}
+
+	pub fn keyframes(&self) -> &[AmbientKeyframe] {
+		&self.keyframes
+	}
+
+	/// The cycle's current state, as the sector would send it.
+	pub fn build_sync(&self) -> Ambient {
+		Ambient {
+			phase: self.phase,
+			rate: self.rate,
+		}
+	}
+
+	pub fn paused(&self) -> bool {
+		self.rate == 0.0
+	}
+
+	pub fn lighting(&self) -> Lighting {
+		lighting(&self.keyframes, self.phase)
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Lighting {
+	pub sky: [f32; 3],
+
+	/// Towards the sun.
+	pub sun_direction: Vector3<f32>,
+	pub sun: [f32; 3],
+
+	pub ambient: [f32; 3],
+}
+
+impl Lighting {
+	/// Used if the sector has no keyframes, which the server shouldn't allow.
+	const FALLBACK: Self = Self {
+		sky: [0.0; 3],
+		sun_direction: Vector3::new(0.0, 1.0, 0.0),
+		sun: [1.0; 3],
+		ambient: [0.3; 3],
+	};
+
+	/// Layout matches the `Lighting` push constants in chunk.wgsl and structure.wgsl, where each `vec3` takes 16 bytes.
+	pub fn push_constants(&self) -> [f32; 12] {
+		let [direction_x, direction_y, direction_z] = self.sun_direction.into();
+		let [sun_r, sun_g, sun_b] = self.sun;
+		let [ambient_r, ambient_g, ambient_b] = self.ambient;
+
+		#[rustfmt::skip]
+		let push_constants = [
+			direction_x, direction_y, direction_z, 0.0,
+			sun_r, sun_g, sun_b, 0.0,
+			ambient_r, ambient_g, ambient_b, 0.0,
+		];
+
+		push_constants
+	}
+
+	pub fn clear_color(&self) -> Color {
+		let [r, g, b] = self.sky.map(f64::from);
+		Color { r, g, b, a: 1.0 }
+	}
+}
+
+/// Lighting at `phase`, interpolated between the keyframes either side of it. The cycle wraps around, so between the
+/// last keyframe and the first is interpolated too.
+///
+/// The sun circles around the x axis, rising at phase 0 and directly overhead at 0.25.
+pub fn lighting(keyframes: &[AmbientKeyframe], phase: f32) -> Lighting {
+	let Some(last) = keyframes.last() else {
+		return Lighting::FALLBACK;
+	};
+
+	let phase = phase.rem_euclid(1.0);
+
+	// The last keyframe at or before phase, if phase is before the first keyframe then it's the last one, wrapped
+	let index = keyframes
+		.iter()
+		.rposition(|keyframe| keyframe.phase <= phase);
+	let (from, to) = match index {
+		Some(index) => (&keyframes[index], &keyframes[(index + 1) % keyframes.len()]),
+		None => (last, &keyframes[0]),
+	};
+
+	let span = (to.phase - from.phase).rem_euclid(1.0);
+	let t = match span > 0.0 {
+		true => (phase - from.phase).rem_euclid(1.0) / span,
+		// Only one keyframe
+		false => 0.0,
+	};
+
+	let angle = phase * TAU;
+
+	Lighting {
+		sky: lerp(from.sky, to.sky, t),
+		sun_direction: vector![angle.cos(), angle.sin(), 0.0],
+		sun: lerp(from.sun, to.sun, t),
+		ambient: lerp(from.ambient, to.ambient, t),
+	}
+}
+
+fn lerp(from: [f32; 3], to: [f32; 3], t: f32) -> [f32; 3] {
+	[0, 1, 2].map(|index| from[index] + (to[index] - from[index]) * t)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn keyframe(phase: f32, brightness: f32) -> AmbientKeyframe {
+		AmbientKeyframe {
+			phase,
+			sky: [brightness; 3],
+			sun: [brightness * 2.0; 3],
+			ambient: [brightness / 2.0; 3],
+		}
+	}
+
+	fn assert_near(a: [f32; 3], b: [f32; 3]) {
+		assert!(
+			a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5),
+			"{a:?} != {b:?}"
+		);
+	}
+
+	#[test]
+	fn lighting_is_interpolated_between_keyframes() {
+		let keyframes = [keyframe(0.0, 0.0), keyframe(0.5, 1.0)];
+
+		assert_near(lighting(&keyframes, 0.0).sky, [0.0; 3]);
+		assert_near(lighting(&keyframes, 0.25).sky, [0.5; 3]);
+		assert_near(lighting(&keyframes, 0.25).sun, [1.0; 3]);
+		assert_near(lighting(&keyframes, 0.25).ambient, [0.25; 3]);
+		assert_near(lighting(&keyframes, 0.5).sky, [1.0; 3]);
+	}
+
+	#[test]
+	fn lighting_wraps_from_the_last_keyframe_to_the_first() {
+		let keyframes = [keyframe(0.25, 0.0), keyframe(0.75, 1.0)];
+
+		// Half way from the last keyframe around to the first, either side of 0
+		assert_near(lighting(&keyframes, 0.0).sky, [0.5; 3]);
+		assert_near(lighting(&keyframes, 0.875).sky, [0.75; 3]);
+		assert_near(lighting(&keyframes, 0.125).sky, [0.25; 3]);
+
+		// Phases outside of 0 to 1 wrap too
+		assert_near(lighting(&keyframes, 1.5).sky, [0.5; 3]);
+		assert_near(lighting(&keyframes, -0.5).sky, [0.5; 3]);
+	}
+
+	#[test]
+	fn lighting_with_one_or_no_keyframes() {
+		assert_near(lighting(&[keyframe(0.5, 0.7)], 0.1).sky, [0.7; 3]);
+		assert_eq!(lighting(&[], 0.3), Lighting::FALLBACK);
+	}
+
+	#[test]
+	fn sun_rises_and_sets() {
+		let keyframes = [keyframe(0.0, 1.0)];
+		let direction = |phase| lighting(&keyframes, phase).sun_direction;
+
+		assert!((direction(0.0) - Vector3::x()).norm() < 1e-5);
+		assert!((direction(0.25) - Vector3::y()).norm() < 1e-5);
+		assert!((direction(0.75) + Vector3::y()).norm() < 1e-5);
+	}
+
+	#[test]
+	fn cycle_advances_locally_until_paused() {
+		let mut cycle = AmbientCycle::new(
+			vec![],
+			Ambient {
+				phase: 0.9,
+				rate: 0.1,
+			},
+		);
+
+		cycle.tick(2.0);
+		assert!((cycle.phase() - 0.1).abs() < 1e-5);
+		assert!(!cycle.paused());
+
+		cycle.update(Ambient {
+			phase: 0.5,
+			rate: 0.0,
+		});
+		cycle.tick(2.0);
+		assert_eq!(cycle.phase(), 0.5);
+		assert!(cycle.paused());
+	}
+}
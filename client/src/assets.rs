@@ -0,0 +1,601 @@
+use crate::config;
+use ab_glyph::FontRef;
+use egui::FontData;
+use image::{ImageError, ImageFormat, RgbaImage};
+use log::info;
+use std::{
+	borrow::Cow,
+	fs,
+	io::{self, ErrorKind::NotFound},
+	path::{Path, PathBuf},
+	str::{self, Utf8Error},
+	time::SystemTime,
+};
+use thiserror::Error;
+use tobj::{LoadError, Model, GPU_LOAD_OPTIONS};
+
+#[cfg(debug)]
+use std::{
+	collections::HashMap,
+	time::{Duration, Instant},
+};
+
+/// Where the client's models, textures, and shaders are loaded from. Any file in the assets directory is used instead
+/// of the copy built into the client, so that they can be changed without rebuilding or replaced by resource packs.
+/// Files that can't be used are reported and the built in copy is used instead.
+#[derive(Clone)]
+pub struct Assets {
+	directory: Option<PathBuf>,
+
+	/// When each asset's file was last modified as of when it was loaded, [`None`] if it wasn't overridden.
+	#[cfg(debug)]
+	loaded: HashMap<Asset, Option<SystemTime>>,
+	#[cfg(debug)]
+	last_checked: Instant,
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Asset {
+	TerrainTextures,
+	StructureBlockTextures,
+	StructureBlockModels,
+	ChunkShader,
+	StructureShader,
+	DebugLineShader,
+	WorldBorderShader,
+	UiFont,
+}
+
+impl Asset {
+	pub const ALL: [Self; 8] = [
+		Self::TerrainTextures,
+		Self::StructureBlockTextures,
+		Self::StructureBlockModels,
+		Self::ChunkShader,
+		Self::StructureShader,
+		Self::DebugLineShader,
+		Self::WorldBorderShader,
+		Self::UiFont,
+	];
+
+	pub const fn file_name(self) -> &'static str {
+		match self {
+			Self::TerrainTextures => "terrain_textures.png",
+			Self::StructureBlockTextures => "structure_block_textures.png",
+			Self::StructureBlockModels => "structure_blocks.obj",
+			Self::ChunkShader => "chunk.wgsl",
+			Self::StructureShader => "structure.wgsl",
+			Self::DebugLineShader => "debug_line.wgsl",
+			Self::WorldBorderShader => "world_border.wgsl",
+			Self::UiFont => "ui_font.ttf",
+		}
+	}
+
+	const fn embedded(self) -> &'static [u8] {
+		match self {
+			Self::TerrainTextures => include_bytes!("resources/terrain_textures.png"),
+			Self::StructureBlockTextures => {
+				include_bytes!("resources/structure_block_textures.png")
+			}
+			Self::StructureBlockModels => include_bytes!("resources/structure_blocks.obj"),
+			Self::ChunkShader => include_bytes!("chunk.wgsl"),
+			Self::StructureShader => include_bytes!("structure.wgsl"),
+			Self::DebugLineShader => include_bytes!("debug_line.wgsl"),
+			Self::WorldBorderShader => include_bytes!("world_border.wgsl"),
+			// egui's own fonts are used unless there's an override, see Assets::ui_font
+			Self::UiFont => &[],
+		}
+	}
+}
+
+/// Why an asset in the assets directory wasn't used.
+#[derive(Debug, Error)]
+pub enum AssetError {
+	#[error("unable to read {0}: {1}")]
+	Read(PathBuf, io::Error),
+
+	#[error("{0} is not a valid PNG: {1}")]
+	Image(PathBuf, ImageError),
+
+	#[error("{path} is {width}x{height}, but {expected}")]
+	Dimensions {
+		path: PathBuf,
+		width: u32,
+		height: u32,
+		expected: String,
+	},
+
+	#[error("{0} is not a valid OBJ: {1}")]
+	Model(PathBuf, LoadError),
+
+	#[error(
+		"{0} has no model named MissingBlock, which is used in place of any other missing model"
+	)]
+	NoMissingBlock(PathBuf),
+
+	#[error("{0} is not valid UTF-8: {1}")]
+	Utf8(PathBuf, Utf8Error),
+
+	#[error("{0} is not a valid shader: {1}")]
+	Shader(PathBuf, String),
+
+	#[error("{0} is not a valid TrueType or OpenType font")]
+	Font(PathBuf),
+}
+
+/// An asset, and why the override was rejected if it was.
+pub type Loaded<T> = (T, Option<AssetError>);
+
+/// Where an asset was loaded from.
+pub enum Source {
+	Embedded,
+	Directory(PathBuf),
+}
+
+impl Source {
+	/// Path to mention in errors, which for embedded assets is just the file name.
+	fn path(&self, asset: Asset) -> PathBuf {
+		match self {
+			Self::Embedded => PathBuf::from(asset.file_name()),
+			Self::Directory(path) => path.clone(),
+		}
+	}
+}
+
+/// Expected layout of a texture atlas.
+#[derive(Clone, Copy)]
+pub struct AtlasLayout {
+	/// The atlas must be a whole number of cells of this many pixels on each side.
+	pub cell_size: Option<u32>,
+	/// The atlas must be this many cells on each side.
+	pub cells: Option<u32>,
+	/// Largest texture the device supports.
+	pub max_dimension: u32,
+}
+
+impl Assets {
+	/// Uses `directory`, or `assets` next to the client's other config if not given.
+	pub fn new(directory: Option<PathBuf>) -> Self {
+		let directory =
+			directory.or_else(|| config::directory().map(|config| config.join("assets")));
+
+		if let Some(directory) = &directory {
+			info!("Loading assets from {}", directory.display());
+		}
+
+		Self {
+			directory,
+
+			#[cfg(debug)]
+			loaded: HashMap::new(),
+			#[cfg(debug)]
+			last_checked: Instant::now(),
+		}
+	}
+
+	fn override_path(&self, asset: Asset) -> Option<PathBuf> {
+		self.directory
+			.as_deref()
+			.map(|directory| directory.join(asset.file_name()))
+	}
+
+	/// Reads `asset` from the assets directory if it's there, otherwise uses the built in copy. A file that exists but
+	/// can't be read is an error.
+	pub fn read(&mut self, asset: Asset) -> Result<(Cow<'static, [u8]>, Source), AssetError> {
+		let result = read_override(self.override_path(asset).as_deref());
+
+		#[cfg(debug)]
+		self.loaded.insert(
+			asset,
+			match &result {
+				Ok(Some((_, modified))) => *modified,
+				_ => None,
+			},
+		);
+
+		match result? {
+			Some((bytes, _)) => Ok((
+				Cow::Owned(bytes),
+				Source::Directory(
+					self.override_path(asset)
+						.expect("there must be a directory to have read from it"),
+				),
+			)),
+			None => Ok((Cow::Borrowed(asset.embedded()), Source::Embedded)),
+		}
+	}
+
+	/// Loads `asset` with `load`, falling back to the built in copy if there's an override that can't be read or
+	/// loaded. The override's error is returned along with the built in copy so that it can be reported, this only
+	/// fails if the built in copy can't be loaded either.
+	fn load_or_embedded<T>(
+		&mut self,
+		asset: Asset,
+		load: impl Fn(&[u8], &Path) -> Result<T, AssetError>,
+	) -> Result<Loaded<T>, AssetError> {
+		let rejected = match self.read(asset) {
+			Ok((bytes, source)) => match load(&bytes, &source.path(asset)) {
+				Ok(value) => return Ok((value, None)),
+				Err(error) => error,
+			},
+			Err(error) => error,
+		};
+
+		let value = load(asset.embedded(), Path::new(asset.file_name()))?;
+
+		Ok((value, Some(rejected)))
+	}
+
+	/// Loads a texture atlas, which must match `layout`.
+	pub fn texture(
+		&mut self,
+		asset: Asset,
+		layout: AtlasLayout,
+	) -> Result<Loaded<RgbaImage>, AssetError> {
+		self.load_or_embedded(asset, |bytes, path| load_texture(bytes, path, layout))
+	}
+
+	/// Loads the structure block models, which must include `MissingBlock`. Texture coordinates are flipped vertically
+	/// to match wgpu.
+	pub fn structure_block_models(&mut self) -> Result<Loaded<Vec<Model>>, AssetError> {
+		self.load_or_embedded(Asset::StructureBlockModels, load_structure_block_models)
+	}
+
+	/// Loads a shader, `create` should create whatever uses it from it's source and return any validation errors.
+	pub fn shader<T>(
+		&mut self,
+		asset: Asset,
+		create: impl Fn(&str) -> Result<T, String>,
+	) -> Result<Loaded<T>, AssetError> {
+		self.load_or_embedded(asset, |bytes, path| {
+			let source =
+				str::from_utf8(bytes).map_err(|error| AssetError::Utf8(path.into(), error))?;
+			create(source).map_err(|error| AssetError::Shader(path.into(), error))
+		})
+	}
+
+	/// Loads the font used for the UI, [`None`] for egui's own fonts. A font that can't be parsed would crash egui once
+	/// it's used, so it's checked here first.
+	pub fn ui_font(&mut self) -> Result<Loaded<Option<FontData>>, AssetError> {
+		self.load_or_embedded(Asset::UiFont, |bytes, path| {
+			if bytes.is_empty() {
+				return Ok(None);
+			}
+
+			FontRef::try_from_slice(bytes).map_err(|_| AssetError::Font(path.into()))?;
+			Ok(Some(FontData::from_owned(bytes.to_vec())))
+		})
+	}
+
+	/// Assets whose files have changed since they were loaded, checked at most once a second.
+	#[cfg(debug)]
+	pub fn changed(&mut self) -> Vec<Asset> {
+		const INTERVAL: Duration = Duration::from_secs(1);
+
+		if self.last_checked.elapsed() < INTERVAL {
+			return vec![];
+		}
+
+		self.last_checked = Instant::now();
+
+		self.loaded
+			.iter()
+			.filter(|(asset, modified)| {
+				let current = self
+					.override_path(**asset)
+					.and_then(|path| fs::metadata(path).ok())
+					.and_then(|metadata| metadata.modified().ok());
+
+				current != **modified
+			})
+			.map(|(asset, _)| *asset)
+			.collect()
+	}
+}
+
+/// An asset file's contents and when it was last modified.
+type Override = (Vec<u8>, Option<SystemTime>);
+
+/// Reads `path` and when it was last modified, [`None`] if there's no path or no file there.
+fn read_override(path: Option<&Path>) -> Result<Option<Override>, AssetError> {
+	let Some(path) = path else {
+		return Ok(None);
+	};
+
+	match fs::read(path) {
+		Ok(bytes) => {
+			let modified = fs::metadata(path)
+				.and_then(|metadata| metadata.modified())
+				.ok();
+
+			info!("Using {}", path.display());
+			Ok(Some((bytes, modified)))
+		}
+		Err(error) if error.kind() == NotFound => Ok(None),
+		Err(error) => Err(AssetError::Read(path.into(), error)),
+	}
+}
+
+fn load_texture(bytes: &[u8], path: &Path, layout: AtlasLayout) -> Result<RgbaImage, AssetError> {
+	let image = image::load_from_memory_with_format(bytes, ImageFormat::Png)
+		.map_err(|error| AssetError::Image(path.into(), error))?
+		.to_rgba8();
+
+	let (width, height) = image.dimensions();
+
+	let expected = if width == 0 || height == 0 {
+		Some("it must not be empty".to_string())
+	} else if width > layout.max_dimension || height > layout.max_dimension {
+		Some(format!(
+			"this device only supports textures up to {0}x{0}",
+			layout.max_dimension
+		))
+	} else if let Some(cells) = layout
+		.cells
+		.filter(|cells| width != height || width % cells != 0)
+	{
+		Some(format!(
+			"it must be square and divide into {cells}x{cells} cells"
+		))
+	} else {
+		layout
+			.cell_size
+			.filter(|cell_size| width % cell_size != 0 || height % cell_size != 0)
+			.map(|cell_size| format!("it must be a multiple of {cell_size} pixels on each side"))
+	};
+
+	match expected {
+		Some(expected) => Err(AssetError::Dimensions {
+			path: path.into(),
+			width,
+			height,
+			expected,
+		}),
+		None => Ok(image),
+	}
+}
+
+fn load_structure_block_models(bytes: &[u8], path: &Path) -> Result<Vec<Model>, AssetError> {
+	let (mut models, _) = tobj::load_obj_buf(
+		&mut &*bytes,
+		&GPU_LOAD_OPTIONS,
+		// Materials aren't used, so whichever material library the models refer to doesn't need to be loaded
+		|_| Ok(Default::default()),
+	)
+	.map_err(|error| AssetError::Model(path.into(), error))?;
+
+	if !models.iter().any(|model| model.name == "MissingBlock") {
+		return Err(AssetError::NoMissingBlock(path.into()));
+	}
+
+	for model in &mut models {
+		for coordinate in model.mesh.texcoords.iter_mut().skip(1).step_by(2) {
+			*coordinate = 1.0 - *coordinate;
+		}
+	}
+
+	Ok(models)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use solarscape_shared::data::Id;
+	use std::{env, io::Cursor};
+
+	/// A temporary assets directory, deleted when dropped.
+	struct TempDir(PathBuf);
+
+	impl TempDir {
+		fn new() -> Self {
+			let directory = env::temp_dir().join(format!("solarscape-assets-{}", Id::new()));
+			fs::create_dir_all(&directory).unwrap();
+			Self(directory)
+		}
+
+		fn write(&self, asset: Asset, contents: &[u8]) {
+			fs::write(self.0.join(asset.file_name()), contents).unwrap();
+		}
+
+		fn assets(&self) -> Assets {
+			Assets::new(Some(self.0.clone()))
+		}
+	}
+
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			let _ = fs::remove_dir_all(&self.0);
+		}
+	}
+
+	fn png(width: u32, height: u32) -> Vec<u8> {
+		let mut bytes = vec![];
+		RgbaImage::new(width, height)
+			.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+			.unwrap();
+		bytes
+	}
+
+	const LAYOUT: AtlasLayout = AtlasLayout {
+		cell_size: Some(16),
+		cells: None,
+		max_dimension: 256,
+	};
+
+	#[test]
+	fn missing_files_use_the_embedded_copy() {
+		let directory = TempDir::new();
+		let (bytes, source) = directory.assets().read(Asset::ChunkShader).unwrap();
+
+		assert_eq!(&*bytes, include_bytes!("chunk.wgsl"));
+		assert!(matches!(source, Source::Embedded));
+	}
+
+	#[test]
+	fn files_in_the_directory_override_the_embedded_copy() {
+		let directory = TempDir::new();
+		directory.write(Asset::ChunkShader, b"override");
+
+		let (bytes, source) = directory.assets().read(Asset::ChunkShader).unwrap();
+
+		assert_eq!(&*bytes, b"override");
+		assert!(
+			matches!(source, Source::Directory(path) if path == directory.0.join("chunk.wgsl"))
+		);
+	}
+
+	#[test]
+	fn unreadable_overrides_fall_back_with_an_error() {
+		let directory = TempDir::new();
+		fs::create_dir(directory.0.join(Asset::ChunkShader.file_name())).unwrap();
+
+		let mut assets = directory.assets();
+		assert!(matches!(
+			assets.read(Asset::ChunkShader),
+			Err(AssetError::Read(..))
+		));
+
+		let (source, rejected) = assets
+			.shader(Asset::ChunkShader, |source| Ok(source.len()))
+			.unwrap();
+		assert_eq!(source, include_str!("chunk.wgsl").len());
+		assert!(matches!(rejected, Some(AssetError::Read(..))));
+	}
+
+	#[test]
+	fn invalid_shaders_fall_back_with_an_error() {
+		let directory = TempDir::new();
+		let mut assets = directory.assets();
+		let create = |source: &str| match source.contains("broken") {
+			true => Err("broken".to_string()),
+			false => Ok(source.to_string()),
+		};
+
+		directory.write(Asset::StructureShader, b"broken");
+		let (source, rejected) = assets.shader(Asset::StructureShader, create).unwrap();
+		assert_eq!(source, include_str!("structure.wgsl"));
+		assert!(matches!(rejected, Some(AssetError::Shader(_, error)) if error == "broken"));
+
+		directory.write(Asset::StructureShader, &[0xFF, 0xFE]);
+		let (_, rejected) = assets.shader(Asset::StructureShader, create).unwrap();
+		assert!(matches!(rejected, Some(AssetError::Utf8(..))));
+
+		directory.write(Asset::StructureShader, b"fine");
+		let (source, rejected) = assets.shader(Asset::StructureShader, create).unwrap();
+		assert_eq!((&*source, rejected.is_none()), ("fine", true));
+	}
+
+	#[test]
+	fn texture_dimensions_are_checked() {
+		let path = Path::new("test.png");
+		let dimensions =
+			|width, height, layout| match load_texture(&png(width, height), path, layout) {
+				Ok(_) => None,
+				Err(AssetError::Dimensions { expected, .. }) => Some(expected),
+				Err(error) => panic!("{error}"),
+			};
+
+		assert_eq!(dimensions(32, 64, LAYOUT), None);
+		assert_eq!(
+			dimensions(512, 16, LAYOUT).unwrap(),
+			"this device only supports textures up to 256x256"
+		);
+		assert_eq!(
+			dimensions(24, 32, LAYOUT).unwrap(),
+			"it must be a multiple of 16 pixels on each side"
+		);
+
+		let grid = AtlasLayout {
+			cells: Some(4),
+			..LAYOUT
+		};
+		assert_eq!(dimensions(64, 64, grid), None);
+		assert_eq!(
+			dimensions(64, 32, grid).unwrap(),
+			"it must be square and divide into 4x4 cells"
+		);
+
+		assert!(matches!(
+			load_texture(b"not a png", path, LAYOUT),
+			Err(AssetError::Image(..))
+		));
+	}
+
+	#[test]
+	fn rejected_textures_fall_back_to_the_embedded_copy() {
+		let directory = TempDir::new();
+		let mut assets = directory.assets();
+		let layout = AtlasLayout {
+			cell_size: None,
+			max_dimension: 8192,
+			..LAYOUT
+		};
+
+		directory.write(Asset::TerrainTextures, &png(8, 8));
+		let (image, rejected) = assets.texture(Asset::TerrainTextures, layout).unwrap();
+		assert_eq!((image.dimensions(), rejected.is_none()), ((8, 8), true));
+
+		directory.write(Asset::TerrainTextures, &png(8192 * 2, 1));
+		let (image, rejected) = assets.texture(Asset::TerrainTextures, layout).unwrap();
+		assert_ne!(image.dimensions(), (8192 * 2, 1));
+		assert!(matches!(
+			rejected,
+			Some(AssetError::Dimensions { width: 16384, .. })
+		));
+	}
+
+	#[test]
+	fn models_need_a_missing_block() {
+		let path = Path::new("test.obj");
+		let triangle = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0.25\nvt 1 0\nvt 0 1\nf 1/1 2/2 3/3\n";
+
+		assert!(matches!(
+			load_structure_block_models(format!("o Block\n{triangle}").as_bytes(), path),
+			Err(AssetError::NoMissingBlock(_))
+		));
+
+		let models =
+			load_structure_block_models(format!("o MissingBlock\n{triangle}").as_bytes(), path)
+				.unwrap();
+
+		// Flipped vertically for wgpu
+		assert_eq!(models[0].mesh.texcoords[..2], [0.0, 0.75]);
+	}
+
+	#[test]
+	fn embedded_assets_load() {
+		let directory = TempDir::new();
+		let mut assets = directory.assets();
+
+		let (models, rejected) = assets.structure_block_models().unwrap();
+		assert!(models.iter().any(|model| model.name == "MissingBlock"));
+		assert!(rejected.is_none());
+
+		let (font, rejected) = assets.ui_font().unwrap();
+		assert!(font.is_none() && rejected.is_none());
+	}
+
+	#[test]
+	fn invalid_fonts_fall_back_to_egui_fonts() {
+		let directory = TempDir::new();
+		directory.write(Asset::UiFont, b"not a font");
+
+		let (font, rejected) = directory.assets().ui_font().unwrap();
+
+		assert!(font.is_none());
+		assert!(matches!(rejected, Some(AssetError::Font(_))));
+	}
+
+	#[test]
+	fn valid_fonts_override_egui_fonts() {
+		let directory = TempDir::new();
+		let fonts = egui::FontDefinitions::default();
+		let font = &fonts.font_data["Ubuntu-Light"].font;
+		directory.write(Asset::UiFont, font);
+
+		let (loaded, rejected) = directory.assets().ui_font().unwrap();
+
+		assert_eq!(loaded.unwrap().font, *font);
+		assert!(rejected.is_none());
+	}
+}
@@ -0,0 +1,65 @@
+use egui::{RichText, Ui};
+use solarscape_shared::message::clientbound::ChatMessage;
+use std::collections::VecDeque;
+
+/// Chat messages received since joining, shown above the chat box while it's open. Starts with the history the sector
+/// sends on join, which is shown dimmed.
+#[derive(Default)]
+pub struct ChatLog {
+	lines: VecDeque<ChatLine>,
+	/// Sequence number of the newest message seen, even if it has since been retracted.
+	newest: Option<u64>,
+}
+
+struct ChatLine {
+	message: ChatMessage,
+	/// Whether this was sent before the player joined.
+	history: bool,
+}
+
+impl ChatLog {
+	/// Oldest lines are dropped to make room past this.
+	const MAX_LINES: usize = 100;
+
+	pub fn push_history(&mut self, history: Vec<ChatMessage>) {
+		for message in history {
+			self.push_line(message, true);
+		}
+	}
+
+	/// Adds a live message, returns `false` if it has already been seen.
+	pub fn push(&mut self, message: ChatMessage) -> bool {
+		self.push_line(message, false)
+	}
+
+	fn push_line(&mut self, message: ChatMessage, history: bool) -> bool {
+		// Sequence numbers only ever go up, so anything at or below the newest is a duplicate
+		if self.newest.is_some_and(|newest| message.sequence <= newest) {
+			return false;
+		}
+
+		self.newest = Some(message.sequence);
+
+		if self.lines.len() == Self::MAX_LINES {
+			self.lines.pop_front();
+		}
+
+		self.lines.push_back(ChatLine { message, history });
+		true
+	}
+
+	pub fn retract(&mut self, sequence: u64) {
+		self.lines.retain(|line| line.message.sequence != sequence);
+	}
+
+	pub fn draw(&self, ui: &mut Ui) {
+		for ChatLine { message, history } in &self.lines {
+			let text = RichText::new(format!("<{}> {}", message.player, message.text));
+
+			ui.label(match history {
+				true => text.weak(),
+				false => text,
+			});
+		}
+	}
+}
@@ -1,4 +1,15 @@
+// Vertices are packed to save memory, see chunk_vertex.rs
 struct VertexInput {
+	// Position relative to the chunk's origin divided by POSITION_RANGE, with the weight in w
+	@location(0) position: vec4<f32>,
+	// Octahedral encoded
+	@location(1) normal: vec2<f32>,
+	// Material ids
+	@location(2) materials: vec2<u32>,
+}
+
+// The unpacked format, used instead with the `fat_chunk_vertices` feature
+struct FatVertexInput {
 	@location(0) position: vec3<f32>,
 	@location(1) normal: vec3<f32>,
 	@location(2) material_a: vec2<u32>,
@@ -6,6 +17,9 @@ struct VertexInput {
 	@location(4) weight: f32,
 }
 
+// Must match chunk_vertex::POSITION_RANGE
+const POSITION_RANGE: f32 = 17.0;
+
 struct Chunk {
 	@location(5) position: vec3<f32>,
 	@location(6) scale: f32,
@@ -20,7 +34,20 @@ struct Vertex {
 	@interpolate(perspective) @location(4) weight: f32,
 }
 
-var<push_constant> camera: mat4x4<f32>;
+// Must match Lighting::push_constants in ambient.rs
+struct Lighting {
+	// Towards the sun
+	sun_direction: vec3<f32>,
+	sun: vec3<f32>,
+	ambient: vec3<f32>,
+}
+
+struct PushConstants {
+	camera: mat4x4<f32>,
+	lighting: Lighting,
+}
+
+var<push_constant> push_constants: PushConstants;
 
 @group(0) @binding(0) var texture: texture_2d<f32>;
 @group(0) @binding(1) var texture_sampler: sampler;
@@ -28,7 +55,22 @@ var<push_constant> camera: mat4x4<f32>;
 @vertex fn vertex(input: VertexInput, chunk: Chunk) -> Vertex {
 	var vertex: Vertex;
 
-	vertex.position = camera * vec4<f32>(chunk.position + (input.position * chunk.scale), 1.0);
+	let position = input.position.xyz * POSITION_RANGE;
+
+	vertex.position = push_constants.camera * vec4<f32>(chunk.position + (position * chunk.scale), 1.0);
+	vertex.chunk_position = position;
+	vertex.normal = decode_octahedral(input.normal);
+	vertex.material_a = material_coordinates(input.materials.x);
+	vertex.material_b = material_coordinates(input.materials.y);
+	vertex.weight = input.position.w;
+
+	return vertex;
+}
+
+@vertex fn vertex_fat(input: FatVertexInput, chunk: Chunk) -> Vertex {
+	var vertex: Vertex;
+
+	vertex.position = push_constants.camera * vec4<f32>(chunk.position + (input.position * chunk.scale), 1.0);
 	vertex.chunk_position = input.position;
 	vertex.normal = input.normal;
 	vertex.material_a = input.material_a;
@@ -38,6 +80,20 @@ var<push_constant> camera: mat4x4<f32>;
 	return vertex;
 }
 
+// Reverses chunk_vertex::encode_octahedral, the lower half of the sphere is folded over the square's diagonals.
+fn decode_octahedral(encoded: vec2<f32>) -> vec3<f32> {
+	var normal = vec3<f32>(encoded, 1.0 - abs(encoded.x) - abs(encoded.y));
+	let fold = max(-normal.z, 0.0);
+	normal.x -= select(-fold, fold, normal.x >= 0.0);
+	normal.y -= select(-fold, fold, normal.y >= 0.0);
+	return normalize(normal);
+}
+
+// Where a material is in the 4x4 material texture.
+fn material_coordinates(material: u32) -> vec2<u32> {
+	return vec2<u32>((material >> 2u) & 3u, material & 3u);
+}
+
 fn get_color(material_coordinate: vec2<u32>, chunk_axis_position: vec2<f32>) -> vec4<f32> {
 	let texture_coordinates = (vec2<f32>(material_coordinate) + fract(chunk_axis_position)) / 4;
 	return textureSample(texture, texture_sampler, texture_coordinates);
@@ -63,5 +119,33 @@ fn get_color(material_coordinate: vec2<u32>, chunk_axis_position: vec2<f32>) ->
 	side *= weights.z;
 	top *= weights.y;
 
-	return front + side + top;
+	let color = front + side + top;
+
+	return vec4<f32>(light(color.rgb, normalize(vertex.normal)), color.a);
+}
+
+// Directional light from the sun, plus ambient light which reaches every surface.
+fn light(color: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+	let lighting = push_constants.lighting;
+	return color * (lighting.ambient + lighting.sun * max(dot(normal, lighting.sun_direction), 0.0));
+}
+
+// Debug render mode, shows the surface normal as a color.
+@fragment fn fragment_normals(vertex: Vertex) -> @location(0) vec4<f32> {
+	return vec4<f32>(normalize(vertex.normal) * 0.5 + 0.5, 1.0);
+}
+
+// Debug render mode, shows the dominant material of each vertex as a flat color.
+@fragment fn fragment_material(vertex: Vertex) -> @location(0) vec4<f32> {
+	var material_coordinate = vertex.material_a;
+	if vertex.weight > 0.5 {
+		material_coordinate = vertex.material_b;
+	}
+
+	switch (material_coordinate.x << 2) | material_coordinate.y {
+		case 12u: { return vec4<f32>(1.0, 0.3, 0.0, 1.0); } // Corium
+		case 13u: { return vec4<f32>(0.5, 0.5, 0.5, 1.0); } // Stone
+		case 14u: { return vec4<f32>(0.2, 0.8, 0.2, 1.0); } // Ground
+		default: { return vec4<f32>(1.0, 0.0, 1.0, 1.0); }
+	}
 }
@@ -0,0 +1,519 @@
+use log::{debug, warn};
+use solarscape_shared::{
+	consts::CELLS_PER_CHUNK,
+	data::world::{content_hash, ChunkCoordinates, Material},
+	message::clientbound::SyncChunk,
+};
+use std::{
+	fs::{self, File},
+	io::{self, ErrorKind::NotFound, Read},
+	path::{Path, PathBuf},
+	sync::{
+		atomic::{AtomicU64, Ordering::Relaxed},
+		Arc,
+	},
+	time::SystemTime,
+};
+
+/// Bytes of chunk data, one for each material followed by each density as a little endian f32.
+const DATA_SIZE: usize = CELLS_PER_CHUNK * 5;
+
+/// A chunk's materials and densities.
+type Data = (
+	Arc<[Material; CELLS_PER_CHUNK]>,
+	Arc<[f32; CELLS_PER_CHUNK]>,
+);
+
+/// Chunks synced by sectors, kept on disk so that reconnecting to a sector doesn't download everything again. The
+/// client tells the sector which chunks it has with [`HaveChunks`], and is sent a [`ChunkUnchanged`] instead of the
+/// data for any that haven't changed since.
+///
+/// Each chunk is it's own file, a bincode header of the coordinates and content hash followed by the data compressed
+/// with zstd. The content hash is checked again when a chunk is read, so a corrupt file is never used. Files are
+/// shared by every sector, and the least recently used are deleted once they add up to more than
+/// [`ChunkCache::MAX_SIZE`].
+///
+/// [`HaveChunks`]: solarscape_shared::message::serverbound::HaveChunks
+/// [`ChunkUnchanged`]: solarscape_shared::message::clientbound::ChunkUnchanged
+#[derive(Clone)]
+pub struct ChunkCache {
+	/// Every sector's chunks, which the size limit applies to.
+	root: PathBuf,
+	/// This sector's chunks.
+	directory: PathBuf,
+	/// Bytes, as of the last eviction plus everything written since.
+	size: Arc<AtomicU64>,
+}
+
+impl ChunkCache {
+	/// Bytes, 256 MiB.
+	const MAX_SIZE: u64 = 256 * 1024 * 1024;
+
+	/// Opens the cache for the sector called `sector`, [`None`] if the platform has no cache directory or it can't be
+	/// created.
+	pub fn open(sector: &str) -> Option<Self> {
+		Self::open_in(dirs::cache_dir()?.join("solarscape").join("chunks"), sector)
+	}
+
+	fn open_in(root: PathBuf, sector: &str) -> Option<Self> {
+		// Sector names come from the server, so anything that could escape the directory is replaced
+		let name = sector
+			.chars()
+			.map(|char| match char.is_ascii_alphanumeric() || char == '-' {
+				true => char,
+				false => '_',
+			})
+			.collect::<String>();
+
+		let directory = root.join(name);
+
+		if let Err(error) = fs::create_dir_all(&directory) {
+			warn!(
+				"Unable to create chunk cache directory {}, chunks won't be cached: {error}",
+				directory.display()
+			);
+			return None;
+		}
+
+		let cache = Self {
+			root,
+			directory,
+			size: Arc::new(AtomicU64::new(0)),
+		};
+
+		cache.evict(Self::MAX_SIZE);
+		Some(cache)
+	}
+
+	fn path(&self, coordinates: &ChunkCoordinates) -> PathBuf {
+		self.directory.join(format!(
+			"{}_{}_{}_{}_{}",
+			coordinates.voxject, coordinates.level, coordinates.x, coordinates.y, coordinates.z
+		))
+	}
+
+	/// Every chunk cached for this sector with it's content hash, read from the file headers. Blocks on file IO.
+	pub fn index(&self) -> Vec<(ChunkCoordinates, u64)> {
+		let entries = match fs::read_dir(&self.directory) {
+			Ok(entries) => entries,
+			Err(error) => {
+				warn!("Unable to list the chunk cache: {error}");
+				return vec![];
+			}
+		};
+
+		entries
+			.filter_map(|entry| {
+				let path = entry.ok()?.path();
+
+				// Chunks still being written, or left half written by a crash
+				if path.extension().is_some() {
+					return None;
+				}
+
+				// Anything unreadable is left for eviction, or to be overwritten next time the chunk is synced
+				File::open(&path)
+					.ok()
+					.and_then(|mut file| bincode::deserialize_from(&mut file).ok())
+			})
+			.collect()
+	}
+
+	/// The data of the chunk at `coordinates`, if it's cached with `expected_hash`. Corrupt files are deleted. Blocks on
+	/// file IO.
+	pub fn read(&self, coordinates: &ChunkCoordinates, expected_hash: u64) -> Option<Data> {
+		let path = self.path(coordinates);
+
+		let bytes = match fs::read(&path) {
+			Ok(bytes) => bytes,
+			Err(error) if error.kind() == NotFound => return None,
+			Err(error) => {
+				warn!("Unable to read cached chunk {coordinates}: {error}");
+				return None;
+			}
+		};
+
+		let Some((cached_coordinates, hash, data)) = decode(&bytes) else {
+			warn!("Cached chunk {coordinates} is corrupt, deleting it");
+			let _ = fs::remove_file(&path);
+			return None;
+		};
+
+		if cached_coordinates != *coordinates || hash != expected_hash {
+			debug!("Cached chunk {coordinates} isn't the copy the sector expected");
+			return None;
+		}
+
+		// Marks the chunk as recently used, eviction going by modified time
+		if let Err(error) = File::options()
+			.write(true)
+			.open(&path)
+			.and_then(|file| file.set_modified(SystemTime::now()))
+		{
+			debug!("Unable to update cached chunk {coordinates}'s modified time: {error}");
+		}
+
+		Some(data)
+	}
+
+	/// Caches `chunk` in the background, replacing any older copy.
+	pub fn write(&self, chunk: &SyncChunk) {
+		let cache = self.clone();
+		let chunk = chunk.clone();
+
+		rayon::spawn(move || cache.store(&chunk));
+	}
+
+	fn store(&self, chunk: &SyncChunk) {
+		let coordinates = chunk.coordinates;
+		let path = self.path(&coordinates);
+
+		// Written under another name and then moved into place, so that a chunk is never read half written
+		let result = (|| {
+			let bytes = encode(chunk)?;
+			let partial = path.with_extension("partial");
+			fs::write(&partial, &bytes)?;
+			fs::rename(&partial, &path)?;
+			Ok::<_, io::Error>(bytes.len() as u64)
+		})();
+
+		let length = match result {
+			Ok(length) => length,
+			Err(error) => {
+				warn!("Unable to cache chunk {coordinates}: {error}");
+				return;
+			}
+		};
+
+		if self.size.fetch_add(length, Relaxed) + length > Self::MAX_SIZE {
+			self.evict(Self::MAX_SIZE);
+		}
+	}
+
+	/// Deletes the least recently used chunks, from any sector, until the cache is within `max_size` bytes, and recounts
+	/// it's size.
+	fn evict(&self, max_size: u64) {
+		let mut files = match files(&self.root) {
+			Ok(files) => files,
+			Err(error) => {
+				warn!("Unable to list the chunk cache: {error}");
+				return;
+			}
+		};
+
+		let mut size = files.iter().map(|(_, length, _)| length).sum::<u64>();
+
+		if size > max_size {
+			files.sort_by_key(|(_, _, modified)| *modified);
+
+			let mut evicted = 0;
+
+			for (path, length, _) in files {
+				if size <= max_size {
+					break;
+				}
+
+				match fs::remove_file(&path) {
+					Ok(()) => {
+						size -= length;
+						evicted += 1;
+					}
+					Err(error) => warn!("Unable to evict cached chunk {}: {error}", path.display()),
+				}
+			}
+
+			debug!("Evicted {evicted} chunks from the chunk cache");
+		}
+
+		self.size.store(size, Relaxed);
+	}
+}
+
+/// Every cached chunk under `root`, with it's length and modified time.
+fn files(root: &Path) -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+	let mut files = vec![];
+
+	for sector in fs::read_dir(root)? {
+		let sector = sector?;
+
+		if !sector.file_type()?.is_dir() {
+			continue;
+		}
+
+		for chunk in fs::read_dir(sector.path())? {
+			let chunk = chunk?;
+			let metadata = chunk.metadata()?;
+
+			if metadata.is_file() {
+				files.push((chunk.path(), metadata.len(), metadata.modified()?));
+			}
+		}
+	}
+
+	Ok(files)
+}
+
+fn encode(chunk: &SyncChunk) -> io::Result<Vec<u8>> {
+	let mut bytes =
+		bincode::serialize(&(chunk.coordinates, chunk.content_hash())).map_err(io::Error::other)?;
+
+	let mut data = Vec::with_capacity(DATA_SIZE);
+	data.extend(chunk.materials.iter().map(|material| *material as u8));
+
+	for density in chunk.densities.iter() {
+		data.extend(density.to_le_bytes());
+	}
+
+	// Densities and materials compress well, a faster level is plenty as it's written while playing
+	bytes.extend(zstd::encode_all(data.as_slice(), 1)?);
+
+	Ok(bytes)
+}
+
+/// The coordinates, content hash, and data in `bytes`, [`None`] if they're corrupt. Only data with the content hash in
+/// the header is accepted.
+fn decode(mut bytes: &[u8]) -> Option<(ChunkCoordinates, u64, Data)> {
+	let (coordinates, hash): (ChunkCoordinates, u64) =
+		bincode::deserialize_from(&mut bytes).ok()?;
+
+	let mut data = Vec::with_capacity(DATA_SIZE);
+	zstd::Decoder::new(bytes)
+		.ok()?
+		.take(DATA_SIZE as u64 + 1)
+		.read_to_end(&mut data)
+		.ok()?;
+
+	if data.len() != DATA_SIZE {
+		return None;
+	}
+
+	let (material_bytes, density_bytes) = data.split_at(CELLS_PER_CHUNK);
+
+	let mut materials = [Material::Nothing; CELLS_PER_CHUNK];
+	for (material, byte) in materials.iter_mut().zip(material_bytes) {
+		*material = Material::try_from(*byte).ok()?;
+	}
+
+	let mut densities = [0.0; CELLS_PER_CHUNK];
+	for (density, bytes) in densities.iter_mut().zip(density_bytes.chunks_exact(4)) {
+		*density = f32::from_le_bytes(bytes.try_into().ok()?);
+	}
+
+	if content_hash(&materials, &densities) != hash {
+		return None;
+	}
+
+	Some((
+		coordinates,
+		hash,
+		(Arc::new(materials), Arc::new(densities)),
+	))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::vector;
+	use solarscape_shared::data::{world::Level, Id};
+	use std::{env, time::Duration};
+
+	struct TempDir(PathBuf);
+
+	impl TempDir {
+		fn new() -> Self {
+			Self(env::temp_dir().join(format!("solarscape-chunk-cache-{}", Id::new())))
+		}
+
+		fn cache(&self, sector: &str) -> ChunkCache {
+			ChunkCache::open_in(self.0.clone(), sector).unwrap()
+		}
+	}
+
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			let _ = fs::remove_dir_all(&self.0);
+		}
+	}
+
+	fn chunk(x: i32) -> SyncChunk {
+		let mut materials = [Material::Nothing; CELLS_PER_CHUNK];
+		let mut densities = [0.0; CELLS_PER_CHUNK];
+
+		for (index, (material, density)) in materials.iter_mut().zip(&mut densities).enumerate() {
+			*material = [Material::Corium, Material::Stone, Material::Ground][index % 3];
+			*density = (index as f32 + x as f32).sin();
+		}
+
+		SyncChunk {
+			coordinates: ChunkCoordinates::new(Id::new(), vector![x, 0, 0], Level::new(0)),
+			generation: 0,
+			materials: Arc::new(materials),
+			densities: Arc::new(densities),
+		}
+	}
+
+	fn set_modified(path: &Path, seconds: u64) {
+		File::options()
+			.write(true)
+			.open(path)
+			.unwrap()
+			.set_modified(SystemTime::UNIX_EPOCH + Duration::from_secs(seconds))
+			.unwrap();
+	}
+
+	#[test]
+	fn chunks_survive_the_round_trip() {
+		let directory = TempDir::new();
+		let cache = directory.cache("test");
+		let chunk = chunk(0);
+		let hash = chunk.content_hash();
+
+		assert_eq!(cache.read(&chunk.coordinates, hash), None);
+
+		cache.store(&chunk);
+		assert_eq!(cache.index(), [(chunk.coordinates, hash)]);
+
+		let (materials, densities) = cache.read(&chunk.coordinates, hash).unwrap();
+		assert_eq!(materials, chunk.materials);
+		assert_eq!(densities, chunk.densities);
+
+		// The sector has a newer copy, this one is left to be overwritten when it's synced
+		assert_eq!(cache.read(&chunk.coordinates, hash ^ 1), None);
+		assert!(cache.path(&chunk.coordinates).exists());
+	}
+
+	#[test]
+	fn sectors_have_their_own_chunks() {
+		let directory = TempDir::new();
+		let chunk = chunk(0);
+
+		directory.cache("one").store(&chunk);
+
+		assert_eq!(directory.cache("one").index().len(), 1);
+		assert_eq!(directory.cache("two").index(), []);
+		assert_eq!(
+			directory
+				.cache("two")
+				.read(&chunk.coordinates, chunk.content_hash()),
+			None
+		);
+	}
+
+	#[test]
+	fn sector_names_cant_escape_the_cache() {
+		let directory = TempDir::new();
+		let cache = directory.cache("../../escaped/sector-1");
+
+		assert_eq!(cache.directory, directory.0.join("______escaped_sector-1"));
+	}
+
+	#[test]
+	fn corrupt_chunks_are_never_used() {
+		let directory = TempDir::new();
+		let cache = directory.cache("test");
+		let chunk = chunk(0);
+		let hash = chunk.content_hash();
+		let path = cache.path(&chunk.coordinates);
+
+		cache.store(&chunk);
+		let bytes = fs::read(&path).unwrap();
+
+		// Truncated, and a flipped bit in the compressed data
+		let mut flipped = bytes.clone();
+		*flipped.last_mut().unwrap() ^= 1;
+
+		for corrupt in [&bytes[..bytes.len() / 2], &flipped] {
+			fs::write(&path, corrupt).unwrap();
+			assert_eq!(cache.read(&chunk.coordinates, hash), None);
+			assert!(!path.exists());
+		}
+
+		// Valid data, that isn't what the header says it is
+		let header = bincode::serialized_size(&(chunk.coordinates, hash)).unwrap() as usize;
+		let mut wrong_hash = bincode::serialize(&(chunk.coordinates, hash ^ 1)).unwrap();
+		wrong_hash.extend(&bytes[header..]);
+		assert!(decode(&wrong_hash).is_none());
+
+		// Materials that don't exist
+		let mut data = vec![0xff; CELLS_PER_CHUNK];
+		data.extend([0; CELLS_PER_CHUNK * 4]);
+		let mut bad_material = bincode::serialize(&(chunk.coordinates, hash)).unwrap();
+		bad_material.extend(zstd::encode_all(data.as_slice(), 1).unwrap());
+		assert!(decode(&bad_material).is_none());
+
+		assert!(decode(&[]).is_none());
+		assert!(decode(&bytes).is_some());
+	}
+
+	#[test]
+	fn chunks_are_checked_against_their_coordinates() {
+		let directory = TempDir::new();
+		let cache = directory.cache("test");
+		let (one, two) = (chunk(1), chunk(2));
+
+		cache.store(&one);
+		fs::copy(cache.path(&one.coordinates), cache.path(&two.coordinates)).unwrap();
+
+		assert_eq!(cache.read(&two.coordinates, one.content_hash()), None);
+		assert_eq!(cache.read(&two.coordinates, two.content_hash()), None);
+	}
+
+	#[test]
+	fn partial_and_unreadable_files_are_left_out_of_the_index() {
+		let directory = TempDir::new();
+		let cache = directory.cache("test");
+		let chunk = chunk(0);
+
+		cache.store(&chunk);
+		fs::write(
+			cache.directory.join("half_written.partial"),
+			encode(&chunk).unwrap(),
+		)
+		.unwrap();
+		fs::write(cache.directory.join("garbage"), b"nonsense").unwrap();
+
+		assert_eq!(cache.index(), [(chunk.coordinates, chunk.content_hash())]);
+	}
+
+	#[test]
+	fn least_recently_used_chunks_are_evicted_from_every_sector() {
+		let directory = TempDir::new();
+		let (one, two) = (directory.cache("one"), directory.cache("two"));
+		let chunks = [chunk(0), chunk(1), chunk(2)];
+
+		one.store(&chunks[0]);
+		two.store(&chunks[1]);
+		one.store(&chunks[2]);
+
+		let paths = [
+			one.path(&chunks[0].coordinates),
+			two.path(&chunks[1].coordinates),
+			one.path(&chunks[2].coordinates),
+		];
+
+		for (seconds, path) in paths.iter().enumerate() {
+			set_modified(path, seconds as u64 + 1);
+		}
+
+		let lengths = paths
+			.iter()
+			.map(|path| fs::metadata(path).unwrap().len())
+			.collect::<Vec<_>>();
+
+		// Reading the oldest makes it the most recently used
+		assert!(one
+			.read(&chunks[0].coordinates, chunks[0].content_hash())
+			.is_some());
+
+		one.evict(lengths[0] + lengths[2]);
+
+		assert!(paths[0].exists());
+		assert!(!paths[1].exists());
+		assert!(paths[2].exists());
+		assert_eq!(one.size.load(Relaxed), lengths[0] + lengths[2]);
+
+		// Already within the limit, just recounted
+		two.evict(u64::MAX);
+		assert_eq!(two.size.load(Relaxed), lengths[0] + lengths[2]);
+	}
+}
@@ -0,0 +1,337 @@
+use bytemuck::{cast_slice, Pod, Zeroable};
+use nalgebra::{Point3, Vector2, Vector3};
+use solarscape_shared::consts::CHUNK_SAMPLE_SIZE;
+use wgpu::{vertex_attr_array, VertexAttribute, VertexBufferLayout, VertexStepMode};
+
+/// Whether chunk meshes use the original unpacked vertex format, which is over twice the size but exact, for comparing
+/// against when the packed format is suspected of causing a rendering problem. See the `fat_chunk_vertices` feature.
+pub const FAT: bool = cfg!(feature = "fat_chunk_vertices");
+
+/// Vertex shader in chunk.wgsl which reads the format in use.
+pub const ENTRY_POINT: &str = match FAT {
+	true => "vertex_fat",
+	false => "vertex",
+};
+
+/// Vertices are within this distance of the chunk's origin on every axis, before the chunk is scaled. Must match
+/// `POSITION_RANGE` in chunk.wgsl.
+pub const POSITION_RANGE: f32 = CHUNK_SAMPLE_SIZE as f32;
+
+/// A chunk mesh vertex as it's built, before being encoded for the GPU.
+#[derive(Clone, Copy)]
+pub struct ChunkVertex {
+	/// Relative to the chunk's origin, within [`POSITION_RANGE`].
+	pub position: Point3<f32>,
+	pub normal: Vector3<f32>,
+	/// Material ids, which are also their position in the material texture.
+	pub material_a: u8,
+	pub material_b: u8,
+	/// How far between `material_a` and `material_b` the vertex is, from 0 to 1.
+	pub weight: f32,
+}
+
+/// Position and weight as unsigned normalized integers, 8 bytes.
+#[allow(unused)]
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct PackedPosition {
+	position: [u16; 3],
+	weight: u16,
+}
+
+unsafe impl Zeroable for PackedPosition {}
+unsafe impl Pod for PackedPosition {}
+
+/// Octahedral encoded normal and both material ids, 4 bytes.
+#[allow(unused)]
+#[derive(Clone, Copy)]
+#[repr(C)]
+struct PackedData {
+	normal: [i8; 2],
+	materials: [u8; 2],
+}
+
+unsafe impl Zeroable for PackedData {}
+unsafe impl Pod for PackedData {}
+
+/// The original format, 12 bytes of position followed by 20 bytes of this.
+#[allow(unused)]
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+struct FatData {
+	normal: Vector3<f32>,
+	material_a: Vector2<u8>,
+	material_b: Vector2<u8>,
+	weight: f32,
+}
+
+unsafe impl Zeroable for FatData {}
+unsafe impl Pod for FatData {}
+
+const PACKED_POSITION_ATTRIBUTES: [VertexAttribute; 1] = vertex_attr_array![0 => Unorm16x4];
+const PACKED_DATA_ATTRIBUTES: [VertexAttribute; 2] =
+	vertex_attr_array![1 => Snorm8x2, 2 => Uint8x2];
+
+const FAT_POSITION_ATTRIBUTES: [VertexAttribute; 1] = vertex_attr_array![0 => Float32x3];
+const FAT_DATA_ATTRIBUTES: [VertexAttribute; 4] =
+	vertex_attr_array![1 => Float32x3, 2 => Uint8x2, 3 => Uint8x2, 4 => Float32];
+
+/// Layouts of the position and data buffers [`encode`] fills.
+pub fn layouts() -> [VertexBufferLayout<'static>; 2] {
+	let (position_size, position_attributes, data_size, data_attributes) = match FAT {
+		true => (
+			size_of::<Point3<f32>>(),
+			&FAT_POSITION_ATTRIBUTES[..],
+			size_of::<FatData>(),
+			&FAT_DATA_ATTRIBUTES[..],
+		),
+		false => (
+			size_of::<PackedPosition>(),
+			&PACKED_POSITION_ATTRIBUTES[..],
+			size_of::<PackedData>(),
+			&PACKED_DATA_ATTRIBUTES[..],
+		),
+	};
+
+	[
+		VertexBufferLayout {
+			array_stride: position_size as u64,
+			step_mode: VertexStepMode::Vertex,
+			attributes: position_attributes,
+		},
+		VertexBufferLayout {
+			array_stride: data_size as u64,
+			step_mode: VertexStepMode::Vertex,
+			attributes: data_attributes,
+		},
+	]
+}
+
+/// Encodes `vertices` into the contents of the position and data buffers, in the format given by [`FAT`].
+pub fn encode(vertices: &[ChunkVertex]) -> (Vec<u8>, Vec<u8>) {
+	if FAT {
+		let positions = vertices
+			.iter()
+			.map(|vertex| vertex.position)
+			.collect::<Vec<_>>();
+
+		let data = vertices
+			.iter()
+			.map(|vertex| FatData {
+				normal: vertex.normal,
+				material_a: material_coordinates(vertex.material_a),
+				material_b: material_coordinates(vertex.material_b),
+				weight: vertex.weight,
+			})
+			.collect::<Vec<_>>();
+
+		return (cast_slice(&positions).to_vec(), cast_slice(&data).to_vec());
+	}
+
+	let positions = vertices
+		.iter()
+		.map(|vertex| PackedPosition {
+			position: vertex
+				.position
+				.coords
+				.map(|coordinate| quantize(coordinate / POSITION_RANGE))
+				.into(),
+			weight: quantize(vertex.weight),
+		})
+		.collect::<Vec<_>>();
+
+	let data = vertices
+		.iter()
+		.map(|vertex| PackedData {
+			normal: encode_octahedral(vertex.normal),
+			materials: [vertex.material_a, vertex.material_b],
+		})
+		.collect::<Vec<_>>();
+
+	(cast_slice(&positions).to_vec(), cast_slice(&data).to_vec())
+}
+
+/// Where a material is in the 4x4 material texture.
+fn material_coordinates(material: u8) -> Vector2<u8> {
+	Vector2::new((material & 0xC) >> 2, material & 0x3)
+}
+
+/// `value` from 0 to 1 as a 16 bit unsigned normalized integer, accurate to within half of `1 / 65535`.
+fn quantize(value: f32) -> u16 {
+	(value.clamp(0.0, 1.0) * u16::MAX as f32).round() as u16
+}
+
+/// Maps a unit vector onto an octahedron and unfolds it into a square, so that it fits in two bytes while keeping the
+/// error under a degree in any direction. Decoded by `decode_octahedral` in chunk.wgsl. Non-finite normals, from
+/// degenerate triangles, become straight up on z.
+fn encode_octahedral(normal: Vector3<f32>) -> [i8; 2] {
+	let normal = normal / (normal.x.abs() + normal.y.abs() + normal.z.abs());
+
+	let (x, y) = match normal.z >= 0.0 {
+		true => (normal.x, normal.y),
+		// The lower half is folded over the diagonals
+		false => (
+			(1.0 - normal.y.abs()) * sign(normal.x),
+			(1.0 - normal.x.abs()) * sign(normal.y),
+		),
+	};
+
+	// Casting saturates and maps NaN to 0
+	[(x * 127.0).round() as i8, (y * 127.0).round() as i8]
+}
+
+/// Like [`f32::signum`], but 0 counts as positive whichever sign it has.
+fn sign(value: f32) -> f32 {
+	match value >= 0.0 {
+		true => 1.0,
+		false => -1.0,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::{point, vector};
+
+	/// Mirrors `decode_octahedral` in chunk.wgsl.
+	fn decode_octahedral([x, y]: [i8; 2]) -> Vector3<f32> {
+		let (x, y) = (x as f32 / 127.0, y as f32 / 127.0);
+		let mut normal = vector![x, y, 1.0 - x.abs() - y.abs()];
+		let fold = (-normal.z).max(0.0);
+		normal.x -= if normal.x >= 0.0 { fold } else { -fold };
+		normal.y -= if normal.y >= 0.0 { fold } else { -fold };
+		normal.normalize()
+	}
+
+	/// Directions spread over the whole sphere, including the axes and the diagonals the lower half is folded over.
+	fn directions() -> Vec<Vector3<f32>> {
+		let mut directions = vec![];
+
+		for latitude in -16..=16 {
+			for longitude in 0..64 {
+				let latitude = latitude as f32 / 16.0 * std::f32::consts::FRAC_PI_2;
+				let longitude = longitude as f32 / 64.0 * std::f32::consts::TAU;
+
+				directions.push(vector![
+					latitude.cos() * longitude.cos(),
+					latitude.cos() * longitude.sin(),
+					latitude.sin()
+				]);
+			}
+		}
+
+		for axis in 0..3 {
+			for sign in [1.0, -1.0] {
+				let mut direction = Vector3::zeros();
+				direction[axis] = sign;
+				directions.push(direction);
+			}
+		}
+
+		directions.push(vector![1.0, -1.0, -1.0].normalize());
+		directions
+	}
+
+	#[test]
+	fn octahedral_normals_are_within_a_degree() {
+		for direction in directions() {
+			let decoded = decode_octahedral(encode_octahedral(direction));
+			let error = decoded.angle(&direction).to_degrees();
+
+			assert!(
+				error < 1.0,
+				"{direction} came back as {decoded}, {error} degrees off"
+			);
+		}
+	}
+
+	#[test]
+	fn octahedral_encoding_ignores_length() {
+		let direction = vector![0.3, -0.5, -0.8];
+		assert_eq!(
+			encode_octahedral(direction),
+			encode_octahedral(direction * 25.0)
+		);
+	}
+
+	#[test]
+	fn degenerate_normals_point_up() {
+		assert_eq!(encode_octahedral(Vector3::zeros()), [0, 0]);
+		assert_eq!(decode_octahedral([0, 0]), Vector3::z());
+	}
+
+	#[test]
+	fn quantization_is_within_half_a_step() {
+		let step = 1.0 / u16::MAX as f32;
+
+		for index in 0..=1000 {
+			let value = index as f32 / 1000.0;
+			let error = (quantize(value) as f32 * step - value).abs();
+
+			assert!(error <= step / 2.0, "{value}");
+		}
+
+		assert_eq!(quantize(-1.0), 0);
+		assert_eq!(quantize(2.0), u16::MAX);
+	}
+
+	#[test]
+	fn positions_are_within_half_a_step_of_the_range() {
+		let vertex = ChunkVertex {
+			position: point![0.0, 8.123_456, POSITION_RANGE],
+			normal: Vector3::z(),
+			material_a: 5,
+			material_b: 9,
+			weight: 0.25,
+		};
+
+		let (positions, data) = encode(&[vertex]);
+
+		if FAT {
+			assert_eq!(positions.len(), 12);
+			assert_eq!(data.len(), 20);
+			return;
+		}
+
+		let position: PackedPosition = bytemuck::pod_read_unaligned(&positions);
+		let decoded = Vector3::from(position.position)
+			.map(|coordinate| coordinate as f32 / u16::MAX as f32 * POSITION_RANGE);
+
+		assert!(
+			(decoded - vertex.position.coords).abs().max()
+				<= POSITION_RANGE / u16::MAX as f32 / 2.0
+		);
+		assert_eq!(position.weight, quantize(0.25));
+
+		let data: PackedData = bytemuck::pod_read_unaligned(&data);
+		assert_eq!(data.materials, [5, 9]);
+		assert_eq!(data.normal, [0, 0]);
+	}
+
+	#[test]
+	fn packed_vertices_are_12_bytes() {
+		assert_eq!(size_of::<PackedPosition>() + size_of::<PackedData>(), 12);
+		assert_eq!(size_of::<Point3<f32>>() + size_of::<FatData>(), 32);
+
+		let strides = layouts()
+			.map(|layout| layout.array_stride)
+			.iter()
+			.sum::<u64>();
+		assert_eq!(strides, if FAT { 32 } else { 12 });
+	}
+
+	#[test]
+	fn constants_match_the_shader() {
+		let shader = include_str!("chunk.wgsl");
+
+		assert!(shader.contains(&format!("const POSITION_RANGE: f32 = {POSITION_RANGE:.1};")));
+		assert!(shader.contains(&format!("@vertex fn {ENTRY_POINT}(")));
+	}
+
+	#[test]
+	fn materials_map_onto_the_texture() {
+		assert_eq!(material_coordinates(0), vector![0, 0]);
+		assert_eq!(material_coordinates(6), vector![1, 2]);
+		assert_eq!(material_coordinates(15), vector![3, 3]);
+	}
+}
@@ -1,18 +1,33 @@
-use crate::{login::Login, renderer::Renderer, world::Sector, ClArgs};
-use egui::Context;
-use std::fmt::Write;
+use crate::{
+	adapter::AdapterPreference,
+	assets::Assets,
+	disconnected::Disconnected,
+	login::Login,
+	renderer::{self, Renderer},
+	safe_mode::SafeMode,
+	world::Sector,
+	ClArgs,
+};
+use egui::{Context, Ui};
+use log::error;
+use std::{fmt::Write, time::Instant};
 use winit::{
 	application::ApplicationHandler,
 	event::{DeviceEvent, DeviceId, WindowEvent},
-	event_loop::ActiveEventLoop,
+	event_loop::{ActiveEventLoop, ControlFlow},
 	window::WindowId,
 };
 
 #[cfg(debug)]
-use crate::gui_test::GuiTest;
+use crate::{
+	console::{self, Target},
+	gui_test::GuiTest,
+};
 
 pub struct Client {
 	renderer: Option<Renderer>,
+	/// Used instead of the renderer if it couldn't start.
+	safe_mode: Option<SafeMode>,
 	state: AnyState,
 
 	pub cl_args: ClArgs,
@@ -20,10 +35,50 @@ pub struct Client {
 
 impl ApplicationHandler for Client {
 	fn resumed(&mut self, event_loop: &ActiveEventLoop) {
-		self.renderer = match Renderer::new(event_loop) {
-			Ok(renderer) => Some(renderer),
-			Err(error) => panic!("{error}"),
+		let adapter_preference = AdapterPreference {
+			gpu: self.cl_args.gpu.clone(),
+			backend: self.cl_args.backend,
+		};
+
+		let window = match renderer::create_window(event_loop) {
+			Ok(window) => window,
+			Err(error) => panic!("unable to create window: {error}"),
 		};
+
+		let assets = Assets::new(self.cl_args.assets.clone());
+
+		match Renderer::start(window.clone(), &adapter_preference, &assets) {
+			Ok(mut renderer) => {
+				renderer.frame_limiter.limit = self.cl_args.fps_limit.into();
+				renderer.gpu_memory_limit = self.cl_args.gpu_memory_limit * 1024 * 1024;
+				self.renderer = Some(renderer);
+			}
+			Err(diagnostics) => match SafeMode::new(window, &diagnostics) {
+				Ok(safe_mode) => {
+					error!("Unable to start the renderer, starting in safe mode");
+					self.safe_mode = Some(safe_mode);
+				}
+				Err(error) => panic!("unable to start safe mode: {error}\n\n{diagnostics}"),
+			},
+		}
+	}
+
+	fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+		let Some(renderer) = &self.renderer else {
+			return;
+		};
+
+		// If the frame limiter has scheduled the next frame, wait until then before requesting it
+		match renderer.frame_limiter.next_frame() {
+			Some(next_frame) if next_frame > Instant::now() => {
+				event_loop.set_control_flow(ControlFlow::WaitUntil(next_frame))
+			}
+			Some(_) => {
+				renderer.window.request_redraw();
+				event_loop.set_control_flow(ControlFlow::Wait);
+			}
+			None => event_loop.set_control_flow(ControlFlow::Wait),
+		}
 	}
 
 	fn window_event(
@@ -32,6 +87,25 @@ impl ApplicationHandler for Client {
 		window_id: WindowId,
 		event: WindowEvent,
 	) {
+		if let Some(safe_mode) = &mut self.safe_mode {
+			if safe_mode.window.id() != window_id {
+				return;
+			}
+
+			match event {
+				WindowEvent::Resized(size) => safe_mode.resize(size),
+				WindowEvent::CloseRequested | WindowEvent::Destroyed => event_loop.exit(),
+				WindowEvent::RedrawRequested => safe_mode.render(),
+				_ => safe_mode.handle_window_event(&event),
+			}
+
+			if safe_mode.quit {
+				event_loop.exit();
+			}
+
+			return;
+		}
+
 		let renderer = match &mut self.renderer {
 			Some(renderer) if renderer.window.id() != window_id => return,
 			Some(renderer) => renderer,
@@ -62,26 +136,60 @@ impl ApplicationHandler for Client {
 				self.state.build_debug_text(&mut debug_text);
 
 				renderer.render(&self.cl_args, &mut self.state, debug_text);
+
+				#[cfg(debug)]
+				for line in renderer.console.take_submitted() {
+					let output = console::run(
+						&line,
+						&mut Target {
+							renderer,
+							state: &mut self.state,
+							cl_args: &self.cl_args,
+						},
+					);
+
+					renderer.console.print(&output);
+				}
 			}
 			_ => {
-				self.state.window_event(&event);
+				#[cfg(debug)]
+				let captured = renderer.console.handle_window_event(&event);
+				#[cfg(not(debug))]
+				let captured = false;
+
+				if !captured {
+					self.state.window_event(&event);
+				}
+
 				renderer.handle_window_event(&event);
 			}
 		}
 	}
 
 	fn device_event(&mut self, _: &ActiveEventLoop, _: DeviceId, event: DeviceEvent) {
+		// Mouse movement would otherwise turn the player while typing
+		#[cfg(debug)]
+		if self
+			.renderer
+			.as_ref()
+			.is_some_and(|renderer| renderer.console.is_open())
+		{
+			return;
+		}
+
 		self.state.device_event(&event)
 	}
 
 	// This should only ever be called on iOS, Android, and Web, none of which we support, so this is untested.
 	fn suspended(&mut self, _: &ActiveEventLoop) {
 		self.renderer = None;
+		self.safe_mode = None;
 	}
 
 	fn exiting(&mut self, _: &ActiveEventLoop) {
 		// We have to do this otherwise we segfault once we exit the event loop
 		self.renderer = None;
+		self.safe_mode = None;
 	}
 }
 
@@ -100,6 +208,7 @@ impl From<ClArgs> for Client {
 			},
 
 			renderer: None,
+			safe_mode: None,
 
 			cl_args,
 		}
@@ -116,6 +225,9 @@ pub trait State {
 
 	fn draw_ui(&mut self, cl_args: &ClArgs, context: &Context) {}
 
+	/// Adds anything specific to this state to the end of the settings.
+	fn draw_settings(&mut self, ui: &mut Ui) {}
+
 	fn window_event(&mut self, event: &WindowEvent) {}
 
 	fn device_event(&mut self, event: &DeviceEvent) {}
@@ -124,6 +236,7 @@ pub trait State {
 pub enum AnyState {
 	Login(Login),
 	Sector(Sector),
+	Disconnected(Disconnected),
 
 	#[cfg(debug)]
 	GuiTest(crate::gui_test::GuiTest),
@@ -134,6 +247,7 @@ impl State for AnyState {
 		match self {
 			Self::Login(state) => state as &mut dyn State,
 			Self::Sector(state) => state as &mut dyn State,
+			Self::Disconnected(state) => state as &mut dyn State,
 
 			#[cfg(debug)]
 			Self::GuiTest(state) => state as &mut dyn State,
@@ -145,6 +259,7 @@ impl State for AnyState {
 		match self {
 			Self::Login(state) => state as &mut dyn State,
 			Self::Sector(state) => state as &mut dyn State,
+			Self::Disconnected(state) => state as &mut dyn State,
 
 			#[cfg(debug)]
 			Self::GuiTest(state) => state as &mut dyn State,
@@ -152,10 +267,23 @@ impl State for AnyState {
 		.draw_ui(cl_args, context)
 	}
 
+	fn draw_settings(&mut self, ui: &mut Ui) {
+		match self {
+			Self::Login(state) => state as &mut dyn State,
+			Self::Sector(state) => state as &mut dyn State,
+			Self::Disconnected(state) => state as &mut dyn State,
+
+			#[cfg(debug)]
+			Self::GuiTest(state) => state as &mut dyn State,
+		}
+		.draw_settings(ui)
+	}
+
 	fn tick(&mut self) -> Option<AnyState> {
 		match self {
 			Self::Login(state) => state as &mut dyn State,
 			Self::Sector(state) => state as &mut dyn State,
+			Self::Disconnected(state) => state as &mut dyn State,
 
 			#[cfg(debug)]
 			Self::GuiTest(state) => state as &mut dyn State,
@@ -167,6 +295,7 @@ impl State for AnyState {
 		match self {
 			Self::Login(state) => state as &mut dyn State,
 			Self::Sector(state) => state as &mut dyn State,
+			Self::Disconnected(state) => state as &mut dyn State,
 
 			#[cfg(debug)]
 			Self::GuiTest(state) => state as &mut dyn State,
@@ -178,6 +307,7 @@ impl State for AnyState {
 		match self {
 			Self::Login(state) => state as &mut dyn State,
 			Self::Sector(state) => state as &mut dyn State,
+			Self::Disconnected(state) => state as &mut dyn State,
 
 			#[cfg(debug)]
 			Self::GuiTest(state) => state as &mut dyn State,
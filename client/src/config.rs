@@ -0,0 +1,63 @@
+use log::warn;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{
+	fs,
+	io::{self, ErrorKind},
+	path::PathBuf,
+};
+
+/// Where the client keeps it's config, if the platform has a config directory.
+pub fn directory() -> Option<PathBuf> {
+	dirs::config_dir().map(|directory| directory.join("solarscape"))
+}
+
+/// Where the client keeps `name` between sessions, if the platform has a config directory.
+fn path(name: &str) -> Option<PathBuf> {
+	directory().map(|directory| directory.join(name))
+}
+
+/// Loads the JSON file `name`, falling back to the default if there isn't one or it can't be read.
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+	let Some(path) = path(name) else {
+		return T::default();
+	};
+
+	let contents = match fs::read_to_string(&path) {
+		Ok(contents) => contents,
+		Err(error) if error.kind() == ErrorKind::NotFound => return T::default(),
+		Err(error) => {
+			warn!("Unable to read {}: {error}", path.display());
+			return T::default();
+		}
+	};
+
+	match serde_json::from_str(&contents) {
+		Ok(value) => value,
+		Err(error) => {
+			warn!("Unable to parse {}: {error}", path.display());
+			T::default()
+		}
+	}
+}
+
+/// Saves `value` as the JSON file `name`, logging rather than failing if it can't be written.
+pub fn save<T: Serialize>(name: &str, value: &T) {
+	let Some(path) = path(name) else {
+		warn!("Unable to save {name}, there is no config directory");
+		return;
+	};
+
+	let result = (|| {
+		if let Some(directory) = path.parent() {
+			fs::create_dir_all(directory)?;
+		}
+
+		fs::write(&path, serde_json::to_string_pretty(value)?)?;
+
+		Ok::<_, io::Error>(())
+	})();
+
+	if let Err(error) = result {
+		warn!("Unable to write {}: {error}", path.display());
+	}
+}
@@ -0,0 +1,189 @@
+use crate::{palette::UiColor, tr};
+use egui::{Align2, Area, Context, Frame, Id, RichText};
+use solarscape_shared::{
+	connection::{ClientEnd, ConnectionSend},
+	message::serverbound::Ping,
+};
+use std::time::{Duration, Instant};
+
+/// Watches the connection to the sector, so that the player is told when it's degrading rather than the world just
+/// freezing. Anything read from the sector counts as it being alive, keep-alives included. Once the connection closes
+/// the sector hands over to [`Disconnected`], which shows the reconnect countdown.
+///
+/// [`Disconnected`]: crate::disconnected::Disconnected
+pub struct ConnectionStatus {
+	sequence: u32,
+	/// The ping waiting for a pong, with when it was sent.
+	pending: Option<(u32, Instant)>,
+	next_ping: Instant,
+	/// Round trip time of the last ping that was answered.
+	last_latency: Option<Duration>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Health {
+	Healthy,
+	/// Nothing has been received for this long.
+	Stale(Duration),
+}
+
+impl ConnectionStatus {
+	/// How long the sector may stay silent before the player is warned. Pings are answered every tick, so a healthy
+	/// connection never goes this long without data, even though keep-alives are further apart.
+	pub const STALE_AFTER: Duration = Duration::from_secs(3);
+	const PING_INTERVAL: Duration = Duration::from_secs(1);
+
+	pub fn new(now: Instant) -> Self {
+		Self {
+			sequence: 0,
+			pending: None,
+			next_ping: now,
+			last_latency: None,
+		}
+	}
+
+	/// Sends a ping if one is due. Only one ping is waited on at a time, a stalled sector shows up as the latency
+	/// climbing rather than as a backlog of pings.
+	pub fn tick(&mut self, connection: &ConnectionSend<ClientEnd>, now: Instant) {
+		// In process sectors can't lag behind the network, so there's nothing to measure
+		if connection.stats().is_none() || self.pending.is_some() || now < self.next_ping {
+			return;
+		}
+
+		self.sequence = self.sequence.wrapping_add(1);
+		self.pending = Some((self.sequence, now));
+		self.next_ping = now + Self::PING_INTERVAL;
+
+		connection.send(Ping {
+			sequence: self.sequence,
+		});
+	}
+
+	/// Handles a pong from the sector, pongs for anything but the pending ping are ignored.
+	pub fn pong(&mut self, sequence: u32, now: Instant) {
+		if let Some((pending, sent)) = self.pending {
+			if pending == sequence {
+				self.last_latency = Some(now.saturating_duration_since(sent));
+				self.pending = None;
+			}
+		}
+	}
+
+	/// Round trip time to the sector, or how long the pending ping has been waiting if that's longer. [`None`] until
+	/// the first ping has been answered.
+	pub fn latency(&self, now: Instant) -> Option<Duration> {
+		let waiting = self
+			.pending
+			.map(|(_, sent)| now.saturating_duration_since(sent));
+
+		self.last_latency
+			.max(waiting.filter(|_| self.last_latency.is_some()))
+	}
+
+	/// How the connection is doing, given when anything was last received from the sector.
+	pub fn health(last_received: Instant, now: Instant) -> Health {
+		let silent = now.saturating_duration_since(last_received);
+
+		match silent >= Self::STALE_AFTER {
+			true => Health::Stale(silent),
+			false => Health::Healthy,
+		}
+	}
+
+	/// Shows a warning in the top right while the connection is stale, nothing while it's healthy.
+	pub fn draw(health: Health, context: &Context) {
+		let Health::Stale(silent) = health else {
+			return;
+		};
+
+		let text = tr!("connection.no_data", seconds = silent.as_secs());
+
+		Area::new(Id::new("connection_status"))
+			.anchor(Align2::RIGHT_TOP, [-4.0, 4.0])
+			.interactable(false)
+			.show(context, |area| {
+				Frame::popup(area.style()).show(area, |frame| {
+					frame.label(
+						RichText::new(format!("⚠ {text}")).color(UiColor::ToastWarning.color()),
+					);
+				});
+			});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use solarscape_shared::connection::Connection;
+
+	const SECOND: Duration = Duration::from_secs(1);
+
+	#[test]
+	fn connections_go_stale_after_a_while_without_data() {
+		let received = Instant::now();
+
+		assert_eq!(
+			ConnectionStatus::health(received, received),
+			Health::Healthy
+		);
+		assert_eq!(
+			ConnectionStatus::health(received, received + 2 * SECOND),
+			Health::Healthy
+		);
+		assert_eq!(
+			ConnectionStatus::health(received, received + ConnectionStatus::STALE_AFTER),
+			Health::Stale(ConnectionStatus::STALE_AFTER)
+		);
+		assert_eq!(
+			ConnectionStatus::health(received, received + 10 * SECOND),
+			Health::Stale(10 * SECOND)
+		);
+
+		// Data arriving brings it straight back
+		let received = received + 10 * SECOND;
+		assert_eq!(
+			ConnectionStatus::health(received, received),
+			Health::Healthy
+		);
+	}
+
+	#[test]
+	fn latency_is_measured_from_matching_pongs() {
+		let now = Instant::now();
+		let mut status = ConnectionStatus::new(now);
+		status.pending = Some((4, now));
+
+		// Unknown until the first pong
+		assert_eq!(status.latency(now + 5 * SECOND), None);
+
+		status.pong(3, now + SECOND);
+		assert!(status.pending.is_some());
+
+		status.pong(4, now + SECOND / 10);
+		assert!(status.pending.is_none());
+		assert_eq!(status.latency(now + 10 * SECOND), Some(SECOND / 10));
+	}
+
+	#[test]
+	fn unanswered_pings_raise_the_latency() {
+		let now = Instant::now();
+		let mut status = ConnectionStatus::new(now);
+		status.last_latency = Some(SECOND / 10);
+		status.pending = Some((1, now));
+
+		assert_eq!(status.latency(now + SECOND / 20), Some(SECOND / 10));
+		assert_eq!(status.latency(now + 2 * SECOND), Some(2 * SECOND));
+	}
+
+	#[test]
+	fn in_process_sectors_are_not_pinged() {
+		let now = Instant::now();
+		let mut status = ConnectionStatus::new(now);
+		let (client, mut server) = Connection::pair();
+
+		status.tick(&client.sender(), now);
+
+		assert!(status.pending.is_none());
+		assert!(server.try_recv().is_err());
+	}
+}
@@ -0,0 +1,802 @@
+use crate::{
+	client::AnyState,
+	config,
+	direct_connect::DirectConnection,
+	dump::Dump,
+	login::Login,
+	renderer::{RenderMode, Renderer},
+	ClArgs,
+};
+use egui::{
+	text::{CCursor, CCursorRange},
+	text_edit::TextEditState,
+	Align2, Context, Key, Modifiers, RichText, ScrollArea, TextEdit, TextStyle, Window,
+};
+use serde::{Deserialize, Serialize};
+use solarscape_shared::{command::tokenize, message::serverbound::SendChatMessage};
+use std::{
+	collections::{BTreeMap, VecDeque},
+	sync::LazyLock,
+};
+use thiserror::Error;
+use winit::{
+	event::{ElementState, KeyEvent, WindowEvent},
+	keyboard::{KeyCode, PhysicalKey},
+};
+
+/// Commands the console runs locally, anything starting with `/` is sent to the sector instead.
+static COMMANDS: LazyLock<CommandRegistry> = LazyLock::new(CommandRegistry::builtin);
+
+/// A console for debug builds, toggled with the grave key. Lines typed into it are run as local commands, or sent to
+/// the sector if they start with `/`. Lines are only queued while drawing, see [`Console::take_submitted`], as running
+/// them needs the renderer and state the console is drawn with.
+#[derive(Default)]
+pub struct Console {
+	open: bool,
+	input: String,
+	/// Set when the input is replaced, so that the cursor can be moved to the end of it.
+	input_replaced: bool,
+	output: VecDeque<String>,
+	history: History,
+	submitted: Vec<String>,
+}
+
+impl Console {
+	const KEY: KeyCode = KeyCode::Backquote;
+	const MAX_OUTPUT_LINES: usize = 500;
+
+	pub fn new() -> Self {
+		Self {
+			history: History::load(),
+			..Self::default()
+		}
+	}
+
+	pub fn is_open(&self) -> bool {
+		self.open
+	}
+
+	/// Toggles the console, returns `true` if the event shouldn't be handled by anything else.
+	pub fn handle_window_event(&mut self, event: &WindowEvent) -> bool {
+		if let WindowEvent::KeyboardInput {
+			event:
+				KeyEvent {
+					physical_key: PhysicalKey::Code(Self::KEY),
+					state: ElementState::Released,
+					repeat: false,
+					..
+				},
+			..
+		} = event
+		{
+			self.open = !self.open;
+			return true;
+		}
+
+		self.open
+	}
+
+	pub fn print(&mut self, text: &str) {
+		for line in text.lines() {
+			if self.output.len() == Self::MAX_OUTPUT_LINES {
+				self.output.pop_front();
+			}
+
+			self.output.push_back(line.to_string());
+		}
+	}
+
+	/// Lines entered since this was last called, for running with [`run`].
+	pub fn take_submitted(&mut self) -> Vec<String> {
+		std::mem::take(&mut self.submitted)
+	}
+
+	pub fn draw(&mut self, context: &Context) {
+		if !self.open {
+			return;
+		}
+
+		// The toggle key is typed into the input as it's pressed, before the console is closed on release
+		self.input.retain(|character| character != '`');
+
+		Window::new("Console")
+			.id(egui::Id::new("console"))
+			.anchor(Align2::CENTER_TOP, [0.0, 4.0])
+			.default_width(600.0)
+			.collapsible(false)
+			.show(context, |window| {
+				ScrollArea::vertical()
+					.max_height(300.0)
+					.stick_to_bottom(true)
+					.auto_shrink([false, true])
+					.show(window, |area| {
+						for line in &self.output {
+							area.label(RichText::new(line).monospace());
+						}
+					});
+
+				// Taken before the input sees them, so that tab doesn't move focus away from it
+				let (up, down, tab) = window.input_mut(|input| {
+					(
+						input.consume_key(Modifiers::NONE, Key::ArrowUp),
+						input.consume_key(Modifiers::NONE, Key::ArrowDown),
+						input.consume_key(Modifiers::NONE, Key::Tab),
+					)
+				});
+
+				if up {
+					if let Some(line) = self.history.previous() {
+						self.input = line.to_string();
+						self.input_replaced = true;
+					}
+				}
+
+				if down {
+					self.input = self.history.next().unwrap_or_default().to_string();
+					self.input_replaced = true;
+				}
+
+				if tab {
+					self.complete();
+				}
+
+				let input = TextEdit::singleline(&mut self.input)
+					.id(egui::Id::new("console_input"))
+					.desired_width(f32::INFINITY)
+					.font(TextStyle::Monospace)
+					.hint_text("help, or /command to run it on the sector")
+					.show(window);
+
+				if self.input_replaced {
+					let mut state = input.state;
+					let end = CCursor::new(self.input.chars().count());
+					state.cursor.set_char_range(Some(CCursorRange::one(end)));
+					TextEditState::store(state, window.ctx(), input.response.id);
+					self.input_replaced = false;
+				}
+
+				if input.response.lost_focus()
+					&& window.input(|input| input.key_pressed(Key::Enter))
+				{
+					let line = self.input.trim().to_string();
+					self.input.clear();
+
+					if !line.is_empty() {
+						self.print(&format!("> {line}"));
+						self.history.push(line.clone());
+						self.history.save();
+						self.submitted.push(line);
+					}
+				}
+
+				input.response.request_focus();
+			});
+	}
+
+	/// Completes the command name being typed, or lists the possibilities if there are several.
+	fn complete(&mut self) {
+		if self.input.starts_with('/') || self.input.contains(char::is_whitespace) {
+			return;
+		}
+
+		let matches = COMMANDS.complete(&self.input);
+
+		match matches.as_slice() {
+			[] => {}
+			[name] => {
+				self.input = format!("{name} ");
+				self.input_replaced = true;
+			}
+			[first, ..] => {
+				let common = matches.iter().fold(*first, |common, name| {
+					let length = common
+						.chars()
+						.zip(name.chars())
+						.take_while(|(a, b)| a == b)
+						.count();
+					&common[..length]
+				});
+
+				if common.len() > self.input.len() {
+					self.input = common.to_string();
+					self.input_replaced = true;
+				}
+
+				self.print(&matches.join("  "));
+			}
+		}
+	}
+}
+
+/// Lines entered into the console, most recent last, kept between sessions.
+#[derive(Default, Deserialize, Serialize)]
+struct History {
+	entries: Vec<String>,
+
+	/// Which entry up and down have moved to, [`None`] while typing a new line.
+	#[serde(skip)]
+	position: Option<usize>,
+}
+
+impl History {
+	const MAX_ENTRIES: usize = 100;
+	const FILE: &'static str = "console_history.json";
+
+	fn load() -> Self {
+		config::load(Self::FILE)
+	}
+
+	fn save(&self) {
+		config::save(Self::FILE, self);
+	}
+
+	/// Adds `line` unless it's the same as the last one, and goes back to typing a new line.
+	fn push(&mut self, line: String) {
+		self.position = None;
+
+		if self.entries.last() == Some(&line) {
+			return;
+		}
+
+		self.entries.push(line);
+
+		if self.entries.len() > Self::MAX_ENTRIES {
+			self.entries.remove(0);
+		}
+	}
+
+	/// Moves back an entry, staying on the oldest once it's reached.
+	fn previous(&mut self) -> Option<&str> {
+		let position = match self.position {
+			Some(position) => position.saturating_sub(1),
+			None => self.entries.len().checked_sub(1)?,
+		};
+
+		self.position = Some(position);
+		Some(&self.entries[position])
+	}
+
+	/// Moves forward an entry, returning [`None`] once past the newest, back on a new line.
+	fn next(&mut self) -> Option<&str> {
+		let position = self.position? + 1;
+
+		match position < self.entries.len() {
+			true => {
+				self.position = Some(position);
+				Some(&self.entries[position])
+			}
+			false => {
+				self.position = None;
+				None
+			}
+		}
+	}
+}
+
+/// What console commands act on.
+pub struct Target<'a> {
+	pub renderer: &'a mut Renderer,
+	pub state: &'a mut AnyState,
+	pub cl_args: &'a ClArgs,
+}
+
+/// Runs a line entered into the console, returning what to print.
+pub fn run(line: &str, target: &mut Target) -> String {
+	if line.starts_with('/') {
+		return match target.state {
+			AnyState::Sector(sector) => {
+				sector
+					.player
+					.connection
+					.send(SendChatMessage { text: line.into() });
+				String::from("Sent to the sector")
+			}
+			_ => ConsoleError::Failed(String::from("Not connected to a sector")).to_string(),
+		};
+	}
+
+	let result = COMMANDS.parse(line).and_then(|(command, mut args)| {
+		(command.handler)(target, &mut args).map_err(|error| match error.is_usage() {
+			true => ConsoleError::Usage(Box::new(error), command.usage()),
+			false => error,
+		})
+	});
+
+	match result {
+		Ok(reply) => reply,
+		Err(error) => error.to_string(),
+	}
+}
+
+type Handler = fn(&mut Target, &mut Args) -> Result<String, ConsoleError>;
+
+pub struct Command {
+	pub name: &'static str,
+	/// Each way the command can be used, without the name, such as `<degrees>`.
+	pub usages: &'static [&'static str],
+	pub description: &'static str,
+
+	/// Returns the text to print.
+	handler: Handler,
+}
+
+impl Command {
+	pub fn usage(&self) -> String {
+		self.usages
+			.iter()
+			.map(|usage| match usage.is_empty() {
+				true => self.name.to_string(),
+				false => format!("{} {usage}", self.name),
+			})
+			.collect::<Vec<_>>()
+			.join(" or ")
+	}
+}
+
+/// Commands the console can run, laid out like the sector's command registry but without permissions, as the console
+/// only acts on the client.
+#[derive(Default)]
+pub struct CommandRegistry {
+	commands: BTreeMap<&'static str, Command>,
+}
+
+impl CommandRegistry {
+	/// A registry with every built in command.
+	pub fn builtin() -> Self {
+		let mut registry = Self::default();
+
+		registry.register(Command {
+			name: "help",
+			usages: &[""],
+			description: "Lists the console's commands",
+			handler: help,
+		});
+
+		registry.register(Command {
+			name: "render_mode",
+			usages: &["<mode>"],
+			description: "Switches render mode, one of normal, wireframe, normals, or material_id",
+			handler: render_mode,
+		});
+
+		registry.register(Command {
+			name: "fov",
+			usages: &["<degrees>"],
+			description: "Sets the vertical field of view",
+			handler: fov,
+		});
+
+		registry.register(Command {
+			name: "dump",
+			usages: &[""],
+			description: "Writes a world dump, the same as F8",
+			handler: dump,
+		});
+
+		registry.register(Command {
+			name: "connect",
+			usages: &["<address> <key>"],
+			description: "Connects directly to a sector with a pre-shared key",
+			handler: connect,
+		});
+
+		registry.register(Command {
+			name: "disconnect",
+			usages: &[""],
+			description: "Leaves the sector and goes back to the login screen",
+			handler: disconnect,
+		});
+
+		registry
+	}
+
+	/// # Panics
+	/// If a command with the same name has already been registered.
+	pub fn register(&mut self, command: Command) {
+		let name = command.name;
+
+		if self.commands.insert(name, command).is_some() {
+			panic!("console command {name} was registered twice");
+		}
+	}
+
+	/// Finds the command `text` is for, and splits the rest into it's arguments.
+	pub fn parse(&self, text: &str) -> Result<(&Command, Args), ConsoleError> {
+		let mut tokens = tokenize(text)
+			.map_err(|_| ConsoleError::UnclosedQuote)?
+			.into_iter();
+		let name = tokens.next().unwrap_or_default();
+
+		let Some(command) = self.commands.get(name.as_str()) else {
+			return Err(ConsoleError::Unknown(name));
+		};
+
+		Ok((
+			command,
+			Args {
+				tokens: tokens.collect(),
+				position: 0,
+			},
+		))
+	}
+
+	/// Names of the commands starting with `partial`, in alphabetical order.
+	pub fn complete(&self, partial: &str) -> Vec<&'static str> {
+		self.commands
+			.keys()
+			.filter(|name| name.starts_with(partial))
+			.copied()
+			.collect()
+	}
+}
+
+/// A command's arguments, which are taken in order as typed values.
+pub struct Args {
+	tokens: Vec<String>,
+	position: usize,
+}
+
+impl Args {
+	/// Takes the next argument, `name` is used in error messages.
+	pub fn next<T: Arg>(&mut self, name: &'static str) -> Result<T, ConsoleError> {
+		let token = self
+			.tokens
+			.get(self.position)
+			.ok_or(ConsoleError::Missing(name))?;
+
+		let value = T::parse(token).ok_or_else(|| ConsoleError::Invalid {
+			name,
+			value: token.clone(),
+			expected: T::EXPECTED,
+		})?;
+
+		self.position += 1;
+
+		Ok(value)
+	}
+
+	/// Fails if there are any arguments which haven't been taken.
+	pub fn finish(&self) -> Result<(), ConsoleError> {
+		match self.tokens.get(self.position) {
+			Some(token) => Err(ConsoleError::Unexpected(token.clone())),
+			None => Ok(()),
+		}
+	}
+}
+
+/// A type commands can take as an argument.
+pub trait Arg: Sized {
+	/// Describes what's expected, such as "a number", for error messages.
+	const EXPECTED: &'static str;
+
+	fn parse(token: &str) -> Option<Self>;
+}
+
+impl Arg for f32 {
+	const EXPECTED: &'static str = "a number";
+
+	fn parse(token: &str) -> Option<Self> {
+		token.parse().ok().filter(|value: &f32| value.is_finite())
+	}
+}
+
+impl Arg for String {
+	const EXPECTED: &'static str = "some text";
+
+	fn parse(token: &str) -> Option<Self> {
+		Some(token.to_string())
+	}
+}
+
+impl Arg for RenderMode {
+	const EXPECTED: &'static str = "normal, wireframe, normals, or material_id";
+
+	fn parse(token: &str) -> Option<Self> {
+		RenderMode::ALL
+			.iter()
+			.find(|mode| mode.name().to_lowercase().replace(' ', "_") == token)
+			.copied()
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum ConsoleError {
+	#[error("Unknown command {0}, try help")]
+	Unknown(String),
+
+	#[error("Unclosed quote")]
+	UnclosedQuote,
+
+	#[error("Missing <{0}>")]
+	Missing(&'static str),
+
+	#[error("`{value}` isn't a valid <{name}>, expected {expected}")]
+	Invalid {
+		name: &'static str,
+		value: String,
+		expected: &'static str,
+	},
+
+	#[error("Unexpected `{0}`")]
+	Unexpected(String),
+
+	/// Wraps an error from the arguments with how the command should be used.
+	#[error("{0}\nUsage: {1}")]
+	Usage(Box<ConsoleError>, String),
+
+	/// The command was used correctly, but couldn't be done.
+	#[error("{0}")]
+	Failed(String),
+}
+
+impl ConsoleError {
+	/// Whether this was caused by the arguments, so the command's usage should be shown with it.
+	pub fn is_usage(&self) -> bool {
+		matches!(
+			self,
+			Self::Missing(_) | Self::Invalid { .. } | Self::Unexpected(_)
+		)
+	}
+}
+
+fn help(_: &mut Target, args: &mut Args) -> Result<String, ConsoleError> {
+	args.finish()?;
+
+	let lines: Vec<String> = COMMANDS
+		.commands
+		.values()
+		.map(|command| format!("{} - {}", command.usage(), command.description))
+		.chain([String::from(
+			"/<command> - Runs a command on the sector, the reply is shown as a toast",
+		)])
+		.collect();
+
+	Ok(lines.join("\n"))
+}
+
+fn render_mode(target: &mut Target, args: &mut Args) -> Result<String, ConsoleError> {
+	let mode: RenderMode = args.next("mode")?;
+	args.finish()?;
+
+	match target.renderer.set_render_mode(mode) {
+		true => Ok(format!("Render mode set to {}", mode.name())),
+		false => Err(ConsoleError::Failed(format!(
+			"{} isn't supported by this device",
+			mode.name()
+		))),
+	}
+}
+
+fn fov(target: &mut Target, args: &mut Args) -> Result<String, ConsoleError> {
+	let degrees: f32 = args.next("degrees")?;
+	args.finish()?;
+
+	if !(Renderer::MIN_FOV..=Renderer::MAX_FOV).contains(&degrees) {
+		return Err(ConsoleError::Failed(format!(
+			"The field of view must be from {} to {} degrees",
+			Renderer::MIN_FOV,
+			Renderer::MAX_FOV
+		)));
+	}
+
+	target.renderer.set_fov(degrees);
+	Ok(format!("Field of view set to {degrees} degrees"))
+}
+
+fn dump(target: &mut Target, args: &mut Args) -> Result<String, ConsoleError> {
+	args.finish()?;
+
+	let AnyState::Sector(sector) = target.state else {
+		return Err(ConsoleError::Failed(String::from(
+			"Not connected to a sector",
+		)));
+	};
+
+	match Dump::new(sector).write() {
+		Ok(path) => Ok(format!("Wrote world dump to {}", path.display())),
+		Err(error) => Err(ConsoleError::Failed(format!(
+			"Couldn't write world dump: {error}"
+		))),
+	}
+}
+
+fn connect(target: &mut Target, args: &mut Args) -> Result<String, ConsoleError> {
+	let address: String = args.next("address")?;
+	let key: String = args.next("key")?;
+	args.finish()?;
+
+	if matches!(target.state, AnyState::Sector(_)) {
+		return Err(ConsoleError::Failed(String::from(
+			"Already connected, disconnect first",
+		)));
+	}
+
+	let connection = DirectConnection { address, key };
+	let reply = format!("Connecting to {}", connection.address);
+
+	*target.state = AnyState::Login(Login::direct(connection, target.cl_args.keep_alive()));
+
+	Ok(reply)
+}
+
+fn disconnect(target: &mut Target, args: &mut Args) -> Result<String, ConsoleError> {
+	args.finish()?;
+
+	if !matches!(target.state, AnyState::Sector(_)) {
+		return Err(ConsoleError::Failed(String::from(
+			"Not connected to a sector",
+		)));
+	}
+
+	*target.state = AnyState::Login(Login::default());
+	Ok(String::from("Disconnected"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn history(entries: &[&str]) -> History {
+		History {
+			entries: entries.iter().map(|entry| entry.to_string()).collect(),
+			position: None,
+		}
+	}
+
+	#[test]
+	fn commands_are_parsed_with_their_arguments() {
+		let (command, mut args) = COMMANDS.parse(r#"connect "localhost:1234" key"#).unwrap();
+
+		assert_eq!(command.name, "connect");
+		assert_eq!(args.next::<String>("address").unwrap(), "localhost:1234");
+		assert_eq!(args.next::<String>("key").unwrap(), "key");
+		assert!(args.finish().is_ok());
+
+		assert!(
+			matches!(COMMANDS.parse("nonsense 1"), Err(ConsoleError::Unknown(name)) if name == "nonsense")
+		);
+		assert!(matches!(
+			COMMANDS.parse(r#"fov "90"#),
+			Err(ConsoleError::UnclosedQuote)
+		));
+	}
+
+	#[test]
+	fn arguments_are_checked() {
+		let (_, mut args) = COMMANDS.parse("fov NaN 5").unwrap();
+
+		assert!(matches!(
+			args.next::<f32>("degrees"),
+			Err(ConsoleError::Invalid { name: "degrees", ref value, .. }) if value == "NaN"
+		));
+		assert!(matches!(args.finish(), Err(ConsoleError::Unexpected(token)) if token == "NaN"));
+
+		let (_, mut args) = COMMANDS.parse("fov").unwrap();
+		let error = args.next::<f32>("degrees").unwrap_err();
+		assert!(matches!(error, ConsoleError::Missing("degrees")));
+		assert!(error.is_usage());
+		assert!(!ConsoleError::Failed(String::new()).is_usage());
+
+		assert_eq!(
+			<RenderMode as Arg>::parse("material_id"),
+			Some(RenderMode::MaterialId)
+		);
+		assert_eq!(<RenderMode as Arg>::parse("Wireframe"), None);
+		assert_eq!(<f32 as Arg>::parse("inf"), None);
+	}
+
+	#[test]
+	fn usages_list_every_form() {
+		let command = Command {
+			name: "test",
+			usages: &["", "<a> <b>"],
+			description: "",
+			handler: |_, _| Ok(String::new()),
+		};
+
+		assert_eq!(command.usage(), "test or test <a> <b>");
+		assert_eq!(COMMANDS.parse("fov").unwrap().0.usage(), "fov <degrees>");
+	}
+
+	#[test]
+	#[should_panic]
+	fn commands_can_only_be_registered_once() {
+		let mut registry = CommandRegistry::builtin();
+		registry.register(Command {
+			name: "help",
+			usages: &[""],
+			description: "",
+			handler: help,
+		});
+	}
+
+	#[test]
+	fn completion_finds_commands_by_prefix() {
+		assert_eq!(COMMANDS.complete("d"), ["disconnect", "dump"]);
+		assert_eq!(COMMANDS.complete("f"), ["fov"]);
+		assert!(COMMANDS.complete("x").is_empty());
+		assert_eq!(COMMANDS.complete("").len(), 6);
+	}
+
+	#[test]
+	fn completing_fills_in_the_input() {
+		let mut console = Console {
+			input: String::from("f"),
+			..Console::default()
+		};
+
+		console.complete();
+		assert_eq!(console.input, "fov ");
+
+		// Both start with "d", so there's nothing more to fill in, but they're listed
+		console.input = String::from("d");
+		console.complete();
+		assert_eq!(console.input, "d");
+		assert_eq!(console.output.back().unwrap(), "disconnect  dump");
+
+		for input in ["/d", "fov d"] {
+			console.input = String::from(input);
+			console.complete();
+			assert_eq!(console.input, input);
+		}
+	}
+
+	#[test]
+	fn output_is_limited() {
+		let mut console = Console::default();
+
+		for line in 0..Console::MAX_OUTPUT_LINES {
+			console.print(&format!("{line}\nmore"));
+		}
+
+		assert_eq!(console.output.len(), Console::MAX_OUTPUT_LINES);
+		assert_eq!(console.output.back().unwrap(), "more");
+	}
+
+	#[test]
+	fn history_steps_back_and_forth() {
+		let mut history = history(&["one", "two", "three"]);
+
+		assert_eq!(history.next(), None);
+		assert_eq!(history.previous(), Some("three"));
+		assert_eq!(history.previous(), Some("two"));
+		assert_eq!(history.previous(), Some("one"));
+		assert_eq!(history.previous(), Some("one"));
+		assert_eq!(history.next(), Some("two"));
+		assert_eq!(history.next(), Some("three"));
+		assert_eq!(history.next(), None);
+		assert_eq!(history.previous(), Some("three"));
+
+		assert_eq!(History::default().previous(), None);
+	}
+
+	#[test]
+	fn history_skips_repeats_and_is_limited() {
+		let mut history = history(&[]);
+
+		history.push(String::from("fov 90"));
+		history.previous();
+		history.push(String::from("fov 90"));
+		assert_eq!(history.entries, ["fov 90"]);
+		assert_eq!(history.position, None);
+
+		for index in 0..History::MAX_ENTRIES {
+			history.push(index.to_string());
+		}
+
+		assert_eq!(history.entries.len(), History::MAX_ENTRIES);
+		assert_eq!(history.entries[0], "0");
+	}
+
+	#[test]
+	fn history_positions_are_not_saved() {
+		let mut history = history(&["one", "two"]);
+		history.previous();
+
+		let loaded: History =
+			serde_json::from_str(&serde_json::to_string(&history).unwrap()).unwrap();
+
+		assert_eq!(loaded.entries, ["one", "two"]);
+		assert_eq!(loaded.position, None);
+	}
+}
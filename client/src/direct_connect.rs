@@ -0,0 +1,102 @@
+use crate::config;
+use serde::{Deserialize, Serialize};
+
+/// Sectors the player has connected to directly, most recent first, kept between sessions. Keys are stored as entered,
+/// pre-shared keys are only meant for LAN play so they aren't treated as secrets.
+#[derive(Default, Deserialize, Serialize)]
+pub struct DirectConnectHistory {
+	entries: Vec<DirectConnection>,
+}
+
+#[derive(Clone, Default, Deserialize, PartialEq, Serialize)]
+pub struct DirectConnection {
+	pub address: String,
+	pub key: String,
+}
+
+impl DirectConnectHistory {
+	const MAX_ENTRIES: usize = 5;
+	const FILE: &'static str = "direct_connect_history.json";
+
+	/// Loads the history, starting a new one if there isn't one or it can't be read.
+	pub fn load() -> Self {
+		config::load(Self::FILE)
+	}
+
+	pub fn save(&self) {
+		config::save(Self::FILE, self);
+	}
+
+	/// Moves `connection` to the front, dropping the oldest entry if there are too many.
+	pub fn add(&mut self, connection: DirectConnection) {
+		self.entries
+			.retain(|entry| entry.address != connection.address);
+		self.entries.insert(0, connection);
+		self.entries.truncate(Self::MAX_ENTRIES);
+	}
+
+	pub fn entries(&self) -> &[DirectConnection] {
+		&self.entries
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn connection(address: &str) -> DirectConnection {
+		DirectConnection {
+			address: address.into(),
+			key: format!("key for {address}"),
+		}
+	}
+
+	fn addresses(history: &DirectConnectHistory) -> Vec<&str> {
+		history
+			.entries()
+			.iter()
+			.map(|entry| &*entry.address)
+			.collect()
+	}
+
+	#[test]
+	fn recent_connections_come_first() {
+		let mut history = DirectConnectHistory::default();
+
+		history.add(connection("a"));
+		history.add(connection("b"));
+		assert_eq!(addresses(&history), ["b", "a"]);
+
+		// Connecting again moves it to the front, with the key used this time
+		history.add(DirectConnection {
+			key: "new key".into(),
+			..connection("a")
+		});
+		assert_eq!(addresses(&history), ["a", "b"]);
+		assert_eq!(history.entries()[0].key, "new key");
+	}
+
+	#[test]
+	fn oldest_connections_are_forgotten() {
+		let mut history = DirectConnectHistory::default();
+
+		for address in ["a", "b", "c", "d", "e", "f", "g"] {
+			history.add(connection(address));
+		}
+
+		assert_eq!(history.entries().len(), DirectConnectHistory::MAX_ENTRIES);
+		assert_eq!(addresses(&history), ["g", "f", "e", "d", "c"]);
+	}
+
+	#[test]
+	fn history_round_trips() {
+		let mut history = DirectConnectHistory::default();
+		history.add(connection("192.168.1.2:23500"));
+		history.add(connection("[::1]:23500"));
+
+		let json = serde_json::to_string(&history).unwrap();
+		let loaded: DirectConnectHistory = serde_json::from_str(&json).unwrap();
+
+		assert!(loaded.entries() == history.entries());
+	}
+}
@@ -0,0 +1,321 @@
+use crate::{
+	client::{AnyState, State},
+	login::{Login, QueueStatus, Session},
+	tr,
+	world::Sector,
+	ClArgs,
+};
+use egui::{Align, Align2, Context, Layout, Vec2, Window};
+use log::{info, warn};
+use solarscape_shared::message::clientbound::Transfer;
+use std::time::{Duration, Instant};
+use tokio::{runtime::Handle, task::JoinHandle};
+
+/// Shown after the connection to a sector is lost, reconnects using the existing session rather than sending the
+/// player back to the login form. Also used when the sector sends the player to another sector, see
+/// [`Disconnected::transfer`].
+pub struct Disconnected {
+	session: Session,
+	/// The sector being transferred to, [`None`] if the connection was lost.
+	transfer: Option<Box<str>>,
+
+	/// How many attempts have failed so far.
+	failed_attempts: u32,
+	last_error: Option<String>,
+
+	next_attempt: Instant,
+	attempt: Option<JoinHandle<Result<Sector, anyhow::Error>>>,
+	/// An attempt waits in the sector's queue for as long as it's full, rather than failing.
+	queue: QueueStatus,
+
+	cancelled: bool,
+}
+
+impl Disconnected {
+	/// How many times to try reconnecting before giving up and going back to the login form.
+	const MAX_ATTEMPTS: u32 = 6;
+
+	const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+	const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+	pub fn new(session: Session) -> Self {
+		Self {
+			session,
+			transfer: None,
+
+			failed_attempts: 0,
+			last_error: None,
+
+			// The server may have just restarted, give it a moment before the first attempt
+			next_attempt: Instant::now() + Self::INITIAL_BACKOFF,
+			attempt: None,
+			queue: QueueStatus::default(),
+
+			cancelled: false,
+		}
+	}
+
+	/// Connects to the sector the player is being transferred to, through the gateway for it. The sector expects the
+	/// player, so the first attempt is made straight away, later attempts back off like any other reconnect.
+	pub fn transfer(mut session: Session, Transfer { sector, gateway }: Transfer) -> Self {
+		match gateway.parse() {
+			Ok(api_endpoint) => session.api_endpoint = api_endpoint,
+			Err(error) => warn!(
+				"The gateway for {sector} isn't a valid URL, trying the current gateway instead: {error}"
+			),
+		}
+
+		Self {
+			transfer: Some(sector),
+			next_attempt: Instant::now(),
+			..Self::new(session)
+		}
+	}
+
+	/// How long to wait after `failed_attempts` failed attempts before trying again, doubling each time.
+	fn backoff(failed_attempts: u32) -> Duration {
+		Self::INITIAL_BACKOFF
+			.saturating_mul(2u32.saturating_pow(failed_attempts))
+			.min(Self::MAX_BACKOFF)
+	}
+}
+
+impl State for Disconnected {
+	fn tick(&mut self) -> Option<AnyState> {
+		if self.cancelled {
+			if let Some(handle) = &self.attempt {
+				handle.abort();
+			}
+
+			return Some(AnyState::Login(Login::default()));
+		}
+
+		if let Some(handle) = &mut self.attempt {
+			if !handle.is_finished() {
+				return None;
+			}
+
+			match Handle::current().block_on(handle).unwrap() {
+				Ok(sector) => {
+					match &self.transfer {
+						Some(destination) => info!("Transferred to {destination}"),
+						None => info!("Reconnected to the sector"),
+					}
+
+					return Some(AnyState::Sector(sector));
+				}
+				Err(error) => {
+					self.failed_attempts += 1;
+					warn!(
+						"Reconnect attempt {} of {} failed: {error}",
+						self.failed_attempts,
+						Self::MAX_ATTEMPTS
+					);
+
+					if self.failed_attempts >= Self::MAX_ATTEMPTS {
+						return Some(AnyState::Login(Login::with_error(tr!(
+							"disconnected.gave_up",
+							error = error
+						))));
+					}
+
+					self.last_error = Some(error.to_string());
+					self.next_attempt = Instant::now() + Self::backoff(self.failed_attempts);
+				}
+			}
+
+			self.attempt = None;
+		}
+
+		if Instant::now() >= self.next_attempt {
+			self.attempt =
+				Some(Handle::current().spawn(self.session.clone().join(self.queue.clone())));
+		}
+
+		None
+	}
+
+	fn draw_ui(&mut self, _: &ClArgs, context: &Context) {
+		let title = match self.transfer {
+			Some(_) => tr!("disconnected.transfer_title"),
+			None => tr!("disconnected.title"),
+		};
+
+		Window::new(title)
+			.id(egui::Id::new("disconnected"))
+			.anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+			.resizable(false)
+			.collapsible(false)
+			.auto_sized()
+			.max_width(400.0)
+			.show(context, |window| {
+				if let Some(error) = &self.last_error {
+					window.label(tr!("disconnected.error", error = error) + "\n");
+				}
+
+				window.allocate_ui_with_layout(
+					Vec2 {
+						x: window.min_rect().width(),
+						y: 0.0,
+					},
+					Layout::left_to_right(Align::Center),
+					|layout| {
+						layout.spinner();
+
+						match self.attempt {
+							Some(_) => {
+								layout.label(self.queue.describe().unwrap_or_else(|| {
+									match (&self.transfer, self.failed_attempts) {
+										(Some(sector), 0) => {
+											tr!("disconnected.transferring", sector = sector)
+										}
+										_ => tr!(
+											"disconnected.reconnecting",
+											attempt = self.failed_attempts + 1,
+											max = Self::MAX_ATTEMPTS
+										),
+									}
+								}))
+							}
+							None => layout.label(tr!(
+								"disconnected.retrying",
+								seconds = self
+									.next_attempt
+									.saturating_duration_since(Instant::now())
+									.as_secs_f32()
+									.ceil()
+							)),
+						};
+
+						layout.with_layout(Layout::right_to_left(Align::Center), |layout| {
+							if layout.button(tr!("common.cancel")).clicked() {
+								self.cancelled = true;
+							}
+						});
+					},
+				);
+			});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use solarscape_shared::connection::KeepAlive;
+	use std::thread;
+	use tokio::runtime::Runtime;
+
+	/// A session for a gateway that isn't there, so every attempt fails straight away.
+	fn session() -> Session {
+		Session {
+			api_endpoint: "http://127.0.0.1:9/api".parse().unwrap(),
+			token: "token".into(),
+			keep_alive: KeepAlive::default(),
+		}
+	}
+
+	/// Ticks until an attempt has been made and has failed, or the state changes.
+	fn tick_until_failed(disconnected: &mut Disconnected) -> Option<AnyState> {
+		let failed_attempts = disconnected.failed_attempts;
+
+		for _ in 0..1000 {
+			let state = disconnected.tick();
+
+			if state.is_some() || disconnected.failed_attempts > failed_attempts {
+				return state;
+			}
+
+			thread::sleep(Duration::from_millis(10));
+		}
+
+		panic!("the attempt should have failed");
+	}
+
+	#[test]
+	fn backoff_doubles_up_to_the_cap() {
+		let schedule = (0..8).map(Disconnected::backoff).collect::<Vec<_>>();
+
+		assert_eq!(
+			schedule,
+			[1, 2, 4, 8, 16, 30, 30, 30].map(Duration::from_secs)
+		);
+		assert_eq!(Disconnected::backoff(u32::MAX), Disconnected::MAX_BACKOFF);
+	}
+
+	#[test]
+	fn failed_attempt_backs_off() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+
+		let mut disconnected = Disconnected {
+			failed_attempts: 2,
+			next_attempt: Instant::now(),
+			..Disconnected::new(session())
+		};
+
+		assert!(tick_until_failed(&mut disconnected).is_none());
+
+		assert_eq!(disconnected.failed_attempts, 3);
+		assert!(disconnected.last_error.is_some());
+		assert!(disconnected.attempt.is_none());
+
+		let wait = disconnected.next_attempt - Instant::now();
+		assert!(wait > Duration::from_secs(7) && wait <= Duration::from_secs(8));
+
+		// Nothing is tried again until the backoff is over
+		assert!(disconnected.tick().is_none());
+		assert!(disconnected.attempt.is_none());
+	}
+
+	#[test]
+	fn gives_up_after_max_attempts() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+
+		let mut disconnected = Disconnected {
+			failed_attempts: Disconnected::MAX_ATTEMPTS - 1,
+			next_attempt: Instant::now(),
+			..Disconnected::new(session())
+		};
+
+		let state = tick_until_failed(&mut disconnected);
+
+		assert!(matches!(state, Some(AnyState::Login(_))));
+		assert_eq!(disconnected.failed_attempts, Disconnected::MAX_ATTEMPTS);
+	}
+
+	#[test]
+	fn first_reconnect_waits_but_transfer_does_not() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+
+		let mut disconnected = Disconnected::new(session());
+		assert!(disconnected.tick().is_none());
+		assert!(disconnected.attempt.is_none());
+
+		let transfer = Transfer {
+			sector: "other".into(),
+			gateway: "http://127.0.0.1:9/other".into(),
+		};
+		let mut transferring = Disconnected::transfer(session(), transfer);
+		assert!(transferring.tick().is_none());
+		assert!(transferring.attempt.is_some());
+		assert_eq!(transferring.session.api_endpoint.path(), "/other");
+	}
+
+	#[test]
+	fn cancel_goes_back_to_login() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+
+		let mut disconnected = Disconnected {
+			next_attempt: Instant::now(),
+			..Disconnected::new(session())
+		};
+
+		assert!(disconnected.tick().is_none());
+		disconnected.cancelled = true;
+
+		assert!(matches!(disconnected.tick(), Some(AnyState::Login(_))));
+	}
+}
@@ -0,0 +1,410 @@
+use crate::{config, tr};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::{
+	cmp::Reverse,
+	fmt::{self, Display, Formatter},
+};
+use winit::{
+	monitor::{MonitorHandle, VideoModeHandle},
+	window::{Fullscreen, Window},
+};
+
+/// How the window is shown, kept between sessions.
+#[derive(Clone, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct DisplaySettings {
+	pub mode: DisplayMode,
+	/// Monitor to go fullscreen on, [`None`] for whichever the window is on.
+	pub monitor: Option<MonitorId>,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum DisplayMode {
+	#[default]
+	Windowed,
+	/// A borderless window covering the monitor, at the monitor's own resolution.
+	Borderless,
+	/// Takes over the monitor, switching it to this video mode.
+	Exclusive(VideoMode),
+}
+
+impl DisplayMode {
+	pub fn name(&self) -> String {
+		match self {
+			Self::Windowed => tr!("settings.display_windowed"),
+			Self::Borderless => tr!("settings.display_borderless"),
+			Self::Exclusive(_) => tr!("settings.display_exclusive"),
+		}
+	}
+}
+
+/// A monitor's video mode, as it's remembered between sessions.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub struct VideoMode {
+	pub width: u32,
+	pub height: u32,
+	pub refresh_rate_millihertz: u32,
+}
+
+impl From<&VideoModeHandle> for VideoMode {
+	fn from(mode: &VideoModeHandle) -> Self {
+		Self {
+			width: mode.size().width,
+			height: mode.size().height,
+			refresh_rate_millihertz: mode.refresh_rate_millihertz(),
+		}
+	}
+}
+
+impl Display for VideoMode {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		write!(
+			formatter,
+			"{}×{} @ {:.0} Hz",
+			self.width,
+			self.height,
+			self.refresh_rate_millihertz as f32 / 1000.0
+		)
+	}
+}
+
+/// Identifies a monitor between sessions. Monitors are matched by name first, as their position changes whenever the
+/// layout does, but more than one monitor can have the same name.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct MonitorId {
+	pub name: Option<String>,
+	pub position: (i32, i32),
+}
+
+impl From<&MonitorHandle> for MonitorId {
+	fn from(monitor: &MonitorHandle) -> Self {
+		Self {
+			name: monitor.name(),
+			position: monitor.position().into(),
+		}
+	}
+}
+
+impl Display for MonitorId {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		let (x, y) = self.position;
+
+		match &self.name {
+			Some(name) => write!(formatter, "{name} ({x}, {y})"),
+			None => write!(formatter, "{x}, {y}"),
+		}
+	}
+}
+
+impl DisplaySettings {
+	const FILE: &'static str = "display.json";
+
+	pub fn load() -> Self {
+		config::load(Self::FILE)
+	}
+
+	pub fn save(&self) {
+		config::save(Self::FILE, self);
+	}
+
+	/// Switches between windowed and fullscreen, for the fullscreen key. Fullscreen is borderless, as it's the mode that
+	/// works everywhere, exclusive fullscreen has to be picked in the settings.
+	pub fn toggle(&mut self) {
+		self.mode = match self.mode {
+			DisplayMode::Windowed => DisplayMode::Borderless,
+			DisplayMode::Borderless | DisplayMode::Exclusive(_) => DisplayMode::Windowed,
+		};
+	}
+
+	/// The monitor to go fullscreen on. If the remembered monitor isn't connected any more, or none was picked, it's
+	/// whichever the window is on.
+	pub fn monitor(&self, window: &Window) -> Option<MonitorHandle> {
+		self.monitor
+			.as_ref()
+			.and_then(|saved| {
+				let monitors = window
+					.available_monitors()
+					.map(|monitor| (MonitorId::from(&monitor), monitor));
+
+				match_monitor(saved, monitors)
+			})
+			.or_else(|| window.current_monitor())
+			.or_else(|| window.primary_monitor())
+	}
+
+	/// Puts `window` in the selected mode. Anything that's gone since the settings were saved falls back to what's
+	/// closest, see [`DisplaySettings::monitor`] and [`select_video_mode`].
+	pub fn apply(&self, window: &Window) {
+		let monitor = self.monitor(window);
+
+		let fullscreen = match self.mode {
+			DisplayMode::Windowed => None,
+			DisplayMode::Borderless => Some(Fullscreen::Borderless(monitor)),
+			DisplayMode::Exclusive(wanted) => {
+				let video_mode = monitor.as_ref().and_then(|monitor| {
+					let modes = monitor
+						.video_modes()
+						.map(|mode| (VideoMode::from(&mode), mode));
+
+					select_video_mode(wanted, modes)
+				});
+
+				match video_mode {
+					Some(video_mode) => {
+						info!(
+							"Using exclusive fullscreen at {}",
+							VideoMode::from(&video_mode)
+						);
+						Some(Fullscreen::Exclusive(video_mode))
+					}
+					None => {
+						warn!("No video modes available for exclusive fullscreen, using borderless instead");
+						Some(Fullscreen::Borderless(monitor))
+					}
+				}
+			}
+		};
+
+		window.set_fullscreen(fullscreen);
+	}
+}
+
+/// Finds `saved` among `monitors`. A monitor with the same name and position is best, then one with the same name,
+/// then one at the same position, as it's most likely the same monitor having been renamed by a driver update.
+fn match_monitor<T>(
+	saved: &MonitorId,
+	monitors: impl IntoIterator<Item = (MonitorId, T)>,
+) -> Option<T> {
+	monitors
+		.into_iter()
+		.filter_map(|(id, monitor)| {
+			let same_name = saved.name.is_some() && id.name == saved.name;
+			let same_position = id.position == saved.position;
+
+			let rank = match (same_name, same_position) {
+				(true, true) => 0,
+				(true, false) => 1,
+				(false, true) => 2,
+				(false, false) => return None,
+			};
+
+			Some((rank, monitor))
+		})
+		.min_by_key(|(rank, _)| *rank)
+		.map(|(_, monitor)| monitor)
+}
+
+/// The video mode in `available` that's closest to `wanted`. The same size is preferred over the same refresh rate,
+/// as a wrong size is far more noticeable, and if the size isn't available at all the largest mode is used.
+fn select_video_mode<T>(
+	wanted: VideoMode,
+	available: impl IntoIterator<Item = (VideoMode, T)>,
+) -> Option<T> {
+	available
+		.into_iter()
+		.max_by_key(|(mode, _)| {
+			(
+				(mode.width, mode.height) == (wanted.width, wanted.height),
+				mode.refresh_rate_millihertz == wanted.refresh_rate_millihertz,
+				mode.width * mode.height,
+				mode.refresh_rate_millihertz,
+			)
+		})
+		.map(|(_, mode)| mode)
+}
+
+/// Every video mode `monitor` supports, largest and fastest first, without repeats for different bit depths.
+pub fn video_modes(monitor: &MonitorHandle) -> Vec<VideoMode> {
+	sort_video_modes(monitor.video_modes().map(|mode| VideoMode::from(&mode)))
+}
+
+fn sort_video_modes(modes: impl IntoIterator<Item = VideoMode>) -> Vec<VideoMode> {
+	let mut modes = modes.into_iter().collect::<Vec<_>>();
+
+	modes.sort_by_key(|mode| {
+		(
+			Reverse(mode.width * mode.height),
+			Reverse(mode.width),
+			Reverse(mode.refresh_rate_millihertz),
+		)
+	});
+	modes.dedup();
+
+	modes
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn mode(width: u32, height: u32, refresh_rate: u32) -> VideoMode {
+		VideoMode {
+			width,
+			height,
+			refresh_rate_millihertz: refresh_rate * 1000,
+		}
+	}
+
+	fn monitor(name: Option<&str>, position: (i32, i32)) -> MonitorId {
+		MonitorId {
+			name: name.map(str::to_owned),
+			position,
+		}
+	}
+
+	#[test]
+	fn toggling_goes_between_windowed_and_borderless() {
+		let mut settings = DisplaySettings::default();
+		assert_eq!(settings.mode, DisplayMode::Windowed);
+
+		settings.toggle();
+		assert_eq!(settings.mode, DisplayMode::Borderless);
+
+		settings.toggle();
+		assert_eq!(settings.mode, DisplayMode::Windowed);
+
+		settings.mode = DisplayMode::Exclusive(mode(1920, 1080, 60));
+		settings.toggle();
+		assert_eq!(settings.mode, DisplayMode::Windowed);
+	}
+
+	#[test]
+	fn monitors_are_matched_by_name_then_position() {
+		let saved = monitor(Some("DP-1"), (0, 0));
+
+		let monitors = [
+			(monitor(Some("HDMI-1"), (0, 0)), "same position"),
+			(monitor(Some("DP-1"), (1920, 0)), "same name"),
+			(monitor(Some("DP-1"), (0, 0)), "both"),
+			(monitor(Some("DP-2"), (3840, 0)), "neither"),
+		];
+
+		assert_eq!(match_monitor(&saved, monitors.clone()), Some("both"));
+		assert_eq!(
+			match_monitor(&saved, monitors[..2].to_vec()),
+			Some("same name")
+		);
+		assert_eq!(
+			match_monitor(&saved, monitors[..1].to_vec()),
+			Some("same position")
+		);
+		assert_eq!(match_monitor(&saved, monitors[3..].to_vec()), None);
+		assert_eq!(match_monitor::<&str>(&saved, []), None);
+	}
+
+	#[test]
+	fn unnamed_monitors_only_match_by_position() {
+		let saved = monitor(None, (0, 0));
+
+		let monitors = [
+			(monitor(None, (1920, 0)), "elsewhere"),
+			(monitor(Some("DP-1"), (0, 0)), "same position"),
+		];
+
+		assert_eq!(
+			match_monitor(&saved, monitors.clone()),
+			Some("same position")
+		);
+		assert_eq!(match_monitor(&saved, monitors[..1].to_vec()), None);
+	}
+
+	#[test]
+	fn the_closest_video_mode_is_selected() {
+		let select = |wanted, available: &[VideoMode]| {
+			select_video_mode(wanted, available.iter().map(|mode| (*mode, *mode)))
+		};
+
+		let available = [
+			mode(1280, 720, 60),
+			mode(1920, 1080, 60),
+			mode(1920, 1080, 144),
+			mode(2560, 1440, 60),
+			mode(2560, 1440, 75),
+		];
+
+		// Exactly what was wanted
+		assert_eq!(
+			select(mode(1920, 1080, 144), &available),
+			Some(mode(1920, 1080, 144))
+		);
+
+		// The size matters more than the refresh rate, then the fastest of that size
+		assert_eq!(
+			select(mode(1920, 1080, 120), &available),
+			Some(mode(1920, 1080, 144))
+		);
+		assert_eq!(
+			select(mode(1280, 720, 75), &available),
+			Some(mode(1280, 720, 60))
+		);
+
+		// Without the size, the largest, and of those the same refresh rate over the fastest
+		assert_eq!(
+			select(mode(3840, 2160, 60), &available),
+			Some(mode(2560, 1440, 60))
+		);
+		assert_eq!(
+			select(mode(3840, 2160, 30), &available),
+			Some(mode(2560, 1440, 75))
+		);
+
+		assert_eq!(select(mode(1920, 1080, 60), &[]), None);
+	}
+
+	#[test]
+	fn video_modes_are_largest_and_fastest_first_without_repeats() {
+		let modes = sort_video_modes([
+			mode(1280, 720, 60),
+			mode(1920, 1080, 60),
+			mode(1920, 1080, 144),
+			mode(1280, 720, 60),
+			mode(1920, 1080, 60),
+			mode(1080, 1920, 60),
+		]);
+
+		assert_eq!(
+			modes,
+			[
+				mode(1920, 1080, 144),
+				mode(1920, 1080, 60),
+				mode(1080, 1920, 60),
+				mode(1280, 720, 60),
+			]
+		);
+	}
+
+	#[test]
+	fn modes_and_monitors_are_shown() {
+		assert_eq!(mode(1920, 1080, 60).to_string(), "1920×1080 @ 60 Hz");
+		assert_eq!(
+			VideoMode {
+				refresh_rate_millihertz: 59_940,
+				..mode(1920, 1080, 0)
+			}
+			.to_string(),
+			"1920×1080 @ 60 Hz"
+		);
+
+		assert_eq!(
+			monitor(Some("DP-1"), (1920, -40)).to_string(),
+			"DP-1 (1920, -40)"
+		);
+		assert_eq!(monitor(None, (0, 0)).to_string(), "0, 0");
+	}
+
+	#[test]
+	fn settings_survive_being_saved() {
+		let settings = DisplaySettings {
+			mode: DisplayMode::Exclusive(mode(2560, 1440, 165)),
+			monitor: Some(monitor(Some("DP-1"), (-2560, 0))),
+		};
+
+		let json = serde_json::to_string(&settings).unwrap();
+		assert!(serde_json::from_str::<DisplaySettings>(&json).unwrap() == settings);
+
+		// Older settings files, or ones that have been edited, fall back to defaults for anything missing
+		let settings = serde_json::from_str::<DisplaySettings>("{}").unwrap();
+		assert!(settings == DisplaySettings::default());
+	}
+}
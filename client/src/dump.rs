@@ -0,0 +1,416 @@
+use crate::{config, world::Sector};
+use serde::{Deserialize, Serialize};
+use solarscape_shared::{
+	data::world::Location,
+	message::{
+		clientbound::{JoinComplete, Sync, SyncChunk, SyncInventory, SyncStructure, Voxject},
+		PROTOCOL_VERSION,
+	},
+};
+use std::{
+	fs::{self, File},
+	io::{self, BufWriter, Write},
+	path::PathBuf,
+	time::{SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+#[cfg(debug)]
+use bincode::Options;
+#[cfg(debug)]
+use solarscape_shared::connection::Connection;
+#[cfg(debug)]
+use std::{io::BufReader, path::Path};
+
+/// Identifies a file as a world dump.
+const MAGIC: [u8; 8] = *b"SDUMP\0\0\0";
+
+/// Bumped whenever [`Header`] or [`Dump`] changes, dumps from any other version are rejected rather than misread.
+pub const FORMAT_VERSION: u32 = 2;
+
+/// Most a [`Header`] could take up, far more than it ever really does.
+#[cfg(debug)]
+const HEADER_LIMIT: u64 = 1024;
+
+/// Written uncompressed ahead of the [`Dump`], so that a dump from another version can be rejected before trying to
+/// read the rest of it.
+#[derive(Deserialize, Serialize)]
+struct Header {
+	magic: [u8; 8],
+	format_version: u32,
+	protocol_version: u32,
+	client_version: Box<str>,
+}
+
+/// Everything the client knows about the world, for reproducing what a player was seeing when they reported a problem.
+///
+/// Only ever built field by field from the world itself, never from the [`Sector`] as a whole, so that the session
+/// token and anything else private about the player can't end up in a file they'll be sharing.
+#[derive(Deserialize, Serialize)]
+pub struct Dump {
+	pub location: Location,
+	/// The sector as the client has it, in the form the sector first sends it.
+	pub sync: Sync,
+	pub structures: Vec<SyncStructure>,
+	/// [`None`] if the sector hadn't sent it yet.
+	pub inventory: Option<SyncInventory>,
+	pub chunks: Vec<SyncChunk>,
+}
+
+impl Dump {
+	pub fn new(sector: &Sector) -> Self {
+		Self {
+			location: sector.player.location,
+			sync: Sync {
+				// The client doesn't keep the sector's name
+				name: "Dump".into(),
+				tick_rate: sector.server_tick_rate,
+				world_radius: sector
+					.world_border
+					.as_ref()
+					.map(|world_border| world_border.radius()),
+				world_radius_chunks: sector.world_radius_chunks,
+				max_level: sector.max_level,
+
+				voxjects: sector
+					.voxjects
+					.values()
+					.map(|voxject| Voxject {
+						id: voxject.id,
+						name: voxject.name.clone(),
+					})
+					.collect(),
+
+				ambient_keyframes: sector.ambient.keyframes().to_vec(),
+				ambient: sector.ambient.build_sync(),
+			},
+			structures: sector
+				.structures
+				.iter()
+				.map(|structure| structure.build_sync(&sector.physics))
+				.collect(),
+			inventory: sector.inventory().cloned(),
+			chunks: sector
+				.chunks
+				.iter()
+				.map(|chunk| SyncChunk {
+					coordinates: chunk.coordinates,
+					generation: chunk.generation,
+					materials: chunk.materials.clone(),
+					densities: chunk.densities.clone(),
+				})
+				.collect(),
+		}
+	}
+
+	/// Writes the dump to a new file in the `dumps` directory of the client's config directory, and returns it's path.
+	pub fn write(&self) -> Result<PathBuf, DumpError> {
+		let directory = config::directory()
+			.ok_or(DumpError::NoDirectory)?
+			.join("dumps");
+		fs::create_dir_all(&directory)?;
+
+		let now = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map_or(0, |now| now.as_secs());
+		let path = directory.join(format!("{now}.dump"));
+
+		self.write_to(&mut BufWriter::new(File::create(&path)?))?;
+
+		Ok(path)
+	}
+
+	fn write_to(&self, file: &mut impl Write) -> Result<(), DumpError> {
+		bincode::serialize_into(
+			&mut *file,
+			&Header {
+				magic: MAGIC,
+				format_version: FORMAT_VERSION,
+				protocol_version: PROTOCOL_VERSION,
+				client_version: env!("CARGO_PKG_VERSION").into(),
+			},
+		)?;
+
+		// Densities and materials compress well, and chunks make up nearly all of the dump
+		let mut encoder = zstd::Encoder::new(file, 3)?;
+		bincode::serialize_into(&mut encoder, self)?;
+		encoder.finish()?.flush()?;
+
+		Ok(())
+	}
+}
+
+/// Loading dumps is only available in debug builds, see `--load-dump`.
+#[cfg(debug)]
+impl Dump {
+	/// Reads a dump written by [`Dump::write`], rejecting dumps from other versions.
+	pub fn read(path: &Path) -> Result<Self, DumpError> {
+		let mut file = BufReader::new(File::open(path)?);
+
+		// Limited, so that a file that isn't a dump can't claim to have an enormous client version
+		let header: Header = bincode::options()
+			.with_fixint_encoding()
+			.allow_trailing_bytes()
+			.with_limit(HEADER_LIMIT)
+			.deserialize_from(&mut file)
+			.ok()
+			.filter(|header: &Header| header.magic == MAGIC)
+			.ok_or(DumpError::NotADump)?;
+
+		if header.format_version != FORMAT_VERSION {
+			return Err(DumpError::FormatVersion(header.format_version));
+		}
+
+		if header.protocol_version != PROTOCOL_VERSION {
+			return Err(DumpError::ProtocolVersion(
+				header.protocol_version,
+				header.client_version,
+			));
+		}
+
+		Ok(bincode::deserialize_from(zstd::Decoder::new(file)?)?)
+	}
+
+	/// Loads the dump into a sector, as if a sector had sent it. Nothing else is ever received, and anything the
+	/// client sends is ignored.
+	pub async fn load(self) -> Result<Sector, anyhow::Error> {
+		let (client, server) = Connection::pair();
+
+		server.send(self.sync);
+
+		for structure in self.structures {
+			server.send(structure);
+		}
+
+		if let Some(inventory) = self.inventory {
+			server.send(inventory);
+		}
+
+		for chunk in self.chunks {
+			server.send(chunk);
+		}
+
+		server.send(JoinComplete);
+
+		// Kept open for as long as the client is, otherwise the client would think it had lost connection
+		tokio::spawn(async move {
+			let mut server = server;
+			while server.recv().await.is_some() {}
+		});
+
+		let mut sector = Sector::new(client, None).await?;
+		sector.player.location = self.location;
+
+		Ok(sector)
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum DumpError {
+	#[error("there's no config directory to write dumps to")]
+	NoDirectory,
+
+	#[error(transparent)]
+	Io(#[from] io::Error),
+
+	#[cfg(debug)]
+	#[error("not a world dump")]
+	NotADump,
+
+	#[cfg(debug)]
+	#[error("dump is format version {0}, but this client only reads version {FORMAT_VERSION}")]
+	FormatVersion(u32),
+
+	#[cfg(debug)]
+	#[error(
+		"dump is from protocol version {0} (client {1}), but this client is protocol version {PROTOCOL_VERSION}"
+	)]
+	ProtocolVersion(u32, Box<str>),
+
+	#[error("dump is corrupt: {0}")]
+	Corrupt(#[from] bincode::Error),
+}
+
+/// Reading dumps back is only possible in debug builds.
+#[cfg(all(test, debug))]
+mod tests {
+	use super::*;
+	use nalgebra::{point, vector, UnitQuaternion, Vector3};
+	use solarscape_shared::{
+		consts::CELLS_PER_CHUNK,
+		data::{
+			world::{
+				BlockType, ChunkCoordinates, Item, Level, Material, LEVELS, UNBOUNDED_CHUNK_RADIUS,
+			},
+			Id,
+		},
+		message::clientbound::{Ambient, AmbientKeyframe, InventoryCapacity, InventorySlot},
+	};
+	use std::{env, sync::Arc};
+
+	/// A temporary dump file, deleted when dropped.
+	struct TempFile(PathBuf);
+
+	impl TempFile {
+		fn new() -> Self {
+			Self(env::temp_dir().join(format!("solarscape-dump-{}.dump", Id::new())))
+		}
+
+		fn write(&self, dump: &Dump) {
+			dump.write_to(&mut File::create(&self.0).unwrap()).unwrap();
+		}
+	}
+
+	impl Drop for TempFile {
+		fn drop(&mut self) {
+			let _ = fs::remove_file(&self.0);
+		}
+	}
+
+	fn dump() -> Dump {
+		let voxject = Id::new();
+		let keyframe = AmbientKeyframe {
+			phase: 0.25,
+			sky: [0.3, 0.5, 0.9],
+			sun: [1.0, 0.95, 0.9],
+			ambient: [0.3, 0.3, 0.35],
+		};
+
+		Dump {
+			location: Location {
+				position: point![1.0, 2.0, 3.0],
+				rotation: UnitQuaternion::identity(),
+			},
+			sync: Sync {
+				name: "Dump".into(),
+				tick_rate: 30,
+				world_radius: Some(500.0),
+				world_radius_chunks: UNBOUNDED_CHUNK_RADIUS,
+				max_level: Level::new(LEVELS - 2),
+				voxjects: vec![Voxject {
+					id: voxject,
+					name: "planet".into(),
+				}],
+				ambient_keyframes: vec![keyframe],
+				ambient: Ambient {
+					phase: 0.5,
+					rate: 0.0,
+				},
+			},
+			structures: vec![SyncStructure {
+				id: Id::new(),
+				owner: Id::new(),
+				location: Location {
+					position: point![0.0, 50.0, 0.0],
+					rotation: UnitQuaternion::identity(),
+				},
+				linear_velocity: Vector3::zeros(),
+				angular_velocity: Vector3::zeros(),
+				sleeping: true,
+				blocks: [(vector![0, 0, 0], BlockType::Block)].into_iter().collect(),
+				block_states: Default::default(),
+			}],
+			inventory: Some(SyncInventory {
+				slots: vec![InventorySlot {
+					item: Item::TestOre,
+					quantity: 12,
+				}],
+				capacity: InventoryCapacity::default(),
+			}),
+			chunks: vec![SyncChunk {
+				coordinates: ChunkCoordinates::new(voxject, Vector3::zeros(), Level::new(0)),
+				generation: 3,
+				materials: Arc::new([Material::Stone; CELLS_PER_CHUNK]),
+				densities: Arc::new([0.5; CELLS_PER_CHUNK]),
+			}],
+		}
+	}
+
+	fn bytes(dump: &Dump) -> Vec<u8> {
+		bincode::serialize(dump).unwrap()
+	}
+
+	#[test]
+	fn dumps_round_trip() {
+		let file = TempFile::new();
+		let dump = dump();
+		file.write(&dump);
+
+		assert_eq!(bytes(&Dump::read(&file.0).unwrap()), bytes(&dump));
+	}
+
+	#[tokio::test]
+	async fn loaded_dumps_dump_the_same() {
+		let dump = dump();
+		let expected = bytes(&dump);
+
+		let mut sector = dump.load().await.unwrap();
+		sector.process_messages();
+
+		assert_eq!(bytes(&Dump::new(&sector)), expected);
+	}
+
+	/// Taken before the sector got around to sending the inventory.
+	#[tokio::test]
+	async fn dumps_without_an_inventory_load() {
+		let mut dump = dump();
+		dump.inventory = None;
+		let expected = bytes(&dump);
+
+		let mut sector = dump.load().await.unwrap();
+		sector.process_messages();
+
+		assert!(sector.inventory().is_none());
+		assert_eq!(bytes(&Dump::new(&sector)), expected);
+	}
+
+	#[test]
+	fn other_versions_are_rejected() {
+		let file = TempFile::new();
+		let header = |format_version, protocol_version| Header {
+			magic: MAGIC,
+			format_version,
+			protocol_version,
+			client_version: "0.0.0".into(),
+		};
+
+		fs::write(
+			&file.0,
+			bincode::serialize(&header(FORMAT_VERSION + 1, PROTOCOL_VERSION)).unwrap(),
+		)
+		.unwrap();
+		assert!(
+			matches!(Dump::read(&file.0), Err(DumpError::FormatVersion(version)) if version == FORMAT_VERSION + 1)
+		);
+
+		fs::write(
+			&file.0,
+			bincode::serialize(&header(FORMAT_VERSION, PROTOCOL_VERSION + 1)).unwrap(),
+		)
+		.unwrap();
+		assert!(matches!(
+			Dump::read(&file.0),
+			Err(DumpError::ProtocolVersion(version, client)) if version == PROTOCOL_VERSION + 1 && &*client == "0.0.0"
+		));
+	}
+
+	#[test]
+	fn other_files_are_rejected() {
+		let file = TempFile::new();
+
+		fs::write(&file.0, b"definitely not a dump, just some text").unwrap();
+		assert!(matches!(Dump::read(&file.0), Err(DumpError::NotADump)));
+
+		// Cut off part way through the body
+		file.write(&dump());
+		let contents = fs::read(&file.0).unwrap();
+		fs::write(&file.0, &contents[..contents.len() / 2]).unwrap();
+		assert!(matches!(
+			Dump::read(&file.0),
+			Err(DumpError::Corrupt(_) | DumpError::Io(_))
+		));
+
+		fs::remove_file(&file.0).unwrap();
+		assert!(matches!(Dump::read(&file.0), Err(DumpError::Io(_))));
+	}
+}
@@ -0,0 +1,192 @@
+use std::{
+	fmt::{self, Display, Formatter},
+	time::{Duration, Instant},
+};
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum FrameLimit {
+	Uncapped,
+	PerSecond(u32),
+}
+
+impl FrameLimit {
+	pub const PRESETS: &'static [Self] = &[
+		Self::Uncapped,
+		Self::PerSecond(30),
+		Self::PerSecond(60),
+		Self::PerSecond(144),
+	];
+
+	fn interval(&self) -> Option<Duration> {
+		match self {
+			Self::Uncapped | Self::PerSecond(0) => None,
+			Self::PerSecond(frames) => Some(Duration::from_secs(1) / *frames),
+		}
+	}
+}
+
+impl From<Option<u32>> for FrameLimit {
+	fn from(value: Option<u32>) -> Self {
+		match value {
+			None | Some(0) => Self::Uncapped,
+			Some(frames) => Self::PerSecond(frames),
+		}
+	}
+}
+
+impl Display for FrameLimit {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Uncapped => formatter.write_str("Uncapped"),
+			Self::PerSecond(frames) => write!(formatter, "{frames} FPS"),
+		}
+	}
+}
+
+/// Decides when the next frame should be drawn. This only delays rendering, anything that runs per-frame should
+/// still be using the frame's delta time.
+pub struct FrameLimiter {
+	pub limit: FrameLimit,
+
+	focused: bool,
+	occluded: bool,
+
+	next_frame: Option<Instant>,
+}
+
+impl FrameLimiter {
+	/// Nobody is looking at the window, so there's no point drawing it quickly.
+	const BACKGROUND_INTERVAL: Duration = Duration::from_millis(100);
+
+	pub fn new(limit: FrameLimit) -> Self {
+		Self {
+			limit,
+
+			focused: true,
+			occluded: false,
+
+			next_frame: None,
+		}
+	}
+
+	pub fn set_focused(&mut self, focused: bool) {
+		self.focused = focused;
+	}
+
+	pub fn set_occluded(&mut self, occluded: bool) {
+		self.occluded = occluded;
+	}
+
+	pub fn is_background(&self) -> bool {
+		!self.focused || self.occluded
+	}
+
+	fn interval(&self) -> Option<Duration> {
+		match self.is_background() {
+			true => Some(
+				self.limit
+					.interval()
+					.map_or(Self::BACKGROUND_INTERVAL, |interval| {
+						interval.max(Self::BACKGROUND_INTERVAL)
+					}),
+			),
+			false => self.limit.interval(),
+		}
+	}
+
+	/// Called once a frame has finished, returns when the next frame should start, or [`None`] if it should start
+	/// immediately.
+	pub fn schedule_next_frame(&mut self, now: Instant) -> Option<Instant> {
+		let Some(interval) = self.interval() else {
+			self.next_frame = None;
+			return None;
+		};
+
+		// Frames are scheduled relative to when the last one was supposed to start rather than when it actually did,
+		// so that wake-up latency doesn't slowly drag the frame rate down. If we've fallen behind then we don't try to
+		// catch up, we just start the next frame as soon as we can.
+		let next_frame = match self.next_frame {
+			Some(last_frame) => (last_frame + interval).max(now),
+			None => now + interval,
+		};
+
+		self.next_frame = Some(next_frame);
+		Some(next_frame)
+	}
+
+	/// When the next frame should start, if it's been scheduled.
+	pub fn next_frame(&self) -> Option<Instant> {
+		self.next_frame
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const SIXTY: Duration = Duration::from_nanos(16_666_666);
+
+	#[test]
+	fn uncapped_frames_start_immediately() {
+		let mut limiter = FrameLimiter::new(FrameLimit::Uncapped);
+		assert_eq!(limiter.schedule_next_frame(Instant::now()), None);
+		assert_eq!(limiter.next_frame(), None);
+
+		assert!(FrameLimit::from(Some(0)) == FrameLimit::Uncapped);
+		assert_eq!(FrameLimit::PerSecond(0).interval(), None);
+	}
+
+	#[test]
+	fn frames_are_scheduled_from_the_last_deadline() {
+		let mut limiter = FrameLimiter::new(FrameLimit::PerSecond(60));
+		let start = Instant::now();
+
+		let first = limiter.schedule_next_frame(start).unwrap();
+		assert_eq!(first, start + SIXTY);
+
+		// Waking up late doesn't push the frame after it back
+		let second = limiter
+			.schedule_next_frame(first + Duration::from_millis(2))
+			.unwrap();
+		assert_eq!(second, first + SIXTY);
+	}
+
+	#[test]
+	fn falling_behind_doesnt_catch_up() {
+		let mut limiter = FrameLimiter::new(FrameLimit::PerSecond(60));
+		let start = Instant::now();
+
+		limiter.schedule_next_frame(start);
+
+		let late = start + Duration::from_secs(1);
+		assert_eq!(limiter.schedule_next_frame(late), Some(late));
+		assert_eq!(limiter.schedule_next_frame(late), Some(late + SIXTY));
+	}
+
+	#[test]
+	fn background_windows_are_drawn_slowly() {
+		let mut limiter = FrameLimiter::new(FrameLimit::Uncapped);
+		let start = Instant::now();
+
+		limiter.set_focused(false);
+		assert!(limiter.is_background());
+		assert_eq!(
+			limiter.schedule_next_frame(start),
+			Some(start + FrameLimiter::BACKGROUND_INTERVAL)
+		);
+
+		limiter.set_focused(true);
+		assert!(!limiter.is_background());
+		assert_eq!(limiter.schedule_next_frame(start), None);
+
+		limiter.set_occluded(true);
+		assert!(limiter.is_background());
+
+		// Slower limits than the background rate are kept
+		limiter.limit = FrameLimit::PerSecond(5);
+		assert_eq!(
+			limiter.schedule_next_frame(start),
+			Some(start + Duration::from_millis(200))
+		);
+	}
+}
@@ -0,0 +1,205 @@
+use std::{
+	fmt::{self, Display, Formatter},
+	ops::Deref,
+	sync::atomic::{AtomicU64, Ordering::Relaxed},
+};
+use wgpu::{Buffer, Texture};
+
+/// Estimated GPU memory used by everything the client has created, see [`GpuCategory`].
+pub static GPU_MEMORY: GpuMemoryTracker = GpuMemoryTracker::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GpuCategory {
+	ChunkMeshes,
+	/// Block models, and the per block instance buffers.
+	Structures,
+	/// Texture atlases and the depth buffer.
+	Textures,
+}
+
+impl GpuCategory {
+	pub const ALL: [Self; 3] = [Self::ChunkMeshes, Self::Structures, Self::Textures];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::ChunkMeshes => "Chunk Meshes",
+			Self::Structures => "Structures",
+			Self::Textures => "Textures",
+		}
+	}
+}
+
+/// Running totals of bytes, which saturate rather than wrapping. These are the sizes requested from wgpu, drivers may
+/// pad or align allocations so the real usage will be a little higher.
+pub struct GpuMemoryTracker {
+	bytes: [AtomicU64; GpuCategory::ALL.len()],
+}
+
+impl GpuMemoryTracker {
+	const fn new() -> Self {
+		Self {
+			bytes: [const { AtomicU64::new(0) }; GpuCategory::ALL.len()],
+		}
+	}
+
+	fn track(&self, category: GpuCategory, bytes: u64) {
+		let _ = self.bytes[category as usize]
+			.fetch_update(Relaxed, Relaxed, |total| Some(total.saturating_add(bytes)));
+	}
+
+	fn untrack(&self, category: GpuCategory, bytes: u64) {
+		let _ = self.bytes[category as usize]
+			.fetch_update(Relaxed, Relaxed, |total| Some(total.saturating_sub(bytes)));
+	}
+
+	pub fn get(&self, category: GpuCategory) -> u64 {
+		self.bytes[category as usize].load(Relaxed)
+	}
+
+	pub fn total(&self) -> u64 {
+		GpuCategory::ALL
+			.iter()
+			.map(|category| self.get(*category))
+			.fold(0, u64::saturating_add)
+	}
+}
+
+/// A buffer or texture counted towards a [`GpuCategory`] for as long as it's alive.
+pub struct Tracked<T> {
+	inner: T,
+	tracker: &'static GpuMemoryTracker,
+	category: GpuCategory,
+	bytes: u64,
+}
+
+impl Tracked<Buffer> {
+	pub fn buffer(category: GpuCategory, buffer: Buffer) -> Self {
+		let bytes = buffer.size();
+		Self::new(category, buffer, bytes)
+	}
+}
+
+impl Tracked<Texture> {
+	pub fn texture(category: GpuCategory, texture: Texture) -> Self {
+		let size = texture.size();
+
+		// Depth and compressed formats don't all have a simple size per texel, 4 bytes is close enough for what we use
+		let texel_bytes = texture.format().block_copy_size(None).unwrap_or(4);
+
+		let bytes = [
+			size.width,
+			size.height,
+			size.depth_or_array_layers,
+			texture.sample_count(),
+			texel_bytes,
+		]
+		.into_iter()
+		.map(u64::from)
+		.fold(1, u64::saturating_mul);
+
+		Self::new(category, texture, bytes)
+	}
+}
+
+impl<T> Tracked<T> {
+	fn new(category: GpuCategory, inner: T, bytes: u64) -> Self {
+		Self::new_in(&GPU_MEMORY, category, inner, bytes)
+	}
+
+	/// Counts towards `tracker` rather than [`GPU_MEMORY`].
+	fn new_in(
+		tracker: &'static GpuMemoryTracker,
+		category: GpuCategory,
+		inner: T,
+		bytes: u64,
+	) -> Self {
+		tracker.track(category, bytes);
+
+		Self {
+			inner,
+			tracker,
+			category,
+			bytes,
+		}
+	}
+}
+
+impl<T> Deref for Tracked<T> {
+	type Target = T;
+
+	fn deref(&self) -> &Self::Target {
+		&self.inner
+	}
+}
+
+impl<T> Drop for Tracked<T> {
+	fn drop(&mut self) {
+		self.tracker.untrack(self.category, self.bytes);
+	}
+}
+
+/// Formats a number of bytes as MiB.
+pub struct Mebibytes(pub u64);
+
+impl Display for Mebibytes {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		write!(formatter, "{:.1} MiB", self.0 as f64 / (1024.0 * 1024.0))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn counters_saturate() {
+		let tracker = GpuMemoryTracker::new();
+
+		tracker.track(GpuCategory::Textures, 10);
+		tracker.untrack(GpuCategory::Textures, 20);
+		assert_eq!(tracker.get(GpuCategory::Textures), 0);
+
+		tracker.track(GpuCategory::Textures, u64::MAX - 1);
+		tracker.track(GpuCategory::Textures, 2);
+		assert_eq!(tracker.get(GpuCategory::Textures), u64::MAX);
+
+		// So does the total
+		tracker.track(GpuCategory::ChunkMeshes, 5);
+		assert_eq!(tracker.total(), u64::MAX);
+	}
+
+	#[test]
+	fn total_adds_up_every_category() {
+		let tracker = GpuMemoryTracker::new();
+
+		tracker.track(GpuCategory::ChunkMeshes, 1);
+		tracker.track(GpuCategory::Structures, 20);
+		tracker.track(GpuCategory::Textures, 300);
+
+		assert_eq!(tracker.get(GpuCategory::Structures), 20);
+		assert_eq!(tracker.total(), 321);
+	}
+
+	#[test]
+	fn tracked_values_count_while_alive() {
+		static TRACKER: GpuMemoryTracker = GpuMemoryTracker::new();
+
+		let first = Tracked::new_in(&TRACKER, GpuCategory::Structures, "first", 1024);
+		let second = Tracked::new_in(&TRACKER, GpuCategory::Structures, "second", 2048);
+		assert_eq!(TRACKER.get(GpuCategory::Structures), 3072);
+		assert_eq!(*first, "first");
+
+		drop(first);
+		assert_eq!(TRACKER.get(GpuCategory::Structures), 2048);
+
+		drop(second);
+		assert_eq!(TRACKER.total(), 0);
+	}
+
+	#[test]
+	fn mebibytes_are_formatted_to_one_place() {
+		assert_eq!(Mebibytes(0).to_string(), "0.0 MiB");
+		assert_eq!(Mebibytes(1024 * 1024).to_string(), "1.0 MiB");
+		assert_eq!(Mebibytes(1536 * 1024).to_string(), "1.5 MiB");
+	}
+}
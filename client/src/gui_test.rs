@@ -4,10 +4,22 @@ use crate::{
 	client::{AnyState, State},
 	ClArgs,
 };
-use egui::{Align2, Context, Window};
+use egui::{Align2, Context, RichText, Window};
+use log::warn;
 
 #[derive(Default)]
-pub struct GuiTest {}
+pub struct GuiTest {
+	/// Cycles through font sizes every frame to make sure egui textures are being freed.
+	texture_stress_test: bool,
+	font_size: f32,
+	/// Whether too many textures have been warned about, so that it's only logged once rather than every frame.
+	warned: bool,
+}
+
+impl GuiTest {
+	/// Fonts at every size share an atlas, so a handful of textures is plenty, any more and something is leaking.
+	const MAX_TEXTURES: usize = 16;
+}
 
 impl State for GuiTest {
 	fn tick(&mut self) -> Option<AnyState> {
@@ -23,6 +35,65 @@ impl State for GuiTest {
 			.max_width(400.0)
 			.show(context, |window| {
 				window.label("Hello, World!\n\nThis is an experimental space for designing new UIs without having to worry about game state straight away, it is only available in debug builds, and is accessible through the --gui-test command line flag.");
+
+				window.separator();
+
+				window.checkbox(&mut self.texture_stress_test, "Texture Stress Test");
+
+				if self.texture_stress_test {
+					self.font_size = (self.font_size + 0.5) % 64.0;
+					window.label(RichText::new("The quick brown fox").size(8.0 + self.font_size));
+				}
+
+				let textures = context.tex_manager().read().num_allocated();
+				window.label(format!("Allocated Textures: {textures}"));
+
+				if textures > Self::MAX_TEXTURES && !self.warned {
+					warn!("{textures} egui textures are allocated, textures are probably being leaked");
+					self.warned = true;
+				}
+			});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::headless::Headless;
+	use clap::Parser;
+
+	#[test]
+	fn texture_stress_test_doesnt_leak() {
+		let cl_args = ClArgs::parse_from(["solarscape-client"]);
+
+		let mut headless = Headless::new();
+		let mut gui_test = GuiTest {
+			texture_stress_test: true,
+			..GuiTest::default()
+		};
+
+		let mut allocated = 0;
+
+		// Twice through every font size
+		for _ in 0..256 {
+			let mut textures = 0;
+
+			headless.run(|context| {
+				gui_test.draw_ui(&cl_args, context);
+				textures = context.tex_manager().read().num_allocated();
 			});
+
+			assert!(
+				textures <= GuiTest::MAX_TEXTURES,
+				"{textures} egui textures are allocated, textures are probably being leaked"
+			);
+			allocated = allocated.max(textures);
+		}
+
+		// The font atlas at least, otherwise nothing was counted
+		assert!(allocated > 0);
+
+		assert!(headless.find_text("The quick brown fox").is_some());
+		assert!(!gui_test.warned);
 	}
 }
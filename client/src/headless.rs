@@ -0,0 +1,121 @@
+use egui::{
+	epaint::{ClippedShape, Shape},
+	Context, Event, FullOutput, Id, Key, LayerId, Modifiers, Order, PointerButton, Pos2, RawInput,
+	Rect, Vec2,
+};
+
+/// Runs UI without a window or renderer, so that a state's `draw_ui` can be driven with made up input and what it
+/// showed checked afterwards. Input is queued with [`Headless::click`], [`Headless::press`] and [`Headless::type_text`],
+/// and given to the next [`Headless::run`].
+///
+/// egui works out what the pointer is over from the previous frame's layout, so run a frame before clicking anything.
+/// Windows are laid out invisibly on their first frame, so run two before looking for anything in a new window.
+pub struct Headless {
+	context: Context,
+	events: Vec<Event>,
+	/// Seconds since the first frame, advanced by [`Headless::FRAME_TIME`] every frame.
+	time: f64,
+	output: FullOutput,
+}
+
+impl Headless {
+	pub const SCREEN_SIZE: Vec2 = Vec2::new(1280.0, 720.0);
+	const FRAME_TIME: f64 = 1.0 / 60.0;
+
+	pub fn new() -> Self {
+		Self {
+			context: Context::default(),
+			events: vec![],
+			time: 0.0,
+			output: FullOutput::default(),
+		}
+	}
+
+	/// Queues moving the pointer to `position` and clicking there with the primary button.
+	pub fn click(&mut self, position: Pos2) -> &mut Self {
+		self.events.push(Event::PointerMoved(position));
+
+		for pressed in [true, false] {
+			self.events.push(Event::PointerButton {
+				pos: position,
+				button: PointerButton::Primary,
+				pressed,
+				modifiers: Modifiers::NONE,
+			});
+		}
+
+		self
+	}
+
+	/// Queues pressing and releasing `key`.
+	pub fn press(&mut self, key: Key) -> &mut Self {
+		for pressed in [true, false] {
+			self.events.push(Event::Key {
+				key,
+				physical_key: None,
+				pressed,
+				repeat: false,
+				modifiers: Modifiers::NONE,
+			});
+		}
+
+		self
+	}
+
+	/// Queues typing `text` into whatever has focus.
+	pub fn type_text(&mut self, text: &str) -> &mut Self {
+		self.events.push(Event::Text(text.into()));
+		self
+	}
+
+	/// Runs one frame of `draw` with the queued input.
+	pub fn run(&mut self, draw: impl FnMut(&Context)) -> &FullOutput {
+		let input = RawInput {
+			screen_rect: Some(Rect::from_min_size(Pos2::ZERO, Self::SCREEN_SIZE)),
+			time: Some(self.time),
+			predicted_dt: Self::FRAME_TIME as f32,
+			events: self.events.drain(..).collect(),
+			..RawInput::default()
+		};
+
+		self.time += Self::FRAME_TIME;
+		self.output = self.context.run(input, draw);
+		&self.output
+	}
+
+	/// Whether the window with `id` was shown in the last frame, `id` being what was given to [`egui::Window::id`].
+	pub fn window_shown(&self, id: Id) -> bool {
+		self.context
+			.memory(|memory| memory.areas().is_visible(&LayerId::new(Order::Middle, id)))
+	}
+
+	/// Where the last frame drew `text`, if it drew it. Only whole pieces of text match, such as a button's label, though
+	/// whitespace around them is ignored.
+	pub fn find_text(&self, text: &str) -> Option<Rect> {
+		fn find(shape: &Shape, text: &str) -> Option<Rect> {
+			match shape {
+				Shape::Text(shape) if shape.galley.text().trim() == text => {
+					Some(shape.visual_bounding_rect())
+				}
+				Shape::Vec(shapes) => shapes.iter().find_map(|shape| find(shape, text)),
+				_ => None,
+			}
+		}
+
+		self.output
+			.shapes
+			.iter()
+			.find_map(|ClippedShape { shape, .. }| find(shape, text))
+	}
+
+	/// Queues clicking on `text` as drawn in the last frame, such as to press a button. Returns `false` without queuing
+	/// anything if it wasn't drawn.
+	pub fn click_text(&mut self, text: &str) -> bool {
+		let Some(rect) = self.find_text(text) else {
+			return false;
+		};
+
+		self.click(rect.center());
+		true
+	}
+}
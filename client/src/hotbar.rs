@@ -0,0 +1,171 @@
+use crate::config;
+use serde::{Deserialize, Serialize};
+use solarscape_shared::data::world::BlockType;
+
+/// The blocks the player can quickly switch between for placing. The arrangement is kept between sessions, which slot
+/// is selected isn't.
+#[derive(Deserialize, Serialize)]
+pub struct Hotbar {
+	slots: [Option<BlockType>; Self::SLOTS],
+
+	#[serde(skip)]
+	selected: usize,
+
+	/// Scrolling that hasn't added up to a whole slot yet, touchpads scroll a little at a time.
+	#[serde(skip)]
+	scroll_remainder: f32,
+}
+
+impl Hotbar {
+	/// One for each number key.
+	pub const SLOTS: usize = 9;
+	const FILE: &'static str = "hotbar.json";
+
+	/// Loads the arrangement, starting with every placeable block if there isn't one or it can't be read.
+	pub fn load() -> Self {
+		config::load(Self::FILE)
+	}
+
+	pub fn save(&self) {
+		config::save(Self::FILE, self);
+	}
+
+	pub fn slots(&self) -> &[Option<BlockType>; Self::SLOTS] {
+		&self.slots
+	}
+
+	pub fn selected_slot(&self) -> usize {
+		self.selected
+	}
+
+	/// The block in the selected slot, [`None`] if it's empty.
+	pub fn selected(&self) -> Option<BlockType> {
+		self.slots[self.selected]
+	}
+
+	/// Selects `slot`, ignored if there's no such slot.
+	pub fn select(&mut self, slot: usize) {
+		if slot < Self::SLOTS {
+			self.selected = slot;
+		}
+	}
+
+	/// Moves the selection by `lines` of scrolling, scrolling down moves right. Wraps around at either end.
+	pub fn scroll(&mut self, lines: f32) {
+		self.scroll_remainder -= lines;
+
+		let steps = self.scroll_remainder.trunc();
+		self.scroll_remainder -= steps;
+
+		self.selected =
+			(self.selected as i64 + steps as i64).rem_euclid(Self::SLOTS as i64) as usize;
+	}
+
+	/// Swaps two slots, the selection stays on the same slot rather than following the block.
+	pub fn swap(&mut self, a: usize, b: usize) {
+		if a < Self::SLOTS && b < Self::SLOTS {
+			self.slots.swap(a, b);
+		}
+	}
+}
+
+impl Default for Hotbar {
+	fn default() -> Self {
+		let mut slots = [None; Self::SLOTS];
+
+		for (slot, block) in slots.iter_mut().zip(BlockType::ALL) {
+			*slot = Some(*block);
+		}
+
+		Self {
+			slots,
+			selected: 0,
+			scroll_remainder: 0.0,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn starts_with_every_placeable_block() {
+		let hotbar = Hotbar::default();
+
+		for (slot, block) in hotbar.slots().iter().enumerate() {
+			assert_eq!(*block, BlockType::ALL.get(slot).copied());
+		}
+
+		assert_eq!(hotbar.selected_slot(), 0);
+		assert_eq!(hotbar.selected(), Some(BlockType::ALL[0]));
+	}
+
+	#[test]
+	fn selecting_missing_slots_is_ignored() {
+		let mut hotbar = Hotbar::default();
+
+		hotbar.select(3);
+		hotbar.select(Hotbar::SLOTS);
+
+		assert_eq!(hotbar.selected_slot(), 3);
+	}
+
+	#[test]
+	fn scrolling_wraps_around() {
+		let mut hotbar = Hotbar::default();
+
+		hotbar.scroll(1.0);
+		assert_eq!(hotbar.selected_slot(), Hotbar::SLOTS - 1);
+
+		hotbar.scroll(-2.0);
+		assert_eq!(hotbar.selected_slot(), 1);
+
+		hotbar.scroll(-(Hotbar::SLOTS as f32));
+		assert_eq!(hotbar.selected_slot(), 1);
+	}
+
+	#[test]
+	fn partial_scrolling_adds_up() {
+		let mut hotbar = Hotbar::default();
+
+		for _ in 0..3 {
+			hotbar.scroll(-0.3);
+		}
+		assert_eq!(hotbar.selected_slot(), 0);
+
+		hotbar.scroll(-0.3);
+		assert_eq!(hotbar.selected_slot(), 1);
+
+		// Scrolling back cancels out what's left over instead of moving straight back
+		hotbar.scroll(0.1);
+		assert_eq!(hotbar.selected_slot(), 1);
+	}
+
+	#[test]
+	fn swapping_keeps_the_selected_slot() {
+		let mut hotbar = Hotbar::default();
+		let slots = *hotbar.slots();
+
+		hotbar.select(0);
+		hotbar.swap(0, 8);
+		hotbar.swap(0, Hotbar::SLOTS);
+
+		assert_eq!(hotbar.selected_slot(), 0);
+		assert_eq!(hotbar.slots()[0], slots[8]);
+		assert_eq!(hotbar.slots()[8], slots[0]);
+	}
+
+	#[test]
+	fn only_the_arrangement_is_kept() {
+		let mut hotbar = Hotbar::default();
+		hotbar.swap(0, 4);
+		hotbar.select(2);
+
+		let json = serde_json::to_string(&hotbar).unwrap();
+		let loaded: Hotbar = serde_json::from_str(&json).unwrap();
+
+		assert_eq!(loaded.slots(), hotbar.slots());
+		assert_eq!(loaded.selected_slot(), 0);
+	}
+}
@@ -0,0 +1,211 @@
+use crate::{surface::terrain_cell, world::Sector};
+use egui::{Area, Context, Frame, Id as EguiId, Order, RichText, TextStyle};
+use nalgebra::Vector3;
+use rapier3d::geometry::Ray;
+use solarscape_shared::{
+	data::{
+		world::{BlockType, CellCoordinates, Material},
+		Id,
+	},
+	physics::{ColliderOwner, CollisionGroup, QueryMask, RayHit},
+};
+use std::fmt::Write;
+use winit::keyboard::KeyCode;
+
+/// What the player is aiming at, shown next to the crosshair while [`Inspection::KEY`] is held. This is a debugging
+/// aid, so like the debug text it isn't translated.
+pub enum Inspection {
+	Terrain {
+		voxject: String,
+		cell: CellCoordinates,
+		/// Material and density of each of [`CellCoordinates::corners`], [`None`] if that chunk isn't loaded.
+		corners: [Option<(Material, f32)>; 8],
+	},
+	StructureBlock {
+		structure: Id,
+		owner: Id,
+		block: BlockType,
+		position: Vector3<i16>,
+		mass: f32,
+	},
+}
+
+impl Inspection {
+	pub const KEY: KeyCode = KeyCode::F3;
+
+	/// Further than blocks can be placed, inspecting is for looking around.
+	const REACH: f32 = 64.0;
+
+	/// How far past the hit point to look, so that the hit is inside of whatever was hit rather than on it's surface.
+	pub const NUDGE: f32 = 0.001;
+
+	/// Inspects whatever is closest along the player's view, if anything is in reach.
+	pub fn find(sector: &Sector) -> Option<Self> {
+		let origin = sector.player.location.position;
+		let direction = sector
+			.player
+			.location
+			.rotation
+			.inverse_transform_vector(&-Vector3::z());
+
+		let RayHit { hit, distance, .. } = sector.physics.cast_ray(
+			&Ray::new(origin, direction),
+			Self::REACH,
+			QueryMask::new(&[CollisionGroup::Terrain, CollisionGroup::Structure]),
+		)?;
+
+		let collider = sector.physics.get_collider(hit.collider)?;
+		let coordinates = match hit.owner? {
+			ColliderOwner::StructureBlock(id, position) => {
+				let structure = sector
+					.structures
+					.iter()
+					.find(|structure| structure.id == id)?;
+
+				return Some(Self::StructureBlock {
+					structure: id,
+					owner: structure.owner,
+					block: structure.get_block(&position)?.typ,
+					position,
+					mass: sector
+						.physics
+						.get_rigid_body(*structure.rigid_body)
+						.map_or(0.0, |rigid_body| rigid_body.mass()),
+				});
+			}
+			ColliderOwner::Chunk(coordinates) => coordinates,
+			ColliderOwner::Player(_) => return None,
+		};
+
+		let hit = origin + direction * (distance + Self::NUDGE);
+		let cell = terrain_cell(collider, coordinates, &hit);
+
+		let corners = cell.corners().map(|corner| {
+			let chunk = sector.chunks.get(&corner.chunk)?;
+			let index = corner.index();
+			Some((chunk.materials[index], chunk.densities[index]))
+		});
+
+		Some(Self::Terrain {
+			voxject: sector.voxjects.get(&coordinates.voxject).map_or_else(
+				|| coordinates.voxject.to_string(),
+				|voxject| voxject.name.to_string(),
+			),
+			cell,
+			corners,
+		})
+	}
+
+	fn describe(&self) -> String {
+		let mut text = String::new();
+
+		match self {
+			Self::Terrain {
+				voxject,
+				cell,
+				corners,
+			} => {
+				writeln!(text, "Voxject: {voxject}").expect("should be able to write to string");
+				writeln!(text, "Chunk: {}", cell.chunk).expect("should be able to write to string");
+				writeln!(text, "Level: {}", cell.chunk.level)
+					.expect("should be able to write to string");
+				writeln!(
+					text,
+					"Cell: {}, {}, {} (index {})",
+					cell.cell.x,
+					cell.cell.y,
+					cell.cell.z,
+					cell.index()
+				)
+				.expect("should be able to write to string");
+
+				for (corner, data) in cell.corners().iter().zip(corners) {
+					let offset = corner.voxject_cell() - cell.voxject_cell();
+
+					match data {
+						Some((material, density)) => writeln!(
+							text,
+							"  +{}{}{}: {material:?} {density:.3}",
+							offset.x, offset.y, offset.z
+						),
+						None => {
+							writeln!(text, "  +{}{}{}: not loaded", offset.x, offset.y, offset.z)
+						}
+					}
+					.expect("should be able to write to string");
+				}
+			}
+			Self::StructureBlock {
+				structure,
+				owner,
+				block,
+				position,
+				mass,
+			} => {
+				writeln!(text, "Structure: {structure}")
+					.expect("should be able to write to string");
+				writeln!(text, "Owner: {owner}").expect("should be able to write to string");
+				writeln!(text, "Block: {}", block.display_name())
+					.expect("should be able to write to string");
+				writeln!(
+					text,
+					"Position: {}, {}, {}",
+					position.x, position.y, position.z
+				)
+				.expect("should be able to write to string");
+				writeln!(text, "Mass: {mass:.1}").expect("should be able to write to string");
+			}
+		}
+
+		text
+	}
+
+	/// Draws the panel just below and to the right of the crosshair.
+	pub fn draw(&self, context: &Context) {
+		Area::new(EguiId::new("inspect"))
+			.order(Order::Tooltip)
+			.fixed_pos(context.screen_rect().center() + egui::vec2(16.0, 16.0))
+			.interactable(false)
+			.show(context, |area| {
+				Frame::popup(area.style()).show(area, |frame| {
+					frame.label(
+						RichText::new(self.describe().trim_end()).text_style(TextStyle::Monospace),
+					);
+				});
+			});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::vector;
+	use solarscape_shared::data::world::Level;
+
+	#[test]
+	fn terrain_lists_each_corner() {
+		let cell = CellCoordinates::from_voxject_cell(
+			"1".parse().unwrap(),
+			vector![15, 2, 3],
+			Level::new(0),
+		);
+		let mut corners = [Some((Material::Stone, 0.5)); 8];
+		corners[7] = None;
+
+		let text = Inspection::Terrain {
+			voxject: "Test".into(),
+			cell,
+			corners,
+		}
+		.describe();
+		let lines: Vec<_> = text.lines().collect();
+
+		assert_eq!(lines[0], "Voxject: Test");
+		assert_eq!(lines[2], "Level: 0");
+		assert_eq!(lines[3], "Cell: 15, 2, 3 (index 3875)");
+		assert_eq!(lines[4], "  +000: Stone 0.500");
+		assert_eq!(lines[5], "  +001: Stone 0.500");
+		assert_eq!(lines[11], "  +111: not loaded");
+		assert_eq!(lines.len(), 12);
+	}
+}
@@ -0,0 +1,125 @@
+use crate::tr;
+use egui::{Align::Min, Align2, Context, Layout, Ui, Window};
+use solarscape_shared::message::clientbound::{InventorySlot, SyncInventory};
+use winit::keyboard::KeyCode;
+
+/// The player's inventory, opened with [`InventoryWindow::KEY`] and closed with Escape or it's close button.
+#[derive(Default)]
+pub struct InventoryWindow {
+	pub open: bool,
+}
+
+impl InventoryWindow {
+	pub const KEY: KeyCode = KeyCode::Tab;
+
+	/// Closes the window if `key` is Escape. Only called while the window is open, other keys are ignored meanwhile.
+	pub fn key_released(&mut self, key: KeyCode) {
+		if key == KeyCode::Escape {
+			self.open = false;
+		}
+	}
+
+	/// Shows the window while it's open, `inventory` being [`None`] until the sector has sent it. Returns whether the
+	/// give test item button was clicked.
+	pub fn draw(&mut self, context: &Context, inventory: Option<&SyncInventory>) -> bool {
+		let mut give_test_item = false;
+
+		Window::new(tr!("inventory.title"))
+			.id(egui::Id::new("inventory"))
+			.anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+			.auto_sized()
+			.collapsible(false)
+			.hscroll(false)
+			.max_width(512.0)
+			.open(&mut self.open)
+			.resizable(false)
+			.show(context, |window| {
+				give_test_item = window.button(tr!("inventory.give_test_item")).clicked();
+
+				match inventory {
+					Some(inventory) => draw_slots(window, inventory),
+					None => {
+						window.label(tr!("inventory.loading"));
+					}
+				}
+			});
+
+		give_test_item
+	}
+}
+
+/// Capacity and contents of `inventory`, in a grid.
+pub fn draw_slots(window: &mut Ui, inventory: &SyncInventory) {
+	window.label(tr!(
+		"inventory.capacity",
+		used = inventory.used(),
+		max_quantity = inventory.capacity.max_quantity,
+		stacks = inventory.slots.len(),
+		max_stacks = inventory.capacity.max_stacks,
+	));
+
+	window.columns(4, |columns| {
+		let mut column = 0;
+
+		for InventorySlot { item, quantity } in &inventory.slots {
+			let next_column = {
+				let result = column;
+				column += 1;
+				if column == columns.len() {
+					column = 0;
+				}
+				result
+			};
+
+			columns[next_column].group(|group| {
+				group.with_layout(Layout::top_down(Min), |group| {
+					group.label(format!("{} ({})", item.display_name(), quantity));
+					group.label(item.description());
+				});
+			});
+		}
+	});
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::headless::Headless;
+
+	#[test]
+	fn escape_closes_inventory() {
+		let mut headless = Headless::new();
+		let mut window = InventoryWindow { open: true };
+
+		for _ in 0..2 {
+			headless.run(|context| {
+				window.draw(context, None);
+			});
+		}
+
+		assert!(headless.window_shown(egui::Id::new("inventory")));
+		assert!(headless.find_text(&tr!("inventory.loading")).is_some());
+
+		window.key_released(KeyCode::Escape);
+
+		// Windows fade out once closed, which takes a few frames
+		for _ in 0..10 {
+			headless.run(|context| {
+				window.draw(context, None);
+			});
+		}
+
+		assert!(!window.open);
+		assert!(!headless.window_shown(egui::Id::new("inventory")));
+	}
+
+	#[test]
+	fn other_keys_leave_inventory_open() {
+		let mut window = InventoryWindow { open: true };
+
+		window.key_released(InventoryWindow::KEY);
+		window.key_released(KeyCode::KeyW);
+
+		assert!(window.open);
+	}
+}
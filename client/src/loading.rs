@@ -0,0 +1,108 @@
+use crate::tr;
+use egui::{Align2, Area, Context, Frame, Id};
+use solarscape_shared::data::world::{ChunkCoordinates, Level};
+
+/// How much of what's around the player has arrived since joining. The sector only sends what it is up front, so the
+/// world fills in over the first few seconds, and this is shown until the sector says it's sent everything nearby
+/// with [`JoinComplete`].
+///
+/// [`JoinComplete`]: solarscape_shared::message::clientbound::JoinComplete
+#[derive(Default)]
+pub struct Loading {
+	/// Level 0 chunks received, higher levels keep arriving long after joining so aren't counted.
+	chunks: u32,
+	structures: u32,
+	complete: bool,
+}
+
+impl Loading {
+	pub fn chunk_received(&mut self, coordinates: &ChunkCoordinates) {
+		if coordinates.level == Level::new(0) {
+			self.chunks += 1;
+		}
+	}
+
+	pub fn structure_received(&mut self) {
+		self.structures += 1;
+	}
+
+	pub fn complete(&mut self) {
+		self.complete = true;
+	}
+
+	/// Shows what has arrived so far at the bottom of the screen, nothing once complete.
+	pub fn draw(&self, context: &Context) {
+		if self.complete {
+			return;
+		}
+
+		let text = tr!(
+			"loading.progress",
+			chunks = self.chunks,
+			structures = self.structures
+		);
+
+		Area::new(Id::new("loading"))
+			.anchor(Align2::CENTER_BOTTOM, [0.0, -96.0])
+			.interactable(false)
+			.show(context, |area| {
+				Frame::popup(area.style()).show(area, |frame| {
+					frame.horizontal(|row| {
+						row.spinner();
+						row.label(text);
+					});
+				});
+			});
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::headless::Headless;
+	use nalgebra::Vector3;
+	use solarscape_shared::data::Id;
+
+	#[test]
+	fn only_level_0_chunks_are_counted() {
+		let voxject = Id::new();
+		let mut loading = Loading::default();
+
+		loading.chunk_received(&ChunkCoordinates::new(
+			voxject,
+			Vector3::zeros(),
+			Level::new(0),
+		));
+		loading.chunk_received(&ChunkCoordinates::new(
+			voxject,
+			Vector3::zeros(),
+			Level::new(1),
+		));
+		loading.chunk_received(&ChunkCoordinates::new(voxject, Vector3::x(), Level::new(0)));
+		loading.structure_received();
+
+		assert_eq!(loading.chunks, 2);
+		assert_eq!(loading.structures, 1);
+	}
+
+	#[test]
+	fn progress_is_shown_until_complete() {
+		let mut headless = Headless::new();
+		let mut loading = Loading::default();
+		loading.structure_received();
+		loading.structure_received();
+
+		let text = tr!("loading.progress", chunks = 0, structures = 2);
+
+		// Areas are sized on their first frame, and only shown from the next
+		for _ in 0..2 {
+			headless.run(|context| loading.draw(context));
+		}
+		assert!(headless.find_text(&text).is_some());
+
+		loading.complete();
+
+		headless.run(|context| loading.draw(context));
+		assert!(headless.find_text(&text).is_none());
+	}
+}
@@ -0,0 +1,426 @@
+use log::{debug, info, warn};
+use solarscape_shared::message::clientbound::LocalizedText;
+use std::{
+	collections::{HashMap, HashSet},
+	fmt::Display,
+	fs::{read_dir, read_to_string},
+	path::Path,
+	sync::{LazyLock, Mutex, RwLock},
+};
+use thiserror::Error;
+
+/// Looks up a UI string in the selected language, with optional `{name}` arguments.
+///
+/// ```ignore
+/// tr!("login.title");
+/// tr!("disconnected.attempt", attempt = 1, max = 6);
+/// ```
+#[macro_export]
+macro_rules! tr {
+	($key:literal) => {
+		$crate::localization::translate($key, &[])
+	};
+	($key:literal, $($name:ident = $value:expr),+ $(,)?) => {
+		$crate::localization::translate(
+			$key,
+			&[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),+],
+		)
+	};
+}
+
+/// Languages built into the client, English must be first as it's the fallback for missing strings.
+const BUILT_IN: &[(&str, &str)] = &[
+	("en", include_str!("resources/languages/en.lang")),
+	("de", include_str!("resources/languages/de.lang")),
+];
+
+pub struct Language {
+	pub code: Box<str>,
+	strings: HashMap<Box<str>, Box<str>>,
+}
+
+impl Language {
+	/// Name of the language in that language, for the language selection.
+	pub fn name(&self) -> &str {
+		self.strings
+			.get("language.name")
+			.map_or(&*self.code, |name| &**name)
+	}
+}
+
+struct Localization {
+	/// Always contains English first.
+	languages: Vec<Language>,
+	selected: usize,
+}
+
+static LOCALIZATION: LazyLock<RwLock<Localization>> = LazyLock::new(|| {
+	let languages: Vec<_> = BUILT_IN
+		.iter()
+		.map(|(code, source)| Language {
+			code: (*code).into(),
+			strings: parse(source).expect("built in language files should be valid"),
+		})
+		.collect();
+
+	// The server's keys aren't visible to anything checking tr! calls, so check them here instead
+	#[cfg(debug)]
+	for key in LocalizedText::KEYS {
+		if !languages[0].strings.contains_key(*key) {
+			log::error!("English has no string for {key}, which the server sends");
+		}
+	}
+
+	RwLock::new(Localization {
+		languages,
+		selected: 0,
+	})
+});
+
+/// Language code and key.
+type MissingString = (Box<str>, Box<str>);
+
+// Missing strings are looked up every frame, so only complain about each one once
+static REPORTED_MISSING: LazyLock<Mutex<HashSet<MissingString>>> = LazyLock::new(Mutex::default);
+
+/// Loads every `<code>.lang` file in `directory`, a file with the same code as a built in language replaces it.
+pub fn load_directory(directory: &Path) {
+	let entries = match read_dir(directory) {
+		Ok(entries) => entries,
+		Err(error) => {
+			warn!(
+				"Unable to read language directory {}: {error}",
+				directory.display()
+			);
+			return;
+		}
+	};
+
+	let mut localization = LOCALIZATION.write().expect("should not be poisoned");
+
+	for path in entries.flatten().map(|entry| entry.path()) {
+		if path.extension().is_none_or(|extension| extension != "lang") {
+			continue;
+		}
+
+		let Some(code) = path.file_stem().and_then(|code| code.to_str()) else {
+			continue;
+		};
+
+		let strings = match read_to_string(&path)
+			.map_err(ParseError::from)
+			.and_then(|source| parse(&source))
+		{
+			Ok(strings) => strings,
+			Err(error) => {
+				warn!("Unable to load language file {}: {error}", path.display());
+				continue;
+			}
+		};
+
+		info!("Loaded language {code} from {}", path.display());
+
+		let language = Language {
+			code: code.into(),
+			strings,
+		};
+
+		match localization
+			.languages
+			.iter_mut()
+			.find(|existing| *existing.code == *code)
+		{
+			Some(existing) => *existing = language,
+			None => localization.languages.push(language),
+		}
+	}
+}
+
+/// Selects the language with `code`, returns `false` if there isn't one.
+pub fn select(code: &str) -> bool {
+	let mut localization = LOCALIZATION.write().expect("should not be poisoned");
+
+	match localization
+		.languages
+		.iter()
+		.position(|language| *language.code == *code)
+	{
+		Some(index) => {
+			localization.selected = index;
+			true
+		}
+		None => false,
+	}
+}
+
+pub fn selected() -> Box<str> {
+	let localization = LOCALIZATION.read().expect("should not be poisoned");
+	localization.languages[localization.selected].code.clone()
+}
+
+/// Codes and names of every available language.
+pub fn languages() -> Vec<(Box<str>, Box<str>)> {
+	LOCALIZATION
+		.read()
+		.expect("should not be poisoned")
+		.languages
+		.iter()
+		.map(|language| (language.code.clone(), language.name().into()))
+		.collect()
+}
+
+/// Use [`tr!`] instead.
+pub fn translate(key: &str, arguments: &[(&str, &dyn Display)]) -> String {
+	let localization = LOCALIZATION.read().expect("should not be poisoned");
+
+	match localization.template(key) {
+		Some(template) => interpolate(template, arguments),
+		None => key.into(),
+	}
+}
+
+/// Translates text sent by the server. The server may be newer than the client and send a key it doesn't have, so
+/// rather than just the key, a missing key is shown with it's params, like `server.example (name: value)`.
+pub fn translate_server(text: &LocalizedText) -> String {
+	let localization = LOCALIZATION.read().expect("should not be poisoned");
+
+	let arguments: Vec<(&str, &dyn Display)> = text
+		.params
+		.iter()
+		.map(|(name, value)| (&**name, value as &dyn Display))
+		.collect();
+
+	if let Some(template) = localization.template(&text.key) {
+		return interpolate(template, &arguments);
+	}
+
+	match arguments.is_empty() {
+		true => text.key.clone().into(),
+		false => format!(
+			"{} ({})",
+			text.key,
+			arguments
+				.iter()
+				.map(|(name, value)| format!("{name}: {value}"))
+				.collect::<Vec<_>>()
+				.join(", ")
+		),
+	}
+}
+
+impl Localization {
+	/// The selected language's string for `key`, or English's if the selected language doesn't have it.
+	fn template(&self, key: &str) -> Option<&str> {
+		let language = &self.languages[self.selected];
+
+		match language.strings.get(key) {
+			Some(template) => Some(template),
+			None => {
+				let newly_missing = REPORTED_MISSING
+					.lock()
+					.expect("should not be poisoned")
+					.insert((language.code.clone(), key.into()));
+
+				if newly_missing {
+					debug!(
+						"Language {} has no string {key}, falling back to English",
+						language.code
+					);
+				}
+
+				self.languages[0]
+					.strings
+					.get(key)
+					.map(|template| &**template)
+			}
+		}
+	}
+}
+
+/// Parses a language file. Each line is either blank, a `#` comment, or `key = value`. `\n` in a value is a new line.
+pub fn parse(source: &str) -> Result<HashMap<Box<str>, Box<str>>, ParseError> {
+	let mut strings = HashMap::new();
+
+	for (index, line) in source.lines().enumerate() {
+		let line = line.trim();
+
+		if line.is_empty() || line.starts_with('#') {
+			continue;
+		}
+
+		let Some((key, value)) = line.split_once('=') else {
+			return Err(ParseError::MissingEquals { line: index + 1 });
+		};
+
+		let key = key.trim();
+
+		if key.is_empty() {
+			return Err(ParseError::EmptyKey { line: index + 1 });
+		}
+
+		let value = value.trim().replace("\\n", "\n");
+
+		if strings.insert(key.into(), value.into()).is_some() {
+			return Err(ParseError::DuplicateKey {
+				line: index + 1,
+				key: key.into(),
+			});
+		}
+	}
+
+	Ok(strings)
+}
+
+/// Replaces every `{name}` in `template` with the matching argument. Unknown names are left as they are, and `{{` is
+/// a literal `{`.
+pub fn interpolate(template: &str, arguments: &[(&str, &dyn Display)]) -> String {
+	let mut output = String::with_capacity(template.len());
+	let mut rest = template;
+
+	while let Some(start) = rest.find('{') {
+		output.push_str(&rest[..start]);
+		rest = &rest[start..];
+
+		if let Some(after) = rest.strip_prefix("{{") {
+			output.push('{');
+			rest = after;
+			continue;
+		}
+
+		let argument = rest.find('}').and_then(|end| {
+			let name = &rest[1..end];
+
+			arguments
+				.iter()
+				.find(|(argument, _)| *argument == name)
+				.map(|(_, value)| (end, value))
+		});
+
+		match argument {
+			Some((end, value)) => {
+				output.push_str(&value.to_string());
+				rest = &rest[end + 1..];
+			}
+			None => {
+				output.push('{');
+				rest = &rest[1..];
+			}
+		}
+	}
+
+	output.push_str(rest);
+	output
+}
+
+#[derive(Debug, Error)]
+pub enum ParseError {
+	#[error("line {line} is not a `key = value` pair")]
+	MissingEquals { line: usize },
+
+	#[error("line {line} has an empty key")]
+	EmptyKey { line: usize },
+
+	#[error("line {line} repeats key `{key}`")]
+	DuplicateKey { line: usize, key: Box<str> },
+
+	#[error(transparent)]
+	Io(#[from] std::io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn built_in(code: &str) -> HashMap<Box<str>, Box<str>> {
+		let (_, source) = BUILT_IN
+			.iter()
+			.find(|(built_in, _)| *built_in == code)
+			.unwrap();
+		parse(source).unwrap()
+	}
+
+	#[test]
+	fn parse_skips_comments_and_blank_lines() {
+		let strings = parse("# comment\n\n  a.b = Hello = there  \nc=Two\\nlines\n").unwrap();
+
+		assert_eq!(strings.len(), 2);
+		assert_eq!(&*strings["a.b"], "Hello = there");
+		assert_eq!(&*strings["c"], "Two\nlines");
+	}
+
+	#[test]
+	fn parse_errors_name_the_line() {
+		assert!(matches!(
+			parse("a = 1\nnonsense"),
+			Err(ParseError::MissingEquals { line: 2 })
+		));
+		assert!(matches!(
+			parse("\n\n = 1"),
+			Err(ParseError::EmptyKey { line: 3 })
+		));
+		assert!(matches!(
+			parse("a = 1\n# a = 2\na = 3"),
+			Err(ParseError::DuplicateKey { line: 3, key }) if &*key == "a"
+		));
+	}
+
+	#[test]
+	fn interpolate_fills_in_arguments() {
+		let arguments: &[(&str, &dyn Display)] = &[("name", &"Fox"), ("count", &3)];
+
+		assert_eq!(
+			interpolate("{name} has {count} {count}s", arguments),
+			"Fox has 3 3s"
+		);
+		assert_eq!(interpolate("{unknown} {name}", arguments), "{unknown} Fox");
+		assert_eq!(interpolate("{{name}} {", arguments), "{name}} {");
+		assert_eq!(interpolate("{name", arguments), "{name");
+		assert_eq!(interpolate("", arguments), "");
+	}
+
+	#[test]
+	fn built_in_languages_are_valid() {
+		let english = built_in("en");
+
+		for (code, source) in BUILT_IN {
+			let strings = parse(source).unwrap();
+			assert!(strings.contains_key("language.name"), "{code} has no name");
+
+			for key in strings.keys() {
+				assert!(
+					english.contains_key(key),
+					"{code} has {key}, English doesn't"
+				);
+			}
+		}
+
+		for key in LocalizedText::KEYS {
+			assert!(
+				english.contains_key(*key),
+				"English has no string for {key}"
+			);
+		}
+	}
+
+	#[test]
+	fn unknown_server_keys_show_their_params() {
+		assert_eq!(
+			translate_server(&LocalizedText {
+				key: "server.from_the_future".into(),
+				params: vec![
+					("name".into(), "Fox".into()),
+					("count".into(), 2_i64.into())
+				],
+			}),
+			"server.from_the_future (name: Fox, count: 2)"
+		);
+
+		assert_eq!(
+			translate_server(&LocalizedText {
+				key: "server.from_the_future".into(),
+				params: vec![],
+			}),
+			"server.from_the_future"
+		);
+	}
+}
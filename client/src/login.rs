@@ -1,86 +1,351 @@
 use crate::{
 	client::{AnyState, State},
+	direct_connect::{DirectConnectHistory, DirectConnection},
+	palette::UiColor,
+	tr,
 	world::Sector,
 	ClArgs,
 };
+use anyhow::anyhow;
 use chacha20poly1305::{aead::AeadMutInPlace, ChaCha20Poly1305, KeyInit};
-use egui::{Align, Align2, Color32, Context, Layout, RichText, Separator, TextEdit, Vec2, Window};
+use egui::{
+	Align, Align2, CollapsingHeader, Context, Key, Layout, RichText, Separator, TextEdit, Vec2,
+	Window,
+};
+use reqwest::{Response, StatusCode, Url};
 use serde::Deserialize;
 use serde_json::from_str;
-use solarscape_shared::connection::Connection;
-use tokio::{io::AsyncWriteExt, net::TcpStream, runtime::Handle, task::JoinHandle};
+use solarscape_shared::{
+	connection::{parse_preshared_key, ClientEnd, Connection, KeepAlive},
+	message::PROTOCOL_VERSION,
+};
+use std::{
+	sync::{Arc, Mutex},
+	time::Duration,
+};
+use tokio::{
+	io::AsyncWriteExt,
+	net::{TcpStream, ToSocketAddrs},
+	runtime::Handle,
+	task::JoinHandle,
+	time::sleep,
+};
+
+/// Everything needed to connect to a sector again without asking the player to log in.
+#[derive(Clone)]
+pub struct Session {
+	pub api_endpoint: Url,
+	pub token: String,
+	pub keep_alive: KeepAlive,
+}
+
+impl Session {
+	/// Exchanges an email and password for an auth token.
+	pub async fn acquire(
+		api_endpoint: Url,
+		keep_alive: KeepAlive,
+		email: String,
+		password: String,
+	) -> Result<Self, anyhow::Error> {
+		let token = reqwest::Client::new()
+			.get(api_endpoint.to_string() + "/dev/token")
+			.query(&[("email", email), ("password", password)])
+			.send()
+			.await?
+			.text()
+			.await?;
+
+		Ok(Self {
+			api_endpoint,
+			token,
+			keep_alive,
+		})
+	}
+
+	/// Asks the gateway for a sector to connect to and connects to it. Connection keys are single use, so this must be
+	/// called again for every connection attempt. If the sector is full this waits in it's queue, keeping `queue` up to
+	/// date, for as long as it takes.
+	pub async fn connect(
+		&self,
+		queue: &QueueStatus,
+	) -> Result<Connection<ClientEnd>, anyhow::Error> {
+		let client = reqwest::Client::new();
+
+		let response = loop {
+			let response = client
+				.get(self.api_endpoint.to_string() + "/dev/connect")
+				.query(&[("queue", true)])
+				.header("Authorization", &self.token)
+				.send()
+				.await?;
+
+			if response.status() != StatusCode::ACCEPTED {
+				break response;
+			}
+
+			let position: QueuePosition = from_str(&response.text().await?)?;
+			queue.set(Some(position));
+
+			// The gateway drops players from the queue if they don't ask again in time, so don't wait any longer
+			sleep(Duration::from_secs(position.retry_after.clamp(1, 10) as u64)).await;
+		};
+
+		queue.set(None);
+
+		#[derive(Deserialize)]
+		struct ConnectionInfo {
+			key: [u8; 32],
+			address: String,
+		}
+
+		let details: ConnectionInfo = from_str(&response_text(response).await?)?;
+
+		handshake(details.address, details.key, self.keep_alive).await
+	}
+
+	/// Lists the player's sessions, most recently used first.
+	pub async fn list_sessions(&self) -> Result<Vec<SessionInfo>, anyhow::Error> {
+		let response = reqwest::Client::new()
+			.get(self.api_endpoint.to_string() + "/sessions")
+			.header("Authorization", &self.token)
+			.send()
+			.await?;
+
+		let body = response_text(response).await?;
+		Ok(from_str(&body)?)
+	}
+
+	/// Revokes the session starting with `prefix`, which can only be this session if `confirm_current` is set.
+	pub async fn revoke_session(
+		&self,
+		prefix: &str,
+		confirm_current: bool,
+	) -> Result<(), anyhow::Error> {
+		let response = reqwest::Client::new()
+			.delete(self.api_endpoint.to_string() + "/sessions/" + prefix)
+			.query(&[("confirm_current", confirm_current)])
+			.header("Authorization", &self.token)
+			.send()
+			.await?;
+
+		response_text(response).await?;
+		Ok(())
+	}
+
+	/// Connects and waits for the sector to sync, see [`Session::connect`].
+	pub async fn join(self, queue: QueueStatus) -> Result<Sector, anyhow::Error> {
+		let connection = self.connect(&queue).await?;
+		Sector::new(connection, Some(self)).await
+	}
+}
+
+/// Where the player is in a full sector's queue, as last told by the gateway.
+#[derive(Clone, Copy, Deserialize)]
+pub struct QueuePosition {
+	/// 1 for the next player to be let in.
+	pub position: u32,
+	pub players: u32,
+	pub capacity: u32,
+	/// Seconds to wait before asking again.
+	pub retry_after: u32,
+}
+
+/// Shares the [`QueuePosition`] between the task connecting and the UI, [`None`] while not queued.
+#[derive(Clone, Default)]
+pub struct QueueStatus(Arc<Mutex<Option<QueuePosition>>>);
+
+impl QueueStatus {
+	pub fn get(&self) -> Option<QueuePosition> {
+		*self
+			.0
+			.lock()
+			.expect("queue status lock should not be poisoned")
+	}
+
+	fn set(&self, position: Option<QueuePosition>) {
+		*self
+			.0
+			.lock()
+			.expect("queue status lock should not be poisoned") = position;
+	}
+
+	/// A line saying where the player is in the queue, if they're queued.
+	pub fn describe(&self) -> Option<String> {
+		self.get().map(|queue| {
+			tr!(
+				"login.queued",
+				position = queue.position,
+				players = queue.players,
+				capacity = queue.capacity
+			)
+		})
+	}
+}
+
+/// One of the player's sessions, as listed by the gateway.
+#[derive(Clone, Deserialize)]
+pub struct SessionInfo {
+	/// The start of the session's token, enough to identify it.
+	pub prefix: String,
+	/// Seconds since the unix epoch.
+	pub created: u64,
+	/// Seconds since the unix epoch.
+	pub last_used: u64,
+	/// Whether this is the session the client is using.
+	pub current: bool,
+}
+
+/// The response's body, or the body as an error if the gateway refused the request.
+async fn response_text(response: Response) -> Result<String, anyhow::Error> {
+	let status = response.status();
+	let text = response.text().await?;
+
+	match status.is_success() {
+		true => Ok(text),
+		false => Err(anyhow!("{status}: {text}")),
+	}
+}
+
+/// Connects to the sector at `address`, which must be expecting `key`, either from the gateway or pre-shared.
+pub async fn handshake(
+	address: impl ToSocketAddrs,
+	key: [u8; 32],
+	keep_alive: KeepAlive,
+) -> Result<Connection<ClientEnd>, anyhow::Error> {
+	let mut key = ChaCha20Poly1305::new_from_slice(&key).unwrap(); // For some reason, anyhow can't convert this
+	let mut stream = TcpStream::connect(address).await?;
+	let mut version_data = PROTOCOL_VERSION.to_le_bytes().to_vec();
+	key.encrypt_in_place(&[0; 12].into(), b"", &mut version_data)
+		.unwrap(); // Anyhow also can't convert this
+	stream.write_u16_le(version_data.len() as u16).await?;
+	stream.write_all(&version_data).await?;
+	stream.flush().await?;
+
+	Ok(Connection::new(stream, key, keep_alive))
+}
 
 #[derive(Default)]
 pub struct Login {
 	email: String,
 	password: String,
 
+	/// Address and key entered for connecting straight to a sector, without the gateway.
+	direct_connection: DirectConnection,
+	/// Loaded the first time it's needed.
+	direct_connect_history: Option<DirectConnectHistory>,
+	/// Saved to the history if the login succeeds.
+	pending_direct_connection: Option<DirectConnection>,
+
 	error: String,
 	login: Option<JoinHandle<Result<Sector, anyhow::Error>>>,
+	queue: QueueStatus,
 }
 
 impl Login {
+	/// Shows the login form with an error, used when we've given up on a session.
+	pub fn with_error(error: String) -> Self {
+		Self {
+			error,
+			..Self::default()
+		}
+	}
+
 	#[cfg(debug)]
 	pub fn from_cl_args(cl_args: &mut ClArgs) -> Self {
+		if let Some(path) = cl_args.load_dump.take() {
+			return Self {
+				login: Some(
+					Handle::current()
+						.spawn(async move { crate::dump::Dump::read(&path)?.load().await }),
+				),
+				..Self::default()
+			};
+		}
+
 		match cl_args.authentication.take() {
-			Some(authentication) => Self {
-				login: Some(Handle::current().spawn(Self::login(
-					cl_args.clone(),
-					authentication.email.clone(),
-					authentication.password.clone(),
-				))),
-
-				email: authentication.email,
-				password: authentication.password,
-
-				error: String::new(),
-			},
+			Some(authentication) => {
+				let queue = QueueStatus::default();
+
+				Self {
+					login: Some(Handle::current().spawn(Self::login(
+						cl_args.clone(),
+						authentication.email.clone(),
+						authentication.password.clone(),
+						queue.clone(),
+					))),
+					queue,
+
+					email: authentication.email,
+					password: authentication.password,
+
+					..Self::default()
+				}
+			}
 			None => Self::default(),
 		}
 	}
 
+	/// Starts connecting straight to a sector, as if it had been entered into the direct connect form.
+	#[cfg(debug)]
+	pub fn direct(connection: DirectConnection, keep_alive: KeepAlive) -> Self {
+		let mut login = Self {
+			direct_connection: connection,
+			..Self::default()
+		};
+
+		login.direct_connect(keep_alive);
+		login
+	}
+
 	async fn login(
 		cl_args: ClArgs,
 		email: String,
 		password: String,
+		queue: QueueStatus,
 	) -> Result<Sector, anyhow::Error> {
-		let reqwest = reqwest::Client::new();
+		let keep_alive = cl_args.keep_alive();
 
-		let token = reqwest
-			.get(cl_args.api_endpoint.to_string() + "/dev/token")
-			.query(&[("email", email), ("password", password)])
-			.send()
+		Session::acquire(cl_args.api_endpoint, keep_alive, email, password)
 			.await?
-			.text()
-			.await?;
-
-		let details = reqwest
-			.get(cl_args.api_endpoint.to_string() + "/dev/connect")
-			.header("Authorization", token)
-			.send()
-			.await?
-			.text()
-			.await?;
+			.join(queue)
+			.await
+	}
 
-		#[derive(Deserialize)]
-		struct ConnectionInfo {
-			key: [u8; 32],
-			address: String,
+	/// Starts logging in with the entered email and password, unless something is already connecting.
+	fn submit(&mut self, cl_args: &ClArgs) {
+		if self.login.is_some() {
+			return;
 		}
 
-		let details: ConnectionInfo = from_str(&details)?;
+		self.login = Some(Handle::current().spawn(Self::login(
+			cl_args.clone(),
+			self.email.clone(),
+			self.password.clone(),
+			self.queue.clone(),
+		)));
+	}
+
+	fn direct_connect_history(&mut self) -> &mut DirectConnectHistory {
+		self.direct_connect_history
+			.get_or_insert_with(DirectConnectHistory::load)
+	}
+
+	fn direct_connect(&mut self, keep_alive: KeepAlive) {
+		let key = match parse_preshared_key(&self.direct_connection.key) {
+			Ok(key) => key,
+			Err(error) => {
+				self.error = error.to_string();
+				return;
+			}
+		};
 
-		let mut key = ChaCha20Poly1305::new_from_slice(&details.key).unwrap(); // For some reason, anyhow can't convert this
-		let mut stream = TcpStream::connect(details.address).await?;
-		let mut version_data = vec![0; 4];
-		key.encrypt_in_place(&[0; 12].into(), b"", &mut version_data)
-			.unwrap(); // Anyhow also can't convert this
-		stream.write_u16_le(version_data.len() as u16).await?;
-		stream.write_all(&version_data).await?;
-		stream.flush().await?;
-		let connection = Connection::new(stream, key);
+		let address = self.direct_connection.address.trim().to_string();
 
-		Ok(Sector::new(connection).await)
+		self.pending_direct_connection = Some(self.direct_connection.clone());
+		self.login = Some(Handle::current().spawn(async move {
+			let connection = handshake(address, key, keep_alive).await?;
+			Sector::new(connection, None).await
+		}));
 	}
 }
 
@@ -88,8 +353,18 @@ impl State for Login {
 	fn tick(&mut self) -> Option<AnyState> {
 		if let Some(handle) = &mut self.login {
 			if handle.is_finished() {
+				let direct_connection = self.pending_direct_connection.take();
+
 				match Handle::current().block_on(handle).unwrap() {
-					Ok(sector) => return Some(AnyState::Sector(sector)),
+					Ok(sector) => {
+						if let Some(direct_connection) = direct_connection {
+							let history = self.direct_connect_history();
+							history.add(direct_connection);
+							history.save();
+						}
+
+						return Some(AnyState::Sector(sector));
+					}
 					Err(error) => self.error = error.to_string(),
 				}
 
@@ -101,7 +376,8 @@ impl State for Login {
 	}
 
 	fn draw_ui(&mut self, cl_args: &ClArgs, context: &Context) {
-		Window::new("Login")
+		Window::new(tr!("login.title"))
+			.id(egui::Id::new("login"))
 			.anchor(Align2::CENTER_CENTER, (0.0, 0.0))
 			.resizable(false)
 			.collapsible(false)
@@ -111,29 +387,34 @@ impl State for Login {
 			.show(context, |window| {
 				if !self.error.is_empty() {
 					window.label(
-						RichText::new(format!("Error: {}\n", &self.error)).color(Color32::RED),
+						RichText::new(tr!("login.error", error = self.error) + "\n")
+							.color(UiColor::ErrorText.color()),
 					);
 				}
 
-				window.label("Email");
+				window.label(tr!("login.email"));
 				window.add(Separator::default().spacing(4.0));
-				window.add(
+				let email = window.add(
 					TextEdit::singleline(&mut self.email)
 						.desired_width(f32::INFINITY)
-						.hint_text("name@example.com"),
+						.hint_text(tr!("login.email_hint")),
 				);
 				window.label("");
 
-				window.label("Password");
+				window.label(tr!("login.password"));
 				window.add(Separator::default().spacing(4.0));
-				window.add(
+				let password = window.add(
 					TextEdit::singleline(&mut self.password)
 						.desired_width(f32::INFINITY)
-						.hint_text("correct horse battery staple")
+						.hint_text(tr!("login.password_hint"))
 						.password(true),
 				);
 				window.label("");
 
+				// Single line text edits give up focus when Enter is pressed
+				let mut submitted = (email.lost_focus() || password.lost_focus())
+					&& window.input(|input| input.key_pressed(Key::Enter));
+
 				window.allocate_ui_with_layout(
 					Vec2 {
 						x: window.min_rect().width(),
@@ -143,25 +424,181 @@ impl State for Login {
 					|layout| {
 						if self.login.is_some() {
 							layout.spinner();
-							layout.label("Connecting...");
+							layout.label(
+								self.queue
+									.describe()
+									.unwrap_or_else(|| tr!("login.connecting")),
+							);
 						}
 
 						layout.with_layout(Layout::right_to_left(Align::Center), |layout| {
-							if layout.button("Login").clicked() {
-								self.login = Some(Handle::current().spawn(Self::login(
-									cl_args.clone(),
-									self.email.clone(),
-									self.password.clone(),
+							submitted |= layout.button(tr!("login.login")).clicked();
+
+							#[cfg(feature = "singleplayer")]
+							if layout.button(tr!("login.singleplayer")).clicked() {
+								self.login = Some(Handle::current().spawn(Sector::new(
+									crate::singleplayer::LocalSector::start(),
+									None,
 								)));
 							}
 
 							layout.hyperlink_to(
-								"Create Account",
+								tr!("login.create_account"),
 								"https://solarscape.astralchroma.dev/create_account",
 							);
 						});
 					},
 				);
+
+				if submitted {
+					self.submit(cl_args);
+				}
+
+				CollapsingHeader::new(tr!("login.direct_connect"))
+					.id_salt("direct_connect")
+					.show(window, |window| {
+						window.label(tr!("login.direct_address"));
+						window.add(
+							TextEdit::singleline(&mut self.direct_connection.address)
+								.desired_width(f32::INFINITY)
+								.hint_text(tr!("login.direct_address_hint")),
+						);
+
+						window.label(tr!("login.direct_key"));
+						window.add(
+							TextEdit::singleline(&mut self.direct_connection.key)
+								.desired_width(f32::INFINITY)
+								.hint_text(tr!("login.direct_key_hint"))
+								.password(true),
+						);
+
+						window.with_layout(Layout::right_to_left(Align::Center), |layout| {
+							if layout.button(tr!("login.direct_connect_button")).clicked() {
+								self.direct_connect(cl_args.keep_alive());
+							}
+						});
+
+						let mut selected = None;
+
+						let history = self.direct_connect_history();
+						if !history.entries().is_empty() {
+							window.label(tr!("login.direct_recent"));
+							window.add(Separator::default().spacing(4.0));
+
+							for entry in history.entries() {
+								if window.button(&entry.address).clicked() {
+									selected = Some(entry.clone());
+								}
+							}
+						}
+
+						if let Some(selected) = selected {
+							self.direct_connection = selected;
+						}
+					});
 			});
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::headless::Headless;
+	use clap::Parser;
+	use std::{future::pending, thread};
+	use tokio::runtime::Runtime;
+
+	fn cl_args() -> ClArgs {
+		// Nothing listens here, so a login that does get started fails straight away
+		ClArgs::parse_from([
+			"solarscape-client",
+			"--api-endpoint",
+			"http://127.0.0.1:9/api",
+		])
+	}
+
+	/// Runs enough frames for the login window to be laid out and shown.
+	fn show(headless: &mut Headless, login: &mut Login, cl_args: &ClArgs) {
+		for _ in 0..2 {
+			headless.run(|context| login.draw_ui(cl_args, context));
+		}
+	}
+
+	/// Clicks the email field and types `text` into it.
+	fn type_email(headless: &mut Headless, login: &mut Login, cl_args: &ClArgs, text: &str) {
+		assert!(headless.click_text(&tr!("login.email_hint")));
+		headless.run(|context| login.draw_ui(cl_args, context));
+
+		headless.type_text(text);
+		headless.run(|context| login.draw_ui(cl_args, context));
+	}
+
+	#[test]
+	fn login_disabled_while_in_flight() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let cl_args = cl_args();
+
+		let mut headless = Headless::new();
+		let mut login = Login {
+			login: Some(runtime.spawn(pending())),
+			..Login::default()
+		};
+
+		show(&mut headless, &mut login, &cl_args);
+		assert!(headless.window_shown(egui::Id::new("login")));
+		assert!(headless.find_text(&tr!("login.connecting")).is_some());
+
+		type_email(&mut headless, &mut login, &cl_args, "player@example.com");
+		assert!(login.email.is_empty());
+
+		assert!(headless.click_text(&tr!("login.login")));
+		headless.run(|context| login.draw_ui(&cl_args, context));
+		assert!(!login.login.as_ref().unwrap().is_finished());
+	}
+
+	#[test]
+	fn error_shown_on_failure() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let cl_args = cl_args();
+
+		let handle = runtime.spawn(async { Err(anyhow!("sector refused the connection")) });
+		while !handle.is_finished() {
+			thread::yield_now();
+		}
+
+		let mut headless = Headless::new();
+		let mut login = Login {
+			login: Some(handle),
+			..Login::default()
+		};
+
+		assert!(login.tick().is_none());
+		assert!(login.login.is_none());
+
+		show(&mut headless, &mut login, &cl_args);
+
+		let error = tr!("login.error", error = "sector refused the connection");
+		assert!(headless.find_text(&error).is_some());
+	}
+
+	#[test]
+	fn enter_submits_login() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let cl_args = cl_args();
+
+		let mut headless = Headless::new();
+		let mut login = Login::default();
+
+		show(&mut headless, &mut login, &cl_args);
+		type_email(&mut headless, &mut login, &cl_args, "player@example.com");
+		assert_eq!(login.email, "player@example.com");
+		assert!(login.login.is_none());
+
+		headless.press(Key::Enter);
+		headless.run(|context| login.draw_ui(&cl_args, context));
+		assert!(login.login.is_some());
+	}
+}
@@ -1,20 +1,63 @@
-use crate::client::Client;
+use crate::{adapter::Backend, client::Client};
 use clap::{Args, Parser};
 use env_logger::Env;
-use log::info;
+use log::{info, warn};
 use reqwest::Url;
-use std::{env, error::Error, time::Instant};
+use solarscape_shared::connection::KeepAlive;
+use std::{
+	env,
+	error::Error,
+	path::PathBuf,
+	time::{Duration, Instant},
+};
 use tokio::runtime::Runtime;
 use winit::event_loop::EventLoop;
 
+mod accessibility;
+mod adapter;
+mod ambient;
+mod assets;
+mod chat;
+mod chunk_cache;
+mod chunk_vertex;
 mod client;
+mod config;
+mod connection_status;
+mod direct_connect;
+mod disconnected;
+mod display_mode;
+mod dump;
+mod frame_limiter;
+mod gpu_memory;
+mod hotbar;
+mod inspect;
+mod inventory_window;
+mod loading;
+mod localization;
 mod login;
+mod map;
+mod palette;
 mod player;
+mod rebuild_queue;
 mod renderer;
+mod roll_assist;
+mod safe_mode;
+mod sessions;
+#[cfg(feature = "singleplayer")]
+mod singleplayer;
+mod surface;
+mod telemetry;
+mod theme;
+mod toasts;
 mod world;
+mod world_border;
 
+#[cfg(debug)]
+mod console;
 #[cfg(debug)]
 mod gui_test;
+#[cfg(test)]
+mod headless;
 
 #[derive(Clone, Parser)]
 #[command(version)]
@@ -23,6 +66,43 @@ pub struct ClArgs {
 	#[arg(long, default_value = "https://solarscape.astralchroma.dev/api")]
 	api_endpoint: Url,
 
+	/// Maximum frames per second to render at, unlimited if not specified
+	#[arg(long)]
+	fps_limit: Option<u32>,
+
+	/// Warn when world content is estimated to use more GPU memory than this many MiB, 0 to never warn
+	#[arg(long, default_value_t = 2048)]
+	gpu_memory_limit: u64,
+
+	/// Only use a graphics adapter with a name containing this, ignoring case
+	#[arg(long)]
+	gpu: Option<String>,
+
+	/// Graphics backend to use, any backend available on the platform is used if not specified
+	#[arg(long, value_enum)]
+	backend: Option<Backend>,
+
+	/// Language to use for the UI, can be changed in game
+	#[arg(long, default_value = "en")]
+	language: String,
+
+	/// Directory of extra `<code>.lang` files, which may also replace built in languages
+	#[arg(long)]
+	languages: Option<PathBuf>,
+
+	/// Directory of models, textures, and shaders to use instead of the built in ones, `assets` in the client's config
+	/// directory if not specified
+	#[arg(long)]
+	assets: Option<PathBuf>,
+
+	/// Seconds to wait before sending a keep-alive to the sector when nothing else has been sent
+	#[arg(long, default_value_t = 10.0, value_parser = parse_seconds)]
+	keep_alive_interval: f32,
+
+	/// Seconds the sector may go without sending anything before the connection is considered lost
+	#[arg(long, default_value_t = 20.0, value_parser = parse_seconds)]
+	connection_timeout: f32,
+
 	#[cfg(debug)]
 	#[command(flatten)]
 	authentication: Option<Authentication>,
@@ -30,6 +110,30 @@ pub struct ClArgs {
 	#[cfg(debug)]
 	#[arg(long)]
 	gui_test: bool,
+
+	/// Loads a world dump written with F8 instead of connecting to a sector, only available in debug builds
+	#[cfg(debug)]
+	#[arg(long, value_name = "FILE")]
+	load_dump: Option<PathBuf>,
+}
+
+impl ClArgs {
+	fn keep_alive(&self) -> KeepAlive {
+		KeepAlive {
+			interval: Duration::from_secs_f32(self.keep_alive_interval),
+			timeout: Duration::from_secs_f32(self.connection_timeout),
+		}
+	}
+}
+
+/// A positive number of seconds, small enough to be a [`Duration`].
+fn parse_seconds(text: &str) -> Result<f32, String> {
+	let seconds = text.parse::<f32>().map_err(|error| error.to_string())?;
+
+	match seconds > 0.0 && Duration::try_from_secs_f32(seconds).is_ok() {
+		true => Ok(seconds),
+		false => Err(String::from("must be a positive number of seconds")),
+	}
 }
 
 #[cfg(debug)]
@@ -58,6 +162,18 @@ fn main() -> Result<(), Box<dyn Error>> {
 
 	info!("Solarscape (Client) v{}", env!("CARGO_PKG_VERSION"));
 
+	if let Some(directory) = &cl_args.languages {
+		localization::load_directory(directory);
+	}
+
+	if !localization::select(&cl_args.language) {
+		warn!(
+			"Unknown language {}, using {} instead",
+			cl_args.language,
+			localization::selected()
+		);
+	}
+
 	let runtime = Runtime::new()?;
 	let _guard = runtime.enter();
 
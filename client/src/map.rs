@@ -0,0 +1,682 @@
+use crate::{
+	palette::UiColor,
+	tr,
+	world::{Chunk, Voxject},
+};
+use dashmap::DashMap;
+use egui::{
+	pos2, vec2, Color32, ColorImage, Context, Painter, Pos2, Rect, Sense, Shape, Stroke,
+	TextureHandle, TextureOptions, Vec2, Window,
+};
+use nalgebra::{Point3, Vector3};
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::{
+	consts::{CELLS_PER_CHUNK, CHUNK_SIZE},
+	data::{
+		world::{ChunkCoordinates, Location, Material},
+		Id,
+	},
+};
+use std::{
+	f32::consts::{PI, TAU},
+	time::{Duration, Instant},
+};
+use winit::keyboard::KeyCode;
+
+/// An overview of the voxject the player is nearest to, drawn from whichever of it's chunks the client has. Opened
+/// with [`Map::KEY`].
+pub struct Map {
+	pub open: bool,
+	projection: Projection,
+	/// 1 shows the whole map, up to [`Map::MAX_ZOOM`].
+	zoom: f32,
+	/// Middle of the view, in map coordinates, see [`Projection::project`].
+	center: Pos2,
+	texture: Option<(TextureHandle, Drawn)>,
+	/// Set whenever a chunk is added or removed, the texture is drawn again when it's next shown.
+	stale: bool,
+	last_drawn: Option<Instant>,
+}
+
+/// What the map's texture was drawn from, it's drawn again if any of this changes.
+#[derive(Clone, Copy, PartialEq)]
+struct Drawn {
+	voxject: Id,
+	projection: Projection,
+	/// Level 0 chunk layer sliced through by [`Projection::TopDown`].
+	layer: i32,
+	/// Meters from the voxject's origin to the edge of a [`Projection::TopDown`] map.
+	extent: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+	/// A horizontal slice through the voxject at the player's height, looking down it's y axis.
+	TopDown,
+	/// Longitude and latitude around the voxject's origin, the whole surface at once.
+	Equirectangular,
+}
+
+impl Projection {
+	/// Width over height of the map.
+	pub fn aspect(self) -> f32 {
+		match self {
+			Self::TopDown => 1.0,
+			Self::Equirectangular => 2.0,
+		}
+	}
+
+	/// Where `position`, relative to the voxject, is on the map, from 0 to 1 on both axes. Top-down maps cover
+	/// `extent` meters either side of the voxject's origin, x to the right and z down, positions past that are outside
+	/// of the 0 to 1 range. Equirectangular maps have longitude 0 along the voxject's z axis in the middle and the
+	/// north pole, along it's y axis, at the top.
+	pub fn project(self, position: &Vector3<f32>, extent: f32) -> Pos2 {
+		match self {
+			Self::TopDown => pos2(
+				(position.x / extent + 1.0) / 2.0,
+				(position.z / extent + 1.0) / 2.0,
+			),
+			Self::Equirectangular => {
+				let Some(direction) = position.try_normalize(f32::EPSILON) else {
+					return pos2(0.5, 0.5);
+				};
+
+				let longitude = direction.x.atan2(direction.z);
+				let latitude = direction.y.clamp(-1.0, 1.0).asin();
+
+				pos2(0.5 + longitude / TAU, 0.5 - latitude / PI)
+			}
+		}
+	}
+
+	/// Which way on the map `direction` is from `position`, [`None`] if it points straight along the axis the map is
+	/// looking down.
+	pub fn direction(
+		self,
+		position: &Vector3<f32>,
+		direction: &Vector3<f32>,
+		extent: f32,
+	) -> Option<Vec2> {
+		let step = match self {
+			Self::TopDown => extent,
+			Self::Equirectangular => position.norm(),
+		} / 1000.0;
+
+		let from = self.project(position, extent);
+		let to = self.project(&(position + direction * step), extent);
+
+		// Stepping across the edge of an equirectangular map wraps around to the other side
+		let mut offset = to - from;
+		offset.x -= offset.x.round();
+
+		let length = offset.length();
+		(length > f32::EPSILON).then(|| offset / length)
+	}
+
+	fn name(self) -> String {
+		match self {
+			Self::TopDown => tr!("map.top_down"),
+			Self::Equirectangular => tr!("map.equirectangular"),
+		}
+	}
+}
+
+impl Map {
+	pub const KEY: KeyCode = KeyCode::KeyM;
+
+	const MAX_ZOOM: f32 = 16.0;
+
+	/// Pixels across the texture's shorter side.
+	const RESOLUTION: usize = 256;
+
+	/// Chunks arrive a few at a time for a while after joining or moving, the texture is only drawn again this often
+	/// while they do.
+	const REDRAW_INTERVAL: Duration = Duration::from_secs(1);
+
+	pub fn new() -> Self {
+		Self {
+			open: false,
+			projection: Projection::TopDown,
+			zoom: 1.0,
+			center: pos2(0.5, 0.5),
+			texture: None,
+			stale: true,
+			last_drawn: None,
+		}
+	}
+
+	/// Called whenever a chunk is added or removed.
+	pub fn invalidate(&mut self) {
+		self.stale = true;
+	}
+
+	/// Draws the map window, if it's open, of `voxject` with the player at `player` and structures at `structures`.
+	pub fn draw(
+		&mut self,
+		context: &Context,
+		voxject: Option<&Voxject>,
+		chunks: &DashMap<ChunkCoordinates, Chunk, FxBuildHasher>,
+		player: &Location,
+		structures: impl Iterator<Item = Point3<f32>>,
+	) {
+		if !self.open {
+			return;
+		}
+
+		let mut open = self.open;
+
+		Window::new(tr!("map.title"))
+			.id(egui::Id::new("map"))
+			.collapsible(false)
+			.default_size([512.0, 512.0])
+			.open(&mut open)
+			.show(context, |window| {
+				let Some(voxject) = voxject else {
+					window.label(tr!("map.no_voxject"));
+					return;
+				};
+
+				window.horizontal(|row| {
+					row.label(&*voxject.name);
+
+					for projection in [Projection::TopDown, Projection::Equirectangular] {
+						if row
+							.selectable_label(self.projection == projection, projection.name())
+							.clicked() && self.projection != projection
+						{
+							self.projection = projection;
+							self.zoom = 1.0;
+							self.center = pos2(0.5, 0.5);
+						}
+					}
+				});
+
+				let player_position = voxject
+					.location
+					.inverse_transform_point(&player.position)
+					.coords;
+
+				let (texture, drawn) = self.texture(context, voxject, chunks, &player_position);
+
+				let width = window.available_width();
+				let size = vec2(width, width / self.projection.aspect());
+				let (response, painter) = window.allocate_painter(size, Sense::drag());
+				let rect = response.rect;
+
+				if response.hovered() {
+					let scroll = window.input(|input| input.smooth_scroll_delta.y);
+					if scroll != 0.0 {
+						self.zoom = (self.zoom * (scroll / 200.0).exp()).clamp(1.0, Self::MAX_ZOOM);
+					}
+				}
+
+				if response.dragged() {
+					let delta = response.drag_delta() / rect.size() / self.zoom;
+					self.center -= delta;
+				}
+
+				self.clamp_view();
+
+				let view = Rect::from_center_size(self.center, Vec2::splat(1.0 / self.zoom));
+				painter.rect_filled(rect, 0.0, window.visuals().extreme_bg_color);
+				painter.image(texture, rect, view, Color32::WHITE);
+
+				let to_screen = |map: Pos2| rect.min + (map - view.min) * self.zoom * rect.size();
+
+				for structure in structures {
+					let position = voxject.location.inverse_transform_point(&structure).coords;
+					let screen = to_screen(drawn.projection.project(&position, drawn.extent));
+
+					if rect.contains(screen) {
+						painter.circle_filled(screen, 2.0, UiColor::StructureMarker.color());
+					}
+				}
+
+				let screen = to_screen(drawn.projection.project(&player_position, drawn.extent));
+				let facing = voxject.location.inverse_transform_vector(
+					&player.rotation.inverse_transform_vector(&-Vector3::z()),
+				);
+
+				if rect.contains(screen) {
+					draw_player(
+						&painter,
+						screen,
+						drawn
+							.projection
+							.direction(&player_position, &facing, drawn.extent),
+					);
+				}
+			});
+
+		self.open = open;
+	}
+
+	/// Keeps the view within the map, which fills the view at every zoom level.
+	fn clamp_view(&mut self) {
+		let margin = 0.5 / self.zoom;
+		self.center.x = self.center.x.clamp(margin, 1.0 - margin);
+		self.center.y = self.center.y.clamp(margin, 1.0 - margin);
+	}
+
+	/// The map's texture, drawn again first if the chunks, voxject, or player's layer have changed since it last was.
+	fn texture(
+		&mut self,
+		context: &Context,
+		voxject: &Voxject,
+		chunks: &DashMap<ChunkCoordinates, Chunk, FxBuildHasher>,
+		player_position: &Vector3<f32>,
+	) -> (egui::TextureId, Drawn) {
+		let layer = (player_position.y / CHUNK_SIZE as f32).floor() as i32;
+
+		let redraw = match &self.texture {
+			None => true,
+			Some((_, drawn)) => {
+				drawn.voxject != voxject.id
+					|| drawn.projection != self.projection
+					|| (drawn.projection == Projection::TopDown && drawn.layer != layer)
+					|| (self.stale
+						&& self
+							.last_drawn
+							.is_none_or(|last| last.elapsed() >= Self::REDRAW_INTERVAL))
+			}
+		};
+
+		if redraw {
+			let (image, drawn) = rasterize(voxject.id, self.projection, chunks, layer);
+
+			match &mut self.texture {
+				Some((texture, existing)) => {
+					texture.set(image, TextureOptions::NEAREST);
+					*existing = drawn;
+				}
+				None => {
+					let texture = context.load_texture("map", image, TextureOptions::NEAREST);
+					self.texture = Some((texture, drawn));
+				}
+			}
+
+			self.stale = false;
+			self.last_drawn = Some(Instant::now());
+		}
+
+		let (texture, drawn) = self.texture.as_ref().expect("texture was just drawn");
+		(texture.id(), *drawn)
+	}
+}
+
+/// Colors each of `voxject`'s chunks by it's most common material onto an image. Coarser chunks are drawn first, so
+/// finer ones cover them where both are loaded.
+fn rasterize(
+	voxject: Id,
+	projection: Projection,
+	chunks: &DashMap<ChunkCoordinates, Chunk, FxBuildHasher>,
+	layer: i32,
+) -> (ColorImage, Drawn) {
+	let slice = (layer as f32 + 0.5) * CHUNK_SIZE as f32;
+
+	let mut summaries = chunks
+		.iter()
+		.filter(|chunk| chunk.coordinates.voxject == voxject)
+		.filter(|chunk| match projection {
+			Projection::TopDown => {
+				let bottom = chunk.coordinates.origin().position.y;
+				(bottom..bottom + chunk.coordinates.size()).contains(&slice)
+			}
+			Projection::Equirectangular => true,
+		})
+		.filter_map(|chunk| {
+			// Equirectangular maps show the surface, a chunk that's all one or the other doesn't have any of it
+			let surface_only = projection == Projection::Equirectangular;
+			Some((
+				chunk.coordinates,
+				dominant_material(&chunk.materials, surface_only)?,
+			))
+		})
+		.collect::<Vec<_>>();
+
+	summaries.sort_by_key(|(coordinates, _)| std::cmp::Reverse(*coordinates.level));
+
+	let extent = summaries
+		.iter()
+		.map(|(coordinates, _)| {
+			let origin = coordinates.origin().position;
+			let far = origin + Vector3::repeat(coordinates.size());
+			[origin.x, origin.z, far.x, far.z]
+				.into_iter()
+				.map(f32::abs)
+				.fold(0.0, f32::max)
+		})
+		.fold(16.0, f32::max);
+
+	let height = Map::RESOLUTION;
+	let width = (height as f32 * projection.aspect()) as usize;
+	let mut image = ColorImage::new([width, height], Color32::TRANSPARENT);
+
+	let mut fill = |min: Pos2, max: Pos2, color: Color32| {
+		let x_range = (min.x * width as f32).floor() as i64..(max.x * width as f32).ceil() as i64;
+		let y_range = (min.y * height as f32).floor().max(0.0) as usize
+			..((max.y * height as f32).ceil() as usize).min(height);
+
+		for y in y_range {
+			for x in x_range.clone() {
+				// Only equirectangular maps reach past their sides, where they wrap around
+				let x = x.rem_euclid(width as i64) as usize;
+				image.pixels[y * width + x] = color;
+			}
+		}
+	};
+
+	for (coordinates, material) in summaries {
+		let color = material_color(material);
+		let origin = coordinates.origin().position.coords;
+		let size = coordinates.size();
+
+		match projection {
+			Projection::TopDown => {
+				let min = projection.project(&origin, extent);
+				let max = projection.project(&(origin + Vector3::repeat(size)), extent);
+				fill(min, max, color);
+			}
+			Projection::Equirectangular => {
+				let center = origin + Vector3::repeat(size / 2.0);
+				let middle = projection.project(&center, extent);
+
+				// Half the angle the chunk covers as seen from the origin, wider towards the poles
+				let angle = (size / 2.0 / center.norm().max(f32::EPSILON)).min(PI);
+				let latitude = (0.5 - middle.y) * PI;
+				let half = vec2(
+					(angle / TAU / latitude.cos().max(0.05)).min(0.5),
+					angle / PI,
+				);
+
+				fill(middle - half, middle + half, color);
+			}
+		}
+	}
+
+	let drawn = Drawn {
+		voxject,
+		projection,
+		layer,
+		extent,
+	};
+
+	(image, drawn)
+}
+
+/// The most common material in a chunk other than [`Material::Nothing`]. [`None`] if it's empty, or if
+/// `surface_only` and it's solid all the way through.
+pub fn dominant_material(
+	materials: &[Material; CELLS_PER_CHUNK],
+	surface_only: bool,
+) -> Option<Material> {
+	let mut counts = [0usize; 4];
+
+	for material in materials {
+		counts[*material as usize & 0b11] += 1;
+	}
+
+	let nothing = counts[Material::Nothing as usize & 0b11];
+	if nothing == CELLS_PER_CHUNK || (surface_only && nothing == 0) {
+		return None;
+	}
+
+	[Material::Corium, Material::Stone, Material::Ground]
+		.into_iter()
+		.max_by_key(|material| counts[*material as usize & 0b11])
+}
+
+/// Same as the colors in `chunk.wgsl`, darkened slightly so that markers stand out.
+fn material_color(material: Material) -> Color32 {
+	match material {
+		Material::Corium => Color32::from_rgb(204, 61, 0),
+		Material::Stone => Color32::from_rgb(102, 102, 102),
+		Material::Ground => Color32::from_rgb(41, 163, 41),
+		Material::Nothing => Color32::TRANSPARENT,
+	}
+}
+
+/// A dot at `position` with a line pointing the way the player is facing.
+fn draw_player(painter: &Painter, position: Pos2, facing: Option<Vec2>) {
+	let color = UiColor::Target.color();
+
+	painter.circle_filled(position, 4.0, color);
+
+	if let Some(facing) = facing {
+		painter.add(Shape::line_segment(
+			[position, position + facing * 12.0],
+			Stroke::new(2.0, color),
+		));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::vector;
+	use solarscape_shared::data::world::Level;
+	use std::sync::Arc;
+
+	fn assert_near(actual: Pos2, expected: Pos2) {
+		assert!(
+			(actual - expected).length() < 1e-4,
+			"{actual:?} isn't {expected:?}"
+		);
+	}
+
+	fn assert_direction(actual: Option<Vec2>, expected: Vec2) {
+		let actual = actual.expect("there should be a direction");
+		assert!(
+			(actual - expected).length() < 1e-3,
+			"{actual:?} isn't {expected:?}"
+		);
+	}
+
+	/// A chunk of mostly `material`, with a layer of nothing on top if it's `surface`.
+	fn chunk(coordinates: ChunkCoordinates, material: Material, surface: bool) -> Chunk {
+		let mut materials = [material; CELLS_PER_CHUNK];
+
+		if surface {
+			materials[..CELLS_PER_CHUNK / CHUNK_SIZE].fill(Material::Nothing);
+		}
+
+		Chunk {
+			coordinates,
+			generation: 0,
+			materials: Arc::new(materials),
+			densities: Arc::new([0.0; CELLS_PER_CHUNK]),
+			mesh: None,
+		}
+	}
+
+	fn chunks(
+		chunks: impl IntoIterator<Item = Chunk>,
+	) -> DashMap<ChunkCoordinates, Chunk, FxBuildHasher> {
+		chunks
+			.into_iter()
+			.map(|chunk| (chunk.coordinates, chunk))
+			.collect()
+	}
+
+	fn pixel(image: &ColorImage, x: f32, y: f32) -> Color32 {
+		let [width, height] = image.size;
+		image.pixels[(y * height as f32) as usize * width + (x * width as f32) as usize]
+	}
+
+	#[test]
+	fn top_down_maps_cover_the_extent_either_side() {
+		let project = |x, y, z| Projection::TopDown.project(&vector![x, y, z], 100.0);
+
+		assert_near(project(0.0, 0.0, 0.0), pos2(0.5, 0.5));
+		assert_near(project(0.0, 1000.0, 0.0), pos2(0.5, 0.5));
+		assert_near(project(-100.0, 0.0, -100.0), pos2(0.0, 0.0));
+		assert_near(project(100.0, 0.0, 100.0), pos2(1.0, 1.0));
+		assert_near(project(50.0, 0.0, -50.0), pos2(0.75, 0.25));
+
+		// Outside of the map, rather than clamped onto it's edge
+		assert_near(project(200.0, 0.0, 0.0), pos2(1.5, 0.5));
+
+		assert_eq!(Projection::TopDown.aspect(), 1.0);
+	}
+
+	#[test]
+	fn equirectangular_maps_are_longitude_and_latitude() {
+		let project = |x, y, z| Projection::Equirectangular.project(&vector![x, y, z], 100.0);
+
+		assert_near(project(0.0, 0.0, 1.0), pos2(0.5, 0.5));
+		assert_near(project(1.0, 0.0, 0.0), pos2(0.75, 0.5));
+		assert_near(project(-1.0, 0.0, 0.0), pos2(0.25, 0.5));
+		assert_near(project(0.0, 1.0, 0.0), pos2(0.5, 0.0));
+		assert_near(project(0.0, -1.0, 0.0), pos2(0.5, 1.0));
+		assert_near(project(1.0, 1.0, 0.0), pos2(0.75, 0.25));
+
+		// The distance from the origin doesn't matter
+		assert_near(project(0.0, 0.0, 5000.0), project(0.0, 0.0, 0.001));
+
+		// Nowhere in particular
+		assert_near(project(0.0, 0.0, 0.0), pos2(0.5, 0.5));
+
+		assert_eq!(Projection::Equirectangular.aspect(), 2.0);
+	}
+
+	#[test]
+	fn directions_point_across_the_map() {
+		let top_down = |position: Vector3<f32>, direction: Vector3<f32>| {
+			Projection::TopDown.direction(&position, &direction, 100.0)
+		};
+
+		assert_direction(top_down(Vector3::zeros(), Vector3::x()), vec2(1.0, 0.0));
+		assert_direction(top_down(Vector3::zeros(), -Vector3::z()), vec2(0.0, -1.0));
+		assert_direction(
+			top_down(vector![50.0, 0.0, 50.0], vector![1.0, 5.0, 1.0]),
+			vec2(1.0, 1.0).normalized(),
+		);
+		assert_eq!(top_down(Vector3::zeros(), Vector3::y()), None);
+
+		let equirectangular = |position: Vector3<f32>, direction: Vector3<f32>| {
+			Projection::Equirectangular.direction(&position, &direction, 100.0)
+		};
+
+		let surface = vector![0.0, 0.0, 100.0];
+		assert_direction(equirectangular(surface, Vector3::x()), vec2(1.0, 0.0));
+		assert_direction(equirectangular(surface, Vector3::y()), vec2(0.0, -1.0));
+		assert_eq!(equirectangular(surface, Vector3::z()), None);
+
+		// Right on the edge of the map, where a step east wraps around to the far west
+		let edge = vector![-0.001, 0.0, -100.0];
+		assert_direction(equirectangular(edge, Vector3::x()), vec2(-1.0, 0.0));
+		assert_direction(equirectangular(edge, -Vector3::x()), vec2(1.0, 0.0));
+	}
+
+	#[test]
+	fn chunks_are_summarised_by_their_most_common_material() {
+		let mut materials = [Material::Nothing; CELLS_PER_CHUNK];
+		assert_eq!(dominant_material(&materials, false), None);
+
+		materials[..10].fill(Material::Stone);
+		materials[10..15].fill(Material::Ground);
+		assert_eq!(dominant_material(&materials, false), Some(Material::Stone));
+		assert_eq!(dominant_material(&materials, true), Some(Material::Stone));
+
+		materials[15..].fill(Material::Corium);
+		assert_eq!(dominant_material(&materials, false), Some(Material::Corium));
+
+		// Solid all the way through, so it's not the surface
+		assert_eq!(dominant_material(&materials, true), None);
+	}
+
+	#[test]
+	fn top_down_maps_slice_through_the_players_layer() {
+		let voxject = Id::new();
+		let at =
+			|x, y, z, level| ChunkCoordinates::new(voxject, vector![x, y, z], Level::new(level));
+
+		let chunks = chunks([
+			chunk(at(0, 0, 0, 1), Material::Ground, false),
+			chunk(at(0, 0, 0, 0), Material::Stone, false),
+			// Above the slice, and another voxject's
+			chunk(at(-1, 1, -1, 0), Material::Corium, false),
+			chunk(
+				ChunkCoordinates::new(Id::new(), vector![-1, 0, 0], Level::new(0)),
+				Material::Corium,
+				false,
+			),
+		]);
+
+		let (image, drawn) = rasterize(voxject, Projection::TopDown, &chunks, 0);
+
+		assert_eq!(drawn.extent, 32.0);
+		assert_eq!(drawn.layer, 0);
+		assert_eq!(image.size, [Map::RESOLUTION, Map::RESOLUTION]);
+
+		// The finer chunk covers the coarser one it's inside of
+		assert_eq!(pixel(&image, 0.6, 0.6), material_color(Material::Stone));
+		assert_eq!(pixel(&image, 0.9, 0.9), material_color(Material::Ground));
+		assert_eq!(pixel(&image, 0.9, 0.6), material_color(Material::Ground));
+
+		assert_eq!(pixel(&image, 0.4, 0.4), Color32::TRANSPARENT);
+		assert_eq!(pixel(&image, 0.4, 0.6), Color32::TRANSPARENT);
+
+		// One layer up only the coarser chunk reaches
+		let (image, _) = rasterize(voxject, Projection::TopDown, &chunks, 1);
+		assert_eq!(pixel(&image, 0.6, 0.6), material_color(Material::Ground));
+		assert_eq!(pixel(&image, 0.4, 0.4), material_color(Material::Corium));
+	}
+
+	#[test]
+	fn equirectangular_maps_only_show_the_surface() {
+		let voxject = Id::new();
+		let at = |x, y, z| ChunkCoordinates::new(voxject, vector![x, y, z], Level::new(0));
+
+		let chunks = chunks([
+			// In front of the origin along z, and to it's west
+			chunk(at(-1, -1, 4), Material::Ground, true),
+			chunk(at(-6, -1, -1), Material::Stone, true),
+			// Solid, so underground
+			chunk(at(4, -1, -1), Material::Corium, false),
+		]);
+
+		let (image, drawn) = rasterize(voxject, Projection::Equirectangular, &chunks, 0);
+
+		assert_eq!(drawn.projection, Projection::Equirectangular);
+		assert_eq!(image.size, [Map::RESOLUTION * 2, Map::RESOLUTION]);
+
+		let center = |coordinates: ChunkCoordinates| {
+			let center = coordinates.center().position.coords;
+			Projection::Equirectangular.project(&center, drawn.extent)
+		};
+
+		let pixel = |map: Pos2| pixel(&image, map.x, map.y);
+
+		assert_eq!(
+			pixel(center(at(-1, -1, 4))),
+			material_color(Material::Ground)
+		);
+		assert_eq!(
+			pixel(center(at(-6, -1, -1))),
+			material_color(Material::Stone)
+		);
+		assert_eq!(pixel(center(at(4, -1, -1))), Color32::TRANSPARENT);
+
+		// Round the back, where nothing is loaded
+		assert_eq!(pixel(pos2(0.0, 0.5)), Color32::TRANSPARENT);
+	}
+
+	#[test]
+	fn the_view_stays_on_the_map() {
+		let mut map = Map::new();
+
+		map.center = pos2(-1.0, 2.0);
+		map.clamp_view();
+		assert_eq!(map.center, pos2(0.5, 0.5));
+
+		map.zoom = 4.0;
+		map.center = pos2(0.0, 1.0);
+		map.clamp_view();
+		assert_eq!(map.center, pos2(0.125, 0.875));
+
+		map.center = pos2(0.3, 0.7);
+		map.clamp_view();
+		assert_eq!(map.center, pos2(0.3, 0.7));
+	}
+}
@@ -0,0 +1,203 @@
+use crate::theme;
+use egui::{Color32, Rgba};
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+
+/// Whether [`Palette::ColorblindSafe`] is selected, see [`select`].
+static COLORBLIND_SAFE: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Palette {
+	Standard,
+	/// Avoids telling anything apart by red and green alone, based on the Okabe-Ito palette.
+	ColorblindSafe,
+}
+
+/// Changes the palette every [`UiColor`] is taken from, it applies to everything drawn after.
+pub fn select(palette: Palette) {
+	COLORBLIND_SAFE.store(palette == Palette::ColorblindSafe, Relaxed);
+}
+
+pub fn selected() -> Palette {
+	match COLORBLIND_SAFE.load(Relaxed) {
+		true => Palette::ColorblindSafe,
+		false => Palette::Standard,
+	}
+}
+
+/// Colors that mean something, rather than being decoration. Anything that uses color to tell things apart should take
+/// it from here, so that it changes with the selected palette. Every color has to be given in both palettes, which the
+/// matches in [`UiColor::in_palette`] enforce.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum UiColor {
+	/// Tints the placement indicator when placing there looks like it'll succeed.
+	PlacementValid,
+	/// Tints the placement indicator when placing there won't succeed.
+	PlacementInvalid,
+	/// Outline of the terrain brush.
+	TerrainBrush,
+	/// Debug lines marking each structure's location.
+	StructureMarker,
+	/// The structure block or terrain cell the player is aiming at.
+	Target,
+	/// The world border, once the player is close to it.
+	WorldBorder,
+
+	ToastInfo,
+	ToastWarning,
+	ToastError,
+	/// Error messages shown in a window, such as why logging in failed.
+	ErrorText,
+	/// Frame times and the like, drawn over the world in the corner of the screen.
+	DebugText,
+}
+
+impl UiColor {
+	#[cfg(test)]
+	pub const ALL: [Self; 11] = [
+		Self::PlacementValid,
+		Self::PlacementInvalid,
+		Self::TerrainBrush,
+		Self::StructureMarker,
+		Self::Target,
+		Self::WorldBorder,
+		Self::ToastInfo,
+		Self::ToastWarning,
+		Self::ToastError,
+		Self::ErrorText,
+		Self::DebugText,
+	];
+
+	/// This color in the selected palette, made readable on the selected theme's windows, see [`Theme::readable`].
+	///
+	/// [`Theme::readable`]: crate::theme::Theme::readable
+	pub fn color(self) -> Color32 {
+		let palette = selected();
+		theme::selected()
+			.theme(palette)
+			.readable(self.in_palette(palette))
+	}
+
+	/// This color in the selected palette as linear RGB, for drawing in the world, which isn't affected by the theme.
+	pub fn linear(self) -> [f32; 3] {
+		let [red, green, blue, _] = Rgba::from(self.in_palette(selected())).to_array();
+		[red, green, blue]
+	}
+
+	pub const fn in_palette(self, palette: Palette) -> Color32 {
+		match palette {
+			Palette::Standard => match self {
+				Self::PlacementValid => Color32::from_rgb(188, 255, 188),
+				Self::PlacementInvalid => Color32::from_rgb(255, 149, 149),
+				Self::TerrainBrush => Color32::from_rgb(255, 231, 124),
+				Self::StructureMarker => Color32::WHITE,
+				Self::Target => Color32::from_rgb(200, 230, 255),
+				Self::WorldBorder => Color32::from_rgb(255, 96, 96),
+
+				Self::ToastInfo => Color32::WHITE,
+				Self::ToastWarning => Color32::YELLOW,
+				Self::ToastError => Color32::LIGHT_RED,
+				Self::ErrorText => Color32::RED,
+				Self::DebugText => Color32::WHITE,
+			},
+			Palette::ColorblindSafe => match self {
+				Self::PlacementValid => Color32::from_rgb(86, 180, 233),
+				Self::PlacementInvalid => Color32::from_rgb(230, 159, 0),
+				Self::TerrainBrush => Color32::from_rgb(240, 228, 66),
+				Self::StructureMarker => Color32::WHITE,
+				Self::Target => Color32::WHITE,
+				Self::WorldBorder => Color32::from_rgb(213, 94, 0),
+
+				Self::ToastInfo => Color32::WHITE,
+				Self::ToastWarning => Color32::from_rgb(240, 228, 66),
+				Self::ToastError => Color32::from_rgb(230, 159, 0),
+				Self::ErrorText => Color32::from_rgb(230, 159, 0),
+				Self::DebugText => Color32::WHITE,
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// Fails to compile if a color is added without being added to [`UiColor::ALL`] here too.
+	fn position(color: UiColor) -> usize {
+		use UiColor::*;
+
+		match color {
+			PlacementValid => 0,
+			PlacementInvalid => 1,
+			TerrainBrush => 2,
+			StructureMarker => 3,
+			Target => 4,
+			WorldBorder => 5,
+			ToastInfo => 6,
+			ToastWarning => 7,
+			ToastError => 8,
+			ErrorText => 9,
+			DebugText => 10,
+		}
+	}
+
+	#[test]
+	fn every_color_is_listed_once() {
+		for (index, color) in UiColor::ALL.into_iter().enumerate() {
+			assert_eq!(position(color), index, "{color:?}");
+		}
+	}
+
+	#[test]
+	fn every_color_is_in_both_palettes() {
+		for palette in [Palette::Standard, Palette::ColorblindSafe] {
+			for color in UiColor::ALL {
+				assert_ne!(
+					color.in_palette(palette),
+					Color32::TRANSPARENT,
+					"{color:?} in {palette:?}"
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn colorblind_safe_palette_changes_red_and_green() {
+		use UiColor::*;
+
+		let changed = UiColor::ALL
+			.into_iter()
+			.filter(|color| {
+				color.in_palette(Palette::Standard) != color.in_palette(Palette::ColorblindSafe)
+			})
+			.collect::<Vec<_>>();
+
+		for color in [
+			PlacementValid,
+			PlacementInvalid,
+			WorldBorder,
+			ToastError,
+			ErrorText,
+		] {
+			assert!(changed.contains(&color), "{color:?}");
+		}
+	}
+
+	#[test]
+	fn opposites_are_told_apart_in_both_palettes() {
+		use UiColor::*;
+
+		for palette in [Palette::Standard, Palette::ColorblindSafe] {
+			for (a, b) in [
+				(PlacementValid, PlacementInvalid),
+				(ToastInfo, ToastWarning),
+				(ToastWarning, ToastError),
+			] {
+				assert_ne!(
+					a.in_palette(palette),
+					b.in_palette(palette),
+					"{a:?} and {b:?} in {palette:?}"
+				);
+			}
+		}
+	}
+}
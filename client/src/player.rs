@@ -1,3 +1,4 @@
+use crate::{roll_assist::RollAssist, surface::Surface};
 use nalgebra::{vector, UnitQuaternion, Vector3};
 use solarscape_shared::{
 	connection::{ClientEnd, Connection},
@@ -37,6 +38,9 @@ impl<L: Locality> DerefMut for Player<L> {
 pub struct Local {
 	pub connection: Connection<ClientEnd>,
 
+	/// What the player was standing on as of the last tick.
+	pub surface: Option<Surface>,
+
 	left_state: OppositeKeyState,
 	right_state: OppositeKeyState,
 
@@ -48,6 +52,10 @@ pub struct Local {
 
 	roll_left_state: OppositeKeyState,
 	roll_right_state: OppositeKeyState,
+
+	pub roll_assist: RollAssist,
+	/// How quickly the roll assist is rolling the player, in radians per second.
+	roll_velocity: f32,
 }
 
 enum OppositeKeyState {
@@ -64,6 +72,12 @@ enum OppositeKeyState {
 impl Locality for Local {}
 
 impl Player<Local> {
+	/// How far in front of the player blocks are placed.
+	pub const PLACEMENT_DISTANCE: f32 = 3.0;
+
+	/// Meters per second the player flies at, see [`Surface::SPEED_MULTIPLIER`] for walking.
+	const FLY_SPEED: f32 = 10.0;
+
 	pub fn new(connection: Connection<ClientEnd>) -> Self {
 		Self {
 			location: Location::default(),
@@ -71,6 +85,8 @@ impl Player<Local> {
 			locality: Local {
 				connection,
 
+				surface: None,
+
 				left_state: OppositeKeyState::Released,
 				right_state: OppositeKeyState::Released,
 
@@ -82,6 +98,9 @@ impl Player<Local> {
 
 				roll_left_state: OppositeKeyState::Released,
 				roll_right_state: OppositeKeyState::Released,
+
+				roll_assist: RollAssist::load(),
+				roll_velocity: 0.0,
 			},
 		}
 	}
@@ -95,10 +114,13 @@ impl Player<Local> {
 		self.location.rotation = rotation * self.location.rotation;
 	}
 
-	pub fn handle_window_event(&mut self, event: &WindowEvent) {
+	/// `block` is what the player has selected to place, if anything.
+	pub fn handle_window_event(&mut self, event: &WindowEvent, block: Option<BlockType>) {
 		match event {
 			WindowEvent::KeyboardInput { event, .. } => self.handle_keyboard_input(event),
-			WindowEvent::MouseInput { state, button, .. } => self.handle_mouse_input(state, button),
+			WindowEvent::MouseInput { state, button, .. } => {
+				self.handle_mouse_input(state, button, block)
+			}
 			_ => {}
 		}
 	}
@@ -170,29 +192,38 @@ impl Player<Local> {
 		}
 	}
 
-	fn handle_mouse_input(&self, state: &ElementState, button: &MouseButton) {
+	fn handle_mouse_input(
+		&self,
+		state: &ElementState,
+		button: &MouseButton,
+		block: Option<BlockType>,
+	) {
 		if matches!(state, ElementState::Pressed) {
 			return;
 		}
 
-		match button {
-			MouseButton::Left => self.place_structure_block(),
-			_ => {}
+		if let (MouseButton::Left, Some(block)) = (button, block) {
+			self.place_structure_block(block);
 		}
 	}
 
-	fn place_structure_block(&self) {
+	/// Where a block would be placed if the player placed one now.
+	pub fn placement_location(&self) -> Location {
+		Location {
+			position: self.location.position
+				+ (self
+					.location
+					.rotation
+					.inverse_transform_vector(&-Vector3::z())
+					* Self::PLACEMENT_DISTANCE),
+			rotation: self.location.rotation,
+		}
+	}
+
+	fn place_structure_block(&self, block: BlockType) {
 		self.connection.send(CreateStructure {
-			location: Location {
-				position: self.location.position
-					+ (self
-						.location
-						.rotation
-						.inverse_transform_vector(&-Vector3::z())
-						* 3.0),
-				rotation: self.location.rotation,
-			},
-			block: BlockType::Block,
+			location: self.placement_location(),
+			block,
 		})
 	}
 
@@ -206,7 +237,8 @@ impl Player<Local> {
 		}
 	}
 
-	pub fn tick(&mut self, delta: f32) {
+	/// `up` is which way the roll assist levels the player towards, see [`RollAssist`].
+	pub fn tick(&mut self, delta: f32, up: Vector3<f32>) {
 		fn key_state_to_float(
 			negative_state: &OppositeKeyState,
 			positive_state: &OppositeKeyState,
@@ -231,15 +263,36 @@ impl Player<Local> {
 		];
 
 		if translation.normalize_mut().is_normal() {
-			translation *= delta * 10.0;
+			translation *= delta * Self::FLY_SPEED;
+
+			if self.surface.is_some() {
+				translation *= Surface::SPEED_MULTIPLIER;
+			}
+
 			self.translate_local(translation.into());
 		}
 
-		let rotation = UnitQuaternion::from_euler_angles(
-			0.0,
-			0.0,
-			key_state_to_float(&self.roll_left_state, &self.roll_right_state) * delta,
-		);
+		let roll = key_state_to_float(&self.roll_left_state, &self.roll_right_state);
+
+		// The assist stays out of the way while the player is rolling themselves
+		let correction = match roll {
+			0.0 => {
+				self.roll_assist
+					.correction(&self.location.rotation, &up, self.roll_velocity, delta)
+			}
+			_ => None,
+		};
+
+		let rotation = match correction {
+			Some((rotation, velocity)) => {
+				self.roll_velocity = velocity;
+				rotation
+			}
+			None => {
+				self.roll_velocity = 0.0;
+				UnitQuaternion::from_euler_angles(0.0, 0.0, roll * delta)
+			}
+		};
 
 		self.rotate(rotation);
 
@@ -0,0 +1,133 @@
+use dashmap::DashMap;
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::data::world::ChunkCoordinates;
+use std::{collections::HashSet, mem};
+
+/// Chunks waiting to have their meshes rebuilt. Syncing a chunk means rebuilding it and every chunk that depends on it,
+/// so when a batch of neighbouring chunks arrives at once the same chunks would be rebuilt many times over. Instead
+/// they're queued here and each is rebuilt once per frame.
+#[derive(Default)]
+pub struct RebuildQueue {
+	pending: HashSet<ChunkCoordinates, FxBuildHasher>,
+
+	/// How many rebuilds have been requested, including ones for chunks which were already queued.
+	pub requested: u64,
+	/// How many rebuilds have actually been done.
+	pub rebuilt: u64,
+}
+
+impl RebuildQueue {
+	pub fn request(&mut self, coordinates: impl IntoIterator<Item = ChunkCoordinates>) {
+		for coordinates in coordinates {
+			self.pending.insert(coordinates);
+			self.requested += 1;
+		}
+	}
+
+	/// Queues `coordinates` along with every chunk that depends on it, see [`SharedSector::dependent_chunks`].
+	///
+	/// [`SharedSector::dependent_chunks`]: crate::world::SharedSector::dependent_chunks
+	pub fn request_with_dependents(
+		&mut self,
+		coordinates: ChunkCoordinates,
+		dependent_chunks: &DashMap<ChunkCoordinates, HashSet<ChunkCoordinates>, FxBuildHasher>,
+	) {
+		if let Some(dependent_chunks) = dependent_chunks.get(&coordinates) {
+			self.request(dependent_chunks.iter().copied());
+		}
+
+		self.request([coordinates]);
+	}
+
+	/// Takes every queued chunk, each of which should be rebuilt.
+	pub fn take(&mut self) -> HashSet<ChunkCoordinates, FxBuildHasher> {
+		let pending = mem::take(&mut self.pending);
+		self.rebuilt += pending.len() as u64;
+		pending
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::{vector, Vector3};
+	use solarscape_shared::data::world::Level;
+
+	fn chunk(coordinates: Vector3<i32>) -> ChunkCoordinates {
+		ChunkCoordinates::new("1".parse().unwrap(), coordinates, Level::new(0))
+	}
+
+	/// Every chunk from `min` to `max` inclusive.
+	fn block(min: i32, max: i32) -> Vec<ChunkCoordinates> {
+		let mut chunks = vec![];
+
+		for x in min..=max {
+			for y in min..=max {
+				for z in min..=max {
+					chunks.push(chunk(vector![x, y, z]));
+				}
+			}
+		}
+
+		chunks
+	}
+
+	/// Dependents as they'd be if every chunk from `min` to `max` had been built.
+	fn built(
+		min: i32,
+		max: i32,
+	) -> DashMap<ChunkCoordinates, HashSet<ChunkCoordinates>, FxBuildHasher> {
+		let dependent_chunks = DashMap::with_hasher(FxBuildHasher);
+
+		for chunk in block(min, max) {
+			for dependency in chunk.mesh_dependencies().unwrap() {
+				dependent_chunks
+					.entry(dependency)
+					.or_insert_with(HashSet::new)
+					.insert(chunk);
+			}
+		}
+
+		dependent_chunks
+	}
+
+	#[test]
+	fn adjacent_chunks_are_each_rebuilt_once() {
+		let dependent_chunks = built(-2, 4);
+		let mut queue = RebuildQueue::default();
+
+		for coordinates in block(1, 2) {
+			queue.request_with_dependents(coordinates, &dependent_chunks);
+		}
+
+		// Each chunk is needed by itself and the 7 chunks on it's negative sides
+		let expected = block(0, 2)
+			.into_iter()
+			.collect::<HashSet<_, FxBuildHasher>>();
+		assert_eq!(queue.take(), expected);
+		assert_eq!((queue.requested, queue.rebuilt), (8 * 9, 27));
+
+		assert!(queue.take().is_empty());
+		assert_eq!(queue.rebuilt, 27);
+	}
+
+	#[test]
+	fn unbuilt_chunks_only_rebuild_themselves() {
+		let mut queue = RebuildQueue::default();
+
+		queue.request_with_dependents(
+			chunk(vector![5, 5, 5]),
+			&DashMap::with_hasher(FxBuildHasher),
+		);
+		queue.request_with_dependents(
+			chunk(vector![5, 5, 5]),
+			&DashMap::with_hasher(FxBuildHasher),
+		);
+
+		assert_eq!(
+			queue.take().into_iter().collect::<Vec<_>>(),
+			[chunk(vector![5, 5, 5])]
+		);
+		assert_eq!((queue.requested, queue.rebuilt), (2, 1));
+	}
+}
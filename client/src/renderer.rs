@@ -1,83 +1,110 @@
+#[cfg(debug)]
+use crate::console::Console;
 use crate::{
+	accessibility::Accessibility,
+	adapter::{self, AdapterError, AdapterPreference, Backend, Described},
+	assets::{Asset, AssetError, Assets, AtlasLayout, Loaded},
+	chunk_vertex,
 	client::{AnyState, State},
+	disconnected::Disconnected,
+	display_mode::{self, DisplayMode, DisplaySettings, MonitorId},
+	frame_limiter::{FrameLimit, FrameLimiter},
+	gpu_memory::{GpuCategory, Mebibytes, Tracked, GPU_MEMORY},
+	localization,
 	login::Login,
+	palette::UiColor,
+	theme::{self, ThemeName},
+	toasts::Toasts,
+	tr,
 	world::Sector,
 	ClArgs,
 };
 use bytemuck::cast_slice;
-use egui::{Align2, Color32, Context, Pos2, ViewportId};
+use egui::{
+	Align2, Area, ComboBox, Context, FontData, Id, Pos2, SelectableLabel, TextureId, ViewportId,
+};
 use egui_wgpu::{Renderer as EguiRenderer, ScreenDescriptor};
 use egui_winit::State as EguiState;
-use image::GenericImageView;
-use log::{error, info, warn};
-use nalgebra::{vector, Isometry3, Perspective3, Translation3, Vector3};
-use solarscape_shared::data::world::BlockType;
+use image::RgbaImage;
+use log::{info, warn};
+use nalgebra::{vector, Isometry3, Matrix4, Perspective3, Point3, Translation3, Vector3};
+use rustc_hash::FxHasher;
+use solarscape_shared::{
+	data::world::{BlockState, BlockType},
+	message::{clientbound::Severity, serverbound::ViewHint},
+};
 use std::{
-	collections::{HashMap, VecDeque},
-	fmt::Write,
+	borrow::Cow,
+	collections::{HashMap, HashSet, VecDeque},
+	f32::consts::TAU,
+	fmt::{self, Display, Formatter, Write},
+	hash::{Hash, Hasher},
 	iter::once,
+	num::NonZeroU64,
 	str::FromStr,
 	sync::Arc,
 	time::{Duration, Instant},
 };
 use thiserror::Error;
-use tobj::GPU_LOAD_OPTIONS;
+use tobj::Model;
 use tokio::runtime::Handle;
 use wgpu::{
-	include_wgsl,
-	rwh::HandleError,
 	util::{BufferInitDescriptor, DeviceExt, TextureDataOrder::LayerMajor},
-	vertex_attr_array, Backends, BindGroup, BindGroupDescriptor, BindGroupEntry,
-	BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendState,
-	Buffer, BufferUsages, Color, ColorTargetState, ColorWrites, CommandEncoderDescriptor,
+	vertex_attr_array, AdapterInfo, Backends, BindGroup, BindGroupDescriptor, BindGroupEntry,
+	BindGroupLayout, BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType,
+	BlendState, Buffer, BufferBinding, BufferBindingType, BufferDescriptor, BufferUsages, Color,
+	ColorTargetState, ColorWrites, CommandEncoder, CommandEncoderDescriptor,
 	CompareFunction::LessEqual,
 	CompositeAlphaMode::Opaque,
-	CreateSurfaceError, DepthStencilState, Device, DeviceDescriptor, Dx12Compiler, Extent3d,
+	CreateSurfaceError, DepthStencilState, Device, DeviceDescriptor, Dx12Compiler, ErrorFilter,
+	Extent3d,
 	Face::Back,
 	Features, FragmentState,
 	FrontFace::Ccw,
 	Gles3MinorVersion::Version0,
 	IndexFormat, Instance, InstanceDescriptor, InstanceFlags, Limits,
-	LoadOp::Clear,
+	LoadOp::{Clear, Load},
 	MemoryHints::Performance,
-	MultisampleState, Operations, PipelineCompilationOptions, PipelineLayoutDescriptor,
-	PolygonMode::Fill,
-	PowerPreference::HighPerformance,
+	MultisampleState, Operations, PipelineCompilationOptions, PipelineLayout,
+	PipelineLayoutDescriptor,
+	PolygonMode::{self, Fill},
 	PresentMode::AutoNoVsync,
 	PrimitiveState,
 	PrimitiveTopology::{LineList, TriangleList},
 	PushConstantRange, Queue, RenderPass, RenderPassColorAttachment,
 	RenderPassDepthStencilAttachment, RenderPassDescriptor, RenderPipeline,
-	RenderPipelineDescriptor, RequestAdapterOptions, RequestDeviceError,
+	RenderPipelineDescriptor, RequestDeviceError,
 	SamplerBindingType::NonFiltering,
-	SamplerDescriptor, ShaderStages,
+	SamplerDescriptor, ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages,
 	StoreOp::Store,
-	Surface, SurfaceConfiguration, SurfaceTargetUnsafe, Texture, TextureDescriptor,
-	TextureDimension::{self, D2},
+	Surface, SurfaceConfiguration, Texture, TextureDescriptor,
+	TextureDimension::D2,
 	TextureFormat::{self, Depth32Float, Rgba8UnormSrgb},
 	TextureSampleType::Float,
 	TextureUsages, TextureView, TextureViewDescriptor, TextureViewDimension, VertexBufferLayout,
 	VertexState, VertexStepMode,
 };
 use winit::{
-	dpi::{LogicalPosition, PhysicalSize},
+	dpi::{PhysicalPosition, PhysicalSize},
 	error::OsError,
-	event::WindowEvent,
+	event::{ElementState, KeyEvent, WindowEvent},
 	event_loop::ActiveEventLoop,
+	keyboard::{KeyCode, PhysicalKey},
 	window::{CursorGrabMode, Window},
 };
 
 pub struct Renderer {
 	// Window & Surface
-	// SAFETY: Window must be first so that it outlives Surface!
-	pub window: Window,
+	pub window: Arc<Window>,
 	surface: Surface<'static>,
 	config: SurfaceConfiguration,
 
 	// Device & Queue
 	// This may be worth splitting out into it's own struct stored in an Arc<T> later
+	adapter_info: AdapterInfo,
 	device: Device,
 	queue: Queue,
+	requirements: Requirements,
 
 	// Frame time information, we will probably improve the infrastructure
 	// around this later to deliver a more detailed breakdown
@@ -86,54 +113,100 @@ pub struct Renderer {
 	frame_time_average: Duration,
 	frames_per_second: usize,
 
+	pub frame_limiter: FrameLimiter,
+
+	/// Whether the player has opted in to sending anonymous performance data, see [`crate::telemetry`].
+	pub share_telemetry: bool,
+
+	accessibility: Accessibility,
+	display: DisplaySettings,
+
+	/// Bytes of GPU memory to warn about going over, 0 to never warn.
+	pub gpu_memory_limit: u64,
+	over_gpu_memory_limit: bool,
+
 	// Egui
 	egui_state: EguiState,
 	egui_renderer: EguiRenderer,
+	egui_textures: HashSet<TextureId>,
 
 	// Depth Buffer
 	depth_buffer_descriptor: TextureDescriptor<'static>,
-	depth_buffer: Texture,
-	depth_buffer_view: TextureView,
+	depth_buffer: Tracked<Texture>,
 
 	// Camera
 	// Might be worth moving later
 	perspective: Perspective3<f32>,
+	world_constants: WorldConstants,
+
+	// Debug Render Modes
+	render_mode: RenderMode,
+	polygon_mode_line_supported: bool,
 
 	// World Rendering
 	// Might be worth moving later
-	chunk_pipeline: RenderPipeline,
+	chunk_shader: ShaderModule,
+	chunk_pipeline_layout: PipelineLayout,
+	chunk_pipelines: HashMap<RenderMode, RenderPipeline>,
+	terrain_textures_bind_group_layout: BindGroupLayout,
+	_terrain_textures: Tracked<Texture>,
 	terrain_textures_bind_group: BindGroup,
 
 	// Structure Rendering
 	// Might also be worth moving later
-	structure_block_pipeline: RenderPipeline,
+	structure_block_shader: ShaderModule,
+	structure_block_pipeline_layout: PipelineLayout,
+	structure_block_pipelines: HashMap<(RenderMode, BlockPass), RenderPipeline>,
 	structure_block_data: HashMap<BlockType, Arc<BlockRenderData>>,
+	structure_block_bind_group_layout: BindGroupLayout,
+	_structure_block_texture: Tracked<Texture>,
 	structure_block_bind_group: BindGroup,
 
 	// Debug Rendering
-	debug_line_pipeline: RenderPipeline,
+	/// [`None`] without push constants, debug lines set them for every line and have no uniform buffer fallback.
+	debug_line_pipeline: Option<RenderPipeline>,
+
+	// World Border Rendering
+	/// [`None`] without push constants, like [`Renderer::debug_line_pipeline`]. The border isn't drawn without them.
+	world_border_pipeline: Option<RenderPipeline>,
+
+	// Assets
+	assets: Assets,
+	/// Problems loading assets, these are shown in every state so they're kept separately from the world's.
+	toasts: Toasts,
+
+	#[cfg(debug)]
+	pub console: Console,
 }
 
 struct BlockRenderData {
-	positions: Buffer,
-	texture_coordinates: Buffer,
-	indices: Buffer,
+	positions: Tracked<Buffer>,
+	texture_coordinates: Tracked<Buffer>,
+	indices: Tracked<Buffer>,
 
 	index_count: u32,
+
+	/// [`None`] if the model's texture coordinates already cover the whole atlas, rather than a single cell.
+	atlas_cell: Option<u32>,
 }
 
-impl Renderer {
-	pub fn new(event_loop: &ActiveEventLoop) -> Result<Self, RenderInitError> {
-		let start_time = Instant::now();
+impl BlockRenderData {
+	/// Atlas cell to put in the instance buffer for `block` in `state`, see `structure.wgsl`. Models are fitted to
+	/// their block's cell in state 0, so any other cell of the same size works with them too.
+	fn instance_atlas_cell(&self, block: BlockType, state: BlockState) -> u32 {
+		match self.atlas_cell {
+			Some(_) => block.atlas_cell(state),
+			None => u32::MAX,
+		}
+	}
+}
 
-		let instance = Instance::new(InstanceDescriptor {
-			backends: Backends::VULKAN | Backends::GL,
-			flags: InstanceFlags::empty(),
-			dx12_shader_compiler: Dx12Compiler::default(), // DirectX is not supported, don't care
-			gles_minor_version: Version0,
-		});
+/// Structure block textures are laid out in a grid of square cells, each this many pixels wide.
+const ATLAS_CELL_SIZE: u32 = 16;
 
-		let window = event_loop.create_window(
+pub fn create_window(event_loop: &ActiveEventLoop) -> Result<Arc<Window>, OsError> {
+	event_loop
+		.create_window(
 			Window::default_attributes()
 				.with_maximized(true)
 				.with_inner_size(PhysicalSize {
@@ -141,77 +214,157 @@ impl Renderer {
 					height: 480,
 				})
 				.with_title("Solarscape"),
-		)?;
+		)
+		.map(Arc::new)
+}
 
-		let surface =
-			unsafe { instance.create_surface_unsafe(SurfaceTargetUnsafe::from_window(&window)?) }?;
+impl Renderer {
+	/// Starts the renderer with [`Requirements::Full`], falling back to [`Requirements::Reduced`] if that fails. If
+	/// neither works, returns why so that it can be shown to the player.
+	pub fn start(
+		window: Arc<Window>,
+		adapter_preference: &AdapterPreference,
+		assets: &Assets,
+	) -> Result<Self, Diagnostics> {
+		let mut failures = vec![];
+
+		for requirements in Requirements::ALL {
+			match Self::new(
+				window.clone(),
+				adapter_preference,
+				requirements,
+				assets.clone(),
+			) {
+				Ok(renderer) => return Ok(renderer),
+				Err(error) => {
+					warn!(
+						"Unable to start the renderer with {} requirements: {error}",
+						requirements.name()
+					);
+					failures.push((requirements, error.to_string()));
+				}
+			}
+		}
 
-		let adapter = Handle::current()
-			.block_on(instance.request_adapter(&RequestAdapterOptions {
-				power_preference: HighPerformance,
-				force_fallback_adapter: false,
-				compatible_surface: Some(&surface),
-			}))
-			.ok_or(RenderInitError::NoAdapter)?;
+		Err(Diagnostics::collect(failures))
+	}
+
+	pub fn new(
+		window: Arc<Window>,
+		adapter_preference: &AdapterPreference,
+		requirements: Requirements,
+		mut assets: Assets,
+	) -> Result<Self, RenderInitError> {
+		let start_time = Instant::now();
+
+		let instance = Instance::new(InstanceDescriptor {
+			backends: requirements.backends(adapter_preference),
+			flags: InstanceFlags::empty(),
+			dx12_shader_compiler: Dx12Compiler::default(),
+			gles_minor_version: Version0,
+		});
+
+		let surface = instance.create_surface(window.clone())?;
+
+		let adapter = adapter::select_adapter(&instance, &surface, adapter_preference)?;
+		let adapter_info = adapter.get_info();
+
+		info!(
+			"Using adapter: {} with {} requirements",
+			Described(&adapter_info),
+			requirements.name()
+		);
+
+		let constants = ShaderConstants::select(requirements, adapter.features());
+
+		// Only needed for the wireframe render mode, so only request it if it's available
+		let polygon_mode_line_supported = requirements == Requirements::Full
+			&& adapter.features().contains(Features::POLYGON_MODE_LINE);
+
+		let mut required_features = Features::empty();
+		required_features.set(
+			Features::PUSH_CONSTANTS,
+			constants == ShaderConstants::PushConstants,
+		);
+		required_features.set(Features::POLYGON_MODE_LINE, polygon_mode_line_supported);
+
+		let required_limits = match requirements {
+			Requirements::Full => Limits {
+				// General Limits
+				max_buffer_size: u64::pow(2, 17),
+
+				// Solarscape Required Limits
+				max_bindings_per_bind_group: 2,
+				max_color_attachment_bytes_per_sample: 8,
+				max_color_attachments: 1,
+				max_inter_stage_shader_components: 11,
+				max_push_constant_size: match constants {
+					ShaderConstants::PushConstants => WORLD_CONSTANTS_SIZE,
+					ShaderConstants::UniformBuffer => 0,
+				},
+				max_sampled_textures_per_shader_stage: 1,
+				max_samplers_per_shader_stage: 1,
+				max_texture_array_layers: 1,
+				max_vertex_attributes: 8,
+				max_vertex_buffer_array_stride: 84,
+				max_vertex_buffers: 3,
+
+				// This also determines the limit of our window resolution, so we'll request what the GPU supports
+				max_texture_dimension_2d: adapter.limits().max_texture_dimension_2d,
+
+				// These are minimums, not maximums, so we'll just request what the GPU supports
+				min_storage_buffer_offset_alignment: adapter
+					.limits()
+					.min_storage_buffer_offset_alignment,
+				min_subgroup_size: adapter.limits().min_subgroup_size,
+				min_uniform_buffer_offset_alignment: adapter
+					.limits()
+					.min_uniform_buffer_offset_alignment,
+
+				// Limits that seem to be imposed by Egui, and by the world constants without push constants
+				max_bind_groups: 2,
+				max_uniform_buffer_binding_size: match constants {
+					ShaderConstants::PushConstants => 16,
+					ShaderConstants::UniformBuffer => WORLD_CONSTANTS_SIZE,
+				},
+				max_uniform_buffers_per_shader_stage: 1,
+
+				// Unused / Undetermined
+				max_compute_invocations_per_workgroup: 0,
+				max_compute_workgroup_size_x: 0,
+				max_compute_workgroup_size_y: 0,
+				max_compute_workgroup_size_z: 0,
+				max_compute_workgroup_storage_size: 0,
+				max_compute_workgroups_per_dimension: 0,
+				max_dynamic_storage_buffers_per_pipeline_layout: 0,
+				max_dynamic_uniform_buffers_per_pipeline_layout: 0,
+				max_non_sampler_bindings: 0,
+				max_storage_buffer_binding_size: 0,
+				max_storage_buffers_per_shader_stage: 0,
+				max_storage_textures_per_shader_stage: 0,
+				max_subgroup_size: 0,
+				max_texture_dimension_1d: 0,
+				max_texture_dimension_3d: 0,
+			},
+			// What any device should manage, rather than only what we need
+			Requirements::Reduced => Limits::downlevel_webgl2_defaults()
+				.using_resolution(adapter.limits())
+				.using_alignment(adapter.limits()),
+		};
 
 		let (device, queue) = Handle::current().block_on(adapter.request_device(
 			&DeviceDescriptor {
 				label: Some("renderer#device"),
-				required_features: Features::PUSH_CONSTANTS,
-				required_limits: Limits {
-					// General Limits
-					max_buffer_size: u64::pow(2, 17),
-
-					// Solarscape Required Limits
-					max_bindings_per_bind_group: 2,
-					max_color_attachment_bytes_per_sample: 8,
-					max_color_attachments: 1,
-					max_inter_stage_shader_components: 11,
-					max_push_constant_size: 112,
-					max_sampled_textures_per_shader_stage: 1,
-					max_samplers_per_shader_stage: 1,
-					max_texture_array_layers: 1,
-					max_vertex_attributes: 7,
-					max_vertex_buffer_array_stride: 68,
-					max_vertex_buffers: 3,
-
-					// This also determines the limit of our window resolution, so we'll request what the GPU supports
-					max_texture_dimension_2d: adapter.limits().max_texture_dimension_2d,
-
-					// These are minimums, not maximums, so we'll just request what the GPU supports
-					min_storage_buffer_offset_alignment:
-						adapter.limits().min_storage_buffer_offset_alignment,
-					min_subgroup_size: adapter.limits().min_subgroup_size,
-					min_uniform_buffer_offset_alignment:
-						adapter.limits().min_uniform_buffer_offset_alignment,
-
-					// Limits that seem to be imposed by Egui
-					max_bind_groups: 2,
-					max_uniform_buffer_binding_size: 16,
-					max_uniform_buffers_per_shader_stage: 1,
-
-					// Unused / Undetermined
-					max_compute_invocations_per_workgroup: 0,
-					max_compute_workgroup_size_x: 0,
-					max_compute_workgroup_size_y: 0,
-					max_compute_workgroup_size_z: 0,
-					max_compute_workgroup_storage_size: 0,
-					max_compute_workgroups_per_dimension: 0,
-					max_dynamic_storage_buffers_per_pipeline_layout: 0,
-					max_dynamic_uniform_buffers_per_pipeline_layout: 0,
-					max_non_sampler_bindings: 0,
-					max_storage_buffer_binding_size: 0,
-					max_storage_buffers_per_shader_stage: 0,
-					max_storage_textures_per_shader_stage: 0,
-					max_subgroup_size: 0,
-					max_texture_dimension_1d: 0,
-					max_texture_dimension_3d: 0,
-				},
+				required_features,
+				required_limits,
 				memory_hints: Performance,
 			},
 			None,
 		))?;
 
+		// Validation errors would otherwise panic, catch them so that the next requirements can be tried instead
+		device.push_error_scope(ErrorFilter::Validation);
+
 		let surface_capabilities = surface.get_capabilities(&adapter);
 
 		let surface_format = surface_capabilities
@@ -236,424 +389,73 @@ impl Renderer {
 
 		surface.configure(&device, &config);
 
-		let terrain_textures_image =
-			image::load_from_memory(include_bytes!("resources/terrain_textures.png"))
-				.expect("terrain_textures.png must be valid");
-		let terrain_textures_rgba8 = terrain_textures_image.to_rgba8();
-		let (terrain_textures_width, terrain_textures_height) = terrain_textures_image.dimensions();
-		let terrain_textures_size = Extent3d {
-			width: terrain_textures_width,
-			height: terrain_textures_height,
-			depth_or_array_layers: 1,
+		let mut toasts = Toasts::new("renderer.toasts", Align2::CENTER_BOTTOM);
+		let mut loader = AssetLoader {
+			device: &device,
+			queue: &queue,
+			assets: &mut assets,
+			toasts: &mut toasts,
 		};
 
-		let terrain_textures = device.create_texture_with_data(
-			&queue,
-			&TextureDescriptor {
-				label: Some("renderer.voxject#texture"),
-				size: terrain_textures_size,
-				mip_level_count: 1,
-				sample_count: 1,
-				dimension: TextureDimension::D2,
-				format: Rgba8UnormSrgb,
-				usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
-				view_formats: &[],
-			},
-			LayerMajor,
-			&terrain_textures_rgba8,
+		let terrain_textures_bind_group_layout = create_atlas_bind_group_layout(
+			&device,
+			"renderer.voxject#texture_bind_group_layout",
+			ShaderStages::FRAGMENT,
 		);
 
-		let terrain_textures_view = terrain_textures.create_view(&TextureViewDescriptor::default());
-		let terrain_textures_sampler = device.create_sampler(&SamplerDescriptor::default());
-
-		let terrain_textures_bind_group_layout =
-			device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-				label: Some("renderer.voxject#texture_bind_group_layout"),
-				entries: &[
-					BindGroupLayoutEntry {
-						binding: 0,
-						visibility: ShaderStages::FRAGMENT,
-						ty: BindingType::Texture {
-							sample_type: Float { filterable: false },
-							view_dimension: TextureViewDimension::D2,
-							multisampled: false,
-						},
-						count: None,
-					},
-					BindGroupLayoutEntry {
-						binding: 1,
-						visibility: ShaderStages::FRAGMENT,
-						ty: BindingType::Sampler(NonFiltering),
-						count: None,
-					},
-				],
-			});
+		let (terrain_textures, terrain_textures_bind_group) =
+			loader.terrain_textures(&terrain_textures_bind_group_layout)?;
 
-		let terrain_textures_bind_group = device.create_bind_group(&BindGroupDescriptor {
-			label: Some("renderer.voxject#texture_bind_group"),
-			layout: &terrain_textures_bind_group_layout,
-			entries: &[
-				BindGroupEntry {
-					binding: 0,
-					resource: BindingResource::TextureView(&terrain_textures_view),
-				},
-				BindGroupEntry {
-					binding: 1,
-					resource: BindingResource::Sampler(&terrain_textures_sampler),
-				},
-			],
-		});
+		// The vertex shader needs the texture's size to find atlas cells
+		let structure_block_bind_group_layout = create_atlas_bind_group_layout(
+			&device,
+			"Block Renderer > Bind Group Layout",
+			ShaderStages::VERTEX_FRAGMENT,
+		);
+
+		let StructureBlocks {
+			data: structure_block_data,
+			texture: structure_block_texture,
+			bind_group: structure_block_bind_group,
+		} = loader.structure_blocks(&structure_block_bind_group_layout)?;
 
-		let chunk_shader = device.create_shader_module(include_wgsl!("chunk.wgsl"));
+		let (world_constants, world_constants_bind_group_layout) =
+			WorldConstants::new(&device, constants);
 
 		let chunk_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
 			label: Some("renderer.voxject#pipeline_layout"),
-			bind_group_layouts: &[&terrain_textures_bind_group_layout],
-			push_constant_ranges: &[PushConstantRange {
-				stages: ShaderStages::VERTEX,
-				range: 0..76,
-			}],
-		});
-
-		let chunk_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-			label: Some("renderer.voxject#pipeline"),
-			layout: Some(&chunk_pipeline_layout),
-			vertex: VertexState {
-				module: &chunk_shader,
-				entry_point: "vertex",
-				compilation_options: PipelineCompilationOptions::default(),
-				buffers: &[
-					VertexBufferLayout {
-						array_stride: 12,
-						step_mode: VertexStepMode::Vertex,
-						attributes: &vertex_attr_array![0 => Float32x3],
-					},
-					VertexBufferLayout {
-						array_stride: 20,
-						step_mode: VertexStepMode::Vertex,
-						attributes: &vertex_attr_array![1 => Float32x3, 2 => Uint8x2, 3 => Uint8x2, 4 => Float32],
-					},
-					VertexBufferLayout {
-						array_stride: 16,
-						step_mode: VertexStepMode::Instance,
-						attributes: &vertex_attr_array![5 => Float32x3, 6 => Float32],
-					},
-				],
-			},
-			primitive: PrimitiveState {
-				topology: TriangleList,
-				strip_index_format: None,
-				front_face: Ccw,
-				cull_mode: Some(Back),
-				unclipped_depth: false,
-				polygon_mode: Fill,
-				conservative: false,
-			},
-			depth_stencil: Some(DepthStencilState {
-				format: Depth32Float,
-				depth_write_enabled: true,
-				depth_compare: LessEqual,
-				stencil: Default::default(),
-				bias: Default::default(),
-			}),
-			multisample: MultisampleState {
-				count: 1,
-				mask: !0,
-				alpha_to_coverage_enabled: false,
-			},
-			fragment: Some(FragmentState {
-				module: &chunk_shader,
-				entry_point: "fragment",
-				compilation_options: PipelineCompilationOptions::default(),
-				targets: &[Some(ColorTargetState {
-					format: config.format,
-					blend: Some(BlendState::REPLACE),
-					write_mask: ColorWrites::ALL,
-				})],
-			}),
-			multiview: None,
-			cache: None,
-		});
-
-		let structure_block_data = {
-			let (structure_block_models, _) = tobj::load_obj_buf(
-				&mut &include_bytes!("resources/structure_blocks.obj")[..],
-				&GPU_LOAD_OPTIONS,
-				// We don't care about the material, but this is required so...
-				|path| match path.file_name().unwrap().to_str().unwrap() == "structure_blocks.mtl" {
-					true => tobj::load_mtl_buf(&mut &include_bytes!("resources/structure_blocks.mtl")[..]),
-					false => panic!("attempted to use unknown material resource"),
-				},
-			)
-			.expect("resources/structure_blocks.obj provided at compile time should be a valid .obj file");
-
-			let mut missing_block = None;
-			let mut structure_blocks = HashMap::with_capacity(BlockType::ALL.len());
-
-			for mut model in structure_block_models {
-				for coord in model.mesh.texcoords.iter_mut().skip(1).step_by(2) {
-					*coord = 1.0 - *coord;
-				}
-
-				let block_render_data = Arc::new(BlockRenderData {
-					positions: device.create_buffer_init(&BufferInitDescriptor {
-						label: Some(&format!(
-							"Block Renderer > Block '{}' > Positions",
-							model.name
-						)),
-						contents: cast_slice(&model.mesh.positions),
-						usage: BufferUsages::VERTEX,
-					}),
-					texture_coordinates: device.create_buffer_init(&BufferInitDescriptor {
-						label: Some(&format!(
-							"Block Renderer > Block '{}' > Texture Coordinates",
-							model.name
-						)),
-						contents: cast_slice(&model.mesh.texcoords),
-						usage: BufferUsages::VERTEX,
-					}),
-					indices: device.create_buffer_init(&BufferInitDescriptor {
-						label: Some(&format!(
-							"Block Renderer > Block '{}' > Indices",
-							model.name
-						)),
-						contents: cast_slice(&model.mesh.indices),
-						usage: BufferUsages::INDEX,
-					}),
-					index_count: model.mesh.indices.len() as u32,
-				});
-
-				match BlockType::from_str(&model.name) {
-					Ok(block) => {
-						if structure_blocks.insert(block, block_render_data).is_some() {
-							warn!("Found duplicate model for block {block:?}! This may be a modelling error and could result in broken block models.");
-						}
-					}
-					Err(_) if model.name == "MissingBlock" => {
-						if missing_block.replace(block_render_data).is_some() {
-							warn!("Found duplicate model for block MissingBlock! This may be a modelling error and could result in broken block models.");
-						}
-					}
-					Err(_) => {}
-				}
-			}
-
-			let missing_block = match missing_block {
-				Some(missing_block) => missing_block,
-				None => panic!("No model found for MissingBlock. This block is required as it serves as a placeholder for other missing blocks."),
-			};
-
-			for block in BlockType::ALL {
-				if !structure_blocks.contains_key(block) {
-					warn!("No model found for block {block:?}, a placeholder will be used instead. This will result in broken block models");
-					structure_blocks.insert(*block, missing_block.clone());
-				}
-			}
-
-			structure_blocks
-		};
-
-		let structure_block_textures_raw =
-			image::load_from_memory(include_bytes!("resources/structure_block_textures.png"))
-				.expect("structure_block_textures.png must be valid")
-				.to_rgba8();
-		let (structure_block_textures_width, structure_block_textures_height) =
-			structure_block_textures_raw.dimensions();
-
-		let structure_block_texture = device.create_texture_with_data(
-			&queue,
-			&TextureDescriptor {
-				label: Some("Block Renderer > Texture"),
-				size: Extent3d {
-					width: structure_block_textures_width,
-					height: structure_block_textures_height,
-					depth_or_array_layers: 1,
-				},
-				mip_level_count: 1,
-				sample_count: 1,
-				dimension: D2,
-				format: Rgba8UnormSrgb,
-				usage: TextureUsages::TEXTURE_BINDING,
-				view_formats: &[],
-			},
-			LayerMajor,
-			&structure_block_textures_raw,
-		);
-
-		let structure_block_texture_view =
-			structure_block_texture.create_view(&TextureViewDescriptor::default());
-		let structure_block_texture_sampler = device.create_sampler(&SamplerDescriptor::default());
-
-		let structure_blocks_bind_group_layout =
-			device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-				label: Some("Block Renderer > Bind Group Layout"),
-				entries: &[
-					BindGroupLayoutEntry {
-						binding: 0,
-						visibility: ShaderStages::FRAGMENT,
-						ty: BindingType::Texture {
-							sample_type: Float { filterable: false },
-							view_dimension: TextureViewDimension::D2,
-							multisampled: false,
-						},
-						count: None,
-					},
-					BindGroupLayoutEntry {
-						binding: 1,
-						visibility: ShaderStages::FRAGMENT,
-						ty: BindingType::Sampler(NonFiltering),
-						count: None,
-					},
-				],
-			});
-
-		let structure_block_bind_group = device.create_bind_group(&BindGroupDescriptor {
-			label: Some("Block Renderer > Bind Group"),
-			layout: &structure_blocks_bind_group_layout,
-			entries: &[
-				BindGroupEntry {
-					binding: 0,
-					resource: BindingResource::TextureView(&structure_block_texture_view),
-				},
-				BindGroupEntry {
-					binding: 1,
-					resource: BindingResource::Sampler(&structure_block_texture_sampler),
-				},
-			],
+			bind_group_layouts: &once(&terrain_textures_bind_group_layout)
+				.chain(&world_constants_bind_group_layout)
+				.collect::<Vec<_>>(),
+			push_constant_ranges: constants.push_constant_ranges(),
 		});
 
-		let structure_block_shader = device.create_shader_module(include_wgsl!("structure.wgsl"));
-
 		let structure_block_pipeline_layout =
 			device.create_pipeline_layout(&PipelineLayoutDescriptor {
 				label: Some("Block Renderer > Pipeline Layout"),
-				bind_group_layouts: &[&structure_blocks_bind_group_layout],
-				push_constant_ranges: &[PushConstantRange {
-					stages: ShaderStages::VERTEX,
-					range: 0..64,
-				}],
+				bind_group_layouts: &once(&structure_block_bind_group_layout)
+					.chain(&world_constants_bind_group_layout)
+					.collect::<Vec<_>>(),
+				push_constant_ranges: constants.push_constant_ranges(),
 			});
 
-		let structure_block_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-			label: Some("Block Renderer > Pipeline"),
-			layout: Some(&structure_block_pipeline_layout),
-			vertex: VertexState {
-				module: &structure_block_shader,
-				entry_point: "vertex",
-				compilation_options: PipelineCompilationOptions::default(),
-				buffers: &[
-					VertexBufferLayout {
-						array_stride: 12,
-						step_mode: VertexStepMode::Vertex,
-						attributes: &vertex_attr_array![0 => Float32x3],
-					},
-					VertexBufferLayout {
-						array_stride: 8,
-						step_mode: VertexStepMode::Vertex,
-						attributes: &vertex_attr_array![1 => Float32x2],
-					},
-					VertexBufferLayout {
-						array_stride: 36,
-						step_mode: VertexStepMode::Instance,
-						attributes: &vertex_attr_array![2 => Float32x4, 3 => Float32x4, 4 => Float32x4, 5 => Float32x4, 6 => Float32],
-					},
-				],
-			},
-			primitive: PrimitiveState {
-				topology: TriangleList,
-				strip_index_format: None,
-				front_face: Ccw,
-				cull_mode: Some(Back),
-				unclipped_depth: false,
-				polygon_mode: Fill,
-				conservative: false,
-			},
-			depth_stencil: Some(DepthStencilState {
-				format: Depth32Float,
-				depth_write_enabled: true,
-				depth_compare: LessEqual,
-				stencil: Default::default(),
-				bias: Default::default(),
-			}),
-			multisample: MultisampleState {
-				count: 1,
-				mask: !0,
-				alpha_to_coverage_enabled: false,
-			},
-			fragment: Some(FragmentState {
-				module: &structure_block_shader,
-				entry_point: "fragment",
-				compilation_options: PipelineCompilationOptions::default(),
-				targets: &[Some(ColorTargetState {
-					format: config.format,
-					blend: Some(BlendState::ALPHA_BLENDING),
-					write_mask: ColorWrites::ALL,
-				})],
-			}),
-			multiview: None,
-			cache: None,
-		});
-
-		let debug_line_shader = device.create_shader_module(include_wgsl!("debug_line.wgsl"));
-
-		let debug_line_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
-			label: Some("Debug Renderer > Pipeline Layout"),
-			bind_group_layouts: &[],
-			push_constant_ranges: &[
-				PushConstantRange {
-					stages: ShaderStages::VERTEX,
-					range: 0..96,
-				},
-				PushConstantRange {
-					stages: ShaderStages::FRAGMENT,
-					range: 96..112,
-				},
-			],
-		});
+		let Shaders {
+			chunk_shader,
+			chunk_pipeline,
+			structure_block_shader,
+			structure_block_pipeline,
+			debug_line_pipeline,
+			world_border_pipeline,
+		} = loader.shaders(
+			constants,
+			&chunk_pipeline_layout,
+			&structure_block_pipeline_layout,
+			config.format,
+		)?;
 
-		let debug_line_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-			label: Some("Debug Renderer > Pipeline"),
-			layout: Some(&debug_line_pipeline_layout),
-			vertex: VertexState {
-				module: &debug_line_shader,
-				entry_point: "vertex",
-				compilation_options: PipelineCompilationOptions::default(),
-				buffers: &[],
-			},
-			primitive: PrimitiveState {
-				topology: LineList,
-				strip_index_format: None,
-				front_face: Ccw,
-				cull_mode: None,
-				unclipped_depth: false,
-				polygon_mode: Fill,
-				conservative: false,
-			},
-			depth_stencil: Some(DepthStencilState {
-				format: Depth32Float,
-				depth_write_enabled: true,
-				depth_compare: LessEqual,
-				stencil: Default::default(),
-				bias: Default::default(),
-			}),
-			multisample: MultisampleState {
-				count: 1,
-				mask: !0,
-				alpha_to_coverage_enabled: false,
-			},
-			fragment: Some(FragmentState {
-				module: &debug_line_shader,
-				entry_point: "fragment",
-				compilation_options: PipelineCompilationOptions::default(),
-				targets: &[Some(ColorTargetState {
-					format: config.format,
-					blend: Some(BlendState::REPLACE),
-					write_mask: ColorWrites::ALL,
-				})],
-			}),
-			multiview: None,
-			cache: None,
-		});
+		if let Some(error) = Handle::current().block_on(device.pop_error_scope()) {
+			return Err(RenderInitError::Validation(error.to_string()));
+		}
 
 		let depth_buffer_descriptor = TextureDescriptor {
 			label: Some("renderer.depth_buffer#buffer"),
@@ -670,23 +472,36 @@ impl Renderer {
 			view_formats: &[],
 		};
 
-		let depth_buffer_view_descriptor = TextureViewDescriptor {
-			label: Some("renderer.depth_buffer#view"),
-			..TextureViewDescriptor::default()
-		};
-
-		let depth_buffer = device.create_texture(&depth_buffer_descriptor);
-		let depth_buffer_view = depth_buffer.create_view(&depth_buffer_view_descriptor);
+		let depth_buffer = Tracked::texture(
+			GpuCategory::Textures,
+			device.create_texture(&depth_buffer_descriptor),
+		);
 
 		let debug_state = EguiState::new(
 			Context::default(),
 			ViewportId::default(),
-			&window,
+			&*window,
 			None,
 			None,
 			None,
 		);
-		let egui_renderer = EguiRenderer::new(&device, config.format, Some(Depth32Float), 1, false);
+		let accessibility = Accessibility::load();
+		accessibility.apply(debug_state.egui_ctx());
+
+		let mut loader = AssetLoader {
+			device: &device,
+			queue: &queue,
+			assets: &mut assets,
+			toasts: &mut toasts,
+		};
+		theme::set_font(debug_state.egui_ctx(), loader.ui_font());
+
+		// The surface is reconfigured once the window has resized to match
+		let display = DisplaySettings::load();
+		display.apply(&window);
+
+		// The UI is drawn in it's own pass on top of everything else, so it doesn't need depth
+		let egui_renderer = EguiRenderer::new(&device, config.format, None, 1, false);
 
 		info!(
 			"Renderer initialized in {:.0?}",
@@ -698,20 +513,32 @@ impl Renderer {
 			surface,
 			config,
 
+			adapter_info,
 			device,
 			queue,
+			requirements,
 
 			frame_times: VecDeque::new(),
 			frame_time_total: Duration::default(),
 			frame_time_average: Duration::default(),
 			frames_per_second: 0,
 
+			frame_limiter: FrameLimiter::new(FrameLimit::Uncapped),
+
+			share_telemetry: false,
+
+			accessibility,
+			display,
+
+			gpu_memory_limit: 0,
+			over_gpu_memory_limit: false,
+
 			egui_state: debug_state,
 			egui_renderer,
+			egui_textures: HashSet::new(),
 
 			depth_buffer_descriptor,
 			depth_buffer,
-			depth_buffer_view,
 
 			perspective: Perspective3::new(
 				width as f32 / height as f32,
@@ -719,19 +546,48 @@ impl Renderer {
 				0.05,
 				f32::MAX,
 			),
+			world_constants,
 
-			chunk_pipeline,
+			render_mode: RenderMode::Normal,
+			polygon_mode_line_supported,
+
+			chunk_shader,
+			chunk_pipeline_layout,
+			chunk_pipelines: HashMap::from([(RenderMode::Normal, chunk_pipeline)]),
+			terrain_textures_bind_group_layout,
+			_terrain_textures: terrain_textures,
 			terrain_textures_bind_group,
 
-			structure_block_pipeline,
+			structure_block_shader,
+			structure_block_pipeline_layout,
+			structure_block_pipelines: HashMap::from([(
+				(RenderMode::Normal, BlockPass::Opaque),
+				structure_block_pipeline,
+			)]),
 			structure_block_data,
+			structure_block_bind_group_layout,
+			_structure_block_texture: structure_block_texture,
 			structure_block_bind_group,
 
 			debug_line_pipeline,
+
+			world_border_pipeline,
+
+			assets,
+			toasts,
+
+			#[cfg(debug)]
+			console: Console::new(),
 		})
 	}
 
 	pub fn resize(&mut self, PhysicalSize { width, height }: PhysicalSize<u32>) {
+		// Minimized windows, and some platforms part way through switching to or from fullscreen, report a size of
+		// zero, which can't be configured. The surface keeps it's old size until the window has a real one again.
+		if width == 0 || height == 0 {
+			return;
+		}
+
 		self.config.width = width;
 		self.config.height = height;
 		self.surface.configure(&self.device, &self.config);
@@ -741,10 +597,10 @@ impl Renderer {
 			height,
 			depth_or_array_layers: 1,
 		};
-		self.depth_buffer = self.device.create_texture(&self.depth_buffer_descriptor);
-		self.depth_buffer_view = self
-			.depth_buffer
-			.create_view(&TextureViewDescriptor::default());
+		self.depth_buffer = Tracked::texture(
+			GpuCategory::Textures,
+			self.device.create_texture(&self.depth_buffer_descriptor),
+		);
 
 		self.perspective.set_aspect(width as f32 / height as f32);
 	}
@@ -756,164 +612,839 @@ impl Renderer {
 			self.frames_per_second, self.frame_time_average
 		)
 		.expect("should be able to write to string");
-	}
 
-	pub fn render(&mut self, cl_args: &ClArgs, state: &mut AnyState, debug_text: String) {
-		let frame_start = Instant::now();
+		writeln!(debug_text, "Adapter: {}", Described(&self.adapter_info))
+			.expect("should be able to write to string");
 
-		let output = match self.surface.get_current_texture() {
-			Ok(output) => output,
-			Err(error) => panic!("{error}"), // We can probably handle this more elegantly later
-		};
+		if self.requirements != Requirements::Full {
+			writeln!(
+				debug_text,
+				"Renderer: {} requirements, {}",
+				self.requirements.name(),
+				self.world_constants.constants().name()
+			)
+			.expect("should be able to write to string");
+		}
 
-		// Handle the GUI
-		let gui_input = self.egui_state.take_egui_input(&self.window);
+		writeln!(debug_text, "Render Mode: {} (F4)", self.render_mode.name())
+			.expect("should be able to write to string");
 
-		let gui_output = self.egui_state.egui_ctx().run(gui_input, |context| {
-			state.draw_ui(cl_args, &context);
+		writeln!(debug_text, "Egui Textures: {}", self.egui_textures.len())
+			.expect("should be able to write to string");
 
-			// Debug Text, we'll add a keybind to toggle this later
-			context.debug_painter().debug_text(
-				Pos2::default(),
-				Align2::LEFT_TOP,
-				Color32::WHITE,
-				debug_text.trim_end(),
-			);
-		});
+		write!(debug_text, "GPU Memory: {}", Mebibytes(GPU_MEMORY.total()))
+			.expect("should be able to write to string");
 
-		self.egui_state
-			.handle_platform_output(&self.window, gui_output.platform_output);
+		for category in GpuCategory::ALL {
+			write!(
+				debug_text,
+				", {} {}",
+				category.name(),
+				Mebibytes(GPU_MEMORY.get(category))
+			)
+			.expect("should be able to write to string");
+		}
 
-		let paint_jobs = self
-			.egui_state
-			.egui_ctx()
-			.tessellate(gui_output.shapes, 1.0);
-		let screen_descriptor = &ScreenDescriptor {
-			size_in_pixels: [self.config.width, self.config.height],
-			pixels_per_point: 1.0, // Don't know how to calculate this, come back to it later.
-		};
+		writeln!(debug_text).expect("should be able to write to string");
+	}
 
-		for (id, image_delta) in gui_output.textures_delta.set {
-			self.egui_renderer
-				.update_texture(&self.device, &self.queue, id, &image_delta);
+	/// Logs when estimated GPU memory usage goes over or back under [`Self::gpu_memory_limit`].
+	fn check_gpu_memory_limit(&mut self) {
+		// 0 disables the limit
+		if self.gpu_memory_limit == 0 {
+			return;
 		}
 
-		let view = output
-			.texture
-			.create_view(&TextureViewDescriptor::default());
-		let mut encoder = self
-			.device
-			.create_command_encoder(&CommandEncoderDescriptor::default());
-
-		self.egui_renderer.update_buffers(
-			&self.device,
-			&self.queue,
-			&mut encoder,
-			&paint_jobs,
-			&screen_descriptor,
-		);
+		let total = GPU_MEMORY.total();
+		let over = total > self.gpu_memory_limit;
 
-		{
-			let mut render_pass = encoder
-				.begin_render_pass(&RenderPassDescriptor {
-					color_attachments: &[Some(RenderPassColorAttachment {
-						ops: Operations {
-							load: Clear(Color::BLACK),
-							store: Store,
-						},
-						resolve_target: None,
-						view: &view,
-					})],
-					depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
-						view: &self.depth_buffer_view,
-						depth_ops: Some(Operations {
-							load: Clear(1.0),
-							store: Store,
-						}),
-						stencil_ops: None,
-					}),
-					..Default::default()
-				})
-				.forget_lifetime();
+		if over == self.over_gpu_memory_limit {
+			return;
+		}
 
-			state.render(self, &mut render_pass);
+		self.over_gpu_memory_limit = over;
 
-			self.egui_renderer
-				.render(&mut render_pass, &paint_jobs, &screen_descriptor);
+		match over {
+			true => warn!(
+				"Estimated GPU memory usage is {}, which is over the limit of {}",
+				Mebibytes(total),
+				Mebibytes(self.gpu_memory_limit)
+			),
+			false => info!(
+				"Estimated GPU memory usage is back under the limit of {}, now {}",
+				Mebibytes(self.gpu_memory_limit),
+				Mebibytes(total)
+			),
 		}
+	}
 
-		self.queue.submit(once(encoder.finish()));
-		output.present();
+	/// How long the last frame took to render.
+	pub fn last_frame_time(&self) -> Option<Duration> {
+		self.frame_times.back().copied()
+	}
 
-		let frame_time = Instant::now() - frame_start;
+	/// Identifies the adapter in telemetry without saying what it is.
+	pub fn adapter_summary_hash(&self) -> u64 {
+		let mut hasher = FxHasher::default();
 
-		self.frame_times.push_back(frame_time);
-		self.frame_time_total += frame_time;
+		self.adapter_info.name.hash(&mut hasher);
+		self.adapter_info.backend.hash(&mut hasher);
+		self.adapter_info.driver.hash(&mut hasher);
 
-		while self.frame_time_total > Duration::from_secs(1) {
-			let old_frame_time = self.frame_times.pop_front()
-				.expect("pop_front should not fail as it is only called if frame_time_total is more than 1 second which requires frame_times to be populated");
-			self.frame_time_total -= old_frame_time;
+		hasher.finish()
+	}
+
+	pub fn is_render_mode_supported(&self, render_mode: RenderMode) -> bool {
+		match render_mode {
+			RenderMode::Wireframe => self.polygon_mode_line_supported,
+			_ => true,
 		}
+	}
 
-		self.frame_time_average = match self.frame_times.is_empty() {
-			true => frame_time,
-			false => self.frame_time_total / self.frame_times.len() as u32,
-		};
+	/// Narrowest and widest vertical field of view in degrees, see [`Renderer::set_fov`].
+	#[cfg(debug)]
+	pub const MIN_FOV: f32 = 30.0;
+	#[cfg(debug)]
+	pub const MAX_FOV: f32 = 150.0;
 
-		self.frames_per_second =
-			(self.frame_times.len() as f64 / self.frame_time_total.as_secs_f64()).round() as usize;
+	/// Switches to `render_mode`, returns `false` if it isn't supported.
+	#[cfg(debug)]
+	pub fn set_render_mode(&mut self, render_mode: RenderMode) -> bool {
+		let supported = self.is_render_mode_supported(render_mode);
 
-		self.window.request_redraw();
+		if supported {
+			self.render_mode = render_mode;
+		}
+
+		supported
 	}
 
-	pub fn handle_window_event(&mut self, event: &WindowEvent) {
-		let _ = self.egui_state.on_window_event(&self.window, &event);
+	/// Sets the vertical field of view, from [`Renderer::MIN_FOV`] to [`Renderer::MAX_FOV`] degrees.
+	#[cfg(debug)]
+	pub fn set_fov(&mut self, degrees: f32) {
+		self.perspective.set_fovy(degrees.to_radians());
 	}
-}
 
-#[allow(unused_variables)]
-trait Render {
-	fn render(&mut self, renderer: &mut Renderer, render_pass: &mut RenderPass) {}
-}
+	/// Switches to the next supported [`RenderMode`].
+	pub fn cycle_render_mode(&mut self) {
+		let mut render_mode = self.render_mode;
 
-impl Render for AnyState {
-	fn render(&mut self, renderer: &mut Renderer, render_pass: &mut RenderPass) {
-		match self {
-			Self::Login(state) => state as &mut dyn Render,
-			Self::Sector(state) => state as &mut dyn Render,
+		loop {
+			render_mode = render_mode.next();
 
-			#[cfg(debug)]
-			Self::GuiTest(_) => return,
+			if self.is_render_mode_supported(render_mode) {
+				break;
+			}
 		}
-		.render(renderer, render_pass)
+
+		self.render_mode = render_mode;
+	}
+
+	/// Loads `reload` again from the assets directory, anything that can't be used keeps what's already loaded.
+	fn reload_assets(&mut self, reload: &[Asset]) {
+		let mut loader = AssetLoader {
+			device: &self.device,
+			queue: &self.queue,
+			assets: &mut self.assets,
+			toasts: &mut self.toasts,
+		};
+
+		if reload.contains(&Asset::TerrainTextures) {
+			match loader.terrain_textures(&self.terrain_textures_bind_group_layout) {
+				Ok((texture, bind_group)) => {
+					self._terrain_textures = texture;
+					self.terrain_textures_bind_group = bind_group;
+				}
+				Err(error) => loader.report(error),
+			}
+		}
+
+		if reload.contains(&Asset::StructureBlockTextures)
+			|| reload.contains(&Asset::StructureBlockModels)
+		{
+			match loader.structure_blocks(&self.structure_block_bind_group_layout) {
+				Ok(structure_blocks) => {
+					self.structure_block_data = structure_blocks.data;
+					self._structure_block_texture = structure_blocks.texture;
+					self.structure_block_bind_group = structure_blocks.bind_group;
+				}
+				Err(error) => loader.report(error),
+			}
+		}
+
+		let shaders = [
+			Asset::ChunkShader,
+			Asset::StructureShader,
+			Asset::DebugLineShader,
+			Asset::WorldBorderShader,
+		];
+
+		if shaders.iter().any(|shader| reload.contains(shader)) {
+			match loader.shaders(
+				self.world_constants.constants(),
+				&self.chunk_pipeline_layout,
+				&self.structure_block_pipeline_layout,
+				self.config.format,
+			) {
+				Ok(shaders) => {
+					// Pipelines for other render modes are created again from the new shaders when they're next used
+					self.chunk_shader = shaders.chunk_shader;
+					self.chunk_pipelines =
+						HashMap::from([(RenderMode::Normal, shaders.chunk_pipeline)]);
+					self.structure_block_shader = shaders.structure_block_shader;
+					self.structure_block_pipelines = HashMap::from([(
+						(RenderMode::Normal, BlockPass::Opaque),
+						shaders.structure_block_pipeline,
+					)]);
+					self.debug_line_pipeline = shaders.debug_line_pipeline;
+					self.world_border_pipeline = shaders.world_border_pipeline;
+				}
+				Err(error) => loader.report(error),
+			}
+		}
+
+		if reload.contains(&Asset::UiFont) {
+			theme::set_font(self.egui_state.egui_ctx(), loader.ui_font());
+		}
+
+		self.toasts
+			.push(tr!("assets.reloaded").into(), Severity::Info);
+	}
+
+	/// Gets the chunk pipeline for the current [`RenderMode`], creating it the first time it's used.
+	fn chunk_pipeline(&mut self) -> &RenderPipeline {
+		self.chunk_pipelines
+			.entry(self.render_mode)
+			.or_insert_with(|| {
+				create_chunk_pipeline(
+					&self.device,
+					&self.chunk_pipeline_layout,
+					&self.chunk_shader,
+					self.config.format,
+					self.render_mode,
+				)
+			})
+	}
+
+	/// Gets the structure block pipeline for the current [`RenderMode`] and `pass`, creating it the first time it's
+	/// used.
+	fn structure_block_pipeline(&mut self, pass: BlockPass) -> &RenderPipeline {
+		self.structure_block_pipelines
+			.entry((self.render_mode, pass))
+			.or_insert_with(|| {
+				create_structure_block_pipeline(
+					&self.device,
+					&self.structure_block_pipeline_layout,
+					&self.structure_block_shader,
+					self.config.format,
+					self.render_mode,
+					pass,
+				)
+			})
+	}
+
+	/// Uploads this frame's camera and lighting, only needed when the uniform buffer is used instead of push constants.
+	fn write_world_constants(&self, camera: &Matrix4<f32>, lighting: &[f32; 12]) {
+		if let WorldConstants::UniformBuffer { buffer, .. } = &self.world_constants {
+			let mut constants = [0.0; (WORLD_CONSTANTS_SIZE / 4) as usize];
+			constants[..16].copy_from_slice(camera.as_slice());
+			constants[16..].copy_from_slice(lighting);
+
+			self.queue.write_buffer(buffer, 0, cast_slice(&constants));
+		}
+	}
+
+	/// Makes the camera and lighting available to the chunk or structure block pipeline set on `render_pass`.
+	fn set_world_constants(
+		&self,
+		render_pass: &mut RenderPass,
+		camera: &Matrix4<f32>,
+		lighting: &[f32; 12],
+	) {
+		match &self.world_constants {
+			WorldConstants::PushConstants => {
+				render_pass.set_push_constants(ShaderStages::VERTEX, 0, cast_slice(&[*camera]));
+				render_pass.set_push_constants(ShaderStages::FRAGMENT, 64, cast_slice(lighting));
+			}
+			WorldConstants::UniformBuffer { bind_group, .. } => {
+				render_pass.set_bind_group(1, bind_group, &[]);
+			}
+		}
+	}
+
+	pub fn render(&mut self, cl_args: &ClArgs, state: &mut AnyState, debug_text: String) {
+		let frame_start = Instant::now();
+
+		let output = match self.surface.get_current_texture() {
+			Ok(output) => output,
+			Err(error) => panic!("{error}"), // We can probably handle this more elegantly later
+		};
+
+		// Handle the GUI
+		let gui_input = self.egui_state.take_egui_input(&self.window);
+
+		#[cfg(debug)]
+		{
+			let changed = self.assets.changed();
+
+			if !changed.is_empty() {
+				self.reload_assets(&changed);
+			}
+		}
+
+		let mut render_mode = self.render_mode;
+		let mut frame_limit = self.frame_limiter.limit;
+		let mut share_telemetry = self.share_telemetry;
+		let mut accessibility = self.accessibility;
+		let mut display = self.display.clone();
+		let window = self.window.clone();
+
+		let supported_render_modes = RenderMode::ALL
+			.iter()
+			.map(|mode| self.is_render_mode_supported(*mode))
+			.collect::<Vec<_>>();
+		let toasts = &mut self.toasts;
+		#[cfg(debug)]
+		let console = &mut self.console;
+
+		let gui_output = self.egui_state.egui_ctx().run(gui_input, |context| {
+			state.draw_ui(cl_args, &context);
+			toasts.draw(context);
+
+			#[cfg(debug)]
+			console.draw(context);
+
+			Area::new(Id::new("render_mode"))
+				.anchor(Align2::RIGHT_TOP, [-4.0, 4.0])
+				.show(context, |area| {
+					for (mode, supported) in RenderMode::ALL.iter().zip(&supported_render_modes) {
+						let label = SelectableLabel::new(render_mode == *mode, mode.name());
+
+						if area.add_enabled(*supported, label).clicked() {
+							render_mode = *mode;
+						}
+					}
+
+					area.separator();
+
+					ComboBox::new("fps_limit", tr!("settings.fps_limit"))
+						.selected_text(frame_limit.to_string())
+						.show_ui(area, |combo_box| {
+							// A limit given on the command line may not be one of the presets
+							let custom = (!FrameLimit::PRESETS.contains(&frame_limit))
+								.then_some(frame_limit);
+
+							for limit in FrameLimit::PRESETS.iter().copied().chain(custom) {
+								combo_box.selectable_value(
+									&mut frame_limit,
+									limit,
+									limit.to_string(),
+								);
+							}
+						});
+
+					// Changes apply straight away, everything is drawn again next frame anyway
+					let mut language = localization::selected();
+					let languages = localization::languages();
+
+					ComboBox::new("language", tr!("settings.language"))
+						.selected_text(
+							languages
+								.iter()
+								.find(|(code, _)| *code == language)
+								.map_or(&*language, |(_, name)| &**name),
+						)
+						.show_ui(area, |combo_box| {
+							for (code, name) in &languages {
+								combo_box.selectable_value(&mut language, code.clone(), &**name);
+							}
+						});
+
+					localization::select(&language);
+
+					area.separator();
+
+					ComboBox::new("display_mode", tr!("settings.display_mode"))
+						.selected_text(display.mode.name())
+						.show_ui(area, |combo_box| {
+							// Exclusive fullscreen starts at the monitor's best mode, which can be changed below
+							let exclusive = match display.mode {
+								DisplayMode::Exclusive(_) => Some(display.mode),
+								_ => display
+									.monitor(&window)
+									.and_then(|monitor| {
+										display_mode::video_modes(&monitor).first().copied()
+									})
+									.map(DisplayMode::Exclusive),
+							};
+
+							for mode in [DisplayMode::Windowed, DisplayMode::Borderless]
+								.into_iter()
+								.chain(exclusive)
+							{
+								combo_box.selectable_value(&mut display.mode, mode, mode.name());
+							}
+						})
+						.response
+						.on_hover_text(tr!("settings.display_mode_hint"));
+
+					if display.mode != DisplayMode::Windowed {
+						let monitor = &mut display.monitor;
+
+						ComboBox::new("monitor", tr!("settings.monitor"))
+							.selected_text(monitor.as_ref().map_or_else(
+								|| tr!("settings.monitor_current"),
+								MonitorId::to_string,
+							))
+							.show_ui(area, |combo_box| {
+								combo_box.selectable_value(
+									monitor,
+									None,
+									tr!("settings.monitor_current"),
+								);
+
+								for id in window
+									.available_monitors()
+									.map(|monitor| MonitorId::from(&monitor))
+								{
+									let label = id.to_string();
+									combo_box.selectable_value(monitor, Some(id), label);
+								}
+							});
+					}
+
+					if let DisplayMode::Exclusive(mut video_mode) = display.mode {
+						ComboBox::new("resolution", tr!("settings.resolution"))
+							.selected_text(video_mode.to_string())
+							.show_ui(area, |combo_box| {
+								let modes = display
+									.monitor(&window)
+									.map(|monitor| display_mode::video_modes(&monitor))
+									.unwrap_or_default();
+
+								for mode in modes {
+									combo_box.selectable_value(
+										&mut video_mode,
+										mode,
+										mode.to_string(),
+									);
+								}
+							});
+
+						display.mode = DisplayMode::Exclusive(video_mode);
+					}
+
+					area.checkbox(&mut share_telemetry, tr!("settings.share_telemetry"))
+						.on_hover_text(tr!("settings.share_telemetry_hint"));
+
+					area.separator();
+
+					let ui_scale = &mut accessibility.ui_scale;
+					ComboBox::new("ui_scale", tr!("settings.ui_scale"))
+						.selected_text(format!("{}%", (*ui_scale * 100.0).round()))
+						.show_ui(area, |combo_box| {
+							// A scale set in the file may not be one of the presets
+							let custom = (!Accessibility::UI_SCALE_PRESETS.contains(ui_scale))
+								.then_some(*ui_scale);
+
+							for scale in Accessibility::UI_SCALE_PRESETS
+								.iter()
+								.copied()
+								.chain(custom)
+							{
+								combo_box.selectable_value(
+									ui_scale,
+									scale,
+									format!("{}%", (scale * 100.0).round()),
+								);
+							}
+						});
+
+					area.checkbox(
+						&mut accessibility.colorblind_palette,
+						tr!("settings.colorblind_palette"),
+					)
+					.on_hover_text(tr!("settings.colorblind_palette_hint"));
+
+					area.checkbox(
+						&mut accessibility.reduced_motion,
+						tr!("settings.reduced_motion"),
+					)
+					.on_hover_text(tr!("settings.reduced_motion_hint"));
+
+					ComboBox::new("theme", tr!("settings.theme"))
+						.selected_text(accessibility.theme.name())
+						.show_ui(area, |combo_box| {
+							for theme in ThemeName::ALL {
+								combo_box.selectable_value(
+									&mut accessibility.theme,
+									theme,
+									theme.name(),
+								);
+							}
+						});
+
+					state.draw_settings(area);
+				});
+
+			// Debug Text, we'll add a keybind to toggle this later
+			context.debug_painter().debug_text(
+				Pos2::default(),
+				Align2::LEFT_TOP,
+				UiColor::DebugText.color(),
+				debug_text.trim_end(),
+			);
+		});
+
+		self.render_mode = render_mode;
+		self.frame_limiter.limit = frame_limit;
+		self.share_telemetry = share_telemetry;
+
+		if accessibility != self.accessibility {
+			self.accessibility = accessibility;
+			accessibility.save();
+			accessibility.apply(self.egui_state.egui_ctx());
+		}
+
+		if display != self.display {
+			self.display = display;
+			self.display.save();
+			self.display.apply(&self.window);
+		}
+
+		self.egui_state
+			.handle_platform_output(&self.window, gui_output.platform_output);
+
+		let paint_jobs = self
+			.egui_state
+			.egui_ctx()
+			.tessellate(gui_output.shapes, gui_output.pixels_per_point);
+		let screen_descriptor = &ScreenDescriptor {
+			size_in_pixels: [self.config.width, self.config.height],
+			pixels_per_point: gui_output.pixels_per_point,
+		};
+
+		for (id, image_delta) in gui_output.textures_delta.set {
+			self.egui_renderer
+				.update_texture(&self.device, &self.queue, id, &image_delta);
+			self.egui_textures.insert(id);
+		}
+
+		let mut encoder = self
+			.device
+			.create_command_encoder(&CommandEncoderDescriptor::default());
+
+		self.egui_renderer.update_buffers(
+			&self.device,
+			&self.queue,
+			&mut encoder,
+			&paint_jobs,
+			&screen_descriptor,
+		);
+
+		let mut frame = Frame {
+			encoder,
+			view: output
+				.texture
+				.create_view(&TextureViewDescriptor::default()),
+			depth_buffer_view: self.depth_buffer.create_view(&TextureViewDescriptor {
+				label: Some("renderer.depth_buffer#view"),
+				..TextureViewDescriptor::default()
+			}),
+			clear_color: state.clear_color(),
+			cleared: false,
+		};
+
+		state.render(self, &mut frame);
+
+		// States that don't draw anything still need the screen cleared
+		if !frame.cleared {
+			drop(frame.opaque_pass());
+		}
+
+		let Frame {
+			mut encoder, view, ..
+		} = frame;
+
+		{
+			let mut render_pass = encoder
+				.begin_render_pass(&RenderPassDescriptor {
+					label: Some("renderer.egui#pass"),
+					color_attachments: &[Some(RenderPassColorAttachment {
+						ops: Operations {
+							load: Load,
+							store: Store,
+						},
+						resolve_target: None,
+						view: &view,
+					})],
+					..Default::default()
+				})
+				.forget_lifetime();
+
+			self.egui_renderer
+				.render(&mut render_pass, &paint_jobs, &screen_descriptor);
+		}
+
+		self.queue.submit(once(encoder.finish()));
+		output.present();
+
+		// Textures may still be used by this frame, so they can only be freed after it has been submitted
+		for id in gui_output.textures_delta.free {
+			self.egui_renderer.free_texture(&id);
+			self.egui_textures.remove(&id);
+		}
+
+		self.check_gpu_memory_limit();
+
+		let frame_time = Instant::now() - frame_start;
+
+		self.frame_times.push_back(frame_time);
+		self.frame_time_total += frame_time;
+
+		while self.frame_time_total > Duration::from_secs(1) {
+			let old_frame_time = self.frame_times.pop_front()
+				.expect("pop_front should not fail as it is only called if frame_time_total is more than 1 second which requires frame_times to be populated");
+			self.frame_time_total -= old_frame_time;
+		}
+
+		self.frame_time_average = match self.frame_times.is_empty() {
+			true => frame_time,
+			false => self.frame_time_total / self.frame_times.len() as u32,
+		};
+
+		self.frames_per_second =
+			(self.frame_times.len() as f64 / self.frame_time_total.as_secs_f64()).round() as usize;
+
+		// Otherwise the redraw will be requested later, see Client::about_to_wait
+		if self
+			.frame_limiter
+			.schedule_next_frame(Instant::now())
+			.is_none()
+		{
+			self.window.request_redraw();
+		}
+	}
+
+	pub fn handle_window_event(&mut self, event: &WindowEvent) {
+		match event {
+			WindowEvent::Focused(focused) => self.frame_limiter.set_focused(*focused),
+			WindowEvent::Occluded(occluded) => self.frame_limiter.set_occluded(*occluded),
+			_ => {}
+		}
+
+		if let WindowEvent::KeyboardInput {
+			event:
+				KeyEvent {
+					physical_key: PhysicalKey::Code(KeyCode::F4),
+					state: ElementState::Released,
+					repeat: false,
+					..
+				},
+			..
+		} = event
+		{
+			self.cycle_render_mode();
+		}
+
+		if let WindowEvent::KeyboardInput {
+			event:
+				KeyEvent {
+					physical_key: PhysicalKey::Code(KeyCode::F5),
+					state: ElementState::Released,
+					repeat: false,
+					..
+				},
+			..
+		} = event
+		{
+			self.reload_assets(&Asset::ALL);
+		}
+
+		if let WindowEvent::KeyboardInput {
+			event:
+				KeyEvent {
+					physical_key: PhysicalKey::Code(KeyCode::F11),
+					state: ElementState::Released,
+					repeat: false,
+					..
+				},
+			..
+		} = event
+		{
+			self.display.toggle();
+			self.display.save();
+			self.display.apply(&self.window);
+		}
+
+		let _ = self.egui_state.on_window_event(&self.window, &event);
+	}
+}
+
+/// The world half of a frame, the UI is drawn on top of it afterwards. Render passes are drawn in the order they're
+/// begun, and the first one clears the screen.
+pub struct Frame {
+	encoder: CommandEncoder,
+	view: TextureView,
+	depth_buffer_view: TextureView,
+
+	clear_color: Color,
+	cleared: bool,
+}
+
+impl Frame {
+	/// For terrain, opaque blocks, and debug lines, which all write depth.
+	pub fn opaque_pass(&mut self) -> RenderPass<'_> {
+		self.begin_pass("renderer.opaque#pass")
+	}
+
+	/// For anything alpha blended, which tests depth without writing it. Begin it after every opaque pass, and draw
+	/// back to front.
+	pub fn transparent_pass(&mut self) -> RenderPass<'_> {
+		self.begin_pass("renderer.transparent#pass")
+	}
+
+	fn begin_pass(&mut self, label: &'static str) -> RenderPass<'_> {
+		let (color_load, depth_load) = match self.cleared {
+			true => (Load, Load),
+			false => (Clear(self.clear_color), Clear(1.0)),
+		};
+
+		self.cleared = true;
+
+		self.encoder.begin_render_pass(&RenderPassDescriptor {
+			label: Some(label),
+			color_attachments: &[Some(RenderPassColorAttachment {
+				ops: Operations {
+					load: color_load,
+					store: Store,
+				},
+				resolve_target: None,
+				view: &self.view,
+			})],
+			depth_stencil_attachment: Some(RenderPassDepthStencilAttachment {
+				view: &self.depth_buffer_view,
+				depth_ops: Some(Operations {
+					load: depth_load,
+					store: Store,
+				}),
+				stencil_ops: None,
+			}),
+			..Default::default()
+		})
+	}
+}
+
+#[allow(unused_variables)]
+trait Render {
+	fn clear_color(&self) -> Color {
+		Color::BLACK
+	}
+
+	fn render(&mut self, renderer: &mut Renderer, frame: &mut Frame) {}
+}
+
+impl Render for AnyState {
+	fn clear_color(&self) -> Color {
+		match self {
+			Self::Login(state) => state as &dyn Render,
+			Self::Sector(state) => state as &dyn Render,
+			Self::Disconnected(state) => state as &dyn Render,
+
+			#[cfg(debug)]
+			Self::GuiTest(_) => return Color::BLACK,
+		}
+		.clear_color()
+	}
+
+	fn render(&mut self, renderer: &mut Renderer, frame: &mut Frame) {
+		match self {
+			Self::Login(state) => state as &mut dyn Render,
+			Self::Sector(state) => state as &mut dyn Render,
+			Self::Disconnected(state) => state as &mut dyn Render,
+
+			#[cfg(debug)]
+			Self::GuiTest(_) => return,
+		}
+		.render(renderer, frame)
 	}
 }
 
 impl Render for Login {}
 
+impl Render for Disconnected {
+	fn render(&mut self, renderer: &mut Renderer, _: &mut Frame) {
+		// The sector may have had the cursor grabbed when the connection was lost
+		let _ = renderer.window.set_cursor_grab(CursorGrabMode::None);
+		renderer.window.set_cursor_visible(true);
+	}
+}
+
 impl Render for Sector {
+	fn clear_color(&self) -> Color {
+		self.ambient.lighting().clear_color()
+	}
+
 	// To anyone that may be reading this code and is experienced, I am well aware this is *terrible*. It's all prototype code though so I
 	// am not dealing with it for now.
 	//
 	// To anyone new to graphics programming, take what you see here as an example of what not to do.
-	fn render(&mut self, renderer: &mut Renderer, render_pass: &mut RenderPass) {
-		if !self.inventory_gui_open {
+	fn render(&mut self, renderer: &mut Renderer, frame: &mut Frame) {
+		#[cfg(debug)]
+		let console_open = renderer.console.is_open();
+		#[cfg(not(debug))]
+		let console_open = false;
+
+		if !self.gui_open() && !console_open {
 			let _ = renderer
 				.window
 				.set_cursor_grab(CursorGrabMode::Confined)
 				.or_else(|_| renderer.window.set_cursor_grab(CursorGrabMode::Locked));
-			let _ = renderer.window.set_cursor_visible(false);
-			let _ = renderer.window.set_cursor_position(LogicalPosition {
-				x: renderer.config.width as f32 / 2.0,
-				y: renderer.config.height as f32 / 2.0,
+			renderer.window.set_cursor_visible(false);
+			// The surface is configured in physical pixels, and follows the window through fullscreen transitions
+			let _ = renderer.window.set_cursor_position(PhysicalPosition {
+				x: renderer.config.width / 2,
+				y: renderer.config.height / 2,
 			});
 		} else {
 			let _ = renderer.window.set_cursor_grab(CursorGrabMode::None);
-			let _ = renderer.window.set_cursor_visible(true);
+			renderer.window.set_cursor_visible(true);
 		}
 
-		self.process_messages(&renderer.device);
+		self.process_messages();
+		self.rebuild_chunks(&renderer.device);
+
+		match renderer.share_telemetry {
+			true => {
+				if let Some(frame_time) = renderer.last_frame_time() {
+					self.telemetry.record(frame_time);
+				}
+
+				let report = self.telemetry.take_report(
+					self.unmeshable_chunks.len(),
+					renderer.adapter_summary_hash(),
+				);
+
+				if let Some(report) = report {
+					self.player.connection.send(report);
+				}
+			}
+			false => self.telemetry.clear(),
+		}
+
+		let view_hint = ViewHint::new(
+			self.player
+				.location
+				.rotation
+				.inverse_transform_vector(&-Vector3::z()),
+			renderer.perspective.fovy(),
+			renderer.perspective.aspect(),
+		);
+
+		if self.view_hint.is_none_or(|sent| sent.differs(&view_hint)) {
+			self.player.connection.send(view_hint);
+			self.view_hint = Some(view_hint);
+		}
 
 		let view = self
 			.player
@@ -923,9 +1454,13 @@ impl Render for Sector {
 			.to_homogeneous()
 			* Translation3::from(-self.player.location.position.coords).to_homogeneous();
 		let camera_matrix = renderer.perspective.to_homogeneous() * view;
+		let lighting = self.ambient.lighting().push_constants();
+		renderer.write_world_constants(&camera_matrix, &lighting);
+
+		let mut render_pass = frame.opaque_pass();
 
-		render_pass.set_pipeline(&renderer.chunk_pipeline);
-		render_pass.set_push_constants(ShaderStages::VERTEX, 0, cast_slice(&[camera_matrix]));
+		render_pass.set_pipeline(renderer.chunk_pipeline());
+		renderer.set_world_constants(&mut render_pass, &camera_matrix, &lighting);
 		render_pass.set_bind_group(0, &renderer.terrain_textures_bind_group, &[]);
 
 		// This should all be indirect multi-draw
@@ -943,10 +1478,11 @@ impl Render for Sector {
 			}
 		}
 
-		render_pass.set_pipeline(&renderer.structure_block_pipeline);
+		render_pass.set_pipeline(renderer.structure_block_pipeline(BlockPass::Opaque));
 
 		// Not sure why this is getting cleared? But oh well.
-		render_pass.set_push_constants(ShaderStages::VERTEX, 0, cast_slice(&[camera_matrix]));
+		renderer.set_world_constants(&mut render_pass, &camera_matrix, &lighting);
+		render_pass.set_bind_group(0, &renderer.structure_block_bind_group, &[]);
 
 		// This should also be indirect multi-draw
 		for structure in &self.structures {
@@ -954,107 +1490,1465 @@ impl Render for Sector {
 				let mut location = *structure.get_location(&self.physics);
 				location.append_translation_mut(&Translation3::from(position.cast()));
 
-				// Yes, we are going to allocate a temporary buffer for every. single. block.
-				// This is how you're supposed to do things... right? *It's not*
-				let mut instance_buffer_data = [0u8; 68];
-				instance_buffer_data[..64]
-					.copy_from_slice(cast_slice(&[location.to_homogeneous()]));
-				instance_buffer_data[64..].copy_from_slice(cast_slice(&[1.0f32]));
+				draw_block(
+					renderer,
+					&mut render_pass,
+					&location.to_homogeneous(),
+					[1.0, 1.0, 1.0, 1.0],
+					block.typ,
+					block.state,
+				);
+			}
+		}
 
-				let instance_buffer = renderer.device.create_buffer_init(&BufferInitDescriptor {
-					label: Some("GPU Torture Buffer"),
-					contents: instance_buffer_data.as_slice(),
-					usage: BufferUsages::VERTEX,
-				});
+		// The dumbest debug line drawer you will ever see.
+		// This is the definition of temporary code.
+		if let Some(debug_line_pipeline) = &renderer.debug_line_pipeline {
+			render_pass.set_pipeline(debug_line_pipeline);
+			render_pass.set_push_constants(ShaderStages::VERTEX, 0, cast_slice(&[camera_matrix]));
+
+			if let Some(brush) = &self.terrain_brush {
+				const SEGMENTS: usize = 32;
+
+				let color = UiColor::TerrainBrush.linear();
+				render_pass.set_push_constants(ShaderStages::FRAGMENT, 96, cast_slice(&[color]));
+
+				// Three circles, one around each axis, is enough to give an idea of the sphere's size
+				let center = brush.center(&self.player.location).coords;
+				let circle_point = |axis: usize, segment: usize| {
+					let angle = segment as f32 / SEGMENTS as f32 * TAU;
+					let (sin, cos) = angle.sin_cos();
+
+					let mut offset = Vector3::zeros();
+					offset[(axis + 1) % 3] = cos * brush.radius;
+					offset[(axis + 2) % 3] = sin * brush.radius;
+
+					center + offset
+				};
+
+				for axis in 0..3 {
+					for segment in 0..SEGMENTS {
+						let position_a = circle_point(axis, segment);
+						let position_b = circle_point(axis, segment + 1);
+						render_pass.set_push_constants(
+							ShaderStages::VERTEX,
+							64,
+							cast_slice(&[position_a]),
+						);
+						render_pass.set_push_constants(
+							ShaderStages::VERTEX,
+							80,
+							cast_slice(&[position_b]),
+						);
+						render_pass.draw(0..2, 0..1);
+					}
+				}
+			}
 
-				let block_data = &renderer.structure_block_data[&block.typ];
+			let color = UiColor::StructureMarker.linear();
+			render_pass.set_push_constants(ShaderStages::FRAGMENT, 96, cast_slice(&[color]));
+
+			// Oh you thought structure block rendering was bad? You haven't seen nothing yet.
+			// *GPU bandwidth screams in pain*
+			for structure in &self.structures {
+				let location = structure.get_location(&self.physics);
+
+				let position_a = location.translation.vector + vector![1.0, 0.0, 0.0];
+				let position_b = location.translation.vector - vector![1.0, 0.0, 0.0];
+				render_pass.set_push_constants(ShaderStages::VERTEX, 64, cast_slice(&[position_a]));
+				render_pass.set_push_constants(ShaderStages::VERTEX, 80, cast_slice(&[position_b]));
+				render_pass.draw(0..2, 0..1);
+
+				let position_a = location.translation.vector + vector![0.0, 1.0, 0.0];
+				let position_b = location.translation.vector - vector![0.0, 1.0, 0.0];
+				render_pass.set_push_constants(ShaderStages::VERTEX, 64, cast_slice(&[position_a]));
+				render_pass.set_push_constants(ShaderStages::VERTEX, 80, cast_slice(&[position_b]));
+				render_pass.draw(0..2, 0..1);
+
+				let position_a = location.translation.vector + vector![0.0, 0.0, 1.0];
+				let position_b = location.translation.vector - vector![0.0, 0.0, 1.0];
+				render_pass.set_push_constants(ShaderStages::VERTEX, 64, cast_slice(&[position_a]));
+				render_pass.set_push_constants(ShaderStages::VERTEX, 80, cast_slice(&[position_b]));
+				render_pass.draw(0..2, 0..1);
+			}
 
-				render_pass.set_vertex_buffer(0, block_data.positions.slice(..));
-				render_pass.set_vertex_buffer(1, block_data.texture_coordinates.slice(..));
-				render_pass.set_vertex_buffer(2, instance_buffer.slice(..));
-				render_pass.set_index_buffer(block_data.indices.slice(..), IndexFormat::Uint32);
-				render_pass.set_bind_group(0, &renderer.structure_block_bind_group, &[]);
-				render_pass.draw_indexed(0..block_data.index_count, 0, 0..1);
+			if let Some(cell) = &self.targeted_cell {
+				let color = UiColor::Target.linear();
+				render_pass.set_push_constants(ShaderStages::FRAGMENT, 96, cast_slice(&[color]));
+
+				for [position_a, position_b] in cube_outline(cell) {
+					render_pass.set_push_constants(
+						ShaderStages::VERTEX,
+						64,
+						cast_slice(&[position_a.coords]),
+					);
+					render_pass.set_push_constants(
+						ShaderStages::VERTEX,
+						80,
+						cast_slice(&[position_b.coords]),
+					);
+					render_pass.draw(0..2, 0..1);
+				}
 			}
 		}
 
-		// Draw a block to act as a placement indicator
-		let location = Isometry3::<f32>::from(
-			self.player.location.position
-				+ (self
-					.player
-					.location
-					.rotation
-					.inverse_transform_vector(&-Vector3::z())
-					* 3.0),
-		);
-		let mut instance_buffer_data = [0u8; 68];
-		instance_buffer_data[..64].copy_from_slice(cast_slice(&[location.to_homogeneous()]));
-		instance_buffer_data[64..].copy_from_slice(cast_slice(&[0.25f32]));
+		drop(render_pass);
 
-		let instance_buffer = renderer.device.create_buffer_init(&BufferInitDescriptor {
-			label: Some("GPU Torture Buffer"),
-			contents: instance_buffer_data.as_slice(),
-			usage: BufferUsages::VERTEX,
-		});
+		let mut transparent_blocks = vec![];
 
-		let block_data = &renderer.structure_block_data[&BlockType::Block];
+		// Draw a block to act as a placement indicator, the terrain brush draws it's own indicator instead
+		if let (None, Some(block)) = (&self.terrain_brush, self.hotbar.selected()) {
+			let placement_location = self.player.placement_location();
 
-		render_pass.set_vertex_buffer(0, block_data.positions.slice(..));
-		render_pass.set_vertex_buffer(1, block_data.texture_coordinates.slice(..));
-		render_pass.set_vertex_buffer(2, instance_buffer.slice(..));
-		render_pass.set_index_buffer(block_data.indices.slice(..), IndexFormat::Uint32);
-		render_pass.set_bind_group(0, &renderer.structure_block_bind_group, &[]);
-		render_pass.draw_indexed(0..block_data.index_count, 0, 0..1);
+			transparent_blocks.push(TransparentBlock {
+				location: Isometry3::from(placement_location.position),
+				color: {
+					let color = match self.is_placement_valid(&placement_location) {
+						true => UiColor::PlacementValid,
+						false => UiColor::PlacementInvalid,
+					};
 
-		// The dumbest debug line drawer you will ever see.
-		// This is the definition of temporary code.
-		render_pass.set_pipeline(&renderer.debug_line_pipeline);
-		render_pass.set_push_constants(ShaderStages::VERTEX, 0, cast_slice(&[camera_matrix]));
+					let [red, green, blue] = color.linear();
+					[red, green, blue, 0.25]
+				},
+				block,
+			});
+		}
 
-		let color = vector![1.0f32, 1.0, 1.0];
-		render_pass.set_push_constants(ShaderStages::FRAGMENT, 96, cast_slice(&[color]));
+		// The border is skipped entirely unless the player is close enough for it to show
+		let camera = self.player.location.position;
+		let world_border = renderer
+			.world_border_pipeline
+			.as_ref()
+			.zip(self.world_border.as_ref())
+			.map(|(pipeline, world_border)| (pipeline, world_border, world_border.opacity(&camera)))
+			.filter(|(_, _, opacity)| *opacity > 0.0);
+
+		// Drawn slightly larger than the block, so that it covers the block's surface rather than fighting it for depth
+		let highlight = self.targeted_block.and_then(|(id, position)| {
+			let structure = self
+				.structures
+				.iter()
+				.find(|structure| structure.id == id)?;
+			let block = structure.get_block(&position)?;
+
+			let mut location = *structure.get_location(&self.physics);
+			location.append_translation_mut(&Translation3::from(position.cast()));
+
+			let model = location.to_homogeneous() * Matrix4::new_scaling(HIGHLIGHT_SCALE);
+			Some((model, block.typ, block.state))
+		});
 
-		// Oh you thought structure block rendering was bad? You haven't seen nothing yet.
-		// *GPU bandwidth screams in pain*
-		for structure in &self.structures {
-			let location = structure.get_location(&self.physics);
-
-			let position_a = location.translation.vector + vector![1.0, 0.0, 0.0];
-			let position_b = location.translation.vector - vector![1.0, 0.0, 0.0];
-			render_pass.set_push_constants(ShaderStages::VERTEX, 64, cast_slice(&[position_a]));
-			render_pass.set_push_constants(ShaderStages::VERTEX, 80, cast_slice(&[position_b]));
-			render_pass.draw(0..2, 0..1);
-
-			let position_a = location.translation.vector + vector![0.0, 1.0, 0.0];
-			let position_b = location.translation.vector - vector![0.0, 1.0, 0.0];
-			render_pass.set_push_constants(ShaderStages::VERTEX, 64, cast_slice(&[position_a]));
-			render_pass.set_push_constants(ShaderStages::VERTEX, 80, cast_slice(&[position_b]));
-			render_pass.draw(0..2, 0..1);
-
-			let position_a = location.translation.vector + vector![0.0, 0.0, 1.0];
-			let position_b = location.translation.vector - vector![0.0, 0.0, 1.0];
-			render_pass.set_push_constants(ShaderStages::VERTEX, 64, cast_slice(&[position_a]));
-			render_pass.set_push_constants(ShaderStages::VERTEX, 80, cast_slice(&[position_b]));
-			render_pass.draw(0..2, 0..1);
+		if transparent_blocks.is_empty() && world_border.is_none() && highlight.is_none() {
+			return;
 		}
+
+		// Blending only comes out right if whatever is behind has already been drawn
+		transparent_blocks.sort_by(|a, b| {
+			let a = nalgebra::distance_squared(&a.location.translation.vector.into(), &camera);
+			let b = nalgebra::distance_squared(&b.location.translation.vector.into(), &camera);
+			b.total_cmp(&a)
+		});
+
+		let mut render_pass = frame.transparent_pass();
+
+		// The border is further away than anything else drawn here whenever it's close enough to matter
+		if let Some((pipeline, world_border, opacity)) = world_border {
+			let rotation = self
+				.player
+				.location
+				.rotation
+				.to_rotation_matrix()
+				.to_homogeneous();
+			let inverse_camera = (renderer.perspective.to_homogeneous() * rotation)
+				.try_inverse()
+				.unwrap_or_default();
+
+			let [red, green, blue] = UiColor::WorldBorder.linear();
+			let mut constants = [0.0; (WORLD_BORDER_CONSTANTS_SIZE / 4) as usize];
+			constants[..16].copy_from_slice(inverse_camera.as_slice());
+			constants[16..19].copy_from_slice(camera.coords.as_slice());
+			constants[19] = world_border.radius();
+			constants[20..].copy_from_slice(&[red, green, blue, opacity]);
+
+			render_pass.set_pipeline(pipeline);
+			render_pass.set_push_constants(ShaderStages::FRAGMENT, 0, cast_slice(&constants));
+			render_pass.draw(0..3, 0..1);
+		}
+
+		render_pass.set_pipeline(renderer.structure_block_pipeline(BlockPass::Transparent));
+		renderer.set_world_constants(&mut render_pass, &camera_matrix, &lighting);
+		render_pass.set_bind_group(0, &renderer.structure_block_bind_group, &[]);
+
+		for TransparentBlock {
+			location,
+			color,
+			block,
+		} in transparent_blocks
+		{
+			draw_block(
+				renderer,
+				&mut render_pass,
+				&location.to_homogeneous(),
+				color,
+				block,
+				// Only placement previews are drawn transparent, and blocks are placed in state 0
+				0,
+			);
+		}
+
+		if let Some((model, block, state)) = highlight {
+			let [red, green, blue] = UiColor::Target.linear();
+
+			render_pass.set_pipeline(renderer.structure_block_pipeline(BlockPass::Highlight));
+			renderer.set_world_constants(&mut render_pass, &camera_matrix, &lighting);
+			render_pass.set_bind_group(0, &renderer.structure_block_bind_group, &[]);
+
+			draw_block(
+				renderer,
+				&mut render_pass,
+				&model,
+				[red, green, blue, HIGHLIGHT_OPACITY],
+				block,
+				state,
+			);
+		}
+	}
+}
+
+/// How much larger than the targeted block it's highlight is drawn, and how opaque it is.
+const HIGHLIGHT_SCALE: f32 = 1.02;
+const HIGHLIGHT_OPACITY: f32 = 0.35;
+
+/// The 12 edges of the unit cube at `location`, as pairs of points.
+fn cube_outline(location: &Isometry3<f32>) -> [[Point3<f32>; 2]; 12] {
+	let corner = |index: usize| {
+		location
+			* Point3::new(
+				(index & 1) as f32,
+				(index >> 1 & 1) as f32,
+				(index >> 2 & 1) as f32,
+			)
+	};
+
+	let mut edges = [[Point3::origin(); 2]; 12];
+	let mut edge = 0;
+
+	// Each edge joins a corner to the one next to it along an axis, counting it from whichever end is lower
+	for index in 0..8 {
+		for axis in 0..3 {
+			if index & 1 << axis == 0 {
+				edges[edge] = [corner(index), corner(index | 1 << axis)];
+				edge += 1;
+			}
+		}
+	}
+
+	edges
+}
+
+struct TransparentBlock {
+	location: Isometry3<f32>,
+	color: [f32; 4],
+	block: BlockType,
+}
+
+/// Draws a single structure block with the model matrix `model`, the structure block pipeline and bind group must
+/// already be set.
+fn draw_block(
+	renderer: &Renderer,
+	render_pass: &mut RenderPass,
+	model: &Matrix4<f32>,
+	color: [f32; 4],
+	block: BlockType,
+	state: BlockState,
+) {
+	// Yes, we are going to allocate a temporary buffer for every. single. block.
+	// This is how you're supposed to do things... right? *It's not*
+	let block_data = &renderer.structure_block_data[&block];
+
+	let mut instance_buffer_data = [0u8; 84];
+	instance_buffer_data[..64].copy_from_slice(cast_slice(&[*model]));
+	instance_buffer_data[64..80].copy_from_slice(cast_slice(&color));
+	instance_buffer_data[80..]
+		.copy_from_slice(cast_slice(&[block_data.instance_atlas_cell(block, state)]));
+
+	let instance_buffer = Tracked::buffer(
+		GpuCategory::Structures,
+		renderer.device.create_buffer_init(&BufferInitDescriptor {
+			label: Some("GPU Torture Buffer"),
+			contents: instance_buffer_data.as_slice(),
+			usage: BufferUsages::VERTEX,
+		}),
+	);
+
+	render_pass.set_vertex_buffer(0, block_data.positions.slice(..));
+	render_pass.set_vertex_buffer(1, block_data.texture_coordinates.slice(..));
+	render_pass.set_vertex_buffer(2, instance_buffer.slice(..));
+	render_pass.set_index_buffer(block_data.indices.slice(..), IndexFormat::Uint32);
+	render_pass.draw_indexed(0..block_data.index_count, 0, 0..1);
+}
+
+/// Alternative ways of drawing the world, used to debug meshing and materials.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum RenderMode {
+	Normal,
+	Wireframe,
+	Normals,
+	MaterialId,
+}
+
+impl RenderMode {
+	pub const ALL: &'static [Self] = &[
+		Self::Normal,
+		Self::Wireframe,
+		Self::Normals,
+		Self::MaterialId,
+	];
+
+	pub const fn name(&self) -> &'static str {
+		match self {
+			Self::Normal => "Normal",
+			Self::Wireframe => "Wireframe",
+			Self::Normals => "Normals",
+			Self::MaterialId => "Material ID",
+		}
+	}
+
+	pub const fn next(&self) -> Self {
+		match self {
+			Self::Normal => Self::Wireframe,
+			Self::Wireframe => Self::Normals,
+			Self::Normals => Self::MaterialId,
+			Self::MaterialId => Self::Normal,
+		}
+	}
+
+	const fn polygon_mode(&self) -> PolygonMode {
+		match self {
+			Self::Wireframe => PolygonMode::Line,
+			_ => Fill,
+		}
+	}
+
+	const fn chunk_fragment_entry_point(&self) -> &'static str {
+		match self {
+			Self::Normal | Self::Wireframe => "fragment",
+			Self::Normals => "fragment_normals",
+			Self::MaterialId => "fragment_material",
+		}
+	}
+
+	// Structure blocks don't have materials, so they are just drawn normally in the Material ID mode
+	const fn structure_fragment_entry_point(&self) -> &'static str {
+		match self {
+			Self::Normal | Self::Wireframe | Self::MaterialId => "fragment",
+			Self::Normals => "fragment_normals",
+		}
+	}
+}
+
+/// What's needed to load assets onto the device, separate from [`Renderer`] so that it can be used before the
+/// renderer exists.
+struct AssetLoader<'r> {
+	device: &'r Device,
+	queue: &'r Queue,
+	assets: &'r mut Assets,
+	toasts: &'r mut Toasts,
+}
+
+/// Structure block models and their texture atlas, see [`AssetLoader::structure_blocks`].
+struct StructureBlocks {
+	data: HashMap<BlockType, Arc<BlockRenderData>>,
+	texture: Tracked<Texture>,
+	bind_group: BindGroup,
+}
+
+/// Shaders and the pipelines that have to be created with them, see [`AssetLoader::shaders`].
+struct Shaders {
+	chunk_shader: ShaderModule,
+	chunk_pipeline: RenderPipeline,
+	structure_block_shader: ShaderModule,
+	structure_block_pipeline: RenderPipeline,
+	debug_line_pipeline: Option<RenderPipeline>,
+	world_border_pipeline: Option<RenderPipeline>,
+}
+
+impl AssetLoader<'_> {
+	/// Logs an asset that couldn't be used and lets the player know.
+	fn report(&mut self, error: AssetError) {
+		warn!("Unable to use asset: {error}");
+		self.toasts.push(
+			tr!("assets.rejected", error = error).into(),
+			Severity::Warning,
+		);
+	}
+
+	fn report_rejected<T>(&mut self, (value, rejected): Loaded<T>) -> T {
+		if let Some(error) = rejected {
+			self.report(error);
+		}
+
+		value
+	}
+
+	/// Loads the UI font, [`None`] for egui's own fonts. Unlike other assets nothing depends on it, so egui's own fonts
+	/// are used if it can't be loaded at all.
+	fn ui_font(&mut self) -> Option<FontData> {
+		match self.assets.ui_font() {
+			Ok(loaded) => self.report_rejected(loaded),
+			Err(error) => {
+				self.report(error);
+				None
+			}
+		}
+	}
+
+	fn terrain_textures(
+		&mut self,
+		layout: &BindGroupLayout,
+	) -> Result<(Tracked<Texture>, BindGroup), AssetError> {
+		let loaded = self.assets.texture(
+			Asset::TerrainTextures,
+			AtlasLayout {
+				cell_size: None,
+				// Materials are laid out 4x4, see chunk.wgsl
+				cells: Some(4),
+				max_dimension: self.device.limits().max_texture_dimension_2d,
+			},
+		)?;
+		let image = self.report_rejected(loaded);
+
+		Ok(create_atlas(
+			self.device,
+			self.queue,
+			"renderer.voxject#texture",
+			&image,
+			layout,
+		))
+	}
+
+	/// Loads the structure block textures and models together, as the models are fitted to the texture atlas.
+	fn structure_blocks(
+		&mut self,
+		layout: &BindGroupLayout,
+	) -> Result<StructureBlocks, AssetError> {
+		let loaded = self.assets.texture(
+			Asset::StructureBlockTextures,
+			AtlasLayout {
+				cell_size: Some(ATLAS_CELL_SIZE),
+				cells: None,
+				max_dimension: self.device.limits().max_texture_dimension_2d,
+			},
+		)?;
+		let image = self.report_rejected(loaded);
+
+		let loaded = self.assets.structure_block_models()?;
+		let models = self.report_rejected(loaded);
+
+		let (width, height) = image.dimensions();
+		let structure_block_data = create_structure_block_data(
+			self.device,
+			models,
+			width / ATLAS_CELL_SIZE,
+			height / ATLAS_CELL_SIZE,
+		);
+
+		let (texture, bind_group) = create_atlas(
+			self.device,
+			self.queue,
+			"Block Renderer > Texture",
+			&image,
+			layout,
+		);
+
+		Ok(StructureBlocks {
+			data: structure_block_data,
+			texture,
+			bind_group,
+		})
+	}
+
+	/// Creates whatever uses `asset` with `create`, catching validation errors so that an override that doesn't work
+	/// falls back to the built in shader rather than crashing.
+	fn shader<T>(
+		&mut self,
+		asset: Asset,
+		create: impl Fn(&str) -> Result<T, String>,
+	) -> Result<T, AssetError> {
+		let device = self.device;
+
+		let loaded = self.assets.shader(asset, |source| {
+			device.push_error_scope(ErrorFilter::Validation);
+			let created = create(source);
+
+			match Handle::current().block_on(device.pop_error_scope()) {
+				Some(error) => Err(error.to_string()),
+				None => created,
+			}
+		})?;
+
+		Ok(self.report_rejected(loaded))
+	}
+
+	/// Loads every shader, along with the normal pipelines for them, which also checks that each shader fits it's
+	/// pipeline.
+	fn shaders(
+		&mut self,
+		constants: ShaderConstants,
+		chunk_pipeline_layout: &PipelineLayout,
+		structure_block_pipeline_layout: &PipelineLayout,
+		format: TextureFormat,
+	) -> Result<Shaders, AssetError> {
+		let device = self.device;
+
+		let world_shader = |label, source: &str| -> Result<ShaderModule, String> {
+			Ok(device.create_shader_module(ShaderModuleDescriptor {
+				label: Some(label),
+				source: ShaderSource::Wgsl(constants.shader_source(source)?),
+			}))
+		};
+
+		let (chunk_shader, chunk_pipeline) = self.shader(Asset::ChunkShader, |source| {
+			let shader = world_shader("chunk.wgsl", source)?;
+			let pipeline = create_chunk_pipeline(
+				device,
+				chunk_pipeline_layout,
+				&shader,
+				format,
+				RenderMode::Normal,
+			);
+			Ok((shader, pipeline))
+		})?;
+
+		let (structure_block_shader, structure_block_pipeline) =
+			self.shader(Asset::StructureShader, |source| {
+				let shader = world_shader("structure.wgsl", source)?;
+				let pipeline = create_structure_block_pipeline(
+					device,
+					structure_block_pipeline_layout,
+					&shader,
+					format,
+					RenderMode::Normal,
+					BlockPass::Opaque,
+				);
+				Ok((shader, pipeline))
+			})?;
+
+		let (debug_line_pipeline, world_border_pipeline) = match constants {
+			ShaderConstants::PushConstants => {
+				let debug_line_pipeline = self.shader(Asset::DebugLineShader, |source| {
+					let shader = device.create_shader_module(ShaderModuleDescriptor {
+						label: Some("debug_line.wgsl"),
+						source: ShaderSource::Wgsl(source.into()),
+					});
+					Ok(create_debug_line_pipeline(device, &shader, format))
+				})?;
+
+				let world_border_pipeline = self.shader(Asset::WorldBorderShader, |source| {
+					let shader = device.create_shader_module(ShaderModuleDescriptor {
+						label: Some("world_border.wgsl"),
+						source: ShaderSource::Wgsl(source.into()),
+					});
+					Ok(create_world_border_pipeline(device, &shader, format))
+				})?;
+
+				(Some(debug_line_pipeline), Some(world_border_pipeline))
+			}
+			ShaderConstants::UniformBuffer => (None, None),
+		};
+
+		Ok(Shaders {
+			chunk_shader,
+			chunk_pipeline,
+			structure_block_shader,
+			structure_block_pipeline,
+			debug_line_pipeline,
+			world_border_pipeline,
+		})
 	}
 }
 
+fn create_atlas_bind_group_layout(
+	device: &Device,
+	label: &str,
+	texture_visibility: ShaderStages,
+) -> BindGroupLayout {
+	device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+		label: Some(label),
+		entries: &[
+			BindGroupLayoutEntry {
+				binding: 0,
+				visibility: texture_visibility,
+				ty: BindingType::Texture {
+					sample_type: Float { filterable: false },
+					view_dimension: TextureViewDimension::D2,
+					multisampled: false,
+				},
+				count: None,
+			},
+			BindGroupLayoutEntry {
+				binding: 1,
+				visibility: ShaderStages::FRAGMENT,
+				ty: BindingType::Sampler(NonFiltering),
+				count: None,
+			},
+		],
+	})
+}
+
+/// Uploads a texture atlas, and binds it along with a sampler for `layout`.
+fn create_atlas(
+	device: &Device,
+	queue: &Queue,
+	label: &str,
+	image: &RgbaImage,
+	layout: &BindGroupLayout,
+) -> (Tracked<Texture>, BindGroup) {
+	let (width, height) = image.dimensions();
+
+	let texture = device.create_texture_with_data(
+		queue,
+		&TextureDescriptor {
+			label: Some(label),
+			size: Extent3d {
+				width,
+				height,
+				depth_or_array_layers: 1,
+			},
+			mip_level_count: 1,
+			sample_count: 1,
+			dimension: D2,
+			format: Rgba8UnormSrgb,
+			usage: TextureUsages::TEXTURE_BINDING,
+			view_formats: &[],
+		},
+		LayerMajor,
+		image,
+	);
+
+	let view = texture.create_view(&TextureViewDescriptor::default());
+	let sampler = device.create_sampler(&SamplerDescriptor::default());
+
+	let bind_group = device.create_bind_group(&BindGroupDescriptor {
+		label: Some(&format!("{label}#bind_group")),
+		layout,
+		entries: &[
+			BindGroupEntry {
+				binding: 0,
+				resource: BindingResource::TextureView(&view),
+			},
+			BindGroupEntry {
+				binding: 1,
+				resource: BindingResource::Sampler(&sampler),
+			},
+		],
+	});
+
+	(Tracked::texture(GpuCategory::Textures, texture), bind_group)
+}
+
+/// Uploads each block's model, fitted to it's cell of the texture atlas. Blocks without a model use `MissingBlock`'s.
+fn create_structure_block_data(
+	device: &Device,
+	models: Vec<Model>,
+	atlas_columns: u32,
+	atlas_rows: u32,
+) -> HashMap<BlockType, Arc<BlockRenderData>> {
+	let mut missing_block = None;
+	let mut structure_blocks = HashMap::with_capacity(BlockType::ALL.len());
+
+	for mut model in models {
+		let block = BlockType::from_str(&model.name);
+
+		// Models are made against the whole atlas, but blocks pick their texture by atlas cell, so their
+		// texture coordinates are made relative to their own cell. MissingBlock is left as is.
+		let atlas_cell = match block {
+			Ok(block) => {
+				let cell = block.atlas_cell(0);
+
+				if cell >= atlas_columns * atlas_rows {
+					warn!("Block {block:?} has atlas cell {cell}, which is outside of structure_block_textures.png. It's model will be ignored.");
+					continue;
+				}
+
+				let origin = [(cell % atlas_columns) as f32, (cell / atlas_columns) as f32];
+				let mut outside_cell = false;
+
+				for coords in model.mesh.texcoords.chunks_exact_mut(2) {
+					coords[0] = coords[0] * atlas_columns as f32 - origin[0];
+					coords[1] = coords[1] * atlas_rows as f32 - origin[1];
+
+					outside_cell |= coords.iter().any(|coord| !(0.0..=1.0).contains(coord));
+				}
+
+				if outside_cell {
+					warn!("Model for block {block:?} has texture coordinates outside of it's atlas cell {cell}. This may be a modelling error and could result in broken block textures.");
+				}
+
+				Some(cell)
+			}
+			Err(_) => None,
+		};
+
+		let block_render_data = Arc::new(BlockRenderData {
+			positions: Tracked::buffer(
+				GpuCategory::Structures,
+				device.create_buffer_init(&BufferInitDescriptor {
+					label: Some(&format!(
+						"Block Renderer > Block '{}' > Positions",
+						model.name
+					)),
+					contents: cast_slice(&model.mesh.positions),
+					usage: BufferUsages::VERTEX,
+				}),
+			),
+			texture_coordinates: Tracked::buffer(
+				GpuCategory::Structures,
+				device.create_buffer_init(&BufferInitDescriptor {
+					label: Some(&format!(
+						"Block Renderer > Block '{}' > Texture Coordinates",
+						model.name
+					)),
+					contents: cast_slice(&model.mesh.texcoords),
+					usage: BufferUsages::VERTEX,
+				}),
+			),
+			indices: Tracked::buffer(
+				GpuCategory::Structures,
+				device.create_buffer_init(&BufferInitDescriptor {
+					label: Some(&format!(
+						"Block Renderer > Block '{}' > Indices",
+						model.name
+					)),
+					contents: cast_slice(&model.mesh.indices),
+					usage: BufferUsages::INDEX,
+				}),
+			),
+			index_count: model.mesh.indices.len() as u32,
+
+			atlas_cell,
+		});
+
+		match block {
+			Ok(block) => {
+				if structure_blocks.insert(block, block_render_data).is_some() {
+					warn!("Found duplicate model for block {block:?}! This may be a modelling error and could result in broken block models.");
+				}
+			}
+			Err(_) if model.name == "MissingBlock" => {
+				if missing_block.replace(block_render_data).is_some() {
+					warn!("Found duplicate model for block MissingBlock! This may be a modelling error and could result in broken block models.");
+				}
+			}
+			Err(_) => {}
+		}
+	}
+
+	let missing_block =
+		missing_block.expect("models without MissingBlock should have been rejected when loaded");
+
+	for block in BlockType::ALL {
+		if !structure_blocks.contains_key(block) {
+			warn!("No model found for block {block:?}, a placeholder will be used instead. This will result in broken block models");
+			structure_blocks.insert(*block, missing_block.clone());
+		}
+	}
+
+	structure_blocks
+}
+
+fn create_chunk_pipeline(
+	device: &Device,
+	layout: &PipelineLayout,
+	shader: &ShaderModule,
+	format: TextureFormat,
+	render_mode: RenderMode,
+) -> RenderPipeline {
+	let [vertex_positions, vertex_data] = chunk_vertex::layouts();
+
+	device.create_render_pipeline(&RenderPipelineDescriptor {
+		label: Some(&format!("renderer.voxject#pipeline({render_mode:?})")),
+		layout: Some(layout),
+		vertex: VertexState {
+			module: shader,
+			entry_point: chunk_vertex::ENTRY_POINT,
+			compilation_options: PipelineCompilationOptions::default(),
+			buffers: &[
+				vertex_positions,
+				vertex_data,
+				VertexBufferLayout {
+					array_stride: 16,
+					step_mode: VertexStepMode::Instance,
+					attributes: &vertex_attr_array![5 => Float32x3, 6 => Float32],
+				},
+			],
+		},
+		primitive: PrimitiveState {
+			topology: TriangleList,
+			strip_index_format: None,
+			front_face: Ccw,
+			cull_mode: Some(Back),
+			unclipped_depth: false,
+			polygon_mode: render_mode.polygon_mode(),
+			conservative: false,
+		},
+		depth_stencil: Some(DepthStencilState {
+			format: Depth32Float,
+			depth_write_enabled: true,
+			depth_compare: LessEqual,
+			stencil: Default::default(),
+			bias: Default::default(),
+		}),
+		multisample: MultisampleState {
+			count: 1,
+			mask: !0,
+			alpha_to_coverage_enabled: false,
+		},
+		fragment: Some(FragmentState {
+			module: shader,
+			entry_point: render_mode.chunk_fragment_entry_point(),
+			compilation_options: PipelineCompilationOptions::default(),
+			targets: &[Some(ColorTargetState {
+				format,
+				blend: Some(BlendState::REPLACE),
+				write_mask: ColorWrites::ALL,
+			})],
+		}),
+		multiview: None,
+		cache: None,
+	})
+}
+
+fn create_debug_line_pipeline(
+	device: &Device,
+	shader: &ShaderModule,
+	format: TextureFormat,
+) -> RenderPipeline {
+	let debug_line_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+		label: Some("Debug Renderer > Pipeline Layout"),
+		bind_group_layouts: &[],
+		push_constant_ranges: &[
+			PushConstantRange {
+				stages: ShaderStages::VERTEX,
+				range: 0..96,
+			},
+			PushConstantRange {
+				stages: ShaderStages::FRAGMENT,
+				range: 96..112,
+			},
+		],
+	});
+
+	device.create_render_pipeline(&RenderPipelineDescriptor {
+		label: Some("Debug Renderer > Pipeline"),
+		layout: Some(&debug_line_pipeline_layout),
+		vertex: VertexState {
+			module: shader,
+			entry_point: "vertex",
+			compilation_options: PipelineCompilationOptions::default(),
+			buffers: &[],
+		},
+		primitive: PrimitiveState {
+			topology: LineList,
+			strip_index_format: None,
+			front_face: Ccw,
+			cull_mode: None,
+			unclipped_depth: false,
+			polygon_mode: Fill,
+			conservative: false,
+		},
+		depth_stencil: Some(DepthStencilState {
+			format: Depth32Float,
+			depth_write_enabled: true,
+			depth_compare: LessEqual,
+			stencil: Default::default(),
+			bias: Default::default(),
+		}),
+		multisample: MultisampleState {
+			count: 1,
+			mask: !0,
+			alpha_to_coverage_enabled: false,
+		},
+		fragment: Some(FragmentState {
+			module: shader,
+			entry_point: "fragment",
+			compilation_options: PipelineCompilationOptions::default(),
+			targets: &[Some(ColorTargetState {
+				format,
+				blend: Some(BlendState::REPLACE),
+				write_mask: ColorWrites::ALL,
+			})],
+		}),
+		multiview: None,
+		cache: None,
+	})
+}
+
+fn create_world_border_pipeline(
+	device: &Device,
+	shader: &ShaderModule,
+	format: TextureFormat,
+) -> RenderPipeline {
+	let world_border_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+		label: Some("renderer.world_border#pipeline_layout"),
+		bind_group_layouts: &[],
+		push_constant_ranges: &[PushConstantRange {
+			stages: ShaderStages::FRAGMENT,
+			range: 0..WORLD_BORDER_CONSTANTS_SIZE,
+		}],
+	});
+
+	device.create_render_pipeline(&RenderPipelineDescriptor {
+		label: Some("renderer.world_border#pipeline"),
+		layout: Some(&world_border_pipeline_layout),
+		vertex: VertexState {
+			module: shader,
+			entry_point: "vertex",
+			compilation_options: PipelineCompilationOptions::default(),
+			buffers: &[],
+		},
+		primitive: PrimitiveState {
+			topology: TriangleList,
+			strip_index_format: None,
+			front_face: Ccw,
+			cull_mode: None,
+			unclipped_depth: false,
+			polygon_mode: Fill,
+			conservative: false,
+		},
+		// Drawn in the transparent pass, the shader works out the depth of the border itself
+		depth_stencil: Some(DepthStencilState {
+			format: Depth32Float,
+			depth_write_enabled: false,
+			depth_compare: LessEqual,
+			stencil: Default::default(),
+			bias: Default::default(),
+		}),
+		multisample: MultisampleState {
+			count: 1,
+			mask: !0,
+			alpha_to_coverage_enabled: false,
+		},
+		fragment: Some(FragmentState {
+			module: shader,
+			entry_point: "fragment",
+			compilation_options: PipelineCompilationOptions::default(),
+			targets: &[Some(ColorTargetState {
+				format,
+				blend: Some(BlendState::ALPHA_BLENDING),
+				write_mask: ColorWrites::ALL,
+			})],
+		}),
+		multiview: None,
+		cache: None,
+	})
+}
+
+/// Inverse camera matrix, then the camera position and border radius, then the color and opacity, see
+/// `world_border.wgsl`.
+const WORLD_BORDER_CONSTANTS_SIZE: u32 = 96;
+
+/// Which of the [`Frame`]'s passes structure blocks are being drawn in.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum BlockPass {
+	Opaque,
+	/// Alpha blended, without writing depth so that anything drawn after still shows through.
+	Transparent,
+	/// Like [`BlockPass::Transparent`], but a solid color rather than textured and lit, see
+	/// [`Sector::targeted_block`].
+	Highlight,
+}
+
+fn create_structure_block_pipeline(
+	device: &Device,
+	layout: &PipelineLayout,
+	shader: &ShaderModule,
+	format: TextureFormat,
+	render_mode: RenderMode,
+	pass: BlockPass,
+) -> RenderPipeline {
+	device.create_render_pipeline(&RenderPipelineDescriptor {
+		label: Some(&format!(
+			"Block Renderer > Pipeline ({render_mode:?}, {pass:?})"
+		)),
+		layout: Some(layout),
+		vertex: VertexState {
+			module: shader,
+			entry_point: "vertex",
+			compilation_options: PipelineCompilationOptions::default(),
+			buffers: &[
+				VertexBufferLayout {
+					array_stride: 12,
+					step_mode: VertexStepMode::Vertex,
+					attributes: &vertex_attr_array![0 => Float32x3],
+				},
+				VertexBufferLayout {
+					array_stride: 8,
+					step_mode: VertexStepMode::Vertex,
+					attributes: &vertex_attr_array![1 => Float32x2],
+				},
+				VertexBufferLayout {
+					array_stride: 84,
+					step_mode: VertexStepMode::Instance,
+					attributes: &vertex_attr_array![2 => Float32x4, 3 => Float32x4, 4 => Float32x4, 5 => Float32x4, 6 => Float32x4, 7 => Uint32],
+				},
+			],
+		},
+		primitive: PrimitiveState {
+			topology: TriangleList,
+			strip_index_format: None,
+			front_face: Ccw,
+			cull_mode: Some(Back),
+			unclipped_depth: false,
+			polygon_mode: render_mode.polygon_mode(),
+			conservative: false,
+		},
+		depth_stencil: Some(DepthStencilState {
+			format: Depth32Float,
+			depth_write_enabled: pass == BlockPass::Opaque,
+			depth_compare: LessEqual,
+			stencil: Default::default(),
+			bias: Default::default(),
+		}),
+		multisample: MultisampleState {
+			count: 1,
+			mask: !0,
+			alpha_to_coverage_enabled: false,
+		},
+		fragment: Some(FragmentState {
+			module: shader,
+			entry_point: match pass {
+				BlockPass::Opaque | BlockPass::Transparent => {
+					render_mode.structure_fragment_entry_point()
+				}
+				BlockPass::Highlight => "fragment_highlight",
+			},
+			compilation_options: PipelineCompilationOptions::default(),
+			targets: &[Some(ColorTargetState {
+				format,
+				blend: Some(match pass {
+					BlockPass::Opaque => BlendState::REPLACE,
+					BlockPass::Transparent | BlockPass::Highlight => BlendState::ALPHA_BLENDING,
+				}),
+				write_mask: ColorWrites::ALL,
+			})],
+		}),
+		multiview: None,
+		cache: None,
+	})
+}
+
 #[derive(Debug, Error)]
 #[error(transparent)]
 pub enum RenderInitError {
-	WindowCreationFailed(#[from] OsError),
-
-	SurfaceHandleCreationFailed(#[from] HandleError),
-
 	SurfaceCreationFailed(#[from] CreateSurfaceError),
 
-	#[error("unable to find suitable adapter")]
-	NoAdapter,
+	Adapter(#[from] AdapterError),
 
 	RequestDeviceFailed(#[from] RequestDeviceError),
 
 	#[error("unable to find suitable surface format")]
 	NoSurfaceFormat,
+
+	/// Only if a built in asset couldn't be used, overrides fall back to those.
+	Asset(#[from] AssetError),
+
+	/// A shader or pipeline was rejected by the device.
+	#[error("unable to create pipelines: {0}")]
+	Validation(String),
+}
+
+/// What the renderer asks of the device. [`Requirements::Full`] is tried first, then [`Requirements::Reduced`] for
+/// devices which can't manage it, see [`Renderer::start`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Requirements {
+	/// Push constants if the adapter has them, and only the limits we need.
+	Full,
+	/// No push constants, no wireframe, the lowest limits any device should support, and OpenGL.
+	Reduced,
+}
+
+impl Requirements {
+	pub const ALL: [Self; 2] = [Self::Full, Self::Reduced];
+
+	pub const fn name(&self) -> &'static str {
+		match self {
+			Self::Full => "full",
+			Self::Reduced => "reduced",
+		}
+	}
+
+	/// Reduced requirements use OpenGL, unless a backend was picked on the command line or the platform doesn't have
+	/// it.
+	pub fn backends(&self, adapter_preference: &AdapterPreference) -> Backends {
+		let has_gl = cfg!(not(any(target_os = "macos", target_os = "ios")));
+
+		match (self, adapter_preference.backend) {
+			(Self::Reduced, None) if has_gl => Backend::Gl.backends(),
+			_ => adapter_preference.backends(),
+		}
+	}
+}
+
+/// How the camera and lighting reach the chunk and structure block shaders. The shaders are written for push
+/// constants, see [`ShaderConstants::shader_source`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ShaderConstants {
+	PushConstants,
+	/// For devices without push constants, a uniform buffer in bind group 1 which is written once per frame.
+	UniformBuffer,
+}
+
+impl ShaderConstants {
+	const PUSH_CONSTANT_DECLARATION: &'static str =
+		"var<push_constant> push_constants: PushConstants;";
+	const UNIFORM_BUFFER_DECLARATION: &'static str =
+		"@group(1) @binding(0) var<uniform> push_constants: PushConstants;";
+
+	/// Push constants are used whenever they're available, unless the requirements rule them out.
+	pub fn select(requirements: Requirements, adapter_features: Features) -> Self {
+		match requirements {
+			Requirements::Full if adapter_features.contains(Features::PUSH_CONSTANTS) => {
+				Self::PushConstants
+			}
+			_ => Self::UniformBuffer,
+		}
+	}
+
+	pub const fn name(&self) -> &'static str {
+		match self {
+			Self::PushConstants => "push constants",
+			Self::UniformBuffer => "uniform buffer",
+		}
+	}
+
+	/// `source` with it's push constants declaration swapped for a uniform buffer if needed. Both have the same layout,
+	/// so nothing else in the shader has to change.
+	///
+	/// Fails if `source` doesn't declare `push_constants` exactly as chunk.wgsl and structure.wgsl do.
+	pub fn shader_source<'s>(&self, source: &'s str) -> Result<Cow<'s, str>, String> {
+		if !source.contains(Self::PUSH_CONSTANT_DECLARATION) {
+			return Err(format!(
+				"world shaders must declare `{}`",
+				Self::PUSH_CONSTANT_DECLARATION
+			));
+		}
+
+		Ok(match self {
+			Self::PushConstants => Cow::Borrowed(source),
+			Self::UniformBuffer => Cow::Owned(source.replacen(
+				Self::PUSH_CONSTANT_DECLARATION,
+				Self::UNIFORM_BUFFER_DECLARATION,
+				1,
+			)),
+		})
+	}
+
+	/// Camera for the vertex stage, then lighting for the fragment stage.
+	fn push_constant_ranges(&self) -> &'static [PushConstantRange] {
+		match self {
+			Self::PushConstants => &[
+				PushConstantRange {
+					stages: ShaderStages::VERTEX,
+					range: 0..64,
+				},
+				PushConstantRange {
+					stages: ShaderStages::FRAGMENT,
+					range: 64..WORLD_CONSTANTS_SIZE,
+				},
+			],
+			Self::UniformBuffer => &[],
+		}
+	}
+}
+
+/// Bytes of camera and lighting constants, a 4x4 matrix then [`Lighting::push_constants`].
+///
+/// [`Lighting::push_constants`]: crate::ambient::Lighting::push_constants
+const WORLD_CONSTANTS_SIZE: u32 = 112;
+
+/// The camera and lighting for the chunk and structure block pipelines, see [`ShaderConstants`].
+enum WorldConstants {
+	PushConstants,
+	UniformBuffer {
+		buffer: Buffer,
+		bind_group: BindGroup,
+	},
+}
+
+impl WorldConstants {
+	/// Also returns the layout of bind group 1, if the pipelines need one.
+	fn new(device: &Device, constants: ShaderConstants) -> (Self, Option<BindGroupLayout>) {
+		if constants == ShaderConstants::PushConstants {
+			return (Self::PushConstants, None);
+		}
+
+		let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+			label: Some("renderer.world_constants#bind_group_layout"),
+			entries: &[BindGroupLayoutEntry {
+				binding: 0,
+				visibility: ShaderStages::VERTEX_FRAGMENT,
+				ty: BindingType::Buffer {
+					ty: BufferBindingType::Uniform,
+					has_dynamic_offset: false,
+					min_binding_size: NonZeroU64::new(WORLD_CONSTANTS_SIZE as u64),
+				},
+				count: None,
+			}],
+		});
+
+		let buffer = device.create_buffer(&BufferDescriptor {
+			label: Some("renderer.world_constants#buffer"),
+			size: WORLD_CONSTANTS_SIZE as u64,
+			usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+			mapped_at_creation: false,
+		});
+
+		let bind_group = device.create_bind_group(&BindGroupDescriptor {
+			label: Some("renderer.world_constants#bind_group"),
+			layout: &layout,
+			entries: &[BindGroupEntry {
+				binding: 0,
+				resource: BindingResource::Buffer(BufferBinding {
+					buffer: &buffer,
+					offset: 0,
+					size: None,
+				}),
+			}],
+		});
+
+		(Self::UniformBuffer { buffer, bind_group }, Some(layout))
+	}
+
+	fn constants(&self) -> ShaderConstants {
+		match self {
+			Self::PushConstants => ShaderConstants::PushConstants,
+			Self::UniformBuffer { .. } => ShaderConstants::UniformBuffer,
+		}
+	}
+}
+
+/// Why the renderer couldn't start, shown in safe mode so that players have something to report.
+pub struct Diagnostics {
+	/// Each set of requirements tried, and why it failed.
+	pub failures: Vec<(Requirements, String)>,
+	/// Every adapter on every backend, not just the ones which were tried.
+	pub adapters: Vec<AdapterInfo>,
+}
+
+impl Diagnostics {
+	fn collect(failures: Vec<(Requirements, String)>) -> Self {
+		let instance = Instance::new(InstanceDescriptor {
+			backends: Backend::ALL,
+			..Default::default()
+		});
+
+		Self {
+			failures,
+			adapters: instance
+				.enumerate_adapters(Backend::ALL)
+				.iter()
+				.map(|adapter| adapter.get_info())
+				.collect(),
+		}
+	}
+}
+
+impl Display for Diagnostics {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		writeln!(
+			formatter,
+			"Solarscape (Client) v{}",
+			env!("CARGO_PKG_VERSION")
+		)?;
+		writeln!(
+			formatter,
+			"OS: {} {}",
+			std::env::consts::OS,
+			std::env::consts::ARCH
+		)?;
+
+		writeln!(formatter)?;
+		writeln!(formatter, "Failures:")?;
+
+		for (requirements, error) in &self.failures {
+			writeln!(formatter, "  {} requirements: {error}", requirements.name())?;
+		}
+
+		writeln!(formatter)?;
+		writeln!(formatter, "Adapters:")?;
+
+		if self.adapters.is_empty() {
+			writeln!(formatter, "  none found")?;
+		}
+
+		for adapter in &self.adapters {
+			writeln!(
+				formatter,
+				"  {}, driver {} {}",
+				Described(adapter),
+				adapter.driver,
+				adapter.driver_info
+			)?;
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use wgpu::{AdapterInfo, DeviceType};
+
+	const WORLD_SHADERS: [&str; 2] = [include_str!("chunk.wgsl"), include_str!("structure.wgsl")];
+
+	#[test]
+	fn push_constants_are_only_used_with_full_requirements() {
+		use ShaderConstants::*;
+
+		let with = Features::PUSH_CONSTANTS;
+		let without = Features::empty();
+
+		assert_eq!(
+			ShaderConstants::select(Requirements::Full, with),
+			PushConstants
+		);
+		assert_eq!(
+			ShaderConstants::select(Requirements::Full, without),
+			UniformBuffer
+		);
+		assert_eq!(
+			ShaderConstants::select(Requirements::Reduced, with),
+			UniformBuffer
+		);
+		assert_eq!(
+			ShaderConstants::select(Requirements::Reduced, without),
+			UniformBuffer
+		);
+	}
+
+	#[test]
+	fn world_shaders_are_unchanged_with_push_constants() {
+		for source in WORLD_SHADERS {
+			let shader = ShaderConstants::PushConstants
+				.shader_source(source)
+				.unwrap();
+
+			assert!(matches!(shader, Cow::Borrowed(shader) if shader == source));
+		}
+	}
+
+	#[test]
+	fn world_shaders_compile_with_a_uniform_buffer() {
+		use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+		for source in WORLD_SHADERS {
+			let shader = ShaderConstants::UniformBuffer
+				.shader_source(source)
+				.unwrap();
+
+			assert!(!shader.contains("var<push_constant>"));
+			assert!(shader.contains(ShaderConstants::UNIFORM_BUFFER_DECLARATION));
+
+			// Without push constants allowed, so anything left over would fail
+			let module = naga::front::wgsl::parse_str(&shader).unwrap();
+			Validator::new(ValidationFlags::all(), Capabilities::empty())
+				.validate(&module)
+				.unwrap();
+		}
+	}
+
+	#[test]
+	fn world_border_constants_match_the_shader() {
+		use naga::valid::{Capabilities, ValidationFlags, Validator};
+
+		let module = naga::front::wgsl::parse_str(include_str!("world_border.wgsl")).unwrap();
+		Validator::new(ValidationFlags::all(), Capabilities::PUSH_CONSTANT)
+			.validate(&module)
+			.unwrap();
+
+		let (_, constants) = module
+			.types
+			.iter()
+			.find(|(_, typ)| typ.name.as_deref() == Some("PushConstants"))
+			.unwrap();
+		let naga::TypeInner::Struct { members, span } = &constants.inner else {
+			panic!("PushConstants isn't a struct");
+		};
+
+		// Indices the constants are written at when drawing the border
+		let offsets = members
+			.iter()
+			.map(|member| member.offset / 4)
+			.collect::<Vec<_>>();
+		assert_eq!(offsets, [0, 16, 19, 20, 23]);
+		assert_eq!(*span, WORLD_BORDER_CONSTANTS_SIZE);
+	}
+
+	#[test]
+	fn shaders_without_push_constants_are_refused() {
+		let source = "@group(0) @binding(0) var<uniform> camera: mat4x4<f32>;";
+
+		for constants in [
+			ShaderConstants::PushConstants,
+			ShaderConstants::UniformBuffer,
+		] {
+			assert!(constants.shader_source(source).is_err());
+		}
+	}
+
+	#[test]
+	fn reduced_requirements_prefer_opengl() {
+		let any = AdapterPreference::default();
+		let vulkan = AdapterPreference {
+			backend: Some(Backend::Vulkan),
+			..Default::default()
+		};
+
+		assert_eq!(Requirements::Full.backends(&any), Backend::ALL);
+		assert_eq!(Requirements::Full.backends(&vulkan), Backends::VULKAN);
+		assert_eq!(Requirements::Reduced.backends(&vulkan), Backends::VULKAN);
+
+		if cfg!(not(any(target_os = "macos", target_os = "ios"))) {
+			assert_eq!(Requirements::Reduced.backends(&any), Backends::GL);
+		}
+	}
+
+	#[test]
+	fn diagnostics_list_failures_and_adapters() {
+		let mut diagnostics = Diagnostics {
+			failures: vec![
+				(Requirements::Full, "no push constants".into()),
+				(Requirements::Reduced, "no adapter".into()),
+			],
+			adapters: vec![],
+		};
+
+		let report = diagnostics.to_string();
+		assert!(report.starts_with(concat!(
+			"Solarscape (Client) v",
+			env!("CARGO_PKG_VERSION"),
+			"\n"
+		)));
+		assert!(report.contains(
+			"Failures:\n  full requirements: no push constants\n  reduced requirements: no adapter\n"
+		));
+		assert!(report.ends_with("Adapters:\n  none found\n"));
+
+		diagnostics.adapters.push(AdapterInfo {
+			name: "Test GPU".into(),
+			vendor: 0,
+			device: 0,
+			device_type: DeviceType::DiscreteGpu,
+			driver: "testdriver".into(),
+			driver_info: "1.2.3".into(),
+			backend: wgpu::Backend::Vulkan,
+		});
+
+		assert!(diagnostics
+			.to_string()
+			.ends_with("Adapters:\n  Test GPU (Vulkan, DiscreteGpu), driver testdriver 1.2.3\n"));
+	}
 }
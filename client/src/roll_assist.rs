@@ -0,0 +1,254 @@
+use crate::config;
+use nalgebra::{UnitQuaternion, Vector3};
+use serde::{Deserialize, Serialize};
+
+/// Gently rolls the player back to level while they aren't rolling themselves. Mouse look pitches and yaws about the
+/// player's own axes, so looking around adds up to a roll over time, which many players find disorienting. Kept
+/// between sessions.
+#[derive(Clone, Copy, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct RollAssist {
+	pub enabled: bool,
+	/// How quickly roll is corrected, roughly the inverse of how many seconds it takes to settle.
+	pub strength: f32,
+}
+
+impl RollAssist {
+	pub const MIN_STRENGTH: f32 = 0.25;
+	pub const MAX_STRENGTH: f32 = 4.0;
+	/// Voxjects further than this away don't have a say in which way is up, world Y is used instead.
+	pub const VOXJECT_RANGE: f32 = 4096.0;
+	const FILE: &'static str = "roll_assist.json";
+
+	pub fn load() -> Self {
+		config::load(Self::FILE)
+	}
+
+	pub fn save(&self) {
+		config::save(Self::FILE, self);
+	}
+
+	/// The rotation to apply this tick, as for [`Player::rotate`], along with the new roll velocity. [`None`] if
+	/// there's no roll to correct, in which case the velocity should be reset.
+	///
+	/// [`Player::rotate`]: crate::player::Player::rotate
+	pub fn correction(
+		&self,
+		rotation: &UnitQuaternion<f32>,
+		up: &Vector3<f32>,
+		velocity: f32,
+		delta: f32,
+	) -> Option<(UnitQuaternion<f32>, f32)> {
+		if !self.enabled {
+			return None;
+		}
+
+		let roll = relative_roll(rotation, up)?;
+		let strength = self.strength.clamp(Self::MIN_STRENGTH, Self::MAX_STRENGTH);
+		let (new_roll, velocity) = critically_damped(roll, velocity, strength, delta);
+
+		Some((
+			UnitQuaternion::from_euler_angles(0.0, 0.0, roll - new_roll),
+			velocity,
+		))
+	}
+}
+
+impl Default for RollAssist {
+	fn default() -> Self {
+		Self {
+			enabled: true,
+			strength: 1.0,
+		}
+	}
+}
+
+/// How far the player is rolled away from `up`, in radians from -π to π, for a `rotation` like [`Location::rotation`]
+/// which takes world space into the player's view. Rolling by this much, as for [`Player::rotate`], levels the
+/// player. [`None`] when looking almost straight along `up` either way, as roll has no meaning there.
+///
+/// [`Location::rotation`]: solarscape_shared::data::world::Location::rotation
+/// [`Player::rotate`]: crate::player::Player::rotate
+pub fn relative_roll(rotation: &UnitQuaternion<f32>, up: &Vector3<f32>) -> Option<f32> {
+	let up = rotation * up.try_normalize(f32::EPSILON)?;
+
+	// Only the part of up that's across the view says anything about roll
+	if up.xy().norm() < 1e-3 {
+		return None;
+	}
+
+	Some(f32::atan2(up.x, up.y))
+}
+
+/// Steps a critically damped spring pulling `position` towards 0 by `delta` seconds, returning the new position and
+/// velocity. `strength` is the spring's natural frequency. Exact rather than integrated, so it can't overshoot or
+/// become unstable however long `delta` is.
+pub fn critically_damped(position: f32, velocity: f32, strength: f32, delta: f32) -> (f32, f32) {
+	let decay = (-strength * delta).exp();
+	let change = (velocity + strength * position) * delta;
+
+	(
+		(position + change) * decay,
+		(velocity - strength * change) * decay,
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::vector;
+	use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, PI};
+
+	fn roll(angle: f32) -> UnitQuaternion<f32> {
+		UnitQuaternion::from_euler_angles(0.0, 0.0, angle)
+	}
+
+	/// Level rotations looking in a few directions that aren't along world Y, yawed and then pitched like mouse look.
+	fn looks() -> Vec<UnitQuaternion<f32>> {
+		[(0.0, 0.0), (0.3, 0.0), (-1.2, 2.0), (0.0, -2.9), (1.4, 0.7)]
+			.into_iter()
+			.map(|(pitch, yaw)| {
+				UnitQuaternion::from_axis_angle(&Vector3::x_axis(), pitch)
+					* UnitQuaternion::from_axis_angle(&Vector3::y_axis(), yaw)
+			})
+			.collect()
+	}
+
+	#[test]
+	fn level_has_no_roll() {
+		for look in looks() {
+			let relative = relative_roll(&look, &Vector3::y()).unwrap();
+			assert!(relative.abs() < 1e-5, "{relative}");
+		}
+	}
+
+	#[test]
+	fn rolling_by_relative_roll_levels() {
+		for look in looks() {
+			for angle in [0.1, -0.5, FRAC_PI_2, -3.0] {
+				let rotation = roll(angle) * look;
+				let relative = relative_roll(&rotation, &Vector3::y()).unwrap();
+
+				assert!(
+					(relative.abs() - angle.abs()).abs() < 1e-4,
+					"{relative} {angle}"
+				);
+
+				let levelled = relative_roll(&(roll(relative) * rotation), &Vector3::y()).unwrap();
+				assert!(levelled.abs() < 1e-4, "{levelled}");
+			}
+		}
+	}
+
+	#[test]
+	fn upside_down_is_half_a_turn() {
+		let relative = relative_roll(&roll(PI), &Vector3::y()).unwrap();
+		assert!((relative.abs() - PI).abs() < 1e-4, "{relative}");
+	}
+
+	#[test]
+	fn roll_is_relative_to_up() {
+		let up = vector![1.0, 1.0, 0.0];
+
+		// Facing along z with up as the view's up, then rolled
+		let level = UnitQuaternion::rotation_between(&up, &Vector3::y()).unwrap();
+		assert!(relative_roll(&level, &up).unwrap().abs() < 1e-4);
+
+		let rolled = relative_roll(&(roll(FRAC_PI_4) * level), &up).unwrap();
+		assert!((rolled.abs() - FRAC_PI_4).abs() < 1e-4, "{rolled}");
+
+		// Only up's direction matters
+		let scaled = relative_roll(&(roll(FRAC_PI_4) * level), &(up * 1000.0)).unwrap();
+		assert!((scaled - rolled).abs() < 1e-5);
+	}
+
+	#[test]
+	fn straight_up_or_down_has_no_roll() {
+		for pitch in [FRAC_PI_2, -FRAC_PI_2] {
+			let rotation = UnitQuaternion::from_euler_angles(pitch, 0.0, 0.0);
+
+			assert_eq!(relative_roll(&rotation, &Vector3::y()), None);
+			assert_eq!(relative_roll(&(roll(1.0) * rotation), &Vector3::y()), None);
+
+			// Almost straight along up is treated the same
+			let almost = UnitQuaternion::from_euler_angles(pitch - pitch.signum() * 1e-4, 0.0, 0.0);
+			assert_eq!(relative_roll(&almost, &Vector3::y()), None);
+		}
+	}
+
+	#[test]
+	fn no_up_has_no_roll() {
+		assert_eq!(relative_roll(&roll(1.0), &Vector3::zeros()), None);
+	}
+
+	#[test]
+	fn critically_damped_matches_exact_solution() {
+		let (position, velocity, strength) = (1.0, 0.5, 2.0_f32);
+
+		for time in [0.0, 0.1, 1.0, 5.0] {
+			let expected =
+				(position + (velocity + strength * position) * time) * (-strength * time).exp();
+			let (actual, _) = critically_damped(position, velocity, strength, time);
+
+			assert!(
+				(actual - expected).abs() < 1e-5,
+				"{time}: {actual} {expected}"
+			);
+		}
+	}
+
+	#[test]
+	fn critically_damped_steps_compose() {
+		let once = critically_damped(1.0, -0.3, 1.5, 0.5);
+
+		let half = critically_damped(1.0, -0.3, 1.5, 0.25);
+		let twice = critically_damped(half.0, half.1, 1.5, 0.25);
+
+		assert!((once.0 - twice.0).abs() < 1e-5 && (once.1 - twice.1).abs() < 1e-5);
+	}
+
+	#[test]
+	fn critically_damped_settles_without_overshoot() {
+		for delta in [1.0 / 144.0, 1.0 / 20.0, 1.0, 100.0] {
+			let (mut position, mut velocity) = (-1.0, 0.0);
+
+			for _ in 0..1000 {
+				let previous = position;
+				(position, velocity) = critically_damped(position, velocity, 1.0, delta);
+
+				assert!(position <= 0.0, "overshot at {delta}");
+				assert!(position >= previous, "moved away at {delta}");
+			}
+
+			if delta >= 1.0 / 20.0 {
+				assert!(position.abs() < 1e-3, "didn't settle at {delta}");
+			}
+		}
+
+		assert_eq!(critically_damped(0.0, 0.0, 1.0, 1.0), (0.0, 0.0));
+	}
+
+	#[test]
+	fn correction_rolls_towards_level() {
+		let assist = RollAssist::default();
+		let rotation = roll(0.5);
+
+		let (correction, velocity) = assist
+			.correction(&rotation, &Vector3::y(), 0.0, 0.1)
+			.unwrap();
+		let before = relative_roll(&rotation, &Vector3::y()).unwrap();
+		let after = relative_roll(&(correction * rotation), &Vector3::y()).unwrap();
+
+		assert!(after.abs() < before.abs());
+		assert!(after.signum() == before.signum());
+		assert!(velocity != 0.0);
+
+		let disabled = RollAssist {
+			enabled: false,
+			..assist
+		};
+		assert!(disabled
+			.correction(&rotation, &Vector3::y(), 0.0, 0.1)
+			.is_none());
+	}
+}
@@ -0,0 +1,249 @@
+use crate::{
+	adapter::{self, AdapterPreference, Backend},
+	renderer::{Diagnostics, RenderInitError},
+	tr,
+};
+use egui::{Align2, Context, ScrollArea, TextStyle, ViewportId, Window as EguiWindow};
+use egui_wgpu::{Renderer as EguiRenderer, ScreenDescriptor};
+use egui_winit::State as EguiState;
+use log::{info, warn};
+use std::{iter::once, sync::Arc};
+use tokio::runtime::Handle;
+use wgpu::{
+	Color, CommandEncoderDescriptor, CompositeAlphaMode::Opaque, Device, DeviceDescriptor,
+	Features, Instance, InstanceDescriptor, Limits, LoadOp::Clear, MemoryHints::MemoryUsage,
+	Operations, PresentMode::AutoVsync, Queue, RenderPassColorAttachment, RenderPassDescriptor,
+	StoreOp::Store, Surface, SurfaceConfiguration, TextureFormat, TextureUsages,
+	TextureViewDescriptor,
+};
+use winit::{dpi::PhysicalSize, event::WindowEvent, window::Window};
+
+/// Shown instead of the game when the [`Renderer`](crate::renderer::Renderer) can't start, so that players can see
+/// why and report it rather than the client crashing. Only draws the UI, which asks far less of the device.
+pub struct SafeMode {
+	pub window: Arc<Window>,
+	surface: Surface<'static>,
+	config: SurfaceConfiguration,
+
+	device: Device,
+	queue: Queue,
+
+	egui_state: EguiState,
+	egui_renderer: EguiRenderer,
+
+	diagnostics: String,
+	pub quit: bool,
+}
+
+impl SafeMode {
+	const CLEAR_COLOR: Color = Color {
+		r: 0.05,
+		g: 0.05,
+		b: 0.08,
+		a: 1.0,
+	};
+
+	pub fn new(window: Arc<Window>, diagnostics: &Diagnostics) -> Result<Self, RenderInitError> {
+		let instance = Instance::new(InstanceDescriptor {
+			backends: Backend::ALL,
+			..Default::default()
+		});
+
+		let surface = instance.create_surface(window.clone())?;
+
+		// The command line preference may be why the renderer failed, so any adapter will do
+		let adapter = adapter::select_adapter(&instance, &surface, &AdapterPreference::default())?;
+
+		info!(
+			"Starting safe mode using adapter: {}",
+			adapter::Described(&adapter.get_info())
+		);
+
+		let (device, queue) = Handle::current().block_on(adapter.request_device(
+			&DeviceDescriptor {
+				label: Some("safe_mode#device"),
+				required_features: Features::empty(),
+				required_limits:
+					Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits()),
+				memory_hints: MemoryUsage,
+			},
+			None,
+		))?;
+
+		let surface_capabilities = surface.get_capabilities(&adapter);
+
+		let surface_format = surface_capabilities
+			.formats
+			.iter()
+			.copied()
+			.find(TextureFormat::is_srgb)
+			.ok_or(RenderInitError::NoSurfaceFormat)?;
+
+		let PhysicalSize { width, height } = window.inner_size();
+
+		let config = SurfaceConfiguration {
+			usage: TextureUsages::RENDER_ATTACHMENT,
+			format: surface_format,
+			width: width.max(1),
+			height: height.max(1),
+			present_mode: AutoVsync,
+			desired_maximum_frame_latency: 2,
+			alpha_mode: Opaque,
+			view_formats: vec![],
+		};
+
+		surface.configure(&device, &config);
+
+		let egui_state = EguiState::new(
+			Context::default(),
+			ViewportId::default(),
+			&*window,
+			None,
+			None,
+			None,
+		);
+		let egui_renderer = EguiRenderer::new(&device, config.format, None, 1, false);
+
+		Ok(Self {
+			window,
+			surface,
+			config,
+
+			device,
+			queue,
+
+			egui_state,
+			egui_renderer,
+
+			diagnostics: diagnostics.to_string(),
+			quit: false,
+		})
+	}
+
+	pub fn resize(&mut self, size: PhysicalSize<u32>) {
+		if size.width == 0 || size.height == 0 {
+			return;
+		}
+
+		self.config.width = size.width;
+		self.config.height = size.height;
+		self.surface.configure(&self.device, &self.config);
+	}
+
+	pub fn handle_window_event(&mut self, event: &WindowEvent) {
+		let response = self.egui_state.on_window_event(&self.window, event);
+
+		if response.repaint {
+			self.window.request_redraw();
+		}
+	}
+
+	pub fn render(&mut self) {
+		let output = match self.surface.get_current_texture() {
+			Ok(output) => output,
+			Err(error) => {
+				warn!("Unable to get the safe mode surface texture: {error}");
+				return;
+			}
+		};
+
+		let gui_input = self.egui_state.take_egui_input(&self.window);
+
+		let gui_output = self.egui_state.egui_ctx().run(gui_input, |context| {
+			EguiWindow::new(tr!("safe_mode.title"))
+				.id(egui::Id::new("safe_mode"))
+				.anchor(Align2::CENTER_CENTER, (0.0, 0.0))
+				.resizable(false)
+				.collapsible(false)
+				.max_width(600.0)
+				.show(context, |window| {
+					window.label(tr!("safe_mode.explanation"));
+					window.separator();
+
+					ScrollArea::vertical()
+						.max_height(300.0)
+						.show(window, |scroll_area| {
+							scroll_area.label(
+								egui::RichText::new(&self.diagnostics)
+									.text_style(TextStyle::Monospace),
+							);
+						});
+
+					window.separator();
+
+					window.horizontal(|horizontal| {
+						if horizontal
+							.button(tr!("safe_mode.copy_diagnostics"))
+							.clicked()
+						{
+							horizontal.ctx().copy_text(self.diagnostics.clone());
+						}
+
+						if horizontal.button(tr!("safe_mode.quit")).clicked() {
+							self.quit = true;
+						}
+					});
+				});
+		});
+
+		self.egui_state
+			.handle_platform_output(&self.window, gui_output.platform_output);
+
+		let paint_jobs = self
+			.egui_state
+			.egui_ctx()
+			.tessellate(gui_output.shapes, gui_output.pixels_per_point);
+		let screen_descriptor = &ScreenDescriptor {
+			size_in_pixels: [self.config.width, self.config.height],
+			pixels_per_point: gui_output.pixels_per_point,
+		};
+
+		for (id, image_delta) in gui_output.textures_delta.set {
+			self.egui_renderer
+				.update_texture(&self.device, &self.queue, id, &image_delta);
+		}
+
+		let mut encoder = self
+			.device
+			.create_command_encoder(&CommandEncoderDescriptor::default());
+
+		self.egui_renderer.update_buffers(
+			&self.device,
+			&self.queue,
+			&mut encoder,
+			&paint_jobs,
+			screen_descriptor,
+		);
+
+		let view = output
+			.texture
+			.create_view(&TextureViewDescriptor::default());
+
+		{
+			let mut render_pass = encoder
+				.begin_render_pass(&RenderPassDescriptor {
+					label: Some("safe_mode.egui#pass"),
+					color_attachments: &[Some(RenderPassColorAttachment {
+						ops: Operations {
+							load: Clear(Self::CLEAR_COLOR),
+							store: Store,
+						},
+						resolve_target: None,
+						view: &view,
+					})],
+					..Default::default()
+				})
+				.forget_lifetime();
+
+			self.egui_renderer
+				.render(&mut render_pass, &paint_jobs, screen_descriptor);
+		}
+
+		self.queue.submit(once(encoder.finish()));
+		output.present();
+
+		for id in gui_output.textures_delta.free {
+			self.egui_renderer.free_texture(&id);
+		}
+	}
+}
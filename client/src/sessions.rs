@@ -0,0 +1,163 @@
+use crate::{
+	login::{Session, SessionInfo},
+	palette::UiColor,
+	tr,
+};
+use egui::{Button, CollapsingHeader, RichText, Ui};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::{runtime::Handle, task::JoinHandle};
+
+/// Lists the player's sessions in the settings, so that ones they no longer use can be revoked. Only loaded once
+/// the section is opened.
+pub struct Sessions {
+	session: Session,
+
+	sessions: Option<Vec<SessionInfo>>,
+	error: Option<String>,
+	/// Listing, or revoking then listing again.
+	request: Option<JoinHandle<Result<Vec<SessionInfo>, anyhow::Error>>>,
+
+	/// Set once the player has asked to revoke the current session, which needs confirming as it logs them out.
+	confirming_current: bool,
+}
+
+impl Sessions {
+	pub fn new(session: Session) -> Self {
+		Self {
+			session,
+			sessions: None,
+			error: None,
+			request: None,
+			confirming_current: false,
+		}
+	}
+
+	fn refresh(&mut self) {
+		let session = self.session.clone();
+		self.request = Some(Handle::current().spawn(async move { session.list_sessions().await }));
+	}
+
+	fn revoke(&mut self, prefix: String, current: bool) {
+		let session = self.session.clone();
+		self.request = Some(Handle::current().spawn(async move {
+			session.revoke_session(&prefix, current).await?;
+
+			// The token used to list them is the one that was just revoked
+			match current {
+				true => Ok(vec![]),
+				false => session.list_sessions().await,
+			}
+		}));
+	}
+
+	pub fn draw(&mut self, ui: &mut Ui) {
+		if let Some(request) = &mut self.request {
+			if request.is_finished() {
+				match Handle::current().block_on(request).unwrap() {
+					Ok(sessions) => {
+						self.sessions = Some(sessions);
+						self.error = None;
+					}
+					Err(error) => self.error = Some(error.to_string()),
+				}
+
+				self.request = None;
+			}
+		}
+
+		let header = CollapsingHeader::new(tr!("sessions.title"))
+			.id_salt("sessions")
+			.show(ui, |ui| {
+				if let Some(error) = &self.error {
+					ui.label(RichText::new(error).color(UiColor::ErrorText.color()));
+				}
+
+				let now = SystemTime::now()
+					.duration_since(UNIX_EPOCH)
+					.map_or(0, |now| now.as_secs());
+
+				let mut revoke = None;
+				let mut confirm_current = false;
+
+				for session in self.sessions.iter().flatten() {
+					ui.horizontal(|ui| {
+						ui.monospace(&session.prefix);
+						ui.label(tr!(
+							"sessions.last_used",
+							ago = format_ago(now.saturating_sub(session.last_used))
+						))
+						.on_hover_text(tr!(
+							"sessions.created",
+							ago = format_ago(now.saturating_sub(session.created))
+						));
+
+						if session.current {
+							ui.label(tr!("sessions.current"));
+						}
+
+						let button = ui.add_enabled(
+							self.request.is_none(),
+							Button::new(tr!("sessions.revoke")),
+						);
+
+						if button.clicked() {
+							match session.current {
+								true => confirm_current = true,
+								false => revoke = Some((session.prefix.clone(), false)),
+							}
+						}
+					});
+				}
+
+				self.confirming_current |= confirm_current;
+
+				if self.confirming_current {
+					ui.label(tr!("sessions.confirm_current"));
+					ui.horizontal(|ui| {
+						if ui.button(tr!("sessions.confirm")).clicked() {
+							self.confirming_current = false;
+
+							let current = self
+								.sessions
+								.iter()
+								.flatten()
+								.find(|session| session.current);
+							if let Some(current) = current {
+								revoke = Some((current.prefix.clone(), true));
+							}
+						}
+
+						if ui.button(tr!("sessions.cancel")).clicked() {
+							self.confirming_current = false;
+						}
+					});
+				}
+
+				if self.request.is_some() {
+					ui.spinner();
+				} else if ui.button(tr!("sessions.refresh")).clicked() {
+					self.refresh();
+				}
+
+				revoke
+			});
+
+		if header.fully_open() && self.sessions.is_none() && self.request.is_none() {
+			self.refresh();
+		}
+
+		if let Some((prefix, current)) = header.body_returned.flatten() {
+			self.revoke(prefix, current);
+		}
+	}
+}
+
+/// How long ago something was, roughly.
+fn format_ago(seconds: u64) -> String {
+	match seconds {
+		0..60 => tr!("sessions.just_now"),
+		60..3600 => tr!("sessions.minutes_ago", minutes = seconds / 60),
+		3600..86400 => tr!("sessions.hours_ago", hours = seconds / 3600),
+		_ => tr!("sessions.days_ago", days = seconds / 86400),
+	}
+}
@@ -0,0 +1,271 @@
+use log::{debug, info};
+use nalgebra::{vector, Vector3};
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::{
+	connection::{ClientEnd, Connection, ServerEnd},
+	consts::{CELLS_PER_CHUNK, CHUNK_SIZE},
+	data::{
+		world::{
+			ChunkCoordinates, Item, Level, Location, Material, LEVELS, UNBOUNDED_CHUNK_RADIUS,
+		},
+		Id,
+	},
+	generation,
+	message::{
+		clientbound::{
+			Ambient, AmbientKeyframe, ChatMessage, InventoryCapacity, InventorySlot, JoinComplete,
+			LocalizedText, Pong, RemoveChunk, RemoveStructure, Severity, Sync, SyncChunk,
+			SyncInventory, UiEvent, Voxject,
+		},
+		serverbound::{DeleteStructure, Ping, SendChatMessage, Serverbound},
+	},
+	physics::Physics,
+	structure::Structure,
+};
+use std::{collections::HashSet, sync::Arc};
+
+/// A sector running inside the client, so that client features can be worked on without a database, gateway, or
+/// sector server. There is a single sphere voxject, chunks are generated as they're needed, and nothing is saved.
+pub struct LocalSector {
+	connection: Connection<ServerEnd>,
+
+	player: Id,
+	voxject: Id,
+
+	physics: Physics,
+	structures: Vec<Structure>,
+
+	inventory: SyncInventory,
+
+	synced_chunks: HashSet<ChunkCoordinates, FxBuildHasher>,
+	/// Set once the first chunks have been synced, and [`JoinComplete`] sent.
+	joined: bool,
+
+	/// Sequence number of the next chat message, there's no history to keep as nobody else can join.
+	chat_sequence: u64,
+}
+
+impl LocalSector {
+	/// How far away from the player level 0 chunks are synced, in chunks.
+	const SYNC_RADIUS: i32 = 4;
+
+	/// The ambient cycle doesn't run, it's always midday.
+	const AMBIENT: AmbientKeyframe = AmbientKeyframe {
+		phase: 0.25,
+		sky: [0.3, 0.5, 0.9],
+		sun: [1.0, 0.95, 0.9],
+		ambient: [0.3, 0.3, 0.35],
+	};
+
+	/// Starts a local sector in the background, returning the client's end of the connection to it. The sector stops
+	/// once the connection is dropped.
+	pub fn start() -> Connection<ClientEnd> {
+		let (client, server) = Connection::pair();
+
+		let sector = Self {
+			connection: server,
+
+			player: Id::new(),
+			voxject: Id::new(),
+
+			physics: Physics::new(),
+			structures: vec![],
+
+			inventory: SyncInventory {
+				slots: vec![],
+				capacity: InventoryCapacity::default(),
+			},
+
+			synced_chunks: HashSet::with_hasher(FxBuildHasher),
+			joined: false,
+
+			chat_sequence: 0,
+		};
+
+		tokio::spawn(sector.run());
+
+		client
+	}
+
+	async fn run(mut self) {
+		info!("Started singleplayer sector");
+
+		self.connection.send(Sync {
+			name: "Singleplayer".into(),
+			// Doesn't actually tick, but this is what sectors run at by default
+			tick_rate: 30,
+			world_radius: None,
+			world_radius_chunks: UNBOUNDED_CHUNK_RADIUS,
+			max_level: Level::new(LEVELS - 2),
+
+			voxjects: vec![Voxject {
+				id: self.voxject,
+				name: "planet".into(),
+			}],
+
+			ambient_keyframes: vec![Self::AMBIENT],
+			ambient: Ambient {
+				phase: Self::AMBIENT.phase,
+				rate: 0.0,
+			},
+		});
+
+		self.connection.send(self.inventory.clone());
+
+		while let Some(message) = self.connection.recv().await {
+			self.handle_message(message);
+		}
+
+		info!("Stopped singleplayer sector");
+	}
+
+	fn handle_message(&mut self, message: Serverbound) {
+		match message {
+			Serverbound::PlayerLocation(location) => {
+				self.sync_chunks(&location);
+
+				// Everything is sent straight away, so the first location is all there is to wait for
+				if !self.joined {
+					self.joined = true;
+					self.connection.send(JoinComplete);
+				}
+			}
+			Serverbound::GiveTestItem => {
+				let (item, quantity) = (Item::TestOre, 1);
+				let added = self.inventory.addable(item, quantity);
+
+				match self
+					.inventory
+					.slots
+					.iter_mut()
+					.find(|slot| slot.item == item)
+				{
+					Some(slot) => slot.quantity += added,
+					None if added > 0 => self.inventory.slots.push(InventorySlot {
+						item,
+						quantity: added,
+					}),
+					None => {}
+				}
+
+				if added < quantity {
+					self.send_system_message(
+						LocalizedText::new("server.inventory_full")
+							.with("quantity", quantity - added)
+							.with("item", item),
+						Severity::Warning,
+					);
+				}
+
+				self.connection.send(self.inventory.clone());
+			}
+			Serverbound::CreateStructure(create_structure) => {
+				// Structures aren't synced again after they're created, so physics doesn't need to run
+				let structure = Structure::new(&mut self.physics, self.player, create_structure);
+				self.connection.send(structure.build_sync(&self.physics));
+				self.structures.push(structure);
+			}
+			Serverbound::DeleteStructure(DeleteStructure { structure }) => {
+				self.structures.retain(|existing| existing.id != structure);
+				self.connection.send(RemoveStructure(structure));
+			}
+			Serverbound::ModifyTerrainBrush(_) | Serverbound::ModifyVoxel(_) => self
+				.send_system_message(
+					LocalizedText::new("singleplayer.terrain_unavailable"),
+					Severity::Info,
+				),
+			// The cycle doesn't run anyway
+			Serverbound::SetAmbientPaused(_) => {}
+			// Nobody to report to
+			Serverbound::ClientTelemetry(_) => {}
+			// Singleplayer chunks aren't cached, so nothing is ever sent as unchanged
+			Serverbound::HaveChunks(_) | Serverbound::RequestChunk(_) => {}
+			// Only a small radius is ever synced, so the order it's synced in hardly matters
+			Serverbound::ViewHint(_) => {}
+			// Interactions are registered by the sector server
+			Serverbound::InteractBlock(_) => self.send_system_message(
+				LocalizedText::new("singleplayer.interaction_unavailable"),
+				Severity::Info,
+			),
+			Serverbound::Ping(Ping { sequence }) => self.connection.send(Pong { sequence }),
+			Serverbound::SendChatMessage(SendChatMessage { text }) => match text.starts_with('/') {
+				true => self.send_system_message(
+					LocalizedText::new("singleplayer.commands_unavailable"),
+					Severity::Info,
+				),
+				false => {
+					self.connection.send(ChatMessage {
+						sequence: self.chat_sequence,
+						player: self.player,
+						text,
+					});
+					self.chat_sequence += 1;
+				}
+			},
+		}
+	}
+
+	/// Syncs the level 0 chunks within [`Self::SYNC_RADIUS`] of the player, and removes those that are now too far
+	/// away.
+	fn sync_chunks(&mut self, location: &Location) {
+		let center = (location.position.coords / CHUNK_SIZE as f32)
+			.map(|coordinate| coordinate.floor() as i32);
+
+		let mut in_range = HashSet::with_hasher(FxBuildHasher);
+
+		for x in -Self::SYNC_RADIUS..=Self::SYNC_RADIUS {
+			for y in -Self::SYNC_RADIUS..=Self::SYNC_RADIUS {
+				for z in -Self::SYNC_RADIUS..=Self::SYNC_RADIUS {
+					let offset: Vector3<i32> = vector![x, y, z];
+
+					if offset.cast::<f32>().norm() > Self::SYNC_RADIUS as f32 {
+						continue;
+					}
+
+					in_range.insert(ChunkCoordinates::new(
+						self.voxject,
+						center + offset,
+						Level::new(0),
+					));
+				}
+			}
+		}
+
+		for coordinates in self.synced_chunks.difference(&in_range) {
+			self.connection.send(RemoveChunk(*coordinates));
+		}
+
+		let mut synced = 0;
+
+		for coordinates in in_range.difference(&self.synced_chunks) {
+			let mut materials = Arc::new([Material::Nothing; CELLS_PER_CHUNK]);
+			let mut densities = Arc::new([0.0; CELLS_PER_CHUNK]);
+
+			generation::sphere(
+				coordinates,
+				Arc::get_mut(&mut materials).expect("should not be shared yet"),
+				Arc::get_mut(&mut densities).expect("should not be shared yet"),
+			);
+
+			self.connection.send(SyncChunk {
+				coordinates: *coordinates,
+				generation: 0,
+				materials,
+				densities,
+			});
+
+			synced += 1;
+		}
+
+		if synced > 0 {
+			debug!("Synced {synced} chunks");
+		}
+
+		self.synced_chunks = in_range;
+	}
+
+	fn send_system_message(&self, text: LocalizedText, severity: Severity) {
+		self.connection
+			.send(UiEvent::SystemMessage { text, severity });
+	}
+}
@@ -8,16 +8,38 @@ struct InstanceInput {
 	@location(3) model_b: vec4<f32>,
 	@location(4) model_c: vec4<f32>,
 	@location(5) model_d: vec4<f32>,
-	@location(6) opacity: f32,
+	// RGB multiplies the texture, A is opacity
+	@location(6) color: vec4<f32>,
+	// Cell of the texture atlas to use, or RAW_TEXTURE_COORDINATES if the model's texture coordinates cover the
+	// whole atlas
+	@location(7) atlas_cell: u32,
 }
 
+// Must match ATLAS_CELL_SIZE in renderer.rs
+const ATLAS_CELL_SIZE: u32 = 16u;
+const RAW_TEXTURE_COORDINATES: u32 = 0xFFFFFFFFu;
+
 struct Vertex {
 	@builtin(position) position: vec4<f32>,
 	@location(0) texture_coordinates: vec2<f32>,
-	@location(1) opacity: f32,
+	@location(1) color: vec4<f32>,
+	@location(2) world_position: vec3<f32>,
+}
+
+// Must match Lighting::push_constants in ambient.rs
+struct Lighting {
+	// Towards the sun
+	sun_direction: vec3<f32>,
+	sun: vec3<f32>,
+	ambient: vec3<f32>,
+}
+
+struct PushConstants {
+	camera: mat4x4<f32>,
+	lighting: Lighting,
 }
 
-var<push_constant> camera: mat4x4<f32>;
+var<push_constant> push_constants: PushConstants;
 
 @group(0) @binding(0) var texture: texture_2d<f32>;
 @group(0) @binding(1) var texture_sampler: sampler;
@@ -27,16 +49,51 @@ var<push_constant> camera: mat4x4<f32>;
 
 	var output: Vertex;
 
-	output.position = camera * model * vec4(vertex.position, 1.0);
-	output.texture_coordinates = vertex.texture_coordinates;
-	output.opacity = instance.opacity;
+	let world_position = model * vec4(vertex.position, 1.0);
+
+	output.position = push_constants.camera * world_position;
+	output.world_position = world_position.xyz;
+	output.texture_coordinates = atlas_texture_coordinates(vertex.texture_coordinates, instance.atlas_cell);
+	output.color = instance.color;
 
 	return output;
 }
 
+fn atlas_texture_coordinates(texture_coordinates: vec2<f32>, atlas_cell: u32) -> vec2<f32> {
+	if atlas_cell == RAW_TEXTURE_COORDINATES {
+		return texture_coordinates;
+	}
+
+	let atlas_size = textureDimensions(texture) / ATLAS_CELL_SIZE;
+	let cell = vec2(atlas_cell % atlas_size.x, atlas_cell / atlas_size.x);
+
+	return (vec2<f32>(cell) + texture_coordinates) / vec2<f32>(atlas_size);
+}
+
 @fragment fn fragment(vertex: Vertex) -> @location(0) vec4<f32> {
-	return vec4(
-		textureSample(texture, texture_sampler, vertex.texture_coordinates).xyz,
-		vertex.opacity
-	);
+	let color = textureSample(texture, texture_sampler, vertex.texture_coordinates).xyz * vertex.color.rgb;
+	return vec4(light(color, flat_normal(vertex.world_position)), vertex.color.a);
 }
+
+// Solid color, for highlighting the block the player is aiming at.
+@fragment fn fragment_highlight(vertex: Vertex) -> @location(0) vec4<f32> {
+	return vertex.color;
+}
+
+// Debug render mode, shows the surface normal as a color.
+@fragment fn fragment_normals(vertex: Vertex) -> @location(0) vec4<f32> {
+	return vec4(flat_normal(vertex.world_position) * 0.5 + 0.5, vertex.color.a);
+}
+
+// The models don't carry normals, so derive a flat one from the surface instead. Framebuffer y points down, hence
+// dpdy first.
+fn flat_normal(world_position: vec3<f32>) -> vec3<f32> {
+	return normalize(cross(dpdy(world_position), dpdx(world_position)));
+}
+
+// Directional light from the sun, plus ambient light which reaches every surface.
+fn light(color: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+	let lighting = push_constants.lighting;
+	return color * (lighting.ambient + lighting.sun * max(dot(normal, lighting.sun_direction), 0.0));
+}
+
@@ -0,0 +1,109 @@
+use crate::world::Sector;
+use nalgebra::{Isometry3, Point3, Translation3, Vector3};
+use rapier3d::geometry::{Ball, Collider};
+use solarscape_shared::{
+	consts::CHUNK_SIZE,
+	data::world::{BlockType, CellCoordinates, ChunkCoordinates, Material},
+	physics::{ColliderOwner, CollisionGroup, QueryMask, ShapeHit},
+};
+
+/// What the player is standing on, if anything. Found by [`Surface::probe`] each tick.
+#[derive(Clone, Copy, Debug)]
+pub enum Surface {
+	Terrain(Material),
+	Block(BlockType),
+}
+
+impl Surface {
+	/// Radius of the sphere swept down from the player, standing in for the player's body until they have one.
+	const PROBE_RADIUS: f32 = 0.4;
+
+	/// How far the sphere is swept, anything further below the player isn't being stood on.
+	const PROBE_DISTANCE: f32 = 1.5;
+
+	/// How far into the surface to look, so that the point is inside of whatever was hit rather than on it's surface.
+	const NUDGE: f32 = 0.001;
+
+	/// Movement speed is multiplied by this while standing on something, so that moving across a surface is slower
+	/// than flying.
+	pub const SPEED_MULTIPLIER: f32 = 0.75;
+
+	/// Sweeps a sphere down from the player, "down" being relative to the player as there's no gravity.
+	pub fn probe(sector: &Sector) -> Option<Self> {
+		let location = &sector.player.location;
+		let down = location.rotation.inverse_transform_vector(&-Vector3::y());
+
+		let ShapeHit {
+			hit, point, normal, ..
+		} = sector.physics.cast_shape(
+			&Isometry3::translation(
+				location.position.x,
+				location.position.y,
+				location.position.z,
+			),
+			&down,
+			&Ball::new(Self::PROBE_RADIUS),
+			Self::PROBE_DISTANCE,
+			QueryMask::new(&[CollisionGroup::Terrain, CollisionGroup::Structure]),
+		)?;
+
+		match hit.owner? {
+			ColliderOwner::StructureBlock(id, position) => sector
+				.structures
+				.iter()
+				.find(|structure| structure.id == id)?
+				.get_block(&position)
+				.map(|block| Self::Block(block.typ)),
+			ColliderOwner::Chunk(coordinates) => {
+				let collider = sector.physics.get_collider(hit.collider)?;
+				let cell = terrain_cell(collider, coordinates, &(point - normal * Self::NUDGE));
+				terrain_material(sector, &cell).map(Self::Terrain)
+			}
+			ColliderOwner::Player(_) => None,
+		}
+	}
+}
+
+/// The cell of the chunk whose mesh `collider` is that `point` is in. `point` should be slightly inside of the mesh,
+/// as a point on it's surface could be in a neighbouring cell.
+pub fn terrain_cell(
+	collider: &Collider,
+	coordinates: ChunkCoordinates,
+	point: &Point3<f32>,
+) -> CellCoordinates {
+	// Chunk meshes are built in cells, placed at the chunk's location but not scaled by it's level
+	let cell_size = coordinates.size() / CHUNK_SIZE as f32;
+	let local = collider.position().inverse_transform_point(point);
+
+	let mut position = coordinates.origin();
+	position.position += local.coords * cell_size;
+
+	position.cell(coordinates.level)
+}
+
+/// Where `cell` is in the frame of `collider`, the mesh of the chunk at `coordinates`. Cells are unit cubes in that
+/// frame, this being their lowest corner. `cell` doesn't have to be in that chunk, as [`terrain_cell`] can find one in
+/// a neighbour.
+pub fn terrain_cell_location(
+	collider: &Collider,
+	coordinates: ChunkCoordinates,
+	cell: &CellCoordinates,
+) -> Isometry3<f32> {
+	let local = cell.voxject_cell() - coordinates.coordinates * CHUNK_SIZE as i32;
+	collider.position() * Translation3::from(local.cast())
+}
+
+/// The material of whichever of the cell's corners is most solid, [`None`] if none of them are loaded or all are
+/// empty.
+pub fn terrain_material(sector: &Sector, cell: &CellCoordinates) -> Option<Material> {
+	cell.corners()
+		.iter()
+		.filter_map(|corner| {
+			let chunk = sector.chunks.get(&corner.chunk)?;
+			let index = corner.index();
+			Some((chunk.materials[index], chunk.densities[index]))
+		})
+		.filter(|(material, _)| !matches!(material, Material::Nothing))
+		.max_by(|(_, a), (_, b)| a.total_cmp(b))
+		.map(|(material, _)| material)
+}
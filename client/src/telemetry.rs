@@ -0,0 +1,192 @@
+use solarscape_shared::message::serverbound::ClientTelemetry;
+use std::time::{Duration, Instant};
+
+/// Collects frame times between telemetry reports. Nothing is collected or sent unless the player has opted in.
+pub struct Telemetry {
+	frame_times: Vec<Duration>,
+	last_report: Instant,
+}
+
+impl Telemetry {
+	/// A minute at 500 FPS, anything past this is dropped rather than letting the buffer grow without limit.
+	const MAX_SAMPLES: usize = 30_000;
+
+	pub fn new() -> Self {
+		Self {
+			frame_times: vec![],
+			last_report: Instant::now(),
+		}
+	}
+
+	pub fn record(&mut self, frame_time: Duration) {
+		if self.frame_times.len() < Self::MAX_SAMPLES {
+			self.frame_times.push(frame_time);
+		}
+	}
+
+	/// Discards everything collected so far, for when the player opts out.
+	pub fn clear(&mut self) {
+		self.frame_times.clear();
+		self.last_report = Instant::now();
+	}
+
+	/// Builds a report once [`ClientTelemetry::INTERVAL`] has passed since the last one.
+	pub fn take_report(
+		&mut self,
+		mesh_jobs_pending: usize,
+		adapter_summary_hash: u64,
+	) -> Option<ClientTelemetry> {
+		if self.last_report.elapsed() < ClientTelemetry::INTERVAL {
+			return None;
+		}
+
+		let stats = frame_stats(&mut self.frame_times);
+		self.clear();
+
+		let FrameStats {
+			fps_avg,
+			fps_p1,
+			frame_time_p99,
+		} = stats?;
+
+		Some(ClientTelemetry {
+			fps_avg,
+			fps_p1,
+			frame_time_p99_ms: frame_time_p99.as_secs_f32() * 1000.0,
+			mesh_jobs_pending: u32::try_from(mesh_jobs_pending).unwrap_or(u32::MAX),
+			adapter_summary_hash,
+		})
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameStats {
+	pub fps_avg: f32,
+	/// Average FPS of the slowest 1% of frames, at least one frame.
+	pub fps_p1: f32,
+	/// The frame time 99% of frames were at or under.
+	pub frame_time_p99: Duration,
+}
+
+/// Summarizes `frame_times`, which are sorted in the process. Returns [`None`] if there are no frame times, or they add
+/// up to no time at all.
+pub fn frame_stats(frame_times: &mut [Duration]) -> Option<FrameStats> {
+	if frame_times.is_empty() {
+		return None;
+	}
+
+	frame_times.sort_unstable();
+
+	let count = frame_times.len();
+	let total = frame_times.iter().sum::<Duration>();
+
+	if total.is_zero() {
+		return None;
+	}
+
+	// Not zero, as the total isn't and these are the longest frames
+	let slowest = &frame_times[count - count.div_ceil(100)..];
+	let slowest_total = slowest.iter().sum::<Duration>();
+
+	// Nearest rank, the smallest frame time that at least 99% of frames are at or under
+	let p99_index = (count * 99).div_ceil(100) - 1;
+
+	Some(FrameStats {
+		fps_avg: count as f32 / total.as_secs_f32(),
+		fps_p1: slowest.len() as f32 / slowest_total.as_secs_f32(),
+		frame_time_p99: frame_times[p99_index],
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn millis(frame_times: &[u64]) -> Vec<Duration> {
+		frame_times
+			.iter()
+			.map(|millis| Duration::from_millis(*millis))
+			.collect()
+	}
+
+	#[test]
+	fn steady_frames() {
+		let stats = frame_stats(&mut millis(&[10; 100])).unwrap();
+
+		assert!((stats.fps_avg - 100.0).abs() < 1e-3);
+		assert!((stats.fps_p1 - 100.0).abs() < 1e-3);
+		assert_eq!(stats.frame_time_p99, Duration::from_millis(10));
+	}
+
+	#[test]
+	fn slowest_frames_are_picked_out() {
+		// 198 quick frames, and 2 slow ones for the slowest 1%
+		let mut frame_times = millis(&[5; 198]);
+		frame_times.extend(millis(&[50, 100]));
+		frame_times.reverse();
+
+		let stats = frame_stats(&mut frame_times).unwrap();
+
+		assert!((stats.fps_avg - 200.0 / 1.14).abs() < 1e-2);
+		assert!((stats.fps_p1 - 2.0 / 0.15).abs() < 1e-3);
+		assert_eq!(stats.frame_time_p99, Duration::from_millis(5));
+
+		// Sorted in the process
+		assert!(frame_times.is_sorted());
+	}
+
+	#[test]
+	fn percentiles_use_the_nearest_rank() {
+		// A single frame is it's own slowest 1% and 99th percentile
+		let stats = frame_stats(&mut millis(&[20])).unwrap();
+		assert!((stats.fps_p1 - 50.0).abs() < 1e-3);
+		assert_eq!(stats.frame_time_p99, Duration::from_millis(20));
+
+		// 99 of 101 frames is under 99%, so the 99th percentile is the 100th frame
+		let mut frame_times = millis(&[1; 99]);
+		frame_times.extend(millis(&[7, 9]));
+
+		let stats = frame_stats(&mut frame_times).unwrap();
+		assert_eq!(stats.frame_time_p99, Duration::from_millis(7));
+		assert!((stats.fps_p1 - 2.0 / 0.016).abs() < 1e-2);
+	}
+
+	#[test]
+	fn nothing_to_report() {
+		assert_eq!(frame_stats(&mut []), None);
+		assert_eq!(frame_stats(&mut [Duration::ZERO; 3]), None);
+	}
+
+	#[test]
+	fn reports_wait_for_the_interval() {
+		let mut telemetry = Telemetry::new();
+
+		for frame_time in millis(&[10; 10]) {
+			telemetry.record(frame_time);
+		}
+
+		assert!(telemetry.take_report(0, 0).is_none());
+
+		telemetry.last_report -= ClientTelemetry::INTERVAL;
+		let report = telemetry.take_report(usize::MAX, 42).unwrap();
+
+		assert!((report.fps_avg - 100.0).abs() < 1e-3);
+		assert!((report.frame_time_p99_ms - 10.0).abs() < 1e-3);
+		assert_eq!(report.mesh_jobs_pending, u32::MAX);
+		assert_eq!(report.adapter_summary_hash, 42);
+
+		// The frames were used up by that report
+		assert!(telemetry.frame_times.is_empty());
+	}
+
+	#[test]
+	fn samples_are_capped() {
+		let mut telemetry = Telemetry::new();
+
+		for _ in 0..Telemetry::MAX_SAMPLES + 10 {
+			telemetry.record(Duration::from_millis(1));
+		}
+
+		assert_eq!(telemetry.frame_times.len(), Telemetry::MAX_SAMPLES);
+	}
+}
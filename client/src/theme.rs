@@ -0,0 +1,288 @@
+use crate::{palette::Palette, tr};
+use egui::{
+	epaint::Shadow, Color32, Context, FontData, FontDefinitions, FontFamily, FontId, Rgba,
+	Rounding, Style, TextStyle, Visuals,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering::Relaxed};
+
+/// Whether [`ThemeName::Light`] is selected, see [`select`].
+static LIGHT: AtomicBool = AtomicBool::new(false);
+
+/// Which of the built in themes is used, kept between sessions with the [`Accessibility`] settings.
+///
+/// [`Accessibility`]: crate::accessibility::Accessibility
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ThemeName {
+	#[default]
+	Dark,
+	Light,
+}
+
+impl ThemeName {
+	pub const ALL: [Self; 2] = [Self::Dark, Self::Light];
+
+	pub fn name(self) -> String {
+		match self {
+			Self::Dark => tr!("settings.theme_dark"),
+			Self::Light => tr!("settings.theme_light"),
+		}
+	}
+
+	/// This theme, with it's accent taken from `palette` so that it stays distinct from the colors that mean something.
+	pub const fn theme(self, palette: Palette) -> Theme {
+		let accent = match (self, palette) {
+			(Self::Dark, Palette::Standard) => Color32::from_rgb(255, 170, 70),
+			(Self::Light, Palette::Standard) => Color32::from_rgb(190, 100, 0),
+			(Self::Dark, Palette::ColorblindSafe) => Color32::from_rgb(86, 180, 233),
+			(Self::Light, Palette::ColorblindSafe) => Color32::from_rgb(0, 114, 178),
+		};
+
+		Theme {
+			dark: matches!(self, Self::Dark),
+			accent,
+			rounding: 4.0,
+			body_size: 14.0,
+			heading_size: 20.0,
+			small_size: 10.0,
+			monospace_size: 13.0,
+			spacing: 6.0,
+		}
+	}
+}
+
+/// How the UI looks, applied to egui's [`Style`] by [`Theme::apply`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Theme {
+	/// Dark backgrounds with light text, otherwise the other way around.
+	pub dark: bool,
+	/// Selections, and anything else that should stand out without meaning anything in particular.
+	pub accent: Color32,
+	/// Corner radius of windows, menus, and widgets.
+	pub rounding: f32,
+	pub body_size: f32,
+	pub heading_size: f32,
+	pub small_size: f32,
+	pub monospace_size: f32,
+	/// Gap between widgets, windows have twice this around their contents.
+	pub spacing: f32,
+}
+
+impl Theme {
+	pub fn apply(&self, style: &mut Style) {
+		let mut visuals = match self.dark {
+			true => Visuals::dark(),
+			false => Visuals::light(),
+		};
+
+		let rounding = Rounding::same(self.rounding);
+		visuals.window_rounding = rounding;
+		visuals.menu_rounding = rounding;
+
+		for widget in [
+			&mut visuals.widgets.noninteractive,
+			&mut visuals.widgets.inactive,
+			&mut visuals.widgets.hovered,
+			&mut visuals.widgets.active,
+			&mut visuals.widgets.open,
+		] {
+			widget.rounding = rounding;
+		}
+
+		visuals.selection.bg_fill = self.accent.gamma_multiply(0.6);
+		visuals.selection.stroke.color = self.readable(self.accent);
+		visuals.hyperlink_color = self.readable(self.accent);
+		visuals.widgets.hovered.bg_stroke.color = self.accent;
+		visuals.widgets.active.bg_stroke.color = self.accent;
+		visuals.window_shadow = Shadow {
+			blur: self.spacing * 2.0,
+			..visuals.window_shadow
+		};
+
+		style.visuals = visuals;
+
+		style.text_styles = [
+			(TextStyle::Small, FontId::proportional(self.small_size)),
+			(TextStyle::Body, FontId::proportional(self.body_size)),
+			(TextStyle::Button, FontId::proportional(self.body_size)),
+			(TextStyle::Heading, FontId::proportional(self.heading_size)),
+			(TextStyle::Monospace, FontId::monospace(self.monospace_size)),
+		]
+		.into();
+
+		style.spacing.item_spacing = [self.spacing, self.spacing].into();
+		style.spacing.window_margin = (self.spacing * 2.0).into();
+		style.spacing.menu_margin = self.spacing.into();
+		style.spacing.button_padding = [self.spacing, self.spacing / 2.0].into();
+	}
+
+	/// `color` made readable on this theme's windows. Light colors are darkened on light themes, and dark colors
+	/// lightened on dark themes, keeping their hue so that they still mean the same thing.
+	pub fn readable(&self, color: Color32) -> Color32 {
+		let rgba = Rgba::from(color);
+		let luminance = 0.2126 * rgba.r() + 0.7152 * rgba.g() + 0.0722 * rgba.b();
+
+		match (self.dark, luminance) {
+			(true, luminance) if luminance < 0.1 => lerp(color, Color32::WHITE, 0.5),
+			(false, luminance) if luminance > 0.3 => lerp(color, Color32::BLACK, 0.55),
+			_ => color,
+		}
+	}
+}
+
+fn lerp(from: Color32, to: Color32, amount: f32) -> Color32 {
+	let [from, to] = [from, to].map(|color| color.to_array().map(f32::from));
+	let [red, green, blue, alpha] = std::array::from_fn(|channel| {
+		(from[channel] + (to[channel] - from[channel]) * amount) as u8
+	});
+	Color32::from_rgba_premultiplied(red, green, blue, alpha)
+}
+
+/// Changes the theme [`selected`] returns, see [`Accessibility::apply`] for applying it to the UI.
+///
+/// [`Accessibility::apply`]: crate::accessibility::Accessibility::apply
+pub fn select(name: ThemeName) {
+	LIGHT.store(name == ThemeName::Light, Relaxed);
+}
+
+pub fn selected() -> ThemeName {
+	match LIGHT.load(Relaxed) {
+		true => ThemeName::Light,
+		false => ThemeName::Dark,
+	}
+}
+
+/// Uses `font` for all proportional text, or egui's own fonts if [`None`]. egui's fonts are kept after it, for any
+/// characters it doesn't have.
+pub fn set_font(context: &Context, font: Option<FontData>) {
+	let mut fonts = FontDefinitions::default();
+
+	if let Some(font) = font {
+		fonts.font_data.insert("ui".into(), font);
+		fonts
+			.families
+			.entry(FontFamily::Proportional)
+			.or_default()
+			.insert(0, "ui".into());
+	}
+
+	context.set_fonts(fonts);
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn luminance(color: Color32) -> f32 {
+		let rgba = Rgba::from(color);
+		0.2126 * rgba.r() + 0.7152 * rgba.g() + 0.0722 * rgba.b()
+	}
+
+	#[test]
+	fn themes_are_applied_to_the_style() {
+		for name in ThemeName::ALL {
+			let theme = name.theme(Palette::Standard);
+			let mut style = Style::default();
+			theme.apply(&mut style);
+
+			assert_eq!(style.visuals.dark_mode, name == ThemeName::Dark);
+			assert_eq!(
+				style.visuals.window_rounding,
+				Rounding::same(theme.rounding)
+			);
+			assert_eq!(
+				style.visuals.widgets.inactive.rounding,
+				Rounding::same(theme.rounding)
+			);
+			assert_eq!(style.visuals.widgets.hovered.bg_stroke.color, theme.accent);
+			assert_eq!(
+				style.text_styles[&TextStyle::Heading],
+				FontId::proportional(theme.heading_size)
+			);
+			assert_eq!(
+				style.text_styles[&TextStyle::Monospace],
+				FontId::monospace(theme.monospace_size)
+			);
+			assert_eq!(style.spacing.item_spacing, [theme.spacing; 2].into());
+			assert_eq!(style.spacing.window_margin, (theme.spacing * 2.0).into());
+		}
+	}
+
+	#[test]
+	fn accents_follow_the_palette() {
+		for name in ThemeName::ALL {
+			assert_ne!(
+				name.theme(Palette::Standard).accent,
+				name.theme(Palette::ColorblindSafe).accent,
+				"{name:?}"
+			);
+		}
+
+		// Okabe-Ito sky blue and blue
+		assert_eq!(
+			ThemeName::Dark.theme(Palette::ColorblindSafe).accent,
+			Color32::from_rgb(86, 180, 233)
+		);
+		assert_eq!(
+			ThemeName::Light.theme(Palette::ColorblindSafe).accent,
+			Color32::from_rgb(0, 114, 178)
+		);
+	}
+
+	#[test]
+	fn colors_are_made_readable_on_the_theme() {
+		let dark = ThemeName::Dark.theme(Palette::Standard);
+		let light = ThemeName::Light.theme(Palette::Standard);
+
+		// Already readable, so left alone
+		assert_eq!(dark.readable(Color32::YELLOW), Color32::YELLOW);
+		assert_eq!(
+			light.readable(Color32::from_rgb(0, 0, 128)),
+			Color32::from_rgb(0, 0, 128)
+		);
+
+		// Dark colors are lightened on dark backgrounds, light colors darkened on light ones
+		assert!(luminance(dark.readable(Color32::from_rgb(0, 0, 128))) >= 0.1);
+		assert!(luminance(light.readable(Color32::YELLOW)) < 0.3);
+		assert!(luminance(light.readable(Color32::WHITE)) < 0.3);
+
+		// Still the same hue, red stays red
+		let [red, green, blue, alpha] = light.readable(Color32::from_rgb(255, 96, 96)).to_array();
+		assert!(red > green && green == blue, "{red} {green} {blue}");
+		assert_eq!(alpha, 255);
+	}
+
+	#[test]
+	fn lerp_goes_between_colors() {
+		assert_eq!(lerp(Color32::BLACK, Color32::WHITE, 0.0), Color32::BLACK);
+		assert_eq!(lerp(Color32::BLACK, Color32::WHITE, 1.0), Color32::WHITE);
+		assert_eq!(
+			lerp(Color32::from_rgb(200, 0, 100), Color32::BLACK, 0.5),
+			Color32::from_rgb(100, 0, 50)
+		);
+	}
+
+	#[test]
+	fn ui_fonts_come_before_eguis() {
+		let context = Context::default();
+		let family = |context: &Context| {
+			let _ = context.run(Default::default(), |_| {});
+			context.fonts(|fonts| {
+				fonts.lock().fonts.definitions().families[&FontFamily::Proportional].clone()
+			})
+		};
+
+		let defaults = FontDefinitions::default().families[&FontFamily::Proportional].clone();
+		assert_eq!(family(&context), defaults);
+
+		let font = FontDefinitions::default().font_data["Hack"].clone();
+		set_font(&context, Some(font));
+
+		let mut expected = vec!["ui".to_owned()];
+		expected.extend(defaults.iter().cloned());
+		assert_eq!(family(&context), expected);
+
+		set_font(&context, None);
+		assert_eq!(family(&context), defaults);
+	}
+}
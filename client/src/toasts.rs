@@ -0,0 +1,134 @@
+use crate::palette::UiColor;
+use egui::{Align, Align2, Area, Color32, Context, Frame, Id, RichText};
+use solarscape_shared::message::clientbound::Severity;
+use std::{
+	collections::VecDeque,
+	time::{Duration, Instant},
+};
+
+/// Short lived messages shown at the top of the screen, such as system messages from the server.
+pub struct Toasts {
+	toasts: VecDeque<Toast>,
+	id: &'static str,
+	anchor: Align2,
+}
+
+struct Toast {
+	text: Box<str>,
+	severity: Severity,
+	expires_at: Instant,
+}
+
+impl Toasts {
+	/// Oldest toasts are dropped early to make room if there are more than this.
+	const MAX_TOASTS: usize = 5;
+	const DURATION: Duration = Duration::from_secs(5);
+
+	/// Toasts shown at `anchor` rather than the top of the screen, `id` must be unique.
+	pub fn new(id: &'static str, anchor: Align2) -> Self {
+		Self {
+			toasts: VecDeque::new(),
+			id,
+			anchor,
+		}
+	}
+
+	pub fn push(&mut self, text: Box<str>, severity: Severity) {
+		self.push_at(text, severity, Instant::now());
+	}
+
+	fn push_at(&mut self, text: Box<str>, severity: Severity, now: Instant) {
+		if self.toasts.len() == Self::MAX_TOASTS {
+			self.toasts.pop_front();
+		}
+
+		self.toasts.push_back(Toast {
+			text,
+			severity,
+			expires_at: now + Self::DURATION,
+		});
+	}
+
+	/// Drops toasts that have been shown for [`Self::DURATION`] as of `now`.
+	fn expire(&mut self, now: Instant) {
+		self.toasts.retain(|toast| toast.expires_at > now);
+	}
+
+	pub fn draw(&mut self, context: &Context) {
+		self.expire(Instant::now());
+
+		if self.toasts.is_empty() {
+			return;
+		}
+
+		let margin = match self.anchor.y() {
+			Align::Max => -8.0,
+			_ => 8.0,
+		};
+
+		Area::new(Id::new(self.id))
+			.anchor(self.anchor, [0.0, margin])
+			.interactable(false)
+			.show(context, |area| {
+				for toast in &self.toasts {
+					Frame::popup(area.style()).show(area, |frame| {
+						frame.label(RichText::new(&*toast.text).color(color(toast.severity)));
+					});
+				}
+			});
+	}
+}
+
+impl Default for Toasts {
+	fn default() -> Self {
+		Self::new("toasts", Align2::CENTER_TOP)
+	}
+}
+
+fn color(severity: Severity) -> Color32 {
+	match severity {
+		Severity::Info => UiColor::ToastInfo,
+		Severity::Warning => UiColor::ToastWarning,
+		Severity::Error => UiColor::ToastError,
+	}
+	.color()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn texts(toasts: &Toasts) -> Vec<&str> {
+		toasts.toasts.iter().map(|toast| &*toast.text).collect()
+	}
+
+	#[test]
+	fn oldest_toasts_are_dropped_past_the_limit() {
+		let mut toasts = Toasts::default();
+		let now = Instant::now();
+
+		for i in 0..Toasts::MAX_TOASTS + 2 {
+			toasts.push_at(i.to_string().into(), Severity::Info, now);
+		}
+
+		assert_eq!(texts(&toasts), ["2", "3", "4", "5", "6"]);
+	}
+
+	#[test]
+	fn toasts_expire_after_duration() {
+		let mut toasts = Toasts::default();
+		let now = Instant::now();
+
+		toasts.push_at("old".into(), Severity::Warning, now);
+		toasts.push_at("new".into(), Severity::Error, now + Duration::from_secs(1));
+
+		toasts.expire(now + Toasts::DURATION - Duration::from_millis(1));
+		assert_eq!(texts(&toasts), ["old", "new"]);
+
+		toasts.expire(now + Toasts::DURATION);
+		assert_eq!(texts(&toasts), ["new"]);
+
+		toasts.expire(now + Toasts::DURATION + Duration::from_secs(1));
+		assert!(texts(&toasts).is_empty());
+	}
+}
@@ -1,33 +1,69 @@
 use crate::{
+	ambient::AmbientCycle,
+	chat::ChatLog,
+	chunk_cache::ChunkCache,
+	chunk_vertex::{self, ChunkVertex},
 	client::{AnyState, State},
+	connection_status::ConnectionStatus,
+	disconnected::Disconnected,
+	dump::Dump,
+	gpu_memory::{GpuCategory, Tracked},
+	hotbar::Hotbar,
+	inspect::Inspection,
+	inventory_window::{self, InventoryWindow},
+	loading::Loading,
+	localization,
+	login::{Login, Session},
+	map::Map,
 	player::{Local, Player},
+	rebuild_queue::RebuildQueue,
+	roll_assist::RollAssist,
+	sessions::Sessions,
+	surface::{terrain_cell, terrain_cell_location, Surface},
+	telemetry::Telemetry,
+	toasts::Toasts,
+	tr,
+	world_border::WorldBorder,
 };
+use anyhow::anyhow;
 use bytemuck::{cast_slice, Pod, Zeroable};
 use dashmap::DashMap;
-use egui::{Align::Min, Align2, Layout, Window};
-use log::debug;
-use nalgebra::{point, vector, Isometry3, Vector2, Vector3};
+use egui::{vec2, Align2, Area, Frame, Slider, TextEdit, Window};
+use log::{debug, info, warn};
+use nalgebra::{point, vector, Isometry3, Point3, Vector3};
 use rapier3d::{
 	dynamics::{RigidBodyBuilder, RigidBodyHandle},
-	geometry::{ColliderBuilder, ColliderHandle},
+	geometry::{ColliderHandle, Cuboid, Ray},
 };
 use rustc_hash::FxBuildHasher;
 use solarscape_shared::{
 	connection::{ClientEnd, Connection},
+	consts::{
+		cell_index, dependency_index, sample_index, CELLS_PER_CHUNK, CHUNK_SAMPLE_SIZE, CHUNK_SIZE,
+		REACH, SAMPLES_PER_CHUNK,
+	},
 	data::{
-		world::{ChunkCoordinates, Material, LEVELS},
+		world::{ChunkCoordinates, Level, Location, Material, WorldPosition, LEVELS},
 		Id,
 	},
 	message::{
-		clientbound::{Clientbound, InventorySlot, RemoveChunk, Sync, SyncChunk, SyncInventory},
-		serverbound::Serverbound,
+		clientbound::{
+			ActionDenied, ChatHistory, ChatRetract, ChunkUnchanged, Clientbound, Disconnect,
+			DisconnectReason, Pong, RemoveChunk, RemoveStructure, SetBlockState, Severity, Sync,
+			SyncChunk, SyncContainer, SyncInventory, Teleport, Transfer, UiEvent,
+		},
+		serverbound::{
+			BrushMode, DeleteStructure, HaveChunks, InteractBlock, ModifyTerrainBrush,
+			RequestChunk, SendChatMessage, Serverbound, ViewHint,
+		},
 	},
-	physics::{AutoCleanup, Physics},
+	physics::{AutoCleanup, ColliderOwner, CollisionGroup, Physics, QueryMask, RayHit},
 	structure::Structure,
 	triangulation_table::{EdgeData, CELL_EDGE_MAP, CORNERS, EDGE_CORNER_MAP},
+	trimesh,
 };
 use std::{
-	collections::{HashMap, HashSet},
+	collections::{HashMap, HashSet, VecDeque},
 	fmt::Write,
 	mem::drop as nom,
 	ops::Deref,
@@ -40,7 +76,7 @@ use wgpu::{
 	Buffer, BufferUsages, Device,
 };
 use winit::{
-	event::{DeviceEvent, ElementState, KeyEvent, WindowEvent},
+	event::{DeviceEvent, ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
 	keyboard::{KeyCode, PhysicalKey},
 };
 
@@ -49,15 +85,87 @@ pub struct Sector {
 
 	pub player: Player<Local>,
 
-	inventory: Vec<InventorySlot>,
-	pub inventory_gui_open: bool,
+	/// [`None`] until the sector has sent it, shortly after joining.
+	inventory: Option<SyncInventory>,
+	pub inventory_window: InventoryWindow,
+
+	/// The container the player last used, as the sector sent it, present while it's shown.
+	container: Option<SyncContainer>,
+
+	/// Structure the player has asked to demolish, waiting for them to confirm it.
+	pub pending_deletion: Option<Id>,
+
+	/// The structure block the player is aiming at and it's position in the structure, highlighted so they know what
+	/// they're about to act on. Found by [`Sector::update_target`] each tick.
+	pub targeted_block: Option<(Id, Vector3<i16>)>,
+	/// Location of the terrain cell the player is aiming at, see [`terrain_cell_location`].
+	pub targeted_cell: Option<Isometry3<f32>>,
+
+	/// Present while the player is using the terrain brush instead of placing blocks.
+	pub terrain_brush: Option<TerrainBrush>,
+
+	pub hotbar: Hotbar,
+
+	pub map: Map,
+
+	/// Whether [`Inspection::KEY`] is held.
+	inspecting: bool,
+
+	/// The message being typed, present while the chat box is open.
+	chat: Option<String>,
+	chat_log: ChatLog,
+
+	toasts: Toasts,
+	loading: Loading,
+
+	/// The last [`ViewHint`] sent, another is only sent once the view [`ViewHint::differs`] from it.
+	pub view_hint: Option<ViewHint>,
+
+	/// Notices from the server waiting to be dismissed, only the first is shown.
+	notices: VecDeque<(Box<str>, Box<str>)>,
+
+	pub ambient: AmbientCycle,
+
+	/// [`None`] if the sector has no border.
+	pub world_border: Option<WorldBorder>,
 
 	pub structures: Vec<Structure>,
 	pub voxjects: HashMap<Id, Voxject>,
+	/// See [`Sync::world_radius_chunks`], chunks from the sector outside of this are ignored.
+	pub world_radius_chunks: i64,
+	/// See [`Sync::max_level`], chunks from the sector above this are ignored.
+	pub max_level: Level,
+	/// Chunks ignored for being outside of the world, which a working sector never sends.
+	insane_chunks: u64,
+
+	/// Chunks from earlier visits to this sector, [`None`] for singleplayer or if there's nowhere to keep them.
+	chunk_cache: Option<ChunkCache>,
+	/// Chunks that couldn't be meshed yet because neither they or their upleveled chunks have all the data needed.
+	pub unmeshable_chunks: HashSet<ChunkCoordinates, FxBuildHasher>,
+	/// Chunks to rebuild at the end of the frame, see [`Sector::rebuild_chunks`].
+	rebuilds: RebuildQueue,
+
+	pub telemetry: Telemetry,
 
 	last_tick_start: Instant,
 
 	pub physics: Physics,
+	/// Ticks per second the server runs at, updates from the server arrive at most this often.
+	pub server_tick_rate: u32,
+
+	/// [`None`] for singleplayer, which can't be reconnected to.
+	session: Option<Session>,
+	/// [`None`] for singleplayer and direct connections, which don't go through the gateway.
+	sessions: Option<Sessions>,
+
+	/// Set once the server connection has closed, the next tick will try to reconnect.
+	connection_lost: bool,
+	/// Set if the connection was closed to send the player to another sector, which the next tick connects to instead.
+	transfer: Option<Transfer>,
+	/// Set if the sector disconnected the player for a reason that reconnecting won't fix, which the next tick shows on
+	/// the login form instead.
+	kicked: Option<String>,
+	connection_status: ConnectionStatus,
 }
 
 pub struct SharedSector {
@@ -66,25 +174,53 @@ pub struct SharedSector {
 }
 
 impl Sector {
-	pub async fn new(mut connection: Connection<ClientEnd>) -> Self {
+	pub async fn new(
+		mut connection: Connection<ClientEnd>,
+		session: Option<Session>,
+	) -> Result<Self, anyhow::Error> {
 		let Sync {
+			name,
+			tick_rate,
+			world_radius,
+			world_radius_chunks,
+			max_level,
 			voxjects,
-			structures,
-			inventory,
+			ambient_keyframes,
+			ambient,
 			..
 		} = loop {
-			let message = connection.recv().await.expect("server should respond");
+			let message = connection
+				.recv()
+				.await
+				.ok_or_else(|| anyhow!("connection closed before the sector synced"))?;
 
 			match message {
 				Clientbound::Sync(sync_sector) => break sync_sector,
+				Clientbound::Disconnect(Disconnect { reason }) => {
+					return Err(anyhow!(disconnect_reason(reason)))
+				}
 				_ => continue,
 			};
 		};
 
+		// Singleplayer sectors are generated locally, so there's nothing to gain from caching them
+		let chunk_cache = session.as_ref().and_then(|_| ChunkCache::open(&name));
+
+		// Sent before the first location, so that the sector knows before it starts sending chunks
+		if let Some(chunk_cache) = chunk_cache.clone() {
+			let entries = tokio::task::spawn_blocking(move || chunk_cache.index()).await?;
+			info!("{} chunks are cached for this sector", entries.len());
+
+			for batch in entries.chunks(HaveChunks::BATCH_SIZE) {
+				connection.send(HaveChunks {
+					entries: batch.to_vec(),
+				});
+			}
+		}
+
 		let player = Player::new(connection);
-		let mut physics = Physics::new();
 
-		Self {
+		Ok(Self {
 			shared: Arc::new(SharedSector {
 				chunks: DashMap::with_hasher(FxBuildHasher),
 				dependent_chunks: DashMap::with_hasher(FxBuildHasher),
@@ -92,8 +228,36 @@ impl Sector {
 
 			player,
 
-			inventory,
-			inventory_gui_open: false,
+			inventory: None,
+			inventory_window: InventoryWindow::default(),
+
+			container: None,
+
+			pending_deletion: None,
+
+			targeted_block: None,
+			targeted_cell: None,
+
+			terrain_brush: None,
+
+			hotbar: Hotbar::load(),
+			map: Map::new(),
+			inspecting: false,
+
+			chat: None,
+			chat_log: ChatLog::default(),
+
+			toasts: Toasts::default(),
+			loading: Loading::default(),
+			view_hint: None,
+			notices: VecDeque::new(),
+
+			ambient: AmbientCycle::new(ambient_keyframes, ambient),
+
+			world_border: world_radius.map(WorldBorder::new),
+			world_radius_chunks,
+			max_level,
+			insane_chunks: 0,
 
 			voxjects: voxjects
 				.into_iter()
@@ -108,18 +272,29 @@ impl Sector {
 					)
 				})
 				.collect(),
-			structures: structures
-				.into_iter()
-				.map(|sync_structure| Structure::new_from_sync(&mut physics, sync_structure))
-				.collect(),
+			structures: vec![],
+
+			chunk_cache,
+			unmeshable_chunks: HashSet::with_hasher(FxBuildHasher),
+			rebuilds: RebuildQueue::default(),
+
+			telemetry: Telemetry::new(),
 
 			last_tick_start: Instant::now(),
 
-			physics,
-		}
+			physics: Physics::new(),
+			server_tick_rate: tick_rate,
+
+			sessions: session.clone().map(Sessions::new),
+			session,
+			connection_lost: false,
+			transfer: None,
+			kicked: None,
+			connection_status: ConnectionStatus::new(Instant::now()),
+		})
 	}
 
-	pub fn process_messages(&mut self, device: &Device) {
+	pub fn process_messages(&mut self) {
 		let start_time = Instant::now();
 
 		loop {
@@ -129,82 +304,552 @@ impl Sector {
 
 			let message = match self.player.connection.try_recv() {
 				Ok(message) => message,
-				Err(TryRecvError::Disconnected) => panic!("disconnected"),
+				Err(TryRecvError::Disconnected) => {
+					self.connection_lost = true;
+					return;
+				}
 				Err(TryRecvError::Empty) => return,
 			};
 
+			if let Clientbound::SyncChunk(SyncChunk { coordinates, .. })
+			| Clientbound::ChunkUnchanged(ChunkUnchanged { coordinates, .. })
+			| Clientbound::RemoveChunk(RemoveChunk(coordinates)) = &message
+			{
+				if !coordinates.is_sane(self.world_radius_chunks, self.max_level)
+					|| !self.voxjects.contains_key(&coordinates.voxject)
+				{
+					self.insane_chunks += 1;
+					warn!(
+						"Ignored chunk {coordinates} from the sector, it's outside of the world ({} so far)",
+						self.insane_chunks
+					);
+					continue;
+				}
+			}
+
 			match message {
 				Clientbound::Sync(_) => continue, // what...?
-				Clientbound::SyncInventory(SyncInventory(inventory)) => self.inventory = inventory,
-				Clientbound::SyncChunk(SyncChunk {
-					coordinates,
-					materials,
-					densities,
-				}) => self.add_chunk(
-					device,
-					Chunk {
+				Clientbound::JoinComplete(_) => self.loading.complete(),
+				Clientbound::SyncInventory(inventory) => self.inventory = Some(inventory),
+				Clientbound::SyncChunk(sync_chunk) => {
+					self.loading.chunk_received(&sync_chunk.coordinates);
+
+					if let Some(chunk_cache) = &self.chunk_cache {
+						chunk_cache.write(&sync_chunk);
+					}
+
+					let SyncChunk {
+						coordinates,
+						generation,
+						materials,
+						densities,
+					} = sync_chunk;
+
+					self.add_chunk(Chunk {
 						coordinates,
+						generation,
 						materials,
 						densities,
 						mesh: None,
-					},
-				),
+					});
+				}
+				Clientbound::ChunkUnchanged(ChunkUnchanged {
+					coordinates,
+					generation,
+					content_hash,
+				}) => {
+					let cached = self
+						.chunk_cache
+						.as_ref()
+						.and_then(|chunk_cache| chunk_cache.read(&coordinates, content_hash));
+
+					match cached {
+						Some((materials, densities)) => {
+							self.loading.chunk_received(&coordinates);
+
+							self.add_chunk(Chunk {
+								coordinates,
+								generation,
+								materials,
+								densities,
+								mesh: None,
+							})
+						}
+						None => {
+							debug!("Cached chunk {coordinates} is gone, asking for it again");
+							self.player.connection.send(RequestChunk(coordinates));
+						}
+					}
+				}
 				Clientbound::RemoveChunk(RemoveChunk(coordinates)) => {
-					self.remove_chunk(device, coordinates)
+					self.remove_chunk(coordinates)
 				}
 				Clientbound::SyncStructure(sync_structure) => {
 					debug!("Synced structure {}", sync_structure.id);
+					self.loading.structure_received();
+
+					// Structures are synced again whenever the server changes them, such as when they're damaged
+					self.structures
+						.retain(|structure| structure.id != sync_structure.id);
 					self.structures
 						.push(Structure::new_from_sync(&mut self.physics, sync_structure));
 				}
+				Clientbound::RemoveStructure(RemoveStructure(id)) => {
+					debug!("Removed structure {id}");
+					self.structures.retain(|structure| structure.id != id);
+
+					if self.pending_deletion == Some(id) {
+						self.pending_deletion = None;
+					}
+
+					if self
+						.container
+						.as_ref()
+						.is_some_and(|container| container.structure == id)
+					{
+						self.container = None;
+					}
+				}
+				Clientbound::SetBlockState(SetBlockState {
+					structure,
+					position,
+					state,
+				}) => {
+					if let Some(structure) = self.structures.iter_mut().find(|s| s.id == structure)
+					{
+						structure.set_block_state(&position, state);
+					}
+				}
+				Clientbound::SyncContainer(container) => self.container = Some(container),
+				Clientbound::UiEvent(ui_event) => match ui_event {
+					UiEvent::SystemMessage { text, severity } => self
+						.toasts
+						.push(localization::translate_server(&text).into(), severity),
+					UiEvent::OpenInventory => self.inventory_window.open = true,
+					UiEvent::CloseInventory => self.inventory_window.open = false,
+					UiEvent::Notice { title, body } => self.notices.push_back((title, body)),
+					UiEvent::ActionDenied(denied) => {
+						let text = match denied {
+							ActionDenied::PlayerStructureLimit { max } => {
+								tr!("denied.player_structure_limit", max = max)
+							}
+							ActionDenied::SectorStructureLimit { max } => {
+								tr!("denied.sector_structure_limit", max = max)
+							}
+							ActionDenied::PlacementRateLimited => {
+								tr!("denied.placement_rate_limited")
+							}
+						};
+
+						self.toasts.push(text.into(), Severity::Warning)
+					}
+				},
+				Clientbound::Ambient(ambient) => self.ambient.update(ambient),
+				Clientbound::ChatMessage(message) => {
+					let text = format!("<{}> {}", message.player, message.text);
+
+					if self.chat_log.push(message) {
+						self.toasts.push(text.into(), Severity::Info);
+					}
+				}
+				Clientbound::ChatHistory(ChatHistory(history)) => {
+					self.chat_log.push_history(history)
+				}
+				Clientbound::ChatRetract(ChatRetract { sequence }) => {
+					self.chat_log.retract(sequence)
+				}
+				Clientbound::Teleport(Teleport { position }) => {
+					self.player.location.position = position
+				}
+				Clientbound::Disconnect(Disconnect { reason }) => {
+					let text = disconnect_reason(reason);
+					warn!("Disconnected by the sector: {text}");
+
+					// Reconnecting would only bring back an away player who isn't there to play
+					if matches!(reason, DisconnectReason::Idle) {
+						self.kicked = Some(text);
+					}
+
+					self.connection_lost = true;
+					return;
+				}
+				Clientbound::Pong(Pong { sequence }) => {
+					self.connection_status.pong(sequence, Instant::now())
+				}
+				Clientbound::Transfer(transfer) => {
+					info!("Being transferred to {}", transfer.sector);
+					self.transfer = Some(transfer);
+					self.connection_lost = true;
+					return;
+				}
 			}
 		}
 	}
 
-	pub fn add_chunk(&mut self, device: &Device, chunk: Chunk) {
-		let coordinates = chunk.coordinates;
-		self.chunks.insert(coordinates, chunk);
+	pub fn inventory(&self) -> Option<&SyncInventory> {
+		self.inventory.as_ref()
+	}
 
-		// Rebuild any chunks that need this chunk
-		'rebuild_dependents: {
-			let dependent_chunks = match self.dependent_chunks.get(&coordinates) {
-				Some(dependent_chunks) => dependent_chunks.clone(),
-				None => break 'rebuild_dependents,
+	/// Whether any window that needs the cursor is open.
+	pub fn gui_open(&self) -> bool {
+		self.inventory_window.open
+			|| self.container.is_some()
+			|| self.map.open
+			|| self.pending_deletion.is_some()
+			|| !self.notices.is_empty()
+			|| self.chat.is_some()
+	}
+
+	/// Handles input for the terrain brush, returns `true` if the event was used.
+	fn handle_terrain_brush_event(&mut self, event: &WindowEvent) -> bool {
+		if let WindowEvent::KeyboardInput {
+			event:
+				KeyEvent {
+					physical_key: PhysicalKey::Code(KeyCode::KeyB),
+					state: ElementState::Released,
+					repeat: false,
+					..
+				},
+			..
+		} = event
+		{
+			self.terrain_brush = match self.terrain_brush {
+				Some(_) => None,
+				None => Some(TerrainBrush::default()),
 			};
 
-			for dependent_chunk in dependent_chunks {
-				self.try_build_chunk(device, dependent_chunk);
+			return true;
+		}
+
+		let Some(brush) = &mut self.terrain_brush else {
+			return false;
+		};
+
+		match event {
+			WindowEvent::MouseInput {
+				state: ElementState::Released,
+				button,
+				..
+			} => {
+				let mode = match button {
+					MouseButton::Left => BrushMode::Add,
+					MouseButton::Right => BrushMode::Remove,
+					_ => return false,
+				};
+
+				let center = brush.center(&self.player.location);
+
+				// Voxjects don't move yet, but when they do we'll want whichever is closest
+				let Some(voxject) = self.voxjects.values().min_by(|a, b| {
+					f32::total_cmp(
+						&(a.location.translation.vector - center.coords).norm(),
+						&(b.location.translation.vector - center.coords).norm(),
+					)
+				}) else {
+					return true;
+				};
+
+				self.player.connection.send(ModifyTerrainBrush {
+					center: WorldPosition(center).relative_to(voxject.id, &voxject.location),
+					radius: brush.radius,
+					material: brush.material,
+					mode,
+				});
 			}
+			WindowEvent::MouseWheel { delta, .. } => {
+				brush.radius = (brush.radius + scroll_lines(delta) * 0.5).clamp(
+					ModifyTerrainBrush::MIN_RADIUS,
+					ModifyTerrainBrush::MAX_RADIUS,
+				);
+			}
+			WindowEvent::KeyboardInput {
+				event:
+					KeyEvent {
+						physical_key: PhysicalKey::Code(code),
+						state: ElementState::Released,
+						repeat: false,
+						..
+					},
+				..
+			} => match code {
+				KeyCode::Digit1 => brush.material = Material::Stone,
+				KeyCode::Digit2 => brush.material = Material::Ground,
+				KeyCode::Digit3 => brush.material = Material::Corium,
+				_ => return false,
+			},
+			_ => return false,
 		}
 
-		self.try_build_chunk(device, coordinates);
+		true
+	}
+
+	/// Handles selecting hotbar slots, returns `true` if the event was used. The terrain brush has the scroll wheel and
+	/// number keys to itself while it's in use, so this should only be called if it didn't use the event.
+	fn handle_hotbar_event(&mut self, event: &WindowEvent) -> bool {
+		if self.terrain_brush.is_some() {
+			return false;
+		}
+
+		match event {
+			WindowEvent::MouseWheel { delta, .. } => self.hotbar.scroll(scroll_lines(delta)),
+			WindowEvent::KeyboardInput {
+				event:
+					KeyEvent {
+						physical_key: PhysicalKey::Code(code),
+						state: ElementState::Released,
+						repeat: false,
+						..
+					},
+				..
+			} => {
+				let slot = match code {
+					KeyCode::Digit1 => 0,
+					KeyCode::Digit2 => 1,
+					KeyCode::Digit3 => 2,
+					KeyCode::Digit4 => 3,
+					KeyCode::Digit5 => 4,
+					KeyCode::Digit6 => 5,
+					KeyCode::Digit7 => 6,
+					KeyCode::Digit8 => 7,
+					KeyCode::Digit9 => 8,
+					_ => return false,
+				};
+
+				self.hotbar.select(slot);
+			}
+			_ => return false,
+		}
+
+		true
+	}
+
+	/// Uses the block the player is aiming at on right click, if it does anything, returns `true` if the event was
+	/// used. The terrain brush has right click to itself while it's in use, so this should only be called if it didn't
+	/// use the event.
+	fn handle_interact_event(&self, event: &WindowEvent) -> bool {
+		let WindowEvent::MouseInput {
+			state: ElementState::Released,
+			button: MouseButton::Right,
+			..
+		} = event
+		else {
+			return false;
+		};
+
+		let Some((structure, position)) = self.targeted_block else {
+			return false;
+		};
+
+		let interactable = self
+			.structures
+			.iter()
+			.find(|s| s.id == structure)
+			.and_then(|structure| structure.get_block(&position))
+			.is_some_and(|block| block.typ.is_interactable());
+
+		if interactable {
+			self.player.connection.send(InteractBlock {
+				structure,
+				position,
+			});
+		}
+
+		interactable
+	}
+
+	/// Draws the hotbar, slots can be dragged onto each other to rearrange them while the cursor is free.
+	fn draw_hotbar(&mut self, context: &egui::Context) {
+		/// Which slot is being dragged.
+		struct DraggedSlot(usize);
+
+		let mut swap = None;
+
+		Area::new(egui::Id::new("hotbar"))
+			.anchor(Align2::CENTER_BOTTOM, [0.0, -4.0])
+			.show(context, |area| {
+				area.horizontal(|row| {
+					for (slot, block) in self.hotbar.slots().iter().enumerate() {
+						let mut frame = Frame::group(row.style()).fill(row.visuals().panel_fill);
+
+						if slot == self.hotbar.selected_slot() {
+							frame = frame.stroke(row.visuals().selection.stroke);
+						}
+
+						let (_, dropped) = row.dnd_drop_zone::<DraggedSlot, _>(frame, |zone| {
+							zone.set_min_size(vec2(64.0, 36.0));
+
+							let id = egui::Id::new(("hotbar_slot", slot));
+							zone.dnd_drag_source(id, DraggedSlot(slot), |source| {
+								source.vertical_centered(|source| {
+									source.label((slot + 1).to_string());
+									source.label(block.map_or("", |block| block.display_name()));
+								});
+							});
+						});
+
+						if let Some(dropped) = dropped {
+							swap = Some((dropped.0, slot));
+						}
+					}
+				});
+			});
+
+		if let Some((a, b)) = swap.filter(|(a, b)| a != b) {
+			self.hotbar.swap(a, b);
+			self.hotbar.save();
+		}
 	}
 
-	pub fn remove_chunk(&mut self, device: &Device, coordinates: ChunkCoordinates) {
-		self.chunks.remove(&coordinates);
+	/// Returns the [`Structure`] the player is looking at, if it is within reach.
+	pub fn targeted_structure(&self) -> Option<Id> {
+		let origin = self.player.location.position;
+		let direction = self
+			.player
+			.location
+			.rotation
+			.inverse_transform_vector(&-Vector3::z());
+
+		let mut closest = None;
+
+		for structure in &self.structures {
+			let location = structure.get_location(&self.physics);
+
+			for (position, _) in structure.iter_blocks() {
+				let center = location * Point3::from(position.cast());
+				let distance = (center - origin).dot(&direction);
+
+				if !(0.0..=REACH).contains(&distance) {
+					continue;
+				}
+
+				// Treat blocks as spheres, it's close enough for picking which structure to target
+				if (center - (origin + direction * distance)).norm() > 0.5 {
+					continue;
+				}
+
+				if closest.is_none_or(|(closest, _)| distance < closest) {
+					closest = Some((distance, structure.id));
+				}
+			}
+		}
+
+		closest.map(|(_, id)| id)
+	}
 
-		let dependent_chunks = match self.dependent_chunks.get(&coordinates) {
-			Some(dependent_chunks) => dependent_chunks.clone(),
-			None => return,
+	/// Updates [`Sector::targeted_block`] and [`Sector::targeted_cell`] to whatever is closest along the player's view,
+	/// if it's within reach.
+	fn update_target(&mut self) {
+		self.targeted_block = None;
+		self.targeted_cell = None;
+
+		let origin = self.player.location.position;
+		let direction = self
+			.player
+			.location
+			.rotation
+			.inverse_transform_vector(&-Vector3::z());
+
+		let Some(RayHit { hit, distance, .. }) = self.physics.cast_ray(
+			&Ray::new(origin, direction),
+			REACH,
+			QueryMask::new(&[CollisionGroup::Terrain, CollisionGroup::Structure]),
+		) else {
+			return;
 		};
 
-		for dependent_chunk in dependent_chunks {
-			self.try_build_chunk(device, dependent_chunk);
+		match hit.owner {
+			Some(ColliderOwner::StructureBlock(id, position)) => {
+				self.targeted_block = Some((id, position));
+			}
+			Some(ColliderOwner::Chunk(coordinates)) => {
+				let Some(collider) = self.physics.get_collider(hit.collider) else {
+					return;
+				};
+
+				let point = origin + direction * (distance + Inspection::NUDGE);
+				let cell = terrain_cell(collider, coordinates, &point);
+				self.targeted_cell = Some(terrain_cell_location(collider, coordinates, &cell));
+			}
+			Some(ColliderOwner::Player(_)) | None => {}
+		}
+	}
+
+	/// Predicts whether placing a block at `location` would succeed, so the placement indicator can show it. The server
+	/// still has the final say, this is only what the client can check cheaply.
+	pub fn is_placement_valid(&self, location: &Location) -> bool {
+		// Built the same way Structure::new builds the structure's rigid body, so we test where it would actually end up
+		let (x, y, z) = location.rotation.euler_angles();
+		let position = Isometry3::new(location.position.coords, vector![x, y, z]);
+
+		// Players aren't included, so blocks can be placed where someone is standing
+		!self.physics.intersects_shape(
+			&position,
+			&Cuboid::new(Vector3::repeat(0.5)),
+			QueryMask::new(&[CollisionGroup::Terrain, CollisionGroup::Structure]),
+		)
+	}
+
+	/// Which way is up for the player, away from the closest voxject within [`RollAssist::VOXJECT_RANGE`], or world Y
+	/// if there isn't one.
+	pub fn up(&self) -> Vector3<f32> {
+		let position = self.player.location.position.coords;
+
+		self.voxjects
+			.values()
+			.map(|voxject| position - voxject.location.translation.vector)
+			.filter(|offset| offset.norm() <= RollAssist::VOXJECT_RANGE)
+			.min_by(|a, b| f32::total_cmp(&a.norm(), &b.norm()))
+			.and_then(|offset| offset.try_normalize(f32::EPSILON))
+			.unwrap_or_else(Vector3::y)
+	}
+
+	/// Adds a synced chunk, it and any chunks that depend on it are rebuilt by [`Sector::rebuild_chunks`].
+	pub fn add_chunk(&mut self, chunk: Chunk) {
+		let coordinates = chunk.coordinates;
+
+		if let Some(existing) = self.chunks.get(&coordinates) {
+			if existing.generation > chunk.generation {
+				debug!(
+					"Ignored stale sync of chunk {coordinates:?}, generation {} is older than {}",
+					chunk.generation, existing.generation
+				);
+				return;
+			}
+		}
+
+		self.chunks.insert(coordinates, chunk);
+		self.map.invalidate();
+
+		self.rebuilds
+			.request_with_dependents(coordinates, &self.shared.dependent_chunks);
+	}
+
+	pub fn remove_chunk(&mut self, coordinates: ChunkCoordinates) {
+		if let Some((_, mut chunk)) = self.chunks.remove(&coordinates) {
+			chunk.clear_mesh(&mut self.physics);
+			self.map.invalidate();
+		}
+
+		self.unmeshable_chunks.remove(&coordinates);
+
+		if let Some(dependent_chunks) = self.shared.dependent_chunks.get(&coordinates) {
+			self.rebuilds.request(dependent_chunks.iter().copied());
+		}
+	}
+
+	/// Rebuilds every chunk queued since the last call, each only once however many times it was queued.
+	pub fn rebuild_chunks(&mut self, device: &Device) {
+		for coordinates in self.rebuilds.take() {
+			self.try_build_chunk(device, coordinates);
 		}
 	}
 
 	// This code is admittedly absolutely fucking terrible, for the time being I don't care, it just needs to work
 	pub fn try_build_chunk(&mut self, device: &Device, grid_coordinates: ChunkCoordinates) {
-		let dependency_grid_coordinates = [
-			grid_coordinates + Vector3::new(0, 0, 0),
-			grid_coordinates + Vector3::new(0, 0, 1),
-			grid_coordinates + Vector3::new(0, 1, 0),
-			grid_coordinates + Vector3::new(0, 1, 1),
-			grid_coordinates + Vector3::new(1, 0, 0),
-			grid_coordinates + Vector3::new(1, 0, 1),
-			grid_coordinates + Vector3::new(1, 1, 0),
-			grid_coordinates + Vector3::new(1, 1, 1),
-		];
+		// Chunks at the very edge of the world can't be built, as there's nothing on their positive side
+		let Some(dependency_grid_coordinates) = grid_coordinates.mesh_dependencies() else {
+			return;
+		};
 
 		let dependency_chunks =
 			dependency_grid_coordinates.map(|coordinates| self.chunks.get(&coordinates));
@@ -212,7 +857,8 @@ impl Sector {
 		let mut upleveled_dependency_grid_coordinates = None;
 		let mut upleveled_dependency_chunks = Default::default();
 
-		let should_uplevel = *grid_coordinates.level != LEVELS - 2;
+		// Nothing is sent above the max level, so chunks on it are built from what they have like the top level's are
+		let should_uplevel = *grid_coordinates.level < (*self.max_level).min(LEVELS - 2);
 		if should_uplevel {
 			upleveled_dependency_grid_coordinates =
 				Some(dependency_grid_coordinates.map(|coordinates| coordinates.upleveled()));
@@ -221,24 +867,24 @@ impl Sector {
 				.map(|coordinates| self.chunks.get(&coordinates));
 		}
 
-		let mut densities = [0.0; 17 * 17 * 17];
-		let mut materials = [Material::Nothing; 17 * 17 * 17];
+		let mut densities = [0.0; SAMPLES_PER_CHUNK];
+		let mut materials = [Material::Nothing; SAMPLES_PER_CHUNK];
 		let mut need_upleveled_chunks = false;
 
-		'x: for x in 0..17 {
-			for y in 0..17 {
-				for z in 0..17 {
-					// messy but probably fast?
-					let chunk_index = ((x & 0x10) >> 2) | ((y & 0x10) >> 3) | ((z & 0x10) >> 4);
-					let cell_index = (x * 289) + (y * 17) + z;
+		'x: for x in 0..CHUNK_SAMPLE_SIZE {
+			for y in 0..CHUNK_SAMPLE_SIZE {
+				for z in 0..CHUNK_SAMPLE_SIZE {
+					let chunk_index = dependency_index(x, y, z);
+					let index = sample_index(x, y, z);
 
 					// The actual chunk we need is loaded, yay! This is the easy path.
 					if let Some(chunk) = &dependency_chunks[chunk_index] {
 						// Data expands a little bit further than chunk data, so we can't just copy the chunk data array
 						// instead we have to map it to the
-						let chunk_cell_index = (x & 0x0F) << 8 | (y & 0x0F) << 4 | z & 0x0F;
-						densities[cell_index] = chunk.densities[chunk_cell_index];
-						materials[cell_index] = chunk.materials[chunk_cell_index];
+						let chunk_cell_index =
+							cell_index(x % CHUNK_SIZE, y % CHUNK_SIZE, z % CHUNK_SIZE);
+						densities[index] = chunk.densities[chunk_cell_index];
+						materials[index] = chunk.materials[chunk_cell_index];
 						continue;
 					}
 
@@ -248,19 +894,21 @@ impl Sector {
 						// Upleveling coordinates is essentially `coordinates / 2`, however because these are relative
 						// coordinates and not global ones, we need to offset them based on the center chunk's position
 						// in the upleveled chunk.
-						let u_x = ((grid_coordinates.coordinates.x as usize & 1) * 8) + (x >> 1);
-						let u_y = ((grid_coordinates.coordinates.y as usize & 1) * 8) + (y >> 1);
-						let u_z = ((grid_coordinates.coordinates.z as usize & 1) * 8) + (z >> 1);
+						let [u_x, u_y, u_z] = [
+							(grid_coordinates.coordinates.x, x),
+							(grid_coordinates.coordinates.y, y),
+							(grid_coordinates.coordinates.z, z),
+						]
+						.map(|(grid, sample)| (grid as usize & 1) * (CHUNK_SIZE / 2) + sample / 2);
 
 						// Now we do the same thing we would do normally, except operating on upleveled chunks
-						let upleveled_chunk_index =
-							((u_x & 0x10) >> 2) | ((u_y & 0x10) >> 3) | ((u_z & 0x10) >> 4);
+						let upleveled_chunk_index = dependency_index(u_x, u_y, u_z);
 
 						if let Some(chunk) = &upleveled_dependency_chunks[upleveled_chunk_index] {
 							let u_chunk_cell_index =
-								(u_x & 0x0F) << 8 | (u_y & 0x0F) << 4 | u_z & 0x0F;
-							densities[cell_index] = chunk.densities[u_chunk_cell_index];
-							materials[cell_index] = chunk.materials[u_chunk_cell_index];
+								cell_index(u_x % CHUNK_SIZE, u_y % CHUNK_SIZE, u_z % CHUNK_SIZE);
+							densities[index] = chunk.densities[u_chunk_cell_index];
+							materials[index] = chunk.materials[u_chunk_cell_index];
 							continue;
 						}
 
@@ -274,8 +922,6 @@ impl Sector {
 			}
 		}
 
-		let upleveled_grid_coordinates = grid_coordinates.upleveled();
-
 		// Make sure we are rebuilt if any chunks we depend on are changed
 		for level_coordinates in dependency_grid_coordinates {
 			match self.dependent_chunks.get_mut(&level_coordinates) {
@@ -290,6 +936,8 @@ impl Sector {
 		}
 
 		if should_uplevel {
+			let upleveled_grid_coordinates = grid_coordinates.upleveled();
+
 			// Now either add or remove our dependency on upleveled chunks
 			for level_coordinates in upleveled_dependency_grid_coordinates.unwrap() {
 				let should_remove = match self.dependent_chunks.get_mut(&level_coordinates) {
@@ -320,17 +968,37 @@ impl Sector {
 		nom(dependency_chunks);
 		nom(upleveled_dependency_chunks);
 
-		let shared_clone = self.shared.clone();
-		if let Some(mut chunk) = shared_clone.chunks.get_mut(&grid_coordinates) {
+		// The chunk is taken out of the map while it's rebuilt, as rebuilding needs the rest of the sector, and holding
+		// a DashMap guard while doing anything with the chunk map would risk deadlocking
+		let Some((_, mut chunk)) = self.chunks.remove(&grid_coordinates) else {
+			return;
+		};
+
+		match need_upleveled_chunks {
 			// Not enough data to build chunk
-			if need_upleveled_chunks {
-				chunk.value_mut().mesh = None;
-				return;
+			true => {
+				chunk.clear_mesh(&mut self.physics);
+				self.unmeshable_chunks.insert(grid_coordinates);
+			}
+			false => {
+				chunk.rebuild_mesh(self, device, densities, materials);
+				self.unmeshable_chunks.remove(&grid_coordinates);
 			}
+		}
 
-			// Now we can build the chunk mesh
-			chunk.rebuild_mesh(self, device, densities, materials);
-		};
+		self.chunks.insert(grid_coordinates, chunk);
+	}
+}
+
+/// Why the sector closed the connection, in the player's language.
+fn disconnect_reason(reason: DisconnectReason) -> String {
+	match reason {
+		DisconnectReason::SectorFull { players, capacity } => tr!(
+			"disconnected.sector_full",
+			players = players,
+			capacity = capacity
+		),
+		DisconnectReason::Idle => tr!("disconnected.idle"),
 	}
 }
 
@@ -340,9 +1008,45 @@ impl State for Sector {
 		let delta = (tick_start - self.last_tick_start).as_secs_f32();
 		self.last_tick_start = tick_start;
 
-		self.player.tick(delta);
+		if self.connection_lost {
+			if let Some(text) = self.kicked.take() {
+				return Some(AnyState::Login(Login::with_error(text)));
+			}
+
+			return Some(match (&self.session, self.transfer.take()) {
+				(Some(session), Some(transfer)) => {
+					AnyState::Disconnected(Disconnected::transfer(session.clone(), transfer))
+				}
+				(Some(session), None) => {
+					warn!("Lost connection to the sector");
+					AnyState::Disconnected(Disconnected::new(session.clone()))
+				}
+				// Singleplayer sectors have no database, so they never transfer anyone
+				(None, _) => {
+					warn!("Lost connection to the sector");
+					AnyState::Login(Login::with_error(tr!("singleplayer.stopped")))
+				}
+			});
+		}
+
+		self.connection_status
+			.tick(&self.player.connection, tick_start);
+
+		self.player.surface = Surface::probe(self);
+		let up = self.up();
+		self.player.tick(delta, up);
 
 		self.physics.tick(delta);
+		self.update_target();
+
+		self.ambient.tick(delta);
+
+		if let Some(world_border) = &mut self.world_border {
+			if world_border.update(&self.player.location.position) {
+				self.toasts
+					.push(tr!("world_border.warning").into(), Severity::Warning);
+			}
+		}
 
 		None
 	}
@@ -360,6 +1064,58 @@ impl State for Sector {
 		)
 		.expect("should be able to write to string");
 
+		match self.player.surface {
+			Some(Surface::Terrain(material)) => writeln!(debug_text, "Surface: {material:?}"),
+			Some(Surface::Block(block)) => writeln!(debug_text, "Surface: {block:?} block"),
+			None => writeln!(debug_text, "Surface: none (flying)"),
+		}
+		.expect("should be able to write to string");
+
+		if let Some(brush) = &self.terrain_brush {
+			writeln!(
+				debug_text,
+				"Terrain Brush: {:?}, radius {:.1} (B to exit)",
+				brush.material, brush.radius
+			)
+			.expect("should be able to write to string");
+		}
+
+		writeln!(debug_text, "Server Tick Rate: {}", self.server_tick_rate)
+			.expect("should be able to write to string");
+
+		if let Some(stats) = self.player.connection.stats() {
+			let latency = self.connection_status.latency(Instant::now());
+
+			writeln!(
+				debug_text,
+				"Latency: {}, Send Queue: {}",
+				latency.map_or(String::from("unknown"), |latency| format!(
+					"{} ms",
+					latency.as_millis()
+				)),
+				stats.queued()
+			)
+			.expect("should be able to write to string");
+		}
+
+		writeln!(
+			debug_text,
+			"Ambient: {:.3}{}",
+			self.ambient.phase(),
+			match self.ambient.paused() {
+				true => " (paused)",
+				false => "",
+			}
+		)
+		.expect("should be able to write to string");
+
+		writeln!(
+			debug_text,
+			"Chunk Rebuilds: {} of {} requested",
+			self.rebuilds.rebuilt, self.rebuilds.requested
+		)
+		.expect("should be able to write to string");
+
 		writeln!(debug_text, "Structures: {}", self.structures.len())
 			.expect("should be able to write to string");
 		writeln!(
@@ -373,54 +1129,325 @@ impl State for Sector {
 		.expect("should be able to write to string");
 	}
 
+	fn draw_settings(&mut self, ui: &mut egui::Ui) {
+		let mut roll_assist = self.player.roll_assist;
+
+		ui.checkbox(&mut roll_assist.enabled, tr!("settings.roll_assist"))
+			.on_hover_text(tr!("settings.roll_assist_hint"));
+
+		ui.add_enabled(
+			roll_assist.enabled,
+			Slider::new(
+				&mut roll_assist.strength,
+				RollAssist::MIN_STRENGTH..=RollAssist::MAX_STRENGTH,
+			)
+			.text(tr!("settings.roll_assist_strength")),
+		);
+
+		if roll_assist != self.player.roll_assist {
+			self.player.roll_assist = roll_assist;
+			roll_assist.save();
+		}
+
+		if let Some(sessions) = &mut self.sessions {
+			sessions.draw(ui);
+		}
+	}
+
 	fn draw_ui(&mut self, _: &crate::ClArgs, context: &egui::Context) {
-		Window::new("Inventory")
-			.anchor(Align2::CENTER_CENTER, [0.0, 0.0])
-			.auto_sized()
-			.collapsible(false)
-			.hscroll(false)
-			.max_width(512.0)
-			.open(&mut self.inventory_gui_open)
-			.resizable(false)
-			.show(context, |window| {
-				if window
-					.button(r#"Temporary magic "give me an item" button"#)
-					.clicked()
-				{
-					self.player.connection.send(Serverbound::GiveTestItem);
-				}
+		self.toasts.draw(context);
+		self.loading.draw(context);
 
-				window.columns(4, |columns| {
-					let mut column = 0;
+		if let Some(stats) = self.player.connection.stats() {
+			let health = ConnectionStatus::health(stats.last_received(), Instant::now());
+			ConnectionStatus::draw(health, context);
+		}
 
-					for InventorySlot { item, quantity } in &self.inventory {
-						let next_column = {
-							let result = column;
-							column += 1;
-							if column == columns.len() {
-								column = 0;
-							}
-							result
-						};
+		if self.terrain_brush.is_none() {
+			self.draw_hotbar(context);
+		}
 
-						columns[next_column].group(|group| {
-							group.with_layout(Layout::top_down(Min), |group| {
-								group.label(format!("{} ({})", item.display_name(), quantity));
-								group.label(item.description());
-							});
-						});
+		if self.inspecting {
+			if let Some(inspection) = Inspection::find(self) {
+				inspection.draw(context);
+			}
+		}
+
+		if let Some((title, body)) = self.notices.front() {
+			let mut dismissed = false;
+
+			Window::new(&**title)
+				.id(egui::Id::new("notice"))
+				.anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+				.auto_sized()
+				.collapsible(false)
+				.resizable(false)
+				.show(context, |window| {
+					window.label(&**body);
+
+					if window.button(tr!("common.ok")).clicked() {
+						dismissed = true;
 					}
 				});
-			});
+
+			if dismissed {
+				self.notices.pop_front();
+			}
+
+			// Notices are modal, nothing else should be interactable until they're dismissed
+			return;
+		}
+
+		if let Some(chat) = &mut self.chat {
+			Area::new(egui::Id::new("chat"))
+				.anchor(Align2::LEFT_BOTTOM, [4.0, -4.0])
+				.show(context, |area| {
+					self.chat_log.draw(area);
+
+					area.add(
+						TextEdit::singleline(chat)
+							.desired_width(400.0)
+							.hint_text(tr!("chat.hint")),
+					)
+					.request_focus();
+				});
+		}
+
+		if let Some(structure) = self.pending_deletion {
+			Window::new(tr!("demolish.title"))
+				.id(egui::Id::new("demolish"))
+				.anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+				.auto_sized()
+				.collapsible(false)
+				.resizable(false)
+				.show(context, |window| {
+					window.label(tr!("demolish.confirm", structure = structure));
+
+					window.horizontal(|layout| {
+						if layout.button(tr!("demolish.demolish")).clicked() {
+							self.player.connection.send(DeleteStructure { structure });
+							self.pending_deletion = None;
+						}
+
+						if layout.button(tr!("common.cancel")).clicked() {
+							self.pending_deletion = None;
+						}
+					});
+				});
+		}
+
+		self.map.draw(
+			context,
+			nearest_voxject(&self.voxjects, &self.player.location.position),
+			&self.shared.chunks,
+			&self.player.location,
+			self.structures.iter().map(|structure| {
+				structure
+					.get_location(&self.physics)
+					.translation
+					.vector
+					.into()
+			}),
+		);
+
+		if self.inventory_window.draw(context, self.inventory.as_ref()) {
+			self.player.connection.send(Serverbound::GiveTestItem);
+		}
+
+		let mut container_open = self.container.is_some();
+
+		if let Some(container) = &self.container {
+			Window::new(tr!("container.title"))
+				.id(egui::Id::new("container"))
+				.anchor(Align2::CENTER_CENTER, [0.0, 0.0])
+				.auto_sized()
+				.collapsible(false)
+				.hscroll(false)
+				.max_width(512.0)
+				.open(&mut container_open)
+				.resizable(false)
+				.show(context, |window| {
+					inventory_window::draw_slots(window, &container.inventory);
+
+					if container.inventory.slots.is_empty() {
+						window.label(tr!("container.empty"));
+					}
+				});
+		}
+
+		if !container_open {
+			self.container = None;
+		}
 	}
 
 	fn window_event(&mut self, event: &WindowEvent) {
-		match self.inventory_gui_open {
+		if !self.notices.is_empty() {
+			if let WindowEvent::KeyboardInput {
+				event:
+					KeyEvent {
+						physical_key: PhysicalKey::Code(KeyCode::Escape | KeyCode::Enter),
+						state: ElementState::Released,
+						repeat: false,
+						..
+					},
+				..
+			} = event
+			{
+				self.notices.pop_front();
+			}
+
+			return;
+		}
+
+		if let Some(chat) = &self.chat {
+			if let WindowEvent::KeyboardInput {
+				event:
+					KeyEvent {
+						physical_key: PhysicalKey::Code(key @ (KeyCode::Escape | KeyCode::Enter)),
+						state: ElementState::Released,
+						repeat: false,
+						..
+					},
+				..
+			} = event
+			{
+				if *key == KeyCode::Enter && !chat.trim().is_empty() {
+					self.player.connection.send(SendChatMessage {
+						text: chat.trim().into(),
+					});
+				}
+
+				self.chat = None;
+			}
+
+			return;
+		}
+
+		if let WindowEvent::KeyboardInput {
+			event:
+				KeyEvent {
+					physical_key: PhysicalKey::Code(Inspection::KEY),
+					state,
+					..
+				},
+			..
+		} = event
+		{
+			self.inspecting = state.is_pressed();
+			return;
+		}
+
+		if let WindowEvent::KeyboardInput {
+			event:
+				KeyEvent {
+					physical_key: PhysicalKey::Code(KeyCode::F8),
+					state: ElementState::Released,
+					repeat: false,
+					..
+				},
+			..
+		} = event
+		{
+			match Dump::new(self).write() {
+				Ok(path) => {
+					info!("Wrote world dump to {}", path.display());
+					self.toasts.push(
+						tr!("dump.written", path = path.display()).into(),
+						Severity::Info,
+					);
+				}
+				Err(error) => {
+					warn!("Failed to write world dump: {error}");
+					self.toasts
+						.push(tr!("dump.failed", error = error).into(), Severity::Warning);
+				}
+			}
+			return;
+		}
+
+		// The server still checks that the player is an admin
+		#[cfg(debug)]
+		if let WindowEvent::KeyboardInput {
+			event:
+				KeyEvent {
+					physical_key: PhysicalKey::Code(KeyCode::F6),
+					state: ElementState::Released,
+					repeat: false,
+					..
+				},
+			..
+		} = event
+		{
+			self.player.connection.send(
+				solarscape_shared::message::serverbound::SetAmbientPaused {
+					paused: !self.ambient.paused(),
+				},
+			);
+			return;
+		}
+
+		if self.pending_deletion.is_some() {
+			if let WindowEvent::KeyboardInput {
+				event:
+					KeyEvent {
+						physical_key: PhysicalKey::Code(KeyCode::Escape),
+						state: ElementState::Released,
+						repeat: false,
+						..
+					},
+				..
+			} = event
+			{
+				self.pending_deletion = None;
+			}
+
+			return;
+		}
+
+		if self.container.is_some() {
+			if let WindowEvent::KeyboardInput {
+				event:
+					KeyEvent {
+						physical_key: PhysicalKey::Code(KeyCode::Escape),
+						state: ElementState::Released,
+						repeat: false,
+						..
+					},
+				..
+			} = event
+			{
+				self.container = None;
+			}
+
+			return;
+		}
+
+		if self.map.open {
+			if let WindowEvent::KeyboardInput {
+				event:
+					KeyEvent {
+						physical_key: PhysicalKey::Code(key),
+						state: ElementState::Released,
+						repeat: false,
+						..
+					},
+				..
+			} = event
+			{
+				if *key == KeyCode::Escape || *key == Map::KEY {
+					self.map.open = false;
+				}
+			}
+
+			return;
+		}
+
+		match self.inventory_window.open {
 			true => {
 				if let WindowEvent::KeyboardInput {
 					event:
 						KeyEvent {
-							physical_key: PhysicalKey::Code(KeyCode::Escape),
+							physical_key: PhysicalKey::Code(key),
 							state: ElementState::Released,
 							repeat: false,
 							..
@@ -428,14 +1455,14 @@ impl State for Sector {
 					..
 				} = event
 				{
-					self.inventory_gui_open = false;
+					self.inventory_window.key_released(*key);
 				}
 			}
 			false => {
 				if let WindowEvent::KeyboardInput {
 					event:
 						KeyEvent {
-							physical_key: PhysicalKey::Code(KeyCode::Tab),
+							physical_key: PhysicalKey::Code(InventoryWindow::KEY),
 							state: ElementState::Released,
 							repeat: false,
 							..
@@ -443,16 +1470,60 @@ impl State for Sector {
 					..
 				} = event
 				{
-					self.inventory_gui_open = true;
-				} else {
-					self.player.handle_window_event(event);
+					self.inventory_window.open = true;
+				} else if let WindowEvent::KeyboardInput {
+					event:
+						KeyEvent {
+							physical_key: PhysicalKey::Code(Map::KEY),
+							state: ElementState::Released,
+							repeat: false,
+							..
+						},
+					..
+				} = event
+				{
+					self.map.open = true;
+				} else if let WindowEvent::KeyboardInput {
+					event:
+						KeyEvent {
+							physical_key: PhysicalKey::Code(KeyCode::KeyX),
+							state: ElementState::Released,
+							repeat: false,
+							..
+						},
+					..
+				} = event
+				{
+					self.pending_deletion = self.targeted_structure();
+				} else if let WindowEvent::KeyboardInput {
+					event:
+						KeyEvent {
+							physical_key: PhysicalKey::Code(key @ (KeyCode::Enter | KeyCode::Slash)),
+							state: ElementState::Released,
+							repeat: false,
+							..
+						},
+					..
+				} = event
+				{
+					// Opened on release, so the key isn't also typed into the chat box
+					self.chat = Some(match key {
+						KeyCode::Slash => String::from("/"),
+						_ => String::new(),
+					});
+				} else if !self.handle_terrain_brush_event(event)
+					&& !self.handle_hotbar_event(event)
+					&& !self.handle_interact_event(event)
+				{
+					self.player
+						.handle_window_event(event, self.hotbar.selected());
 				}
 			}
 		}
 	}
 
 	fn device_event(&mut self, event: &DeviceEvent) {
-		if !self.inventory_gui_open {
+		if !self.gui_open() {
 			self.player.handle_device_event(event);
 		}
 	}
@@ -466,58 +1537,99 @@ impl Deref for Sector {
 	}
 }
 
+/// How many lines `delta` scrolled, upwards being positive. Touchpads scroll by pixels, which are roughly converted.
+fn scroll_lines(delta: &MouseScrollDelta) -> f32 {
+	match delta {
+		MouseScrollDelta::LineDelta(_, y) => *y,
+		MouseScrollDelta::PixelDelta(position) => position.y as f32 / 32.0,
+	}
+}
+
+pub struct TerrainBrush {
+	pub radius: f32,
+	pub material: Material,
+}
+
+impl TerrainBrush {
+	/// Gap between the player and the nearest edge of the brush, so that the player isn't inside of it.
+	const GAP: f32 = 2.0;
+
+	pub fn center(&self, location: &Location) -> Point3<f32> {
+		location.position
+			+ location.rotation.inverse_transform_vector(&-Vector3::z()) * (self.radius + Self::GAP)
+	}
+}
+
+impl Default for TerrainBrush {
+	fn default() -> Self {
+		Self {
+			radius: 2.0,
+			material: Material::Stone,
+		}
+	}
+}
+
 pub struct Voxject {
 	pub id: Id,
 	pub name: Box<str>,
 	pub location: Isometry3<f32>,
 }
 
+/// The voxject closest to `position`, however far away it is.
+fn nearest_voxject<'a>(
+	voxjects: &'a HashMap<Id, Voxject>,
+	position: &Point3<f32>,
+) -> Option<&'a Voxject> {
+	voxjects.values().min_by(|a, b| {
+		let a = (position.coords - a.location.translation.vector).norm();
+		let b = (position.coords - b.location.translation.vector).norm();
+		f32::total_cmp(&a, &b)
+	})
+}
+
 #[non_exhaustive]
 pub struct Chunk {
 	pub coordinates: ChunkCoordinates,
-	pub materials: Box<[Material; 4096]>,
-	pub densities: Box<[f32; 4096]>,
+	pub generation: u64,
+	pub materials: Arc<[Material; CELLS_PER_CHUNK]>,
+	pub densities: Arc<[f32; CELLS_PER_CHUNK]>,
 	pub mesh: Option<ChunkMesh>,
 }
 
 pub struct ChunkMesh {
 	pub vertex_count: u32,
 
-	pub vertex_position_buffer: Buffer,
-	pub vertex_data_buffer: Buffer,
-	pub instance_buffer: Buffer,
+	pub vertex_position_buffer: Tracked<Buffer>,
+	pub vertex_data_buffer: Tracked<Buffer>,
+	pub instance_buffer: Tracked<Buffer>,
 
-	collider: AutoCleanup<ColliderHandle>,
+	_collider: Option<AutoCleanup<ColliderHandle>>,
 	rigid_body: AutoCleanup<RigidBodyHandle>,
 }
 
-#[allow(unused)]
-#[derive(Clone, Copy)]
-#[repr(packed)]
-struct VertexData {
-	normal: Vector3<f32>,
-	material_a: Vector2<u8>,
-	material_b: Vector2<u8>,
-	weight: f32,
-}
-
 impl Chunk {
+	/// Drops the chunk's mesh, removing it's rigid body and collider immediately rather than on the next physics tick.
+	pub fn clear_mesh(&mut self, physics: &mut Physics) {
+		if let Some(mesh) = self.mesh.take() {
+			physics.remove_now(*mesh.rigid_body);
+		}
+	}
+
 	pub fn rebuild_mesh(
 		&mut self,
 		sector: &mut Sector,
 		device: &Device,
-		densities: [f32; 17 * 17 * 17],
-		materials: [Material; 17 * 17 * 17],
+		densities: [f32; SAMPLES_PER_CHUNK],
+		materials: [Material; SAMPLES_PER_CHUNK],
 	) {
-		let mut vertex_positions = vec![];
-		let mut vertex_data = vec![];
+		// Remove the old collider now, otherwise it would overlap with the new one until the next physics tick
+		self.clear_mesh(&mut sector.physics);
 
-		unsafe impl Zeroable for VertexData {}
-		unsafe impl Pod for VertexData {}
+		let mut vertices = vec![];
 
-		for x in 0..16 {
-			for y in 0..16 {
-				for z in 0..16 {
+		for x in 0..CHUNK_SIZE {
+			for y in 0..CHUNK_SIZE {
+				for z in 0..CHUNK_SIZE {
 					let indexes = [
 						(x, y, z + 1),
 						(x + 1, y, z + 1),
@@ -528,7 +1640,7 @@ impl Chunk {
 						(x + 1, y + 1, z),
 						(x, y + 1, z),
 					]
-					.map(|(x, y, z)| (x * 289) + (y * 17) + z);
+					.map(|(x, y, z)| sample_index(x, y, z));
 
 					let densities = indexes.map(|index| densities[index]);
 					let materials = indexes.map(|index| materials[index]);
@@ -550,8 +1662,7 @@ impl Chunk {
 					} = CELL_EDGE_MAP[case_index];
 
 					for edge_indices in edge_indices.chunks(3).take(count as usize) {
-						let mut cell_vertex_positions = vec![];
-						let mut cell_vertex_data = vec![];
+						let mut cell_vertices = vec![];
 
 						for edge_index in edge_indices.iter() {
 							let (a_index, b_index) = EDGE_CORNER_MAP[*edge_index as usize];
@@ -581,39 +1692,30 @@ impl Chunk {
 								materials[b_index]
 							};
 
-							cell_vertex_positions
-								.push(point![x as f32, y as f32, z as f32] + vertex);
-
-							cell_vertex_data.push(VertexData {
+							cell_vertices.push(ChunkVertex {
+								position: point![x as f32, y as f32, z as f32] + vertex,
 								normal: Vector3::default(),
-								material_a: vector![
-									(a_material as u8 & 0xC) >> 2,
-									a_material as u8 & 0x3
-								],
-								material_b: vector![
-									(b_material as u8 & 0xC) >> 2,
-									b_material as u8 & 0x3
-								],
+								material_a: a_material as u8 & 0xF,
+								material_b: b_material as u8 & 0xF,
 								weight,
 							});
 						}
 
-						let normal = (cell_vertex_positions[1] - cell_vertex_positions[0])
-							.cross(&(cell_vertex_positions[2] - cell_vertex_positions[0]))
+						let normal = (cell_vertices[1].position - cell_vertices[0].position)
+							.cross(&(cell_vertices[2].position - cell_vertices[0].position))
 							.normalize();
 
-						cell_vertex_data[0].normal = normal;
-						cell_vertex_data[1].normal = normal;
-						cell_vertex_data[2].normal = normal;
+						for vertex in &mut cell_vertices {
+							vertex.normal = normal;
+						}
 
-						vertex_positions.extend_from_slice(&cell_vertex_positions);
-						vertex_data.extend_from_slice(&cell_vertex_data);
+						vertices.extend_from_slice(&cell_vertices);
 					}
 				}
 			}
 		}
 
-		if vertex_data.is_empty() {
+		if vertices.is_empty() {
 			self.mesh = None;
 			return;
 		}
@@ -628,43 +1730,73 @@ impl Chunk {
 		unsafe impl Zeroable for InstanceData {}
 		unsafe impl Pod for InstanceData {}
 
-		let rigid_body = sector.physics.insert_rigid_body(
-			RigidBodyBuilder::fixed().translation(self.coordinates.voxject_relative_translation()),
+		let location = self.coordinates.world_location(
+			&sector
+				.voxjects
+				.get(&self.coordinates.voxject)
+				.map_or_else(Isometry3::identity, |voxject| voxject.location),
 		);
 
+		let rigid_body = sector
+			.physics
+			.insert_rigid_body(RigidBodyBuilder::fixed().position(location));
+
+		let vertex_positions = vertices
+			.iter()
+			.map(|vertex| vertex.position)
+			.collect::<Vec<_>>();
+
 		let vertex_indices = (0..vertex_positions.len() as u32)
 			.collect::<Vec<_>>()
 			.chunks_exact(3)
 			.map(|chunk| [chunk[0], chunk[1], chunk[2]])
-			.collect();
+			.collect::<Vec<_>>();
 
-		self.mesh = Some(ChunkMesh {
-			vertex_count: vertex_data.len() as u32,
+		// The mesh is still drawn if nothing survives sanitizing, there's just nothing to collide with
+		let collider = trimesh::collider(&vertex_positions, &vertex_indices).map(|collider| {
+			sector.physics.insert_collider_with_owner(
+				*rigid_body,
+				collider,
+				CollisionGroup::Terrain,
+				ColliderOwner::Chunk(self.coordinates),
+			)
+		});
 
-			vertex_position_buffer: device.create_buffer_init(&BufferInitDescriptor {
-				label: Some("chunk.mesh#vertex_position_buffer"),
-				contents: cast_slice(&vertex_positions),
-				usage: BufferUsages::VERTEX,
-			}),
-			vertex_data_buffer: device.create_buffer_init(&BufferInitDescriptor {
-				label: Some("chunk.mesh#vertex_data_buffer"),
-				contents: cast_slice(&vertex_data),
-				usage: BufferUsages::VERTEX,
-			}),
-			instance_buffer: device.create_buffer_init(&BufferInitDescriptor {
-				label: Some("chunk.mesh.instance_buffer"),
-				contents: cast_slice(&[InstanceData {
-					position: self.coordinates.coordinates.cast()
-						* (16u64 << *self.coordinates.level) as f32,
-					scale: (*self.coordinates.level + 1) as f32,
-				}]),
-				usage: BufferUsages::VERTEX,
-			}),
+		let (vertex_position_contents, vertex_data_contents) = chunk_vertex::encode(&vertices);
 
-			collider: sector.physics.insert_rigid_body_collider(
-				*rigid_body,
-				ColliderBuilder::trimesh(vertex_positions, vertex_indices),
+		self.mesh = Some(ChunkMesh {
+			vertex_count: vertices.len() as u32,
+
+			vertex_position_buffer: Tracked::buffer(
+				GpuCategory::ChunkMeshes,
+				device.create_buffer_init(&BufferInitDescriptor {
+					label: Some("chunk.mesh#vertex_position_buffer"),
+					contents: &vertex_position_contents,
+					usage: BufferUsages::VERTEX,
+				}),
 			),
+			vertex_data_buffer: Tracked::buffer(
+				GpuCategory::ChunkMeshes,
+				device.create_buffer_init(&BufferInitDescriptor {
+					label: Some("chunk.mesh#vertex_data_buffer"),
+					contents: &vertex_data_contents,
+					usage: BufferUsages::VERTEX,
+				}),
+			),
+			instance_buffer: Tracked::buffer(
+				GpuCategory::ChunkMeshes,
+				device.create_buffer_init(&BufferInitDescriptor {
+					label: Some("chunk.mesh.instance_buffer"),
+					contents: cast_slice(&[InstanceData {
+						// The shader doesn't rotate chunks yet, which is fine until voxjects can rotate
+						position: location.translation.vector,
+						scale: (*self.coordinates.level + 1) as f32,
+					}]),
+					usage: BufferUsages::VERTEX,
+				}),
+			),
+
+			_collider: collider,
 			rigid_body,
 		});
 	}
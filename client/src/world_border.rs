@@ -0,0 +1,89 @@
+use nalgebra::Point3;
+
+/// The sector's world border, which is drawn as a translucent shell around the sector's origin once the player gets
+/// close to it, see `world_border.wgsl`.
+pub struct WorldBorder {
+	radius: f32,
+	/// Set once the player has been warned, and cleared once they've moved back from the border.
+	warned: bool,
+}
+
+impl WorldBorder {
+	/// How far from the border it starts fading in.
+	pub const FADE_DISTANCE: f32 = 32.0;
+
+	/// Fraction of the radius the player is warned at.
+	const WARNING: f32 = 0.95;
+
+	/// Fraction of the radius the player has to move back inside of before they're warned again, so that moving about
+	/// near the warning distance doesn't warn them over and over.
+	const WARNING_RESET: f32 = 0.9;
+
+	pub fn new(radius: f32) -> Self {
+		Self {
+			radius,
+			warned: false,
+		}
+	}
+
+	pub fn radius(&self) -> f32 {
+		self.radius
+	}
+
+	/// How visible the border is with the camera at `position`, from 0 until the camera is within
+	/// [`WorldBorder::FADE_DISTANCE`] of it up to 1 at the border and beyond. Nothing needs drawing at 0.
+	pub fn opacity(&self, position: &Point3<f32>) -> f32 {
+		let distance_to_border = self.radius - position.coords.norm();
+		(1.0 - distance_to_border / Self::FADE_DISTANCE).clamp(0.0, 1.0)
+	}
+
+	/// Returns `true` when the player at `position` has just come close enough to the border to be warned.
+	pub fn update(&mut self, position: &Point3<f32>) -> bool {
+		let distance = position.coords.norm();
+
+		if distance < self.radius * Self::WARNING_RESET {
+			self.warned = false;
+		} else if distance >= self.radius * Self::WARNING && !self.warned {
+			self.warned = true;
+			return true;
+		}
+
+		false
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::point;
+
+	#[test]
+	fn fades_in_approaching_the_border() {
+		let border = WorldBorder::new(100.0);
+
+		assert_eq!(border.opacity(&Point3::origin()), 0.0);
+		assert_eq!(
+			border.opacity(&point![0.0, 100.0 - WorldBorder::FADE_DISTANCE, 0.0]),
+			0.0
+		);
+		assert_eq!(border.opacity(&point![0.0, 0.0, 84.0]), 0.5);
+		assert_eq!(border.opacity(&point![-100.0, 0.0, 0.0]), 1.0);
+		assert_eq!(border.opacity(&point![200.0, 0.0, 0.0]), 1.0);
+	}
+
+	#[test]
+	fn warns_once_until_moving_back() {
+		let mut border = WorldBorder::new(100.0);
+
+		assert!(!border.update(&point![94.0, 0.0, 0.0]));
+		assert!(border.update(&point![95.0, 0.0, 0.0]));
+		assert!(!border.update(&point![99.0, 0.0, 0.0]));
+
+		// Not far enough back to be warned again
+		assert!(!border.update(&point![92.0, 0.0, 0.0]));
+		assert!(!border.update(&point![96.0, 0.0, 0.0]));
+
+		assert!(!border.update(&point![80.0, 0.0, 0.0]));
+		assert!(border.update(&point![0.0, -96.0, 0.0]));
+	}
+}
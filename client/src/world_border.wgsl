@@ -0,0 +1,81 @@
+// Draws the world border as a sphere around the sector's origin, by intersecting each pixel's view ray with it rather
+// than drawing an actual mesh. Only drawn when the camera is close to the border, see world_border.rs.
+
+struct PushConstants {
+	// Inverse of the camera matrix without the camera's translation, so that unprojecting gives a direction
+	inverse_camera: mat4x4<f32>,
+	camera_position: vec3<f32>,
+	radius: f32,
+	color: vec3<f32>,
+	opacity: f32,
+}
+
+var<push_constant> push_constants: PushConstants;
+
+// Spacing of the grid lines drawn on the border, in meters
+const GRID_SPACING: f32 = 4.0;
+
+struct VertexOutput {
+	@builtin(position) position: vec4<f32>,
+	@location(0) ndc: vec2<f32>,
+}
+
+// A single triangle covering the whole screen
+@vertex fn vertex(@builtin(vertex_index) vertex_index: u32) -> VertexOutput {
+	let ndc = vec2<f32>(f32(vertex_index & 1u) * 4.0 - 1.0, f32(vertex_index >> 1u) * 4.0 - 1.0);
+
+	var output: VertexOutput;
+	output.position = vec4<f32>(ndc, 0.0, 1.0);
+	output.ndc = ndc;
+	return output;
+}
+
+struct FragmentOutput {
+	@location(0) color: vec4<f32>,
+	@builtin(frag_depth) depth: f32,
+}
+
+@fragment fn fragment(input: VertexOutput) -> FragmentOutput {
+	// Two points along the view ray at known depths, which give both the ray's direction and how depth changes along it
+	let near = push_constants.inverse_camera * vec4<f32>(input.ndc, 0.25, 1.0);
+	let far = push_constants.inverse_camera * vec4<f32>(input.ndc, 0.75, 1.0);
+	let near_distance = length(near.xyz / near.w);
+	let far_distance = length(far.xyz / far.w);
+	let direction = near.xyz / near.w / near_distance;
+
+	// Solve |camera + direction * t| = radius for t
+	let camera = push_constants.camera_position;
+	let b = dot(camera, direction);
+	let c = dot(camera, camera) - push_constants.radius * push_constants.radius;
+	let discriminant = b * b - c;
+
+	if discriminant < 0.0 {
+		discard;
+	}
+
+	// From inside the border is ahead on the far side of the sphere, from outside it's the near side
+	var t: f32;
+	if c < 0.0 {
+		t = -b + sqrt(discriminant);
+	} else {
+		t = -b - sqrt(discriminant);
+	}
+
+	if t <= 0.0 {
+		discard;
+	}
+
+	// Depth goes as a + b / distance along the ray, which the two points are enough to solve for
+	let depth_b = 0.5 / (1.0 / far_distance - 1.0 / near_distance);
+	let depth_a = 0.25 - depth_b / near_distance;
+
+	let hit = camera + direction * t;
+	// Distance to the nearest grid line on each axis
+	let grid = (0.5 - abs(fract(hit / GRID_SPACING) - 0.5)) * GRID_SPACING;
+	let line = 1.0 - smoothstep(0.0, 0.1, min(grid.x, min(grid.y, grid.z)));
+
+	var output: FragmentOutput;
+	output.color = vec4<f32>(push_constants.color, push_constants.opacity * mix(0.1, 0.6, line));
+	output.depth = depth_a + depth_b / t;
+	return output;
+}
@@ -0,0 +1,361 @@
+use crate::Gateway;
+use axum::{
+	extract::{ConnectInfo, Request, State},
+	http::HeaderMap,
+	middleware::Next,
+	response::Response,
+};
+use ipnet::IpNet;
+use std::{
+	fmt::{self, Display, Formatter},
+	net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+	str::FromStr,
+};
+
+/// Address of whoever made a request, available to handlers as an extension. This is the socket peer, unless the peer
+/// is one of the `--trusted-proxies`, in which case it's the first hop in the forwarding headers that isn't.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ClientIp(pub IpAddr);
+
+impl Display for ClientIp {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		self.0.fmt(formatter)
+	}
+}
+
+/// Reverse proxies whose forwarding headers are believed, given to `--trusted-proxies` as either a network like
+/// `10.0.0.0/8` or a single address.
+#[derive(Clone, Copy, Debug)]
+pub struct TrustedProxy(IpNet);
+
+impl FromStr for TrustedProxy {
+	type Err = String;
+
+	fn from_str(text: &str) -> Result<Self, Self::Err> {
+		if let Ok(network) = IpNet::from_str(text) {
+			return Ok(Self(network.trunc()));
+		}
+
+		IpAddr::from_str(text)
+			.map(|address| Self(IpNet::from(address)))
+			.map_err(|_| format!("{text:?} is neither an address nor a network in CIDR notation"))
+	}
+}
+
+/// Works out the [`ClientIp`] of `peer`'s request. Forwarding headers are only looked at when `peer` is trusted,
+/// otherwise anyone could claim to be anyone. `Forwarded` is preferred over `X-Forwarded-For` when both are present.
+///
+/// Hops are walked from the one closest to the gateway outwards, and the first that isn't a trusted proxy is the
+/// client. A hop that can't be parsed, such as an obfuscated or `unknown` one, stops the walk at the last trusted
+/// proxy before it, as nothing it claims about hops further out can be believed.
+pub fn resolve(peer: IpAddr, headers: &HeaderMap, trusted: &[TrustedProxy]) -> ClientIp {
+	let is_trusted = |address: &IpAddr| trusted.iter().any(|proxy| proxy.0.contains(address));
+
+	if !is_trusted(&peer) {
+		return ClientIp(peer);
+	}
+
+	let hops = match headers.contains_key("Forwarded") {
+		true => forwarded_hops(headers),
+		false => x_forwarded_for_hops(headers),
+	};
+
+	let mut client = peer;
+
+	// Furthest hop first in the headers, so walk them backwards
+	for hop in hops.iter().rev() {
+		let Some(address) = hop else {
+			break;
+		};
+
+		client = *address;
+
+		if !is_trusted(address) {
+			break;
+		}
+	}
+
+	ClientIp(client)
+}
+
+/// Every `for` hop in the `Forwarded` headers, see [RFC 7239](https://www.rfc-editor.org/rfc/rfc7239). [`None`] for
+/// hops that aren't an address, and for elements with no `for` at all.
+fn forwarded_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+	let mut hops = vec![];
+
+	for value in headers.get_all("Forwarded") {
+		let Ok(value) = value.to_str() else {
+			hops.push(None);
+			continue;
+		};
+
+		for element in value.split(',') {
+			let node = element.split(';').find_map(|pair| {
+				let (key, value) = pair.split_once('=')?;
+				key.trim().eq_ignore_ascii_case("for").then_some(value)
+			});
+
+			hops.push(node.and_then(|node| parse_node(node.trim().trim_matches('"'))));
+		}
+	}
+
+	hops
+}
+
+/// Every hop in the `X-Forwarded-For` headers, [`None`] for hops that aren't an address.
+fn x_forwarded_for_hops(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+	let mut hops = vec![];
+
+	for value in headers.get_all("X-Forwarded-For") {
+		let Ok(value) = value.to_str() else {
+			hops.push(None);
+			continue;
+		};
+
+		hops.extend(value.split(',').map(|hop| parse_node(hop.trim())));
+	}
+
+	hops
+}
+
+/// Parses an address that may have a port, with IPv6 addresses in brackets if they do, like `192.0.2.1`,
+/// `192.0.2.1:80`, `2001:db8::1`, or `[2001:db8::1]:80`.
+fn parse_node(node: &str) -> Option<IpAddr> {
+	if let Some(bracketed) = node.strip_prefix('[') {
+		let (address, port) = bracketed.split_once(']')?;
+
+		if !(port.is_empty() || port.strip_prefix(':').is_some_and(is_port)) {
+			return None;
+		}
+
+		return address
+			.parse::<Ipv6Addr>()
+			.ok()
+			.map(|address| IpAddr::V6(address).to_canonical());
+	}
+
+	if let Ok(address) = node.parse::<IpAddr>() {
+		return Some(address.to_canonical());
+	}
+
+	// Only IPv4 can have an unbracketed port, IPv6 would've parsed above
+	let (address, port) = node.split_once(':')?;
+
+	match is_port(port) {
+		true => address.parse::<Ipv4Addr>().ok().map(IpAddr::V4),
+		false => None,
+	}
+}
+
+fn is_port(port: &str) -> bool {
+	port.bytes().all(|byte| byte.is_ascii_digit()) && port.parse::<u16>().is_ok()
+}
+
+/// Adds the request's [`ClientIp`] to it's extensions.
+pub async fn middleware(
+	State(gateway): State<Gateway>,
+	ConnectInfo(peer): ConnectInfo<SocketAddr>,
+	mut request: Request,
+	next: Next,
+) -> Response {
+	let client_ip = resolve(
+		peer.ip().to_canonical(),
+		request.headers(),
+		&gateway.cl_args.trusted_proxies,
+	);
+
+	request.extensions_mut().insert(client_ip);
+
+	next.run(request).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const PEER: &str = "10.0.0.1";
+
+	fn trusted(proxies: &[&str]) -> Vec<TrustedProxy> {
+		proxies.iter().map(|proxy| proxy.parse().unwrap()).collect()
+	}
+
+	fn headers(headers: &[(&'static str, &'static str)]) -> HeaderMap {
+		let mut map = HeaderMap::new();
+
+		for (name, value) in headers {
+			map.append(*name, value.parse().unwrap());
+		}
+
+		map
+	}
+
+	fn resolve_from(
+		peer: &str,
+		request: &[(&'static str, &'static str)],
+		proxies: &[&str],
+	) -> String {
+		resolve(peer.parse().unwrap(), &headers(request), &trusted(proxies)).to_string()
+	}
+
+	#[test]
+	fn untrusted_peer_ignores_headers() {
+		let request = [
+			("X-Forwarded-For", "203.0.113.7"),
+			("Forwarded", "for=203.0.113.8"),
+		];
+
+		assert_eq!(resolve_from(PEER, &request, &[]), PEER);
+		assert_eq!(resolve_from(PEER, &request, &["192.168.0.0/16"]), PEER);
+	}
+
+	#[test]
+	fn trusted_peer_without_headers_is_the_client() {
+		assert_eq!(resolve_from(PEER, &[], &["10.0.0.0/8"]), PEER);
+	}
+
+	#[test]
+	fn spoofed_x_forwarded_for_is_skipped() {
+		// The client sent it's own X-Forwarded-For claiming to be 1.1.1.1, and the proxy appended the real address
+		let request = [("X-Forwarded-For", "1.1.1.1, 203.0.113.7")];
+
+		assert_eq!(resolve_from(PEER, &request, &["10.0.0.1"]), "203.0.113.7");
+	}
+
+	#[test]
+	fn multiple_hops_stop_at_the_first_untrusted() {
+		let request = [(
+			"X-Forwarded-For",
+			"198.51.100.2, 203.0.113.7, 10.0.0.3, 10.0.0.2",
+		)];
+
+		assert_eq!(resolve_from(PEER, &request, &["10.0.0.0/8"]), "203.0.113.7");
+	}
+
+	#[test]
+	fn hops_split_across_headers_are_joined() {
+		let request = [
+			("X-Forwarded-For", "198.51.100.2"),
+			("X-Forwarded-For", "203.0.113.7, 10.0.0.2"),
+		];
+
+		assert_eq!(resolve_from(PEER, &request, &["10.0.0.0/8"]), "203.0.113.7");
+	}
+
+	#[test]
+	fn every_hop_trusted_is_the_furthest() {
+		let request = [("X-Forwarded-For", "10.0.0.3, 10.0.0.2")];
+
+		assert_eq!(resolve_from(PEER, &request, &["10.0.0.0/8"]), "10.0.0.3");
+	}
+
+	#[test]
+	fn malformed_hop_stops_at_the_last_trusted() {
+		for hop in [
+			"unknown",
+			"",
+			"not an address",
+			"300.0.0.1",
+			"203.0.113.7:99999",
+		] {
+			let value = format!("198.51.100.2, {hop}, 10.0.0.2");
+			let mut request = HeaderMap::new();
+			request.insert("X-Forwarded-For", value.parse().unwrap());
+
+			assert_eq!(
+				resolve(PEER.parse().unwrap(), &request, &trusted(&["10.0.0.0/8"])).to_string(),
+				"10.0.0.2",
+				"{value:?}"
+			);
+		}
+	}
+
+	#[test]
+	fn forwarded_is_preferred_over_x_forwarded_for() {
+		let request = [
+			("X-Forwarded-For", "198.51.100.2"),
+			("Forwarded", "for=203.0.113.7;proto=https, for=10.0.0.2"),
+		];
+
+		assert_eq!(resolve_from(PEER, &request, &["10.0.0.0/8"]), "203.0.113.7");
+	}
+
+	#[test]
+	fn forwarded_elements_without_for_stop_the_walk() {
+		let request = [("Forwarded", "for=203.0.113.7, proto=https, for=10.0.0.2")];
+
+		assert_eq!(resolve_from(PEER, &request, &["10.0.0.0/8"]), "10.0.0.2");
+	}
+
+	#[test]
+	fn forwarded_obfuscated_identifiers_stop_the_walk() {
+		let request = [("Forwarded", "for=203.0.113.7, for=_hidden, for=10.0.0.2")];
+
+		assert_eq!(resolve_from(PEER, &request, &["10.0.0.0/8"]), "10.0.0.2");
+	}
+
+	#[test]
+	fn ipv6_hops() {
+		let request = [(
+			"Forwarded",
+			r#"for="[2001:db8::7]:4711", FOR="[2001:db8:ffff::1]""#,
+		)];
+		assert_eq!(
+			resolve_from("2001:db8:ffff::2", &request, &["2001:db8:ffff::/48"]),
+			"2001:db8::7"
+		);
+
+		let request = [("X-Forwarded-For", "2001:db8::7, [2001:db8::8]:80")];
+		assert_eq!(resolve_from(PEER, &request, &["10.0.0.1"]), "2001:db8::8");
+	}
+
+	#[test]
+	fn ipv4_mapped_hops_are_ipv4() {
+		let request = [("X-Forwarded-For", "::ffff:203.0.113.7")];
+
+		assert_eq!(resolve_from(PEER, &request, &["10.0.0.1"]), "203.0.113.7");
+	}
+
+	#[test]
+	fn nodes_are_parsed() {
+		let parse = |node| parse_node(node).map(|address| address.to_string());
+
+		assert_eq!(parse("192.0.2.1").as_deref(), Some("192.0.2.1"));
+		assert_eq!(parse("192.0.2.1:80").as_deref(), Some("192.0.2.1"));
+		assert_eq!(parse("2001:db8::1").as_deref(), Some("2001:db8::1"));
+		assert_eq!(parse("[2001:db8::1]").as_deref(), Some("2001:db8::1"));
+		assert_eq!(parse("[2001:db8::1]:80").as_deref(), Some("2001:db8::1"));
+
+		for malformed in [
+			"",
+			"unknown",
+			"192.0.2.1:",
+			"192.0.2.1:http",
+			"192.0.2.1:+80",
+			"192.0.2.1:65536",
+			"[192.0.2.1]",
+			"[2001:db8::1",
+			"[2001:db8::1]80",
+			"2001:db8::1:80:80:80:80:80",
+		] {
+			assert_eq!(parse(malformed), None, "{malformed:?}");
+		}
+	}
+
+	#[test]
+	fn trusted_proxies_are_parsed() {
+		let proxies = trusted(&["10.1.2.3/8", "192.0.2.1", "2001:db8::/32"]);
+		let contains = |address: &str| {
+			let address = address.parse::<IpAddr>().unwrap();
+			proxies.iter().any(|proxy| proxy.0.contains(&address))
+		};
+
+		// Host bits are dropped rather than rejected
+		assert!(contains("10.200.0.1"));
+		assert!(contains("192.0.2.1"));
+		assert!(!contains("192.0.2.2"));
+		assert!(contains("2001:db8:1::1"));
+
+		assert!("10.0.0.0/33".parse::<TrustedProxy>().is_err());
+		assert!("localhost".parse::<TrustedProxy>().is_err());
+	}
+}
@@ -0,0 +1,259 @@
+use crate::{request_id::REQUEST_ID_HEADER, Gateway};
+use axum::{
+	extract::{Request, State},
+	http::{
+		header::{
+			ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+			ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+			ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, ORIGIN, VARY,
+		},
+		HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+	},
+	middleware::Next,
+	response::{IntoResponse, Response},
+};
+use clap::Args;
+use itertools::Itertools;
+
+/// Which web pages may call the API from a browser, native clients aren't affected by any of this.
+#[derive(Args, Clone)]
+pub struct CorsConfig {
+	/// Origin allowed to call the API from a browser, such as `https://example.com`, or `*` for any. May be given more
+	/// than once, no origins are allowed by default
+	#[arg(long = "cors-origin")]
+	pub origins: Vec<String>,
+
+	/// Methods browsers may use when calling the API from an allowed origin
+	#[arg(
+		long = "cors-methods",
+		value_delimiter = ',',
+		default_value = "GET,DELETE"
+	)]
+	pub methods: Vec<Method>,
+
+	/// Request headers browsers may send when calling the API from an allowed origin
+	#[arg(
+		long = "cors-headers",
+		value_delimiter = ',',
+		default_value = "Authorization"
+	)]
+	pub headers: Vec<HeaderName>,
+}
+
+impl CorsConfig {
+	/// How long browsers may cache a preflight response, in seconds.
+	const MAX_AGE: u32 = 600;
+
+	fn allows_origin(&self, origin: &HeaderValue) -> bool {
+		self.origins
+			.iter()
+			.any(|allowed| allowed == "*" || allowed.as_bytes() == origin.as_bytes())
+	}
+
+	fn allows_method(&self, method: &HeaderValue) -> bool {
+		self.methods
+			.iter()
+			.any(|allowed| allowed.as_str().as_bytes() == method.as_bytes())
+	}
+
+	/// Whether every header in a preflight's `Access-Control-Request-Headers` is allowed.
+	fn allows_headers(&self, headers: Option<&HeaderValue>) -> bool {
+		let Some(headers) = headers else {
+			return true;
+		};
+
+		let Ok(headers) = headers.to_str() else {
+			return false;
+		};
+
+		headers
+			.split(',')
+			.map(str::trim)
+			.filter(|header| !header.is_empty())
+			.all(|header| {
+				self.headers
+					.iter()
+					.any(|allowed| allowed.as_str().eq_ignore_ascii_case(header))
+			})
+	}
+
+	/// Answers a preflight request, `origin` has already been checked. Preflights asking for a method or header that
+	/// isn't allowed are refused outright, rather than answered with what is.
+	fn preflight(&self, origin: HeaderValue, request_headers: &HeaderMap) -> Response {
+		let method = request_headers
+			.get(ACCESS_CONTROL_REQUEST_METHOD)
+			.expect("preflights should have a requested method");
+
+		if !self.allows_method(method)
+			|| !self.allows_headers(request_headers.get(ACCESS_CONTROL_REQUEST_HEADERS))
+		{
+			return StatusCode::FORBIDDEN.into_response();
+		}
+
+		let mut response = StatusCode::NO_CONTENT.into_response();
+		let headers = response.headers_mut();
+
+		headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+		headers.insert(
+			ACCESS_CONTROL_ALLOW_METHODS,
+			HeaderValue::from_str(&self.methods.iter().join(", "))
+				.expect("methods should be valid header values"),
+		);
+		headers.insert(
+			ACCESS_CONTROL_ALLOW_HEADERS,
+			HeaderValue::from_str(&self.headers.iter().join(", "))
+				.expect("header names should be valid header values"),
+		);
+		headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(Self::MAX_AGE));
+		headers.append(VARY, HeaderValue::from_static("Origin"));
+
+		response
+	}
+}
+
+/// Adds CORS headers to responses to requests from an allowed origin, and answers their preflight requests. Requests
+/// from other origins are handled as normal but without the headers, so browsers won't let the page read them, and
+/// their preflights are refused.
+pub async fn middleware(State(gateway): State<Gateway>, request: Request, next: Next) -> Response {
+	let cors = &gateway.cl_args.cors;
+
+	let Some(origin) = request.headers().get(ORIGIN).cloned() else {
+		return next.run(request).await;
+	};
+
+	let is_preflight = request.method() == Method::OPTIONS
+		&& request
+			.headers()
+			.contains_key(ACCESS_CONTROL_REQUEST_METHOD);
+
+	if !cors.allows_origin(&origin) {
+		return match is_preflight {
+			true => StatusCode::FORBIDDEN.into_response(),
+			false => next.run(request).await,
+		};
+	}
+
+	if is_preflight {
+		return cors.preflight(origin, request.headers());
+	}
+
+	let mut response = next.run(request).await;
+	let headers = response.headers_mut();
+
+	headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, origin);
+	headers.insert(
+		ACCESS_CONTROL_EXPOSE_HEADERS,
+		HeaderValue::from_static(REQUEST_ID_HEADER),
+	);
+	headers.append(VARY, HeaderValue::from_static("Origin"));
+
+	response
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::{body::Body, middleware, routing::get, Router};
+	use tower::ServiceExt;
+
+	const ALLOWED: &str = "https://allowed.example";
+
+	/// The `/api` router as far as CORS is concerned, with a single `GET /test`. The database is never connected to.
+	fn router() -> Router {
+		let gateway = crate::tests::gateway(&["--cors-origin", ALLOWED]);
+
+		Router::new()
+			.route("/test", get(|| async { "test" }))
+			.layer(middleware::from_fn_with_state(gateway, super::middleware))
+	}
+
+	fn preflight(origin: &str, method: &str, headers: Option<&str>) -> Request {
+		let mut request = Request::builder()
+			.method(Method::OPTIONS)
+			.uri("/test")
+			.header(ORIGIN, origin)
+			.header(ACCESS_CONTROL_REQUEST_METHOD, method);
+
+		if let Some(headers) = headers {
+			request = request.header(ACCESS_CONTROL_REQUEST_HEADERS, headers);
+		}
+
+		request.body(Body::empty()).unwrap()
+	}
+
+	fn get_from(origin: Option<&str>) -> Request {
+		let mut request = Request::builder().uri("/test");
+
+		if let Some(origin) = origin {
+			request = request.header(ORIGIN, origin);
+		}
+
+		request.body(Body::empty()).unwrap()
+	}
+
+	#[tokio::test]
+	async fn preflight_from_allowed_origin_is_answered() {
+		let response = router()
+			.oneshot(preflight(ALLOWED, "DELETE", Some("authorization")))
+			.await
+			.unwrap();
+
+		assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+		let headers = response.headers();
+		assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], ALLOWED);
+		assert_eq!(headers[ACCESS_CONTROL_ALLOW_METHODS], "GET, DELETE");
+		assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "authorization");
+		assert_eq!(headers[ACCESS_CONTROL_MAX_AGE], "600");
+		assert_eq!(headers[VARY], "Origin");
+	}
+
+	#[tokio::test]
+	async fn preflight_for_disallowed_method_or_header_is_refused() {
+		for request in [
+			preflight(ALLOWED, "PUT", None),
+			preflight(ALLOWED, "GET", Some("Authorization, X-Custom")),
+		] {
+			let response = router().oneshot(request).await.unwrap();
+
+			assert_eq!(response.status(), StatusCode::FORBIDDEN);
+			assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+		}
+	}
+
+	#[tokio::test]
+	async fn preflight_from_disallowed_origin_is_refused() {
+		let response = router()
+			.oneshot(preflight("https://evil.example", "GET", None))
+			.await
+			.unwrap();
+
+		assert_eq!(response.status(), StatusCode::FORBIDDEN);
+		assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+	}
+
+	#[tokio::test]
+	async fn request_from_allowed_origin_gets_headers() {
+		let response = router().oneshot(get_from(Some(ALLOWED))).await.unwrap();
+
+		assert_eq!(response.status(), StatusCode::OK);
+
+		let headers = response.headers();
+		assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], ALLOWED);
+		assert_eq!(headers[ACCESS_CONTROL_EXPOSE_HEADERS], REQUEST_ID_HEADER);
+		assert_eq!(headers[VARY], "Origin");
+	}
+
+	#[tokio::test]
+	async fn request_from_disallowed_origin_is_handled_without_headers() {
+		for request in [get_from(Some("https://evil.example")), get_from(None)] {
+			let response = router().oneshot(request).await.unwrap();
+
+			assert_eq!(response.status(), StatusCode::OK);
+			assert!(!response.headers().contains_key(ACCESS_CONTROL_ALLOW_ORIGIN));
+			assert!(!response
+				.headers()
+				.contains_key(ACCESS_CONTROL_EXPOSE_HEADERS));
+		}
+	}
+}
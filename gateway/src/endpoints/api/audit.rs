@@ -0,0 +1,187 @@
+use crate::{
+	endpoints::api::openapi::{internal_error, parameter, text, unauthorized},
+	extractors::Authenticated,
+	request_id::CurrentRequestId,
+	types::InternalError,
+	Gateway,
+};
+use axum::{
+	debug_handler,
+	extract::{Query, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	routing::get,
+	Json, Router,
+};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use solarscape_shared::data::Id;
+use sqlx::query;
+use thiserror::Error;
+
+/// Most entries returned by one request, get the rest by asking again with `since` set to the last entry's `created`.
+const MAX_ENTRIES: i64 = 1000;
+
+#[derive(Deserialize)]
+struct AuditOptions {
+	player: Option<Id>,
+	/// Seconds since the unix epoch.
+	since: Option<i64>,
+}
+
+#[derive(Serialize)]
+struct AuditEntry {
+	sector: String,
+	player: Id,
+	tick: i64,
+	/// As the sector wrote it, see `AuditEvent` in the sector server.
+	event: Value,
+	/// How many times the event happened in a row.
+	count: i32,
+	/// Seconds since the unix epoch.
+	created: i64,
+}
+
+/// Lists audit log entries oldest first, optionally only those for one player or since a time. Only players given
+/// with `--admin` may read the audit log.
+#[debug_handler]
+async fn list(
+	State(Gateway {
+		database, cl_args, ..
+	}): State<Gateway>,
+	Authenticated(id, _): Authenticated,
+	Query(AuditOptions { player, since }): Query<AuditOptions>,
+) -> Result<Json<Vec<AuditEntry>>, AuditError> {
+	if !cl_args.admins.contains(&id) {
+		return Err(AuditError::Forbidden);
+	}
+
+	let entries = query!(
+		r#"SELECT
+			sector,
+			player AS "player: Id",
+			tick,
+			event,
+			count,
+			EXTRACT(EPOCH FROM created)::Int8 AS "created!"
+		FROM audit_log
+		WHERE ($1::Int8 IS NULL OR player = $1)
+			AND ($2::Int8 IS NULL OR created >= TO_TIMESTAMP($2) AT TIME ZONE 'UTC')
+		ORDER BY id LIMIT $3"#,
+		player as _,
+		since,
+		MAX_ENTRIES
+	)
+	.fetch_all(&database)
+	.await?
+	.into_iter()
+	.map(|entry| AuditEntry {
+		sector: entry.sector,
+		player: entry.player,
+		tick: entry.tick,
+		// Sectors always write JSON, but it's better to show something odd than fail the whole request
+		event: serde_json::from_str(&entry.event).unwrap_or(Value::String(entry.event)),
+		count: entry.count,
+		created: entry.created,
+	})
+	.collect::<Vec<_>>();
+
+	let count = entries.len();
+	info!(player_id:% = id; "[{CurrentRequestId}] Player {id} read {count} audit log entries");
+
+	Ok(Json(entries))
+}
+
+#[derive(Debug, Error)]
+enum AuditError {
+	#[error("Only admins may read the audit log")]
+	Forbidden,
+
+	#[error(transparent)]
+	Internal(#[from] anyhow::Error),
+}
+
+impl<E: InternalError> From<E> for AuditError {
+	fn from(value: E) -> Self {
+		Self::Internal(value.into())
+	}
+}
+
+impl IntoResponse for AuditError {
+	fn into_response(self) -> Response {
+		use log::error;
+
+		match self {
+			AuditError::Forbidden => (StatusCode::FORBIDDEN, self.to_string()),
+			AuditError::Internal(error) => {
+				error!("[{CurrentRequestId}] {error}");
+				(
+					StatusCode::INTERNAL_SERVER_ERROR,
+					"Internal / Unknown Error".into(),
+				)
+			}
+		}
+		.into_response()
+	}
+}
+
+pub fn paths() -> Value {
+	let audit_entry = json!({
+		"type": "object",
+		"required": ["sector", "player", "tick", "event", "count", "created"],
+		"properties": {
+			"sector": { "type": "string" },
+			"player": { "type": "integer" },
+			"tick": {
+				"type": "integer",
+				"description": "The sector's tick when the event first happened, only comparable within a run",
+			},
+			"event": {
+				"type": "object",
+				"description": "What happened, `type` says which kind of event it is",
+			},
+			"count": {
+				"type": "integer",
+				"description": "How many times the event happened in a row",
+			},
+			"created": {
+				"type": "integer",
+				"description": "Seconds since the unix epoch",
+			},
+		},
+	});
+
+	let summary = format!("Lists up to {MAX_ENTRIES} audit log entries oldest first, admins only");
+	let since = "Only entries created at or after this many seconds since the unix epoch";
+
+	json!({
+		"/api/audit": {
+			"get": {
+				"summary": summary,
+				"security": [{ "token": [] }],
+				"parameters": [
+					parameter("query", "player", "integer", false, "Only entries for this player"),
+					parameter("query", "since", "integer", false, since),
+				],
+				"responses": {
+					"200": {
+						"description": "Audit log entries",
+						"content": {
+							"application/json": {
+								"schema": { "type": "array", "items": audit_entry },
+							},
+						},
+					},
+					"401": unauthorized(),
+					"403": text(&AuditError::Forbidden.to_string()),
+					"500": internal_error(),
+				},
+			},
+		},
+	})
+}
+
+pub fn router() -> Router<Gateway> {
+	Router::new().route("/", get(list))
+}
@@ -1,5 +1,7 @@
 use crate::{
+	endpoints::api::openapi::{internal_error, parameter, text, unauthorized},
 	extractors::Authenticated,
+	request_id::CurrentRequestId,
 	types::{Email, InternalError, Token},
 	Gateway, ARGON_2,
 };
@@ -13,9 +15,15 @@ use axum::{
 	Json, Router,
 };
 use chacha20poly1305::{aead::OsRng, ChaCha20Poly1305, KeyInit};
+use log::{debug, info};
 use serde::{Deserialize, Serialize};
-use solarscape_shared::message::backend::AllowConnection;
-use sqlx::{query, query_scalar};
+use serde_json::{json, Value};
+use solarscape_shared::{
+	data::Id,
+	message::backend::{AllowConnection, SectorStatus},
+};
+use sqlx::{query, query_scalar, PgPool, Postgres, Transaction};
+use std::time::Duration;
 use thiserror::Error;
 
 #[derive(Deserialize)]
@@ -26,8 +34,32 @@ struct GetToken {
 
 #[debug_handler]
 async fn token(
-	State(Gateway { database, .. }): State<Gateway>,
-	Query(GetToken { email, password }): Query<GetToken>,
+	State(Gateway {
+		database, stats, ..
+	}): State<Gateway>,
+	Query(get_token): Query<GetToken>,
+) -> Result<Token, GetTokenError> {
+	let result = issue_token(&database, get_token).await;
+
+	stats.record(|stats| {
+		stats.token_requests += 1;
+
+		match &result {
+			Ok(_) => stats.token_successes += 1,
+			Err(GetTokenError::AccountDoesNotExist) => {
+				stats.token_failures.account_does_not_exist += 1
+			}
+			Err(GetTokenError::IncorrectPassword) => stats.token_failures.incorrect_password += 1,
+			Err(GetTokenError::Internal(_)) => stats.token_failures.internal += 1,
+		}
+	});
+
+	result
+}
+
+async fn issue_token(
+	database: &PgPool,
+	GetToken { email, password }: GetToken,
 ) -> Result<Token, GetTokenError> {
 	let mut transaction = database.begin().await?;
 
@@ -103,13 +135,14 @@ impl<E: InternalError> From<E> for GetTokenError {
 
 impl IntoResponse for GetTokenError {
 	fn into_response(self) -> Response {
+		use crate::request_id::CurrentRequestId;
 		use log::error;
 
 		match self {
 			GetTokenError::AccountDoesNotExist => (StatusCode::NOT_FOUND, "Account does not exist"),
 			GetTokenError::IncorrectPassword => (StatusCode::UNAUTHORIZED, "Incorrect Password"),
 			GetTokenError::Internal(error) => {
-				error!("{error}");
+				error!("[{CurrentRequestId}] {error}");
 				(
 					StatusCode::INTERNAL_SERVER_ERROR,
 					"Internal / Unknown Error",
@@ -120,45 +153,264 @@ impl IntoResponse for GetTokenError {
 	}
 }
 
+/// How long a queued player is kept in the queue without asking again, see [`QueuePosition::retry_after`]. Given to
+/// Postgres as an interval.
+const QUEUE_TIMEOUT: &str = "30 seconds";
+
+/// Longest a queued player is told to wait before asking again, well within [`QUEUE_TIMEOUT`].
+const MAX_RETRY_AFTER: u32 = 10;
+
+#[derive(Deserialize)]
+struct ConnectOptions {
+	/// Waits in the queue while the sector is full, rather than being turned away.
+	#[serde(default)]
+	queue: bool,
+}
+
 #[debug_handler]
 async fn connect(
-	State(Gateway { database, cl_args }): State<Gateway>,
-	Authenticated(id): Authenticated,
-) -> Result<Json<ConnectionInfo>, ConnectError> {
+	State(Gateway {
+		database,
+		cl_args,
+		stats,
+		..
+	}): State<Gateway>,
+	Authenticated(id, _): Authenticated,
+	Query(ConnectOptions { queue }): Query<ConnectOptions>,
+) -> Result<ConnectResponse, ConnectError> {
+	stats.record(|stats| {
+		*stats
+			.connect_requests
+			.entry(cl_args.sector.as_str().into())
+			.or_default() += 1
+	});
+
+	let mut transaction = database.begin().await?;
+
+	if let Some(position) = admit(&mut transaction, &cl_args.sector, id, queue).await? {
+		transaction.commit().await?;
+
+		debug!(
+			player_id:% = id;
+			"[{CurrentRequestId}] Player {id} is number {} in the queue for {}",
+			position.position, cl_args.sector
+		);
+
+		return Ok(ConnectResponse::Queued(position));
+	}
+
 	// Generate Encryption Key
 	let key = ChaCha20Poly1305::generate_key(&mut OsRng);
 
+	// Keys are identified by their own id in logs, as the key itself needs to stay secret
+	let key_id = Id::new();
+
 	// Send Key to Sector Server through Channel
 	// Currently, sector servers just create a channel with the same name as the sector
 	// This is fine for now, but will need to be improved when we implement proper support for multiple sectors
 	let allow_connection = AllowConnection {
 		id,
+		key_id,
 		key: key.into(),
 	};
 	let message = serde_json::to_string(&allow_connection).unwrap();
+
+	// Also recorded in the database, in case the sector server isn't listening for the notification right now. The
+	// player has a minute to use the key before the sector server discards it.
+	query!(
+		"INSERT INTO pending_connections(key_id, sector, player_id, key, expires)
+			VALUES ($1, $2, $3, $4, NOW() + INTERVAL '1 minute')",
+		key_id as _,
+		cl_args.sector,
+		id as _,
+		key.as_slice(),
+	)
+	.execute(&mut *transaction)
+	.await?;
+
+	// Notifications are only sent once the transaction commits, so the row will always exist by the time it's received
 	query!(
 		"SELECT pg_notify(channel, message) FROM (VALUES ($1, $2)) notifies(channel, message)",
 		cl_args.sector,
 		message,
 	)
-	.execute(&database)
+	.execute(&mut *transaction)
 	.await?;
 
+	transaction.commit().await?;
+
+	info!(
+		key_id:% = key_id, player_id:% = id;
+		"[{CurrentRequestId}] Allowed player {id} to connect to {} with key {key_id}",
+		cl_args.sector
+	);
+
 	// Respond with Connection Info
-	Ok(Json(ConnectionInfo {
+	Ok(ConnectResponse::Admitted(ConnectionInfo {
 		key: key.into(),
 		address: cl_args.sector_address.clone(),
 	}))
 }
 
+/// Decides whether `player` can be let into `sector` now, going by the sector's last [`SectorStatus`]. Players are let
+/// in while the sector has room for them and everyone queued ahead of them. Otherwise they're queued if `queue` is set,
+/// and their position is returned, or turned away if it isn't.
+///
+/// The sector's status row is locked until `transaction` ends, so that two players can't both take the last place.
+async fn admit(
+	transaction: &mut Transaction<'_, Postgres>,
+	sector: &str,
+	player: Id,
+	queue: bool,
+) -> Result<Option<QueuePosition>, ConnectError> {
+	let status = query!(
+		r#"SELECT players, capacity, EXTRACT(EPOCH FROM NOW() - reported)::Float8 AS "age!"
+			FROM sector_status WHERE sector = $1 FOR UPDATE"#,
+		sector
+	)
+	.fetch_optional(&mut **transaction)
+	.await?;
+
+	// The database's clock may be a little ahead of the sector's, which would give a negative age
+	let status = status
+		.filter(|row| {
+			Duration::try_from_secs_f64(row.age).unwrap_or_default() <= SectorStatus::STALE_AFTER
+		})
+		.map(|row| SectorStatus {
+			players: row.players as u32,
+			capacity: row.capacity as u32,
+		});
+
+	// Without a recent report there's no knowing whether the sector is full, but the sector still turns players away
+	// itself if it is
+	let Some(mut status) = status.filter(|status| status.capacity != 0) else {
+		return Ok(None);
+	};
+
+	// Keys which haven't been used yet are players on their way in, which the sector hasn't counted yet
+	let arriving = query_scalar!(
+		r#"SELECT COUNT(*) AS "count!" FROM pending_connections
+			WHERE sector = $1 AND key IS NOT NULL AND expires > NOW()"#,
+		sector
+	)
+	.fetch_one(&mut **transaction)
+	.await?;
+
+	status.players = status.players.saturating_add(arriving as u32);
+
+	query!(
+		"DELETE FROM pending_connections WHERE sector = $1 AND key IS NULL AND expires < NOW()",
+		sector
+	)
+	.execute(&mut **transaction)
+	.await?;
+
+	// Players not already queued go to the back of the queue
+	let ahead = query_scalar!(
+		r#"SELECT COUNT(*) AS "count!" FROM pending_connections
+			WHERE sector = $1 AND key IS NULL AND player_id != $2 AND queued < COALESCE(
+				(SELECT queued FROM pending_connections WHERE sector = $1 AND key IS NULL AND player_id = $2),
+				'infinity'
+			)"#,
+		sector,
+		player as _
+	)
+	.fetch_one(&mut **transaction)
+	.await? as u32;
+
+	let Some(position) = place(status, ahead, queue)? else {
+		query!(
+			"DELETE FROM pending_connections WHERE sector = $1 AND key IS NULL AND player_id = $2",
+			sector,
+			player as _
+		)
+		.execute(&mut **transaction)
+		.await?;
+
+		return Ok(None);
+	};
+
+	query!(
+		"INSERT INTO pending_connections(key_id, sector, player_id, key, expires)
+			VALUES ($1, $2, $3, NULL, NOW() + $4::Text::Interval)
+			ON CONFLICT (sector, player_id) WHERE key IS NULL DO UPDATE SET expires = EXCLUDED.expires",
+		Id::new() as _,
+		sector,
+		player as _,
+		QUEUE_TIMEOUT,
+	)
+	.execute(&mut **transaction)
+	.await?;
+
+	Ok(Some(position))
+}
+
+/// The part of [`admit`] which doesn't need the database, with `ahead` players queued ahead of this one. Returns
+/// [`None`] if they can be let in.
+fn place(
+	status: SectorStatus,
+	ahead: u32,
+	queue: bool,
+) -> Result<Option<QueuePosition>, ConnectError> {
+	if ahead < status.room().unwrap_or(u32::MAX) {
+		return Ok(None);
+	}
+
+	if !queue {
+		return Err(ConnectError::SectorFull {
+			players: status.players,
+			capacity: status.capacity,
+		});
+	}
+
+	let position = ahead + 1;
+
+	Ok(Some(QueuePosition {
+		position,
+		players: status.players,
+		capacity: status.capacity,
+		// Players further back won't be let in any time soon, so they needn't ask as often
+		retry_after: (1 + position).min(MAX_RETRY_AFTER),
+	}))
+}
+
+enum ConnectResponse {
+	Admitted(ConnectionInfo),
+	Queued(QueuePosition),
+}
+
+impl IntoResponse for ConnectResponse {
+	fn into_response(self) -> Response {
+		match self {
+			ConnectResponse::Admitted(info) => Json(info).into_response(),
+			ConnectResponse::Queued(position) => {
+				(StatusCode::ACCEPTED, Json(position)).into_response()
+			}
+		}
+	}
+}
+
 #[derive(Serialize)]
 struct ConnectionInfo {
 	key: [u8; 32],
 	address: String,
 }
 
+#[derive(Debug, PartialEq, Serialize)]
+struct QueuePosition {
+	/// 1 for the next player to be let in.
+	position: u32,
+	players: u32,
+	capacity: u32,
+	/// Seconds to wait before asking again, the player is dropped from the queue if they don't.
+	retry_after: u32,
+}
+
 #[derive(Debug, Error)]
 enum ConnectError {
+	#[error("Sector full ({players}/{capacity} players)")]
+	SectorFull { players: u32, capacity: u32 },
+
 	#[error(transparent)]
 	Internal(#[from] anyhow::Error),
 }
@@ -171,23 +423,183 @@ impl<E: InternalError> From<E> for ConnectError {
 
 impl IntoResponse for ConnectError {
 	fn into_response(self) -> Response {
+		use crate::request_id::CurrentRequestId;
 		use log::error;
 
 		match self {
+			ConnectError::SectorFull { .. } => {
+				(StatusCode::SERVICE_UNAVAILABLE, self.to_string()).into_response()
+			}
 			ConnectError::Internal(error) => {
-				error!("{error}");
+				error!("[{CurrentRequestId}] {error}");
 				(
 					StatusCode::INTERNAL_SERVER_ERROR,
 					"Internal / Unknown Error",
 				)
+					.into_response()
 			}
 		}
-		.into_response()
 	}
 }
 
+pub fn paths() -> Value {
+	json!({
+		"/api/dev/token": {
+			"get": {
+				"summary": "Logs in, creating a new session",
+				"parameters": [
+					parameter("query", "email", "string", true, "Email address of the account"),
+					parameter("query", "password", "string", true, "Password of the account"),
+				],
+				"responses": {
+					"200": text("The new session's token, as 32 hex digits"),
+					"400": text("Email address is invalid, or a parameter is missing"),
+					"401": text(&GetTokenError::IncorrectPassword.to_string()),
+					"404": text(&GetTokenError::AccountDoesNotExist.to_string()),
+					"500": internal_error(),
+				},
+			},
+		},
+		"/api/dev/connect": {
+			"get": {
+				"summary": "Allows the player to connect to the sector",
+				"security": [{ "token": [] }],
+				"parameters": [
+					parameter(
+						"query",
+						"queue",
+						"boolean",
+						false,
+						"Waits in the queue while the sector is full, rather than being turned away"
+					),
+				],
+				"responses": {
+					"200": {
+						"description": "Sector address, and a key valid for a minute",
+						"content": {
+							"application/json": {
+								"schema": {
+									"type": "object",
+									"required": ["key", "address"],
+									"properties": {
+										"key": {
+											"type": "array",
+											"items": {
+												"type": "integer",
+												"minimum": 0,
+												"maximum": 255,
+											},
+											"minItems": 32,
+											"maxItems": 32,
+										},
+										"address": { "type": "string" },
+									},
+								},
+							},
+						},
+					},
+					"202": {
+						"description": "The sector is full and the player is queued, they must ask again within \
+							`retry_after` seconds to keep their place",
+						"content": {
+							"application/json": {
+								"schema": {
+									"type": "object",
+									"required": ["position", "players", "capacity", "retry_after"],
+									"properties": {
+										"position": {
+											"type": "integer",
+											"description": "1 for the next player to be let in",
+										},
+										"players": { "type": "integer" },
+										"capacity": { "type": "integer" },
+										"retry_after": { "type": "integer" },
+									},
+								},
+							},
+						},
+					},
+					"401": unauthorized(),
+					"500": internal_error(),
+					"503": text("Sector full, with the current and maximum player count, only without `queue`"),
+				},
+			},
+		},
+	})
+}
+
 pub fn router() -> Router<Gateway> {
 	Router::new()
 		.route("/token", get(token))
 		.route("/connect", get(connect))
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn status(players: u32, capacity: u32) -> SectorStatus {
+		SectorStatus { players, capacity }
+	}
+
+	#[test]
+	fn players_are_let_in_while_there_is_room() {
+		assert!(place(status(3, 4), 0, false).unwrap().is_none());
+		assert!(place(status(100, 0), 50, false).unwrap().is_none());
+	}
+
+	#[test]
+	fn full_sectors_turn_players_away_without_queueing() {
+		let Err(error) = place(status(4, 4), 0, false) else {
+			panic!("the sector is full");
+		};
+
+		assert!(matches!(
+			error,
+			ConnectError::SectorFull {
+				players: 4,
+				capacity: 4
+			}
+		));
+		assert_eq!(
+			error.into_response().status(),
+			StatusCode::SERVICE_UNAVAILABLE
+		);
+
+		// Arriving players can push the count past the capacity
+		assert!(place(status(6, 4), 0, false).is_err());
+	}
+
+	#[test]
+	fn full_sectors_queue_players() {
+		assert_eq!(
+			place(status(4, 4), 0, true).unwrap(),
+			Some(QueuePosition {
+				position: 1,
+				players: 4,
+				capacity: 4,
+				retry_after: 2,
+			})
+		);
+
+		let position = place(status(4, 4), 20, true).unwrap().unwrap();
+		assert_eq!(position.position, 21);
+		assert_eq!(position.retry_after, MAX_RETRY_AFTER);
+	}
+
+	#[test]
+	fn queued_players_are_let_in_in_order() {
+		// One place opens up, so the first in the queue gets it and the rest wait
+		assert!(place(status(3, 4), 0, true).unwrap().is_none());
+		assert_eq!(place(status(3, 4), 1, true).unwrap().unwrap().position, 2);
+
+		// Then everyone left moves up once the next place opens
+		assert!(place(status(2, 4), 1, true).unwrap().is_none());
+	}
+
+	#[test]
+	fn queued_players_are_told_to_ask_again() {
+		let response = ConnectResponse::Queued(place(status(1, 1), 0, true).unwrap().unwrap());
+		assert_eq!(response.into_response().status(), StatusCode::ACCEPTED);
+	}
+}
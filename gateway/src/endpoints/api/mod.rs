@@ -1,8 +1,15 @@
 use crate::Gateway;
 use axum::Router;
 
+mod audit;
 mod dev;
+pub mod openapi;
+mod sessions;
 
 pub fn router() -> Router<Gateway> {
-	Router::new().nest("/dev", dev::router())
+	Router::new()
+		.nest("/audit", audit::router())
+		.nest("/dev", dev::router())
+		.nest("/sessions", sessions::router())
+		.merge(openapi::router())
 }
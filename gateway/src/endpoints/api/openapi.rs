@@ -0,0 +1,206 @@
+//! Describes the gateway's routes as an OpenAPI document. Each endpoint module describes it's own routes next to
+//! their handlers, response descriptions for errors come from the error's own message so that the two can't disagree.
+
+use super::{audit, dev, sessions};
+use crate::{endpoints::web, extractors::AuthenticationError, Gateway};
+use axum::{
+	debug_handler,
+	http::{HeaderMap, HeaderValue},
+	response::IntoResponse,
+	routing::get,
+	Json, Router,
+};
+use serde_json::{json, Map, Value};
+
+/// The whole document, paths from every module are merged together.
+pub fn document() -> Value {
+	let mut paths = Map::new();
+
+	for module_paths in [
+		audit::paths(),
+		dev::paths(),
+		sessions::paths(),
+		web::paths(),
+		self::paths(),
+	] {
+		if let Value::Object(module_paths) = module_paths {
+			paths.extend(module_paths);
+		}
+	}
+
+	json!({
+		"openapi": "3.1.0",
+		"info": {
+			"title": "Solarscape Gateway",
+			"version": env!("CARGO_PKG_VERSION"),
+		},
+		"paths": paths,
+		"components": {
+			"securitySchemes": {
+				"token": {
+					"type": "apiKey",
+					"in": "header",
+					"name": "Authorization",
+					"description": "A token from `/api/dev/token`, as 32 hex digits.",
+				},
+			},
+			"responses": {
+				"Unauthorized": text(&AuthenticationError::Unauthorized.to_string()),
+				"InternalError": text("Internal / Unknown Error"),
+			},
+		},
+	})
+}
+
+/// A response with a plain text body.
+pub fn text(description: &str) -> Value {
+	json!({
+		"description": description,
+		"content": { "text/plain": { "schema": { "type": "string" } } },
+	})
+}
+
+/// A response with a plain text body, which is one of `messages`.
+pub fn text_one_of(messages: &[String]) -> Value {
+	json!({
+		"description": messages.join(", or "),
+		"content": {
+			"text/plain": { "schema": { "type": "string", "enum": messages } },
+		},
+	})
+}
+
+pub fn unauthorized() -> Value {
+	json!({ "$ref": "#/components/responses/Unauthorized" })
+}
+
+pub fn internal_error() -> Value {
+	json!({ "$ref": "#/components/responses/InternalError" })
+}
+
+/// A parameter, `location` is `query`, `path` or `header`, and `typ` is a JSON schema type.
+pub fn parameter(
+	location: &str,
+	name: &str,
+	typ: &str,
+	required: bool,
+	description: &str,
+) -> Value {
+	json!({
+		"in": location,
+		"name": name,
+		"required": required,
+		"description": description,
+		"schema": { "type": typ },
+	})
+}
+
+#[debug_handler]
+async fn openapi() -> Json<Value> {
+	Json(document())
+}
+
+#[debug_handler]
+async fn docs() -> impl IntoResponse {
+	let mut html_header_map = HeaderMap::new();
+	html_header_map.append(
+		"Content-Type",
+		HeaderValue::from_static("text/html;charset=utf-8"),
+	);
+
+	(html_header_map, include_str!("docs.html"))
+}
+
+fn paths() -> Value {
+	json!({
+		"/api/openapi.json": {
+			"get": {
+				"summary": "This document",
+				"responses": {
+					"200": {
+						"description": "OpenAPI 3.1 document",
+						"content": { "application/json": {} },
+					},
+				},
+			},
+		},
+		"/api/docs": {
+			"get": {
+				"summary": "Readable view of this document",
+				"responses": {
+					"200": { "description": "HTML page", "content": { "text/html": {} } },
+				},
+			},
+		},
+	})
+}
+
+pub fn router() -> Router<Gateway> {
+	Router::new()
+		.route("/openapi.json", get(openapi))
+		.route("/docs", get(docs))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::tests::gateway;
+	use std::collections::BTreeSet;
+
+	/// The path of every route `router` has, written the way the document writes them. axum doesn't have a way to list
+	/// them, but it's debug output has them all, nested routes included. A nested router's `/` is served both with and
+	/// without a trailing slash, the document only has it without.
+	fn routes(router: &Router) -> BTreeSet<String> {
+		let debug = format!("{router:?}");
+
+		// The path router comes first, so these are it's paths rather than the fallback router's
+		let (_, paths) = debug
+			.split_once("paths: {")
+			.expect("router's debug output should list it's paths");
+		let (paths, _) = paths.split_once('}').unwrap();
+
+		paths
+			.split(", ")
+			.map(|entry| {
+				let (_, path) = entry.split_once(": ").unwrap();
+
+				let path = path.trim_matches('"');
+
+				path.strip_suffix('/')
+					.filter(|path| !path.is_empty())
+					.unwrap_or(path)
+					.split('/')
+					.map(|segment| match segment.strip_prefix(':') {
+						Some(parameter) => format!("{{{parameter}}}"),
+						None => segment.to_owned(),
+					})
+					.collect::<Vec<_>>()
+					.join("/")
+			})
+			.collect()
+	}
+
+	#[tokio::test]
+	async fn document_has_every_route() {
+		let routes = routes(&crate::router(gateway(&[])));
+
+		let document = document();
+		let documented = document["paths"]
+			.as_object()
+			.unwrap()
+			.keys()
+			.cloned()
+			.collect::<BTreeSet<_>>();
+
+		assert!(routes.contains("/api/sessions/{prefix}"), "{routes:?}");
+
+		let undocumented = routes.difference(&documented).collect::<Vec<_>>();
+		assert!(
+			undocumented.is_empty(),
+			"not in the document: {undocumented:?}"
+		);
+
+		let missing = documented.difference(&routes).collect::<Vec<_>>();
+		assert!(missing.is_empty(), "documented but not routed: {missing:?}");
+	}
+}
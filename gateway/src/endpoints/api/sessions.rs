@@ -0,0 +1,381 @@
+use crate::{
+	endpoints::api::openapi::{internal_error, parameter, text, text_one_of, unauthorized},
+	extractors::Authenticated,
+	request_id::CurrentRequestId,
+	types::{InternalError, Token},
+	Gateway,
+};
+use axum::{
+	debug_handler,
+	extract::{Path, Query, State},
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	routing::{delete, get},
+	Json, Router,
+};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::query;
+use thiserror::Error;
+
+/// How many characters of a token are shown to identify it, enough to tell a player's sessions apart without being
+/// usable as the token itself.
+const PREFIX_LENGTH: usize = 8;
+
+/// Shortest prefix accepted when revoking a session.
+const MIN_PREFIX_LENGTH: usize = 4;
+
+#[derive(Serialize)]
+struct SessionInfo {
+	prefix: String,
+	/// Seconds since the unix epoch.
+	created: i64,
+	/// Seconds since the unix epoch, only updated about once a minute.
+	last_used: i64,
+	/// Whether this is the token the request was made with.
+	current: bool,
+}
+
+/// Lists the player's valid tokens, most recently used first. Only a prefix of each token is given out, so that this
+/// can't be used to obtain another session's token.
+#[debug_handler]
+async fn list(
+	State(Gateway { database, .. }): State<Gateway>,
+	Authenticated(id, current): Authenticated,
+) -> Result<Json<Vec<SessionInfo>>, ListSessionsError> {
+	let sessions = query!(
+		r#"SELECT
+			token AS "token: Token",
+			EXTRACT(EPOCH FROM created)::Int8 AS "created!",
+			EXTRACT(EPOCH FROM used)::Int8 AS "last_used!"
+		FROM tokens WHERE player_id = $1 AND valid = true
+		ORDER BY used DESC"#,
+		id as _
+	)
+	.fetch_all(&database)
+	.await?
+	.into_iter()
+	.map(|session| SessionInfo {
+		prefix: session.token.to_string()[..PREFIX_LENGTH].to_string(),
+		created: session.created,
+		last_used: session.last_used,
+		current: session.token == current,
+	})
+	.collect();
+
+	Ok(Json(sessions))
+}
+
+#[derive(Debug, Error)]
+enum ListSessionsError {
+	#[error(transparent)]
+	Internal(#[from] anyhow::Error),
+}
+
+impl<E: InternalError> From<E> for ListSessionsError {
+	fn from(value: E) -> Self {
+		Self::Internal(value.into())
+	}
+}
+
+impl IntoResponse for ListSessionsError {
+	fn into_response(self) -> Response {
+		use log::error;
+
+		match self {
+			ListSessionsError::Internal(error) => {
+				error!("[{CurrentRequestId}] {error}");
+				(
+					StatusCode::INTERNAL_SERVER_ERROR,
+					"Internal / Unknown Error",
+				)
+			}
+		}
+		.into_response()
+	}
+}
+
+#[derive(Deserialize)]
+struct RevokeOptions {
+	/// Revoking the token the request is made with logs the player out, so it has to be asked for explicitly.
+	#[serde(default)]
+	confirm_current: bool,
+}
+
+/// Revokes the player's token starting with `prefix`. Tokens belonging to other players are never matched, so
+/// guessing prefixes can't be used to log anyone else out.
+#[debug_handler]
+async fn revoke(
+	State(Gateway { database, .. }): State<Gateway>,
+	Authenticated(id, current): Authenticated,
+	Path(prefix): Path<String>,
+	Query(RevokeOptions { confirm_current }): Query<RevokeOptions>,
+) -> Result<StatusCode, RevokeSessionError> {
+	check_prefix(&prefix)?;
+
+	let tokens = query!(
+		r#"SELECT token AS "token: Token" FROM tokens WHERE player_id = $1 AND valid = true"#,
+		id as _
+	)
+	.fetch_all(&database)
+	.await?;
+
+	let token = match_prefix(tokens.into_iter().map(|row| row.token), &prefix)?;
+	check_current(token, current, confirm_current)?;
+
+	query!(
+		"DELETE FROM tokens WHERE token = $1 AND player_id = $2",
+		token as _,
+		id as _
+	)
+	.execute(&database)
+	.await?;
+
+	info!(player_id:% = id; "[{CurrentRequestId}] Player {id} revoked a session");
+
+	Ok(StatusCode::NO_CONTENT)
+}
+
+/// Rejects anything that isn't part of a token's hex form, before the database is asked.
+fn check_prefix(prefix: &str) -> Result<(), RevokeSessionError> {
+	match (MIN_PREFIX_LENGTH..=32).contains(&prefix.len())
+		&& prefix
+			.chars()
+			.all(|character| matches!(character, '0'..='9' | 'a'..='f'))
+	{
+		true => Ok(()),
+		false => Err(RevokeSessionError::InvalidPrefix),
+	}
+}
+
+/// Refuses to revoke the token the request was made with, unless `confirm_current` is set.
+fn check_current(
+	token: Token,
+	current: Token,
+	confirm_current: bool,
+) -> Result<(), RevokeSessionError> {
+	match token == current && !confirm_current {
+		true => Err(RevokeSessionError::CurrentSession),
+		false => Ok(()),
+	}
+}
+
+/// Finds the one token starting with `prefix`.
+fn match_prefix(
+	tokens: impl Iterator<Item = Token>,
+	prefix: &str,
+) -> Result<Token, RevokeSessionError> {
+	let mut matches = tokens.filter(|token| token.to_string().starts_with(prefix));
+
+	match (matches.next(), matches.next()) {
+		(Some(token), None) => Ok(token),
+		(Some(_), Some(_)) => Err(RevokeSessionError::Ambiguous),
+		(None, _) => Err(RevokeSessionError::NotFound),
+	}
+}
+
+#[derive(Debug, Error)]
+enum RevokeSessionError {
+	#[error("Session prefix must be {MIN_PREFIX_LENGTH} to 32 lowercase hex characters")]
+	InvalidPrefix,
+
+	#[error("No session with that prefix")]
+	NotFound,
+
+	#[error("More than one session has that prefix, use a longer one")]
+	Ambiguous,
+
+	#[error("That is the current session, set confirm_current to revoke it anyway")]
+	CurrentSession,
+
+	#[error(transparent)]
+	Internal(#[from] anyhow::Error),
+}
+
+impl<E: InternalError> From<E> for RevokeSessionError {
+	fn from(value: E) -> Self {
+		Self::Internal(value.into())
+	}
+}
+
+impl IntoResponse for RevokeSessionError {
+	fn into_response(self) -> Response {
+		use log::error;
+
+		let status = match &self {
+			RevokeSessionError::InvalidPrefix => StatusCode::BAD_REQUEST,
+			RevokeSessionError::NotFound => StatusCode::NOT_FOUND,
+			RevokeSessionError::Ambiguous | RevokeSessionError::CurrentSession => {
+				StatusCode::CONFLICT
+			}
+			RevokeSessionError::Internal(error) => {
+				error!("[{CurrentRequestId}] {error}");
+				return (
+					StatusCode::INTERNAL_SERVER_ERROR,
+					"Internal / Unknown Error",
+				)
+					.into_response();
+			}
+		};
+
+		(status, self.to_string()).into_response()
+	}
+}
+
+pub fn paths() -> Value {
+	let session_info = json!({
+		"type": "object",
+		"required": ["prefix", "created", "last_used", "current"],
+		"properties": {
+			"prefix": {
+				"type": "string",
+				"description": format!("First {PREFIX_LENGTH} hex digits of the session's token"),
+			},
+			"created": {
+				"type": "integer",
+				"description": "Seconds since the unix epoch",
+			},
+			"last_used": {
+				"type": "integer",
+				"description": "Seconds since the unix epoch, only updated about once a minute",
+			},
+			"current": {
+				"type": "boolean",
+				"description": "Whether this is the session the request was made with",
+			},
+		},
+	});
+
+	let prefix = format!("{MIN_PREFIX_LENGTH} to 32 lowercase hex digits");
+	let confirm_current = "Must be set to revoke the session the request is made with";
+
+	json!({
+		"/api/sessions": {
+			"get": {
+				"summary": "Lists the player's sessions, most recently used first",
+				"security": [{ "token": [] }],
+				"responses": {
+					"200": {
+						"description": "The player's sessions",
+						"content": {
+							"application/json": {
+								"schema": { "type": "array", "items": session_info },
+							},
+						},
+					},
+					"401": unauthorized(),
+					"500": internal_error(),
+				},
+			},
+		},
+		"/api/sessions/{prefix}": {
+			"delete": {
+				"summary": "Revokes the player's session whose token starts with the prefix",
+				"security": [{ "token": [] }],
+				"parameters": [
+					parameter("path", "prefix", "string", true, &prefix),
+					parameter("query", "confirm_current", "boolean", false, confirm_current),
+				],
+				"responses": {
+					"204": { "description": "Session revoked" },
+					"400": text(&RevokeSessionError::InvalidPrefix.to_string()),
+					"401": unauthorized(),
+					"404": text(&RevokeSessionError::NotFound.to_string()),
+					"409": text_one_of(&[
+						RevokeSessionError::Ambiguous.to_string(),
+						RevokeSessionError::CurrentSession.to_string(),
+					]),
+					"500": internal_error(),
+				},
+			},
+		},
+	})
+}
+
+pub fn router() -> Router<Gateway> {
+	Router::new()
+		.route("/", get(list))
+		.route("/:prefix", delete(revoke))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tokens() -> [Token; 3] {
+		[
+			"0123456789abcdef0123456789abcdef".into(),
+			"0123ffffffffffffffffffffffffffff".into(),
+			"fedcba9876543210fedcba9876543210".into(),
+		]
+	}
+
+	#[test]
+	fn prefixes_are_4_to_32_lowercase_hex_digits() {
+		for prefix in ["0123", "fedcba98", "0123456789abcdef0123456789abcdef"] {
+			assert!(check_prefix(prefix).is_ok(), "{prefix}");
+		}
+
+		for prefix in [
+			"",
+			"012",
+			"0123456789abcdef0123456789abcdef0",
+			"0123ABCD",
+			"0123456g",
+			"01 23",
+		] {
+			assert!(
+				matches!(check_prefix(prefix), Err(RevokeSessionError::InvalidPrefix)),
+				"{prefix}"
+			);
+		}
+	}
+
+	#[test]
+	fn prefixes_must_match_exactly_one_token() {
+		let [a, b, c] = tokens();
+
+		assert_eq!(match_prefix(tokens().into_iter(), "01234").unwrap(), a);
+		assert_eq!(match_prefix(tokens().into_iter(), "0123f").unwrap(), b);
+		assert_eq!(
+			match_prefix(tokens().into_iter(), &c.to_string()).unwrap(),
+			c
+		);
+
+		assert!(matches!(
+			match_prefix(tokens().into_iter(), "0123"),
+			Err(RevokeSessionError::Ambiguous)
+		));
+		assert!(matches!(
+			match_prefix(tokens().into_iter(), "aaaa"),
+			Err(RevokeSessionError::NotFound)
+		));
+		assert!(matches!(
+			match_prefix([].into_iter(), "0123"),
+			Err(RevokeSessionError::NotFound)
+		));
+	}
+
+	#[test]
+	fn the_current_session_needs_confirming() {
+		let [a, b, _] = tokens();
+
+		assert!(matches!(
+			check_current(a, a, false),
+			Err(RevokeSessionError::CurrentSession)
+		));
+		assert!(check_current(a, a, true).is_ok());
+		assert!(check_current(a, b, false).is_ok());
+	}
+
+	#[test]
+	fn confirming_is_off_unless_asked_for() {
+		let options: Query<RevokeOptions> = Query::try_from_uri(&"/?".parse().unwrap()).unwrap();
+		assert!(!options.confirm_current);
+
+		let options: Query<RevokeOptions> =
+			Query::try_from_uri(&"/?confirm_current=true".parse().unwrap()).unwrap();
+		assert!(options.confirm_current);
+	}
+}
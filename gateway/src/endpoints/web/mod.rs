@@ -1,4 +1,6 @@
 use crate::{
+	endpoints::api::openapi::{parameter, text},
+	password_policy::PasswordViolation,
 	types::{Email, InternalError, Username},
 	Gateway, ARGON_2,
 };
@@ -15,6 +17,7 @@ use axum::{
 	Router,
 };
 use serde::Deserialize;
+use serde_json::{json, Value};
 use solarscape_shared::data::Id;
 use sqlx::{error::ErrorKind::UniqueViolation, query, Error::Database};
 use thiserror::Error;
@@ -28,13 +31,19 @@ struct CreateAccount {
 
 #[debug_handler]
 async fn create_account(
-	State(Gateway { database, .. }): State<Gateway>,
+	State(Gateway {
+		database,
+		password_policy,
+		..
+	}): State<Gateway>,
 	Query(CreateAccount {
 		username,
 		email,
 		password,
 	}): Query<CreateAccount>,
 ) -> Result<&'static str, CreateAccountError> {
+	password_policy.check(&password, &username, &email).await?;
+
 	let salt = SaltString::generate(&mut OsRng);
 	let password = ARGON_2
 		.hash_password(password.as_bytes(), &salt)?
@@ -76,6 +85,9 @@ enum CreateAccountError {
 	#[error("Account Exists!")]
 	AccountExists,
 
+	#[error(transparent)]
+	Password(#[from] PasswordViolation),
+
 	#[error(transparent)]
 	Internal(#[from] anyhow::Error),
 }
@@ -88,6 +100,7 @@ impl<E: InternalError> From<E> for CreateAccountError {
 
 impl IntoResponse for CreateAccountError {
 	fn into_response(self) -> Response {
+		use crate::request_id::CurrentRequestId;
 		use log::error;
 
 		match self {
@@ -95,8 +108,19 @@ impl IntoResponse for CreateAccountError {
 				StatusCode::CONFLICT,
 				r#"<p style="color:red">Account Exists!</p>"#,
 			),
+			// The reason code lets scripts tell violations apart without matching on the message
+			CreateAccountError::Password(violation) => {
+				return (
+					StatusCode::UNPROCESSABLE_ENTITY,
+					format!(
+						r#"<p style="color:red" data-reason="{}">{violation}</p>"#,
+						violation.code()
+					),
+				)
+					.into_response()
+			}
 			CreateAccountError::Internal(error) => {
-				error!("{error}");
+				error!("[{CurrentRequestId}] {error}");
 				(
 					StatusCode::INTERNAL_SERVER_ERROR,
 					r#"<p style="color:red">Internal / Unknown Error!</p>"#,
@@ -130,6 +154,54 @@ async fn htmx() -> impl IntoResponse {
 	(js_header_map, include_str!("htmx-2.0.2.min.js"))
 }
 
+pub fn paths() -> Value {
+	let html =
+		|description: &str| json!({ "description": description, "content": { "text/html": {} } });
+
+	let reasons = [
+		PasswordViolation::TooShort { minimum: 0 },
+		PasswordViolation::MatchesUsername,
+		PasswordViolation::MatchesEmail,
+		PasswordViolation::Breached,
+	]
+	.map(|violation| format!("`{}`", violation.code()))
+	.join(", ");
+
+	json!({
+		"/web/index.html": {
+			"get": {
+				"summary": "Account creation page",
+				"responses": { "200": html("HTML page") },
+			},
+		},
+		"/web/htmx-2.0.2.min.js": {
+			"get": {
+				"summary": "htmx, used by the account creation page",
+				"responses": {
+					"200": { "description": "JavaScript", "content": { "text/javascript": {} } },
+				},
+			},
+		},
+		"/web/create_account": {
+			"get": {
+				"summary": "Creates an account, responding with a message for the page to show",
+				"parameters": [
+					parameter("query", "username", "string", true, "1 to 32 of 0-9A-Za-z_"),
+					parameter("query", "email", "string", true, "Email address, not yet verified"),
+					parameter("query", "password", "string", true, "Must meet the password policy"),
+				],
+				"responses": {
+					"200": html("Account created"),
+					"400": text("Username or email address is invalid, or a parameter is missing"),
+					"409": html(&CreateAccountError::AccountExists.to_string()),
+					"422": html(&format!("Password rejected, `data-reason` is one of {reasons}")),
+					"500": html("Internal / Unknown Error!"),
+				},
+			},
+		},
+	})
+}
+
 pub fn router() -> Router<Gateway> {
 	Router::new()
 		.route("/index.html", get(root))
@@ -9,11 +9,13 @@ use axum::{
 	response::{IntoResponse, Response},
 };
 use solarscape_shared::data::Id;
-use sqlx::{query, query_scalar};
+use sqlx::query;
+use std::time::UNIX_EPOCH;
 use thiserror::Error;
 
+/// The player a request is from, and the token they authenticated with.
 #[derive(Clone, Copy)]
-pub struct Authenticated(pub Id);
+pub struct Authenticated(pub Id, pub Token);
 
 #[async_trait]
 impl FromRequestParts<Gateway> for Authenticated {
@@ -21,7 +23,9 @@ impl FromRequestParts<Gateway> for Authenticated {
 
 	async fn from_request_parts(
 		parts: &mut Parts,
-		Gateway { database, .. }: &Gateway,
+		Gateway {
+			database, clock, ..
+		}: &Gateway,
 	) -> Result<Self, Self::Rejection> {
 		let token: Token = parts
 			.headers
@@ -31,22 +35,39 @@ impl FromRequestParts<Gateway> for Authenticated {
 			.map_err(|_| AuthenticationError::Unauthorized)?
 			.into();
 
-		let id: Id = query_scalar!(
-			r#"SELECT player_id AS "id: Id" FROM tokens WHERE token = $1 AND valid = true"#,
-			token as _
+		let now = clock
+			.now_utc()
+			.duration_since(UNIX_EPOCH)
+			.unwrap_or_default()
+			.as_secs_f64();
+
+		let token_info = query!(
+			r#"SELECT
+				player_id AS "id: Id",
+				used < TO_TIMESTAMP($2) - INTERVAL '1 minute' AS "stale!"
+			FROM tokens WHERE token = $1 AND valid = true"#,
+			token as _,
+			now
 		)
-		.fetch_one(database)
+		.fetch_optional(database)
 		.await?
 		.ok_or(AuthenticationError::Unauthorized)?;
 
-		query!(
-			"UPDATE tokens SET used = DEFAULT WHERE token = $1",
-			token as _
-		)
-		.execute(database)
-		.await?;
+		let id = token_info.id.ok_or(AuthenticationError::Unauthorized)?;
+
+		// Only needs to be accurate enough to show the player when a session was last used, so this isn't written on
+		// every request
+		if token_info.stale {
+			query!(
+				"UPDATE tokens SET used = TO_TIMESTAMP($2) WHERE token = $1",
+				token as _,
+				now
+			)
+			.execute(database)
+			.await?;
+		}
 
-		Ok(Self(id))
+		Ok(Self(id, token))
 	}
 }
 
@@ -67,12 +88,13 @@ impl<E: InternalError> From<E> for AuthenticationError {
 
 impl IntoResponse for AuthenticationError {
 	fn into_response(self) -> Response {
+		use crate::request_id::CurrentRequestId;
 		use log::error;
 
 		match self {
 			AuthenticationError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized"),
 			AuthenticationError::Internal(error) => {
-				error!("{error}");
+				error!("[{CurrentRequestId}] {error}");
 				(StatusCode::INTERNAL_SERVER_ERROR, "Internal Error")
 			}
 		}
@@ -1,22 +1,38 @@
 use crate::endpoints::{api, web};
 use argon2::Argon2;
-use axum::{http::StatusCode, Router};
+use axum::{http::StatusCode, middleware, Router};
 use clap::{Args, Parser};
-use env_logger::Env;
+use client_ip::TrustedProxy;
+use cors::CorsConfig;
 use itertools::Itertools;
-use log::info;
+use log::{error, info};
+use password_policy::{DefaultPasswordPolicy, PasswordPolicy, PwnedPasswords};
+use solarscape_shared::{
+	clock::{self, Clock},
+	data::Id,
+	logging::{self, LogFormat},
+};
 use sqlx::{postgres::PgConnectOptions, PgPool};
+use stats::Stats;
 use std::{
 	fs::read_to_string,
+	io::{self, ErrorKind::NotFound},
 	net::SocketAddr,
-	path::PathBuf,
+	path::{absolute, PathBuf},
+	process::ExitCode,
 	str::FromStr,
 	sync::{Arc, LazyLock},
 	time::Instant,
 };
+use thiserror::Error;
 use tokio::{net::TcpListener, runtime::Runtime};
 
+mod client_ip;
+mod cors;
 mod extractors;
+mod password_policy;
+mod request_id;
+mod stats;
 mod types;
 
 mod endpoints {
@@ -43,6 +59,31 @@ pub struct ClArgs {
 	/// Address of sector to log all players into
 	#[arg(long)]
 	pub sector_address: String,
+
+	/// Format to write logs in
+	#[arg(long, value_enum, default_value_t)]
+	pub log_format: LogFormat,
+
+	/// Minimum number of characters new passwords must have
+	#[arg(long, default_value_t = 10)]
+	pub minimum_password_length: usize,
+
+	/// Rejects new passwords found in Have I Been Pwned's breach list, only the first 5 characters of the password's
+	/// SHA-1 hash are sent
+	#[arg(long)]
+	pub check_breached_passwords: bool,
+
+	/// Player allowed to use admin endpoints, such as reading the audit log, may be given more than once
+	#[arg(long = "admin")]
+	pub admins: Vec<Id>,
+
+	/// Reverse proxies to believe the `Forwarded` and `X-Forwarded-For` headers of, as a comma separated list of
+	/// addresses or networks like `10.0.0.0/8`. Requests from anywhere else are attributed to their socket address
+	#[arg(long, value_delimiter = ',')]
+	pub trusted_proxies: Vec<TrustedProxy>,
+
+	#[command(flatten)]
+	pub cors: CorsConfig,
 }
 
 #[derive(Args, Clone)]
@@ -57,37 +98,89 @@ pub struct PostgreSQL {
 	pub postgres_file: Option<PathBuf>,
 }
 
+impl PostgreSQL {
+	/// Resolves the connection options from either `--postgres` or the file given by `--postgres-file`.
+	pub fn connect_options(&self) -> Result<PgConnectOptions, PostgresConfigError> {
+		if let Some(postgres) = &self.postgres {
+			return Ok(postgres.clone());
+		}
+
+		let path = self
+			.postgres_file
+			.as_ref()
+			.expect("clap should require either --postgres or --postgres-file");
+
+		// Only used for error messages, so if we can't resolve it just show whatever we were given
+		let path = absolute(path).unwrap_or_else(|_| path.clone());
+
+		let url = read_to_string(&path).map_err(|error| match error.kind() {
+			NotFound => PostgresConfigError::NotFound { path: path.clone() },
+			_ => PostgresConfigError::Io {
+				path: path.clone(),
+				source: error,
+			},
+		})?;
+
+		PgConnectOptions::from_str(url.trim())
+			.map_err(|source| PostgresConfigError::Invalid { path, source })
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum PostgresConfigError {
+	#[error("Postgres connection url file does not exist.\n  Looked for: {}", path.display())]
+	NotFound { path: PathBuf },
+
+	#[error("Unable to read Postgres connection url file.\n  File: {}\n  Reason: {source}", path.display())]
+	Io { path: PathBuf, source: io::Error },
+
+	#[error("Postgres connection url file does not contain a valid connection url.\n  File: {}\n  Reason: {source}\n  See: https://docs.rs/sqlx/latest/sqlx/postgres/struct.PgConnectOptions.html", path.display())]
+	Invalid { path: PathBuf, source: sqlx::Error },
+}
+
+impl PostgresConfigError {
+	/// Each kind of failure exits with it's own code so that scripts can tell them apart.
+	pub fn exit_code(&self) -> ExitCode {
+		ExitCode::from(match self {
+			Self::NotFound { .. } => 3,
+			Self::Io { .. } => 4,
+			Self::Invalid { .. } => 5,
+		})
+	}
+}
+
 #[derive(Clone)]
 pub struct Gateway {
 	pub database: PgPool,
 	pub cl_args: Arc<ClArgs>,
+	pub stats: Arc<Stats>,
+	/// Where token expiry checks get the time from.
+	pub clock: Arc<dyn Clock>,
+	pub password_policy: Arc<dyn PasswordPolicy>,
 }
 
-fn main() {
+fn main() -> ExitCode {
 	let start_time = Instant::now();
 
 	let cl_args = ClArgs::parse();
 
-	env_logger::init_from_env(Env::default().default_filter_or(if cfg!(debug_assertions) {
-		"debug"
-	} else {
-		"info"
-	}));
+	logging::init(
+		cl_args.log_format,
+		if cfg!(debug_assertions) {
+			"debug"
+		} else {
+			"info"
+		},
+	);
 	info!("Solarscape (Gateway) v{}", env!("CARGO_PKG_VERSION"));
 
-	let postgres = cl_args.postgres.postgres.clone().unwrap_or_else(|| {
-		let file = cl_args
-			.postgres
-			.postgres_file
-		.as_ref()
-			.expect("file should be Some if url is None");
-
-		PgConnectOptions::from_str(
-			&read_to_string(file)
-				.expect("should exist and be readable")
-		)
-			.expect("file should contain a valid postgres connection url, see: https://docs.rs/sqlx/latest/sqlx/postgres/struct.PgConnectOptions.html")
-	}).application_name("solarscape-gateway");
+	let postgres = match cl_args.postgres.connect_options() {
+		Ok(postgres) => postgres.application_name("solarscape-gateway"),
+		Err(error) => {
+			error!("{error}");
+			return error.exit_code();
+		}
+	};
 
 	let runtime = Runtime::new().expect("failed to start tokio runtime");
 
@@ -99,20 +192,66 @@ fn main() {
 		.block_on(TcpListener::bind(cl_args.address))
 		.expect("failed to bind to socket address");
 
-	let router = Router::new()
-		.nest("/web", web::router())
-		.nest("/api", api::router())
-		.fallback(|| async { StatusCode::NOT_FOUND })
-		.with_state(Gateway {
-			database,
-			cl_args: Arc::new(cl_args),
-		});
+	let stats = Arc::new(Stats::new());
+
+	let password_policy = Arc::new(DefaultPasswordPolicy {
+		minimum_length: cl_args.minimum_password_length,
+		breach_list: match cl_args.check_breached_passwords {
+			true => Some(Box::new(PwnedPasswords::new())),
+			false => None,
+		},
+	});
+
+	runtime.spawn(stats::report(
+		cl_args.address.to_string(),
+		database.clone(),
+		stats.clone(),
+	));
+
+	let gateway = Gateway {
+		database,
+		cl_args: Arc::new(cl_args),
+		stats,
+		clock: clock::system(),
+		password_policy,
+	};
+
+	let router = router(gateway);
 
 	info!("Ready! {:.0?}", Instant::now() - start_time);
 
 	runtime
-		.block_on(async { axum::serve(listener, router).await })
+		.block_on(async {
+			axum::serve(
+				listener,
+				router.into_make_service_with_connect_info::<SocketAddr>(),
+			)
+			.await
+		})
 		.unwrap();
+
+	ExitCode::SUCCESS
+}
+
+/// Everything the gateway serves, along with the middleware every request goes through.
+fn router(gateway: Gateway) -> Router {
+	Router::new()
+		.nest("/web", web::router())
+		.nest(
+			"/api",
+			api::router().layer(middleware::from_fn_with_state(
+				gateway.clone(),
+				cors::middleware,
+			)),
+		)
+		.fallback(|| async { StatusCode::NOT_FOUND })
+		.layer(middleware::from_fn(request_id::middleware))
+		// Outermost, so that the client's address is known by the time anything logs
+		.layer(middleware::from_fn_with_state(
+			gateway.clone(),
+			client_ip::middleware,
+		))
+		.with_state(gateway)
 }
 
 const LOOKUP: [char; 16] = [
@@ -166,3 +305,36 @@ pub fn to_bytes(string: &str) -> Vec<u8> {
 
 	bytes
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	/// A gateway for tests, with the arguments it requires followed by `args`. It's database is never connected to.
+	pub fn gateway(args: &[&str]) -> Gateway {
+		let required = [
+			"solarscape-gateway",
+			"--postgres",
+			"postgres://localhost/solarscape",
+			"--address",
+			"127.0.0.1:0",
+			"--sector",
+			"test",
+			"--sector-address",
+			"127.0.0.1:0",
+		];
+
+		let cl_args = ClArgs::parse_from(required.iter().chain(args));
+
+		Gateway {
+			database: PgPool::connect_lazy_with(cl_args.postgres.connect_options().unwrap()),
+			cl_args: Arc::new(cl_args),
+			stats: Arc::new(Stats::new()),
+			clock: clock::system(),
+			password_policy: Arc::new(DefaultPasswordPolicy {
+				minimum_length: 10,
+				breach_list: None,
+			}),
+		}
+	}
+}
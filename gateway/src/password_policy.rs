@@ -0,0 +1,314 @@
+use crate::{
+	to_string,
+	types::{Email, Username},
+};
+use log::warn;
+use reqwest::Client;
+use sha1::{Digest, Sha1};
+use std::{future::Future, pin::Pin, time::Duration};
+use thiserror::Error;
+
+pub type Check<'a> = Pin<Box<dyn Future<Output = Result<(), PasswordViolation>> + Send + 'a>>;
+pub type Range<'a> = Pin<Box<dyn Future<Output = Result<String, anyhow::Error>> + Send + 'a>>;
+
+/// Decides which passwords new accounts are allowed to use.
+pub trait PasswordPolicy: Send + Sync + 'static {
+	fn check<'a>(
+		&'a self,
+		password: &'a str,
+		username: &'a Username,
+		email: &'a Email,
+	) -> Check<'a>;
+}
+
+/// Why a password was rejected. [`PasswordViolation::code`] is what's sent to clients, so that they can show their own
+/// message for it.
+#[derive(Debug, Error)]
+pub enum PasswordViolation {
+	#[error("Password must be at least {minimum} characters long!")]
+	TooShort { minimum: usize },
+
+	#[error("Password can't be your username!")]
+	MatchesUsername,
+
+	#[error("Password can't be your email address!")]
+	MatchesEmail,
+
+	#[error("Password has appeared in a data breach, please choose another!")]
+	Breached,
+}
+
+impl PasswordViolation {
+	pub fn code(&self) -> &'static str {
+		match self {
+			Self::TooShort { .. } => "too_short",
+			Self::MatchesUsername => "matches_username",
+			Self::MatchesEmail => "matches_email",
+			Self::Breached => "breached",
+		}
+	}
+}
+
+pub struct DefaultPasswordPolicy {
+	pub minimum_length: usize,
+	/// Checked after every other rule passes, if any.
+	pub breach_list: Option<Box<dyn BreachList>>,
+}
+
+impl PasswordPolicy for DefaultPasswordPolicy {
+	fn check<'a>(
+		&'a self,
+		password: &'a str,
+		username: &'a Username,
+		email: &'a Email,
+	) -> Check<'a> {
+		Box::pin(async move {
+			if password.chars().count() < self.minimum_length {
+				return Err(PasswordViolation::TooShort {
+					minimum: self.minimum_length,
+				});
+			}
+
+			if password.eq_ignore_ascii_case(username.as_str()) {
+				return Err(PasswordViolation::MatchesUsername);
+			}
+
+			if password.eq_ignore_ascii_case(email.local_part()) {
+				return Err(PasswordViolation::MatchesEmail);
+			}
+
+			let Some(breach_list) = &self.breach_list else {
+				return Ok(());
+			};
+
+			let (prefix, suffix) = hash_prefix(password);
+
+			// Failing closed would stop anyone creating an account whenever the breach list is down
+			let range = match breach_list.range(&prefix).await {
+				Ok(range) => range,
+				Err(error) => {
+					warn!("Couldn't check password against breach list, allowing it: {error}");
+					return Ok(());
+				}
+			};
+
+			match in_range(&range, &suffix) {
+				true => Err(PasswordViolation::Breached),
+				false => Ok(()),
+			}
+		})
+	}
+}
+
+/// A list of breached password hashes which can be searched by hash prefix, so that neither the password nor it's hash
+/// are ever sent anywhere.
+pub trait BreachList: Send + Sync + 'static {
+	/// Returns every hash suffix starting with `prefix`, one per line, as `SUFFIX:COUNT`.
+	fn range<'a>(&'a self, prefix: &'a str) -> Range<'a>;
+}
+
+/// The Have I Been Pwned range API, see: <https://haveibeenpwned.com/API/v3#PwnedPasswords>
+pub struct PwnedPasswords {
+	client: Client,
+}
+
+impl PwnedPasswords {
+	/// Account creation waits on the check, so don't let a slow response hold it up for long.
+	const TIMEOUT: Duration = Duration::from_secs(2);
+
+	pub fn new() -> Self {
+		Self {
+			client: Client::builder()
+				.timeout(Self::TIMEOUT)
+				.build()
+				.expect("failed to create http client"),
+		}
+	}
+}
+
+impl BreachList for PwnedPasswords {
+	fn range<'a>(&'a self, prefix: &'a str) -> Range<'a> {
+		Box::pin(async move {
+			Ok(self
+				.client
+				.get(format!("https://api.pwnedpasswords.com/range/{prefix}"))
+				// Pads the response with fake suffixes, so it's size doesn't give away which prefix was asked for
+				.header("Add-Padding", "true")
+				.send()
+				.await?
+				.error_for_status()?
+				.text()
+				.await?)
+		})
+	}
+}
+
+/// Splits the password's uppercase hex SHA-1 hash into the 5 character prefix sent to the breach list, and the rest.
+pub fn hash_prefix(password: &str) -> (String, String) {
+	let mut hash = to_string(&Sha1::digest(password.as_bytes())).to_ascii_uppercase();
+	let suffix = hash.split_off(5);
+	(hash, suffix)
+}
+
+fn in_range(range: &str, suffix: &str) -> bool {
+	range.lines().any(|line| match line.trim().split_once(':') {
+		// Padding entries have a count of 0
+		Some((candidate, count)) => {
+			candidate.eq_ignore_ascii_case(suffix)
+				&& count.trim().parse().is_ok_and(|count: u64| count > 0)
+		}
+		None => false,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use anyhow::anyhow;
+
+	const PASSWORD: &str = "correct horse battery staple";
+	const PREFIX: &str = "ABF7A";
+	const SUFFIX: &str = "AD6438836DBE526AA231ABDE2D0EEF74D42";
+
+	/// Answers with `range` for [`PREFIX`], and nothing for any other prefix. [`None`] is a breach list that can't be
+	/// reached.
+	struct FakeBreachList {
+		range: Option<String>,
+	}
+
+	impl BreachList for FakeBreachList {
+		fn range<'a>(&'a self, prefix: &'a str) -> Range<'a> {
+			Box::pin(async move {
+				match (&self.range, prefix) {
+					(None, _) => Err(anyhow!("unreachable")),
+					(Some(range), PREFIX) => Ok(range.clone()),
+					(Some(_), _) => Ok(String::new()),
+				}
+			})
+		}
+	}
+
+	fn policy(range: Option<&str>) -> DefaultPasswordPolicy {
+		DefaultPasswordPolicy {
+			minimum_length: 10,
+			breach_list: range.map(|range| {
+				Box::new(FakeBreachList {
+					range: Some(range.into()),
+				}) as Box<dyn BreachList>
+			}),
+		}
+	}
+
+	async fn check(
+		policy: &DefaultPasswordPolicy,
+		password: &str,
+	) -> Result<(), PasswordViolation> {
+		let username = serde_json::from_str(r#""Astralchroma_1""#).unwrap();
+		let email = serde_json::from_str(r#""starlight.example@example.com""#).unwrap();
+		policy.check(password, &username, &email).await
+	}
+
+	#[test]
+	fn hash_prefix_splits_sha1() {
+		assert_eq!(
+			hash_prefix("password"),
+			("5BAA6".into(), "1E4C9B93F3F0682250B6CF8331B7EE68FD8".into())
+		);
+		assert_eq!(hash_prefix(PASSWORD), (PREFIX.into(), SUFFIX.into()));
+	}
+
+	#[tokio::test]
+	async fn short_passwords_are_rejected() {
+		let policy = policy(None);
+
+		assert!(matches!(
+			check(&policy, "").await,
+			Err(PasswordViolation::TooShort { minimum: 10 })
+		));
+		assert!(matches!(
+			check(&policy, "123456789").await,
+			Err(PasswordViolation::TooShort { .. })
+		));
+		assert!(check(&policy, "1234567890").await.is_ok());
+
+		// Characters are counted, not bytes
+		assert!(matches!(
+			check(&policy, "ééééééééé").await,
+			Err(PasswordViolation::TooShort { .. })
+		));
+	}
+
+	#[tokio::test]
+	async fn username_and_email_are_rejected() {
+		let policy = policy(None);
+
+		assert!(matches!(
+			check(&policy, "Astralchroma_1").await,
+			Err(PasswordViolation::MatchesUsername)
+		));
+		assert!(matches!(
+			check(&policy, "ASTRALCHROMA_1").await,
+			Err(PasswordViolation::MatchesUsername)
+		));
+		assert!(matches!(
+			check(&policy, "Starlight.Example").await,
+			Err(PasswordViolation::MatchesEmail)
+		));
+
+		// Only the local part is checked, the whole address is fine
+		assert!(check(&policy, "starlight.example@example.com")
+			.await
+			.is_ok());
+	}
+
+	#[tokio::test]
+	async fn breached_passwords_are_rejected() {
+		let range = format!("0000000000000000000000000000000000A:0\n{SUFFIX}:42\n");
+
+		assert!(matches!(
+			check(&policy(Some(&range)), PASSWORD).await,
+			Err(PasswordViolation::Breached)
+		));
+		assert!(check(&policy(Some(&range)), "a different password")
+			.await
+			.is_ok());
+	}
+
+	#[tokio::test]
+	async fn padding_isnt_a_breach() {
+		let range = format!("{}:0\r\n", SUFFIX.to_ascii_lowercase());
+		assert!(check(&policy(Some(&range)), PASSWORD).await.is_ok());
+	}
+
+	#[tokio::test]
+	async fn unreachable_breach_list_fails_open() {
+		let policy = DefaultPasswordPolicy {
+			minimum_length: 10,
+			breach_list: Some(Box::new(FakeBreachList { range: None })),
+		};
+
+		assert!(check(&policy, PASSWORD).await.is_ok());
+
+		// The other rules still apply
+		assert!(matches!(
+			check(&policy, "short").await,
+			Err(PasswordViolation::TooShort { .. })
+		));
+	}
+
+	#[test]
+	fn codes_are_stable() {
+		let codes = [
+			PasswordViolation::TooShort { minimum: 10 },
+			PasswordViolation::MatchesUsername,
+			PasswordViolation::MatchesEmail,
+			PasswordViolation::Breached,
+		]
+		.map(|violation| violation.code());
+
+		assert_eq!(
+			codes,
+			["too_short", "matches_username", "matches_email", "breached"]
+		);
+	}
+}
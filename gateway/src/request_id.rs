@@ -0,0 +1,245 @@
+use crate::client_ip::ClientIp;
+use axum::{
+	extract::Request,
+	http::{HeaderValue, Uri},
+	middleware::Next,
+	response::Response,
+};
+use itertools::Itertools;
+use log::{debug, info};
+use solarscape_shared::data::Id;
+use std::{
+	fmt::{self, Display, Formatter},
+	time::Instant,
+};
+use tokio::task_local;
+
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Query parameters which should never end up in the logs.
+const REDACTED_PARAMETERS: [&str; 2] = ["password", "token"];
+
+task_local! {
+	static CURRENT: RequestId;
+}
+
+/// Unique id assigned to each request, available to handlers as an extension.
+#[derive(Clone, Copy)]
+pub struct RequestId(pub Id);
+
+impl Display for RequestId {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		self.0.fmt(formatter)
+	}
+}
+
+/// Displays as the id of the request currently being handled, or `-` if there isn't one. Intended for log messages in
+/// places that don't have access to the request, such as [`IntoResponse`](axum::response::IntoResponse) impls.
+pub struct CurrentRequestId;
+
+impl Display for CurrentRequestId {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		match CURRENT.try_with(|request_id| *request_id) {
+			Ok(request_id) => request_id.fmt(formatter),
+			Err(_) => formatter.write_str("-"),
+		}
+	}
+}
+
+pub async fn middleware(mut request: Request, next: Next) -> Response {
+	let start_time = Instant::now();
+
+	let request_id = RequestId(Id::new());
+	request.extensions_mut().insert(request_id);
+
+	let method = request.method().clone();
+	let path = redact(request.uri());
+	let client_ip = request
+		.extensions()
+		.get::<ClientIp>()
+		.map_or_else(|| "-".to_string(), ClientIp::to_string);
+
+	let mut response = CURRENT.scope(request_id, next.run(request)).await;
+
+	response.headers_mut().insert(
+		REQUEST_ID_HEADER,
+		HeaderValue::from_str(&request_id.to_string())
+			.expect("request id should only contain digits"),
+	);
+
+	let status = response.status();
+	let latency = Instant::now() - start_time;
+
+	let status_code = status.as_u16();
+	let latency_ms = latency.as_secs_f64() * 1000.0;
+
+	match status.is_success() {
+		true => debug!(
+			request_id:% = request_id, client_ip:% = client_ip, method:% = method, path:% = path, status = status_code,
+			latency_ms;
+			"[{request_id}] {client_ip} {method} {path} {status} {latency:.0?}"
+		),
+		false => info!(
+			request_id:% = request_id, client_ip:% = client_ip, method:% = method, path:% = path, status = status_code,
+			latency_ms;
+			"[{request_id}] {client_ip} {method} {path} {status} {latency:.0?}"
+		),
+	}
+
+	response
+}
+
+fn redact(uri: &Uri) -> String {
+	let Some(query) = uri.query() else {
+		return uri.path().to_string();
+	};
+
+	let query = query
+		.split('&')
+		.map(|parameter| match parameter.split_once('=') {
+			Some((key, _))
+				if REDACTED_PARAMETERS
+					.iter()
+					.any(|redacted| key.eq_ignore_ascii_case(redacted)) =>
+			{
+				format!("{key}=REDACTED")
+			}
+			_ => parameter.to_string(),
+		})
+		.join("&");
+
+	format!("{}?{query}", uri.path())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use axum::{
+		body::{to_bytes, Body},
+		extract::Extension,
+		http::StatusCode,
+		middleware,
+		routing::get,
+		Router,
+	};
+	use tower::ServiceExt;
+
+	/// Responds with the request's id as the handler sees it, both from the extension and [`CurrentRequestId`].
+	fn router() -> Router {
+		Router::new()
+			.route(
+				"/test",
+				get(|Extension(request_id): Extension<RequestId>| async move {
+					format!("{request_id} {CurrentRequestId}")
+				}),
+			)
+			.fallback(|| async { StatusCode::NOT_FOUND })
+			.layer(middleware::from_fn(super::middleware))
+	}
+
+	fn get_request(uri: &str) -> Request {
+		Request::builder().uri(uri).body(Body::empty()).unwrap()
+	}
+
+	fn header(response: &Response) -> String {
+		response.headers()[REQUEST_ID_HEADER]
+			.to_str()
+			.unwrap()
+			.to_string()
+	}
+
+	#[tokio::test]
+	async fn request_id_is_generated_and_echoed() {
+		let response = router().oneshot(get_request("/test")).await.unwrap();
+
+		assert_eq!(response.status(), StatusCode::OK);
+
+		let request_id = header(&response);
+		assert!(request_id.parse::<Id>().is_ok(), "{request_id}");
+
+		let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+		assert_eq!(body, format!("{request_id} {request_id}"));
+	}
+
+	#[tokio::test]
+	async fn every_request_gets_its_own_id() {
+		let first = router().oneshot(get_request("/test")).await.unwrap();
+		let second = router().oneshot(get_request("/test")).await.unwrap();
+
+		assert_ne!(header(&first), header(&second));
+	}
+
+	#[tokio::test]
+	async fn failed_requests_have_an_id_too() {
+		let response = router().oneshot(get_request("/missing")).await.unwrap();
+
+		assert_eq!(response.status(), StatusCode::NOT_FOUND);
+		assert!(header(&response).parse::<Id>().is_ok());
+	}
+
+	#[tokio::test]
+	async fn client_request_ids_are_replaced() {
+		let request = Request::builder()
+			.uri("/test")
+			.header(REQUEST_ID_HEADER, "1234")
+			.body(Body::empty())
+			.unwrap();
+
+		let response = router().oneshot(request).await.unwrap();
+
+		assert_eq!(
+			response.headers().get_all(REQUEST_ID_HEADER).iter().count(),
+			1
+		);
+		assert_ne!(header(&response), "1234");
+	}
+
+	#[test]
+	fn current_request_id_outside_of_a_request() {
+		assert_eq!(CurrentRequestId.to_string(), "-");
+	}
+
+	fn redacted(uri: &str) -> String {
+		redact(&uri.parse().unwrap())
+	}
+
+	#[test]
+	fn password_and_token_are_redacted() {
+		assert_eq!(
+			redacted("/api/login?username=test&password=hunter2"),
+			"/api/login?username=test&password=REDACTED"
+		);
+		assert_eq!(
+			redacted("/api/session?token=secret&expires=60"),
+			"/api/session?token=REDACTED&expires=60"
+		);
+		assert_eq!(
+			redacted("/api/login?password=a&token=b&password=c"),
+			"/api/login?password=REDACTED&token=REDACTED&password=REDACTED"
+		);
+	}
+
+	#[test]
+	fn redaction_ignores_case() {
+		assert_eq!(
+			redacted("/api/login?Password=hunter2&TOKEN=secret"),
+			"/api/login?Password=REDACTED&TOKEN=REDACTED"
+		);
+	}
+
+	#[test]
+	fn other_parameters_are_kept() {
+		assert_eq!(redacted("/api/players"), "/api/players");
+		assert_eq!(
+			redacted("/api/players?page=2&passwords=1&tokens"),
+			"/api/players?page=2&passwords=1&tokens"
+		);
+
+		// No value to redact
+		assert_eq!(redacted("/api/login?password"), "/api/login?password");
+		assert_eq!(
+			redacted("/api/login?password="),
+			"/api/login?password=REDACTED"
+		);
+	}
+}
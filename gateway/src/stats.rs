@@ -0,0 +1,72 @@
+use log::warn;
+use solarscape_shared::message::backend::GatewayStats;
+use sqlx::{query, PgPool};
+use std::{
+	mem,
+	sync::{Arc, Mutex},
+	time::Instant,
+};
+use tokio::time::{interval_at, MissedTickBehavior};
+
+/// Counts requests between reports, see [`GatewayStats`].
+pub struct Stats {
+	current: Mutex<(GatewayStats, Instant)>,
+}
+
+impl Stats {
+	pub fn new() -> Self {
+		Self {
+			current: Mutex::new((GatewayStats::default(), Instant::now())),
+		}
+	}
+
+	pub fn record(&self, record: impl FnOnce(&mut GatewayStats)) {
+		let mut current = self
+			.current
+			.lock()
+			.expect("stats lock should not be poisoned");
+		record(&mut current.0);
+	}
+
+	/// Takes everything counted since the last call, starting the next period.
+	fn take(&self) -> GatewayStats {
+		let mut current = self
+			.current
+			.lock()
+			.expect("stats lock should not be poisoned");
+		let (mut stats, start) =
+			mem::replace(&mut *current, (GatewayStats::default(), Instant::now()));
+
+		stats.period = start.elapsed().as_secs();
+		stats
+	}
+}
+
+/// Writes a report to the `gateway_stats` table every [`GatewayStats::INTERVAL`], forever.
+pub async fn report(gateway: String, database: PgPool, stats: Arc<Stats>) {
+	let mut interval = interval_at(
+		tokio::time::Instant::now() + GatewayStats::INTERVAL,
+		GatewayStats::INTERVAL,
+	);
+	interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+	loop {
+		interval.tick().await;
+
+		let report = serde_json::to_string(&stats.take()).expect("stats should serialize");
+
+		let result = query!(
+			"INSERT INTO gateway_stats(gateway, reported, stats) VALUES ($1, NOW(), $2)
+				ON CONFLICT (gateway) DO UPDATE SET reported = EXCLUDED.reported, stats = EXCLUDED.stats",
+			gateway,
+			report,
+		)
+		.execute(&database)
+		.await;
+
+		// The counts are lost, but the next report will still be accurate for it's own period
+		if let Err(error) = result {
+			warn!("Unable to write gateway stats: {error}");
+		}
+	}
+}
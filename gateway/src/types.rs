@@ -4,6 +4,7 @@ use axum::response::{IntoResponse, Response};
 use email_address::{EmailAddress, Options};
 use serde::{de::Unexpected, Deserialize, Deserializer};
 use sqlx::{encode::IsNull, error::BoxDynError, Database, Decode, Encode, Type, TypeInfo};
+use std::fmt::{self, Debug, Display, Formatter};
 
 pub trait InternalError: Into<anyhow::Error> {}
 
@@ -15,6 +16,12 @@ impl InternalError for argon2::password_hash::Error {}
 #[sqlx(transparent)]
 pub struct Username(Box<str>);
 
+impl Username {
+	pub fn as_str(&self) -> &str {
+		&self.0
+	}
+}
+
 impl<'d> Deserialize<'d> for Username {
 	fn deserialize<D: Deserializer<'d>>(deserializer: D) -> std::result::Result<Self, D::Error> {
 		let username = Box::<str>::deserialize(deserializer)?;
@@ -46,6 +53,13 @@ impl<'d> Deserialize<'d> for Username {
 /// Represents a valid Email Address which may or may not be verified or in use.
 pub struct Email(EmailAddress);
 
+impl Email {
+	/// The part before the `@`.
+	pub fn local_part(&self) -> &str {
+		self.0.local_part()
+	}
+}
+
 impl<'d> Deserialize<'d> for Email {
 	fn deserialize<D: Deserializer<'d>>(deserializer: D) -> std::result::Result<Self, D::Error> {
 		let address = Box::<str>::deserialize(deserializer)?;
@@ -98,7 +112,7 @@ where
 	}
 }
 
-#[derive(Type)]
+#[derive(Clone, Copy, Eq, PartialEq, Type)]
 #[sqlx(transparent)]
 pub struct Token([u8; 16]);
 
@@ -110,6 +124,19 @@ impl Token {
 	}
 }
 
+impl Display for Token {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		formatter.write_str(&to_string(self.0.as_slice()))
+	}
+}
+
+// Only the start of the token, so it can't be leaked through logs
+impl Debug for Token {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		write!(formatter, "Token({}…)", &self.to_string()[..8])
+	}
+}
+
 impl IntoResponse for Token {
 	fn into_response(self) -> Response {
 		to_string(self.0.as_slice()).into_response()
@@ -0,0 +1,24 @@
+-- Players on their way from one sector to another. The sector they're leaving writes a row before sending them off,
+-- and the sector they're going to consumes it as they connect, putting them where the row says. Rows that aren't
+-- consumed before they expire are abandoned transfers, the player just spawns as normal. As the player is the key, a
+-- player can only be on their way to one sector at a time.
+CREATE TABLE player_transfers (
+	player_id   BigInt      PRIMARY KEY
+	                        REFERENCES players(id) ON DELETE CASCADE,
+
+	source      VarChar(64) NOT NULL,
+	destination VarChar(64) NOT NULL,
+
+	-- Where the player was in the source sector, sectors don't have their own coordinates yet
+	position    Real[]      NOT NULL
+	                        CHECK (cardinality(position) = 3),
+	-- Quaternion, i, j, k, then w
+	rotation    Real[]      NOT NULL
+	                        CHECK (cardinality(rotation) = 4),
+
+	created     Timestamp   NOT NULL
+	                        DEFAULT NOW(),
+	expires     Timestamp   NOT NULL
+);
+
+CREATE INDEX player_transfers_destination ON player_transfers (destination);
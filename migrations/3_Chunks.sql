@@ -0,0 +1,22 @@
+-- Only chunks which have been modified are stored, everything else is regenerated as needed. Voxject ids are not
+-- stable across restarts, so chunks are keyed by sector and voxject name instead.
+CREATE TABLE chunks (
+	sector    VarChar(64) NOT NULL,
+	voxject   VarChar(64) NOT NULL,
+
+	x         Int         NOT NULL,
+	y         Int         NOT NULL,
+	z         Int         NOT NULL,
+
+	saved     Timestamp   NOT NULL
+	                      DEFAULT NOW(),
+
+	-- One byte per cell, see `Material`
+	materials ByteA       NOT NULL
+	                      CHECK (length(materials) = 4096),
+
+	densities Real[]      NOT NULL
+	                      CHECK (cardinality(densities) = 4096),
+
+	PRIMARY KEY (sector, voxject, x, y, z)
+);
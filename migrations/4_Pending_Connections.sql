@@ -0,0 +1,14 @@
+-- Connection keys the gateway has handed out but that haven't been used yet. Sector servers are also told about keys
+-- with NOTIFY, this table is so that keys sent while a sector server wasn't listening aren't lost.
+CREATE TABLE pending_connections (
+	key_id    BigInt      PRIMARY KEY,
+
+	sector    VarChar(64) NOT NULL,
+	player_id BigInt      NOT NULL
+	                      REFERENCES players(id) ON DELETE CASCADE,
+
+	key       ByteA       NOT NULL
+	                      CHECK (length(key) = 32),
+
+	expires   Timestamp   NOT NULL
+);
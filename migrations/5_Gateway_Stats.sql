@@ -0,0 +1,12 @@
+-- Counters each gateway reports every 30 seconds, so that sector servers can show whether login problems are on the
+-- gateway's side. Each gateway keeps a single row, which every report replaces.
+CREATE TABLE gateway_stats (
+	-- The address the gateway accepts connections on
+	gateway  VarChar(64) PRIMARY KEY,
+
+	reported Timestamp   NOT NULL
+	                     DEFAULT NOW(),
+
+	-- `GatewayStats` as JSON
+	stats    Text        NOT NULL
+);
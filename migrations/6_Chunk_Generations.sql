@@ -0,0 +1,3 @@
+-- Counts how many times each chunk has been saved, so that a sector snapshot can tell whether the database has been
+-- saved since the snapshot was taken. Chunks saved before this are treated as having been saved once.
+ALTER TABLE chunks ADD COLUMN generation BigInt NOT NULL DEFAULT 1;
@@ -0,0 +1,14 @@
+-- Each sector's most recent chat messages, so that players joining after a restart still see what was said. Sectors
+-- delete all but their most recent messages as they write new ones.
+CREATE TABLE chat_history (
+	sector   VarChar(64) NOT NULL,
+	sequence BigInt      NOT NULL,
+
+	player   BigInt      NOT NULL,
+	text     Text        NOT NULL,
+
+	sent     Timestamp   NOT NULL
+	                     DEFAULT NOW(),
+
+	PRIMARY KEY (sector, sequence)
+);
@@ -0,0 +1,23 @@
+-- Each sector server's player count, reported every 10 seconds so that gateways can turn players away from a full
+-- sector rather than sending them to be rejected. Each sector keeps a single row, which every report replaces.
+CREATE TABLE sector_status (
+	sector   VarChar(64) PRIMARY KEY,
+
+	reported Timestamp   NOT NULL
+	                     DEFAULT NOW(),
+
+	players  Int4        NOT NULL
+	                     CHECK (players >= 0),
+
+	-- 0 if the sector has no limit
+	capacity Int4        NOT NULL
+	                     CHECK (capacity >= 0)
+);
+
+-- Players waiting for room in a full sector are kept alongside the keys, as a row without a key. They're admitted in
+-- the order they were queued, and dropped from the queue if they stop asking before they're admitted.
+ALTER TABLE pending_connections
+	ALTER COLUMN key DROP NOT NULL,
+	ADD COLUMN queued Timestamp NOT NULL DEFAULT NOW();
+
+CREATE UNIQUE INDEX pending_connections_queue ON pending_connections (sector, player_id) WHERE key IS NULL;
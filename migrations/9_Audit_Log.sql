@@ -0,0 +1,23 @@
+-- Moderation-relevant things players do, written by sector servers in batches and read through the gateway's
+-- /api/audit endpoint. Identical events in the same batch are written once with a count. Rows are never changed or
+-- removed once written.
+CREATE TABLE audit_log (
+	id      BigSerial   PRIMARY KEY,
+	sector  VarChar(64) NOT NULL,
+
+	player  BigInt      NOT NULL,
+	-- The sector's tick when the event first happened, only comparable between entries from the same run of a sector
+	tick    BigInt      NOT NULL,
+	-- JSON, see AuditEvent in the sector server
+	event   Text        NOT NULL,
+	count   Int4        NOT NULL
+	                    CHECK (count > 0),
+
+	created Timestamp   NOT NULL
+	                    DEFAULT NOW()
+);
+
+CREATE INDEX audit_log_player ON audit_log (player, created);
+
+CREATE RULE audit_log_no_update AS ON UPDATE TO audit_log DO INSTEAD NOTHING;
+CREATE RULE audit_log_no_delete AS ON DELETE TO audit_log DO INSTEAD NOTHING;
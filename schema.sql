@@ -2,7 +2,7 @@
 -- combination of those migrations to be used as a programmer reference, it should not be used for an actual database
 -- testing or otherwise.
 --
--- Currently in line with: `2_Items_&_Inventories.sql`
+-- Currently in line with: `10_Player_Transfers.sql`
 
 CREATE TABLE players (
 	id       BigInt       PRIMARY KEY
@@ -65,3 +65,146 @@ CREATE TABLE inventory_items (
 
 	PRIMARY KEY (inventory_id, item_id)
 );
+
+-- Only chunks which have been modified are stored, everything else is regenerated as needed. Voxject ids are not
+-- stable across restarts, so chunks are keyed by sector and voxject name instead.
+CREATE TABLE chunks (
+	sector     VarChar(64) NOT NULL,
+	voxject    VarChar(64) NOT NULL,
+
+	x          Int         NOT NULL,
+	y          Int         NOT NULL,
+	z          Int         NOT NULL,
+
+	saved      Timestamp   NOT NULL
+	                       DEFAULT NOW(),
+
+	-- How many times the chunk has been saved, see `6_Chunk_Generations.sql`
+	generation BigInt      NOT NULL
+	                       DEFAULT 1,
+
+	-- One byte per cell, see `Material`
+	materials  ByteA       NOT NULL
+	                       CHECK (length(materials) = 4096),
+
+	densities  Real[]      NOT NULL
+	                       CHECK (cardinality(densities) = 4096),
+
+	PRIMARY KEY (sector, voxject, x, y, z)
+);
+
+-- Connection keys the gateway has handed out but that haven't been used yet. Sector servers are also told about keys
+-- with NOTIFY, this table is so that keys sent while a sector server wasn't listening aren't lost.
+--
+-- Players waiting for room in a full sector are kept here too, as a row without a key. They're admitted in the order
+-- they were queued, and dropped from the queue if they stop asking before they're admitted.
+CREATE TABLE pending_connections (
+	key_id    BigInt      PRIMARY KEY,
+
+	sector    VarChar(64) NOT NULL,
+	player_id BigInt      NOT NULL
+	                      REFERENCES players(id) ON DELETE CASCADE,
+
+	-- NULL while the player is queued
+	key       ByteA       CHECK (length(key) = 32),
+
+	expires   Timestamp   NOT NULL,
+
+	queued    Timestamp   NOT NULL
+	                      DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX pending_connections_queue ON pending_connections (sector, player_id) WHERE key IS NULL;
+
+-- Counters each gateway reports every 30 seconds, so that sector servers can show whether login problems are on the
+-- gateway's side. Each gateway keeps a single row, which every report replaces.
+CREATE TABLE gateway_stats (
+	-- The address the gateway accepts connections on
+	gateway  VarChar(64) PRIMARY KEY,
+
+	reported Timestamp   NOT NULL
+	                     DEFAULT NOW(),
+
+	-- `GatewayStats` as JSON
+	stats    Text        NOT NULL
+);
+
+-- Each sector's most recent chat messages, so that players joining after a restart still see what was said. Sectors
+-- delete all but their most recent messages as they write new ones.
+CREATE TABLE chat_history (
+	sector   VarChar(64) NOT NULL,
+	sequence BigInt      NOT NULL,
+
+	player   BigInt      NOT NULL,
+	text     Text        NOT NULL,
+
+	sent     Timestamp   NOT NULL
+	                     DEFAULT NOW(),
+
+	PRIMARY KEY (sector, sequence)
+);
+
+-- Each sector server's player count, reported every 10 seconds so that gateways can turn players away from a full
+-- sector rather than sending them to be rejected. Each sector keeps a single row, which every report replaces.
+CREATE TABLE sector_status (
+	sector   VarChar(64) PRIMARY KEY,
+
+	reported Timestamp   NOT NULL
+	                     DEFAULT NOW(),
+
+	players  Int4        NOT NULL
+	                     CHECK (players >= 0),
+
+	-- 0 if the sector has no limit
+	capacity Int4        NOT NULL
+	                     CHECK (capacity >= 0)
+);
+
+-- Moderation-relevant things players do, written by sector servers in batches and read through the gateway's
+-- /api/audit endpoint. Identical events in the same batch are written once with a count. Rows are never changed or
+-- removed once written.
+CREATE TABLE audit_log (
+	id      BigSerial   PRIMARY KEY,
+	sector  VarChar(64) NOT NULL,
+
+	player  BigInt      NOT NULL,
+	-- The sector's tick when the event first happened, only comparable between entries from the same run of a sector
+	tick    BigInt      NOT NULL,
+	-- JSON, see AuditEvent in the sector server
+	event   Text        NOT NULL,
+	count   Int4        NOT NULL
+	                    CHECK (count > 0),
+
+	created Timestamp   NOT NULL
+	                    DEFAULT NOW()
+);
+
+CREATE INDEX audit_log_player ON audit_log (player, created);
+
+CREATE RULE audit_log_no_update AS ON UPDATE TO audit_log DO INSTEAD NOTHING;
+CREATE RULE audit_log_no_delete AS ON DELETE TO audit_log DO INSTEAD NOTHING;
+
+-- Players on their way from one sector to another. The sector they're leaving writes a row before sending them off,
+-- and the sector they're going to consumes it as they connect, putting them where the row says. Rows that aren't
+-- consumed before they expire are abandoned transfers, the player just spawns as normal. As the player is the key, a
+-- player can only be on their way to one sector at a time.
+CREATE TABLE player_transfers (
+	player_id   BigInt      PRIMARY KEY
+	                        REFERENCES players(id) ON DELETE CASCADE,
+
+	source      VarChar(64) NOT NULL,
+	destination VarChar(64) NOT NULL,
+
+	-- Where the player was in the source sector, sectors don't have their own coordinates yet
+	position    Real[]      NOT NULL
+	                        CHECK (cardinality(position) = 3),
+	-- Quaternion, i, j, k, then w
+	rotation    Real[]      NOT NULL
+	                        CHECK (cardinality(rotation) = 4),
+
+	created     Timestamp   NOT NULL
+	                        DEFAULT NOW(),
+	expires     Timestamp   NOT NULL
+);
+
+CREATE INDEX player_transfers_destination ON player_transfers (destination);
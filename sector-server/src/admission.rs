@@ -0,0 +1,550 @@
+use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, KeyInit};
+use futures::{
+	future::BoxFuture,
+	stream::{BoxStream, StreamExt},
+	FutureExt,
+};
+use log::{debug, error, info, warn};
+use solarscape_shared::{
+	data::Id,
+	message::{backend::AllowConnection, PROTOCOL_VERSION},
+};
+use sqlx::{
+	postgres::{PgListener, PgNotification},
+	query, PgPool,
+};
+use std::{
+	collections::HashMap,
+	fmt::{self, Display, Formatter},
+	time::Duration,
+};
+use thiserror::Error;
+use tokio::time::sleep;
+
+/// Connection keys the gateway has handed out for this sector. Keys normally arrive by NOTIFY, but they are also
+/// recorded in the `pending_connections` table, which is polled in case a notification was missed. Rows without a key
+/// are players the gateway has queued, which are left for the gateway to manage.
+pub struct PendingConnections {
+	sector: Box<str>,
+	keys: HashMap<[u8; 32], PendingConnection>,
+
+	/// Key from `--preshared-key-file`, and the player connecting with it. This is never consumed or expired.
+	preshared_key: Option<([u8; 32], Id)>,
+
+	pub counters: Counters,
+}
+
+#[derive(Clone, Copy)]
+struct PendingConnection {
+	player: Id,
+	key_id: Id,
+}
+
+/// Totals since the sector server started.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Counters {
+	/// Keys received by NOTIFY.
+	pub notifications: u64,
+
+	/// Keys found by polling which were never received by NOTIFY.
+	pub polled: u64,
+
+	/// Keys which were never used.
+	pub expired: u64,
+
+	/// Connections which were accepted.
+	pub handshakes: u64,
+}
+
+impl PendingConnections {
+	pub const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+	pub fn new(sector: Box<str>) -> Self {
+		Self {
+			sector,
+			keys: HashMap::new(),
+
+			preshared_key: None,
+
+			counters: Counters::default(),
+		}
+	}
+
+	pub fn notified(&mut self, AllowConnection { id, key_id, key }: AllowConnection) {
+		self.counters.notifications += 1;
+
+		debug!(key_id:% = key_id, player_id:% = id; "Received key {key_id} for player {id}");
+
+		self.keys
+			.insert(key, PendingConnection { player: id, key_id });
+	}
+
+	/// Lets anyone with `key` connect as `player`, without going through the gateway.
+	pub fn set_preshared_key(&mut self, key: [u8; 32], player: Id) {
+		self.preshared_key = Some((key, player));
+	}
+
+	/// Purges expired keys, then picks up any keys that weren't received by NOTIFY.
+	pub async fn poll(&mut self, database: &PgPool) -> Result<(), sqlx::Error> {
+		let expired = query!(
+			r#"DELETE FROM pending_connections WHERE sector = $1 AND key IS NOT NULL AND expires < NOW()
+				RETURNING key_id AS "key_id: Id""#,
+			&*self.sector
+		)
+		.fetch_all(database)
+		.await?;
+
+		for row in &expired {
+			self.keys.retain(|_, pending| pending.key_id != row.key_id);
+			debug!(key_id:% = row.key_id; "Key {} expired without being used", row.key_id);
+		}
+
+		if !expired.is_empty() {
+			self.counters.expired += expired.len() as u64;
+			info!("Purged {} expired connection keys", expired.len());
+		}
+
+		let pending = query!(
+			r#"SELECT key_id AS "key_id: Id", player_id AS "player_id: Id", key AS "key!"
+				FROM pending_connections WHERE sector = $1 AND key IS NOT NULL"#,
+			&*self.sector
+		)
+		.fetch_all(database)
+		.await?;
+
+		for row in pending {
+			let Ok(key) = <[u8; 32]>::try_from(row.key) else {
+				// The table has a check constraint on the length, so this shouldn't be possible
+				warn!(key_id:% = row.key_id; "Key {} has the wrong length", row.key_id);
+				continue;
+			};
+
+			if self.keys.contains_key(&key) {
+				continue;
+			}
+
+			self.counters.polled += 1;
+
+			info!(
+				key_id:% = row.key_id, player_id:% = row.player_id;
+				"Recovered key {} for player {}, the notification for it was missed", row.key_id, row.player_id
+			);
+
+			self.keys.insert(
+				key,
+				PendingConnection {
+					player: row.player_id,
+					key_id: row.key_id,
+				},
+			);
+		}
+
+		Ok(())
+	}
+
+	/// Finds the key that `handshake` was encrypted with, consuming it unless it's the pre-shared key. Returns the
+	/// player, the key's id if it came from the gateway, and the cipher to use for the connection. A handshake from a
+	/// client on a different [`PROTOCOL_VERSION`] is refused without consuming it's key.
+	pub fn accept(
+		&mut self,
+		handshake: &[u8],
+	) -> Result<(Id, Option<Id>, ChaCha20Poly1305), HandshakeError> {
+		let decrypts = |key: &[u8; 32]| {
+			let cipher = ChaCha20Poly1305::new(key.into());
+			let version_data = cipher.decrypt((&[0; 12]).into(), handshake).ok()?;
+
+			Some((cipher, u32::from_le_bytes(version_data.try_into().ok()?)))
+		};
+
+		let check_version = |version| match version {
+			PROTOCOL_VERSION => Ok(()),
+			version => Err(HandshakeError::Version(version)),
+		};
+
+		if let Some((key, (cipher, version))) = self
+			.keys
+			.keys()
+			.find_map(|key| Some((*key, decrypts(key)?)))
+		{
+			check_version(version)?;
+
+			let PendingConnection { player, key_id } = self.keys.remove(&key).unwrap();
+
+			self.counters.handshakes += 1;
+
+			return Ok((player, Some(key_id), cipher));
+		}
+
+		let unknown_key = HandshakeError::UnknownKey(self.keys.len());
+		let (key, player) = self.preshared_key.ok_or(unknown_key)?;
+		let (cipher, version) = decrypts(&key).ok_or(unknown_key)?;
+		check_version(version)?;
+
+		self.counters.handshakes += 1;
+
+		Ok((player, None, cipher))
+	}
+}
+
+/// Why a handshake was refused, see [`PendingConnections::accept`].
+#[derive(Clone, Copy, Debug, Error)]
+pub enum HandshakeError {
+	#[error("didn't match any of the {0} pending keys")]
+	UnknownKey(usize),
+	#[error("is for protocol version {0}, but this server is protocol version {PROTOCOL_VERSION}")]
+	Version(u32),
+}
+
+type NotificationStream = BoxStream<'static, Result<PgNotification, sqlx::Error>>;
+
+/// Starts listening for notifications, called again whenever the connection is lost.
+type Connector =
+	Box<dyn Fn() -> BoxFuture<'static, Result<NotificationStream, sqlx::Error>> + Send + Sync>;
+
+/// Receives keys sent to this sector by NOTIFY. If the connection is lost it's replaced, backing off between attempts,
+/// while keys keep arriving through [`PendingConnections::poll`] in the meantime.
+pub struct KeyListener {
+	connector: Connector,
+
+	connection: ListenerConnection,
+	backoff: Backoff,
+	state: ListenerState,
+}
+
+enum ListenerConnection {
+	Listening(NotificationStream),
+	/// Waits out the backoff, then connects.
+	Reconnecting(BoxFuture<'static, Result<NotificationStream, sqlx::Error>>),
+}
+
+pub enum ListenerEvent {
+	Notified(AllowConnection),
+	/// Notifications sent while disconnected were lost, so pending connections should be polled.
+	Reconnected,
+}
+
+impl KeyListener {
+	pub async fn connect(database: PgPool, channel: Box<str>) -> Result<Self, sqlx::Error> {
+		Self::with_connector(Box::new(move || {
+			listen(database.clone(), channel.clone()).boxed()
+		}))
+		.await
+	}
+
+	async fn with_connector(connector: Connector) -> Result<Self, sqlx::Error> {
+		let stream = connector().await?;
+
+		Ok(Self {
+			connector,
+
+			connection: ListenerConnection::Listening(stream),
+			backoff: Backoff::default(),
+			state: ListenerState::Connected,
+		})
+	}
+
+	/// Waits for the next key, or for the connection to come back after being lost. This is cancel safe, a reconnect
+	/// in progress carries on from where it was the next time this is called.
+	pub async fn recv(&mut self) -> ListenerEvent {
+		loop {
+			match &mut self.connection {
+				ListenerConnection::Listening(stream) => match stream.next().await {
+					Some(Ok(notification)) => match serde_json::from_str(notification.payload()) {
+						Ok(allow_connection) => return ListenerEvent::Notified(allow_connection),
+						Err(error) => {
+							error!(
+								"error while deserializing allow connection notification: {error}"
+							)
+						}
+					},
+					Some(Err(error)) => self.disconnected(error.to_string().into()),
+					None => self.disconnected("connection closed".into()),
+				},
+				ListenerConnection::Reconnecting(reconnect) => match reconnect.await {
+					Ok(stream) => {
+						info!(listener = "connected"; "Listening for connection keys again");
+
+						self.connection = ListenerConnection::Listening(stream);
+						self.backoff.reset();
+						self.state = ListenerState::Connected;
+
+						return ListenerEvent::Reconnected;
+					}
+					Err(error) => self.disconnected(error.to_string().into()),
+				},
+			}
+		}
+	}
+
+	pub fn state(&self) -> &ListenerState {
+		&self.state
+	}
+
+	fn disconnected(&mut self, error: Box<str>) {
+		let delay = self.backoff.next_delay();
+
+		warn!(
+			listener = "reconnecting", attempt = self.backoff.attempts;
+			"Lost connection for connection keys, retrying in {delay:.0?}, keys will be polled until then: {error}"
+		);
+
+		let reconnect = (self.connector)();
+
+		self.connection = ListenerConnection::Reconnecting(
+			async move {
+				sleep(delay).await;
+				reconnect.await
+			}
+			.boxed(),
+		);
+
+		self.state = ListenerState::Reconnecting {
+			attempts: self.backoff.attempts,
+			last_error: error,
+		};
+	}
+}
+
+async fn listen(database: PgPool, channel: Box<str>) -> Result<NotificationStream, sqlx::Error> {
+	let mut listener = PgListener::connect_with(&database).await?;
+	listener.listen(&channel).await?;
+	Ok(listener.into_stream().boxed())
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ListenerState {
+	Connected,
+	Reconnecting { attempts: u32, last_error: Box<str> },
+}
+
+impl Display for ListenerState {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Connected => write!(formatter, "connected"),
+			Self::Reconnecting {
+				attempts,
+				last_error,
+			} => write!(
+				formatter,
+				"reconnecting after {attempts} attempts, last error: {last_error}"
+			),
+		}
+	}
+}
+
+/// Doubles the delay between reconnection attempts, up to [`Self::MAX_DELAY`].
+#[derive(Default)]
+struct Backoff {
+	attempts: u32,
+}
+
+impl Backoff {
+	const INITIAL_DELAY: Duration = Duration::from_secs(1);
+	const MAX_DELAY: Duration = Duration::from_secs(60);
+
+	fn next_delay(&mut self) -> Duration {
+		let delay = Self::INITIAL_DELAY
+			.saturating_mul(1 << self.attempts.min(16))
+			.min(Self::MAX_DELAY);
+
+		self.attempts = self.attempts.saturating_add(1);
+
+		delay
+	}
+
+	fn reset(&mut self) {
+		self.attempts = 0;
+	}
+}
+
+/// Deletes a key once it has been used, so that it isn't picked up again by polling.
+pub async fn consume(database: &PgPool, key_id: Id) -> Result<(), sqlx::Error> {
+	query!(
+		"DELETE FROM pending_connections WHERE key_id = $1",
+		key_id as _
+	)
+	.execute(database)
+	.await?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::stream;
+	use std::{
+		collections::VecDeque,
+		sync::{Arc, Mutex},
+	};
+	use tokio::time::{timeout, Instant};
+
+	/// What each call to the connector does, in order.
+	enum Attempt {
+		/// Connects, then the connection is lost with an error straight away.
+		Drops,
+		/// Connects, and stays connected.
+		Stays,
+		Fails,
+	}
+
+	fn error() -> sqlx::Error {
+		sqlx::Error::Protocol("lost".into())
+	}
+
+	/// Connects as `attempts` says to, returning how many times it's been called.
+	fn connector(attempts: impl IntoIterator<Item = Attempt>) -> (Connector, Arc<Mutex<u32>>) {
+		let attempts = Arc::new(Mutex::new(attempts.into_iter().collect::<VecDeque<_>>()));
+		let calls = Arc::new(Mutex::new(0));
+
+		let counted = calls.clone();
+		let connector: Connector = Box::new(move || {
+			*counted.lock().unwrap() += 1;
+
+			let result = match attempts.lock().unwrap().pop_front() {
+				Some(Attempt::Drops) => Ok(stream::once(async { Err(error()) }).boxed()),
+				Some(Attempt::Stays) => Ok(stream::pending().boxed()),
+				Some(Attempt::Fails) | None => Err(error()),
+			};
+
+			async move { result }.boxed()
+		});
+
+		(connector, calls)
+	}
+
+	#[test]
+	fn backoff_doubles_up_to_the_limit() {
+		let mut backoff = Backoff::default();
+		let delays: Vec<_> = (0..8).map(|_| backoff.next_delay().as_secs()).collect();
+
+		assert_eq!(delays, [1, 2, 4, 8, 16, 32, 60, 60]);
+		assert_eq!(backoff.attempts, 8);
+
+		backoff.reset();
+		assert_eq!(backoff.next_delay(), Backoff::INITIAL_DELAY);
+
+		// Doesn't overflow after a very long outage
+		backoff.attempts = u32::MAX;
+		assert_eq!(backoff.next_delay(), Backoff::MAX_DELAY);
+		assert_eq!(backoff.attempts, u32::MAX);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn lost_connections_are_replaced_with_backoff() {
+		let (connector, calls) = connector([
+			Attempt::Drops,
+			Attempt::Fails,
+			Attempt::Fails,
+			Attempt::Stays,
+		]);
+
+		let mut listener = KeyListener::with_connector(connector).await.unwrap();
+		assert_eq!(*listener.state(), ListenerState::Connected);
+
+		// Still waiting out the first delay
+		let start = Instant::now();
+		assert!(timeout(Duration::from_millis(500), listener.recv())
+			.await
+			.is_err());
+
+		assert!(matches!(
+			listener.state(),
+			ListenerState::Reconnecting { attempts: 1, last_error } if last_error.ends_with("lost")
+		));
+
+		// Carries on from where it was, failing twice more before reconnecting
+		assert!(matches!(listener.recv().await, ListenerEvent::Reconnected));
+		assert_eq!(start.elapsed(), Duration::from_secs(1 + 2 + 4));
+		assert_eq!(*calls.lock().unwrap(), 4);
+
+		assert_eq!(*listener.state(), ListenerState::Connected);
+		assert_eq!(listener.backoff.attempts, 0);
+	}
+
+	#[tokio::test(start_paused = true)]
+	async fn connecting_fails_without_retrying() {
+		let (connector, calls) = connector([Attempt::Fails, Attempt::Stays]);
+
+		assert!(KeyListener::with_connector(connector).await.is_err());
+		assert_eq!(*calls.lock().unwrap(), 1);
+	}
+
+	#[test]
+	fn listener_state_is_described() {
+		assert_eq!(ListenerState::Connected.to_string(), "connected");
+		assert_eq!(
+			ListenerState::Reconnecting {
+				attempts: 3,
+				last_error: "timed out".into()
+			}
+			.to_string(),
+			"reconnecting after 3 attempts, last error: timed out"
+		);
+	}
+
+	/// What a client sends to connect with `key`, see `login::handshake` in the client.
+	fn handshake(key: &[u8; 32], version: u32) -> Vec<u8> {
+		ChaCha20Poly1305::new(key.into())
+			.encrypt((&[0; 12]).into(), &version.to_le_bytes()[..])
+			.unwrap()
+	}
+
+	#[test]
+	fn preshared_keys_are_never_consumed() {
+		let mut pending = PendingConnections::new("test".into());
+		let (key, player) = ([7; 32], Id::new());
+		pending.set_preshared_key(key, player);
+
+		for _ in 0..3 {
+			let (accepted, key_id, _) = pending.accept(&handshake(&key, PROTOCOL_VERSION)).unwrap();
+
+			assert_eq!(accepted, player);
+			assert_eq!(key_id, None);
+		}
+
+		assert_eq!(pending.counters.handshakes, 3);
+	}
+
+	#[test]
+	fn gateway_keys_are_consumed() {
+		let mut pending = PendingConnections::new("test".into());
+		let (key, key_id, player) = ([9; 32], Id::new(), Id::new());
+		pending.notified(AllowConnection {
+			id: player,
+			key_id,
+			key,
+		});
+
+		let (accepted, accepted_key_id, _) =
+			pending.accept(&handshake(&key, PROTOCOL_VERSION)).unwrap();
+		assert_eq!((accepted, accepted_key_id), (player, Some(key_id)));
+
+		assert!(matches!(
+			pending.accept(&handshake(&key, PROTOCOL_VERSION)),
+			Err(HandshakeError::UnknownKey(0))
+		));
+	}
+
+	#[test]
+	fn other_versions_and_keys_are_refused() {
+		let mut pending = PendingConnections::new("test".into());
+		let (key, player) = ([7; 32], Id::new());
+		pending.set_preshared_key(key, player);
+
+		assert!(matches!(
+			pending.accept(&handshake(&key, PROTOCOL_VERSION + 1)),
+			Err(HandshakeError::Version(version)) if version == PROTOCOL_VERSION + 1
+		));
+		assert!(matches!(
+			pending.accept(&handshake(&[8; 32], PROTOCOL_VERSION)),
+			Err(HandshakeError::UnknownKey(0))
+		));
+		assert!(matches!(
+			pending.accept(b"nonsense"),
+			Err(HandshakeError::UnknownKey(0))
+		));
+
+		assert_eq!(pending.counters.handshakes, 0);
+	}
+}
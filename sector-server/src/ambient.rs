@@ -0,0 +1,147 @@
+use crate::config;
+use solarscape_shared::message::clientbound::{Ambient, AmbientKeyframe};
+
+/// Advances the sector's ambient cycle. Players are only sent the phase every so often, the client advances it
+/// itself in between.
+pub struct AmbientCycle {
+	pub keyframes: Vec<AmbientKeyframe>,
+	period: f32,
+
+	phase: f32,
+	paused: bool,
+
+	/// Seconds since players were last sent the phase.
+	since_update: f32,
+}
+
+impl AmbientCycle {
+	/// Seconds between updates, the client's estimate shouldn't drift far in this time.
+	const UPDATE_INTERVAL: f32 = 10.0;
+
+	/// Sectors start at midday, the phase is only kept across restarts by snapshots.
+	const START_PHASE: f32 = 0.25;
+
+	pub fn new(config::Ambient { period, keyframes }: config::Ambient) -> Self {
+		Self {
+			keyframes,
+			period,
+
+			phase: Self::START_PHASE,
+			paused: false,
+
+			since_update: 0.0,
+		}
+	}
+
+	/// Returns `true` if players should be sent an update.
+	pub fn advance(&mut self, delta: f32) -> bool {
+		if !self.paused {
+			self.phase = (self.phase + delta / self.period).fract();
+		}
+
+		self.since_update += delta;
+
+		if self.since_update < Self::UPDATE_INTERVAL {
+			return false;
+		}
+
+		self.since_update = 0.0;
+		true
+	}
+
+	/// Returns `true` if the cycle was changed, in which case players should be sent an update.
+	pub fn set_paused(&mut self, paused: bool) -> bool {
+		if self.paused == paused {
+			return false;
+		}
+
+		self.paused = paused;
+		self.since_update = 0.0;
+		true
+	}
+
+	pub fn phase(&self) -> f32 {
+		self.phase
+	}
+
+	pub fn is_paused(&self) -> bool {
+		self.paused
+	}
+
+	/// Continues from where a snapshot left off.
+	pub fn restore(&mut self, phase: f32, paused: bool) {
+		self.phase = phase.rem_euclid(1.0);
+		self.paused = paused;
+	}
+
+	pub fn build_sync(&self) -> Ambient {
+		Ambient {
+			phase: self.phase,
+			rate: match self.paused {
+				true => 0.0,
+				false => 1.0 / self.period,
+			},
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn cycle(period: f32) -> AmbientCycle {
+		AmbientCycle::new(config::Ambient {
+			period,
+			..config::Ambient::default()
+		})
+	}
+
+	#[test]
+	fn phase_wraps_around() {
+		let mut cycle = cycle(100.0);
+		assert_eq!(cycle.phase(), AmbientCycle::START_PHASE);
+
+		cycle.advance(25.0);
+		assert!((cycle.phase() - 0.5).abs() < 1e-5);
+
+		cycle.advance(60.0);
+		assert!((cycle.phase() - 0.1).abs() < 1e-5);
+	}
+
+	#[test]
+	fn updates_are_sent_every_interval() {
+		let mut cycle = cycle(100.0);
+		let step = AmbientCycle::UPDATE_INTERVAL / 4.0;
+
+		for _ in 0..3 {
+			assert!(!cycle.advance(step));
+		}
+
+		assert!(cycle.advance(step));
+		assert!(!cycle.advance(step));
+	}
+
+	#[test]
+	fn paused_cycles_hold_their_phase() {
+		let mut cycle = cycle(100.0);
+
+		assert!(cycle.set_paused(true));
+		assert!(!cycle.set_paused(true));
+
+		cycle.advance(50.0);
+		assert_eq!(cycle.phase(), AmbientCycle::START_PHASE);
+		assert_eq!(cycle.build_sync().rate, 0.0);
+
+		assert!(cycle.set_paused(false));
+		assert_eq!(cycle.build_sync().rate, 0.01);
+	}
+
+	#[test]
+	fn restored_phases_are_wrapped() {
+		let mut cycle = cycle(100.0);
+
+		cycle.restore(-0.25, true);
+		assert_eq!(cycle.phase(), 0.75);
+		assert!(cycle.is_paused());
+	}
+}
@@ -0,0 +1,472 @@
+use crate::{config, sector::SharedSector};
+use log::{debug, warn};
+use serde::Serialize;
+use solarscape_shared::{
+	data::{
+		world::{Item, VoxjectPosition},
+		Id,
+	},
+	message::serverbound::BrushMode,
+};
+use sqlx::{query, PgPool};
+use std::{
+	collections::{HashMap, VecDeque},
+	mem::take,
+	sync::{
+		atomic::{AtomicU64, Ordering::Relaxed},
+		Arc,
+	},
+};
+
+/// Something a player did which moderators may want to look back on. Written to the `audit_log` table as JSON, which
+/// the gateway's `/api/audit` endpoint serves as is, so renaming a variant or field breaks reading older entries.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuditEvent {
+	StructureCreated {
+		structure: Id,
+	},
+	StructureDeleted {
+		structure: Id,
+	},
+	/// Only brushes with at least [`config::Audit::terrain_radius`] are logged.
+	TerrainModified {
+		center: VoxjectPosition,
+		radius: f32,
+		mode: BrushMode,
+	},
+	ItemsGranted {
+		item: Item,
+		quantity: i64,
+	},
+	/// `command` is everything after the `/`.
+	AdminCommand {
+		command: Box<str>,
+		succeeded: bool,
+	},
+	/// Events past [`config::Audit::rate_limit`], which are counted rather than logged.
+	Suppressed {
+		category: AuditCategory,
+	},
+}
+
+impl AuditEvent {
+	pub fn category(&self) -> AuditCategory {
+		match self {
+			Self::StructureCreated { .. } | Self::StructureDeleted { .. } => {
+				AuditCategory::Structure
+			}
+			Self::TerrainModified { .. } => AuditCategory::Terrain,
+			Self::ItemsGranted { .. } => AuditCategory::Inventory,
+			Self::AdminCommand { .. } => AuditCategory::Command,
+			Self::Suppressed { category } => *category,
+		}
+	}
+}
+
+/// Kinds of [`AuditEvent`], each is rate limited separately.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditCategory {
+	Structure,
+	Terrain,
+	Inventory,
+	Command,
+}
+
+/// An [`AuditEvent`] waiting to be written, along with how many times in a row it happened.
+#[derive(Debug)]
+pub struct AuditEntry {
+	pub player: Id,
+	pub tick: u64,
+	pub event: AuditEvent,
+	pub count: u32,
+}
+
+/// Queues [`AuditEvent`]s and writes them to the database every [`config::Audit::flush_interval`]. Writes are fire and
+/// forget, anything that can't be queued or written is counted in [`AuditLog::dropped`] rather than holding up the
+/// sector.
+pub struct AuditLog {
+	config: config::Audit,
+	queue: VecDeque<AuditEntry>,
+	/// Events logged by each player in each category since the last flush, for [`config::Audit::rate_limit`].
+	logged: HashMap<(Id, AuditCategory), u32>,
+	since_flush: f32,
+	/// Events that were lost because the queue was full or the write failed. Shared with the writes in progress.
+	dropped: Arc<AtomicU64>,
+}
+
+impl AuditLog {
+	pub fn new(config: config::Audit) -> Self {
+		Self {
+			config,
+			queue: VecDeque::new(),
+			logged: HashMap::new(),
+			since_flush: 0.0,
+			dropped: Arc::new(AtomicU64::new(0)),
+		}
+	}
+
+	/// Whether a terrain brush of `radius` is big enough to be logged.
+	pub fn covers_brush(&self, radius: f32) -> bool {
+		self.config.enabled && radius >= self.config.terrain_radius
+	}
+
+	/// Queues `event`. If it's the same as one already queued for `player` that is counted again instead, and if
+	/// `player` is over [`config::Audit::rate_limit`] for it's category it's counted as suppressed.
+	pub fn record(&mut self, player: Id, tick: u64, event: AuditEvent) {
+		if !self.config.enabled || self.aggregate(player, &event) {
+			return;
+		}
+
+		let key = (player, event.category());
+		let logged = self.logged.get(&key).copied().unwrap_or_default();
+
+		let event = match self.config.rate_limit {
+			0 => event,
+			limit if logged < limit => event,
+			_ => AuditEvent::Suppressed {
+				category: event.category(),
+			},
+		};
+
+		if matches!(event, AuditEvent::Suppressed { .. }) && self.aggregate(player, &event) {
+			return;
+		}
+
+		if self.queue.len() >= self.config.max_queued {
+			self.dropped.fetch_add(1, Relaxed);
+			return;
+		}
+
+		*self.logged.entry(key).or_default() += 1;
+
+		self.queue.push_back(AuditEntry {
+			player,
+			tick,
+			event,
+			count: 1,
+		});
+	}
+
+	/// Counts `event` against an identical queued entry for `player`, returning `false` if there isn't one.
+	fn aggregate(&mut self, player: Id, event: &AuditEvent) -> bool {
+		let Some(entry) = self
+			.queue
+			.iter_mut()
+			.find(|entry| entry.player == player && entry.event == *event)
+		else {
+			return false;
+		};
+
+		entry.count = entry.count.saturating_add(1);
+		true
+	}
+
+	/// Events lost so far, because the queue was full or a write failed.
+	pub fn dropped(&self) -> u64 {
+		self.dropped.load(Relaxed)
+	}
+
+	pub fn tick(&mut self, delta: f32, sector: &SharedSector) {
+		self.since_flush += delta;
+
+		if self.since_flush >= self.config.flush_interval {
+			self.flush(sector);
+		}
+	}
+
+	/// Starts writing everything queued, and resets the rate limits.
+	pub fn flush(&mut self, sector: &SharedSector) {
+		let entries = self.take();
+
+		if entries.is_empty() {
+			return;
+		}
+
+		let Some(database) = sector.persistence.database().cloned() else {
+			debug!(
+				"Discarding {} audit log entries, there's no database",
+				entries.len()
+			);
+			return;
+		};
+
+		let name = sector.name.clone();
+		let dropped = self.dropped.clone();
+
+		sector.runtime.spawn(async move {
+			if let Err(error) = write(&database, &name, &entries).await {
+				warn!(
+					"Unable to write {} audit log entries: {error}",
+					entries.len()
+				);
+				dropped.fetch_add(entries.len() as u64, Relaxed);
+			}
+		});
+	}
+
+	/// Takes everything queued, and resets the rate limits.
+	fn take(&mut self) -> VecDeque<AuditEntry> {
+		self.since_flush = 0.0;
+		self.logged.clear();
+		take(&mut self.queue)
+	}
+}
+
+/// Appends `entries` to the audit log in one statement.
+pub async fn write(
+	database: &PgPool,
+	sector: &str,
+	entries: &VecDeque<AuditEntry>,
+) -> Result<(), sqlx::Error> {
+	let mut players = Vec::with_capacity(entries.len());
+	let mut ticks = Vec::with_capacity(entries.len());
+	let mut events = Vec::with_capacity(entries.len());
+	let mut counts = Vec::with_capacity(entries.len());
+
+	for entry in entries {
+		players.push(entry.player);
+		ticks.push(entry.tick as i64);
+		events.push(serde_json::to_string(&entry.event).expect("audit events should serialize"));
+		counts.push(entry.count as i32);
+	}
+
+	query!(
+		"INSERT INTO audit_log(sector, player, tick, event, count)
+			SELECT $1, * FROM UNNEST($2::Int8[], $3::Int8[], $4::Text[], $5::Int4[])",
+		sector,
+		&players as _,
+		&ticks,
+		&events,
+		&counts
+	)
+	.execute(database)
+	.await?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::point;
+	use serde_json::json;
+
+	fn id(id: u64) -> Id {
+		id.to_string().parse().unwrap()
+	}
+
+	fn log(rate_limit: u32, max_queued: usize) -> AuditLog {
+		AuditLog::new(config::Audit {
+			rate_limit,
+			max_queued,
+			..Default::default()
+		})
+	}
+
+	fn created(structure: u64) -> AuditEvent {
+		AuditEvent::StructureCreated {
+			structure: id(structure),
+		}
+	}
+
+	/// Each entry's player, event, and count, in the order they were queued.
+	fn queued(log: &mut AuditLog) -> Vec<(Id, AuditEvent, u32)> {
+		log.take()
+			.into_iter()
+			.map(|entry| (entry.player, entry.event, entry.count))
+			.collect()
+	}
+
+	#[test]
+	fn identical_events_are_aggregated() {
+		let mut log = log(20, 1024);
+
+		log.record(id(1), 1, created(10));
+		log.record(id(1), 2, created(10));
+		log.record(id(1), 3, created(11));
+		log.record(id(2), 4, created(10));
+		log.record(id(1), 5, created(10));
+
+		assert_eq!(
+			queued(&mut log),
+			[
+				(id(1), created(10), 3),
+				(id(1), created(11), 1),
+				(id(2), created(10), 1),
+			]
+		);
+	}
+
+	#[test]
+	fn aggregated_entry_keeps_first_tick() {
+		let mut log = log(20, 1024);
+
+		log.record(id(1), 7, created(10));
+		log.record(id(1), 9, created(10));
+
+		let entries = log.take();
+		assert_eq!(entries.len(), 1);
+		assert_eq!(entries[0].tick, 7);
+	}
+
+	#[test]
+	fn events_past_rate_limit_are_suppressed() {
+		let mut log = log(3, 1024);
+
+		for structure in 0..10 {
+			log.record(id(1), structure, created(structure));
+		}
+
+		// Aggregating doesn't count against the limit
+		log.record(id(1), 10, created(0));
+
+		let suppressed = AuditEvent::Suppressed {
+			category: AuditCategory::Structure,
+		};
+
+		assert_eq!(
+			queued(&mut log),
+			[
+				(id(1), created(0), 2),
+				(id(1), created(1), 1),
+				(id(1), created(2), 1),
+				(id(1), suppressed, 7),
+			]
+		);
+	}
+
+	#[test]
+	fn rate_limit_is_per_player_and_category() {
+		let mut log = log(1, 1024);
+
+		log.record(id(1), 0, created(1));
+		log.record(id(2), 0, created(2));
+		log.record(
+			id(1),
+			0,
+			AuditEvent::ItemsGranted {
+				item: Item::TestOre,
+				quantity: 5,
+			},
+		);
+
+		assert!(queued(&mut log)
+			.iter()
+			.all(|(_, event, _)| !matches!(event, AuditEvent::Suppressed { .. })));
+	}
+
+	#[test]
+	fn take_resets_rate_limit() {
+		let mut log = log(1, 1024);
+
+		log.record(id(1), 0, created(1));
+		log.take();
+		log.record(id(1), 1, created(2));
+
+		assert_eq!(queued(&mut log), [(id(1), created(2), 1)]);
+	}
+
+	#[test]
+	fn zero_rate_limit_disables_it() {
+		let mut log = log(0, 1024);
+
+		for structure in 0..100 {
+			log.record(id(1), structure, created(structure));
+		}
+
+		assert_eq!(log.take().len(), 100);
+	}
+
+	#[test]
+	fn full_queue_drops_events() {
+		let mut log = log(0, 2);
+
+		for structure in 0..5 {
+			log.record(id(1), structure, created(structure));
+		}
+
+		// Still aggregated into what's already queued
+		log.record(id(1), 5, created(0));
+
+		assert_eq!(log.dropped(), 3);
+		assert_eq!(
+			queued(&mut log),
+			[(id(1), created(0), 2), (id(1), created(1), 1)]
+		);
+	}
+
+	#[test]
+	fn disabled_log_records_nothing() {
+		let mut log = AuditLog::new(config::Audit {
+			enabled: false,
+			..Default::default()
+		});
+
+		log.record(id(1), 0, created(1));
+
+		assert!(log.take().is_empty());
+		assert!(!log.covers_brush(f32::INFINITY));
+	}
+
+	#[test]
+	fn only_large_brushes_are_covered() {
+		let log = log(20, 1024);
+
+		assert!(!log.covers_brush(3.9));
+		assert!(log.covers_brush(4.0));
+		assert!(log.covers_brush(10.0));
+	}
+
+	#[test]
+	fn events_serialize_stably() {
+		let cases = [
+			(
+				created(1),
+				json!({ "type": "structure_created", "structure": 1 }),
+			),
+			(
+				AuditEvent::StructureDeleted { structure: id(2) },
+				json!({ "type": "structure_deleted", "structure": 2 }),
+			),
+			(
+				AuditEvent::TerrainModified {
+					center: VoxjectPosition::new(id(3), point![1.0, -2.0, 0.5]),
+					radius: 8.0,
+					mode: BrushMode::Remove,
+				},
+				json!({
+					"type": "terrain_modified",
+					"center": { "voxject": 3, "position": [1.0, -2.0, 0.5] },
+					"radius": 8.0,
+					"mode": "Remove",
+				}),
+			),
+			(
+				AuditEvent::ItemsGranted {
+					item: Item::TestOre,
+					quantity: -4,
+				},
+				json!({ "type": "items_granted", "item": "TestOre", "quantity": -4 }),
+			),
+			(
+				AuditEvent::AdminCommand {
+					command: "give 1 test_ore 4".into(),
+					succeeded: true,
+				},
+				json!({ "type": "admin_command", "command": "give 1 test_ore 4", "succeeded": true }),
+			),
+			(
+				AuditEvent::Suppressed {
+					category: AuditCategory::Terrain,
+				},
+				json!({ "type": "suppressed", "category": "terrain" }),
+			),
+		];
+
+		for (event, expected) in cases {
+			assert_eq!(serde_json::to_value(&event).unwrap(), expected);
+		}
+	}
+}
@@ -0,0 +1,234 @@
+use solarscape_shared::{
+	data::Id,
+	message::clientbound::{ChatHistory, ChatMessage},
+};
+use sqlx::{query, PgPool};
+use std::collections::VecDeque;
+
+/// The sector's most recent chat messages, for showing to players as they join.
+pub struct Chat {
+	history: VecDeque<ChatMessage>,
+	/// How many messages are kept, none are if `0`.
+	capacity: usize,
+	next_sequence: u64,
+}
+
+impl Chat {
+	pub fn new(capacity: usize) -> Self {
+		Self {
+			history: VecDeque::with_capacity(capacity),
+			capacity,
+			next_sequence: 0,
+		}
+	}
+
+	/// Restores history loaded with [`load`], sequence numbers carry on from the newest message.
+	pub fn restore(capacity: usize, mut history: Vec<ChatMessage>) -> Self {
+		history.sort_by_key(|message| message.sequence);
+
+		let next_sequence = history.last().map_or(0, |message| message.sequence + 1);
+
+		// The capacity may have been lowered since the history was saved
+		let excess = history.len().saturating_sub(capacity);
+
+		Self {
+			history: history.into_iter().skip(excess).collect(),
+			capacity,
+			next_sequence,
+		}
+	}
+
+	pub fn capacity(&self) -> usize {
+		self.capacity
+	}
+
+	/// Adds a message to the history, dropping the oldest message if it's full. Returns the message to send to
+	/// players.
+	pub fn push(&mut self, player: Id, text: Box<str>) -> ChatMessage {
+		let message = ChatMessage {
+			sequence: self.next_sequence,
+			player,
+			text,
+		};
+
+		self.next_sequence += 1;
+
+		if self.capacity > 0 {
+			if self.history.len() == self.capacity {
+				self.history.pop_front();
+			}
+
+			self.history.push_back(message.clone());
+		}
+
+		message
+	}
+
+	/// The sequence number of `player`'s most recent message still in the history.
+	pub fn latest_from(&self, player: Id) -> Option<u64> {
+		self.history
+			.iter()
+			.rev()
+			.find(|message| message.player == player)
+			.map(|message| message.sequence)
+	}
+
+	/// Removes a message from the history, returning `false` if it isn't in the history.
+	pub fn retract(&mut self, sequence: u64) -> bool {
+		let Ok(index) = self
+			.history
+			.binary_search_by_key(&sequence, |message| message.sequence)
+		else {
+			return false;
+		};
+
+		self.history.remove(index);
+		true
+	}
+
+	pub fn build_sync(&self) -> ChatHistory {
+		ChatHistory(self.history.iter().cloned().collect())
+	}
+}
+
+/// Loads the sector's most recent `capacity` messages.
+pub async fn load(
+	database: &PgPool,
+	sector: &str,
+	capacity: usize,
+) -> Result<Vec<ChatMessage>, sqlx::Error> {
+	Ok(query!(
+		r#"SELECT sequence, player AS "player: Id", text FROM chat_history
+			WHERE sector = $1 ORDER BY sequence DESC LIMIT $2"#,
+		sector,
+		capacity as i64
+	)
+	.fetch_all(database)
+	.await?
+	.into_iter()
+	.map(|record| ChatMessage {
+		sequence: record.sequence as u64,
+		player: record.player,
+		text: record.text.into(),
+	})
+	.collect())
+}
+
+/// Writes `message`, and deletes any messages which have since fallen out of the most recent `capacity`.
+pub async fn write(
+	database: &PgPool,
+	sector: &str,
+	capacity: usize,
+	message: &ChatMessage,
+) -> Result<(), sqlx::Error> {
+	let mut transaction = database.begin().await?;
+
+	query!(
+		"INSERT INTO chat_history(sector, sequence, player, text) VALUES ($1, $2, $3, $4)",
+		sector,
+		message.sequence as i64,
+		message.player as _,
+		&*message.text
+	)
+	.execute(&mut *transaction)
+	.await?;
+
+	query!(
+		"DELETE FROM chat_history WHERE sector = $1 AND sequence <= $2",
+		sector,
+		message.sequence as i64 - capacity as i64
+	)
+	.execute(&mut *transaction)
+	.await?;
+
+	transaction.commit().await
+}
+
+pub async fn retract(database: &PgPool, sector: &str, sequence: u64) -> Result<(), sqlx::Error> {
+	query!(
+		"DELETE FROM chat_history WHERE sector = $1 AND sequence = $2",
+		sector,
+		sequence as i64
+	)
+	.execute(database)
+	.await?;
+
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn sequences(chat: &Chat) -> Vec<u64> {
+		chat.build_sync()
+			.0
+			.iter()
+			.map(|message| message.sequence)
+			.collect()
+	}
+
+	#[test]
+	fn full_history_drops_the_oldest_message() {
+		let mut chat = Chat::new(3);
+
+		for (sequence, text) in ["a", "b", "c", "d"].into_iter().enumerate() {
+			assert_eq!(chat.push(Id::new(), text.into()).sequence, sequence as u64);
+		}
+
+		assert_eq!(sequences(&chat), [1, 2, 3]);
+		assert_eq!(&*chat.build_sync().0[0].text, "b");
+	}
+
+	#[test]
+	fn no_history_is_kept_without_capacity() {
+		let mut chat = Chat::new(0);
+
+		assert_eq!(chat.push(Id::new(), "a".into()).sequence, 0);
+		assert_eq!(chat.push(Id::new(), "b".into()).sequence, 1);
+		assert!(sequences(&chat).is_empty());
+	}
+
+	#[test]
+	fn retracted_messages_leave_the_history() {
+		let mut chat = Chat::new(10);
+		let (player, other) = (Id::new(), Id::new());
+
+		chat.push(player, "first".into());
+		chat.push(other, "second".into());
+		chat.push(player, "third".into());
+
+		assert_eq!(chat.latest_from(player), Some(2));
+		assert!(chat.retract(2));
+		assert!(!chat.retract(2));
+		assert_eq!(chat.latest_from(player), Some(0));
+		assert_eq!(sequences(&chat), [0, 1]);
+
+		// Sequence numbers aren't reused
+		assert_eq!(chat.push(other, "fourth".into()).sequence, 3);
+		assert!(chat.latest_from(Id::new()).is_none());
+	}
+
+	#[test]
+	fn restored_history_carries_on() {
+		let message = |sequence| ChatMessage {
+			sequence,
+			player: Id::new(),
+			text: sequence.to_string().into(),
+		};
+
+		// Loaded newest first, and from when the capacity was larger
+		let mut chat = Chat::restore(2, vec![message(7), message(5), message(6)]);
+
+		assert_eq!(sequences(&chat), [6, 7]);
+		assert_eq!(chat.push(Id::new(), "next".into()).sequence, 8);
+		assert_eq!(sequences(&chat), [7, 8]);
+
+		assert_eq!(
+			Chat::restore(5, vec![])
+				.push(Id::new(), "a".into())
+				.sequence,
+			0
+		);
+	}
+}
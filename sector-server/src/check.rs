@@ -0,0 +1,239 @@
+use crate::{config, sector::Voxject};
+use log::{error, info};
+use nalgebra::vector;
+use solarscape_shared::data::world::{ChunkCoordinates, Level};
+use sqlx::{postgres::PgConnectOptions, query_scalar};
+use std::{
+	fmt::{self, Display, Formatter},
+	net::{SocketAddr, TcpListener},
+	panic::catch_unwind,
+	path::PathBuf,
+	process::ExitCode,
+};
+use tokio::runtime::Runtime;
+
+/// Tables the sector server uses, which should have been created by the migrations.
+const REQUIRED_TABLES: &[&str] = &[
+	"players",
+	"items",
+	"inventories",
+	"inventory_items",
+	"chunks",
+	"pending_connections",
+	"sector_status",
+	"audit_log",
+	"player_transfers",
+];
+
+/// Checks that the sector server could start, without starting it. Every check is run even if an earlier one fails,
+/// so that a single run reports everything that needs fixing.
+pub fn run(
+	config_path: Option<PathBuf>,
+	postgres: PgConnectOptions,
+	address: SocketAddr,
+) -> ExitCode {
+	let mut report = Report::default();
+
+	let config = config::load_config(config_path);
+	report.push(
+		"config",
+		match &config {
+			Ok(config) => Ok(format!(
+				"sector {} with {} voxjects",
+				config.name,
+				config.voxjects.len()
+			)),
+			Err(error) => Err(error.to_string()),
+		},
+	);
+
+	report.push("database", check_database(postgres));
+
+	report.push(
+		"address",
+		TcpListener::bind(address)
+			.map(|_| format!("{address} is available"))
+			.map_err(|error| format!("unable to bind {address}: {error}")),
+	);
+
+	if let Ok(config) = config {
+		for voxject in config.voxjects {
+			let name = voxject.name.clone();
+			report.push("generation", check_generation(voxject).map(|_| name.into()));
+		}
+	}
+
+	report.log();
+
+	match report.passed() {
+		true => ExitCode::SUCCESS,
+		false => ExitCode::FAILURE,
+	}
+}
+
+fn check_database(postgres: PgConnectOptions) -> Result<String, String> {
+	let runtime = Runtime::new().map_err(|error| error.to_string())?;
+
+	runtime.block_on(async {
+		let database = crate::connect_database(postgres)
+			.await
+			.map_err(|error| format!("unable to connect: {error}"))?;
+
+		let mut missing = vec![];
+
+		for table in REQUIRED_TABLES {
+			let exists = query_scalar!(r#"SELECT to_regclass($1) IS NOT NULL AS "exists!""#, table)
+				.fetch_one(&database)
+				.await
+				.map_err(|error| error.to_string())?;
+
+			if !exists {
+				missing.push(*table);
+			}
+		}
+
+		match missing.is_empty() {
+			true => Ok(format!(
+				"connected, all {} tables exist",
+				REQUIRED_TABLES.len()
+			)),
+			false => Err(format!(
+				"missing tables {}, have the migrations been run?",
+				missing.join(", ")
+			)),
+		}
+	})
+}
+
+/// Generates the center chunk of the voxject, to catch generators that panic with the configured parameters or
+/// produce densities that can't be meshed.
+fn check_generation(voxject: config::Voxject) -> Result<(), String> {
+	// Generation doesn't depend on the LOD curve
+	let (id, voxject) = Voxject::new(voxject, &config::Lod::default());
+	let coordinates = ChunkCoordinates::new(id, vector![0, 0, 0], Level::new(0));
+
+	let data = catch_unwind(|| (voxject.generator)(&coordinates))
+		.map_err(|_| format!("generating voxject {} panicked", voxject.name))?;
+
+	let non_finite = data
+		.iter_voxels()
+		.find(|(_, _, density)| !density.is_finite());
+
+	match non_finite {
+		Some((cell, _, density)) => Err(format!(
+			"voxject {} generated a density of {density} at {}, {}, {}",
+			voxject.name, cell.x, cell.y, cell.z
+		)),
+		None => Ok(()),
+	}
+}
+
+#[derive(Default)]
+struct Report {
+	checks: Vec<Check>,
+}
+
+struct Check {
+	name: &'static str,
+	result: Result<String, String>,
+}
+
+impl Report {
+	fn push(&mut self, name: &'static str, result: Result<String, String>) {
+		self.checks.push(Check { name, result });
+	}
+
+	fn failures(&self) -> usize {
+		self.checks
+			.iter()
+			.filter(|check| check.result.is_err())
+			.count()
+	}
+
+	fn passed(&self) -> bool {
+		self.failures() == 0
+	}
+
+	fn log(&self) {
+		for check in &self.checks {
+			match &check.result {
+				Ok(_) => info!(check = check.name; "{check}"),
+				Err(_) => error!(check = check.name; "{check}"),
+			}
+		}
+
+		match self.passed() {
+			true => info!("{self}"),
+			false => error!("{self}"),
+		}
+	}
+}
+
+impl Display for Check {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		match &self.result {
+			Ok(detail) => write!(formatter, "[ok] {}: {detail}", self.name),
+			Err(reason) => write!(formatter, "[failed] {}: {reason}", self.name),
+		}
+	}
+}
+
+impl Display for Report {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		match self.failures() {
+			0 => write!(formatter, "All {} checks passed", self.checks.len()),
+			failures => write!(
+				formatter,
+				"{failures} of {} checks failed",
+				self.checks.len()
+			),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn report(results: Vec<Result<&str, &str>>) -> Report {
+		let mut report = Report::default();
+
+		for result in results {
+			report.push("test", result.map(Into::into).map_err(Into::into));
+		}
+
+		report
+	}
+
+	#[test]
+	fn checks_are_formatted_with_their_result() {
+		let report = report(vec![Ok("fine"), Err("broken")]);
+
+		assert_eq!(report.checks[0].to_string(), "[ok] test: fine");
+		assert_eq!(report.checks[1].to_string(), "[failed] test: broken");
+	}
+
+	#[test]
+	fn every_failure_is_counted() {
+		let passed = report(vec![Ok("a"), Ok("b")]);
+		assert!(passed.passed());
+		assert_eq!(passed.to_string(), "All 2 checks passed");
+
+		let failed = report(vec![Err("a"), Ok("b"), Err("c")]);
+		assert!(!failed.passed());
+		assert_eq!(failed.failures(), 2);
+		assert_eq!(failed.to_string(), "2 of 3 checks failed");
+
+		assert!(Report::default().passed());
+	}
+
+	#[test]
+	fn default_voxjects_generate() {
+		let voxject = config::Voxject {
+			name: "test".into(),
+			lod: None,
+		};
+
+		assert_eq!(check_generation(voxject), Ok(()));
+	}
+}
@@ -0,0 +1,919 @@
+use crate::{
+	audit::AuditEvent,
+	inventory::Inventory,
+	player::Player,
+	sector::{Sector, COLLISION_BUILDS},
+};
+use log::info;
+use nalgebra::{point, Point3, Vector3};
+use solarscape_shared::{
+	command::tokenize,
+	consts::CHUNK_SIZE,
+	data::{world::Item, Id},
+	message::{
+		backend::GatewayStats,
+		clientbound::{LocalizedText, Severity, Teleport},
+	},
+};
+use sqlx::query_scalar;
+use std::{collections::BTreeMap, sync::atomic::Ordering::Relaxed, time::Duration};
+use thiserror::Error;
+use tokio::runtime::Handle;
+
+/// What a player is allowed to do, each command requires one of these.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Permission {
+	Player,
+	Admin,
+}
+
+type Handler = fn(&mut Sector, Id, &mut Args) -> Result<String, CommandError>;
+
+pub struct Command {
+	pub name: &'static str,
+	/// Each way the command can be used, without the name, such as `<x> <y> <z>`.
+	pub usages: &'static [&'static str],
+	pub description: &'static str,
+	pub permission: Permission,
+
+	/// Returns the reply to send to the player.
+	handler: Handler,
+}
+
+impl Command {
+	pub fn usage(&self) -> String {
+		self.usages
+			.iter()
+			.map(|usage| match usage.is_empty() {
+				true => format!("/{}", self.name),
+				false => format!("/{} {usage}", self.name),
+			})
+			.collect::<Vec<_>>()
+			.join(" or ")
+	}
+}
+
+/// Commands players can run by sending a chat message starting with `/`.
+#[derive(Default)]
+pub struct CommandRegistry {
+	commands: BTreeMap<&'static str, Command>,
+}
+
+impl CommandRegistry {
+	/// A registry with every built in command.
+	pub fn builtin() -> Self {
+		let mut registry = Self::default();
+
+		registry.register(Command {
+			name: "help",
+			usages: &[""],
+			description: "Lists the commands you can use",
+			permission: Permission::Player,
+			handler: help,
+		});
+
+		registry.register(Command {
+			name: "tp",
+			usages: &["<x> <y> <z>", "<player>"],
+			description: "Teleports you to a position or a player, ~ makes a coordinate relative",
+			permission: Permission::Player,
+			handler: tp,
+		});
+
+		registry.register(Command {
+			name: "give",
+			usages: &["<item> <count>"],
+			description: "Adds items to your inventory",
+			permission: Permission::Admin,
+			handler: give,
+		});
+
+		registry.register(Command {
+			name: "where",
+			usages: &[""],
+			description: "Shows your position and which chunk you're in",
+			permission: Permission::Player,
+			handler: r#where,
+		});
+
+		registry.register(Command {
+			name: "status",
+			usages: &[""],
+			description: "Shows the state of the sector and the gateway",
+			permission: Permission::Admin,
+			handler: status,
+		});
+
+		registry.register(Command {
+			name: "snapshot",
+			usages: &[""],
+			description:
+				"Writes the sector's state to it's snapshot file, for moving it to a new process",
+			permission: Permission::Admin,
+			handler: snapshot,
+		});
+
+		registry.register(Command {
+			name: "record",
+			usages: &["<player> start", "<player> stop"],
+			description:
+				"Starts or stops recording the messages a player sends, for replaying later",
+			permission: Permission::Admin,
+			handler: record,
+		});
+
+		registry.register(Command {
+			name: "retract",
+			usages: &["<player>"],
+			description: "Removes a player's most recent chat message from everyone's chat",
+			permission: Permission::Admin,
+			handler: retract,
+		});
+
+		registry.register(Command {
+			name: "rollback",
+			usages: &["<player> <duration>"],
+			description:
+				"Undoes a player's terrain edits from the last while, leaving cells changed since alone",
+			permission: Permission::Admin,
+			handler: rollback,
+		});
+
+		registry.register(Command {
+			name: "transfer",
+			usages: &["<player> <sector>"],
+			description: "Sends a player to another sector, where they arrive where they were here",
+			permission: Permission::Admin,
+			handler: transfer,
+		});
+
+		registry.register(Command {
+			name: "players",
+			usages: &[""],
+			description: "Lists the players in this sector",
+			permission: Permission::Player,
+			handler: players,
+		});
+
+		registry
+	}
+
+	/// # Panics
+	/// If a command with the same name has already been registered.
+	pub fn register(&mut self, command: Command) {
+		let name = command.name;
+
+		if self.commands.insert(name, command).is_some() {
+			panic!("command /{name} was registered twice");
+		}
+	}
+
+	/// Finds the command `text` is for, and splits the rest into it's arguments. `text` is everything after the `/`.
+	pub fn parse(
+		&self,
+		text: &str,
+		permission: Permission,
+	) -> Result<(&Command, Args), CommandError> {
+		let mut tokens = tokenize(text)
+			.map_err(|_| CommandError::UnclosedQuote)?
+			.into_iter();
+		let name = tokens.next().unwrap_or_default();
+
+		let Some(command) = self.commands.get(name.as_str()) else {
+			return Err(CommandError::Unknown(name));
+		};
+
+		if permission < command.permission {
+			return Err(CommandError::Forbidden(command.name));
+		}
+
+		Ok((
+			command,
+			Args {
+				tokens: tokens.collect(),
+				position: 0,
+			},
+		))
+	}
+
+	/// Commands a player with `permission` is allowed to run, in alphabetical order.
+	pub fn available(&self, permission: Permission) -> impl Iterator<Item = &Command> {
+		self.commands
+			.values()
+			.filter(move |command| command.permission <= permission)
+	}
+}
+
+/// Runs the command in `text` for `player`, replying to them alone with the result. `text` is everything after the `/`.
+pub fn run(sector: &mut Sector, player: Id, text: &str) {
+	info!(player_id:% = player; "Player {player} ran /{text}");
+
+	let permission = sector.permission(player);
+
+	let result = match sector.commands.parse(text, permission) {
+		Ok((command, mut args)) => {
+			let (handler, usage) = (command.handler, command.usage());
+			let audited = command.permission == Permission::Admin;
+
+			let result =
+				handler(sector, player, &mut args).map_err(|error| match error.is_usage() {
+					true => CommandError::Usage(Box::new(error), usage),
+					false => error,
+				});
+
+			if audited {
+				sector.audit.record(
+					player,
+					sector.ticks,
+					AuditEvent::AdminCommand {
+						command: text.into(),
+						succeeded: result.is_ok(),
+					},
+				);
+			}
+
+			result
+		}
+		Err(error) => Err(error),
+	};
+
+	match result {
+		// Commands are for admins, so their replies aren't translated
+		Ok(reply) => sector.send_system_message(
+			player,
+			LocalizedText::new("server.command_reply").with("reply", reply),
+			Severity::Info,
+		),
+		Err(error) => sector.send_system_message(
+			player,
+			LocalizedText::new("server.command_failed").with("error", error.to_string()),
+			Severity::Warning,
+		),
+	}
+}
+
+/// A command's arguments, which are taken in order as typed values.
+pub struct Args {
+	tokens: Vec<String>,
+	position: usize,
+}
+
+impl Args {
+	/// Arguments which haven't been taken yet.
+	pub fn remaining(&self) -> usize {
+		self.tokens.len() - self.position
+	}
+
+	/// Takes the next argument, `name` is used in error messages.
+	pub fn next<T: Arg>(&mut self, name: &'static str) -> Result<T, CommandError> {
+		let token = self
+			.tokens
+			.get(self.position)
+			.ok_or(CommandError::Missing(name))?;
+
+		let value = T::parse(token).ok_or_else(|| CommandError::Invalid {
+			name,
+			value: token.clone(),
+			expected: T::EXPECTED,
+		})?;
+
+		self.position += 1;
+
+		Ok(value)
+	}
+
+	/// Fails if there are any arguments which haven't been taken.
+	pub fn finish(&self) -> Result<(), CommandError> {
+		match self.tokens.get(self.position) {
+			Some(token) => Err(CommandError::Unexpected(token.clone())),
+			None => Ok(()),
+		}
+	}
+}
+
+/// A type commands can take as an argument.
+pub trait Arg: Sized {
+	/// Describes what's expected, such as "a number", for error messages.
+	const EXPECTED: &'static str;
+
+	fn parse(token: &str) -> Option<Self>;
+}
+
+impl Arg for i64 {
+	const EXPECTED: &'static str = "a whole number";
+
+	fn parse(token: &str) -> Option<Self> {
+		token.parse().ok()
+	}
+}
+
+impl Arg for f32 {
+	const EXPECTED: &'static str = "a number";
+
+	fn parse(token: &str) -> Option<Self> {
+		token.parse().ok().filter(|value: &f32| value.is_finite())
+	}
+}
+
+impl Arg for String {
+	const EXPECTED: &'static str = "some text";
+
+	fn parse(token: &str) -> Option<Self> {
+		Some(token.to_string())
+	}
+}
+
+/// Whether to start or stop something.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Toggle {
+	Start,
+	Stop,
+}
+
+impl Arg for Toggle {
+	const EXPECTED: &'static str = "start or stop";
+
+	fn parse(token: &str) -> Option<Self> {
+		match token {
+			"start" => Some(Self::Start),
+			"stop" => Some(Self::Stop),
+			_ => None,
+		}
+	}
+}
+
+impl Arg for Item {
+	const EXPECTED: &'static str = "an item name, such as test_ore";
+
+	fn parse(token: &str) -> Option<Self> {
+		Item::ALL.iter().find(|item| item.name() == token).copied()
+	}
+}
+
+impl Arg for Duration {
+	const EXPECTED: &'static str = "a duration, such as 30s, 10m or 1h";
+
+	fn parse(token: &str) -> Option<Self> {
+		let (amount, unit) = token.split_at(token.find(|c: char| !c.is_ascii_digit())?);
+
+		let seconds = match unit {
+			"s" => 1,
+			"m" => 60,
+			"h" => 60 * 60,
+			"d" => 24 * 60 * 60,
+			_ => return None,
+		};
+
+		Some(Duration::from_secs(
+			amount.parse::<u64>().ok()?.checked_mul(seconds)?,
+		))
+	}
+}
+
+/// A coordinate which is either absolute, or relative to the player's current position when written with a `~`
+/// prefix, `~` alone being their current position.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Coordinate {
+	Absolute(f32),
+	Relative(f32),
+}
+
+impl Coordinate {
+	pub fn resolve(self, current: f32) -> f32 {
+		match self {
+			Self::Absolute(value) => value,
+			Self::Relative(offset) => current + offset,
+		}
+	}
+}
+
+impl Arg for Coordinate {
+	const EXPECTED: &'static str = "a number, or ~ followed by an optional offset";
+
+	fn parse(token: &str) -> Option<Self> {
+		match token.strip_prefix('~') {
+			Some("") => Some(Self::Relative(0.0)),
+			Some(offset) => f32::parse(offset).map(Self::Relative),
+			None => f32::parse(token).map(Self::Absolute),
+		}
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum CommandError {
+	#[error("Unknown command /{0}, try /help")]
+	Unknown(String),
+
+	#[error("You don't have permission to use /{0}")]
+	Forbidden(&'static str),
+
+	#[error("Unclosed quote")]
+	UnclosedQuote,
+
+	#[error("Missing <{0}>")]
+	Missing(&'static str),
+
+	#[error("`{value}` isn't a valid <{name}>, expected {expected}")]
+	Invalid {
+		name: &'static str,
+		value: String,
+		expected: &'static str,
+	},
+
+	#[error("Unexpected `{0}`")]
+	Unexpected(String),
+
+	/// Wraps an error from the arguments with how the command should be used.
+	#[error("{0}\nUsage: {1}")]
+	Usage(Box<CommandError>, String),
+
+	/// The command was used correctly, but couldn't be done.
+	#[error("{0}")]
+	Failed(String),
+}
+
+impl CommandError {
+	/// Whether this was caused by the arguments, so the command's usage should be shown with it.
+	pub fn is_usage(&self) -> bool {
+		matches!(
+			self,
+			Self::Missing(_) | Self::Invalid { .. } | Self::Unexpected(_)
+		)
+	}
+}
+
+fn connected_player(sector: &mut Sector, id: Id) -> Result<&mut Player, CommandError> {
+	sector
+		.players
+		.iter_mut()
+		.find(|player| player.id == id)
+		.ok_or_else(|| CommandError::Failed("You aren't connected to this sector".into()))
+}
+
+/// Finds a connected player by username or id.
+fn find_player(sector: &Sector, name: &str) -> Result<Id, CommandError> {
+	lookup_player(sector, name)?
+		.filter(|id| sector.players.iter().any(|player| player.id == *id))
+		.ok_or_else(|| CommandError::Failed(format!("{name} isn't in this sector")))
+}
+
+/// Finds a player by id or username, whether or not they're in this sector.
+fn lookup_player(sector: &Sector, name: &str) -> Result<Option<Id>, CommandError> {
+	Ok(match name.parse() {
+		Ok(id) => Some(id),
+		// Without a database players can only be found by id
+		Err(_) => match sector.shared.persistence.database() {
+			Some(database) => Handle::current()
+				.block_on(
+					query_scalar!(
+						r#"SELECT id AS "id: Id" FROM players WHERE username = $1"#,
+						name
+					)
+					.fetch_optional(database),
+				)
+				.map_err(|error| {
+					CommandError::Failed(format!("Unable to look up {name}: {error}"))
+				})?,
+			None => None,
+		},
+	})
+}
+
+fn help(sector: &mut Sector, player: Id, args: &mut Args) -> Result<String, CommandError> {
+	args.finish()?;
+
+	Ok(sector
+		.commands
+		.available(sector.permission(player))
+		.map(|command| format!("{} - {}", command.usage(), command.description))
+		.collect::<Vec<_>>()
+		.join("\n"))
+}
+
+/// Where `/tp` sends the player.
+#[derive(Debug, PartialEq)]
+enum TpTarget {
+	Player(String),
+	Position([Coordinate; 3]),
+}
+
+impl TpTarget {
+	/// Takes every argument, a player if there's only one, otherwise a position.
+	fn parse(args: &mut Args) -> Result<Self, CommandError> {
+		let target = match args.remaining() {
+			1 => Self::Player(args.next("player")?),
+			_ => Self::Position([args.next("x")?, args.next("y")?, args.next("z")?]),
+		};
+
+		args.finish()?;
+
+		Ok(target)
+	}
+}
+
+fn tp(sector: &mut Sector, player: Id, args: &mut Args) -> Result<String, CommandError> {
+	let position = match TpTarget::parse(args)? {
+		TpTarget::Player(name) => {
+			let target = find_player(sector, &name)?;
+			connected_player(sector, target)?.location.position
+		}
+		TpTarget::Position([x, y, z]) => {
+			let current = connected_player(sector, player)?.location.position;
+			point![
+				x.resolve(current.x),
+				y.resolve(current.y),
+				z.resolve(current.z)
+			]
+		}
+	};
+
+	let player = connected_player(sector, player)?;
+	player.location.position = position;
+	player.send(Teleport { position });
+
+	Ok(format!(
+		"Teleported to {:.1} {:.1} {:.1}",
+		position.x, position.y, position.z
+	))
+}
+
+fn give(sector: &mut Sector, player: Id, args: &mut Args) -> Result<String, CommandError> {
+	let item = args.next::<Item>("item")?;
+	let count = args.next::<i64>("count")?;
+	args.finish()?;
+
+	if count < 1 {
+		return Err(CommandError::Failed("Count must be at least 1".into()));
+	}
+
+	let inventory = Inventory::new(
+		player,
+		sector.inventory_capacity,
+		&sector.shared.persistence,
+	);
+
+	let added = inventory
+		.add(item, count)
+		.map_err(|error| CommandError::Failed(format!("Unable to add items: {error}")))?;
+
+	let sync = inventory.build_sync();
+	let recipient = connected_player(sector, player)?;
+	recipient.inventory_synced = true;
+	recipient.send(sync);
+
+	if added > 0 {
+		sector.audit.record(
+			player,
+			sector.ticks,
+			AuditEvent::ItemsGranted {
+				item,
+				quantity: added,
+			},
+		);
+	}
+
+	Ok(match added < count {
+		true => format!(
+			"Gave you {added} {}, your inventory didn't have space for the other {}",
+			item.display_name(),
+			count - added
+		),
+		false => format!("Gave you {added} {}", item.display_name()),
+	})
+}
+
+fn r#where(sector: &mut Sector, player: Id, args: &mut Args) -> Result<String, CommandError> {
+	args.finish()?;
+
+	let position: Point3<f32> = connected_player(sector, player)?.location.position;
+	let chunk: Vector3<i32> =
+		(position.coords / CHUNK_SIZE as f32).map(|coordinate| coordinate.floor() as i32);
+
+	Ok(format!(
+		"You're at {:.1} {:.1} {:.1}, in level 0 chunk {} {} {}",
+		position.x, position.y, position.z, chunk.x, chunk.y, chunk.z
+	))
+}
+
+fn status(sector: &mut Sector, _: Id, args: &mut Args) -> Result<String, CommandError> {
+	args.finish()?;
+
+	let mut lines = vec![format!(
+		"Sector {}: {} players, {} structures",
+		sector.shared.name,
+		match sector.max_players {
+			0 => sector.players.len().to_string(),
+			max => format!("{}/{max}", sector.players.len()),
+		},
+		sector.structures.len()
+	)];
+
+	lines.push(format!(
+		"Collision rebuilds skipped: {} cancelled, {} abandoned",
+		COLLISION_BUILDS.cancelled.load(Relaxed),
+		COLLISION_BUILDS.abandoned.load(Relaxed)
+	));
+
+	let (generating, parked) = sector.shared.generation_queue.counts();
+	lines.push(format!(
+		"Chunk generation: {generating} running, {parked} waiting"
+	));
+
+	lines.push(format!(
+		"Interest radius: {}, {} structure messages suppressed",
+		match sector.interest_radius.is_finite() {
+			true => format!("{}m", sector.interest_radius),
+			false => String::from("unlimited"),
+		},
+		sector.interest_suppressed
+	));
+
+	lines.push(format!(
+		"Away: {} players, {} disconnected for being away too long",
+		sector
+			.players
+			.iter()
+			.filter(|player| player.idle.is_away())
+			.count(),
+		sector.idle_kicks
+	));
+
+	lines.push(format!(
+		"Audit log: {} events dropped",
+		sector.audit.dropped()
+	));
+
+	if sector.gateway_reports.is_empty() {
+		lines.push(String::from(
+			"Gateway: no reports yet, is a gateway running?",
+		));
+	}
+
+	for report in &sector.gateway_reports {
+		let GatewayStats {
+			period,
+			token_requests,
+			token_successes,
+			token_failures,
+			connect_requests,
+		} = &report.stats;
+
+		lines.push(format!(
+			"Gateway {}{}, reported {:.0?} ago, covering {period}s:",
+			report.gateway,
+			match report.is_stale() {
+				true => " (STALE)",
+				false => "",
+			},
+			report.age()
+		));
+
+		lines.push(format!(
+			"  Tokens: {token_requests} requested, {token_successes} issued, {} incorrect password, {} unknown account, \
+			{} internal errors",
+			token_failures.incorrect_password,
+			token_failures.account_does_not_exist,
+			token_failures.internal
+		));
+
+		lines.push(format!(
+			"  Connects here: {}",
+			connect_requests
+				.get(&*sector.shared.name)
+				.copied()
+				.unwrap_or_default()
+		));
+	}
+
+	Ok(lines.join("\n"))
+}
+
+fn players(sector: &mut Sector, _: Id, args: &mut Args) -> Result<String, CommandError> {
+	args.finish()?;
+
+	let ids: Vec<Id> = sector.players.iter().map(|player| player.id).collect();
+
+	let names = Handle::current().block_on(async {
+		let mut names = Vec::with_capacity(ids.len());
+
+		for id in &ids {
+			let username = match sector.shared.persistence.database() {
+				Some(database) => {
+					query_scalar!("SELECT username FROM players WHERE id = $1", id as _)
+						.fetch_optional(database)
+						.await?
+				}
+				None => None,
+			};
+
+			names.push(username.unwrap_or_else(|| id.to_string()));
+		}
+
+		Ok::<_, sqlx::Error>(names)
+	});
+
+	let names = names
+		.map_err(|error| CommandError::Failed(format!("Unable to look up players: {error}")))?;
+
+	Ok(format!("{} players: {}", names.len(), names.join(", ")))
+}
+
+fn retract(sector: &mut Sector, _: Id, args: &mut Args) -> Result<String, CommandError> {
+	let name = args.next::<String>("player")?;
+	args.finish()?;
+
+	let sequence = lookup_player(sector, &name)?
+		.and_then(|target| sector.chat.latest_from(target))
+		.ok_or_else(|| {
+			CommandError::Failed(format!("{name} has no messages in the chat history"))
+		})?;
+
+	sector.retract_chat_message(sequence);
+
+	Ok(format!("Retracted {name}'s most recent message"))
+}
+
+fn rollback(sector: &mut Sector, _: Id, args: &mut Args) -> Result<String, CommandError> {
+	let name = args.next::<String>("player")?;
+	let duration = args.next::<Duration>("duration")?;
+	args.finish()?;
+
+	if !sector.terrain_journal.is_enabled() {
+		return Err(CommandError::Failed(String::from(
+			"The terrain journal is disabled, set `terrain_journal.window` in the sector config to enable it",
+		)));
+	}
+
+	let target = lookup_player(sector, &name)?
+		.ok_or_else(|| CommandError::Failed(format!("There's no player called {name}")))?;
+
+	let report = sector.rollback(target, duration);
+
+	if report.strokes == 0 {
+		return Err(CommandError::Failed(format!(
+			"{name} has no terrain edits to roll back from then"
+		)));
+	}
+
+	Ok(format!(
+		"Rolled back {} terrain edits by {name}, restoring {} cells and skipping {} changed since",
+		report.strokes, report.restored, report.conflicts
+	))
+}
+
+fn transfer(sector: &mut Sector, _: Id, args: &mut Args) -> Result<String, CommandError> {
+	let name = args.next::<String>("player")?;
+	let destination = args.next::<String>("sector")?;
+	args.finish()?;
+
+	let target = find_player(sector, &name)?;
+
+	sector
+		.transfer(target, &destination)
+		.map_err(|error| CommandError::Failed(format!("Unable to transfer {name}: {error}")))?;
+
+	Ok(format!("Sent {name} to {destination}"))
+}
+
+fn snapshot(sector: &mut Sector, player: Id, args: &mut Args) -> Result<String, CommandError> {
+	args.finish()?;
+
+	match sector.request_snapshot(player) {
+		true => Ok(String::from(
+			"Taking a snapshot, you'll be told once it's been written",
+		)),
+		false => Err(CommandError::Failed(String::from(
+			"Snapshots are disabled, set `snapshot_path` in the sector config to enable them",
+		))),
+	}
+}
+
+fn record(sector: &mut Sector, _: Id, args: &mut Args) -> Result<String, CommandError> {
+	let name = args.next::<String>("player")?;
+	let toggle = args.next::<Toggle>("action")?;
+	args.finish()?;
+
+	let target = find_player(sector, &name)?;
+
+	match toggle {
+		Toggle::Start => {
+			if connected_player(sector, target)?.recorder.is_some() {
+				return Err(CommandError::Failed(format!(
+					"{name} is already being recorded"
+				)));
+			}
+
+			let recorder = sector.start_recorder(target).map_err(|error| {
+				CommandError::Failed(format!("Unable to record {name}: {error}"))
+			})?;
+			let path = recorder.path().display().to_string();
+
+			connected_player(sector, target)?.recorder = Some(recorder);
+
+			Ok(format!("Recording {name} to {path}"))
+		}
+		Toggle::Stop => match &mut connected_player(sector, target)?.recorder {
+			Some(recorder) => {
+				recorder.stop();
+				Ok(format!("Stopped recording {name}"))
+			}
+			None => Err(CommandError::Failed(format!("{name} isn't being recorded"))),
+		},
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn parse_tp(text: &str) -> Result<TpTarget, CommandError> {
+		let registry = CommandRegistry::builtin();
+		let (_, mut args) = registry.parse(text, Permission::Player)?;
+		TpTarget::parse(&mut args)
+	}
+
+	#[test]
+	fn tp_takes_a_player_or_a_position() {
+		assert_eq!(
+			parse_tp("tp someone").unwrap(),
+			TpTarget::Player("someone".into())
+		);
+		assert_eq!(
+			parse_tp("tp 1 -2 3.5").unwrap(),
+			TpTarget::Position([
+				Coordinate::Absolute(1.0),
+				Coordinate::Absolute(-2.0),
+				Coordinate::Absolute(3.5)
+			])
+		);
+	}
+
+	#[test]
+	fn tp_rejects_the_wrong_number_of_arguments() {
+		assert!(matches!(parse_tp("tp"), Err(CommandError::Missing("x"))));
+		assert!(matches!(
+			parse_tp("tp 1 2"),
+			Err(CommandError::Missing("z"))
+		));
+		assert!(matches!(
+			parse_tp("tp 1 2 3 4"),
+			Err(CommandError::Unexpected(token)) if token == "4"
+		));
+
+		for text in ["tp", "tp 1 2", "tp 1 2 3 4"] {
+			assert!(parse_tp(text).unwrap_err().is_usage(), "{text}");
+		}
+	}
+
+	#[test]
+	fn tp_rejects_non_finite_coordinates() {
+		for coordinate in [
+			"NaN", "nan", "inf", "-inf", "infinity", "1e39", "~NaN", "~inf", "~-1e39",
+		] {
+			let text = format!("tp 0 {coordinate} 0");
+
+			assert!(
+				matches!(
+					parse_tp(&text),
+					Err(CommandError::Invalid { name: "y", ref value, .. }) if value == coordinate
+				),
+				"{text}"
+			);
+		}
+	}
+
+	#[test]
+	fn tp_takes_relative_coordinates() {
+		let Ok(TpTarget::Position([x, y, z])) = parse_tp("tp ~ ~5 ~-2.5") else {
+			panic!("relative coordinates should parse");
+		};
+
+		assert_eq!(x, Coordinate::Relative(0.0));
+		assert_eq!(y, Coordinate::Relative(5.0));
+		assert_eq!(z, Coordinate::Relative(-2.5));
+
+		assert_eq!(x.resolve(10.0), 10.0);
+		assert_eq!(y.resolve(10.0), 15.0);
+		assert_eq!(z.resolve(10.0), 7.5);
+
+		// Mixed with absolute coordinates, which ignore where the player is
+		assert_eq!(
+			parse_tp("tp 4 ~1 -4").unwrap(),
+			TpTarget::Position([
+				Coordinate::Absolute(4.0),
+				Coordinate::Relative(1.0),
+				Coordinate::Absolute(-4.0)
+			])
+		);
+		assert_eq!(Coordinate::Absolute(4.0).resolve(10.0), 4.0);
+	}
+
+	#[test]
+	fn tp_rejects_malformed_relative_coordinates() {
+		for coordinate in ["~~1", "1~", "~x", "~+"] {
+			let text = format!("tp {coordinate} 0 0");
+
+			assert!(parse_tp(&text).is_err(), "{text}");
+		}
+	}
+}
@@ -0,0 +1,1191 @@
+use crate::lod::LodCurve;
+use hocon::HoconLoader;
+use serde::Deserialize;
+use solarscape_shared::{
+	connection::KeepAlive,
+	data::{world::LEVELS, Id},
+	message::clientbound::{AmbientKeyframe, InventoryCapacity},
+};
+use std::{
+	collections::{HashMap, HashSet},
+	fmt::{self, Display, Formatter},
+	fs::read_to_string,
+	io::{self, ErrorKind::NotFound},
+	path::{absolute, PathBuf},
+	process::ExitCode,
+	time::Duration,
+};
+use thiserror::Error;
+
+/// Environment variable used to locate the sector config file when `--config` is not given.
+pub const CONFIG_ENV: &str = "SOLARSCAPE_CONFIG";
+
+/// Names end up in database columns, so they can't be any longer than this.
+const MAX_NAME_LENGTH: usize = 64;
+
+#[derive(Deserialize)]
+pub struct Sector {
+	pub name: Box<str>,
+	pub voxjects: Vec<Voxject>,
+
+	/// Seconds between automatic saves, `0` disables autosaving. Modified chunks are still saved on shutdown. Counted in
+	/// ticks, so saves are further apart while ticks are overrunning.
+	#[serde(default = "default_autosave_interval")]
+	pub autosave_interval: u64,
+
+	/// Default capacity of every player's inventory.
+	#[serde(default)]
+	pub inventory: InventoryCapacity,
+
+	#[serde(default)]
+	pub ambient: Ambient,
+
+	/// Players allowed to use admin features, such as pausing the ambient cycle.
+	#[serde(default)]
+	pub admins: Vec<Id>,
+
+	#[serde(default)]
+	pub memory_thresholds: MemoryThresholds,
+
+	/// Most dynamic structures that may be awake at once, those furthest from players are put to sleep past this. `0`
+	/// disables the limit.
+	#[serde(default = "default_max_awake_structures")]
+	pub max_awake_structures: usize,
+
+	#[serde(default)]
+	pub connection: ConnectionTiming,
+
+	#[serde(default)]
+	pub structure_limits: StructureLimits,
+
+	/// Ticks per second, from [`MIN_TICK_RATE`] to [`MAX_TICK_RATE`]. Gameplay runs at the same speed at any rate,
+	/// higher rates are smoother but use more CPU time.
+	#[serde(default = "default_tick_rate")]
+	pub tick_rate: u32,
+
+	/// File `/snapshot` writes the sector's state to, for handing it to a new process with `--restore`. Snapshots are
+	/// disabled if not set.
+	#[serde(default)]
+	pub snapshot_path: Option<PathBuf>,
+
+	#[serde(default)]
+	pub recording: Recording,
+
+	/// Distance from the sector's origin that players are shown a border at, `0` for no border. Players who cross it are
+	/// teleported back onto it, and terrain past it isn't sent to them.
+	#[serde(default)]
+	pub world_radius: f32,
+
+	/// Highest level chunks are sent to players at, up to [`MAX_LEVEL`]. A small world is covered by the chunks around
+	/// the origin well before the top level, so the levels above would only send blurrier copies of the same terrain.
+	#[serde(default = "default_max_level")]
+	pub max_level: u8,
+
+	/// How many of the most recent chat messages are kept and shown to players as they join, `0` keeps none. Kept in
+	/// the database too, so that they survive restarts.
+	#[serde(default = "default_chat_history")]
+	pub chat_history: usize,
+
+	/// Distance in meters that players are sent structures within, those further away are removed from the player's
+	/// client until they're back in range. `0` sends every structure to every player.
+	#[serde(default = "default_interest_radius")]
+	pub interest_radius: f32,
+
+	/// Most players allowed in the sector at once, `0` for no limit. Reported to gateways, which turn players away or
+	/// queue them while the sector is full. Connections past the limit are rejected here too.
+	#[serde(default = "default_max_players")]
+	pub max_players: u32,
+
+	#[serde(default)]
+	pub audit: Audit,
+
+	#[serde(default)]
+	pub generation_cache: GenerationCache,
+
+	#[serde(default)]
+	pub terrain_journal: TerrainJournal,
+
+	/// Most chunks generated in the background at once, `0` for no limit. Chunks past this wait their turn, so that a
+	/// player joining can't fill the thread pool for minutes.
+	#[serde(default = "default_max_generation_jobs")]
+	pub max_generation_jobs: usize,
+
+	#[serde(default)]
+	pub transfers: Transfers,
+
+	#[serde(default)]
+	pub afk: Afk,
+
+	/// LOD curve for voxjects that don't set their own.
+	#[serde(default)]
+	pub lod: Lod,
+}
+
+pub const MIN_TICK_RATE: u32 = 10;
+pub const MAX_TICK_RATE: u32 = 120;
+
+/// Highest [`Sector::max_level`] allowed, chunks on the top level are never sent, see [`ChunkCoordinates::is_sane`].
+///
+/// [`ChunkCoordinates::is_sane`]: solarscape_shared::data::world::ChunkCoordinates::is_sane
+pub const MAX_LEVEL: u8 = LEVELS - 2;
+
+const fn default_autosave_interval() -> u64 {
+	300
+}
+
+const fn default_tick_rate() -> u32 {
+	30
+}
+
+const fn default_max_awake_structures() -> usize {
+	256
+}
+
+const fn default_max_level() -> u8 {
+	MAX_LEVEL
+}
+
+const fn default_chat_history() -> usize {
+	100
+}
+
+const fn default_interest_radius() -> f32 {
+	1024.0
+}
+
+const fn default_max_players() -> u32 {
+	64
+}
+
+const fn default_max_generation_jobs() -> usize {
+	64
+}
+
+#[derive(Deserialize)]
+pub struct Voxject {
+	pub name: Box<str>,
+
+	/// Overrides the sector's [`Sector::lod`] for this voxject.
+	#[serde(default)]
+	pub lod: Option<Lod>,
+}
+
+/// How far around players chunks are sent to them at each level, either one of the presets or a radius in chunks for
+/// each level starting from level 0, see [`LodCurve`].
+///
+/// [`LodCurve`]: crate::lod::LodCurve
+#[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum Lod {
+	Preset(LodPreset),
+	Radii(Vec<u32>),
+}
+
+impl Default for Lod {
+	fn default() -> Self {
+		Self::Preset(LodPreset::Near)
+	}
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LodPreset {
+	/// Only the chunks right around the player, the cheapest to run.
+	Near,
+	/// Several chunks around the player at each of the lower levels, so that terrain further away can be seen.
+	Far,
+}
+
+/// The sector's day/night cycle, see [`AmbientKeyframe`].
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Ambient {
+	/// Seconds for one full cycle.
+	pub period: f32,
+
+	/// In ascending order of phase.
+	pub keyframes: Vec<AmbientKeyframe>,
+}
+
+impl Default for Ambient {
+	fn default() -> Self {
+		Self {
+			period: 1200.0,
+			keyframes: vec![
+				AmbientKeyframe {
+					phase: 0.0,
+					sky: [0.4, 0.2, 0.15],
+					sun: [0.8, 0.45, 0.3],
+					ambient: [0.2, 0.15, 0.15],
+				},
+				AmbientKeyframe {
+					phase: 0.25,
+					sky: [0.3, 0.5, 0.9],
+					sun: [1.0, 0.95, 0.9],
+					ambient: [0.3, 0.3, 0.35],
+				},
+				AmbientKeyframe {
+					phase: 0.5,
+					sky: [0.5, 0.2, 0.1],
+					sun: [0.8, 0.4, 0.2],
+					ambient: [0.2, 0.15, 0.15],
+				},
+				AmbientKeyframe {
+					phase: 0.75,
+					sky: [0.0, 0.0, 0.02],
+					sun: [0.0, 0.0, 0.0],
+					ambient: [0.05, 0.05, 0.1],
+				},
+			],
+		}
+	}
+}
+
+/// Memory use in MiB above which a message is logged, `0` disables the message for that category.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct MemoryThresholds {
+	pub chunk_data: usize,
+	pub collision_meshes: usize,
+	pub structures: usize,
+}
+
+impl Default for MemoryThresholds {
+	fn default() -> Self {
+		Self {
+			chunk_data: 2048,
+			collision_meshes: 1024,
+			structures: 256,
+		}
+	}
+}
+
+/// Limits on structure creation, to stop a player from filling the sector with structures. `0` disables a limit.
+#[derive(Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct StructureLimits {
+	/// Most structures a single player may own.
+	pub per_player: usize,
+	/// Most structures the sector may have, across all players.
+	pub per_sector: usize,
+	/// Structures each player may place per second, sustained. Terrain edits come out of the same allowance.
+	pub placements_per_second: f32,
+	/// Structures each player may place in quick succession before [`StructureLimits::placements_per_second`] applies.
+	pub placement_burst: f32,
+}
+
+impl Default for StructureLimits {
+	fn default() -> Self {
+		Self {
+			per_player: 256,
+			per_sector: 4096,
+			placements_per_second: 5.0,
+			placement_burst: 10.0,
+		}
+	}
+}
+
+/// Keep-alive timing for player connections, in seconds, see [`KeepAlive`].
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct ConnectionTiming {
+	/// How long to wait before sending a keep-alive when nothing else has been sent.
+	pub keep_alive_interval: f32,
+	/// How long a player may go without sending anything before they're disconnected.
+	pub timeout: f32,
+}
+
+impl ConnectionTiming {
+	pub fn keep_alive(&self) -> KeepAlive {
+		KeepAlive {
+			interval: Duration::from_secs_f32(self.keep_alive_interval),
+			timeout: Duration::from_secs_f32(self.timeout),
+		}
+	}
+}
+
+impl Default for ConnectionTiming {
+	fn default() -> Self {
+		let KeepAlive { interval, timeout } = KeepAlive::default();
+
+		Self {
+			keep_alive_interval: interval.as_secs_f32(),
+			timeout: timeout.as_secs_f32(),
+		}
+	}
+}
+
+/// Recording of the messages players send, for replaying with `replay` when debugging desyncs, see [`Recorder`].
+///
+/// [`Recorder`]: crate::recording::Recorder
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Recording {
+	/// Directory recordings are written to, recording is disabled if not set.
+	pub directory: Option<PathBuf>,
+	/// Players who are recorded whenever they're connected, others can be recorded with `/record`.
+	pub players: Vec<Id>,
+	/// MiB each recording file may grow to before a new one is started, `0` disables the limit.
+	pub max_file_size: u64,
+	/// Most files kept for each recording, the oldest are deleted past this. `0` keeps every file.
+	pub max_files: usize,
+	/// Seconds between checkpoints of the player's state, which a replay is checked against.
+	pub checkpoint_interval: f32,
+}
+
+impl Default for Recording {
+	fn default() -> Self {
+		Self {
+			directory: None,
+			players: vec![],
+			max_file_size: 64,
+			max_files: 8,
+			checkpoint_interval: 1.0,
+		}
+	}
+}
+
+/// Log of moderation-relevant things players do, written to the database for reading through the gateway, see
+/// [`AuditLog`].
+///
+/// [`AuditLog`]: crate::audit::AuditLog
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Audit {
+	pub enabled: bool,
+	/// Smallest terrain brush radius that's logged, smaller edits are too common to be worth keeping.
+	pub terrain_radius: f32,
+	/// Most events each player may log in each category between writes, past this they're only counted. `0` disables
+	/// the limit.
+	pub rate_limit: u32,
+	/// Seconds between writes.
+	pub flush_interval: f32,
+	/// Most events waiting to be written, past this they're dropped.
+	pub max_queued: usize,
+}
+
+impl Default for Audit {
+	fn default() -> Self {
+		Self {
+			enabled: true,
+			terrain_radius: 4.0,
+			rate_limit: 20,
+			flush_interval: 5.0,
+			max_queued: 1024,
+		}
+	}
+}
+
+/// Generated chunks kept on disk so that they don't have to be generated again when the sector restarts, see
+/// [`GenerationCache`].
+///
+/// [`GenerationCache`]: crate::generation_cache::GenerationCache
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct GenerationCache {
+	/// Directory the cache is kept in, the cache is disabled if not set.
+	pub directory: Option<PathBuf>,
+	/// MiB the cache may grow to before the least recently used chunks are deleted, `0` disables the limit.
+	pub max_size: u64,
+	/// Ignores cached chunks, generating everything again and replacing what was cached. Set by `--regenerate`.
+	#[serde(skip)]
+	pub regenerate: bool,
+}
+
+impl Default for GenerationCache {
+	fn default() -> Self {
+		Self {
+			directory: None,
+			max_size: 1024,
+			regenerate: false,
+		}
+	}
+}
+
+/// Recent terrain brush strokes kept for each player so that they can be undone with `/rollback`, see
+/// [`TerrainJournal`].
+///
+/// [`TerrainJournal`]: crate::terrain_journal::TerrainJournal
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct TerrainJournal {
+	/// Seconds strokes are kept for, `0` disables the journal.
+	pub window: u64,
+	/// MiB kept for each player before their oldest strokes are forgotten, `0` disables the limit.
+	pub max_size: usize,
+}
+
+impl Default for TerrainJournal {
+	fn default() -> Self {
+		Self {
+			window: 1800,
+			max_size: 16,
+		}
+	}
+}
+
+/// Other sectors that players can be sent to with `/transfer`.
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Transfers {
+	/// Seconds a player has to arrive at the destination before the transfer is abandoned, and they spawn there as
+	/// normal.
+	pub expiry: u64,
+	/// Each sector players can be sent to, with the API endpoint of the gateway that admits players to it.
+	pub destinations: HashMap<Box<str>, Box<str>>,
+}
+
+impl Default for Transfers {
+	fn default() -> Self {
+		Self {
+			expiry: 60,
+			destinations: HashMap::new(),
+		}
+	}
+}
+
+/// Players who haven't done anything for a while, see [`Idle`].
+///
+/// [`Idle`]: crate::idle::Idle
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct Afk {
+	/// Seconds without input before a player is marked as away, `0` disables AFK detection.
+	pub timeout: u64,
+	/// Seconds a player may stay away before they're disconnected, `0` never disconnects them.
+	pub kick_after: u64,
+}
+
+impl Default for Afk {
+	fn default() -> Self {
+		Self {
+			timeout: 300,
+			kick_after: 0,
+		}
+	}
+}
+
+/// Loads and validates the sector config file at `path`, `path` should be [`None`] only if neither `--config` or
+/// [`CONFIG_ENV`] were provided.
+pub fn load_config(path: Option<PathBuf>) -> Result<Sector, ConfigError> {
+	let path = path.ok_or(ConfigError::Unspecified)?;
+
+	// Only used for error messages, so if we can't resolve it just show whatever we were given
+	let path = absolute(&path).unwrap_or(path);
+
+	let string = read_to_string(&path).map_err(|error| match error.kind() {
+		NotFound => ConfigError::NotFound { path: path.clone() },
+		_ => ConfigError::Io {
+			path: path.clone(),
+			source: error,
+		},
+	})?;
+
+	// Strict, otherwise anything after a syntax error is quietly ignored
+	let config: Sector = HoconLoader::new()
+		.strict()
+		.load_str(&string)
+		.map_err(|error| ConfigError::Parse {
+			path: path.clone(),
+			location: locate_syntax_error(&string),
+			message: match error {
+				hocon::Error::Parse | hocon::Error::Deserialization { .. } => {
+					"invalid syntax".into()
+				}
+				error => error.to_string(),
+			},
+		})?
+		.resolve()
+		.map_err(|error| ConfigError::Parse {
+			path: path.clone(),
+			location: None,
+			message: match error {
+				hocon::Error::Deserialization { message } => message,
+				error => error.to_string(),
+			},
+		})?;
+
+	let problems = config.validate();
+
+	match problems.is_empty() {
+		true => Ok(config),
+		false => Err(ConfigError::Invalid { path, problems }),
+	}
+}
+
+/// Where the syntax error in `string` is as a line and column, both from 1, since hocon doesn't say. That's the line
+/// after the last one which can be parsed along with everything before it, once any brackets still open are closed, or
+/// the last bracket left open if there isn't one. The column is that of a stray closing bracket or unterminated string
+/// on the line, if there is one, otherwise where the line's content starts.
+fn locate_syntax_error(string: &str) -> Option<(usize, usize)> {
+	let parses = |prefix: &str| HoconLoader::new().strict().load_str(prefix).is_ok();
+
+	let lines = string.lines().collect::<Vec<_>>();
+
+	// Brackets still open, and stray closing brackets or unterminated strings, as (character, line, column)
+	let mut open = vec![];
+	let mut stray = vec![];
+
+	let mut prefix = String::new();
+	let mut parsed_lines = 0;
+
+	for (line_index, line) in lines.iter().enumerate() {
+		let mut characters = line.chars().enumerate().peekable();
+
+		while let Some((column, character)) = characters.next() {
+			let position = (character, line_index + 1, column + 1);
+
+			match character {
+				'#' => break,
+				'/' if characters.peek().is_some_and(|(_, next)| *next == '/') => break,
+				'"' => loop {
+					match characters.next() {
+						Some((_, '\\')) => {
+							characters.next();
+						}
+						Some((_, '"')) => break,
+						Some(_) => {}
+						None => {
+							stray.push(position);
+							break;
+						}
+					}
+				},
+				'{' | '[' => open.push(position),
+				'}' | ']' => match open.last() {
+					Some(('{', ..)) if character == '}' => {
+						open.pop();
+					}
+					Some(('[', ..)) if character == ']' => {
+						open.pop();
+					}
+					_ => stray.push(position),
+				},
+				_ => {}
+			}
+		}
+
+		prefix.push_str(line);
+		prefix.push('\n');
+
+		let closers = open
+			.iter()
+			.rev()
+			.map(|(bracket, ..)| match bracket {
+				'{' => '}',
+				_ => ']',
+			})
+			.collect::<String>();
+
+		if parses(&format!("{prefix}{closers}")) {
+			parsed_lines = line_index + 1;
+		}
+	}
+
+	let Some(line) = lines.get(parsed_lines) else {
+		// Everything parses once closed, so it's a bracket that's never closed
+		return open.last().map(|(_, line, column)| (*line, *column));
+	};
+
+	let line_number = parsed_lines + 1;
+
+	let column = stray
+		.iter()
+		.find(|(_, line, _)| *line == line_number)
+		.map(|(_, _, column)| *column)
+		.unwrap_or_else(|| line.chars().take_while(|c| c.is_whitespace()).count() + 1);
+
+	Some((line_number, column))
+}
+
+impl Sector {
+	fn validate(&self) -> Vec<Box<str>> {
+		let mut problems = vec![];
+
+		if self.name.is_empty() {
+			problems.push("`name` must not be empty".into());
+		} else if self.name.chars().count() > MAX_NAME_LENGTH {
+			problems.push(
+				format!("`name` must not be longer than {MAX_NAME_LENGTH} characters").into(),
+			);
+		}
+
+		if self.voxjects.is_empty() {
+			problems.push("`voxjects` must contain at least one voxject".into());
+		}
+
+		let mut names = HashSet::new();
+		for (index, voxject) in self.voxjects.iter().enumerate() {
+			if voxject.name.is_empty() {
+				problems.push(format!("`voxjects[{index}].name` must not be empty").into());
+			} else if voxject.name.chars().count() > MAX_NAME_LENGTH {
+				problems.push(
+					format!(
+						"`voxjects[{index}].name` must not be longer than {MAX_NAME_LENGTH} characters"
+					)
+					.into(),
+				);
+			} else if !names.insert(&voxject.name) {
+				problems.push(
+					format!(
+						"`voxjects[{index}].name` is `{}`, which is already used by another voxject",
+						voxject.name
+					)
+					.into(),
+				);
+			}
+		}
+
+		let sector_curve = LodCurve::new(&self.lod);
+		let mut lod_problems = sector_curve.validate("lod");
+
+		let curves = self
+			.voxjects
+			.iter()
+			.enumerate()
+			.map(|(index, voxject)| match &voxject.lod {
+				Some(lod) => {
+					let curve = LodCurve::new(lod);
+					lod_problems.extend(curve.validate(&format!("voxjects[{index}].lod")));
+					curve
+				}
+				None => sector_curve.clone(),
+			})
+			.collect::<Vec<_>>();
+
+		// Only estimated once the curves are valid, a huge radius would take far too long to count
+		if lod_problems.is_empty() {
+			let estimated_chunks = curves.iter().map(LodCurve::estimate_chunks).sum::<usize>();
+
+			if estimated_chunks > LodCurve::MAX_CHUNKS {
+				problems.push(
+					format!(
+						"`lod` and `voxjects[].lod` send each player about {estimated_chunks} chunks, more than the {} allowed",
+						LodCurve::MAX_CHUNKS
+					)
+					.into(),
+				);
+			}
+		}
+
+		problems.extend(lod_problems);
+
+		if self.inventory.max_stacks == 0 {
+			problems.push("`inventory.max_stacks` must be greater than 0".into());
+		}
+
+		if self.inventory.max_quantity <= 0 {
+			problems.push("`inventory.max_quantity` must be greater than 0".into());
+		}
+
+		if !(self.ambient.period.is_finite() && self.ambient.period > 0.0) {
+			problems.push("`ambient.period` must be greater than 0".into());
+		}
+
+		if self.ambient.keyframes.is_empty() {
+			problems.push("`ambient.keyframes` must contain at least one keyframe".into());
+		}
+
+		if !(MIN_TICK_RATE..=MAX_TICK_RATE).contains(&self.tick_rate) {
+			problems.push(
+				format!("`tick_rate` must be from {MIN_TICK_RATE} to {MAX_TICK_RATE}").into(),
+			);
+		}
+
+		let ConnectionTiming {
+			keep_alive_interval,
+			timeout,
+		} = self.connection;
+
+		// Also rules out anything too large to be a Duration, which would panic later
+		if !(keep_alive_interval > 0.0 && Duration::try_from_secs_f32(keep_alive_interval).is_ok())
+		{
+			problems.push("`connection.keep_alive_interval` must be greater than 0".into());
+		} else if !(timeout > keep_alive_interval && Duration::try_from_secs_f32(timeout).is_ok()) {
+			problems.push(
+				"`connection.timeout` must be greater than `connection.keep_alive_interval`".into(),
+			);
+		}
+
+		let StructureLimits {
+			placements_per_second,
+			placement_burst,
+			..
+		} = self.structure_limits;
+
+		if !(placements_per_second.is_finite() && placements_per_second >= 0.0) {
+			problems.push("`structure_limits.placements_per_second` must not be negative".into());
+		} else if placements_per_second > 0.0
+			&& !(placement_burst.is_finite() && placement_burst >= 1.0)
+		{
+			problems.push("`structure_limits.placement_burst` must be at least 1".into());
+		}
+
+		if !(self.world_radius.is_finite() && self.world_radius >= 0.0) {
+			problems.push("`world_radius` must not be negative".into());
+		}
+
+		if self.max_level > MAX_LEVEL {
+			problems.push(format!("`max_level` must not be greater than {MAX_LEVEL}").into());
+		}
+
+		if !(self.interest_radius.is_finite() && self.interest_radius >= 0.0) {
+			problems.push("`interest_radius` must not be negative".into());
+		}
+
+		if !(self.audit.terrain_radius.is_finite() && self.audit.terrain_radius >= 0.0) {
+			problems.push("`audit.terrain_radius` must not be negative".into());
+		}
+
+		if !(self.audit.flush_interval.is_finite() && self.audit.flush_interval > 0.0) {
+			problems.push("`audit.flush_interval` must be greater than 0".into());
+		}
+
+		if self.audit.max_queued == 0 {
+			problems.push("`audit.max_queued` must be greater than 0".into());
+		}
+
+		if self.transfers.expiry == 0 {
+			problems.push("`transfers.expiry` must be greater than 0".into());
+		}
+
+		for (destination, gateway) in &self.transfers.destinations {
+			if *destination == self.name {
+				problems
+					.push(format!("`transfers.destinations.{destination}` is this sector").into());
+			} else if destination.chars().count() > MAX_NAME_LENGTH {
+				problems.push(
+					format!(
+						"`transfers.destinations.{destination}` must not be longer than {MAX_NAME_LENGTH} characters"
+					)
+					.into(),
+				);
+			}
+
+			if gateway.is_empty() {
+				problems.push(
+					format!("`transfers.destinations.{destination}` must not be empty").into(),
+				);
+			}
+		}
+
+		let checkpoint_interval = self.recording.checkpoint_interval;
+
+		if !(checkpoint_interval > 0.0 && Duration::try_from_secs_f32(checkpoint_interval).is_ok())
+		{
+			problems.push("`recording.checkpoint_interval` must be greater than 0".into());
+		}
+
+		let mut last_phase = None;
+		for (index, keyframe) in self.ambient.keyframes.iter().enumerate() {
+			if !(0.0..1.0).contains(&keyframe.phase) {
+				problems.push(
+					format!(
+						"`ambient.keyframes[{index}].phase` must be at least 0 and less than 1"
+					)
+					.into(),
+				);
+			} else if last_phase.is_some_and(|last_phase| keyframe.phase <= last_phase) {
+				problems.push(
+					format!(
+						"`ambient.keyframes[{index}].phase` must be greater than the previous keyframe's"
+					)
+					.into(),
+				);
+			}
+
+			last_phase = Some(keyframe.phase);
+
+			for (field, color) in [
+				("sky", keyframe.sky),
+				("sun", keyframe.sun),
+				("ambient", keyframe.ambient),
+			] {
+				if !color
+					.iter()
+					.all(|channel| channel.is_finite() && *channel >= 0.0)
+				{
+					problems.push(
+						format!("`ambient.keyframes[{index}].{field}` must not be negative").into(),
+					);
+				}
+			}
+		}
+
+		problems
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+	#[error("No sector config file was specified.\n  Pass `--config <path>` or set the `{CONFIG_ENV}` environment variable.")]
+	Unspecified,
+
+	#[error("Sector config file does not exist.\n  Looked for: {}", path.display())]
+	NotFound { path: PathBuf },
+
+	#[error("Unable to read sector config file.\n  File: {}\n  Reason: {source}", path.display())]
+	Io { path: PathBuf, source: io::Error },
+
+	#[error(
+		"Unable to parse sector config file.\n  File: {}{}\n  Reason: {message}",
+		path.display(),
+		location.map_or(String::new(), |(line, column)| format!(":{line}:{column}"))
+	)]
+	Parse {
+		path: PathBuf,
+		/// Line and column of a syntax error, see [`locate_syntax_error`].
+		location: Option<(usize, usize)>,
+		message: String,
+	},
+
+	#[error("Sector config file is invalid.\n  File: {}\n{}", path.display(), Problems(problems))]
+	Invalid {
+		path: PathBuf,
+		problems: Vec<Box<str>>,
+	},
+}
+
+impl ConfigError {
+	/// Each kind of failure exits with it's own code so that scripts can tell them apart.
+	pub fn exit_code(&self) -> ExitCode {
+		ExitCode::from(match self {
+			Self::Unspecified => 2,
+			Self::NotFound { .. } => 3,
+			Self::Io { .. } => 4,
+			Self::Parse { .. } => 5,
+			Self::Invalid { .. } => 6,
+		})
+	}
+}
+
+struct Problems<'a>(&'a [Box<str>]);
+
+impl Display for Problems<'_> {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		for (index, problem) in self.0.iter().enumerate() {
+			if index != 0 {
+				writeln!(formatter)?;
+			}
+
+			write!(formatter, "  - {problem}")?;
+		}
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{env, fs, path::Path};
+
+	/// A temporary directory, deleted when dropped.
+	struct TempDir(PathBuf);
+
+	impl TempDir {
+		fn new() -> Self {
+			let directory = env::temp_dir().join(format!("solarscape-config-{}", Id::new()));
+			fs::create_dir_all(&directory).unwrap();
+			Self(directory)
+		}
+
+		/// Writes `contents` to a config file in the directory, returning it's path.
+		fn config(&self, contents: &str) -> PathBuf {
+			let path = self.0.join("sector.conf");
+			fs::write(&path, contents).unwrap();
+			path
+		}
+	}
+
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			let _ = fs::remove_dir_all(&self.0);
+		}
+	}
+
+	fn load(contents: &str) -> (Result<Sector, ConfigError>, PathBuf) {
+		let directory = TempDir::new();
+		let path = directory.config(contents);
+		(load_config(Some(path.clone())), path)
+	}
+
+	fn location(contents: &str) -> Option<(usize, usize)> {
+		match load(contents).0 {
+			Err(ConfigError::Parse { location, .. }) => location,
+			Err(error) => panic!("expected a parse error, got {error}"),
+			Ok(_) => panic!("expected a parse error"),
+		}
+	}
+
+	#[test]
+	fn valid_config_loads() {
+		let (config, _) = load("name: test\nvoxjects: [{ name: a }]\n");
+		let config = config.unwrap();
+
+		assert_eq!(&*config.name, "test");
+		assert_eq!(config.voxjects.len(), 1);
+	}
+
+	#[test]
+	fn example_config_loads() {
+		let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("run/example.conf");
+		assert!(load_config(Some(path)).is_ok());
+	}
+
+	#[test]
+	fn unspecified() {
+		let error = load_config(None).err().unwrap();
+
+		assert!(matches!(error, ConfigError::Unspecified));
+		assert_eq!(error.exit_code(), ExitCode::from(2));
+		assert_eq!(
+			error.to_string(),
+			"No sector config file was specified.\n  Pass `--config <path>` or set the `SOLARSCAPE_CONFIG` environment variable."
+		);
+	}
+
+	#[test]
+	fn not_found() {
+		let directory = TempDir::new();
+		let path = directory.0.join("missing.conf");
+
+		let error = load_config(Some(path.clone())).err().unwrap();
+
+		assert!(matches!(&error, ConfigError::NotFound { path: found } if *found == path));
+		assert_eq!(error.exit_code(), ExitCode::from(3));
+		assert_eq!(
+			error.to_string(),
+			format!(
+				"Sector config file does not exist.\n  Looked for: {}",
+				path.display()
+			)
+		);
+	}
+
+	#[test]
+	fn relative_paths_are_shown_absolute() {
+		let error = load_config(Some(PathBuf::from("solarscape-missing.conf")))
+			.err()
+			.unwrap();
+
+		let ConfigError::NotFound { path } = error else {
+			panic!("expected not found, got {error}");
+		};
+		assert!(path.is_absolute());
+		assert!(path.ends_with("solarscape-missing.conf"));
+	}
+
+	#[test]
+	fn io() {
+		let directory = TempDir::new();
+
+		// Reading a directory fails, but not because it doesn't exist
+		let error = load_config(Some(directory.0.clone())).err().unwrap();
+
+		assert!(matches!(&error, ConfigError::Io { path, .. } if *path == directory.0));
+		assert_eq!(error.exit_code(), ExitCode::from(4));
+		assert!(error.to_string().starts_with(&format!(
+			"Unable to read sector config file.\n  File: {}\n  Reason: ",
+			directory.0.display()
+		)));
+	}
+
+	#[test]
+	fn syntax_error() {
+		let (error, path) = load("name: test\nvoxjects: [\n\t{ name: a }\n\t}\n]\n");
+		let error = error.err().unwrap();
+
+		assert_eq!(error.exit_code(), ExitCode::from(5));
+		assert_eq!(
+			error.to_string(),
+			format!(
+				"Unable to parse sector config file.\n  File: {}:4:2\n  Reason: invalid syntax",
+				path.display()
+			)
+		);
+	}
+
+	#[test]
+	fn deserialization_error() {
+		let (error, path) = load("name: test\n");
+		let error = error.err().unwrap();
+
+		assert!(matches!(error, ConfigError::Parse { location: None, .. }));
+		assert_eq!(error.exit_code(), ExitCode::from(5));
+
+		let message = error.to_string();
+		assert!(message.starts_with(&format!(
+			"Unable to parse sector config file.\n  File: {}\n  Reason: ",
+			path.display()
+		)));
+		assert!(message.contains("voxjects"), "{message}");
+	}
+
+	#[test]
+	fn anything_after_a_syntax_error_isnt_ignored() {
+		// Without strict parsing this loads, with the second voxject silently missing
+		assert_eq!(
+			location("name: test\nvoxjects: [{ name: a }]\n}\nvoxjects: [{ name: b }]\n"),
+			Some((3, 1))
+		);
+	}
+
+	#[test]
+	fn syntax_errors_are_located() {
+		// Stray and mismatched closing brackets
+		assert_eq!(location("name: test\n  ]\n"), Some((2, 3)));
+		assert_eq!(location("a: [1, 2}\nb: 3\n"), Some((1, 9)));
+
+		// Unterminated string
+		assert_eq!(location("a: 1\nb: \"test\nc: 3\n"), Some((2, 4)));
+
+		// Bracket never closed, which is where it was opened
+		assert_eq!(location("a: 1\nb: {\n\tc: 2\n\td: [3, 4]\n"), Some((2, 4)));
+
+		// Nothing else wrong on the line, so where it starts
+		assert_eq!(location("a: 1\n\tb c d e\nf: 3\n"), Some((2, 2)));
+		assert_eq!(location("a: 1\nb:\n"), Some((2, 1)));
+	}
+
+	#[test]
+	fn brackets_in_strings_and_comments_are_ignored() {
+		assert_eq!(
+			location("a: \"}\" # ]\nb: \"{\\\"\" // }\n}\n"),
+			Some((3, 1))
+		);
+	}
+
+	#[test]
+	fn values_continued_on_the_next_line_arent_errors() {
+		assert_eq!(location("a =\n\t1\nb: [\n\t2\n\t3\n]\n}\n"), Some((7, 1)));
+	}
+
+	#[test]
+	fn invalid() {
+		let (error, path) = load("name: \"\"\nvoxjects: []\n");
+		let error = error.err().unwrap();
+
+		assert_eq!(error.exit_code(), ExitCode::from(6));
+		assert_eq!(
+			error.to_string(),
+			format!(
+				"Sector config file is invalid.\n  File: {}\n  - `name` must not be empty\n  - `voxjects` must contain at least one voxject",
+				path.display()
+			)
+		);
+	}
+
+	#[test]
+	fn tick_rate_must_be_in_range() {
+		let config = |tick_rate: &str| {
+			load(&format!(
+				"name: test\nvoxjects: [{{ name: a }}]\n{tick_rate}"
+			))
+			.0
+		};
+
+		assert_eq!(config("").unwrap().tick_rate, 30);
+
+		for tick_rate in [MIN_TICK_RATE, 60, MAX_TICK_RATE] {
+			assert_eq!(
+				config(&format!("tick_rate: {tick_rate}"))
+					.unwrap()
+					.tick_rate,
+				tick_rate
+			);
+		}
+
+		for tick_rate in [0, MIN_TICK_RATE - 1, MAX_TICK_RATE + 1] {
+			let error = config(&format!("tick_rate: {tick_rate}")).err().unwrap();
+			assert!(
+				error
+					.to_string()
+					.ends_with("`tick_rate` must be from 10 to 120"),
+				"{error}"
+			);
+		}
+	}
+
+	#[test]
+	fn transfers_are_validated() {
+		let config = |transfers: &str| {
+			load(&format!(
+				"name: test\nvoxjects: [{{ name: a }}]\ntransfers: {transfers}"
+			))
+			.0
+		};
+
+		let transfers = config("{ destinations: { other: \"http://gateway\" } }")
+			.unwrap()
+			.transfers;
+		assert_eq!(transfers.expiry, 60);
+		assert_eq!(&*transfers.destinations["other"], "http://gateway");
+
+		let error = config(&format!(
+			"{{ expiry: 0, destinations: {{ test: a, other: \"\", {}: a }} }}",
+			"a".repeat(MAX_NAME_LENGTH + 1)
+		))
+		.err()
+		.unwrap()
+		.to_string();
+
+		for problem in [
+			"`transfers.expiry` must be greater than 0".to_owned(),
+			"`transfers.destinations.test` is this sector".to_owned(),
+			"`transfers.destinations.other` must not be empty".to_owned(),
+			format!(
+				"`transfers.destinations.{}` must not be longer than {MAX_NAME_LENGTH} characters",
+				"a".repeat(MAX_NAME_LENGTH + 1)
+			),
+		] {
+			assert!(error.contains(&format!("  - {problem}")), "{error}");
+		}
+		assert_eq!(error.matches("  - ").count(), 4, "{error}");
+	}
+
+	#[test]
+	fn lod_curves_are_validated() {
+		let config = |contents: &str| load(&format!("name: test\n{contents}")).0;
+
+		let sector = config("lod: far\nvoxjects: [{ name: a }, { name: b, lod: [2, 1] }]").unwrap();
+		assert!(matches!(sector.lod, Lod::Preset(LodPreset::Far)));
+		assert!(matches!(&sector.voxjects[1].lod, Some(Lod::Radii(radii)) if *radii == [2, 1]));
+
+		let error = config("lod: [1, 2]\nvoxjects: [{ name: a }, { name: b, lod: [0] }]")
+			.err()
+			.unwrap()
+			.to_string();
+		assert!(
+			error.contains("  - `lod[1]` must not be greater than `lod[0]`"),
+			"{error}"
+		);
+		assert!(
+			error.contains("  - `voxjects[1].lod[0]` must be greater than 0"),
+			"{error}"
+		);
+
+		// Each curve is fine on it's own, but not all of them together
+		let far = LodCurve::new(&Lod::Preset(LodPreset::Far)).estimate_chunks();
+		let voxjects = LodCurve::MAX_CHUNKS / far + 1;
+
+		let error = config(&format!(
+			"lod: far\nvoxjects: [{}]",
+			(0..voxjects)
+				.map(|index| format!("{{ name: v{index} }}"))
+				.collect::<Vec<_>>()
+				.join(", ")
+		))
+		.err()
+		.unwrap()
+		.to_string();
+		assert!(
+			error.contains(&format!(
+				"send each player about {} chunks, more than the {} allowed",
+				far * voxjects,
+				LodCurve::MAX_CHUNKS
+			)),
+			"{error}"
+		);
+	}
+}
@@ -0,0 +1,103 @@
+use nalgebra::Vector3;
+use std::time::Duration;
+
+/// How hard an explosion hits whatever is around it. Everything falls off linearly from the center, reaching nothing
+/// at `radius`.
+#[derive(Clone, Copy, Debug)]
+pub struct Explosion {
+	pub radius: f32,
+	/// Damage to blocks at the center.
+	pub damage: f32,
+	/// How much density terrain at the center loses, terrain densities are roughly a distance from the surface.
+	pub erosion: f32,
+	/// Impulse given to structures at the center.
+	pub impulse: f32,
+}
+
+impl Explosion {
+	/// Set off by an [`Explosive`] block once [`Explosion::FUSE`] has passed since it was placed.
+	///
+	/// [`Explosive`]: solarscape_shared::data::world::BlockType::Explosive
+	pub const EXPLOSIVE_BLOCK: Self = Self {
+		radius: 6.0,
+		damage: 250.0,
+		erosion: 4.0,
+		impulse: 60.0,
+	};
+
+	/// How long after being placed an explosive block explodes.
+	pub const FUSE: Duration = Duration::from_secs(3);
+
+	/// 1 at the center, down to 0 at [`Explosion::radius`] and beyond.
+	pub fn falloff(&self, distance: f32) -> f32 {
+		(1.0 - distance / self.radius).clamp(0.0, 1.0)
+	}
+
+	/// Damage to a block `distance` from the center.
+	pub fn damage_at(&self, distance: f32) -> f32 {
+		self.damage * self.falloff(distance)
+	}
+
+	/// How much density terrain `distance` from the center loses.
+	pub fn erosion_at(&self, distance: f32) -> f32 {
+		self.erosion * self.falloff(distance)
+	}
+
+	/// Impulse given to something `offset` from the center, pushing it directly away. Something exactly at the center
+	/// has no direction to be pushed in, so isn't pushed at all.
+	pub fn impulse_at(&self, offset: Vector3<f32>) -> Vector3<f32> {
+		offset
+			.try_normalize(f32::EPSILON)
+			.map_or_else(Vector3::zeros, |direction| {
+				direction * self.impulse * self.falloff(offset.norm())
+			})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::vector;
+
+	const EXPLOSION: Explosion = Explosion {
+		radius: 4.0,
+		damage: 100.0,
+		erosion: 2.0,
+		impulse: 10.0,
+	};
+
+	#[test]
+	fn falls_off_linearly_to_the_radius() {
+		for (distance, falloff) in [(0.0, 1.0), (1.0, 0.75), (2.0, 0.5), (4.0, 0.0), (10.0, 0.0)] {
+			assert_eq!(EXPLOSION.falloff(distance), falloff, "{distance}");
+		}
+
+		assert_eq!(EXPLOSION.damage_at(1.0), 75.0);
+		assert_eq!(EXPLOSION.erosion_at(2.0), 1.0);
+		assert_eq!(EXPLOSION.damage_at(5.0), 0.0);
+	}
+
+	#[test]
+	fn pushes_directly_away_from_the_center() {
+		assert_eq!(
+			EXPLOSION.impulse_at(vector![0.0, 2.0, 0.0]),
+			vector![0.0, 5.0, 0.0]
+		);
+		assert_eq!(
+			EXPLOSION.impulse_at(vector![-1.0, 0.0, 0.0]),
+			vector![-7.5, 0.0, 0.0]
+		);
+
+		let impulse = EXPLOSION.impulse_at(vector![1.0, 1.0, 1.0]);
+		assert!((impulse.normalize() - vector![1.0, 1.0, 1.0].normalize()).norm() < 1e-6);
+	}
+
+	#[test]
+	fn nothing_is_pushed_from_the_center_or_beyond_the_radius() {
+		assert_eq!(EXPLOSION.impulse_at(Vector3::zeros()), Vector3::zeros());
+		assert_eq!(
+			EXPLOSION.impulse_at(vector![0.0, 0.0, 5.0]),
+			Vector3::zeros()
+		);
+	}
+}
@@ -0,0 +1,62 @@
+use log::warn;
+use solarscape_shared::message::backend::GatewayStats;
+use sqlx::{query, PgPool};
+use std::time::{Duration, Instant};
+
+/// The latest report from a gateway, see [`GatewayStats`].
+pub struct GatewayReport {
+	/// The address the gateway accepts connections on.
+	pub gateway: Box<str>,
+	pub stats: GatewayStats,
+
+	/// When the report was written, going by our clock rather than the database's.
+	reported: Instant,
+}
+
+impl GatewayReport {
+	pub fn age(&self) -> Duration {
+		self.reported.elapsed()
+	}
+
+	/// Whether the gateway seems to have stopped reporting.
+	pub fn is_stale(&self) -> bool {
+		self.age() > GatewayStats::STALE_AFTER
+	}
+}
+
+/// Reads the latest report from every gateway. Reports which can't be parsed are skipped.
+pub async fn fetch(database: &PgPool) -> Result<Vec<GatewayReport>, sqlx::Error> {
+	let rows = query!(
+		r#"SELECT gateway, EXTRACT(EPOCH FROM NOW() - reported)::Float8 AS "age!", stats
+			FROM gateway_stats ORDER BY gateway"#
+	)
+	.fetch_all(database)
+	.await?;
+
+	let now = Instant::now();
+
+	Ok(rows
+		.into_iter()
+		.filter_map(|row| {
+			let stats = match serde_json::from_str(&row.stats) {
+				Ok(stats) => stats,
+				Err(error) => {
+					warn!(
+						"Unable to parse stats from gateway {}: {error}",
+						row.gateway
+					);
+					return None;
+				}
+			};
+
+			// The database's clock may be a little ahead of ours, which would give a negative age
+			let age = Duration::try_from_secs_f64(row.age).unwrap_or_default();
+
+			Some(GatewayReport {
+				gateway: row.gateway.into(),
+				stats,
+				reported: now.checked_sub(age).unwrap_or(now),
+			})
+		})
+		.collect())
+}
@@ -0,0 +1,422 @@
+use crate::{config, sector::Data};
+use log::{debug, info, warn};
+use solarscape_shared::{
+	consts::CELLS_PER_CHUNK,
+	data::world::{ChunkCoordinates, Material},
+	hash::fnv1a,
+};
+use std::{
+	fs::{self, File},
+	io::{self, ErrorKind::NotFound, Read},
+	path::{Path, PathBuf},
+	sync::atomic::{AtomicU64, Ordering::Relaxed},
+	time::SystemTime,
+};
+
+/// Bytes in a cached chunk, one for each material followed by each density as a little endian f32.
+const DATA_SIZE: usize = CELLS_PER_CHUNK * 5;
+
+/// Fraction of [`config::GenerationCache::max_size`] eviction goes down to, so that it isn't needed again for a while.
+/// Each eviction lists the whole cache.
+const LOW_WATER: f64 = 0.9;
+
+/// Extension of chunks that are still being written.
+const PARTIAL: &str = "partial";
+
+/// Generated chunk data kept on disk, so that a restarted sector doesn't have to generate everything again. Chunks are
+/// keyed by the hash of the generator that made them, see [`generator_hash`], so changing a generator leaves the old
+/// chunks unused until they're evicted rather than serving stale terrain. Each chunk's modified time is updated when
+/// it's read, and the least recently used chunks are deleted once the cache is over [`config::GenerationCache::max_size`].
+pub struct GenerationCache {
+	directory: PathBuf,
+	/// Bytes, `0` if there's no limit.
+	max_size: u64,
+	regenerate: bool,
+	/// Bytes, as of the last eviction plus everything written since.
+	size: AtomicU64,
+}
+
+impl GenerationCache {
+	/// Opens the cache, [`None`] if it's disabled or the directory can't be created.
+	pub fn new(
+		config::GenerationCache {
+			directory,
+			max_size,
+			regenerate,
+		}: config::GenerationCache,
+	) -> Option<Self> {
+		let directory = directory?;
+
+		if let Err(error) = fs::create_dir_all(&directory) {
+			warn!(
+				"Unable to create generation cache directory {}, chunks won't be cached: {error}",
+				directory.display()
+			);
+			return None;
+		}
+
+		if regenerate {
+			info!("Ignoring cached chunks, everything will be generated again");
+		}
+
+		let cache = Self {
+			directory,
+			max_size: max_size * 1024 * 1024,
+			regenerate,
+			size: AtomicU64::new(0),
+		};
+
+		cache.evict();
+		Some(cache)
+	}
+
+	fn path(&self, generator_hash: u64, coordinates: &ChunkCoordinates) -> PathBuf {
+		self.directory
+			.join(format!("{generator_hash:016x}"))
+			.join(format!(
+				"{}_{}_{}_{}",
+				coordinates.level, coordinates.x, coordinates.y, coordinates.z
+			))
+	}
+
+	/// The chunk at `coordinates` as made by the generator with `generator_hash`, if it's cached.
+	pub fn read(&self, generator_hash: u64, coordinates: &ChunkCoordinates) -> Option<Data> {
+		if self.regenerate {
+			return None;
+		}
+
+		// Opened for writing too, so that the modified time can be updated without opening it again
+		let mut file = match File::options()
+			.read(true)
+			.write(true)
+			.open(self.path(generator_hash, coordinates))
+		{
+			Ok(file) => file,
+			Err(error) if error.kind() == NotFound => return None,
+			Err(error) => {
+				warn!("Unable to read cached chunk {coordinates}, it will be generated instead: {error}");
+				return None;
+			}
+		};
+
+		let mut bytes = Vec::with_capacity(DATA_SIZE);
+
+		if let Err(error) = file.read_to_end(&mut bytes) {
+			warn!(
+				"Unable to read cached chunk {coordinates}, it will be generated instead: {error}"
+			);
+			return None;
+		}
+
+		let Some(data) = decode(&bytes) else {
+			warn!("Cached chunk {coordinates} is corrupt, it will be generated again");
+			return None;
+		};
+
+		// Marks the chunk as recently used, eviction going by modified time
+		if let Err(error) = file.set_modified(SystemTime::now()) {
+			debug!("Unable to update cached chunk {coordinates}'s modified time: {error}");
+		}
+
+		Some(data)
+	}
+
+	/// Caches `bytes` from [`encode`], evicting chunks if that puts the cache over it's size limit. Blocks on file IO.
+	pub fn write(&self, generator_hash: u64, coordinates: &ChunkCoordinates, bytes: &[u8]) {
+		let path = self.path(generator_hash, coordinates);
+
+		// Written under another name and then moved into place, so that a chunk is never read half written
+		let result = (|| {
+			if let Some(directory) = path.parent() {
+				fs::create_dir_all(directory)?;
+			}
+
+			let partial = path.with_extension(PARTIAL);
+			fs::write(&partial, bytes)?;
+			fs::rename(&partial, &path)
+		})();
+
+		if let Err(error) = result {
+			warn!("Unable to cache chunk {coordinates}: {error}");
+			return;
+		}
+
+		let size = self.size.fetch_add(bytes.len() as u64, Relaxed) + bytes.len() as u64;
+
+		if self.max_size > 0 && size > self.max_size {
+			self.evict();
+		}
+	}
+
+	/// Deletes the least recently used chunks until the cache is down to [`LOW_WATER`] of it's size limit, and recounts
+	/// it's size.
+	fn evict(&self) {
+		let mut files = match files(&self.directory) {
+			Ok(files) => files,
+			Err(error) => {
+				warn!("Unable to list the generation cache: {error}");
+				return;
+			}
+		};
+
+		let mut size = files.iter().map(|(_, length, _)| length).sum::<u64>();
+
+		if self.max_size > 0 && size > self.max_size {
+			files.sort_by_key(|(_, _, modified)| *modified);
+
+			let low_water = (self.max_size as f64 * LOW_WATER) as u64;
+			let mut evicted = 0;
+
+			for (path, length, _) in files {
+				if size <= low_water {
+					break;
+				}
+
+				match fs::remove_file(&path) {
+					Ok(()) => {
+						size -= length;
+						evicted += 1;
+					}
+					Err(error) => warn!("Unable to evict cached chunk {}: {error}", path.display()),
+				}
+			}
+
+			debug!("Evicted {evicted} chunks from the generation cache");
+		}
+
+		self.size.store(size, Relaxed);
+	}
+}
+
+/// Every cached chunk in `directory`, with it's length and modified time. Chunks still being written are left out, so
+/// they aren't evicted from under their writer.
+fn files(directory: &Path) -> io::Result<Vec<(PathBuf, u64, SystemTime)>> {
+	let mut files = vec![];
+
+	for generator in fs::read_dir(directory)? {
+		let generator = generator?;
+
+		if !generator.file_type()?.is_dir() {
+			continue;
+		}
+
+		for chunk in fs::read_dir(generator.path())? {
+			let chunk = chunk?;
+			let metadata = chunk.metadata()?;
+
+			if metadata.is_file() && chunk.path().extension() != Some(PARTIAL.as_ref()) {
+				files.push((chunk.path(), metadata.len(), metadata.modified()?));
+			}
+		}
+	}
+
+	Ok(files)
+}
+
+/// `data` in the format [`GenerationCache`] keeps it in.
+pub fn encode(data: &Data) -> Vec<u8> {
+	let mut bytes = Vec::with_capacity(DATA_SIZE);
+
+	bytes.extend(data.materials.iter().map(|material| *material as u8));
+
+	for density in data.densities.iter() {
+		bytes.extend(density.to_le_bytes());
+	}
+
+	bytes
+}
+
+fn decode(bytes: &[u8]) -> Option<Data> {
+	if bytes.len() != DATA_SIZE {
+		return None;
+	}
+
+	let (materials, densities) = bytes.split_at(CELLS_PER_CHUNK);
+	let mut data = Data::default();
+
+	for (material, byte) in data.materials.iter_mut().zip(materials) {
+		*material = Material::try_from(*byte).ok()?;
+	}
+
+	for (density, bytes) in data.densities.iter_mut().zip(densities.chunks_exact(4)) {
+		*density = f32::from_le_bytes(bytes.try_into().ok()?);
+	}
+
+	Some(data)
+}
+
+/// Identifies a generator's output, from it's name, version, and parameters. Uses [`fnv1a`], as cached chunks are
+/// looked up by it so it has to be the same across runs.
+pub fn generator_hash(name: &str, version: u32, parameters: &[u8]) -> u64 {
+	// Lengths are included so that moving bytes between the name and parameters changes the hash
+	fnv1a(
+		[
+			&(name.len() as u64).to_le_bytes()[..],
+			name.as_bytes(),
+			&version.to_le_bytes(),
+			&(parameters.len() as u64).to_le_bytes(),
+			parameters,
+		]
+		.into_iter()
+		.flatten()
+		.copied(),
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::vector;
+	use solarscape_shared::data::{world::Level, Id};
+	use std::{env, time::Duration};
+
+	/// A cache in it's own temporary directory, deleted when dropped.
+	struct TestCache(GenerationCache);
+
+	impl TestCache {
+		fn new(max_size: u64) -> Self {
+			let directory =
+				env::temp_dir().join(format!("solarscape-generation-cache-{}", Id::new()));
+			fs::create_dir_all(&directory).unwrap();
+
+			Self(GenerationCache {
+				directory,
+				max_size,
+				regenerate: false,
+				size: AtomicU64::new(0),
+			})
+		}
+	}
+
+	impl Drop for TestCache {
+		fn drop(&mut self) {
+			let _ = fs::remove_dir_all(&self.0.directory);
+		}
+	}
+
+	fn chunk(x: i32) -> ChunkCoordinates {
+		ChunkCoordinates::new(Id::new(), vector![x, -x, 0], Level::new(0))
+	}
+
+	fn data(density: f32) -> Data {
+		let mut data = Data::default();
+		data.materials[1] = Material::Stone;
+		data.densities[1] = density;
+		data
+	}
+
+	#[test]
+	fn generator_hash_is_stable() {
+		// Cached chunks are looked up by this, so it can't change between runs or platforms
+		assert_eq!(generator_hash("sphere", 1, &[]), 0x82d010dbf6e8058f);
+		assert_eq!(
+			generator_hash("sphere", 1, &[]),
+			generator_hash("sphere", 1, &[])
+		);
+	}
+
+	#[test]
+	fn generator_hash_covers_every_input() {
+		let hash = generator_hash("sphere", 1, &[1, 2]);
+
+		assert_ne!(hash, generator_hash("sphere", 2, &[1, 2]));
+		assert_ne!(hash, generator_hash("sphere", 1, &[1, 3]));
+		assert_ne!(hash, generator_hash("spheres", 1, &[1, 2]));
+		assert_ne!(generator_hash("ab", 1, &[]), generator_hash("a", 1, b"b"));
+	}
+
+	#[test]
+	fn written_chunks_are_read_back() {
+		let cache = TestCache::new(0);
+		let coordinates = chunk(-3);
+		let data = data(0.5);
+
+		assert!(cache.0.read(1, &coordinates).is_none());
+
+		cache.0.write(1, &coordinates, &encode(&data));
+
+		let read = cache
+			.0
+			.read(1, &coordinates)
+			.expect("chunk should be cached");
+		assert_eq!(read.materials, data.materials);
+		assert_eq!(read.densities, data.densities);
+
+		// A different generator's chunk isn't the same chunk
+		assert!(cache.0.read(2, &coordinates).is_none());
+	}
+
+	#[test]
+	fn regenerate_ignores_cached_chunks() {
+		let mut cache = TestCache::new(0);
+		let coordinates = chunk(0);
+
+		cache.0.write(1, &coordinates, &encode(&data(0.5)));
+		cache.0.regenerate = true;
+
+		assert!(cache.0.read(1, &coordinates).is_none());
+	}
+
+	#[test]
+	fn corrupt_chunks_are_generated_again() {
+		let cache = TestCache::new(0);
+		let coordinates = chunk(0);
+
+		cache.0.write(1, &coordinates, &[0; DATA_SIZE - 1]);
+		assert!(cache.0.read(1, &coordinates).is_none());
+
+		cache.0.write(1, &coordinates, &[u8::MAX; DATA_SIZE]);
+		assert!(cache.0.read(1, &coordinates).is_none());
+	}
+
+	#[test]
+	fn eviction_removes_least_recently_used_down_to_low_water() {
+		let mut cache = TestCache::new(0);
+		let now = SystemTime::now();
+		let chunks = (0..60).map(chunk).collect::<Vec<_>>();
+
+		for (index, coordinates) in chunks.iter().enumerate() {
+			cache.0.write(1, coordinates, &encode(&data(index as f32)));
+
+			// Later chunks were used more recently
+			File::options()
+				.write(true)
+				.open(cache.0.path(1, coordinates))
+				.and_then(|file| file.set_modified(now - Duration::from_secs(60 - index as u64)))
+				.unwrap();
+		}
+
+		cache.0.max_size = 1024 * 1024;
+		cache.0.evict();
+
+		// 46 chunks is the most that fits in 90% of 1 MiB
+		let kept = 46;
+
+		for (index, coordinates) in chunks.iter().enumerate() {
+			assert_eq!(
+				cache.0.path(1, coordinates).exists(),
+				index >= chunks.len() - kept,
+				"chunk {index} should only be kept if it's one of the {kept} most recently used"
+			);
+		}
+
+		assert_eq!(cache.0.size.load(Relaxed), (kept * DATA_SIZE) as u64);
+	}
+
+	#[test]
+	fn eviction_skips_partial_chunks() {
+		let cache = TestCache::new(1);
+		let coordinates = chunk(0);
+
+		cache.0.write(1, &coordinates, &encode(&data(0.5)));
+
+		// As if another write was part way through
+		let partial = cache.0.path(1, &chunk(1)).with_extension(PARTIAL);
+		fs::write(&partial, [0; DATA_SIZE]).unwrap();
+
+		cache.0.evict();
+
+		assert!(!cache.0.path(1, &coordinates).exists());
+		assert!(partial.exists());
+	}
+}
@@ -0,0 +1,220 @@
+use crate::sector::Chunk;
+use std::{
+	collections::VecDeque,
+	iter,
+	sync::{Arc, Mutex, MutexGuard, Weak},
+};
+
+/// Limits how many chunks are generated in the background at once. A player joining asks for thousands of chunks in one
+/// go, and without a limit those jobs fill rayon's pool, leaving everything else that uses it waiting for minutes.
+/// Chunks past the limit are parked and started in the order they were asked for as running jobs finish, which is
+/// already the order the [`SyncQueue`] thinks most important.
+///
+/// Only background generation is limited. Anything that needs a chunk's data straight away, such as building
+/// collision with a chunk's neighbours, generates it on it's own thread, so it never waits behind parked chunks.
+///
+/// [`SyncQueue`]: crate::sync_queue::SyncQueue
+pub struct GenerationQueue {
+	/// `0` if there's no limit.
+	max_jobs: usize,
+	state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+	running: usize,
+	/// Only weak references are kept, so that parked chunks which stop being needed can still be dropped.
+	parked: VecDeque<Weak<Chunk>>,
+}
+
+impl GenerationQueue {
+	pub fn new(max_jobs: usize) -> Self {
+		Self {
+			max_jobs,
+			state: Mutex::default(),
+		}
+	}
+
+	fn state(&self) -> MutexGuard<'_, State> {
+		self.state
+			.lock()
+			.expect("generation queue state should not be poisoned")
+	}
+
+	/// Generates `chunk` in the background, straight away if there's room or once earlier chunks have been.
+	pub fn admit(self: &Arc<Self>, chunk: Weak<Chunk>) {
+		{
+			let mut state = self.state();
+
+			if self.max_jobs > 0 && state.running >= self.max_jobs {
+				state.parked.push_back(chunk);
+				return;
+			}
+
+			state.running += 1;
+		}
+
+		self.spawn(chunk);
+	}
+
+	fn spawn(self: &Arc<Self>, chunk: Weak<Chunk>) {
+		let queue = self.clone();
+
+		rayon::spawn(move || {
+			// Chunks that nothing wants any more have already been dropped, so there's no work to throw away
+			if let Some(chunk) = chunk.upgrade() {
+				chunk.generate_in_background();
+			}
+
+			queue.finish();
+		});
+	}
+
+	/// Starts the next parked chunk that's still wanted in place of a job that just finished.
+	fn finish(self: &Arc<Self>) {
+		let next = {
+			let mut state = self.state();
+
+			let next =
+				iter::from_fn(|| state.parked.pop_front()).find(|chunk| chunk.strong_count() > 0);
+
+			if next.is_none() {
+				state.running -= 1;
+			}
+
+			next
+		};
+
+		if let Some(chunk) = next {
+			self.spawn(chunk);
+		}
+	}
+
+	/// Jobs generating chunks right now, and chunks parked waiting for one.
+	pub fn counts(&self) -> (usize, usize) {
+		let state = self.state();
+		(state.running, state.parked.len())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::sector::{tests::shared_sector, Data, SharedSector};
+	use solarscape_shared::data::world::{ChunkCoordinates, Level};
+	use std::{
+		sync::atomic::{AtomicUsize, Ordering::Relaxed},
+		thread,
+		time::{Duration, Instant},
+	};
+	use tokio::runtime::Runtime;
+
+	/// A sector whose background generation is limited to `max_jobs`.
+	fn limited_sector(
+		runtime: &Runtime,
+		generator: fn(&ChunkCoordinates) -> Data,
+		max_jobs: usize,
+	) -> (Arc<SharedSector>, ChunkCoordinates) {
+		let (mut sector, coordinates) = shared_sector(runtime, generator);
+		Arc::get_mut(&mut sector)
+			.expect("nothing else should have the sector yet")
+			.generation_queue = Arc::new(GenerationQueue::new(max_jobs));
+		(sector, coordinates)
+	}
+
+	/// Every level 0 chunk within `radius` chunks of `origin`'s voxject's origin, in no particular order.
+	fn coordinates(origin: ChunkCoordinates, radius: i32) -> Vec<ChunkCoordinates> {
+		let mut coordinates = vec![];
+
+		for x in -radius..=radius {
+			for y in -radius..=radius {
+				for z in -radius..=radius {
+					coordinates.push(ChunkCoordinates::new(
+						origin.voxject,
+						[x, y, z].into(),
+						Level::new(0),
+					));
+				}
+			}
+		}
+
+		coordinates
+	}
+
+	fn wait_until(condition: impl Fn() -> bool) {
+		let start = Instant::now();
+
+		while !condition() {
+			assert!(start.elapsed() < Duration::from_secs(60), "timed out");
+			thread::sleep(Duration::from_millis(1));
+		}
+	}
+
+	#[test]
+	fn floods_never_run_more_than_the_limit() {
+		static RUNNING: AtomicUsize = AtomicUsize::new(0);
+		static MOST_RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+		fn generator(_: &ChunkCoordinates) -> Data {
+			let running = RUNNING.fetch_add(1, Relaxed) + 1;
+			MOST_RUNNING.fetch_max(running, Relaxed);
+			thread::sleep(Duration::from_micros(50));
+			RUNNING.fetch_sub(1, Relaxed);
+			Data::default()
+		}
+
+		let runtime = Runtime::new().unwrap();
+		let (sector, origin) = limited_sector(&runtime, generator, 8);
+
+		// 12,167 chunks
+		let chunks = coordinates(origin, 11)
+			.into_iter()
+			.map(|coordinates| sector.get_chunk(coordinates))
+			.collect::<Vec<_>>();
+		assert!(chunks.len() >= 10_000);
+
+		let (running, parked) = sector.generation_queue.counts();
+		assert!(running <= 8);
+		assert!(
+			parked > 0,
+			"asking for chunks is far quicker than generating them"
+		);
+
+		wait_until(|| {
+			assert!(sector.generation_queue.counts().0 <= 8);
+			chunks.iter().all(|chunk| chunk.try_read_data().is_some())
+		});
+
+		assert!(MOST_RUNNING.load(Relaxed) <= 8);
+
+		// The last jobs finish just after storing their chunk's data
+		wait_until(|| sector.generation_queue.counts() == (0, 0));
+	}
+
+	#[test]
+	fn parked_chunks_that_are_dropped_are_skipped() {
+		static GENERATED: AtomicUsize = AtomicUsize::new(0);
+
+		fn generator(_: &ChunkCoordinates) -> Data {
+			thread::sleep(Duration::from_millis(20));
+			GENERATED.fetch_add(1, Relaxed);
+			Data::default()
+		}
+
+		let runtime = Runtime::new().unwrap();
+		let (sector, origin) = limited_sector(&runtime, generator, 1);
+
+		let mut chunks = coordinates(origin, 2)
+			.into_iter()
+			.map(|coordinates| sector.get_chunk(coordinates))
+			.collect::<Vec<_>>();
+		let wanted = chunks.pop().unwrap();
+		drop(chunks);
+
+		wait_until(|| wanted.try_read_data().is_some());
+
+		// The first may have started before it was dropped, the rest are skipped until the one that's still wanted
+		assert!(GENERATED.load(Relaxed) <= 2);
+		wait_until(|| sector.generation_queue.counts() == (0, 0));
+	}
+}
@@ -0,0 +1,255 @@
+use crate::config;
+use solarscape_shared::{data::world::Location, message::serverbound::Serverbound};
+use std::time::{Duration, Instant};
+
+/// Distance in meters a player has to move, and angle in radians they have to turn, for a location update to count as
+/// them doing something. Clients send their location every frame whether or not it has changed, and it drifts
+/// slightly even while the player is standing still.
+const ACTIVE_DISTANCE: f32 = 0.01;
+const ACTIVE_ANGLE: f32 = 0.01;
+
+/// How far an away player has to move, or turn, from where they were when they went away for them to be back. Higher
+/// than [`ACTIVE_DISTANCE`] and [`ACTIVE_ANGLE`], so that drifting while away doesn't bring them back for a moment and
+/// have them fetch everything again.
+const RETURN_DISTANCE: f32 = 0.5;
+const RETURN_ANGLE: f32 = 0.1;
+
+/// Distance in meters that away players are still sent structures within, roughly the chunks they keep.
+pub const AWAY_INTEREST_RADIUS: f32 = 32.0;
+
+/// Tracks whether a player has done anything lately. Players who haven't for [`config::Afk::timeout`] are marked as
+/// away, which shrinks what they're sent to just what's around them until they're back, see [`Player::update_locks`].
+///
+/// [`Player::update_locks`]: crate::player::Player::update_locks
+pub struct Idle {
+	last_active: Instant,
+	/// [`None`] while the player is active.
+	away: Option<Away>,
+}
+
+struct Away {
+	since: Instant,
+	location: Location,
+}
+
+/// What [`Idle::tick`] found.
+#[derive(Debug, PartialEq)]
+pub enum IdleTransition {
+	/// The player has just been marked as away.
+	WentAway,
+	/// The player has been away for longer than [`config::Afk::kick_after`], and should be disconnected.
+	Kick,
+}
+
+impl Idle {
+	pub fn new(now: Instant) -> Self {
+		Self {
+			last_active: now,
+			away: None,
+		}
+	}
+
+	pub fn is_away(&self) -> bool {
+		self.away.is_some()
+	}
+
+	/// Looks at a message from the player, who was at `location` before it. Returns `true` if it brings them back from
+	/// being away. Pings, telemetry, view hints, and the chunk cache's messages are sent by the client on it's own, so they don't
+	/// count as the player doing anything.
+	pub fn message(&mut self, message: &Serverbound, location: &Location, now: Instant) -> bool {
+		let active = match message {
+			Serverbound::Ping(_)
+			| Serverbound::ClientTelemetry(_)
+			| Serverbound::HaveChunks(_)
+			| Serverbound::RequestChunk(_)
+			| Serverbound::ViewHint(_) => false,
+			Serverbound::PlayerLocation(new) => match &self.away {
+				Some(away) => moved(&away.location, new, RETURN_DISTANCE, RETURN_ANGLE),
+				None => moved(location, new, ACTIVE_DISTANCE, ACTIVE_ANGLE),
+			},
+			_ => true,
+		};
+
+		if !active {
+			return false;
+		}
+
+		self.last_active = now;
+		self.away.take().is_some()
+	}
+
+	/// Marks the player as away once they've been idle for long enough, the player being at `location`.
+	pub fn tick(
+		&mut self,
+		now: Instant,
+		location: &Location,
+		config: &config::Afk,
+	) -> Option<IdleTransition> {
+		if config.timeout == 0 {
+			return None;
+		}
+
+		match &self.away {
+			None => {
+				let idle = now.saturating_duration_since(self.last_active);
+
+				(idle >= Duration::from_secs(config.timeout)).then(|| {
+					self.away = Some(Away {
+						since: now,
+						location: *location,
+					});
+
+					IdleTransition::WentAway
+				})
+			}
+			Some(away) => {
+				let away_for = now.saturating_duration_since(away.since);
+
+				(config.kick_after > 0 && away_for >= Duration::from_secs(config.kick_after))
+					.then_some(IdleTransition::Kick)
+			}
+		}
+	}
+}
+
+fn moved(from: &Location, to: &Location, distance: f32, angle: f32) -> bool {
+	(to.position - from.position).norm() > distance || from.rotation.angle_to(&to.rotation) > angle
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::{Point3, UnitQuaternion, Vector3};
+	use solarscape_shared::message::serverbound::{Ping, SendChatMessage, ViewHint};
+
+	fn afk(timeout: u64, kick_after: u64) -> config::Afk {
+		config::Afk {
+			timeout,
+			kick_after,
+		}
+	}
+
+	fn here() -> Location {
+		Location {
+			position: Point3::origin(),
+			rotation: UnitQuaternion::identity(),
+		}
+	}
+
+	fn moved_by(distance: f32) -> Serverbound {
+		Serverbound::PlayerLocation(Location {
+			position: Point3::origin() + Vector3::x() * distance,
+			..here()
+		})
+	}
+
+	fn turned_by(angle: f32) -> Serverbound {
+		Serverbound::PlayerLocation(Location {
+			rotation: UnitQuaternion::from_euler_angles(0.0, angle, 0.0),
+			..here()
+		})
+	}
+
+	#[test]
+	fn only_the_player_doing_something_counts() {
+		let start = Instant::now();
+		let later = start + Duration::from_secs(10);
+
+		let mut idle = Idle::new(start);
+		idle.message(&Serverbound::Ping(Ping { sequence: 0 }), &here(), later);
+		let hint = ViewHint::new(Vector3::x(), 1.0, 1.0);
+		idle.message(&Serverbound::ViewHint(hint), &here(), later);
+		idle.message(&moved_by(ACTIVE_DISTANCE / 2.0), &here(), later);
+		idle.message(&turned_by(ACTIVE_ANGLE / 2.0), &here(), later);
+		assert_eq!(idle.last_active, start);
+
+		for message in [
+			moved_by(ACTIVE_DISTANCE * 2.0),
+			turned_by(ACTIVE_ANGLE * 2.0),
+			Serverbound::SendChatMessage(SendChatMessage { text: "hi".into() }),
+		] {
+			let mut idle = Idle::new(start);
+			assert!(!idle.message(&message, &here(), later));
+			assert_eq!(idle.last_active, later);
+		}
+	}
+
+	#[test]
+	fn players_go_away_then_are_kicked() {
+		let start = Instant::now();
+		let at = |seconds| start + Duration::from_secs(seconds);
+		let config = afk(300, 600);
+
+		let mut idle = Idle::new(start);
+		assert_eq!(idle.tick(at(299), &here(), &config), None);
+		assert!(!idle.is_away());
+
+		assert_eq!(
+			idle.tick(at(300), &here(), &config),
+			Some(IdleTransition::WentAway)
+		);
+		assert!(idle.is_away());
+
+		// Away for long enough is from when they went away, not when they were last active
+		assert_eq!(idle.tick(at(899), &here(), &config), None);
+		assert_eq!(
+			idle.tick(at(900), &here(), &config),
+			Some(IdleTransition::Kick)
+		);
+	}
+
+	#[test]
+	fn zero_disables_going_away_and_kicking() {
+		let start = Instant::now();
+		let much_later = start + Duration::from_secs(1_000_000);
+
+		let mut idle = Idle::new(start);
+		assert_eq!(idle.tick(much_later, &here(), &afk(0, 1)), None);
+		assert!(!idle.is_away());
+
+		assert_eq!(
+			idle.tick(much_later, &here(), &afk(1, 0)),
+			Some(IdleTransition::WentAway)
+		);
+		assert_eq!(
+			idle.tick(
+				much_later + much_later.duration_since(start),
+				&here(),
+				&afk(1, 0)
+			),
+			None
+		);
+	}
+
+	#[test]
+	fn coming_back_takes_more_than_drifting() {
+		let start = Instant::now();
+		let later = start + Duration::from_secs(10);
+
+		let mut idle = Idle::new(start);
+		idle.tick(start + Duration::from_secs(1), &here(), &afk(1, 0));
+		assert!(idle.is_away());
+
+		// Enough to count while active, but not enough to come back
+		assert!(!idle.message(&moved_by(ACTIVE_DISTANCE * 2.0), &here(), later));
+		assert!(!idle.message(&turned_by(ACTIVE_ANGLE * 2.0), &here(), later));
+
+		// Measured from where they went away, so drifting a little at a time doesn't add up to coming back
+		let drifted = Location {
+			position: Point3::origin() + Vector3::x() * (RETURN_DISTANCE * 0.9),
+			..here()
+		};
+		assert!(!idle.message(&moved_by(RETURN_DISTANCE * 0.9), &drifted, later));
+		assert!(idle.is_away());
+		assert_eq!(idle.last_active, start);
+
+		assert!(idle.message(&moved_by(RETURN_DISTANCE * 2.0), &drifted, later));
+		assert!(!idle.is_away());
+		assert_eq!(idle.last_active, later);
+
+		// Anything else the player does brings them back too
+		idle.tick(later + Duration::from_secs(1), &here(), &afk(1, 0));
+		assert!(idle.is_away());
+		assert!(idle.message(&turned_by(RETURN_ANGLE * 2.0), &here(), later));
+	}
+}
@@ -0,0 +1,354 @@
+use nalgebra::{Point3, Vector3};
+use rapier3d::geometry::Ray;
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::{
+	consts::REACH,
+	data::{
+		world::{BlockState, BlockType},
+		Id,
+	},
+	message::clientbound::{InventoryCapacity, InventorySlot, SyncInventory},
+	physics::{ColliderOwner, CollisionGroup, Physics, QueryMask},
+	structure::Structure,
+};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// What happens when a block of a particular type is used, registered for that type in [`Interactions`].
+pub trait Interaction: Send {
+	/// What using a block in `state` does.
+	fn interact(&self, state: BlockState) -> Effect;
+}
+
+/// What an [`Interaction`] does, which the sector carries out.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Effect {
+	/// Changes the block's state, every player that has it's structure is sent the change.
+	SetState(BlockState),
+	/// Sends the block's container to the player that used it, see [`Containers`].
+	OpenContainer,
+}
+
+/// Which [`Interaction`] each interactable block type has.
+pub struct Interactions {
+	interactions: HashMap<BlockType, Box<dyn Interaction>, FxBuildHasher>,
+}
+
+impl Interactions {
+	/// Every block type's built in interaction.
+	pub fn new() -> Self {
+		let mut interactions = Self {
+			interactions: HashMap::with_hasher(FxBuildHasher),
+		};
+
+		interactions.register(BlockType::Switch, Switch);
+		interactions.register(BlockType::Container, Container);
+
+		debug_assert!(
+			BlockType::ALL
+				.iter()
+				.all(|block| block.is_interactable() == interactions.get(*block).is_some()),
+			"BlockType::is_interactable should match the registered interactions"
+		);
+
+		interactions
+	}
+
+	/// Replaces whatever `block` did when used before.
+	pub fn register(&mut self, block: BlockType, interaction: impl Interaction + 'static) {
+		self.interactions.insert(block, Box::new(interaction));
+	}
+
+	pub fn get(&self, block: BlockType) -> Option<&dyn Interaction> {
+		self.interactions
+			.get(&block)
+			.map(|interaction| &**interaction)
+	}
+}
+
+/// Turns a [`BlockType::Switch`] on or off.
+pub struct Switch;
+
+impl Interaction for Switch {
+	fn interact(&self, state: BlockState) -> Effect {
+		Effect::SetState(match state {
+			0 => 1,
+			_ => 0,
+		})
+	}
+}
+
+/// Opens a [`BlockType::Container`].
+pub struct Container;
+
+impl Interaction for Container {
+	fn interact(&self, _: BlockState) -> Effect {
+		Effect::OpenContainer
+	}
+}
+
+/// Why someone couldn't use a block.
+#[derive(Clone, Copy, Debug, Error, PartialEq)]
+pub enum Unreachable {
+	#[error("it's {distance:.1}m away, further than {}m", REACH)]
+	OutOfRange { distance: f32 },
+
+	#[error("something is in the way")]
+	Obstructed,
+}
+
+/// Extra distance allowed past [`REACH`], as the player may have moved since the client checked.
+pub const REACH_SLACK: f32 = 1.0;
+
+/// Checks that a player looking from `eye` can reach the block at `position` in `structure`, that it's within
+/// [`REACH`] of it's center and that nothing else is in the way. Only terrain and other blocks can be
+/// in the way, players aren't.
+pub fn check_reach(
+	physics: &Physics,
+	eye: &Point3<f32>,
+	structure: &Structure,
+	position: &Vector3<i16>,
+) -> Result<(), Unreachable> {
+	let center = structure.get_location(physics) * Point3::from(position.cast::<f32>());
+	let offset = center - eye;
+	let distance = offset.norm();
+
+	if !distance.is_finite() || distance > REACH + REACH_SLACK {
+		return Err(Unreachable::OutOfRange { distance });
+	}
+
+	let Some(direction) = offset.try_normalize(f32::EPSILON) else {
+		// Already inside of the block
+		return Ok(());
+	};
+
+	let hit = physics.cast_ray(
+		&Ray::new(*eye, direction),
+		distance,
+		QueryMask::new(&[CollisionGroup::Terrain, CollisionGroup::Structure]),
+	);
+
+	match hit.and_then(|hit| hit.hit.owner) {
+		None => Ok(()),
+		Some(ColliderOwner::StructureBlock(id, hit)) if id == structure.id && hit == *position => {
+			Ok(())
+		}
+		Some(_) => Err(Unreachable::Obstructed),
+	}
+}
+
+/// What every [`BlockType::Container`] in the sector holds. Only kept in memory, so containers are emptied when the
+/// sector restarts, and when their block is destroyed.
+#[derive(Default)]
+pub struct Containers {
+	contents: HashMap<(Id, Vector3<i16>), Vec<InventorySlot>, FxBuildHasher>,
+}
+
+impl Containers {
+	pub const CAPACITY: InventoryCapacity = InventoryCapacity {
+		max_stacks: 9,
+		max_quantity: 100,
+	};
+
+	pub fn build_sync(&self, structure: Id, position: Vector3<i16>) -> SyncInventory {
+		SyncInventory {
+			slots: self
+				.contents
+				.get(&(structure, position))
+				.cloned()
+				.unwrap_or_default(),
+			capacity: Self::CAPACITY,
+		}
+	}
+
+	/// Forgets what the container at `position` in `structure` held, for when it's destroyed.
+	pub fn remove(&mut self, structure: Id, position: &Vector3<i16>) {
+		self.contents.remove(&(structure, *position));
+	}
+
+	/// Forgets every container in `structure`, for when it's removed.
+	pub fn remove_structure(&mut self, structure: Id) {
+		self.contents.retain(|(id, _), _| *id != structure);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::{point, vector, UnitQuaternion};
+	use solarscape_shared::{
+		data::world::{Item, Location},
+		message::clientbound::SyncStructure,
+	};
+
+	/// A structure at `position` made of `blocks`, with queries brought up to date so that it can be hit.
+	fn structure(
+		physics: &mut Physics,
+		position: Point3<f32>,
+		blocks: &[(Vector3<i16>, BlockType)],
+	) -> Structure {
+		let structure = Structure::new_from_sync(
+			physics,
+			SyncStructure {
+				id: Id::new(),
+				owner: Id::new(),
+				location: Location {
+					position,
+					rotation: UnitQuaternion::identity(),
+				},
+				linear_velocity: Vector3::zeros(),
+				angular_velocity: Vector3::zeros(),
+				sleeping: false,
+				blocks: blocks.iter().copied().collect(),
+				block_states: Default::default(),
+			},
+		);
+
+		physics.update_queries();
+		structure
+	}
+
+	#[test]
+	fn interactable_blocks_are_registered() {
+		let interactions = Interactions::new();
+
+		for block in BlockType::ALL {
+			assert_eq!(
+				interactions.get(*block).is_some(),
+				block.is_interactable(),
+				"{block:?}"
+			);
+		}
+	}
+
+	#[test]
+	fn registering_replaces_an_interaction() {
+		let mut interactions = Interactions::new();
+		interactions.register(BlockType::Switch, Container);
+
+		let switch = interactions.get(BlockType::Switch).unwrap();
+		assert_eq!(switch.interact(0), Effect::OpenContainer);
+
+		interactions.register(BlockType::Block, Switch);
+		assert!(interactions.get(BlockType::Block).is_some());
+	}
+
+	#[test]
+	fn switches_toggle_and_containers_open() {
+		assert_eq!(Switch.interact(0), Effect::SetState(1));
+		assert_eq!(Switch.interact(1), Effect::SetState(0));
+
+		// Anything else is treated as on, so it still turns off
+		assert_eq!(Switch.interact(7), Effect::SetState(0));
+
+		assert_eq!(Container.interact(0), Effect::OpenContainer);
+		assert_eq!(Container.interact(3), Effect::OpenContainer);
+	}
+
+	#[test]
+	fn blocks_must_be_within_reach() {
+		let mut physics = Physics::new();
+		let structure = structure(
+			&mut physics,
+			point![0.0, 0.0, 0.0],
+			&[(Vector3::zeros(), BlockType::Switch)],
+		);
+		let position = Vector3::zeros();
+		let reach = |eye: Point3<f32>| check_reach(&physics, &eye, &structure, &position);
+
+		assert_eq!(reach(point![5.0, 0.0, 0.0]), Ok(()));
+		assert_eq!(reach(point![REACH + REACH_SLACK - 0.1, 0.0, 0.0]), Ok(()));
+		assert_eq!(reach(point![0.0, 0.0, 0.0]), Ok(()));
+
+		assert_eq!(
+			reach(point![0.0, 20.0, 0.0]),
+			Err(Unreachable::OutOfRange { distance: 20.0 })
+		);
+		assert!(matches!(
+			reach(point![REACH + REACH_SLACK + 0.1, 0.0, 0.0]),
+			Err(Unreachable::OutOfRange { .. })
+		));
+		assert!(matches!(
+			reach(point![f32::NAN, 0.0, 0.0]),
+			Err(Unreachable::OutOfRange { .. })
+		));
+	}
+
+	#[test]
+	fn blocks_must_be_in_sight() {
+		let mut physics = Physics::new();
+		let target = structure(
+			&mut physics,
+			point![0.0, 0.0, 0.0],
+			&[
+				(Vector3::zeros(), BlockType::Switch),
+				(vector![0, 0, 1], BlockType::Block),
+			],
+		);
+		let _other = structure(
+			&mut physics,
+			point![3.0, 0.0, 0.0],
+			&[(Vector3::zeros(), BlockType::Block)],
+		);
+		let reach = |eye: Point3<f32>| check_reach(&physics, &eye, &target, &Vector3::zeros());
+
+		// Behind another block of the same structure, and of another structure
+		assert_eq!(reach(point![0.0, 0.0, 5.0]), Err(Unreachable::Obstructed));
+		assert_eq!(reach(point![5.0, 0.0, 0.0]), Err(Unreachable::Obstructed));
+
+		// In plain sight
+		assert_eq!(reach(point![0.0, 5.0, 0.0]), Ok(()));
+		assert_eq!(reach(point![-5.0, 0.0, 0.0]), Ok(()));
+	}
+
+	#[test]
+	fn containers_are_forgotten_with_their_block() {
+		let mut containers = Containers::default();
+		let (structure, other) = (Id::new(), Id::new());
+		let slot = InventorySlot {
+			item: Item::TestOre,
+			quantity: 5,
+		};
+
+		for (id, position) in [
+			(structure, vector![0, 0, 0]),
+			(structure, vector![1, 0, 0]),
+			(other, vector![0, 0, 0]),
+		] {
+			containers.contents.insert((id, position), vec![slot]);
+		}
+
+		let sync = containers.build_sync(structure, vector![0, 0, 0]);
+		assert_eq!(sync.slots.len(), 1);
+		assert_eq!(sync.capacity.max_stacks, Containers::CAPACITY.max_stacks);
+
+		// Never used, so empty
+		assert!(containers
+			.build_sync(structure, vector![5, 0, 0])
+			.slots
+			.is_empty());
+
+		containers.remove(structure, &vector![0, 0, 0]);
+		assert!(containers
+			.build_sync(structure, vector![0, 0, 0])
+			.slots
+			.is_empty());
+		assert_eq!(
+			containers
+				.build_sync(structure, vector![1, 0, 0])
+				.slots
+				.len(),
+			1
+		);
+
+		containers.remove_structure(structure);
+		assert!(containers
+			.build_sync(structure, vector![1, 0, 0])
+			.slots
+			.is_empty());
+		assert_eq!(
+			containers.build_sync(other, vector![0, 0, 0]).slots.len(),
+			1
+		);
+	}
+}
@@ -0,0 +1,328 @@
+use nalgebra::{Point3, Vector3};
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::data::Id;
+use std::collections::{HashMap, HashSet};
+
+/// Where each entity is, bucketed into a coarse grid so that finding everything near a player doesn't mean checking
+/// every entity in the sector.
+#[derive(Default)]
+pub struct InterestGrid {
+	cells: HashMap<Vector3<i32>, HashSet<Id, FxBuildHasher>, FxBuildHasher>,
+	positions: HashMap<Id, Point3<f32>, FxBuildHasher>,
+}
+
+impl InterestGrid {
+	/// Size of each grid cell in meters.
+	pub const CELL_SIZE: f32 = 256.0;
+
+	fn cell(position: &Point3<f32>) -> Vector3<i32> {
+		(position.coords / Self::CELL_SIZE).map(|coordinate| coordinate.floor() as i32)
+	}
+
+	/// Adds `entity` at `position`, or moves it there if it's already in the grid.
+	pub fn update(&mut self, entity: Id, position: Point3<f32>) {
+		let cell = Self::cell(&position);
+
+		if let Some(previous) = self.positions.insert(entity, position) {
+			let previous = Self::cell(&previous);
+
+			if previous == cell {
+				return;
+			}
+
+			self.remove_from_cell(entity, &previous);
+		}
+
+		self.cells.entry(cell).or_default().insert(entity);
+	}
+
+	/// Where `entity` was when it was last added or moved, [`None`] if it isn't in the grid.
+	pub fn position(&self, entity: Id) -> Option<&Point3<f32>> {
+		self.positions.get(&entity)
+	}
+
+	pub fn remove(&mut self, entity: Id) {
+		if let Some(position) = self.positions.remove(&entity) {
+			self.remove_from_cell(entity, &Self::cell(&position));
+		}
+	}
+
+	/// Every entity within `radius` of `center`. An infinite radius returns every entity.
+	pub fn query(&self, center: &Point3<f32>, radius: f32) -> HashSet<Id, FxBuildHasher> {
+		if radius.is_infinite() {
+			return self.positions.keys().copied().collect();
+		}
+
+		let offset = Vector3::repeat(radius);
+		let min = Self::cell(&(center - offset));
+		let max = Self::cell(&(center + offset));
+
+		let mut entities = HashSet::with_hasher(FxBuildHasher);
+
+		for x in min.x..=max.x {
+			for y in min.y..=max.y {
+				for z in min.z..=max.z {
+					let Some(cell) = self.cells.get(&Vector3::new(x, y, z)) else {
+						continue;
+					};
+
+					entities.extend(cell.iter().copied().filter(|entity| {
+						(self.positions[entity] - center).norm_squared() <= radius * radius
+					}));
+				}
+			}
+		}
+
+		entities
+	}
+
+	fn remove_from_cell(&mut self, entity: Id, cell: &Vector3<i32>) {
+		if let Some(entities) = self.cells.get_mut(cell) {
+			entities.remove(&entity);
+
+			if entities.is_empty() {
+				self.cells.remove(cell);
+			}
+		}
+	}
+}
+
+/// The entities a player has been sent, so that they can be told when one leaves their interest radius.
+#[derive(Default)]
+pub struct Interest {
+	visible: HashSet<Id, FxBuildHasher>,
+}
+
+/// Entities which have come into or gone out of a player's interest radius, see [`Interest::update`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct InterestChanges {
+	pub entered: Vec<Id>,
+	pub left: Vec<Id>,
+}
+
+impl Interest {
+	/// How many structures may be sent to each player per second as they come into range. Joining a busy sector would
+	/// otherwise mean building thousands of syncs on the sector's thread at once.
+	const STRUCTURES_PER_SECOND: u32 = 960;
+
+	/// How many structures that have come into range should be sent each tick at `tick_rate`. The rest are removed
+	/// again, so that they come up as entered on the next update.
+	pub fn budget(tick_rate: u32) -> usize {
+		(Self::STRUCTURES_PER_SECOND / tick_rate).max(1) as usize
+	}
+
+	/// Replaces the visible entities with `in_range`, returning which need sending and which need removing.
+	pub fn update(&mut self, in_range: HashSet<Id, FxBuildHasher>) -> InterestChanges {
+		let changes = InterestChanges {
+			entered: in_range.difference(&self.visible).copied().collect(),
+			left: self.visible.difference(&in_range).copied().collect(),
+		};
+
+		self.visible = in_range;
+		changes
+	}
+
+	/// Whether the player has been sent `entity`.
+	pub fn contains(&self, entity: Id) -> bool {
+		self.visible.contains(&entity)
+	}
+
+	pub fn insert(&mut self, entity: Id) {
+		self.visible.insert(entity);
+	}
+
+	/// Forgets `entity`, returning whether the player had been sent it.
+	pub fn remove(&mut self, entity: Id) -> bool {
+		self.visible.remove(&entity)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::point;
+
+	fn id(id: u64) -> Id {
+		id.to_string().parse().unwrap()
+	}
+
+	fn ids(ids: &[u64]) -> HashSet<Id, FxBuildHasher> {
+		ids.iter().copied().map(id).collect()
+	}
+
+	#[test]
+	fn query_finds_entities_within_radius() {
+		let mut grid = InterestGrid::default();
+		grid.update(id(1), point![10.0, 0.0, 0.0]);
+		grid.update(id(2), point![100.0, 0.0, 0.0]);
+		grid.update(id(3), point![0.0, 300.0, 0.0]);
+
+		assert_eq!(grid.query(&point![0.0, 0.0, 0.0], 50.0), ids(&[1]));
+		assert_eq!(grid.query(&point![0.0, 0.0, 0.0], 100.0), ids(&[1, 2]));
+		assert_eq!(grid.query(&point![0.0, 0.0, 0.0], 1000.0), ids(&[1, 2, 3]));
+		assert_eq!(
+			grid.query(&point![0.0, 0.0, 0.0], f32::INFINITY),
+			ids(&[1, 2, 3])
+		);
+		assert!(grid.query(&point![-1000.0, 0.0, 0.0], 100.0).is_empty());
+	}
+
+	#[test]
+	fn query_crosses_cells_and_negative_coordinates() {
+		let mut grid = InterestGrid::default();
+
+		// Either side of the boundaries at 0 and -256
+		grid.update(id(1), point![-1.0, -1.0, -1.0]);
+		grid.update(id(2), point![1.0, 1.0, 1.0]);
+		grid.update(id(3), point![-257.0, 0.0, 0.0]);
+		grid.update(id(4), point![-255.0, 0.0, 0.0]);
+
+		assert_eq!(grid.query(&point![0.0, 0.0, 0.0], 2.0), ids(&[1, 2]));
+		assert_eq!(grid.query(&point![-256.0, 0.0, 0.0], 2.0), ids(&[3, 4]));
+	}
+
+	#[test]
+	fn update_moves_entities() {
+		let mut grid = InterestGrid::default();
+		grid.update(id(1), point![0.0, 0.0, 0.0]);
+
+		// Within the same cell
+		grid.update(id(1), point![10.0, 0.0, 0.0]);
+		assert_eq!(grid.position(id(1)), Some(&point![10.0, 0.0, 0.0]));
+		assert!(grid.query(&point![0.0, 0.0, 0.0], 5.0).is_empty());
+		assert_eq!(grid.query(&point![10.0, 0.0, 0.0], 5.0), ids(&[1]));
+
+		// Into another cell, leaving nothing behind in the old one
+		grid.update(id(1), point![1000.0, 0.0, 0.0]);
+		assert!(grid.query(&point![10.0, 0.0, 0.0], 5.0).is_empty());
+		assert_eq!(grid.query(&point![1000.0, 0.0, 0.0], 5.0), ids(&[1]));
+		assert_eq!(grid.cells.len(), 1);
+	}
+
+	#[test]
+	fn remove_forgets_entities() {
+		let mut grid = InterestGrid::default();
+		grid.update(id(1), point![0.0, 0.0, 0.0]);
+		grid.update(id(2), point![1.0, 0.0, 0.0]);
+
+		grid.remove(id(1));
+		grid.remove(id(3));
+
+		assert_eq!(grid.position(id(1)), None);
+		assert_eq!(grid.query(&point![0.0, 0.0, 0.0], f32::INFINITY), ids(&[2]));
+		assert_eq!(grid.query(&point![0.0, 0.0, 0.0], 5.0), ids(&[2]));
+
+		grid.remove(id(2));
+		assert!(grid.cells.is_empty());
+	}
+
+	#[test]
+	fn update_diffs_entered_and_left() {
+		struct Step {
+			/// Entities moved before the update.
+			moves: &'static [(u64, [f32; 3])],
+			player: [f32; 3],
+			entered: &'static [u64],
+			left: &'static [u64],
+		}
+
+		const RADIUS: f32 = 100.0;
+
+		let steps = [
+			// Joining sees what's already nearby
+			Step {
+				moves: &[(1, [0.0; 3]), (2, [50.0, 0.0, 0.0]), (3, [500.0, 0.0, 0.0])],
+				player: [0.0; 3],
+				entered: &[1, 2],
+				left: &[],
+			},
+			// Nothing changed
+			Step {
+				moves: &[],
+				player: [0.0; 3],
+				entered: &[],
+				left: &[],
+			},
+			// Moving within range isn't a change
+			Step {
+				moves: &[(2, [-50.0, 0.0, 0.0])],
+				player: [0.0; 3],
+				entered: &[],
+				left: &[],
+			},
+			// An entity moves out, another moves in
+			Step {
+				moves: &[(2, [200.0, 0.0, 0.0]), (3, [0.0, 90.0, 0.0])],
+				player: [0.0; 3],
+				entered: &[3],
+				left: &[2],
+			},
+			// The player moves, so everything they could see leaves and what's around them enters
+			Step {
+				moves: &[],
+				player: [250.0, 0.0, 0.0],
+				entered: &[2],
+				left: &[1, 3],
+			},
+			// Right on the edge of the radius is still in range
+			Step {
+				moves: &[(1, [350.0, 0.0, 0.0])],
+				player: [250.0, 0.0, 0.0],
+				entered: &[1],
+				left: &[],
+			},
+		];
+
+		let mut grid = InterestGrid::default();
+		let mut interest = Interest::default();
+
+		for (index, step) in steps.iter().enumerate() {
+			for &(entity, position) in step.moves {
+				grid.update(id(entity), position.into());
+			}
+
+			let in_range = grid.query(&step.player.into(), RADIUS);
+			let changes = interest.update(in_range);
+
+			let entered = changes.entered.into_iter().collect::<HashSet<_, _>>();
+			let left = changes.left.into_iter().collect::<HashSet<_, _>>();
+
+			assert_eq!(entered, ids(step.entered), "entered on step {index}");
+			assert_eq!(left, ids(step.left), "left on step {index}");
+
+			for entity in step.entered {
+				assert!(interest.contains(id(*entity)), "step {index}");
+			}
+
+			for entity in step.left {
+				assert!(!interest.contains(id(*entity)), "step {index}");
+			}
+		}
+	}
+
+	#[test]
+	fn removed_entities_leave_on_next_update() {
+		let mut grid = InterestGrid::default();
+		let mut interest = Interest::default();
+
+		grid.update(id(1), point![0.0, 0.0, 0.0]);
+		interest.update(grid.query(&point![0.0, 0.0, 0.0], 100.0));
+
+		grid.remove(id(1));
+		let changes = interest.update(grid.query(&point![0.0, 0.0, 0.0], 100.0));
+
+		assert_eq!(
+			changes,
+			InterestChanges {
+				entered: vec![],
+				left: vec![id(1)],
+			}
+		);
+	}
+
+	#[test]
+	fn budget_is_at_least_one() {
+		assert_eq!(Interest::budget(60), 16);
+		assert_eq!(Interest::budget(u32::MAX), 1);
+	}
+}
@@ -0,0 +1,188 @@
+use crate::persistence::Persistence;
+use solarscape_shared::{
+	data::{world::Item, Id},
+	message::clientbound::{InventoryCapacity, InventorySlot, SyncInventory},
+};
+use sqlx::{query, query_as, Postgres, Transaction};
+use tokio::runtime::Handle;
+
+/// A player's inventory, which lives in the database rather than in memory, unless the sector isn't using a database.
+pub struct Inventory<'a> {
+	id: Id,
+	capacity: InventoryCapacity,
+	persistence: &'a Persistence,
+}
+
+impl<'a> Inventory<'a> {
+	pub fn new(id: Id, capacity: InventoryCapacity, persistence: &'a Persistence) -> Self {
+		Self {
+			id,
+			capacity,
+			persistence,
+		}
+	}
+
+	pub fn build_sync(&self) -> SyncInventory {
+		Handle::current().block_on(self.load()).expect("inventory")
+	}
+
+	/// Same as [`Inventory::build_sync`], without blocking, for loading inventories off of the sector's thread.
+	pub async fn load(&self) -> Result<SyncInventory, sqlx::Error> {
+		let slots = match self.persistence {
+			Persistence::Database(database) => {
+				let mut connection = database.acquire().await?;
+				Self::slots(self.id, &mut connection).await?
+			}
+			Persistence::Memory(inventories) => inventories
+				.lock()
+				.unwrap()
+				.get(&self.id)
+				.cloned()
+				.unwrap_or_default(),
+		};
+
+		Ok(SyncInventory {
+			slots,
+			capacity: self.capacity,
+		})
+	}
+
+	/// Adds as much of `quantity` `item` as will fit, returning how many were actually added. Anything that doesn't
+	/// fit is up to the caller to deal with.
+	pub fn add(&self, item: Item, quantity: i64) -> Result<i64, sqlx::Error> {
+		let database = match self.persistence {
+			Persistence::Database(database) => database,
+			Persistence::Memory(inventories) => {
+				let mut inventories = inventories.lock().unwrap();
+				let slots = inventories.entry(self.id).or_default();
+
+				let added = SyncInventory {
+					slots: slots.clone(),
+					capacity: self.capacity,
+				}
+				.addable(item, quantity);
+
+				match slots.iter_mut().find(|slot| slot.item == item) {
+					Some(slot) => slot.quantity += added,
+					None if added > 0 => slots.push(InventorySlot {
+						item,
+						quantity: added,
+					}),
+					None => {}
+				}
+
+				return Ok(added);
+			}
+		};
+
+		Handle::current().block_on(async {
+			let mut transaction = database.begin().await?;
+
+			// Lock the inventory first, otherwise two adds at once could both take the last of the space
+			query!(
+				"SELECT id FROM inventories WHERE id = $1 FOR UPDATE",
+				self.id as _
+			)
+			.fetch_one(&mut *transaction)
+			.await?;
+
+			let inventory = SyncInventory {
+				slots: Self::slots(self.id, &mut transaction).await?,
+				capacity: self.capacity,
+			};
+			let added = inventory.addable(item, quantity);
+
+			Self::insert_items(self.id, item, added, &mut transaction).await?;
+
+			transaction.commit().await?;
+
+			Ok(added)
+		})
+	}
+
+	async fn slots(
+		id: Id,
+		connection: &mut sqlx::PgConnection,
+	) -> Result<Vec<InventorySlot>, sqlx::Error> {
+		query_as!(
+			InventorySlot,
+			r#"SELECT item AS "item: Item", COUNT(*) as "quantity!"
+				FROM items JOIN inventory_items ON id = item_id
+				WHERE inventory_id = $1
+				GROUP BY item"#,
+			id as _,
+		)
+		.fetch_all(connection)
+		.await
+	}
+
+	/// Inserts `quantity` new `item`s into inventory `id`, in one statement per table rather than two per item.
+	async fn insert_items(
+		id: Id,
+		item: Item,
+		quantity: i64,
+		transaction: &mut Transaction<'_, Postgres>,
+	) -> Result<(), sqlx::Error> {
+		if quantity <= 0 {
+			return Ok(());
+		}
+
+		let item_ids: Vec<Id> = (0..quantity).map(|_| Id::new()).collect();
+
+		query!(
+			"INSERT INTO items(id, item) SELECT UNNEST($1::Int8[]), $2",
+			&item_ids as _,
+			item as _
+		)
+		.execute(&mut **transaction)
+		.await?;
+
+		query!(
+			"INSERT INTO inventory_items(inventory_id, item_id) SELECT $1, UNNEST($2::Int8[])",
+			id as _,
+			&item_ids as _
+		)
+		.execute(&mut **transaction)
+		.await?;
+
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tokio::runtime::Runtime;
+
+	const CAPACITY: InventoryCapacity = InventoryCapacity {
+		max_stacks: 1,
+		max_quantity: 100,
+	};
+
+	#[test]
+	fn overflow_isnt_added() {
+		let persistence = Persistence::memory();
+		let inventory = Inventory::new(Id::default(), CAPACITY, &persistence);
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+
+		assert_eq!(inventory.add(Item::TestOre, 60).unwrap(), 60);
+		assert_eq!(inventory.add(Item::TestOre, 60).unwrap(), 40);
+		assert_eq!(inventory.add(Item::TestOre, 60).unwrap(), 0);
+
+		let slots = inventory.build_sync().slots;
+		assert_eq!(slots.len(), 1);
+		assert_eq!(slots[0].quantity, 100);
+	}
+
+	#[test]
+	fn nothing_added_leaves_no_empty_stack() {
+		let persistence = Persistence::memory();
+		let inventory = Inventory::new(Id::default(), CAPACITY, &persistence);
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+
+		assert_eq!(inventory.add(Item::TestOre, 0).unwrap(), 0);
+		assert!(inventory.build_sync().slots.is_empty());
+	}
+}
@@ -0,0 +1,376 @@
+use crate::config::{self, LodPreset};
+use nalgebra::{vector, Point3};
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::{
+	consts::CHUNK_SIZE,
+	data::{
+		world::{ChunkCoordinates, Level, VoxjectPosition, LEVELS},
+		Id,
+	},
+};
+use std::collections::HashSet;
+
+/// How far around each player chunks of a voxject are sent to them, as a radius for each level, see
+/// [`LodCurve::client_locks`].
+///
+/// Each level's radius is counted in that level's chunks, which are twice the size of the level below's, so a curve
+/// with the same radius at every level reaches twice as far with each level. Chunks locked at one level are locked at
+/// every level above too, so a radius that reaches less far than the level below's has no effect.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LodCurve {
+	/// Starting from level 0, levels past the end have a radius of 0.
+	radii: Box<[u32]>,
+}
+
+impl LodCurve {
+	/// Most levels a curve can have a radius for, the top level has nothing to uplevel to.
+	pub const MAX_LEVELS: usize = LEVELS as usize - 1;
+
+	/// Largest radius any level may have, in chunks.
+	pub const MAX_RADIUS: u32 = 16;
+
+	/// Most chunks each player may be sent across every voxject, see [`LodCurve::estimate_chunks`].
+	pub const MAX_CHUNKS: usize = 32768;
+
+	pub fn new(lod: &config::Lod) -> Self {
+		let radii = match lod {
+			config::Lod::Preset(LodPreset::Near) => &[1][..],
+			config::Lod::Preset(LodPreset::Far) => &[3, 3, 2, 2, 2, 2, 1, 1][..],
+			config::Lod::Radii(radii) => radii,
+		};
+
+		Self {
+			radii: radii.into(),
+		}
+	}
+
+	/// Radius of `level` in it's own chunks.
+	pub fn radius(&self, level: Level) -> i32 {
+		self.radii
+			.get(*level as usize)
+			.map_or(0, |radius| *radius as i32)
+	}
+
+	/// Anything wrong with the curve, `path` being where it is in the config. Radii may not grow from one level to the
+	/// next, as it's the radius in chunks rather than the distance it covers that decides how many chunks a level
+	/// adds, and level 0 needs a radius for players to be sent what's around them.
+	pub fn validate(&self, path: &str) -> Vec<Box<str>> {
+		let mut problems = vec![];
+
+		if self.radii.first().is_none_or(|radius| *radius == 0) {
+			problems.push(format!("`{path}[0]` must be greater than 0").into());
+		}
+
+		if self.radii.len() > Self::MAX_LEVELS {
+			problems.push(
+				format!(
+					"`{path}` must not have radii for more than {} levels",
+					Self::MAX_LEVELS
+				)
+				.into(),
+			);
+		}
+
+		for (level, radius) in self.radii.iter().enumerate() {
+			if *radius > Self::MAX_RADIUS {
+				problems.push(
+					format!(
+						"`{path}[{level}]` must not be greater than {}",
+						Self::MAX_RADIUS
+					)
+					.into(),
+				);
+			} else if level > 0 && *radius > self.radii[level - 1] {
+				problems.push(
+					format!(
+						"`{path}[{level}]` must not be greater than `{path}[{}]`",
+						level - 1
+					)
+					.into(),
+				);
+			}
+		}
+
+		problems
+	}
+
+	/// Adds the chunks that a player at `position` should be sent to `client_locks`. Away players are only sent the
+	/// chunk they're in at each level, so that what's right around them is still there when they're back, see [`Idle`].
+	///
+	/// [`Idle`]: crate::idle::Idle
+	pub fn client_locks(
+		&self,
+		position: &VoxjectPosition,
+		away: bool,
+		client_locks: &mut HashSet<ChunkCoordinates, FxBuildHasher>,
+	) {
+		// These values are relative to the current level. So a player position of
+		// (0.5 0.5 0.5, Chunk 0 0 0, Level 0) is the same as (0.25 0.25 0.25, Chunk 0, 0, 0, Level 1).
+		let mut player_position = position.position.coords / CHUNK_SIZE as f32;
+		let mut player_chunk = position.chunk(Level::new(0));
+		let mut level_chunks = HashSet::new();
+
+		for level in 0..LEVELS - 1 {
+			let level = Level::new(level);
+			let radius = match away {
+				true => 0,
+				false => self.radius(level),
+			};
+
+			if radius > 0 || (away && *level == 0) {
+				let min = player_chunk.map(|coordinate| coordinate.saturating_sub(radius));
+				let max = player_chunk.map(|coordinate| coordinate.saturating_add(radius));
+
+				for x in min.x..=max.x {
+					for y in min.y..=max.y {
+						for z in min.z..=max.z {
+							let chunk =
+								ChunkCoordinates::new(position.voxject, vector![x, y, z], level);
+
+							// circles look nicer
+							let chunk_center =
+								vector![x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5];
+							if player_chunk != chunk
+								&& player_position.metric_distance(&chunk_center) as i32 > radius
+							{
+								continue;
+							}
+
+							// Level is at most 26 here, so this can't fail
+							level_chunks.extend(chunk.try_upleveled());
+						}
+					}
+				}
+			}
+
+			// Chunks at the very edge of the world have no children, there's nothing we can do about those
+			for chunk in &level_chunks {
+				client_locks.extend(chunk.children().into_iter().flatten());
+			}
+
+			let Some(upleveled_player_chunk) = player_chunk.try_upleveled() else {
+				break;
+			};
+
+			player_position /= 2.0;
+			player_chunk = upleveled_player_chunk;
+
+			level_chunks = level_chunks
+				.into_iter()
+				.filter_map(|chunk| chunk.try_upleveled())
+				.collect();
+		}
+	}
+
+	/// How many chunks of a voxject with this curve each player is sent, so that the cost of a config can be seen
+	/// before anyone joins. Counted for a player at the voxject's origin, where chunks of every level meet, which is
+	/// about as many as a player is ever sent.
+	pub fn estimate_chunks(&self) -> usize {
+		let mut client_locks = HashSet::with_hasher(FxBuildHasher);
+		self.client_locks(
+			&VoxjectPosition::new(Id::new(), Point3::origin()),
+			false,
+			&mut client_locks,
+		);
+		client_locks.len()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn curve(radii: &[u32]) -> LodCurve {
+		LodCurve::new(&config::Lod::Radii(radii.into()))
+	}
+
+	fn preset(preset: LodPreset) -> LodCurve {
+		LodCurve::new(&config::Lod::Preset(preset))
+	}
+
+	/// The locks [`Player::compute_locks`] took before radii were configurable.
+	///
+	/// [`Player::compute_locks`]: crate::player::Player::compute_locks
+	fn old_client_locks(
+		position: &VoxjectPosition,
+		away: bool,
+	) -> HashSet<ChunkCoordinates, FxBuildHasher> {
+		const MULTIPLIER: i32 = 1;
+
+		let mut client_locks = HashSet::with_hasher(FxBuildHasher);
+		let mut player_position = position.position.coords / 16.0;
+		let mut player_chunk = position.chunk(Level::new(0));
+		let mut level_chunks = HashSet::new();
+
+		for level in 0..LEVELS - 1 {
+			let level = Level::new(level);
+			let radius = match away {
+				true => 0,
+				false => ((*level as i32 / LEVELS as i32) * MULTIPLIER + MULTIPLIER) >> *level,
+			};
+
+			if radius > 0 || (away && *level == 0) {
+				let min = player_chunk.map(|coordinate| coordinate.saturating_sub(radius));
+				let max = player_chunk.map(|coordinate| coordinate.saturating_add(radius));
+
+				for x in min.x..=max.x {
+					for y in min.y..=max.y {
+						for z in min.z..=max.z {
+							let chunk =
+								ChunkCoordinates::new(position.voxject, vector![x, y, z], level);
+
+							let chunk_center =
+								vector![x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5];
+							if player_chunk != chunk
+								&& player_position.metric_distance(&chunk_center) as i32 > radius
+							{
+								continue;
+							}
+
+							level_chunks.extend(chunk.try_upleveled());
+						}
+					}
+				}
+			}
+
+			for chunk in &level_chunks {
+				client_locks.extend(chunk.children().into_iter().flatten());
+			}
+
+			let Some(upleveled_player_chunk) = player_chunk.try_upleveled() else {
+				break;
+			};
+
+			player_position /= 2.0;
+			player_chunk = upleveled_player_chunk;
+
+			level_chunks = level_chunks
+				.into_iter()
+				.filter_map(|chunk| chunk.try_upleveled())
+				.collect();
+		}
+
+		client_locks
+	}
+
+	#[test]
+	fn the_near_preset_is_the_old_formula() {
+		const MULTIPLIER: i32 = 1;
+
+		let near = preset(LodPreset::Near);
+
+		for level in 0..LEVELS {
+			assert_eq!(
+				near.radius(Level::new(level)),
+				((level as i32 / LEVELS as i32) * MULTIPLIER + MULTIPLIER) >> level,
+				"level {level}"
+			);
+		}
+
+		let voxject = Id::new();
+
+		// A fixed spread of positions, on and around chunk boundaries at several levels, and far from the origin
+		let positions = (0..200u32)
+			.map(|index| {
+				let scatter = |seed: u32| {
+					let hash = seed.wrapping_mul(0x9e3779b9) ^ (seed >> 7);
+					(hash % 20_000) as f32 / 10.0 - 1000.0
+				};
+
+				let index = index * 3;
+				Point3::new(scatter(index), scatter(index + 1), scatter(index + 2))
+			})
+			.chain([
+				Point3::origin(),
+				Point3::new(16.0, 16.0, 16.0),
+				Point3::new(-0.001, 31.999, 256.0),
+				Point3::new(1_000_000.0, -1_000_000.0, 12_345.0),
+			]);
+
+		for position in positions {
+			let position = VoxjectPosition::new(voxject, position);
+
+			for away in [false, true] {
+				let mut client_locks = HashSet::with_hasher(FxBuildHasher);
+				near.client_locks(&position, away, &mut client_locks);
+
+				assert_eq!(
+					client_locks,
+					old_client_locks(&position, away),
+					"{:?}, away: {away}",
+					position.position
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn levels_past_the_curve_have_no_radius() {
+		let curve = curve(&[3, 2]);
+
+		assert_eq!(curve.radius(Level::new(0)), 3);
+		assert_eq!(curve.radius(Level::new(1)), 2);
+		assert_eq!(curve.radius(Level::new(2)), 0);
+		assert_eq!(curve.radius(Level::new(LEVELS - 1)), 0);
+	}
+
+	#[test]
+	fn curves_are_validated() {
+		assert_eq!(
+			preset(LodPreset::Near).validate("lod"),
+			Vec::<Box<str>>::new()
+		);
+		assert_eq!(
+			preset(LodPreset::Far).validate("lod"),
+			Vec::<Box<str>>::new()
+		);
+		assert_eq!(curve(&[2, 2, 1]).validate("lod"), Vec::<Box<str>>::new());
+
+		let problems = |radii: &[u32]| curve(radii).validate("lod");
+
+		assert_eq!(problems(&[]), ["`lod[0]` must be greater than 0".into()]);
+		assert_eq!(problems(&[0]), ["`lod[0]` must be greater than 0".into()]);
+		assert_eq!(
+			problems(&[1, 2]),
+			["`lod[1]` must not be greater than `lod[0]`".into()]
+		);
+		assert_eq!(
+			problems(&[LodCurve::MAX_RADIUS + 1]),
+			[format!("`lod[0]` must not be greater than {}", LodCurve::MAX_RADIUS).into()]
+		);
+		assert_eq!(
+			problems(&[1; LodCurve::MAX_LEVELS + 1]),
+			[format!(
+				"`lod` must not have radii for more than {} levels",
+				LodCurve::MAX_LEVELS
+			)
+			.into()]
+		);
+		assert_eq!(
+			curve(&[0]).validate("voxjects[2].lod"),
+			["`voxjects[2].lod[0]` must be greater than 0".into()]
+		);
+	}
+
+	#[test]
+	fn estimates_count_the_chunks_sent() {
+		let near = preset(LodPreset::Near).estimate_chunks();
+		let far = preset(LodPreset::Far).estimate_chunks();
+
+		let mut client_locks = HashSet::with_hasher(FxBuildHasher);
+		preset(LodPreset::Near).client_locks(
+			&VoxjectPosition::new(Id::new(), Point3::origin()),
+			false,
+			&mut client_locks,
+		);
+		assert_eq!(near, client_locks.len());
+
+		// Bigger radii, and radii for more levels, only ever cost more
+		assert!(near < curve(&[2]).estimate_chunks());
+		assert!(curve(&[2]).estimate_chunks() < curve(&[2, 2]).estimate_chunks());
+		assert!(near < far);
+
+		assert!(far <= LodCurve::MAX_CHUNKS);
+		assert!(curve(&[LodCurve::MAX_RADIUS; 4]).estimate_chunks() > LodCurve::MAX_CHUNKS);
+	}
+}
@@ -1,81 +1,239 @@
-use crate::sector::config;
-use chacha20poly1305::{aead::Aead, ChaCha20Poly1305, KeyInit};
-use clap::Parser;
-use env_logger::Env;
-use futures::StreamExt;
-use log::{error, info, warn};
+use admission::{KeyListener, ListenerEvent, PendingConnections};
+use clap::{Parser, Subcommand};
+use log::{debug, error, info, warn};
+use persistence::Persistence;
+use preview::PreviewArgs;
 use rayon::spawn_broadcast;
+use replay::ReplayArgs;
 use sector::{Event, Sector};
 use solarscape_shared::{
-	connection::{Connection, ServerEnd},
-	message::backend::AllowConnection,
-};
-use sqlx::{
-	postgres::{PgConnectOptions, PgListener},
-	PgPool,
+	clock,
+	connection::{parse_preshared_key, Connection, KeyParseError, ServerEnd},
+	data::Id,
+	logging::{self, LogFormat},
+	message::backend::{GatewayStats, SectorStatus},
 };
+use sqlx::{postgres::PgConnectOptions, PgPool};
 use std::{
-	collections::HashMap, fs::read_to_string, io, net::SocketAddr, path::PathBuf, time::Instant,
+	fs, io,
+	net::SocketAddr,
+	path::PathBuf,
+	process::{self, ExitCode},
+	sync::atomic::Ordering::Relaxed,
+	time::Instant,
 };
 use thiserror::Error;
 use thread_priority::ThreadPriority;
-use tokio::{io::AsyncReadExt, net::TcpListener, runtime::Runtime, select};
+use tokio::{
+	io::AsyncReadExt,
+	net::TcpListener,
+	runtime::Runtime,
+	select,
+	signal::ctrl_c,
+	time::{interval_at, MissedTickBehavior},
+};
 
+mod admission;
+mod ambient;
+mod audit;
+mod chat;
+mod check;
+mod command;
+mod config;
+mod explosion;
+mod gateway_stats;
 mod generation;
+mod generation_cache;
+mod generation_queue;
+mod idle;
+mod interaction;
+mod interest;
+mod inventory;
+mod lod;
+mod memory;
+mod persistence;
 mod player;
+mod preview;
+mod recording;
+mod replay;
+mod save;
+mod scheduler;
 mod sector;
+mod sector_status;
+mod snapshot;
+mod structure_index;
+mod structure_limits;
+mod structure_locks;
+mod sync_queue;
+mod telemetry;
+mod terrain;
+mod terrain_journal;
+mod transfer;
 
 #[derive(Parser)]
-#[command(version)]
+#[command(version, subcommand_negates_reqs = true)]
 struct ClArgs {
+	#[command(subcommand)]
+	command: Option<Command>,
+
 	/// Postgres Connection Url, see: https://docs.rs/sqlx/latest/sqlx/postgres/struct.PgConnectOptions.html
-	#[arg(long)]
-	postgres: PgConnectOptions,
+	#[arg(long, required = true)]
+	postgres: Option<PgConnectOptions>,
 
 	/// Socket address to accept connections on
-	#[arg(long)]
-	address: SocketAddr,
+	#[arg(long, required = true)]
+	address: Option<SocketAddr>,
 
 	/// Path to sector config file
+	#[arg(long, env = config::CONFIG_ENV)]
+	config: Option<PathBuf>,
+
+	/// File containing a key, as 64 hex digits, that lets players connect without going through the gateway. Anyone with
+	/// the key connects as --preshared-key-player, so only use this on a LAN or for development
+	#[arg(long, requires = "preshared_key_player")]
+	preshared_key_file: Option<PathBuf>,
+
+	/// Player that connections using --preshared-key-file connect as
+	#[arg(long, requires = "preshared_key_file")]
+	preshared_key_player: Option<Id>,
+
+	/// Check the config, database, and address, then exit without starting the sector
 	#[arg(long)]
-	config: PathBuf,
+	check: bool,
+
+	/// Snapshot written by /snapshot to restore before accepting connections, for moving a running sector to a new
+	/// process. Chunks the database has saved since the snapshot was taken are left as they are
+	#[arg(long)]
+	restore: Option<PathBuf>,
+
+	/// Ignore the generation cache for this run, generating every chunk again and replacing what was cached
+	#[arg(long)]
+	regenerate: bool,
+
+	/// Format to write logs in
+	#[arg(long, value_enum, default_value_t, global = true)]
+	log_format: LogFormat,
+}
+
+#[derive(Subcommand)]
+enum Command {
+	/// Generate chunks and write them to a Wavefront OBJ file, without starting a sector
+	Preview(PreviewArgs),
+
+	/// Replay a recording of a player's messages against a fresh sector, and check it matches what was recorded
+	Replay(ReplayArgs),
 }
 
-fn main() -> Result<(), SectorServerError> {
+fn main() -> Result<ExitCode, SectorServerError> {
 	let start_time = Instant::now();
 
 	let mut cl_args = ClArgs::parse();
 
-	env_logger::init_from_env(Env::default().default_filter_or(if cfg!(debug_assertions) {
-		"debug"
-	} else {
-		"info"
-	}));
+	logging::init(
+		cl_args.log_format,
+		if cfg!(debug_assertions) {
+			"debug"
+		} else {
+			"info"
+		},
+	);
 
 	info!("Solarscape (Server) v{}", env!("CARGO_PKG_VERSION"));
 
+	match cl_args.command.take() {
+		Some(Command::Preview(preview_args)) => {
+			preview::run(preview_args)?;
+			return Ok(ExitCode::SUCCESS);
+		}
+		Some(Command::Replay(replay_args)) => {
+			let config = match config::load_config(cl_args.config.take()) {
+				Ok(config) => config,
+				Err(error) => {
+					error!("{error}");
+					return Ok(error.exit_code());
+				}
+			};
+
+			return Ok(match replay::run(replay_args, config) {
+				Ok(true) => ExitCode::SUCCESS,
+				Ok(false) => ExitCode::FAILURE,
+				Err(error) => {
+					error!("Unable to replay: {error}");
+					ExitCode::FAILURE
+				}
+			});
+		}
+		None => {}
+	}
+
+	let (Some(postgres), Some(address)) = (cl_args.postgres.take(), cl_args.address) else {
+		unreachable!("clap should require --postgres and --address without a subcommand");
+	};
+
+	if cl_args.check {
+		return Ok(check::run(cl_args.config.take(), postgres, address));
+	}
+
+	// Load the config before anything else, there's no point connecting to the database if we're going to bail anyway
+	let mut config = match config::load_config(cl_args.config.take()) {
+		Ok(config) => config,
+		Err(error) => {
+			error!("{error}");
+			return Ok(error.exit_code());
+		}
+	};
+
+	config.generation_cache.regenerate = cl_args.regenerate;
+
 	let runtime = Runtime::new()?;
 	let a = runtime.enter();
 
-	cl_args.postgres = cl_args.postgres.application_name("solarscape-sector");
-	let database = runtime.block_on(PgPool::connect_with(cl_args.postgres))?;
+	let database = runtime.block_on(connect_database(postgres))?;
 
-	let sector = {
-		let config: config::Sector = {
-			let string = read_to_string(cl_args.config)?;
-			hocon::de::from_str(&string)?
-		};
+	let keep_alive = config.connection.keep_alive();
 
-		Sector::new(database.clone(), config)
-	};
+	let mut sector = Sector::new(
+		Persistence::Database(database.clone()),
+		clock::system(),
+		config,
+	)?;
+
+	if let Some(path) = cl_args.restore.take() {
+		let report = snapshot::read(&path).and_then(|snapshot| sector.restore(snapshot));
+
+		match report {
+			Ok(report) => info!("Restored {report} from {}", path.display()),
+			Err(error) => {
+				error!("Unable to restore {}: {error}", path.display());
+				return Ok(ExitCode::FAILURE);
+			}
+		}
+	}
 
 	let shared_sector = sector.shared.clone();
 
-	let mut allow_connection_listener = runtime.block_on(PgListener::connect_with(&database))?;
-	runtime.block_on(allow_connection_listener.listen(&sector.name))?;
-	let mut allow_connection_stream = allow_connection_listener.into_stream();
+	let mut key_listener =
+		runtime.block_on(KeyListener::connect(database.clone(), sector.name.clone()))?;
+
+	// Now that we're listening, catch up on anything that was sent while we weren't
+	let mut pending_connections = PendingConnections::new(sector.name.clone());
+	runtime.block_on(pending_connections.poll(&database))?;
+
+	if let (Some(path), Some(player)) = (
+		cl_args.preshared_key_file.take(),
+		cl_args.preshared_key_player,
+	) {
+		let key = parse_preshared_key(&fs::read_to_string(&path)?)?;
+		pending_connections.set_preshared_key(key, player);
 
-	let connection_listener = runtime.block_on(TcpListener::bind(cl_args.address))?;
+		warn!(
+			"Anyone with the pre-shared key from {} can connect as player {player} without going through the gateway, \
+			this is insecure and only meant for LAN play and development",
+			path.display()
+		);
+	}
+
+	let connection_listener = runtime.block_on(TcpListener::bind(address))?;
 
 	info!("Setting Rayon Thread Priority");
 	spawn_broadcast(|_| {
@@ -87,36 +245,79 @@ fn main() -> Result<(), SectorServerError> {
 	info!("Ready! {:.0?}", Instant::now() - start_time);
 
 	runtime.spawn(async move {
-		let mut key_id_map = HashMap::new();
+		let mut poll_interval = interval_at(
+			tokio::time::Instant::now() + PendingConnections::POLL_INTERVAL,
+			PendingConnections::POLL_INTERVAL,
+		);
+		poll_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+		let mut last_counters = pending_connections.counters;
+
+		let mut gateway_stats_interval = interval_at(tokio::time::Instant::now(), GatewayStats::INTERVAL);
+		gateway_stats_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+		let mut sector_status_interval = interval_at(tokio::time::Instant::now(), SectorStatus::INTERVAL);
+		sector_status_interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
 
 		loop {
 			select! {
-				allow_connection = allow_connection_stream.next() => {
-					let AllowConnection { id, key } = match allow_connection {
-						None => {
-							error!("allow connection stream closed?");
-							return;
-						}
-						Some(allow_connection) => match allow_connection {
-							Err(error) => {
-								error!("error while reading allow_connection_notification: {error}");
-								return;
-							}
-							Ok(allow_connection) => match serde_json::from_str(allow_connection.payload()) {
-								Err(error) => {
-									error!("error while deserializing allow connection notification: {error}");
-									continue
-								}
-								Ok(allow_connection) => allow_connection,
-							}
+				event = key_listener.recv() => match event {
+					ListenerEvent::Notified(allow_connection) => pending_connections.notified(allow_connection),
+					ListenerEvent::Reconnected => {
+						// Anything sent while we were disconnected was lost
+						if let Err(error) = pending_connections.poll(&database).await {
+							warn!("Unable to poll pending connections: {error}");
 						}
+					}
+				},
+
+				_ = poll_interval.tick() => {
+					if let Err(error) = pending_connections.poll(&database).await {
+						warn!("Unable to poll pending connections: {error}");
+					}
+
+					match transfer::expire(&database).await {
+						Ok(0) => {}
+						Ok(expired) => info!("Purged {expired} expired player transfers"),
+						Err(error) => warn!("Unable to purge expired player transfers: {error}"),
+					}
+
+					let counters = pending_connections.counters;
+					if counters != last_counters {
+						debug!(
+							notifications = counters.notifications,
+							polled = counters.polled,
+							expired = counters.expired,
+							handshakes = counters.handshakes,
+							listener:% = key_listener.state();
+							"Connection keys: {} notified, {} polled, {} expired, {} handshakes, listener {}",
+							counters.notifications, counters.polled, counters.expired, counters.handshakes,
+							key_listener.state()
+						);
+						last_counters = counters;
+					}
+				},
+
+				_ = gateway_stats_interval.tick() => match gateway_stats::fetch(&database).await {
+					Ok(reports) => {
+						let _ = shared_sector.send(Event::GatewayStats(reports));
+					}
+					Err(error) => warn!("Unable to read gateway stats: {error}"),
+				},
+
+				_ = sector_status_interval.tick() => {
+					let status = SectorStatus {
+						players: shared_sector.player_count.load(Relaxed),
+						capacity: shared_sector.max_players,
 					};
 
-					key_id_map.insert(key, id);
+					if let Err(error) = sector_status::report(&database, &shared_sector.name, status).await {
+						warn!("Unable to report sector status: {error}");
+					}
 				},
 
 				connection = connection_listener.accept() => {
-					let (mut stream, _) = match connection {
+					let (mut stream, address) = match connection {
 						Err(error) => {
 							error!("unable to accept further connections due to error: {error}");
 							return;
@@ -135,35 +336,80 @@ fn main() -> Result<(), SectorServerError> {
 						_ => continue,
 					}
 
-					let mut iterator = key_id_map.iter();
-					while let Some((key, id)) = iterator.next() {
-						let cipher = ChaCha20Poly1305::new(key.into());
-						let version_data = match cipher.decrypt((&[0; 12]).into(), &*buffer) {
-							Err(_) => continue,
-							Ok(version_data) => version_data,
-						};
-						let (key, id) = (*key, *id);
-						if version_data.len() == 4 && version_data == [0, 0, 0, 0] {
-							let connection = Connection::<ServerEnd>::new(stream, cipher);
-							key_id_map.remove(&key);
-							shared_sector.send(Event::PlayerConnected(id, connection));
-							break;
+					let (id, key_id, cipher) = match pending_connections.accept(&buffer) {
+						Ok(accepted) => accepted,
+						Err(error) => {
+							warn!("Handshake from {address} {error}");
+							continue;
+						}
+					};
+
+					match key_id {
+						Some(key_id) => {
+							info!(key_id:% = key_id, player_id:% = id; "Player {id} completed handshake with key {key_id}");
+
+							// Deleted before the next poll can run, otherwise the poll would pick the key up again
+							if let Err(error) = admission::consume(&database, key_id).await {
+								warn!(key_id:% = key_id; "Unable to delete used key {key_id}: {error}");
+							}
+						}
+						None => info!(player_id:% = id; "Player {id} completed handshake with the pre-shared key"),
+					}
+
+					// A player turned away for the sector being full loses their transfer, and spawns as normal when they
+					// next get in, gateways don't send players to full sectors so this should be rare
+					let arrival = match transfer::take(&database, id, &shared_sector.name).await {
+						Ok(arrival) => arrival,
+						Err(error) => {
+							warn!(player_id:% = id; "Unable to look up a transfer for player {id}: {error}");
+							None
 						}
+					};
+
+					if arrival.is_some() {
+						info!(player_id:% = id; "Player {id} arrived from another sector");
 					}
+
+					let connection = Connection::<ServerEnd>::with_clock(
+						stream,
+						cipher,
+						keep_alive,
+						shared_sector.clock.clone(),
+					);
+					let _ = shared_sector.send(Event::PlayerConnected(id, connection, arrival));
 				}
 			}
 		}
 	});
 
+	let shared_sector = sector.shared.clone();
+	runtime.spawn(async move {
+		if ctrl_c().await.is_err() {
+			return;
+		}
+
+		let _ = shared_sector.send(Event::Shutdown);
+
+		// Saving could take a while, or get stuck, so give the impatient a way out
+		if ctrl_c().await.is_ok() {
+			warn!("Exiting without waiting for the save to finish");
+			process::exit(1);
+		}
+	});
+
 	sector.run();
 
-	Ok(())
+	Ok(ExitCode::SUCCESS)
+}
+
+async fn connect_database(postgres: PgConnectOptions) -> Result<PgPool, sqlx::Error> {
+	PgPool::connect_with(postgres.application_name("solarscape-sector")).await
 }
 
 #[derive(Debug, Error)]
 #[error(transparent)]
 pub enum SectorServerError {
-	Hocon(#[from] hocon::Error),
 	Io(#[from] io::Error),
 	Sqlx(#[from] sqlx::Error),
+	PresharedKey(#[from] KeyParseError),
 }
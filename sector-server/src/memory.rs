@@ -0,0 +1,286 @@
+use crate::{config, sector::SharedSector};
+use log::info;
+use std::{
+	fmt::{self, Display, Formatter},
+	sync::atomic::{AtomicUsize, Ordering::Relaxed},
+};
+
+/// Bytes used by everything in the sector, see [`Category`].
+pub static MEMORY: MemoryStats = MemoryStats::new();
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Category {
+	/// [`crate::sector::Data`], which is the same size for every chunk.
+	ChunkData,
+	/// [`crate::sector::Collision`] vertices and indices.
+	CollisionMeshes,
+	/// Structure blocks.
+	Structures,
+}
+
+impl Category {
+	pub const ALL: [Self; 3] = [Self::ChunkData, Self::CollisionMeshes, Self::Structures];
+
+	pub fn name(self) -> &'static str {
+		match self {
+			Self::ChunkData => "chunk data",
+			Self::CollisionMeshes => "collision meshes",
+			Self::Structures => "structures",
+		}
+	}
+}
+
+/// Running totals of allocated bytes, only allocations that are large or numerous enough to matter are counted.
+/// Counters saturate rather than wrapping, so an untrack without a matching track leaves a total that's too low rather
+/// than one that's wrapped around to something huge.
+pub struct MemoryStats {
+	bytes: [AtomicUsize; Category::ALL.len()],
+}
+
+impl MemoryStats {
+	const fn new() -> Self {
+		Self {
+			bytes: [const { AtomicUsize::new(0) }; Category::ALL.len()],
+		}
+	}
+
+	pub fn track(&self, category: Category, bytes: usize) {
+		let _ = self.bytes[category as usize]
+			.fetch_update(Relaxed, Relaxed, |total| Some(total.saturating_add(bytes)));
+	}
+
+	pub fn untrack(&self, category: Category, bytes: usize) {
+		let _ = self.bytes[category as usize]
+			.fetch_update(Relaxed, Relaxed, |total| Some(total.saturating_sub(bytes)));
+	}
+
+	pub fn get(&self, category: Category) -> usize {
+		self.bytes[category as usize].load(Relaxed)
+	}
+}
+
+/// Counts towards a [`Category`] for as long as it's alive, for allocations owned by a single value.
+pub struct Tracked {
+	stats: &'static MemoryStats,
+	category: Category,
+	bytes: usize,
+}
+
+impl Tracked {
+	pub fn new(category: Category, bytes: usize) -> Self {
+		Self::new_in(&MEMORY, category, bytes)
+	}
+
+	/// Counts towards `stats` rather than [`MEMORY`].
+	fn new_in(stats: &'static MemoryStats, category: Category, bytes: usize) -> Self {
+		stats.track(category, bytes);
+
+		Self {
+			stats,
+			category,
+			bytes,
+		}
+	}
+
+	/// Changes how many bytes are counted, for allocations that grow after they're created.
+	pub fn set(&mut self, bytes: usize) {
+		self.stats.untrack(self.category, self.bytes);
+		self.stats.track(self.category, bytes);
+		self.bytes = bytes;
+	}
+}
+
+impl Drop for Tracked {
+	fn drop(&mut self) {
+		self.stats.untrack(self.category, self.bytes);
+	}
+}
+
+/// Logs memory usage when a category goes over or back under it's threshold from the sector config.
+pub struct MemoryMonitor {
+	thresholds: [usize; Category::ALL.len()],
+	over: [bool; Category::ALL.len()],
+
+	/// Seconds since the totals were last checked.
+	since_check: f32,
+
+	/// Whether the counters didn't match the last recount, a second mismatch in a row is reported as a leak.
+	#[cfg(debug)]
+	mismatched: bool,
+}
+
+impl MemoryMonitor {
+	/// Seconds between checks.
+	const CHECK_INTERVAL: f32 = 10.0;
+
+	pub fn new(
+		config::MemoryThresholds {
+			chunk_data,
+			collision_meshes,
+			structures,
+		}: config::MemoryThresholds,
+	) -> Self {
+		Self {
+			thresholds: [chunk_data, collision_meshes, structures].map(|mib| mib * 1024 * 1024),
+			over: [false; Category::ALL.len()],
+
+			since_check: 0.0,
+
+			#[cfg(debug)]
+			mismatched: false,
+		}
+	}
+
+	pub fn tick(&mut self, delta: f32, sector: &SharedSector) {
+		self.since_check += delta;
+
+		if self.since_check < Self::CHECK_INTERVAL {
+			return;
+		}
+
+		self.since_check = 0.0;
+
+		for category in Category::ALL {
+			let threshold = self.thresholds[category as usize];
+
+			// 0 disables the threshold
+			if threshold == 0 {
+				continue;
+			}
+
+			let bytes = MEMORY.get(category);
+			let over = bytes >= threshold;
+
+			if over == self.over[category as usize] {
+				continue;
+			}
+
+			self.over[category as usize] = over;
+
+			info!(
+				category = category.name(),
+				bytes = bytes,
+				threshold = threshold;
+				"Memory used by {} is {} the {} threshold, now {}",
+				category.name(),
+				match over {
+					true => "over",
+					false => "back under",
+				},
+				Bytes(threshold),
+				Bytes(bytes)
+			);
+		}
+
+		#[cfg(debug)]
+		self.recount(sector);
+
+		#[cfg(not(debug))]
+		let _ = sector;
+	}
+
+	/// Recounts chunk data and collision meshes from every loaded chunk, to catch allocations that were never
+	/// untracked. Chunks being generated or dropped during the recount can cause a one off mismatch, so only a mismatch
+	/// twice in a row is reported.
+	#[cfg(debug)]
+	fn recount(&mut self, sector: &SharedSector) {
+		use log::error;
+
+		let Some((data, collision)) = sector.recount_memory() else {
+			// A chunk was locked, try again next time
+			return;
+		};
+
+		let counted = [
+			MEMORY.get(Category::ChunkData),
+			MEMORY.get(Category::CollisionMeshes),
+		];
+
+		let mismatched = counted != [data, collision];
+
+		if mismatched && self.mismatched {
+			error!(
+				"Memory counters don't match a recount, something is leaking: chunk data counted {} but found {}, \
+				collision meshes counted {} but found {}",
+				Bytes(counted[0]),
+				Bytes(data),
+				Bytes(counted[1]),
+				Bytes(collision)
+			);
+		}
+
+		self.mismatched = mismatched;
+	}
+}
+
+/// Formats a number of bytes in the largest unit that keeps it above 1.
+pub struct Bytes(pub usize);
+
+impl Display for Bytes {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+		let mut value = self.0 as f64;
+		let mut unit = 0;
+
+		while value >= 1024.0 && unit < UNITS.len() - 1 {
+			value /= 1024.0;
+			unit += 1;
+		}
+
+		match unit {
+			0 => write!(formatter, "{} {}", self.0, UNITS[0]),
+			_ => write!(formatter, "{value:.1} {}", UNITS[unit]),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn counters_saturate() {
+		let stats = MemoryStats::new();
+
+		stats.track(Category::Structures, 100);
+		stats.untrack(Category::Structures, 150);
+		assert_eq!(stats.get(Category::Structures), 0);
+
+		stats.track(Category::Structures, usize::MAX - 1);
+		stats.track(Category::Structures, 10);
+		assert_eq!(stats.get(Category::Structures), usize::MAX);
+
+		// Categories are counted separately
+		assert_eq!(stats.get(Category::ChunkData), 0);
+		assert_eq!(stats.get(Category::CollisionMeshes), 0);
+	}
+
+	#[test]
+	fn tracked_allocations_count_while_alive() {
+		static STATS: MemoryStats = MemoryStats::new();
+
+		let mut first = Tracked::new_in(&STATS, Category::CollisionMeshes, 100);
+		let second = Tracked::new_in(&STATS, Category::CollisionMeshes, 20);
+		assert_eq!(STATS.get(Category::CollisionMeshes), 120);
+
+		first.set(300);
+		assert_eq!(STATS.get(Category::CollisionMeshes), 320);
+
+		drop(second);
+		assert_eq!(STATS.get(Category::CollisionMeshes), 300);
+
+		drop(first);
+		assert_eq!(STATS.get(Category::CollisionMeshes), 0);
+	}
+
+	#[test]
+	fn bytes_are_formatted_in_the_largest_unit() {
+		assert_eq!(Bytes(0).to_string(), "0 B");
+		assert_eq!(Bytes(1023).to_string(), "1023 B");
+		assert_eq!(Bytes(1024).to_string(), "1.0 KiB");
+		assert_eq!(Bytes(1536 * 1024).to_string(), "1.5 MiB");
+		assert_eq!(Bytes(3 << 30).to_string(), "3.0 GiB");
+		assert_eq!(Bytes(2048 << 30).to_string(), "2048.0 GiB");
+	}
+}
@@ -0,0 +1,26 @@
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::{data::Id, message::clientbound::InventorySlot};
+use sqlx::PgPool;
+use std::{collections::HashMap, sync::Mutex};
+
+/// Where a sector keeps anything that outlives it.
+pub enum Persistence {
+	Database(PgPool),
+	/// Nothing is saved, and inventories only last as long as the sector. Used by `replay`, so that recordings can be
+	/// replayed without a database.
+	Memory(Mutex<HashMap<Id, Vec<InventorySlot>, FxBuildHasher>>),
+}
+
+impl Persistence {
+	pub fn memory() -> Self {
+		Self::Memory(Mutex::new(HashMap::with_hasher(FxBuildHasher)))
+	}
+
+	/// The database, [`None`] if nothing is being saved.
+	pub fn database(&self) -> Option<&PgPool> {
+		match self {
+			Self::Database(database) => Some(database),
+			Self::Memory(_) => None,
+		}
+	}
+}
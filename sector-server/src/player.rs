@@ -1,36 +1,98 @@
-use crate::sector::{ClientLock, Sector, SharedSector, TickLock};
-use nalgebra::{convert_unchecked, vector, IsometryMatrix3, Vector3};
+use crate::{
+	idle::Idle,
+	interest::Interest,
+	recording::Recorder,
+	sector::{ClientLock, Sector, SharedSector, TickLock},
+	structure_limits::TokenBucket,
+	sync_queue::{SyncQueue, View},
+	telemetry::TelemetrySummary,
+};
+use log::warn;
 use rustc_hash::FxBuildHasher;
 use solarscape_shared::{
 	connection::{Connection, ServerEnd},
 	data::{
-		world::{ChunkCoordinates, Item, Level, Location, LEVELS},
+		world::{ChunkCoordinates, Level, Location, WorldPosition},
 		Id,
 	},
-	message::clientbound::{InventorySlot, Sync, Voxject},
+	message::{
+		clientbound::{Sync, Voxject},
+		serverbound::ViewHint,
+	},
 };
-use sqlx::{query_as, PgPool};
 use std::{
-	collections::HashSet,
+	collections::{HashMap, HashSet},
 	ops::{Deref, DerefMut},
 	sync::Arc,
+	time::Instant,
 };
-use tokio::runtime::Handle;
 
 pub struct Player {
 	pub id: Id,
 	pub connection: Connection<ServerEnd>,
 
 	pub location: Location,
+	/// The last valid [`ViewHint`] the player sent, [`None`] if they haven't sent one.
+	pub view_hint: Option<ViewHint>,
 
 	pub client_locks: Vec<ClientLock>,
 	pub tick_locks: Vec<TickLock>,
+
+	/// Chunks waiting to be client locked, see [`SyncQueue`].
+	pub sync_queue: SyncQueue,
+	/// Content hashes of the chunks the client said it has cached when joining, each is used for the first client lock
+	/// of that chunk and then forgotten.
+	pub cached_chunks: HashMap<ChunkCoordinates, u64, FxBuildHasher>,
+
+	pub telemetry: TelemetrySummary,
+
+	/// Limits how quickly structures can be placed and terrain modified, [`None`] if there's no limit.
+	pub placements: Option<TokenBucket>,
+
+	/// Records the messages the player sends, [`None`] if they aren't being recorded.
+	pub recorder: Option<Recorder>,
+
+	/// Structures the player has been sent, see [`Sector::interest_radius`].
+	pub interest: Interest,
+	/// Structures within the player's interest radius still waiting to be sent, see [`Interest::budget`].
+	pub structure_backlog: usize,
+
+	/// Whether the player has been sent their inventory, so that the load started when they joined can't replace a
+	/// newer one, see [`Event::InventoryLoaded`].
+	///
+	/// [`Event::InventoryLoaded`]: crate::sector::Event::InventoryLoaded
+	pub inventory_synced: bool,
+	/// Whether the player has sent their location yet, nothing around them can be sent until they have.
+	pub located: bool,
+	/// Set until the player has been sent [`JoinComplete`], see [`Sector::complete_joins`].
+	///
+	/// [`JoinComplete`]: solarscape_shared::message::clientbound::JoinComplete
+	/// [`Sector::complete_joins`]: crate::sector::Sector::complete_joins
+	pub joining: bool,
+
+	pub idle: Idle,
 }
 
 impl Player {
 	pub fn accept(sector: &Sector, id: Id, connection: Connection<ServerEnd>) -> Self {
+		let recorder = match sector.recording.players.contains(&id) {
+			true => match sector.start_recorder(id) {
+				Ok(recorder) => Some(recorder),
+				Err(error) => {
+					warn!(player_id:% = id; "Unable to start recording player {id}: {error}");
+					None
+				}
+			},
+			false => None,
+		};
+
+		// Only what the sector is, everything in it is sent gradually afterwards so that a busy sector doesn't stall
 		connection.send(Sync {
 			name: sector.name.clone(),
+			tick_rate: sector.tick_rate,
+			world_radius: sector.world_radius,
+			world_radius_chunks: sector.world_radius_chunks,
+			max_level: sector.max_level,
 
 			voxjects: sector
 				.voxjects
@@ -40,38 +102,71 @@ impl Player {
 					name: voxject.name.clone(),
 				})
 				.collect(),
-			structures: sector
-				.structures
-				.iter()
-				.map(|structure| structure.build_sync(&sector.physics))
-				.collect(),
 
-			inventory: Self::get_inventory(id, &sector.database),
+			ambient_keyframes: sector.ambient.keyframes.clone(),
+			ambient: sector.ambient.build_sync(),
 		});
 
+		// Sent while handling the sector's events, so no message can be missed or sent twice between this and the first
+		// live message
+		connection.send(sector.chat.build_sync());
+
 		Self {
 			id,
 			connection,
 			location: Location::default(),
+			view_hint: None,
 			client_locks: vec![],
 			tick_locks: vec![],
+			sync_queue: SyncQueue::new(),
+			cached_chunks: HashMap::with_hasher(FxBuildHasher),
+			telemetry: TelemetrySummary::default(),
+
+			placements: match sector.structure_limits.placements_per_second {
+				0.0 => None,
+				rate => Some(TokenBucket::new(
+					rate,
+					sector.structure_limits.placement_burst,
+					sector.clock.now_instant(),
+				)),
+			},
+
+			recorder,
+
+			interest: Interest::default(),
+			structure_backlog: 0,
+
+			inventory_synced: false,
+			located: false,
+			joining: true,
+
+			idle: Idle::new(sector.clock.now_instant()),
 		}
 	}
 
-	pub fn get_inventory(id: Id, database: &PgPool) -> Vec<InventorySlot> {
-		Handle::current()
-			.block_on(
-				query_as!(
-					InventorySlot,
-					r#"SELECT item AS "item: Item", COUNT(*) as "quantity!"
-						FROM items JOIN inventory_items ON id = item_id
-						WHERE inventory_id = $1
-						GROUP BY item"#,
-					id as _,
-				)
-				.fetch_all(database),
-			)
-			.expect("inventory")
+	/// Whether the player may place a structure or modify terrain, taking one of their [`Player::placements`] if so.
+	pub fn try_place(&mut self, now: Instant) -> bool {
+		self.placements
+			.as_mut()
+			.is_none_or(|placements| placements.try_take(now))
+	}
+
+	/// Locks the chunks around the player's current location, releasing those that are no longer needed. Called
+	/// whenever they move, send a [`ViewHint`], or come back from being away.
+	pub fn update_locks(&mut self, sector: &Arc<SharedSector>) {
+		let (mut new_client_locks, new_tick_locks) = self.compute_locks(sector);
+
+		self.client_locks
+			// Retain will remove any chunks that aren't in the new list, remove will remove any chunks from the new list
+			// that were in the old list
+			.retain(|lock| new_client_locks.remove(&lock.coordinates()));
+
+		// New client locks are created gradually from the sync queue, rather than all at once
+		let view = View::new(&self.location, self.view_hint.as_ref());
+		self.sync_queue
+			.update(new_client_locks, &self.location, view, sector);
+
+		TickLock::update(&mut self.tick_locks, new_tick_locks, sector);
 	}
 
 	pub fn compute_locks(
@@ -81,85 +176,28 @@ impl Player {
 		HashSet<ChunkCoordinates, FxBuildHasher>,
 		HashSet<ChunkCoordinates, FxBuildHasher>,
 	) {
-		const MULTIPLIER: i32 = 1;
+		// Away players only keep the chunk they're in at each level, see LodCurve::client_locks
+		let away = self.idle.is_away();
 
 		let mut client_locks = HashSet::with_hasher(FxBuildHasher);
 		let mut tick_locks = HashSet::with_hasher(FxBuildHasher);
 
 		for voxject in sector.voxjects.values() {
-			// These values are relative to the current level. So a player position of
-			// (0.5 0.5 0.5, Chunk 0 0 0, Level 0) is the same as (0.25 0.25 0.25, Chunk 0, 0, 0, Level 1).
-
-			// Voxjects temporarily do not have a position until we integrate Rapier
-			let mut player_position = IsometryMatrix3::default()
-				.inverse_transform_vector(&self.location.position.coords)
-				/ 16.0;
-			let mut player_chunk = ChunkCoordinates::new(
-				voxject.id,
-				convert_unchecked(player_position),
-				Level::new(0),
-			);
-			let mut level_chunks = HashSet::new();
-
-			tick_locks.insert(player_chunk);
-
-			for level in 0..LEVELS - 1 {
-				let level = Level::new(level);
-				let radius = ((*level as i32 / LEVELS as i32) * MULTIPLIER + MULTIPLIER) >> *level;
-
-				if radius > 0 {
-					for x in
-						player_chunk.coordinates.x - radius..=player_chunk.coordinates.x + radius
-					{
-						for y in player_chunk.coordinates.y - radius
-							..=player_chunk.coordinates.y + radius
-						{
-							for z in player_chunk.coordinates.z - radius
-								..=player_chunk.coordinates.z + radius
-							{
-								let chunk =
-									ChunkCoordinates::new(voxject.id, vector![x, y, z], level);
-
-								// circles look nicer
-								let chunk_center =
-									vector![x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5];
-								if player_chunk != chunk
-									&& player_position.metric_distance(&chunk_center) as i32
-										> radius
-								{
-									continue;
-								}
-
-								level_chunks.insert(chunk.upleveled());
-							}
-						}
-					}
-				}
-
-				for chunk in &level_chunks {
-					let chunk = chunk.downleveled();
-					client_locks.insert(chunk + Vector3::new(0, 0, 0));
-					client_locks.insert(chunk + Vector3::new(0, 0, 1));
-					client_locks.insert(chunk + Vector3::new(0, 1, 0));
-					client_locks.insert(chunk + Vector3::new(0, 1, 1));
-					client_locks.insert(chunk + Vector3::new(1, 0, 0));
-					client_locks.insert(chunk + Vector3::new(1, 0, 1));
-					client_locks.insert(chunk + Vector3::new(1, 1, 0));
-					client_locks.insert(chunk + Vector3::new(1, 1, 1));
-				}
+			let relative_position =
+				WorldPosition(self.location.position).relative_to(voxject.id, &voxject.location);
 
-				player_position /= 2.0;
-				player_chunk = player_chunk.upleveled();
+			tick_locks.insert(relative_position.chunk(Level::new(0)));
 
-				if *level < LEVELS - 2 {
-					level_chunks = level_chunks
-						.into_iter()
-						.map(|chunk| chunk.upleveled())
-						.collect();
-				}
-			}
+			voxject
+				.lod
+				.client_locks(&relative_position, away, &mut client_locks);
 		}
 
+		// Clients ignore anything past the border or above the max level, so there's no point in sending it
+		client_locks.retain(|coordinates| {
+			coordinates.is_sane(sector.world_radius_chunks, sector.max_level)
+		});
+
 		(client_locks, tick_locks)
 	}
 }
@@ -0,0 +1,166 @@
+use crate::{
+	generation::{sphere_generator, Generator},
+	sector::{Collision, Data},
+};
+use clap::Args;
+use log::info;
+use nalgebra::vector;
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::{
+	consts::CELLS_PER_CHUNK,
+	data::{
+		world::{ChunkCoordinates, Level, Material, LEVELS},
+		Id,
+	},
+};
+use std::{
+	collections::HashMap,
+	fs::File,
+	io::{self, BufWriter, Write},
+	path::PathBuf,
+	time::Instant,
+};
+
+/// Generates and triangulates chunks without starting a sector, writing the result as a Wavefront OBJ file. Intended
+/// for quickly checking what changes to world generation look like.
+#[derive(Args)]
+pub struct PreviewArgs {
+	/// Path to write the Wavefront OBJ file to
+	#[arg(long)]
+	output: PathBuf,
+
+	/// Level to generate chunks at
+	#[arg(long, default_value_t = 0, value_parser = clap::value_parser!(u8).range(..LEVELS as i64))]
+	level: u8,
+
+	/// How many chunks to generate out from the voxject's center in each direction
+	#[arg(long, default_value_t = 4)]
+	radius: i32,
+}
+
+pub fn run(
+	PreviewArgs {
+		output,
+		level,
+		radius,
+	}: PreviewArgs,
+) -> io::Result<()> {
+	let start_time = Instant::now();
+
+	// Voxject ids aren't used by generators, and the preview only ever has one voxject anyway
+	let voxject = Id::new();
+	let level = Level::new(level);
+	let generator: Generator = sphere_generator;
+
+	let mut data = HashMap::<_, Data, _>::with_hasher(FxBuildHasher);
+
+	let mut stats = Stats::default();
+	let mut writer = BufWriter::new(File::create(&output)?);
+
+	writeln!(
+		writer,
+		"# Solarscape world generation preview, level {level}"
+	)?;
+
+	for x in -radius..radius {
+		for y in -radius..radius {
+			for z in -radius..radius {
+				let coordinates = ChunkCoordinates::new(voxject, vector![x, y, z], level);
+
+				let Some(dependencies) = coordinates.mesh_dependencies() else {
+					continue;
+				};
+
+				// Collision meshes are built from borrowed data, so make sure it's all generated before borrowing it
+				for dependency in dependencies {
+					data.entry(dependency)
+						.or_insert_with(|| generator(&dependency));
+				}
+
+				stats.count_chunk(&data[&coordinates]);
+
+				let collision =
+					Collision::build(dependencies.map(|coordinates| &data[&coordinates]));
+
+				// Collision is relative to the chunk and in the chunk's level's scale
+				let translation = coordinates.origin().position.coords;
+				let scale = f32::powi(2.0, *level as i32);
+
+				for vertex in &collision.vertices {
+					let vertex = translation + vertex.coords * scale;
+					writeln!(writer, "v {} {} {}", vertex.x, vertex.y, vertex.z)?;
+				}
+
+				for [a, b, c] in &collision.indices {
+					let offset = stats.vertices + 1;
+					writeln!(writer, "f {} {} {}", a + offset, b + offset, c + offset)?;
+				}
+
+				stats.vertices += collision.vertices.len() as u32;
+				stats.triangles += collision.indices.len();
+			}
+		}
+	}
+
+	writer.flush()?;
+
+	info!(
+		"Wrote {} in {:.0?}",
+		output.display(),
+		Instant::now() - start_time
+	);
+
+	stats.log();
+
+	Ok(())
+}
+
+#[derive(Default)]
+struct Stats {
+	vertices: u32,
+	triangles: usize,
+
+	empty_chunks: usize,
+	solid_chunks: usize,
+	mixed_chunks: usize,
+
+	corium: usize,
+	stone: usize,
+	ground: usize,
+	nothing: usize,
+}
+
+impl Stats {
+	fn count_chunk(&mut self, data: &Data) {
+		let mut nothing = 0;
+
+		for (_, material, _) in data.iter_voxels() {
+			match material {
+				Material::Corium => self.corium += 1,
+				Material::Stone => self.stone += 1,
+				Material::Ground => self.ground += 1,
+				Material::Nothing => nothing += 1,
+			}
+		}
+
+		self.nothing += nothing;
+
+		match nothing {
+			0 => self.solid_chunks += 1,
+			CELLS_PER_CHUNK => self.empty_chunks += 1,
+			_ => self.mixed_chunks += 1,
+		}
+	}
+
+	fn log(&self) {
+		info!("{} vertices, {} triangles", self.vertices, self.triangles);
+		info!(
+			"Chunks: {} empty, {} solid, {} mixed",
+			self.empty_chunks, self.solid_chunks, self.mixed_chunks
+		);
+		info!(
+			"Cells: {} corium, {} stone, {} ground, {} nothing",
+			self.corium, self.stone, self.ground, self.nothing
+		);
+	}
+}
@@ -0,0 +1,627 @@
+use crate::{config, player::Player, sector::SharedSector};
+use log::{error, info};
+use rustc_hash::FxBuildHasher;
+use serde::{Deserialize, Serialize};
+use solarscape_shared::{
+	data::{
+		world::{ChunkCoordinates, Location},
+		Id,
+	},
+	message::{serverbound::Serverbound, PROTOCOL_VERSION},
+};
+use std::{
+	collections::VecDeque,
+	fs::{self, File},
+	hash::BuildHasher,
+	io::{self, BufReader, BufWriter, ErrorKind::UnexpectedEof, Read, Write},
+	path::{Path, PathBuf},
+	time::{SystemTime, UNIX_EPOCH},
+};
+use thiserror::Error;
+
+/// Identifies a file as a recording.
+const MAGIC: [u8; 8] = *b"SREPLAY\0";
+
+/// Bumped whenever [`Header`] or [`Frame`] changes, recordings from any other version are rejected rather than misread.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// Starts every recording file, so that each file can be identified on it's own.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct Header {
+	magic: [u8; 8],
+	schema_version: u32,
+	protocol_version: u32,
+
+	pub sector: Box<str>,
+	pub player: Id,
+	pub tick_rate: u32,
+	/// Which file of the recording this is, counting from 0.
+	pub part: u32,
+}
+
+/// Everything after the [`Header`] is a sequence of frames, each written as it's length as a little endian `u32`
+/// followed by the frame itself.
+#[derive(Deserialize, Serialize)]
+pub enum Frame {
+	/// A message the player sent which the sector accepted during `tick`. Messages the sector rejected aren't recorded.
+	Message {
+		tick: u64,
+		message: Serverbound,
+	},
+	/// The player created a structure. Structure ids are random, so this is how a replay works out which of it's own
+	/// structures a recorded [`DeleteStructure`] refers to.
+	///
+	/// [`DeleteStructure`]: solarscape_shared::message::serverbound::DeleteStructure
+	StructureCreated(Id),
+	Checkpoint(Checkpoint),
+}
+
+/// The player's state at the end of a tick, which replays are checked against.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct Checkpoint {
+	pub tick: u64,
+	pub location: Location,
+	/// See [`hash_locks`].
+	pub lock_hash: u64,
+	/// How many chunks the player has, or is waiting for, client and tick locks on.
+	pub locks: u32,
+	/// How many structures the player owns.
+	pub structures: u32,
+}
+
+/// Hashes the chunks a player has locked. Chunks are identified by voxject name, as voxject ids differ between
+/// processes, and each chunk's hash is summed so that the order the locks were taken in doesn't matter.
+pub fn hash_locks<'a>(
+	sector: &SharedSector,
+	client_locks: impl Iterator<Item = &'a ChunkCoordinates>,
+	tick_locks: impl Iterator<Item = &'a ChunkCoordinates>,
+) -> (u64, u32) {
+	let mut hash = 0u64;
+	let mut count = 0;
+
+	for (client, coordinates) in client_locks
+		.map(|coordinates| (true, coordinates))
+		.chain(tick_locks.map(|coordinates| (false, coordinates)))
+	{
+		let voxject = sector
+			.voxjects
+			.get(&coordinates.voxject)
+			.map(|voxject| &*voxject.name);
+
+		hash = hash.wrapping_add(FxBuildHasher.hash_one((
+			client,
+			voxject,
+			coordinates.coordinates,
+			*coordinates.level,
+		)));
+		count += 1;
+	}
+
+	(hash, count)
+}
+
+/// Records the messages a player sends, along with a [`Checkpoint`] every `checkpoint_interval` ticks. Files are named
+/// `<player>-<started>-<part>.replay`, a new part is started at the first checkpoint after the current one reaches
+/// `max_file_size`, so every part starts with a checkpoint.
+pub struct Recorder {
+	directory: PathBuf,
+	/// Start of every file name in this recording.
+	name: String,
+	header: Header,
+
+	file: BufWriter<File>,
+	file_size: u64,
+	/// Files written so far which haven't been deleted, oldest first.
+	files: VecDeque<PathBuf>,
+
+	max_file_size: Option<u64>,
+	max_files: Option<usize>,
+	checkpoint_interval: u64,
+
+	/// Tick of the last checkpoint, [`None`] until the first. Messages aren't recorded until there's been a checkpoint,
+	/// so that a replay always has a state to start from.
+	last_checkpoint: Option<u64>,
+	/// Set by [`Recorder::stop`], a final checkpoint is written at the end of the tick.
+	stopping: bool,
+}
+
+impl Recorder {
+	pub fn start(
+		config: &config::Recording,
+		sector: &str,
+		player: Id,
+		tick_rate: u32,
+	) -> Result<Self, RecordingError> {
+		let directory = config.directory.clone().ok_or(RecordingError::Disabled)?;
+		fs::create_dir_all(&directory)?;
+
+		let started = SystemTime::now()
+			.duration_since(UNIX_EPOCH)
+			.map_or(0, |now| now.as_secs());
+		let name = format!("{player}-{started}");
+
+		let header = Header {
+			magic: MAGIC,
+			schema_version: SCHEMA_VERSION,
+			protocol_version: PROTOCOL_VERSION,
+
+			sector: sector.into(),
+			player,
+			tick_rate,
+			part: 0,
+		};
+
+		let path = directory.join(format!("{name}-0000.replay"));
+		let mut file = BufWriter::new(File::create(&path)?);
+		let file_size = write_frame(&mut file, &header)?;
+
+		Ok(Self {
+			directory,
+			name,
+			header,
+
+			file,
+			file_size,
+			files: VecDeque::from([path]),
+
+			max_file_size: match config.max_file_size {
+				0 => None,
+				mib => Some(mib * 1024 * 1024),
+			},
+			max_files: match config.max_files {
+				0 => None,
+				max => Some(max),
+			},
+			checkpoint_interval: (config.checkpoint_interval * tick_rate as f32)
+				.round()
+				.max(1.0) as u64,
+
+			last_checkpoint: None,
+			stopping: false,
+		})
+	}
+
+	/// Path of the file currently being written.
+	pub fn path(&self) -> &Path {
+		self.files.back().expect("there is always a current file")
+	}
+
+	/// Whether messages are being recorded yet, see [`Recorder::last_checkpoint`].
+	pub fn is_started(&self) -> bool {
+		self.last_checkpoint.is_some()
+	}
+
+	pub fn is_stopping(&self) -> bool {
+		self.stopping
+	}
+
+	/// Stops recording at the end of the tick, after a final checkpoint.
+	pub fn stop(&mut self) {
+		self.stopping = true;
+	}
+
+	pub fn checkpoint_due(&self, tick: u64) -> bool {
+		self.stopping
+			|| self
+				.last_checkpoint
+				.is_none_or(|last| tick - last >= self.checkpoint_interval)
+	}
+
+	pub fn message(&mut self, tick: u64, message: Serverbound) -> io::Result<()> {
+		self.file_size += write_frame(&mut self.file, &Frame::Message { tick, message })?;
+		Ok(())
+	}
+
+	pub fn structure_created(&mut self, structure: Id) -> io::Result<()> {
+		self.file_size += write_frame(&mut self.file, &Frame::StructureCreated(structure))?;
+		Ok(())
+	}
+
+	/// Writes `checkpoint`, first starting a new file if this one is full. Flushed straight away, so that a crash loses
+	/// no more than one checkpoint interval.
+	pub fn checkpoint(&mut self, checkpoint: Checkpoint) -> io::Result<()> {
+		if self.is_started()
+			&& self
+				.max_file_size
+				.is_some_and(|max_file_size| self.file_size >= max_file_size)
+		{
+			self.rotate()?;
+		}
+
+		self.file_size += write_frame(&mut self.file, &Frame::Checkpoint(checkpoint))?;
+		self.file.flush()?;
+
+		self.last_checkpoint = Some(checkpoint.tick);
+
+		Ok(())
+	}
+
+	fn rotate(&mut self) -> io::Result<()> {
+		self.file.flush()?;
+
+		self.header.part += 1;
+
+		let path = self
+			.directory
+			.join(format!("{}-{:04}.replay", self.name, self.header.part));
+		let mut file = BufWriter::new(File::create(&path)?);
+		self.file_size = write_frame(&mut file, &self.header)?;
+		self.file = file;
+		self.files.push_back(path);
+
+		while self
+			.max_files
+			.is_some_and(|max_files| self.files.len() > max_files)
+		{
+			if let Some(oldest) = self.files.pop_front() {
+				fs::remove_file(oldest)?;
+			}
+		}
+
+		Ok(())
+	}
+}
+
+/// Runs `write` with the player's recorder if they're being recorded, stopping the recording if it fails.
+pub fn record(player: &mut Player, write: impl FnOnce(&mut Recorder) -> io::Result<()>) {
+	let Some(recorder) = &mut player.recorder else {
+		return;
+	};
+
+	if let Err(error) = write(recorder) {
+		error!(
+			player_id:% = player.id;
+			"Stopped recording player {} after failing to write {}: {error}",
+			player.id,
+			recorder.path().display()
+		);
+		player.recorder = None;
+	}
+}
+
+/// Stops recording the player, if they're being recorded.
+pub fn finish(player: &mut Player) {
+	if let Some(recorder) = player.recorder.take() {
+		info!(
+			player_id:% = player.id;
+			"Stopped recording player {}, last written to {}",
+			player.id,
+			recorder.path().display()
+		);
+	}
+}
+
+/// Returns how many bytes were written.
+fn write_frame(file: &mut impl Write, frame: &impl Serialize) -> io::Result<u64> {
+	let bytes = bincode::serialize(frame).map_err(io::Error::other)?;
+	let length = u32::try_from(bytes.len()).map_err(io::Error::other)?;
+
+	file.write_all(&length.to_le_bytes())?;
+	file.write_all(&bytes)?;
+
+	Ok(size_of::<u32>() as u64 + bytes.len() as u64)
+}
+
+/// Reads the next frame, [`None`] at the end of the file. A frame cut off part way through, as the last frame will be
+/// if the sector stopped while writing it, is treated as the end of the file.
+fn read_frame<T: for<'de> Deserialize<'de>>(
+	file: &mut impl Read,
+) -> Result<Option<T>, RecordingError> {
+	let mut length = [0; size_of::<u32>()];
+
+	let mut bytes = match file.read_exact(&mut length) {
+		Ok(()) => vec![0; u32::from_le_bytes(length) as usize],
+		Err(error) if error.kind() == UnexpectedEof => return Ok(None),
+		Err(error) => return Err(error.into()),
+	};
+
+	match file.read_exact(&mut bytes) {
+		Ok(()) => Ok(Some(bincode::deserialize(&bytes)?)),
+		Err(error) if error.kind() == UnexpectedEof => Ok(None),
+		Err(error) => Err(error.into()),
+	}
+}
+
+/// Reads a recording file written by [`Recorder`], rejecting recordings from other versions.
+pub fn read(path: &Path) -> Result<(Header, Vec<Frame>), RecordingError> {
+	let mut file = BufReader::new(File::open(path)?);
+
+	let header: Header = read_frame(&mut file)
+		.ok()
+		.flatten()
+		.filter(|header: &Header| header.magic == MAGIC)
+		.ok_or(RecordingError::NotARecording)?;
+
+	if header.schema_version != SCHEMA_VERSION {
+		return Err(RecordingError::SchemaVersion(header.schema_version));
+	}
+
+	if header.protocol_version != PROTOCOL_VERSION {
+		return Err(RecordingError::ProtocolVersion(header.protocol_version));
+	}
+
+	let mut frames = vec![];
+
+	while let Some(frame) = read_frame(&mut file)? {
+		frames.push(frame);
+	}
+
+	Ok((header, frames))
+}
+
+#[derive(Debug, Error)]
+pub enum RecordingError {
+	#[error("recording is disabled, set `recording.directory` in the sector config to enable it")]
+	Disabled,
+
+	#[error(transparent)]
+	Io(#[from] io::Error),
+
+	#[error("not a recording")]
+	NotARecording,
+
+	#[error(
+		"recording is schema version {0}, but this server only reads version {SCHEMA_VERSION}"
+	)]
+	SchemaVersion(u32),
+
+	#[error(
+		"recording is from protocol version {0}, but this server is protocol version {PROTOCOL_VERSION}"
+	)]
+	ProtocolVersion(u32),
+
+	#[error("recording is corrupt: {0}")]
+	Corrupt(#[from] bincode::Error),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::sector::tests::shared_sector;
+	use solarscape_shared::data::world::Level;
+	use std::{env, io::Cursor};
+	use tokio::runtime::Runtime;
+
+	/// A temporary recording directory, deleted when dropped.
+	struct TempDir(PathBuf);
+
+	impl TempDir {
+		fn new() -> Self {
+			Self(env::temp_dir().join(format!("solarscape-recording-{}", Id::new())))
+		}
+
+		fn config(&self, max_file_size: u64, max_files: usize) -> config::Recording {
+			config::Recording {
+				directory: Some(self.0.clone()),
+				max_file_size,
+				max_files,
+				checkpoint_interval: 0.1,
+				..Default::default()
+			}
+		}
+
+		fn files(&self) -> Vec<PathBuf> {
+			let mut files: Vec<_> = fs::read_dir(&self.0)
+				.unwrap()
+				.map(|entry| entry.unwrap().path())
+				.collect();
+			files.sort();
+			files
+		}
+	}
+
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			let _ = fs::remove_dir_all(&self.0);
+		}
+	}
+
+	fn checkpoint(tick: u64) -> Checkpoint {
+		Checkpoint {
+			tick,
+			location: Location::default(),
+			lock_hash: tick * 7,
+			locks: 2,
+			structures: 1,
+		}
+	}
+
+	fn ticks(frames: &[Frame]) -> Vec<(char, u64)> {
+		frames
+			.iter()
+			.map(|frame| match frame {
+				Frame::Message { tick, .. } => ('m', *tick),
+				Frame::StructureCreated(_) => ('s', 0),
+				Frame::Checkpoint(checkpoint) => ('c', checkpoint.tick),
+			})
+			.collect()
+	}
+
+	#[test]
+	fn frames_round_trip() {
+		let mut bytes = vec![];
+		let written = write_frame(&mut bytes, &checkpoint(5)).unwrap();
+		write_frame(&mut bytes, &checkpoint(6)).unwrap();
+
+		assert_eq!(written as usize * 2, bytes.len());
+
+		let mut file = Cursor::new(bytes);
+		let read: Checkpoint = read_frame(&mut file).unwrap().unwrap();
+		assert_eq!((read.tick, read.lock_hash), (5, 35));
+		assert_eq!(
+			read_frame::<Checkpoint>(&mut file).unwrap().unwrap().tick,
+			6
+		);
+		assert!(read_frame::<Checkpoint>(&mut file).unwrap().is_none());
+	}
+
+	#[test]
+	fn cut_off_frames_end_the_file() {
+		let mut bytes = vec![];
+		write_frame(&mut bytes, &checkpoint(5)).unwrap();
+		let whole = bytes.len();
+		write_frame(&mut bytes, &checkpoint(6)).unwrap();
+
+		for length in [whole + 2, bytes.len() - 1] {
+			let mut file = Cursor::new(&bytes[..length]);
+			assert!(read_frame::<Checkpoint>(&mut file).unwrap().is_some());
+			assert!(read_frame::<Checkpoint>(&mut file).unwrap().is_none());
+		}
+	}
+
+	#[test]
+	fn recordings_round_trip() {
+		let directory = TempDir::new();
+		let player = Id::new();
+		let mut recorder = Recorder::start(&directory.config(0, 0), "test", player, 30).unwrap();
+
+		recorder.checkpoint(checkpoint(10)).unwrap();
+		recorder
+			.message(11, Serverbound::PlayerLocation(Location::default()))
+			.unwrap();
+		recorder.structure_created(Id::new()).unwrap();
+		recorder.checkpoint(checkpoint(13)).unwrap();
+		drop(recorder);
+
+		let files = directory.files();
+		assert_eq!(files.len(), 1);
+
+		let (header, frames) = read(&files[0]).unwrap();
+		assert_eq!(
+			(
+				&*header.sector,
+				header.player,
+				header.tick_rate,
+				header.part
+			),
+			("test", player, 30, 0)
+		);
+		assert_eq!(ticks(&frames), [('c', 10), ('m', 11), ('s', 0), ('c', 13)]);
+	}
+
+	#[test]
+	fn recording_needs_a_directory() {
+		assert!(matches!(
+			Recorder::start(&config::Recording::default(), "test", Id::new(), 30),
+			Err(RecordingError::Disabled)
+		));
+	}
+
+	#[test]
+	fn other_versions_and_files_are_rejected() {
+		let directory = TempDir::new();
+		fs::create_dir_all(&directory.0).unwrap();
+		let path = directory.0.join("test.replay");
+
+		let header = |schema_version, protocol_version| {
+			let mut bytes = vec![];
+			write_frame(
+				&mut bytes,
+				&Header {
+					magic: MAGIC,
+					schema_version,
+					protocol_version,
+					sector: "test".into(),
+					player: Id::new(),
+					tick_rate: 30,
+					part: 0,
+				},
+			)
+			.unwrap();
+			bytes
+		};
+
+		fs::write(&path, header(SCHEMA_VERSION + 1, PROTOCOL_VERSION)).unwrap();
+		assert!(
+			matches!(read(&path), Err(RecordingError::SchemaVersion(version)) if version == SCHEMA_VERSION + 1)
+		);
+
+		fs::write(&path, header(SCHEMA_VERSION, PROTOCOL_VERSION + 1)).unwrap();
+		assert!(
+			matches!(read(&path), Err(RecordingError::ProtocolVersion(version)) if version == PROTOCOL_VERSION + 1)
+		);
+
+		fs::write(&path, b"\x02\0\0\0hi").unwrap();
+		assert!(matches!(read(&path), Err(RecordingError::NotARecording)));
+
+		fs::write(&path, []).unwrap();
+		assert!(matches!(read(&path), Err(RecordingError::NotARecording)));
+	}
+
+	#[test]
+	fn files_are_rotated_at_checkpoints() {
+		let directory = TempDir::new();
+		let mut recorder = Recorder::start(&directory.config(1, 2), "test", Id::new(), 30).unwrap();
+
+		// A little over 1 MiB of messages between each checkpoint
+		let location = Serverbound::PlayerLocation(Location::default());
+		let per_mib = 1024 * 1024 / bincode::serialized_size(&location).unwrap() as u64;
+
+		for part in 0..4 {
+			let tick = part * 1000;
+			recorder.checkpoint(checkpoint(tick)).unwrap();
+
+			for message in 0..per_mib {
+				recorder
+					.message(tick + 1 + message % 900, location.clone())
+					.unwrap();
+			}
+		}
+
+		recorder.checkpoint(checkpoint(4000)).unwrap();
+		drop(recorder);
+
+		// Only the last two parts are kept, and each starts with a checkpoint
+		let files = directory.files();
+		assert_eq!(files.len(), 2);
+
+		for (path, (part, tick)) in files.iter().zip([(3, 3000), (4, 4000)]) {
+			assert!(path
+				.to_string_lossy()
+				.ends_with(&format!("-{part:04}.replay")));
+
+			let (header, frames) = read(path).unwrap();
+			assert_eq!(header.part, part);
+			assert_eq!(ticks(&frames)[0], ('c', tick));
+		}
+	}
+
+	#[test]
+	fn checkpoints_are_due_every_interval() {
+		let directory = TempDir::new();
+		// 0.1 seconds at 30Hz is every 3 ticks
+		let mut recorder = Recorder::start(&directory.config(0, 0), "test", Id::new(), 30).unwrap();
+
+		assert!(!recorder.is_started());
+		assert!(recorder.checkpoint_due(0));
+
+		recorder.checkpoint(checkpoint(10)).unwrap();
+		assert!(recorder.is_started());
+		assert!(!recorder.checkpoint_due(12));
+		assert!(recorder.checkpoint_due(13));
+
+		recorder.stop();
+		assert!(recorder.checkpoint_due(11));
+	}
+
+	#[test]
+	fn lock_hashes_ignore_order() {
+		let runtime = Runtime::new().unwrap();
+		let (sector, coordinates) = shared_sector(&runtime, |_| Default::default());
+
+		let other = ChunkCoordinates::new(
+			coordinates.voxject,
+			coordinates.coordinates.add_scalar(1),
+			Level::new(0),
+		);
+		let (a, b) = (&[coordinates, other], &[other, coordinates]);
+
+		let (hash, count) = hash_locks(&sector, a.iter(), [].iter());
+		assert_eq!(count, 2);
+		assert_eq!(hash_locks(&sector, b.iter(), [].iter()), (hash, 2));
+
+		// Client and tick locks on the same chunks are told apart
+		assert_ne!(hash_locks(&sector, [].iter(), a.iter()).0, hash);
+		assert_ne!(hash_locks(&sector, a[..1].iter(), a[1..].iter()).0, hash);
+	}
+}
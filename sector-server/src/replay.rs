@@ -0,0 +1,433 @@
+use crate::{
+	config,
+	persistence::Persistence,
+	player::Player,
+	recording::{self, Checkpoint, Frame, Header, RecordingError},
+	sector::{Event, Sector},
+};
+use clap::Args;
+use log::{info, warn};
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::{
+	clock::MockClock,
+	connection::{ClientEnd, Connection},
+	data::Id,
+	message::{
+		clientbound::Clientbound,
+		serverbound::{DeleteStructure, InteractBlock, Serverbound},
+	},
+};
+use std::{collections::HashSet, path::PathBuf, sync::Arc};
+use thiserror::Error;
+use tokio::runtime::Runtime;
+
+/// Replays a recording made with `/record` or `recording.players` against a fresh sector, without a database or any
+/// network, and checks the player's state against the recorded checkpoints. Uses the same `--config` as the sector
+/// the recording was made on.
+#[derive(Args)]
+pub struct ReplayArgs {
+	/// Recording files, in order. Later files of a recording carry on from earlier ones, and any of them can be
+	/// replayed without the files before it
+	#[arg(required = true)]
+	files: Vec<PathBuf>,
+}
+
+/// Returns `true` if every checkpoint matched.
+pub fn run(
+	ReplayArgs { files }: ReplayArgs,
+	mut config: config::Sector,
+) -> Result<bool, ReplayError> {
+	let mut header: Option<Header> = None;
+	let mut frames = vec![];
+
+	for path in &files {
+		let (file_header, file_frames) =
+			recording::read(path).map_err(|error| ReplayError::Read(path.clone(), error))?;
+
+		if let Some(header) = &header {
+			if file_header.player != header.player || file_header.sector != header.sector {
+				return Err(ReplayError::DifferentRecording(path.clone()));
+			}
+
+			if file_header.part != header.part + 1 {
+				warn!(
+					"{} is part {}, but the previous file was part {}, checkpoints after the gap are likely to mismatch",
+					path.display(),
+					file_header.part,
+					header.part
+				);
+			}
+		}
+
+		header = Some(file_header);
+		frames.extend(file_frames);
+	}
+
+	let Some(header) = header else {
+		unreachable!("clap should require at least one file");
+	};
+
+	if header.sector != config.name {
+		return Err(ReplayError::WrongSector(header.sector));
+	}
+
+	// The replay is fed exactly what the sector accepted, so nothing needs limiting, and it shouldn't record itself
+	config.tick_rate = header.tick_rate;
+	config.structure_limits.placements_per_second = 0.0;
+	config.snapshot_path = None;
+	config.recording = config::Recording::default();
+
+	let runtime = Runtime::new().map_err(RecordingError::from)?;
+	let _runtime = runtime.enter();
+
+	// Time only moves as the replay ticks, so it runs as fast as it can while still seeing the same times
+	let clock = Arc::new(MockClock::new());
+	let sector = Sector::new(Persistence::memory(), clock.clone(), config)?;
+	let (client, server) = Connection::pair();
+	let _ = sector
+		.shared
+		.send(Event::PlayerConnected(header.player, server, None));
+
+	let mut frames = frames.into_iter();
+
+	// Every recording file starts with a checkpoint, which is where the replay starts from
+	let Some(Frame::Checkpoint(start)) = frames.next() else {
+		return Err(ReplayError::NoCheckpoint);
+	};
+
+	let mut replay = Replay {
+		sector,
+		clock,
+		client,
+		player: header.player,
+
+		recorded_structures: vec![],
+		replayed_structures: vec![],
+		seen_structures: HashSet::with_hasher(FxBuildHasher),
+
+		structures_offset: 0,
+		checkpoints: 0,
+		mismatches: 0,
+	};
+
+	// The player connects at the default location with no locks, so the location only needs sending if it's elsewhere
+	replay.sector.ticks = start.tick.saturating_sub(1);
+	if start.locks > 0 {
+		replay
+			.client
+			.send(Serverbound::PlayerLocation(start.location));
+	}
+
+	replay.advance_to(start.tick);
+
+	// Structures the player owned before the recording started don't exist in the replay, so only changes are compared
+	let replayed = replay.sector.checkpoint(replay.player()?);
+	replay.structures_offset = start.structures as i64 - replay.structures(&replayed);
+	replay.check(&start)?;
+
+	for frame in frames {
+		match frame {
+			Frame::Message { tick, message } => {
+				replay.advance_to(tick.saturating_sub(1));
+
+				let message = match message {
+					Serverbound::DeleteStructure(DeleteStructure { structure }) => {
+						Serverbound::DeleteStructure(DeleteStructure {
+							structure: replay.replayed_structure(structure),
+						})
+					}
+					Serverbound::InteractBlock(interact) => {
+						Serverbound::InteractBlock(InteractBlock {
+							structure: replay.replayed_structure(interact.structure),
+							..interact
+						})
+					}
+					message => message,
+				};
+
+				replay.client.send(message);
+			}
+			Frame::StructureCreated(structure) => replay.recorded_structures.push(structure),
+			Frame::Checkpoint(checkpoint) => {
+				replay.advance_to(checkpoint.tick);
+				replay.check(&checkpoint)?;
+			}
+		}
+	}
+
+	info!(
+		"Replayed {} ticks, {} of {} checkpoints matched",
+		replay.sector.ticks - start.tick,
+		replay.checkpoints - replay.mismatches,
+		replay.checkpoints
+	);
+
+	Ok(replay.mismatches == 0)
+}
+
+struct Replay {
+	sector: Sector,
+	clock: Arc<MockClock>,
+	client: Connection<ClientEnd>,
+	player: Id,
+
+	/// Structures the player created while recording, and in the replay, in the order they were created.
+	recorded_structures: Vec<Id>,
+	replayed_structures: Vec<Id>,
+	seen_structures: HashSet<Id, FxBuildHasher>,
+
+	/// How many more structures the player owned when recording, see [`Replay::check`].
+	structures_offset: i64,
+	checkpoints: usize,
+	mismatches: usize,
+}
+
+impl Replay {
+	/// Ticks the sector until it's run `tick`.
+	fn advance_to(&mut self, tick: u64) {
+		let delta = self.sector.tick_duration();
+
+		while self.sector.ticks < tick {
+			self.clock.advance(delta);
+			self.sector.tick(delta.as_secs_f32());
+
+			while let Ok(message) = self.client.try_recv() {
+				if let Clientbound::SyncStructure(sync) = message {
+					if sync.owner == self.player && self.seen_structures.insert(sync.id) {
+						self.replayed_structures.push(sync.id);
+					}
+				}
+			}
+		}
+	}
+
+	/// The replayed structure created in place of the recorded `structure`, or `structure` itself if it wasn't created
+	/// during the recording.
+	fn replayed_structure(&self, structure: Id) -> Id {
+		self.recorded_structures
+			.iter()
+			.position(|recorded| *recorded == structure)
+			.and_then(|index| self.replayed_structures.get(index))
+			.copied()
+			.unwrap_or(structure)
+	}
+
+	fn player(&self) -> Result<&Player, ReplayError> {
+		self.sector
+			.players
+			.iter()
+			.find(|player| player.id == self.player)
+			.ok_or(ReplayError::Disconnected(self.sector.ticks))
+	}
+
+	/// How many structures the player would own in `replayed` if they'd owned the same ones as when recording.
+	fn structures(&self, replayed: &Checkpoint) -> i64 {
+		replayed.structures as i64 + self.structures_offset
+	}
+
+	/// Compares the player's state with `recorded`, logging any differences.
+	fn check(&mut self, recorded: &Checkpoint) -> Result<(), ReplayError> {
+		let replayed = self.sector.checkpoint(self.player()?);
+		let mut differences = vec![];
+
+		if replayed.location.position != recorded.location.position
+			|| replayed.location.rotation != recorded.location.rotation
+		{
+			differences.push(format!(
+				"location is {}, recorded {}",
+				replayed.location.position, recorded.location.position
+			));
+		}
+
+		if replayed.lock_hash != recorded.lock_hash {
+			differences.push(format!(
+				"{} chunks are locked, recorded {} with a different hash",
+				replayed.locks, recorded.locks
+			));
+		}
+
+		let structures = self.structures(&replayed);
+		if structures != recorded.structures as i64 {
+			differences.push(format!(
+				"owns {structures} structures, recorded {}",
+				recorded.structures
+			));
+		}
+
+		self.checkpoints += 1;
+
+		if !differences.is_empty() {
+			self.mismatches += 1;
+			warn!(
+				"Checkpoint at tick {} doesn't match: {}",
+				recorded.tick,
+				differences.join(", ")
+			);
+		}
+
+		Ok(())
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum ReplayError {
+	#[error("unable to read {}: {1}", .0.display())]
+	Read(PathBuf, RecordingError),
+
+	#[error(transparent)]
+	Recording(#[from] RecordingError),
+
+	#[error("{} is from a different recording than the files before it", .0.display())]
+	DifferentRecording(PathBuf),
+
+	#[error("recording is of sector `{0}`, but the config is for a different sector")]
+	WrongSector(Box<str>),
+
+	#[error("recording doesn't start with a checkpoint")]
+	NoCheckpoint,
+
+	#[error("the player was disconnected at tick {0}")]
+	Disconnected(u64),
+
+	#[error("unable to start the sector: {0}")]
+	Sqlx(#[from] sqlx::Error),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::{point, UnitQuaternion};
+	use solarscape_shared::{
+		data::world::{BlockType, Location},
+		message::serverbound::CreateStructure,
+	};
+	use std::{env, fs};
+
+	/// A temporary recording directory, deleted when dropped.
+	struct TempDir(PathBuf);
+
+	impl Drop for TempDir {
+		fn drop(&mut self) {
+			let _ = fs::remove_dir_all(&self.0);
+		}
+	}
+
+	fn config(directory: &TempDir, player: Id) -> config::Sector {
+		hocon::de::from_str(&format!(
+			"name: test\nvoxjects: [{{ name: test }}]\nrecording: {{ directory: \"{}\", players: [\"{player}\"], checkpoint_interval: 0.1 }}",
+			directory.0.display()
+		))
+		.unwrap()
+	}
+
+	/// Somewhere well above the voxject.
+	fn location(height: f32) -> Location {
+		Location {
+			position: point![0.0, height, 0.0],
+			rotation: UnitQuaternion::identity(),
+		}
+	}
+
+	/// Records `player` creating, moving around, and deleting a structure, returning the recording's files.
+	fn record(directory: &TempDir, player: Id) -> Vec<PathBuf> {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = Sector::new(
+			Persistence::memory(),
+			clock.clone(),
+			config(directory, player),
+		)
+		.unwrap();
+
+		let (client, server) = Connection::pair();
+		let _ = sector.send(Event::PlayerConnected(player, server, None));
+
+		let tick = |sector: &mut Sector, ticks| {
+			for _ in 0..ticks {
+				let delta = sector.tick_duration();
+				clock.advance(delta);
+				sector.tick(delta.as_secs_f32());
+			}
+		};
+
+		tick(&mut sector, 5);
+		client.send(Serverbound::PlayerLocation(location(1000.0)));
+		tick(&mut sector, 5);
+		client.send(Serverbound::CreateStructure(CreateStructure {
+			location: location(1000.0),
+			block: BlockType::Block,
+		}));
+		tick(&mut sector, 5);
+		client.send(Serverbound::PlayerLocation(location(1010.0)));
+		tick(&mut sector, 5);
+
+		let structure = sector.structures[0].id;
+		client.send(Serverbound::DeleteStructure(DeleteStructure { structure }));
+		tick(&mut sector, 10);
+
+		// Disconnecting finishes the recording
+		drop(client);
+		tick(&mut sector, 2);
+		assert!(sector.players.is_empty());
+
+		let mut files: Vec<_> = fs::read_dir(&directory.0)
+			.unwrap()
+			.map(|entry| entry.unwrap().path())
+			.collect();
+		files.sort();
+		files
+	}
+
+	#[test]
+	fn replays_match_their_recording() {
+		let directory = TempDir(env::temp_dir().join(format!("solarscape-replay-{}", Id::new())));
+		let player = Id::new();
+		let files = record(&directory, player);
+
+		let (_, frames) = recording::read(&files[0]).unwrap();
+		assert!(frames
+			.iter()
+			.any(|frame| matches!(frame, Frame::StructureCreated(_))));
+		assert!(frames.iter().any(|frame| matches!(
+			frame,
+			Frame::Message {
+				message: Serverbound::DeleteStructure(_),
+				..
+			}
+		)));
+		assert!(
+			frames
+				.iter()
+				.filter(|frame| matches!(frame, Frame::Checkpoint(_)))
+				.count() > 5
+		);
+
+		assert!(run(ReplayArgs { files }, config(&directory, player)).unwrap());
+	}
+
+	#[test]
+	fn replays_are_checked_against_the_recording() {
+		let directory = TempDir(env::temp_dir().join(format!("solarscape-replay-{}", Id::new())));
+		let player = Id::new();
+		let files = record(&directory, player);
+
+		// Recorded on another sector
+		let mut other_sector = config(&directory, player);
+		other_sector.name = "other".into();
+		assert!(matches!(
+			run(ReplayArgs { files: files.clone() }, other_sector),
+			Err(ReplayError::WrongSector(sector)) if &*sector == "test"
+		));
+
+		// Another player's recording can't carry on from this one
+		let other = record(&directory, Id::new())
+			.into_iter()
+			.find(|file| !files.contains(file))
+			.unwrap();
+		assert!(matches!(
+			run(ReplayArgs { files: vec![files[0].clone(), other.clone()] }, config(&directory, player)),
+			Err(ReplayError::DifferentRecording(path)) if path == other
+		));
+	}
+}
@@ -0,0 +1,319 @@
+use crate::sector::{Data, SharedSector, Voxject};
+use dashmap::DashMap;
+use log::{debug, error};
+use nalgebra::vector;
+use solarscape_shared::data::{
+	world::{ChunkCoordinates, Level, Material},
+	Id,
+};
+use sqlx::{query, query_scalar, PgPool};
+use std::{
+	collections::HashMap,
+	fmt::{self, Display, Formatter},
+	time::{Duration, Instant},
+};
+use tokio::sync::oneshot::Sender;
+
+/// Summary of a finished save, sent to whoever requested the save.
+#[derive(Clone, Copy, Debug)]
+pub struct SaveReport {
+	pub chunks: usize,
+	pub duration: Duration,
+}
+
+impl Display for SaveReport {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		write!(formatter, "{} chunks in {:.0?}", self.chunks, self.duration)
+	}
+}
+
+/// Makes sure only one save is written at a time. Saves requested while another is in progress are combined into a
+/// single save which starts once the current one finishes.
+#[derive(Default)]
+pub struct SaveQueue {
+	in_progress: Option<Vec<Sender<SaveReport>>>,
+	queued: Option<Vec<Sender<SaveReport>>>,
+}
+
+impl SaveQueue {
+	/// Returns `true` if a save should be started now, otherwise the save has been queued.
+	pub fn request(&mut self, reply: Option<Sender<SaveReport>>) -> bool {
+		match self.in_progress {
+			Some(_) => {
+				self.queued.get_or_insert_with(Vec::new).extend(reply);
+				false
+			}
+			None => {
+				self.in_progress = Some(reply.into_iter().collect());
+				true
+			}
+		}
+	}
+
+	/// Replies to everyone waiting on the current save, returns `true` if a queued save should be started now. If the
+	/// save failed then the replies are dropped instead.
+	pub fn finish(&mut self, report: Option<SaveReport>) -> bool {
+		for reply in self.in_progress.take().unwrap_or_default() {
+			if let Some(report) = report {
+				let _ = reply.send(report);
+			}
+		}
+
+		self.in_progress = self.queued.take();
+		self.in_progress.is_some()
+	}
+
+	pub fn is_in_progress(&self) -> bool {
+		self.in_progress.is_some()
+	}
+}
+
+/// A copy of a modified chunk's data, taken on the sector thread so that it can be written without holding any locks.
+pub struct ChunkSnapshot {
+	pub coordinates: ChunkCoordinates,
+	pub materials: Vec<u8>,
+	pub densities: Vec<f32>,
+}
+
+impl ChunkSnapshot {
+	pub fn new(coordinates: ChunkCoordinates, data: &Data) -> Self {
+		Self {
+			coordinates,
+			materials: data
+				.materials
+				.iter()
+				.map(|material| *material as u8)
+				.collect(),
+			densities: data.densities.to_vec(),
+		}
+	}
+}
+
+/// Writes a batch of chunk snapshots in a single transaction. Nothing is written if the sector isn't using a database.
+pub async fn write(
+	sector: &SharedSector,
+	chunks: Vec<ChunkSnapshot>,
+) -> Result<SaveReport, sqlx::Error> {
+	let start_time = Instant::now();
+
+	let Some(database) = sector.persistence.database() else {
+		return Ok(SaveReport {
+			chunks: 0,
+			duration: Instant::now() - start_time,
+		});
+	};
+
+	if !chunks.is_empty() {
+		let mut transaction = database.begin().await?;
+
+		let mut generations = Vec::with_capacity(chunks.len());
+
+		for chunk in &chunks {
+			let ChunkSnapshot {
+				coordinates,
+				materials,
+				densities,
+			} = chunk;
+
+			let generation = query_scalar!(
+				"
+					INSERT INTO chunks(sector, voxject, x, y, z, materials, densities)
+					VALUES ($1, $2, $3, $4, $5, $6, $7)
+					ON CONFLICT (sector, voxject, x, y, z) DO UPDATE SET
+						saved = NOW(),
+						generation = chunks.generation + 1,
+						materials = EXCLUDED.materials,
+						densities = EXCLUDED.densities
+					RETURNING generation
+				",
+				&*sector.name,
+				&*sector.voxjects[&coordinates.voxject].name,
+				coordinates.x,
+				coordinates.y,
+				coordinates.z,
+				materials,
+				densities
+			)
+			.fetch_one(&mut *transaction)
+			.await?;
+
+			generations.push(generation);
+		}
+
+		transaction.commit().await?;
+
+		for (chunk, generation) in chunks.iter().zip(generations) {
+			sector.saved_chunks.insert(chunk.coordinates, generation);
+		}
+	}
+
+	Ok(SaveReport {
+		chunks: chunks.len(),
+		duration: Instant::now() - start_time,
+	})
+}
+
+/// Finds which chunks have been saved for this sector and how many times, so that only those chunks need to be looked
+/// up when loading.
+pub async fn load_saved_chunks(
+	database: &PgPool,
+	sector: &str,
+	voxjects: &HashMap<Id, Voxject>,
+) -> Result<DashMap<ChunkCoordinates, i64>, sqlx::Error> {
+	let voxject_ids = voxjects
+		.values()
+		.map(|voxject| (&*voxject.name, voxject.id))
+		.collect::<HashMap<_, _>>();
+
+	let saved_chunks = DashMap::new();
+
+	for record in query!(
+		"SELECT voxject, x, y, z, generation FROM chunks WHERE sector = $1",
+		sector
+	)
+	.fetch_all(database)
+	.await?
+	{
+		// The voxject may have been removed from the config since, in which case the chunk is just ignored
+		let Some(voxject) = voxject_ids.get(&*record.voxject) else {
+			continue;
+		};
+
+		saved_chunks.insert(
+			ChunkCoordinates::new(
+				*voxject,
+				vector![record.x, record.y, record.z],
+				Level::new(0),
+			),
+			record.generation,
+		);
+	}
+
+	debug!("{} saved chunks", saved_chunks.len());
+
+	Ok(saved_chunks)
+}
+
+/// Loads a chunk's data if it has been saved, blocking until it's loaded. Returns [`None`] if the chunk should be
+/// generated instead.
+pub fn load_chunk(sector: &SharedSector, coordinates: &ChunkCoordinates) -> Option<Data> {
+	if !sector.saved_chunks.contains_key(coordinates) {
+		return None;
+	}
+
+	let database = sector.persistence.database()?;
+
+	let record = sector.runtime.block_on(
+		query!(
+			"SELECT materials, densities FROM chunks WHERE sector = $1 AND voxject = $2 AND x = $3 AND y = $4 AND z = $5",
+			&*sector.name,
+			&*sector.voxjects[&coordinates.voxject].name,
+			coordinates.x,
+			coordinates.y,
+			coordinates.z
+		)
+		.fetch_optional(database),
+	);
+
+	let record = match record {
+		Ok(record) => record?,
+		Err(error) => {
+			error!("Failed to load chunk {coordinates}, it will be generated instead: {error}");
+			return None;
+		}
+	};
+
+	let mut data = Data::default();
+
+	for (index, material) in record.materials.into_iter().enumerate() {
+		let Ok(material) = Material::try_from(material) else {
+			error!("Saved chunk {coordinates} contains an invalid material, it will be generated instead");
+			return None;
+		};
+
+		data.materials[index] = material;
+	}
+
+	data.densities.copy_from_slice(&record.densities);
+
+	Some(data)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use tokio::sync::oneshot::{self, error::TryRecvError, Receiver};
+
+	fn report(chunks: usize) -> SaveReport {
+		SaveReport {
+			chunks,
+			duration: Duration::ZERO,
+		}
+	}
+
+	fn reply() -> (Option<Sender<SaveReport>>, Receiver<SaveReport>) {
+		let (sender, receiver) = oneshot::channel();
+		(Some(sender), receiver)
+	}
+
+	#[test]
+	fn saves_requested_during_a_save_are_combined() {
+		let mut queue = SaveQueue::default();
+		let (first, mut first_receiver) = reply();
+		let (second, mut second_receiver) = reply();
+		let (third, mut third_receiver) = reply();
+
+		assert!(queue.request(first));
+		assert!(queue.is_in_progress());
+
+		assert!(!queue.request(second));
+		assert!(!queue.request(None));
+		assert!(!queue.request(third));
+
+		// Only the save that was in progress has finished
+		assert!(queue.finish(Some(report(1))));
+		assert_eq!(first_receiver.try_recv().unwrap().chunks, 1);
+		assert_eq!(second_receiver.try_recv().err(), Some(TryRecvError::Empty));
+
+		// The queued requests were all covered by a single save
+		assert!(!queue.finish(Some(report(2))));
+		assert_eq!(second_receiver.try_recv().unwrap().chunks, 2);
+		assert_eq!(third_receiver.try_recv().unwrap().chunks, 2);
+		assert!(!queue.is_in_progress());
+	}
+
+	#[test]
+	fn failed_saves_drop_their_replies() {
+		let mut queue = SaveQueue::default();
+		let (first, mut receiver) = reply();
+
+		assert!(queue.request(first));
+		assert!(!queue.finish(None));
+		assert_eq!(receiver.try_recv().err(), Some(TryRecvError::Closed));
+
+		// Unrequested saves, like autosaves, still take their turn
+		assert!(queue.request(None));
+		assert!(!queue.request(None));
+		assert!(queue.finish(Some(report(0))));
+		assert!(!queue.finish(Some(report(0))));
+	}
+
+	#[test]
+	fn snapshots_copy_the_chunk_data() {
+		let coordinates = ChunkCoordinates::new(Id::new(), vector![1, -2, 3], Level::new(0));
+		let mut data = Data::default();
+		data.set_voxel(vector![0, 0, 0], Material::Stone, 1.5)
+			.unwrap();
+
+		let snapshot = ChunkSnapshot::new(coordinates, &data);
+		data.set_voxel(vector![0, 0, 0], Material::Ground, -1.0)
+			.unwrap();
+
+		assert_eq!(snapshot.coordinates, coordinates);
+		assert_eq!(snapshot.materials.len(), data.materials.len());
+		assert_eq!(snapshot.materials[0], Material::Stone as u8);
+		assert_eq!(snapshot.materials[1], Material::Nothing as u8);
+		assert_eq!(snapshot.densities[0], 1.5);
+		assert_eq!(snapshot.densities[1], 0.0);
+	}
+}
@@ -0,0 +1,197 @@
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::data::Id;
+use std::{
+	cmp::Reverse,
+	collections::{BinaryHeap, HashMap},
+};
+
+/// Something the sector does on a particular tick, see [`Scheduler`].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Task {
+	/// Requests a save, as if by [`Event::Save`], and schedules the next one.
+	///
+	/// [`Event::Save`]: crate::sector::Event::Save
+	Autosave,
+	/// Explodes the structure's explosive blocks, along with the structure itself.
+	Detonate(Id),
+}
+
+/// Identifies a scheduled [`Task`], for cancelling it.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct TaskId(u64);
+
+/// Tasks waiting for the tick they're due on, so that anything which should happen after a number of ticks doesn't
+/// need it's own timer checked every tick.
+///
+/// Tasks are run in the order they're due, and in the order they were scheduled for tasks due on the same tick.
+/// Nothing is ever due before the next tick, so scheduling a task for the current tick or earlier runs it next tick.
+pub struct Scheduler {
+	queue: BinaryHeap<Reverse<(u64, TaskId)>>,
+	/// Every task that hasn't run or been cancelled yet. Cancelling only removes it from here, it's entry in the queue
+	/// is skipped once it comes up.
+	tasks: HashMap<TaskId, Task, FxBuildHasher>,
+	next_id: u64,
+	/// The tick most recently drained, see [`Scheduler::drain_due`].
+	tick: u64,
+}
+
+impl Scheduler {
+	pub fn new() -> Self {
+		Self {
+			queue: BinaryHeap::new(),
+			tasks: HashMap::with_hasher(FxBuildHasher),
+			next_id: 0,
+			tick: 0,
+		}
+	}
+
+	/// Runs `task` on `tick`, or next tick if that has already passed.
+	pub fn schedule_at(&mut self, tick: u64, task: Task) -> TaskId {
+		let id = TaskId(self.next_id);
+		self.next_id += 1;
+
+		self.queue
+			.push(Reverse((tick.max(self.tick.saturating_add(1)), id)));
+		self.tasks.insert(id, task);
+
+		id
+	}
+
+	/// Runs `task` `ticks` ticks after the current one, with `0` also meaning next tick.
+	pub fn schedule_in(&mut self, ticks: u64, task: Task) -> TaskId {
+		self.schedule_at(self.tick.saturating_add(ticks), task)
+	}
+
+	/// Stops `task` from running, returning it if it hadn't already run or been cancelled.
+	pub fn cancel(&mut self, task: TaskId) -> Option<Task> {
+		self.tasks.remove(&task)
+	}
+
+	/// Takes every task due on or before `tick`, which becomes the current tick. Tasks scheduled while handling them
+	/// are due on a later tick at the earliest, so handling tasks can't keep this tick from ending.
+	pub fn drain_due(&mut self, tick: u64) -> Vec<Task> {
+		self.tick = tick;
+
+		let mut due = vec![];
+
+		while let Some(Reverse((due_tick, id))) = self.queue.peek().copied() {
+			if due_tick > tick {
+				break;
+			}
+
+			self.queue.pop();
+
+			// Cancelled tasks are only forgotten here
+			if let Some(task) = self.tasks.remove(&id) {
+				due.push(task);
+			}
+		}
+
+		due
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn detonate(id: u64) -> Task {
+		Task::Detonate(id.to_string().parse().unwrap())
+	}
+
+	#[test]
+	fn tasks_run_in_due_order() {
+		let mut scheduler = Scheduler::new();
+
+		scheduler.schedule_at(30, detonate(3));
+		scheduler.schedule_at(10, detonate(1));
+		scheduler.schedule_at(20, detonate(2));
+
+		assert_eq!(scheduler.drain_due(9), vec![]);
+		assert_eq!(scheduler.drain_due(20), vec![detonate(1), detonate(2)]);
+		assert_eq!(scheduler.drain_due(29), vec![]);
+		assert_eq!(scheduler.drain_due(30), vec![detonate(3)]);
+	}
+
+	#[test]
+	fn tasks_due_on_the_same_tick_run_in_scheduled_order() {
+		let mut scheduler = Scheduler::new();
+
+		for id in 0..16 {
+			scheduler.schedule_at(5, detonate(id));
+		}
+
+		assert_eq!(
+			scheduler.drain_due(5),
+			(0..16).map(detonate).collect::<Vec<_>>()
+		);
+	}
+
+	#[test]
+	fn cancelled_tasks_are_skipped() {
+		let mut scheduler = Scheduler::new();
+
+		let first = scheduler.schedule_at(1, detonate(1));
+		let second = scheduler.schedule_at(1, detonate(2));
+		scheduler.schedule_at(1, Task::Autosave);
+
+		assert_eq!(scheduler.cancel(second), Some(detonate(2)));
+		assert_eq!(scheduler.cancel(second), None);
+
+		assert_eq!(scheduler.drain_due(1), vec![detonate(1), Task::Autosave]);
+
+		// Already ran, so there's nothing to cancel
+		assert_eq!(scheduler.cancel(first), None);
+		assert!(scheduler.tasks.is_empty());
+		assert!(scheduler.queue.is_empty());
+	}
+
+	#[test]
+	fn past_due_tasks_run_next_tick() {
+		let mut scheduler = Scheduler::new();
+		scheduler.drain_due(100);
+
+		scheduler.schedule_at(50, detonate(1));
+		scheduler.schedule_at(100, detonate(2));
+		scheduler.schedule_in(0, detonate(3));
+
+		assert_eq!(scheduler.drain_due(100), vec![]);
+		assert_eq!(
+			scheduler.drain_due(101),
+			vec![detonate(1), detonate(2), detonate(3)]
+		);
+	}
+
+	#[test]
+	fn tasks_scheduled_while_draining_wait_for_a_later_tick() {
+		let mut scheduler = Scheduler::new();
+		scheduler.schedule_at(10, Task::Autosave);
+
+		assert_eq!(scheduler.drain_due(10), vec![Task::Autosave]);
+
+		scheduler.schedule_in(0, Task::Autosave);
+		assert_eq!(scheduler.drain_due(10), vec![]);
+		assert_eq!(scheduler.drain_due(11), vec![Task::Autosave]);
+	}
+
+	#[test]
+	fn schedule_in_is_relative_to_the_current_tick() {
+		let mut scheduler = Scheduler::new();
+		scheduler.drain_due(40);
+
+		scheduler.schedule_in(5, Task::Autosave);
+
+		assert_eq!(scheduler.drain_due(44), vec![]);
+		assert_eq!(scheduler.drain_due(45), vec![Task::Autosave]);
+	}
+
+	#[test]
+	fn far_future_ticks_saturate() {
+		let mut scheduler = Scheduler::new();
+		scheduler.drain_due(u64::MAX - 1);
+
+		scheduler.schedule_in(u64::MAX, Task::Autosave);
+
+		assert_eq!(scheduler.drain_due(u64::MAX), vec![Task::Autosave]);
+	}
+}
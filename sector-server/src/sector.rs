@@ -1,91 +1,271 @@
 use crate::{
-	generation::{sphere_generator, Generator},
+	ambient::AmbientCycle,
+	audit::{AuditEvent, AuditLog},
+	chat::{self, Chat},
+	command::{self, CommandRegistry, Permission},
+	config,
+	explosion::Explosion,
+	gateway_stats::GatewayReport,
+	generation::{sphere_generator, sphere_generator_hash, Generator},
+	generation_cache::{self, GenerationCache},
+	generation_queue::GenerationQueue,
+	idle::{IdleTransition, AWAY_INTEREST_RADIUS},
+	interaction::{self, Containers, Effect, Interactions, REACH_SLACK},
+	interest::{Interest, InterestGrid},
+	inventory::Inventory,
+	lod::LodCurve,
+	memory::{Category, MemoryMonitor, Tracked, MEMORY},
+	persistence::Persistence,
 	player::Player,
+	recording::{self, Checkpoint, Recorder, RecordingError},
+	save::{self, ChunkSnapshot, SaveQueue, SaveReport},
+	scheduler::{Scheduler, Task, TaskId},
+	snapshot::{
+		self, RestoreReport, Snapshot, SnapshotChunk, SnapshotError, SnapshotQueue, SnapshotReport,
+	},
+	structure_index::StructureIndex,
+	structure_limits::StructureCounts,
+	structure_locks::{self, MAX_CHUNKS_PER_STRUCTURE},
+	sync_queue::SyncQueue,
+	terrain::{self, BrushCell},
+	terrain_journal::{self, CellValues, RollbackReport, TerrainJournal},
+	transfer::{self, TransferError},
 };
 use dashmap::DashMap;
-use log::{debug, warn};
-use nalgebra::{point, vector, Point3};
+use log::{debug, error, info, warn};
+use nalgebra::{point, vector, Isometry3, Point3, Vector3};
 use rapier3d::{
 	dynamics::{RigidBodyBuilder, RigidBodyHandle},
-	geometry::{ColliderBuilder, ColliderHandle},
+	geometry::{Ball, ColliderHandle},
 };
 use rustc_hash::FxBuildHasher;
 use solarscape_shared::{
+	clock::Clock,
 	connection::{Connection, ConnectionSend, ServerEnd},
+	consts::{
+		cell_index, sample_index, CELLS_PER_CHUNK, CHUNK_SAMPLE_SIZE, CHUNK_SIZE, REACH,
+		SAMPLES_PER_CHUNK,
+	},
 	data::{
-		world::{ChunkCoordinates, Material},
+		world::{
+			BlockType, CellCoordinates, ChunkCoordinates, Item, Level, Location, Material,
+			VoxjectPosition, UNBOUNDED_CHUNK_RADIUS,
+		},
 		Id,
 	},
 	message::{
-		clientbound::{Clientbound, SyncChunk, SyncInventory},
-		serverbound::Serverbound,
+		clientbound::{
+			ActionDenied, ChatRetract, ChunkUnchanged, Disconnect, DisconnectReason,
+			InventoryCapacity, JoinComplete, LocalizedText, Pong, RemoveStructure, SetBlockState,
+			Severity, SyncChunk, SyncContainer, SyncInventory, Teleport, Transfer, UiEvent,
+		},
+		serverbound::{
+			DeleteStructure, HaveChunks, InteractBlock, ModifyTerrainBrush, ModifyVoxel, Ping,
+			RequestChunk, SendChatMessage, Serverbound, SetAmbientPaused,
+		},
 	},
-	physics::{AutoCleanup, Physics},
+	physics::{AutoCleanup, ColliderOwner, CollisionGroup, Physics, QueryMask},
 	structure::Structure,
+	throttle::THROTTLE,
+	throttled_warn,
 	triangulation_table::{EdgeData, CELL_EDGE_MAP, CORNERS, EDGE_CORNER_MAP},
+	trimesh,
 };
-use sqlx::{query, PgPool};
 use std::{
-	collections::HashMap,
-	mem::drop as nom,
+	collections::{HashMap, HashSet},
+	mem::{drop as nom, size_of},
 	ops::Deref,
+	path::PathBuf,
 	sync::{
-		atomic::{AtomicUsize, Ordering::Relaxed},
-		Arc, Weak,
+		atomic::{
+			AtomicBool, AtomicU32, AtomicU64, AtomicUsize,
+			Ordering::{AcqRel, Acquire, Relaxed},
+		},
+		Arc, Mutex, Weak,
 	},
-	thread,
-	time::{Duration, Instant},
+	time::Duration,
 };
+use thiserror::Error;
 use tokio::{
 	runtime::Handle,
 	sync::{
 		mpsc::{
 			unbounded_channel as channel, UnboundedReceiver as Receiver, UnboundedSender as Sender,
 		},
-		Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard,
+		oneshot, RwLock, RwLockReadGuard, RwLockWriteGuard,
 	},
 };
 
-pub mod config {
-	use serde::Deserialize;
-
-	#[derive(Deserialize)]
-	pub struct Sector {
-		pub name: Box<str>,
-		pub voxjects: Vec<Voxject>,
-	}
-
-	#[derive(Deserialize)]
-	pub struct Voxject {
-		pub name: Box<str>,
-	}
-}
-
 pub struct Sector {
 	pub shared: Arc<SharedSector>,
 
 	events: Receiver<Event>,
 
-	players: Vec<Player>,
+	pub players: Vec<Player>,
 	ticking_chunks: HashMap<ChunkCoordinates, TickingChunk, FxBuildHasher>,
 	pub structures: Vec<Structure>,
+	/// Tick locks held for each structure, see [`Sector::update_structures`].
+	structure_locks: HashMap<Id, Vec<TickLock>, FxBuildHasher>,
+	structure_index: StructureIndex,
+	max_awake_structures: Option<usize>,
+	pub structure_limits: config::StructureLimits,
+	/// Always matches [`Sector::structures`], checked on every save.
+	structure_counts: StructureCounts,
+	/// Structures with explosive blocks, and the [`Task::Detonate`] that explodes them.
+	fuses: HashMap<Id, TaskId, FxBuildHasher>,
+	/// What each interactable block type does when used, see [`Sector::interact_block`].
+	interactions: Interactions,
+	containers: Containers,
+
+	/// Where every structure is, for finding the ones within [`Sector::interest_radius`] of each player.
+	pub interest_grid: InterestGrid,
+	/// See [`config::Sector::interest_radius`], infinite if every structure is sent to every player.
+	pub interest_radius: f32,
+	/// Structure syncs and removals not sent because the structure was out of the player's interest radius.
+	pub interest_suppressed: u64,
 
 	pub physics: Physics,
+	/// Ticks per second, see [`config::Sector::tick_rate`].
+	pub tick_rate: u32,
+	/// Ticks since the sector started.
+	pub ticks: u64,
+	scheduler: Scheduler,
+
+	/// Ticks between autosaves, [`None`] if autosaving is disabled.
+	autosave_interval: Option<u64>,
+	/// Chunks which have been modified since they were last saved, kept loaded until then.
+	dirty_chunks: HashMap<ChunkCoordinates, Arc<Chunk>, FxBuildHasher>,
+	save_queue: SaveQueue,
+	shutting_down: bool,
+
+	/// Where `/snapshot` writes to, [`None`] if snapshots are disabled.
+	pub snapshot_path: Option<PathBuf>,
+	snapshot_queue: SnapshotQueue,
+	/// Where players were when the snapshot this sector was restored from was taken, see [`Sector::restore`].
+	restored_locations: HashMap<Id, Location>,
+
+	pub recording: config::Recording,
+
+	/// See [`config::Sector::world_radius`], [`None`] if there's no border.
+	pub world_radius: Option<f32>,
+
+	pub inventory_capacity: InventoryCapacity,
+
+	pub ambient: AmbientCycle,
+	admins: HashSet<Id>,
+	pub commands: CommandRegistry,
+	pub chat: Chat,
+	pub audit: AuditLog,
+	pub terrain_journal: TerrainJournal,
+	transfers: config::Transfers,
+	afk: config::Afk,
+	/// Players disconnected for being away too long since the sector started, see [`config::Afk::kick_after`].
+	pub idle_kicks: u64,
+
+	/// See [`Event::GatewayStats`].
+	pub gateway_reports: Vec<GatewayReport>,
+
+	memory: MemoryMonitor,
 }
 
 impl Sector {
-	pub fn new(database: PgPool, config::Sector { name, voxjects }: config::Sector) -> Self {
+	pub fn new(
+		persistence: Persistence,
+		clock: Arc<dyn Clock>,
+		config::Sector {
+			name,
+			voxjects,
+			autosave_interval,
+			inventory,
+			ambient,
+			admins,
+			memory_thresholds,
+			max_awake_structures,
+			// Only needed when accepting connections, see main
+			connection: _,
+			structure_limits,
+			tick_rate,
+			snapshot_path,
+			recording,
+			world_radius,
+			max_level,
+			chat_history,
+			interest_radius,
+			max_players,
+			audit,
+			generation_cache,
+			terrain_journal,
+			max_generation_jobs,
+			transfers,
+			afk,
+			lod,
+		}: config::Sector,
+	) -> Result<Self, sqlx::Error> {
 		let (sender, events) = channel();
 
-		Self {
+		let runtime = Handle::current();
+
+		let voxjects: HashMap<_, _> = voxjects
+			.into_iter()
+			.map(|voxject| Voxject::new(voxject, &lod))
+			.collect();
+
+		for voxject in voxjects.values() {
+			info!(
+				"Players are sent about {} chunks of voxject {}",
+				voxject.lod.estimate_chunks(),
+				voxject.name
+			);
+		}
+
+		// Far enough that every chunk within the border is included, wherever the voxject is
+		let world_radius_chunks = match world_radius {
+			0.0 => UNBOUNDED_CHUNK_RADIUS,
+			radius => {
+				let farthest_voxject = voxjects
+					.values()
+					.map(|voxject| voxject.location.translation.vector.norm())
+					.fold(0.0, f32::max);
+
+				(((radius + farthest_voxject) / CHUNK_SIZE as f32).ceil() as i64)
+					.min(UNBOUNDED_CHUNK_RADIUS)
+			}
+		};
+
+		let saved_chunks = match persistence.database() {
+			Some(database) => {
+				runtime.block_on(save::load_saved_chunks(database, &name, &voxjects))?
+			}
+			None => DashMap::new(),
+		};
+
+		let chat = match persistence.database() {
+			Some(database) if chat_history > 0 => Chat::restore(
+				chat_history,
+				runtime.block_on(chat::load(database, &name, chat_history))?,
+			),
+			_ => Chat::new(chat_history),
+		};
+
+		Ok(Self {
 			shared: Arc::new(SharedSector {
 				name,
 
-				database,
+				persistence,
+				runtime,
 				sender,
+				clock,
 
-				voxjects: voxjects.into_iter().map(Voxject::new).collect(),
+				voxjects,
+				world_radius_chunks,
+				max_level: Level::new(max_level),
 				chunks: DashMap::new(),
+				saved_chunks,
+				generation_cache: GenerationCache::new(generation_cache).map(Arc::new),
+				generation_queue: Arc::new(GenerationQueue::new(max_generation_jobs)),
+
+				player_count: AtomicU32::new(0),
+				max_players,
 			}),
 
 			events,
@@ -93,44 +273,188 @@ impl Sector {
 			players: vec![],
 			ticking_chunks: HashMap::with_hasher(FxBuildHasher),
 			structures: vec![],
+			structure_locks: HashMap::with_hasher(FxBuildHasher),
+			structure_index: StructureIndex::default(),
+			max_awake_structures: match max_awake_structures {
+				0 => None,
+				max => Some(max),
+			},
+			structure_limits,
+			structure_counts: StructureCounts::default(),
+			fuses: HashMap::with_hasher(FxBuildHasher),
+			interactions: Interactions::new(),
+			containers: Containers::default(),
+
+			interest_grid: InterestGrid::default(),
+			interest_radius: match interest_radius {
+				0.0 => f32::INFINITY,
+				radius => radius,
+			},
+			interest_suppressed: 0,
 
 			physics: Physics::new(),
-		}
+			tick_rate,
+			ticks: 0,
+			scheduler: Scheduler::new(),
+
+			autosave_interval: match autosave_interval {
+				0 => None,
+				seconds => Some(seconds * tick_rate as u64),
+			},
+			dirty_chunks: HashMap::with_hasher(FxBuildHasher),
+			save_queue: SaveQueue::default(),
+			shutting_down: false,
+
+			snapshot_path,
+			snapshot_queue: SnapshotQueue::default(),
+			restored_locations: HashMap::new(),
+
+			recording,
+
+			world_radius: match world_radius {
+				0.0 => None,
+				radius => Some(radius),
+			},
+
+			inventory_capacity: inventory,
+
+			ambient: AmbientCycle::new(ambient),
+			admins: admins.into_iter().collect(),
+			commands: CommandRegistry::builtin(),
+			chat,
+			audit: AuditLog::new(audit),
+			terrain_journal: TerrainJournal::new(terrain_journal),
+			transfers,
+			afk,
+			idle_kicks: 0,
+
+			gateway_reports: vec![],
+
+			memory: MemoryMonitor::new(memory_thresholds),
+		})
 	}
 
+	/// Runs the sector until it is shut down with [`Event::Shutdown`].
 	pub fn run(mut self) {
-		let target_tick_time = Duration::from_secs(1) / 30;
-		let mut last_tick_start = Instant::now();
+		let target_tick_time = self.tick_duration();
+		let clock = self.clock.clone();
+		let mut last_tick_start = clock.now_instant();
+
+		if let Some(autosave_interval) = self.autosave_interval {
+			self.scheduler
+				.schedule_in(autosave_interval, Task::Autosave);
+		}
 
 		loop {
-			let tick_start = Instant::now();
+			let tick_start = clock.now_instant();
 			let delta = (tick_start - last_tick_start).as_secs_f32();
 			last_tick_start = tick_start;
 
 			self.tick(delta);
 
-			let tick_duration = Instant::now() - tick_start;
+			if self.shutting_down
+				&& !self.save_queue.is_in_progress()
+				&& !self.snapshot_queue.is_busy()
+			{
+				THROTTLE.flush();
+				info!("Shut down");
+				return;
+			}
+
+			let tick_duration = clock.now_instant() - tick_start;
 
 			match target_tick_time.checked_sub(tick_duration) {
-				Some(time_until_next_tick) => thread::sleep(time_until_next_tick),
+				Some(time_until_next_tick) => {
+					self.runtime.block_on(clock.sleep(time_until_next_tick))
+				}
 				None => {
-					warn!("Tick took {tick_duration:.0?}, exceeding {target_tick_time:.0?} target")
+					let (generating, parked) = self.shared.generation_queue.counts();
+
+					throttled_warn!(
+						"sector::tick_overrun",
+						Duration::from_secs(10),
+						duration_ms = tick_duration.as_secs_f64() * 1000.0,
+						target_ms = target_tick_time.as_secs_f64() * 1000.0,
+						generating_chunks = generating,
+						parked_chunks = parked;
+						"Tick took {tick_duration:.0?}, exceeding {target_tick_time:.0?} target"
+					)
 				}
 			}
 		}
 	}
 
-	fn tick(&mut self, delta: f32) {
+	/// How long each tick should take.
+	pub fn tick_duration(&self) -> Duration {
+		Duration::from_secs(1) / self.tick_rate
+	}
+
+	/// `delta` is the time since the last tick actually started, which is used for anything that should keep up with
+	/// real time. Physics always steps by [`Sector::tick_duration`] instead so that it's deterministic, meaning it runs
+	/// slow rather than becoming unstable when ticks overrun.
+	pub fn tick(&mut self, delta: f32) {
+		self.ticks += 1;
+
+		self.run_scheduled_tasks();
 		self.handle_events();
 		self.process_players();
-		self.physics.tick(delta);
+		self.physics.tick(self.tick_duration().as_secs_f32());
+		self.update_structures();
+		self.update_interest();
+		self.complete_joins();
+		self.record_checkpoints();
+
+		if self.ambient.advance(delta) {
+			self.send_ambient();
+		}
+
+		self.memory.tick(delta, &self.shared);
+		self.audit.tick(delta, &self.shared);
+
+		self.player_count.store(self.players.len() as u32, Relaxed);
 	}
 
 	fn handle_events(&mut self) {
 		while let Ok(event) = self.events.try_recv() {
 			match event {
-				Event::PlayerConnected(id, connection) => {
-					let player = Player::accept(self, id, connection);
+				Event::PlayerConnected(id, connection, arrival) => {
+					let players = self.players.len() as u32;
+
+					// Gateways shouldn't send anyone past the limit, but their view of the player count can be out of
+					// date, and the pre-shared key doesn't go through a gateway at all
+					if self.max_players != 0 && players >= self.max_players {
+						warn!(
+							player_id:% = id;
+							"Player {id} was turned away, the sector is full ({players}/{})",
+							self.max_players
+						);
+
+						// The message is still written once the connection is dropped
+						connection.send(Disconnect {
+							reason: DisconnectReason::SectorFull {
+								players,
+								capacity: self.max_players,
+							},
+						});
+
+						continue;
+					}
+
+					info!(player_id:% = id; "Player {id} connected");
+					let mut player = Player::accept(self, id, connection);
+					self.load_inventory(id);
+
+					// A player arriving from another sector goes where they were there, rather than where they were
+					// when this sector's snapshot was taken
+					let restored = self.restored_locations.remove(&id);
+
+					if let Some(location) = arrival.or(restored) {
+						player.location = location;
+						player.send(Teleport {
+							position: location.position,
+						});
+					}
+
 					self.players.push(player);
 				}
 				Event::TickLockChunk(coordinates) => {
@@ -141,560 +465,4450 @@ impl Sector {
 					self.ticking_chunks.remove(&coordinates);
 				}
 				Event::CreateStructure(structure) => {
-					for player in &self.players {
-						player.send(structure.build_sync(&self.physics))
-					}
-
 					debug!(
 						"Structure {:?} created at {:?}!",
 						structure.id,
 						structure.get_location(&self.physics).translation
 					);
 
-					self.structures.push(structure);
-				}
-			}
-		}
-	}
+					let (player, structure_id) = (structure.owner, structure.id);
+					self.add_structure(structure);
 
-	pub fn process_players(&mut self) {
-		self.players
-			.retain(|player| player.connection.is_connected());
+					self.audit.record(
+						player,
+						self.ticks,
+						AuditEvent::StructureCreated {
+							structure: structure_id,
+						},
+					);
+				}
+				Event::DeleteStructure { player, structure } => {
+					let Some(index) = self.structures.iter().position(|s| s.id == structure) else {
+						debug!("Player {player} tried to delete structure {structure}, which doesn't exist");
+						self.send_system_message(
+							player,
+							LocalizedText::new("server.structure_gone"),
+							Severity::Info,
+						);
+						continue;
+					};
 
-		for player in self.players.iter_mut() {
-			while let Ok(message) = player.try_recv() {
-				match message {
-					Serverbound::PlayerLocation(location) => {
-						// TODO: Check that this makes sense, we don't want players to just teleport :foxple:
-						player.location = location;
+					if self.structures[index].owner != player {
+						warn!("Player {player} tried to delete structure {structure}, which they don't own");
+						self.send_system_message(
+							player,
+							LocalizedText::new("server.not_structure_owner"),
+							Severity::Warning,
+						);
+						continue;
+					}
 
-						let (mut new_client_locks, mut new_tick_locks) =
-							player.compute_locks(&self.shared);
+					// Nothing is refunded, placing blocks doesn't cost any items yet
+					self.remove_structure(index);
+					self.audit.record(
+						player,
+						self.ticks,
+						AuditEvent::StructureDeleted { structure },
+					);
 
-						player
-							.client_locks
-							// Retain will remove any chunks that aren't in the new list, remove will remove any chunks
-							// from the new list that were in the old list
-							.retain(|lock| new_client_locks.remove(&lock.chunk.coordinates));
-
-						for coordinates in new_client_locks {
-							player.client_locks.push(ClientLock::new(
-								&self.shared,
-								coordinates,
-								player.connection.sender(),
-							));
-						}
+					debug!("Structure {structure} deleted by {player}");
+				}
+				Event::ModifyTerrain { player, brush } => self.modify_terrain(player, brush),
+				Event::ModifyVoxel { player, modify } => self.modify_voxel(player, modify),
+				Event::ChatMessage { player, text } => match text.strip_prefix('/') {
+					Some(command) => command::run(self, player, command),
+					None => {
+						info!(player_id:% = player; "<{player}> {text}");
+						self.send_chat_message(player, text);
+					}
+				},
+				Event::SetAmbientPaused { player, paused } => {
+					if !self.admins.contains(&player) {
+						warn!(
+							"Player {player} tried to pause the ambient cycle, but isn't an admin"
+						);
+						self.send_system_message(
+							player,
+							LocalizedText::new("server.ambient_admin_only"),
+							Severity::Warning,
+						);
+						continue;
+					}
 
-						// Same as before, though there probably isn't a performance gain to doing it here
-						player
-							.tick_locks
-							.retain(|lock| new_tick_locks.remove(&lock.0.coordinates));
+					if self.ambient.set_paused(paused) {
+						info!(
+							"Ambient cycle {} by {player}",
+							match paused {
+								true => "paused",
+								false => "resumed",
+							}
+						);
+						self.send_ambient();
+					}
+				}
+				Event::InteractBlock { player, interact } => self.interact_block(player, interact),
+				Event::InventoryLoaded { player, inventory } => {
+					// They may have already left
+					let Some(player) = self.players.iter_mut().find(|p| p.id == player) else {
+						continue;
+					};
 
-						for coordinates in new_tick_locks {
-							player
-								.tick_locks
-								.push(TickLock::new(&self.shared, coordinates));
+					match inventory {
+						// Something has changed it since, and already sent the newer one
+						Ok(_) if player.inventory_synced => {}
+						Ok(inventory) => {
+							player.inventory_synced = true;
+							player.send(inventory);
 						}
+						Err(error) => warn!(
+							player_id:% = player.id;
+							"Unable to load player {}'s inventory: {error}",
+							player.id
+						),
+					}
+				}
+				Event::Save { reply } => {
+					// Structures aren't saved yet, but this is a good time to make sure nothing has slipped past
+					if !self.structure_counts.reconcile(&self.structures) {
+						warn!("Structure counts had drifted from the structure list, they've been recounted");
 					}
-					Serverbound::GiveTestItem => {
-						// borrroooowwww checkkkeerrr
-						let database_pool = self.shared.database.clone();
 
-						// How not to handle database queries: execute them blocking on the main thread
-						Handle::current().block_on(async {
-							let mut transaction = database_pool
-								.begin()
-								.await
-								.expect("database is fucked, probably");
+					if self.save_queue.request(reply) {
+						self.start_save();
+					}
+				}
+				Event::SaveFinished(result) => {
+					let report = match result {
+						Ok(report) => {
+							let duration_ms = report.duration.as_secs_f64() * 1000.0;
+
+							match report.chunks {
+								0 => debug!(chunks = report.chunks, duration_ms; "Saved {report}"),
+								_ => info!(chunks = report.chunks, duration_ms; "Saved {report}"),
+							}
 
-							let item_id = Id::new();
+							Some(report)
+						}
+						Err(error) => {
+							error!("Failed to save: {error}");
+							None
+						}
+					};
+
+					if self.save_queue.finish(report) {
+						self.start_save();
+					}
 
-							query!(
-								"INSERT INTO items(id, item) VALUES ($1, 'TestOre')",
-								item_id as _
+					if self.snapshot_queue.start(self.save_queue.is_in_progress()) {
+						self.start_snapshot();
+					}
+				}
+				Event::SnapshotFinished(result) => {
+					let (message, severity) = match result {
+						Ok(report) => {
+							info!("Wrote snapshot of {report}");
+							(
+								LocalizedText::new("server.snapshot_written")
+									.with("report", report.to_string()),
+								Severity::Info,
 							)
-							.execute(&mut *transaction)
-							.await
-							.expect("what");
-
-							query!(
-								"INSERT INTO inventory_items(inventory_id, item_id) VALUES ($1, $2)",
-								player.id as _,
-								item_id as _
+						}
+						Err(error) => {
+							error!("Failed to write snapshot: {error}");
+							(
+								LocalizedText::new("server.snapshot_failed")
+									.with("error", error.to_string()),
+								Severity::Warning,
 							)
-							.execute(&mut *transaction)
-							.await
-							.unwrap();
-
-							transaction.commit().await.unwrap();
-						});
+						}
+					};
 
-						let inventory_list = Player::get_inventory(player.id, &database_pool);
+					for player in self.snapshot_queue.finish() {
+						self.send_system_message(player, message.clone(), severity);
+					}
 
-						player.send(SyncInventory(inventory_list));
+					if self.snapshot_queue.start(self.save_queue.is_in_progress()) {
+						self.start_snapshot();
 					}
-					Serverbound::CreateStructure(create_structure) => {
-						let structure = Structure::new(&mut self.physics, create_structure);
-						let _ = self.shared.sender.send(Event::CreateStructure(structure));
+				}
+				Event::GatewayStats(reports) => self.gateway_reports = reports,
+				Event::Shutdown => {
+					info!("Shutting down");
+					self.shutting_down = true;
+					self.audit.flush(&self.shared);
+
+					if self.save_queue.request(None) {
+						self.start_save();
 					}
 				}
 			}
 		}
 	}
-}
 
-/// [`Event`]s are sent to [`Sector`]s and are processed at the start of the next tick.
-pub enum Event {
-	PlayerConnected(Id, Connection<ServerEnd>),
-	TickLockChunk(ChunkCoordinates),
-	TickReleaseChunk(ChunkCoordinates),
-	CreateStructure(Structure),
-}
+	/// Snapshots everything that needs saving and writes it in the background, the result is sent back as
+	/// [`Event::SaveFinished`]. Only one save should be in progress at a time, see [`SaveQueue`].
+	fn start_save(&mut self) {
+		let chunks = self
+			.dirty_chunks
+			.drain()
+			.map(|(_, chunk)| chunk)
+			.collect::<Vec<_>>();
 
-/// A [`SharedSector`] allows accessing shared information about a [`Sector`], as well as sending events to be
-/// processed at the start of the next tick. It does not allow directly accessing the [`Sector`]'s internal state
-/// however.
-pub struct SharedSector {
-	pub name: Box<str>,
+		let snapshots = chunks
+			.iter()
+			.map(|chunk| ChunkSnapshot::new(chunk.coordinates, &chunk.read_data_immediately()))
+			.collect();
 
-	pub database: PgPool,
-	sender: Sender<Event>,
+		let sector = self.shared.clone();
+		self.runtime.spawn(async move {
+			let result = save::write(&sector, snapshots).await;
 
-	pub voxjects: HashMap<Id, Voxject>,
-	chunks: DashMap<ChunkCoordinates, Weak<Chunk>>,
-}
+			// The chunks are kept loaded until they've been written, otherwise they could be reloaded from an older save
+			nom(chunks);
 
-impl SharedSector {
-	/// Sends an event to the [`Sector`] to be processed at the start of the next tick. The event is returned if the
-	/// event could not be sent.
-	pub fn send(&self, event: Event) -> Result<(), Event> {
-		self.sender.send(event).map_err(|error| error.0)
+			let _ = sector.send(Event::SaveFinished(result));
+		});
 	}
 
-	pub fn get_chunk(self: &Arc<Self>, coordinates: ChunkCoordinates) -> Arc<Chunk> {
-		self.chunks
-			.get(&coordinates)
-			.as_deref()
-			.and_then(Weak::upgrade)
-			.unwrap_or_else(|| {
-				let chunk = Chunk::new(self, coordinates);
-				self.chunks.insert(coordinates, Arc::downgrade(&chunk));
-				chunk
-			})
-	}
-}
+	/// Queues a snapshot, telling `player` once it's been written. Returns `false` if snapshots are disabled.
+	pub fn request_snapshot(&mut self, player: Id) -> bool {
+		if self.snapshot_path.is_none() {
+			return false;
+		}
 
-impl Deref for Sector {
-	type Target = Arc<SharedSector>;
+		self.snapshot_queue.request(player);
 
-	fn deref(&self) -> &Self::Target {
-		&self.shared
+		if self.snapshot_queue.start(self.save_queue.is_in_progress()) {
+			self.start_snapshot();
+		}
+
+		true
 	}
-}
 
-pub struct Voxject {
-	pub id: Id,
-	pub name: Box<str>,
-	pub generator: Generator,
-}
+	/// Copies everything a snapshot needs and writes it in the background, the result is sent back as
+	/// [`Event::SnapshotFinished`]. Only one snapshot should be in progress at a time, see [`SnapshotQueue`].
+	fn start_snapshot(&mut self) {
+		let Some(path) = self.snapshot_path.clone() else {
+			return;
+		};
 
-impl Voxject {
-	pub fn new(config::Voxject { name }: config::Voxject) -> (Id, Self) {
-		let id = Id::new();
-		let voxject = Self {
-			id,
-			name,
-			generator: sphere_generator,
+		let chunks = self
+			.dirty_chunks
+			.values()
+			.map(|chunk| {
+				let coordinates = chunk.coordinates;
+
+				SnapshotChunk::new(
+					self.voxjects[&coordinates.voxject].name.clone(),
+					self.saved_chunks
+						.get(&coordinates)
+						.map_or(0, |generation| *generation),
+					ChunkSnapshot::new(coordinates, &chunk.read_data_immediately()),
+				)
+			})
+			.collect();
+
+		let snapshot = Snapshot {
+			sector: self.name.clone(),
+
+			chunks,
+			structures: self
+				.structures
+				.iter()
+				.map(|structure| structure.build_sync(&self.physics))
+				.collect(),
+			players: self
+				.players
+				.iter()
+				.map(|player| (player.id, player.location))
+				.collect(),
+
+			ambient_phase: self.ambient.phase(),
+			ambient_paused: self.ambient.is_paused(),
 		};
-		(id, voxject)
+
+		let sector = self.shared.clone();
+		self.runtime.spawn_blocking(move || {
+			let result = snapshot::write(&path, &snapshot);
+			let _ = sector.send(Event::SnapshotFinished(result));
+		});
 	}
-}
 
-#[non_exhaustive]
-pub struct Chunk {
-	pub sector: Weak<SharedSector>,
-	pub coordinates: ChunkCoordinates,
+	/// Loads a snapshot written by another process for this sector, before any players have connected. Chunks are
+	/// only restored if the database's copy hasn't been saved since the snapshot was taken, see
+	/// [`snapshot::snapshot_wins`]. Anything else the snapshot has replaces what the sector started with.
+	pub fn restore(&mut self, snapshot: Snapshot) -> Result<RestoreReport, SnapshotError> {
+		let Snapshot {
+			sector,
+			chunks,
+			structures,
+			players,
+			ambient_phase,
+			ambient_paused,
+		} = snapshot;
 
-	subscribed_clients: Mutex<Vec<Arc<ConnectionSend<ServerEnd>>>>,
+		if sector != self.name {
+			return Err(SnapshotError::WrongSector(sector));
+		}
 
-	// Multiple tick locks may exist, we need to avoid removing a chunk from the ticking list if its tick locked
-	// elsewhere.
-	tick_lock_count: AtomicUsize,
+		let voxject_ids = self
+			.voxjects
+			.values()
+			.map(|voxject| (&*voxject.name, voxject.id))
+			.collect::<HashMap<_, _>>();
 
-	data: RwLock<Option<Data>>,
-	collision: RwLock<Option<Collision>>,
-}
+		let mut report = RestoreReport::default();
+		let mut restored_chunks = vec![];
 
-pub type DataTryReadGuard<'a> = RwLockReadGuard<'a, Option<Data>>;
-pub type DataReadGuard<'a> = RwLockReadGuard<'a, Data>;
+		for chunk in chunks {
+			// The voxject may have been removed from the config since, in which case the chunk is just ignored
+			let Some(voxject) = voxject_ids.get(&*chunk.voxject) else {
+				warn!(
+					"Snapshot has a chunk of voxject {}, which doesn't exist",
+					chunk.voxject
+				);
+				report.skipped_chunks += 1;
+				continue;
+			};
 
-pub type CollisionReadGuard<'a> = RwLockReadGuard<'a, Collision>;
+			let coordinates = ChunkCoordinates::new(*voxject, chunk.position, Level::new(0));
+			let saved = self
+				.saved_chunks
+				.get(&coordinates)
+				.map(|generation| *generation);
 
-impl Chunk {
-	fn new(sector: &Arc<SharedSector>, coordinates: ChunkCoordinates) -> Arc<Self> {
-		let return_chunk = Arc::new(Self {
-			sector: Arc::downgrade(sector),
-			coordinates,
+			if !snapshot::snapshot_wins(chunk.generation, saved) {
+				debug!("Chunk {coordinates} has been saved since the snapshot was taken, keeping the saved copy");
+				report.skipped_chunks += 1;
+				continue;
+			}
 
-			subscribed_clients: Mutex::new(vec![]),
+			restored_chunks.push(ChunkSnapshot {
+				coordinates,
+				materials: chunk.materials,
+				densities: chunk.densities,
+			});
+		}
 
-			tick_lock_count: AtomicUsize::new(0),
+		// Written straight away so that chunks load the same way whether or not they came from a snapshot
+		report.chunks = restored_chunks.len();
+		self.runtime
+			.block_on(save::write(&self.shared, restored_chunks))?;
 
-			data: RwLock::default(),
-			collision: RwLock::default(),
-		});
+		for sync in structures {
+			let structure = Structure::new_from_sync(&mut self.physics, sync);
+			self.add_structure(structure);
+			report.structures += 1;
+		}
 
-		let chunk = return_chunk.clone();
-		rayon::spawn(move || {
-			// If try_unwrap returns Ok then nothing else wanted the chunk, so to avoid doing work that will be
-			// immediately discarded, we only generate the chunk if we cannot take exclusive ownership of it.
-			if let Err(chunk) = Arc::try_unwrap(chunk) {
-				let data = chunk.data.blocking_write();
-				let _ = chunk.generate_data(data);
-			}
-		});
+		report.players = players.len();
+		self.restored_locations = players.into_iter().collect();
 
-		return_chunk
+		self.ambient.restore(ambient_phase, ambient_paused);
+
+		Ok(report)
 	}
 
-	fn generate_data<'a>(
-		&'a self,
-		mut data: RwLockWriteGuard<'a, Option<Data>>,
-	) -> RwLockReadGuard<'a, Option<Data>> {
-		// Another thread may synchronously generate chunks instead of waiting if the chunk is needed immediately. So
-		// if that has happened, don't re-generate the chunk.
-		if data.is_some() {
-			return data.downgrade();
-		}
+	/// Adds a newly created or restored structure to the sector, and sends it to every player within the interest
+	/// radius.
+	fn add_structure(&mut self, structure: Structure) {
+		let position = structure
+			.get_location(&self.physics)
+			.translation
+			.vector
+			.into();
+		self.interest_grid.update(structure.id, position);
 
-		let generator = self
-			.sector
-			.upgrade()
-			.expect("Chunk should not be used after Sector has been dropped")
-			.voxjects[&self.coordinates.voxject]
-			.generator;
+		for player in &mut self.players {
+			let radius = match player.idle.is_away() {
+				true => AWAY_INTEREST_RADIUS.min(self.interest_radius),
+				false => self.interest_radius,
+			};
 
-		*data = Some(generator(&self.coordinates));
+			// Players that haven't sent their location yet get it from update_interest once they have
+			if !player.located || (player.location.position - position).norm() > radius {
+				self.interest_suppressed += 1;
+				continue;
+			}
 
-		let data = data.downgrade();
+			player.send(structure.build_sync(&self.physics));
+			player.interest.insert(structure.id);
+		}
 
-		let message = Clientbound::SyncChunk(SyncChunk {
-			coordinates: self.coordinates,
-			materials: data.as_ref().unwrap().materials.clone(),
-			densities: data.as_ref().unwrap().densities.clone(),
-		});
+		// Anything the new structure was placed on or next to should react to it
+		if let Some(chunks) = structure_locks::overlapped_chunks(
+			self.shared.voxjects.values(),
+			&structure.aabb(&self.physics),
+		) {
+			self.wake_structures_in(&chunks);
+			self.structure_index.update(structure.id, chunks);
+		}
 
-		self.subscribed_clients
-			.blocking_lock()
-			.iter()
-			.for_each(|connection| connection.send(message.clone()));
+		if structure
+			.iter_blocks()
+			.any(|(_, block)| block.typ == BlockType::Explosive)
+		{
+			let fuse = Explosion::FUSE.as_secs_f32() * self.tick_rate as f32;
+			let task = self
+				.scheduler
+				.schedule_in(fuse.round() as u64, Task::Detonate(structure.id));
+			self.fuses.insert(structure.id, task);
+		}
 
-		data
+		MEMORY.track(Category::Structures, structure.heap_size());
+		self.structure_counts.add(structure.owner);
+		self.structures.push(structure);
 	}
 
-	fn generate_collision<'a>(
-		self: &'a Arc<Self>,
-		mut collision: RwLockWriteGuard<'a, Option<Collision>>,
-	) -> RwLockReadGuard<'a, Option<Collision>> {
-		if collision.is_some() {
-			return collision.downgrade();
+	/// Removes the structure at `index` in [`Sector::structures`], and tells every player that has it that it's gone.
+	fn remove_structure(&mut self, index: usize) {
+		// Dropping the structure takes care of it's rigid body and colliders
+		let removed = self.structures.swap_remove(index);
+		let structure = removed.id;
+		MEMORY.untrack(Category::Structures, removed.heap_size());
+		self.structure_counts.remove(removed.owner);
+		nom(removed);
+		self.structure_locks.remove(&structure);
+
+		if let Some(task) = self.fuses.remove(&structure) {
+			self.scheduler.cancel(task);
 		}
 
-		let sector = self
-			.sector
-			.upgrade()
-			.expect("Chunk should not be used after Sector has been dropped");
+		self.containers.remove_structure(structure);
 
-		let chunks = [
-			self.clone(),
-			sector.get_chunk(self.coordinates + vector![0, 0, 1]),
-			sector.get_chunk(self.coordinates + vector![0, 1, 0]),
-			sector.get_chunk(self.coordinates + vector![0, 1, 1]),
-			sector.get_chunk(self.coordinates + vector![1, 0, 0]),
-			sector.get_chunk(self.coordinates + vector![1, 0, 1]),
-			sector.get_chunk(self.coordinates + vector![1, 1, 0]),
-			sector.get_chunk(self.coordinates + vector![1, 1, 1]),
-		];
+		// Structures resting on the removed one need to fall
+		if let Some(chunks) = self.structure_index.chunks_of(structure).cloned() {
+			self.structure_index.remove(structure);
+			self.wake_structures_in(&chunks);
+		}
 
-		let chunk_data_guards = chunks.each_ref().map(|chunk| chunk.read_data_immediately());
+		self.interest_grid.remove(structure);
 
-		let mut densities = [0f32; usize::pow(17, 3)];
-		let mut materials = [Material::Nothing; usize::pow(17, 3)];
+		for player in &mut self.players {
+			match player.interest.remove(structure) {
+				true => player.send(RemoveStructure(structure)),
+				false => self.interest_suppressed += 1,
+			}
+		}
+	}
 
-		for x in 0..17 {
-			for y in 0..17 {
-				for z in 0..17 {
-					let chunk_index = ((x & 0x10) >> 2) | ((y & 0x10) >> 3) | ((z & 0x10) >> 4);
-					let cell_index = (x * 17 * 17) + (y * 17) + z;
-					let chunk_cell_index = (x & 0x0F) << 8 | (y & 0x0F) << 4 | z & 0x0F;
+	/// Damages a block, destroying it once it has no health left, and removing the structure once it has no blocks
+	/// left. Returns [`None`] if there's no such block, otherwise whether it was destroyed. Structures that still exist
+	/// need sending to players again afterwards, see [`Sector::resync_structures`], which is left to the caller so that
+	/// several blocks can be damaged before sending the structure once.
+	pub fn apply_damage(
+		&mut self,
+		structure: Id,
+		position: &Vector3<i16>,
+		amount: f32,
+	) -> Option<bool> {
+		let index = self.structures.iter().position(|s| s.id == structure)?;
 
-					densities[cell_index] =
-						chunk_data_guards[chunk_index].densities[chunk_cell_index];
-					materials[cell_index] =
-						chunk_data_guards[chunk_index].materials[chunk_cell_index];
-				}
-			}
+		if !self.structures[index].damage_block(position, amount)? {
+			return Some(false);
 		}
 
-		let mut new_collision = Collision::default();
+		self.containers.remove(structure, position);
 
-		for x in 0..16 {
-			for y in 0..16 {
-				for z in 0..16 {
-					let indexes = [
-						(x, y, z + 1),
-						(x + 1, y, z + 1),
-						(x + 1, y, z),
-						(x, y, z),
-						(x, y + 1, z + 1),
-						(x + 1, y + 1, z + 1),
-						(x + 1, y + 1, z),
-						(x, y + 1, z),
-					]
-					.map(|(x, y, z)| (x * 289) + (y * 17) + z);
+		if self.structures[index].num_blocks() == 0 {
+			self.remove_structure(index);
+			debug!("Structure {structure} destroyed");
+			return Some(true);
+		}
 
-					let densities = indexes.map(|index| densities[index]);
-					let materials = indexes.map(|index| materials[index]);
+		// The structure, and anything resting on it, may no longer be supported
+		if let Some(rigid_body) = self
+			.physics
+			.get_rigid_body_mut(*self.structures[index].rigid_body)
+		{
+			rigid_body.wake_up(true);
+		}
 
-					#[allow(clippy::identity_op)]
-							#[rustfmt::skip]
-							let case_index = (!matches!(materials[0], Material::Nothing) as usize) << 0
-								| (!matches!(materials[1], Material::Nothing) as usize) << 1
-								| (!matches!(materials[2], Material::Nothing) as usize) << 2
-								| (!matches!(materials[3], Material::Nothing) as usize) << 3
-								| (!matches!(materials[4], Material::Nothing) as usize) << 4
-								| (!matches!(materials[5], Material::Nothing) as usize) << 5
-								| (!matches!(materials[6], Material::Nothing) as usize) << 6
-								| (!matches!(materials[7], Material::Nothing) as usize) << 7;
+		if let Some(chunks) = self.structure_index.chunks_of(structure).cloned() {
+			self.wake_structures_in(&chunks);
+		}
 
-					let EdgeData {
-						count,
-						edge_indices,
-					} = CELL_EDGE_MAP[case_index];
+		Some(true)
+	}
 
-					for edge_indices in edge_indices.chunks(3).take(count as usize) {
-						let vertices = edge_indices
-							.iter()
-							.map(|edge_index| {
-								let (a_index, b_index) = EDGE_CORNER_MAP[*edge_index as usize];
+	/// Sends each of `structures` that still exist to every player that has them again, replacing their copy.
+	fn resync_structures(&mut self, structures: &[Id]) {
+		for structure in &self.structures {
+			if !structures.contains(&structure.id) {
+				continue;
+			}
 
-								let a_density = densities[a_index];
-								let b_density = densities[b_index];
+			let sync = structure.build_sync(&self.physics);
 
-								let weight = if a_density == b_density {
-									0.5
-								} else {
-									(0.0 - a_density) / (b_density - a_density)
-								};
+			for player in &self.players {
+				match player.interest.contains(structure.id) {
+					true => player.send(sync.clone()),
+					false => self.interest_suppressed += 1,
+				}
+			}
+		}
+	}
 
-								let a = CORNERS[a_index];
-								let b = CORNERS[b_index];
+	/// Moves awake structures in the interest grid, then sends each player the structures that have come within their
+	/// interest radius, nearest first and no more than [`Interest::budget`] a tick, and removes those that have left it.
+	fn update_interest(&mut self) {
+		for structure in &self.structures {
+			let Some(rigid_body) = self.physics.get_rigid_body(*structure.rigid_body) else {
+				continue;
+			};
 
-								let vertex = a + weight * (b - a);
+			if rigid_body.is_dynamic() && !rigid_body.is_sleeping() {
+				self.interest_grid
+					.update(structure.id, (*rigid_body.translation()).into());
+			}
+		}
 
-								point![x as f32, y as f32, z as f32] + vertex
-							})
-							.collect::<Vec<_>>();
+		for player in &mut self.players {
+			// Otherwise whatever is around the origin would be sent first, only to be removed again
+			if !player.located {
+				continue;
+			}
 
-						new_collision.vertices.extend_from_slice(&vertices);
-					}
+			// Away players are only sent what's right around them, the rest is sent again once they're back
+			let radius = match player.idle.is_away() {
+				true => AWAY_INTEREST_RADIUS.min(self.interest_radius),
+				false => self.interest_radius,
+			};
+
+			let in_range = self.interest_grid.query(&player.location.position, radius);
+			let mut changes = player.interest.update(in_range);
+
+			let budget = Interest::budget(self.tick_rate);
+			player.structure_backlog = changes.entered.len().saturating_sub(budget);
+
+			if player.structure_backlog > 0 {
+				let distance = |structure| {
+					self.interest_grid
+						.position(structure)
+						.map_or(f32::INFINITY, |position| {
+							(position - player.location.position).norm_squared()
+						})
+				};
+
+				changes
+					.entered
+					.sort_by(|a, b| distance(*a).total_cmp(&distance(*b)));
+
+				// Forgotten again, so that they're entered on a later tick instead
+				for structure in changes.entered.drain(budget..) {
+					player.interest.remove(structure);
 				}
 			}
-		}
 
-		new_collision.indices = (0..new_collision.vertices.len() as u32)
-			.collect::<Vec<_>>()
-			.chunks_exact(3)
-			.map(|chunk| [chunk[0], chunk[1], chunk[2]])
-			.collect();
+			for structure in changes.entered {
+				if let Some(structure) = self.structures.iter().find(|s| s.id == structure) {
+					player.send(structure.build_sync(&self.physics));
+				}
+			}
 
-		*collision = Some(new_collision);
-		return collision.downgrade();
+			for structure in changes.left {
+				player.send(RemoveStructure(structure));
+			}
+		}
 	}
 
-	pub fn read_data_immediately(&self) -> DataReadGuard {
-		{
-			let data = self.data.blocking_read();
+	/// Sends [`JoinComplete`] to joining players once they've been sent every level 0 chunk and structure around them.
+	fn complete_joins(&mut self) {
+		let level_0 = Level::new(0);
 
-			if data.is_some() {
-				return RwLockReadGuard::map(data, |v| v.as_ref().unwrap());
+		for player in &mut self.players {
+			if !player.joining || !player.located || player.structure_backlog > 0 {
+				continue;
 			}
-		}
 
-		let data = self.generate_data(self.data.blocking_write());
-		RwLockReadGuard::map(data, |v| v.as_ref().unwrap())
+			let chunks_pending = player
+				.sync_queue
+				.pending()
+				.any(|coordinates| coordinates.level == level_0)
+				|| player
+					.client_locks
+					.iter()
+					.any(|lock| lock.coordinates().level == level_0 && !lock.is_synced());
+
+			if chunks_pending {
+				continue;
+			}
+
+			player.joining = false;
+			player.send(JoinComplete);
+			debug!(player_id:% = player.id; "Player {} has finished joining", player.id);
+		}
 	}
 
-	pub fn read_collision_immediately<'a>(self: &'a Arc<Chunk>) -> CollisionReadGuard<'a> {
-		{
-			let collision = self.collision.blocking_read();
+	/// Runs every [`Task`] due this tick, before anything else happens in it.
+	fn run_scheduled_tasks(&mut self) {
+		for task in self.scheduler.drain_due(self.ticks) {
+			match task {
+				Task::Autosave => {
+					// Handled with the rest of this tick's events, the same as any other save request
+					let _ = self.shared.send(Event::Save { reply: None });
 
-			if collision.is_some() {
-				return RwLockReadGuard::map(collision, |v| v.as_ref().unwrap());
+					if let Some(autosave_interval) = self.autosave_interval {
+						self.scheduler
+							.schedule_in(autosave_interval, Task::Autosave);
+					}
+				}
+				Task::Detonate(structure) => self.detonate(structure),
 			}
 		}
-
-		let collision = self.generate_collision(self.collision.blocking_write());
-		RwLockReadGuard::map(collision, |v| v.as_ref().unwrap())
 	}
 
-	pub fn try_read_data(&self) -> DataTryReadGuard {
-		self.data.blocking_read()
+	/// Explodes a structure whose fuse has run out, along with the structure itself.
+	fn detonate(&mut self, structure: Id) {
+		self.fuses.remove(&structure);
+
+		let Some(index) = self.structures.iter().position(|s| s.id == structure) else {
+			return;
+		};
+
+		let location = *self.structures[index].get_location(&self.physics);
+		let centers = self.structures[index]
+			.iter_blocks()
+			.filter(|(_, block)| block.typ == BlockType::Explosive)
+			.map(|(position, _)| location.transform_point(&position.cast().into()))
+			.collect::<Vec<_>>();
+
+		self.remove_structure(index);
+
+		for center in centers {
+			self.explode(Explosion::EXPLOSIVE_BLOCK, center);
+		}
+
+		debug!("Structure {structure} exploded");
 	}
 
-	pub fn trigger_collision_mesh_rebuild(self: Arc<Self>) {
-		rayon::spawn(move || {
-			// If try_unwrap returns Ok then nothing else wanted the chunk, so to avoid doing work that will be
-			// immediately discarded, we only generate the chunk's collision mesh if we cannot take exclusive ownership
-			// of it.
-			if let Err(chunk) = Arc::try_unwrap(self) {
-				let collision = chunk.collision.blocking_write();
-				let _ = chunk.generate_collision(collision);
+	/// Damages blocks, erodes terrain, and pushes structures within `explosion`'s radius of `center`.
+	fn explode(&mut self, explosion: Explosion, center: Point3<f32>) {
+		// Colliders of structures destroyed earlier this tick are still in the query pipeline, but their owners are
+		// gone so they're skipped
+		self.physics.update_queries();
+
+		let hits = self.physics.intersections_with_shape(
+			&Isometry3::translation(center.x, center.y, center.z),
+			&Ball::new(explosion.radius),
+			QueryMask::new(&[CollisionGroup::Terrain, CollisionGroup::Structure]),
+		);
+
+		let mut voxjects = HashSet::with_hasher(FxBuildHasher);
+		let mut damaged = vec![];
+
+		for hit in hits {
+			match hit.owner {
+				Some(ColliderOwner::Chunk(coordinates)) => {
+					voxjects.insert(coordinates.voxject);
+				}
+				Some(ColliderOwner::StructureBlock(structure, position)) => {
+					let Some(block_center) =
+						self.structures.iter().find(|s| s.id == structure).map(|s| {
+							s.get_location(&self.physics)
+								.transform_point(&position.cast().into())
+						})
+					else {
+						continue;
+					};
+
+					let damage = explosion.damage_at((block_center - center).norm());
+
+					if self.apply_damage(structure, &position, damage).is_some()
+						&& !damaged.contains(&structure)
+					{
+						damaged.push(structure);
+					}
+				}
+				_ => {}
 			}
-		});
-	}
-}
+		}
 
-impl Drop for Chunk {
-	fn drop(&mut self) {
-		if let Some(sector) = Weak::upgrade(&self.sector) {
-			sector.chunks.remove(&self.coordinates);
+		for structure in &self.structures {
+			if !damaged.contains(&structure.id) {
+				continue;
+			}
+
+			if let Some(rigid_body) = self.physics.get_rigid_body_mut(*structure.rigid_body) {
+				let impulse =
+					explosion.impulse_at(rigid_body.center_of_mass().coords - center.coords);
+				rigid_body.apply_impulse(impulse, true);
+			}
+		}
+
+		self.resync_structures(&damaged);
+
+		for voxject in voxjects {
+			let location = self.voxject_location(voxject);
+			let center = VoxjectPosition::new(voxject, location.inverse_transform_point(&center));
+
+			self.modify_cells(center, explosion.radius, |_, data, cells| {
+				terrain::erode(data, cells, |distance| explosion.erosion_at(distance))
+			});
 		}
 	}
-}
 
-/// A wrapper around [`Chunk`] that stores extra information needed to allow the chunk to tick, and should not be
-/// accessible outside of the sector thread.
-struct TickingChunk {
-	inner: Arc<Chunk>,
-	_rigid_body: AutoCleanup<RigidBodyHandle>,
-	_collider: Option<AutoCleanup<ColliderHandle>>,
-}
+	fn modify_terrain(&mut self, player: Id, brush: ModifyTerrainBrush) {
+		// They may have disconnected since
+		let Some(eye) = self
+			.players
+			.iter()
+			.find(|p| p.id == player)
+			.map(|player| player.location.position)
+		else {
+			return;
+		};
 
-impl TickingChunk {
-	fn register(sector: &mut Sector, chunk: Arc<Chunk>) {
-		let rigid_body = sector.physics.insert_rigid_body(
-			RigidBodyBuilder::fixed().translation(chunk.coordinates.voxject_relative_translation()),
+		let Some(voxject) = self.voxjects.get(&brush.center.voxject) else {
+			debug!(
+				"Player {player} tried to modify voxject {}, which doesn't exist",
+				brush.center.voxject
+			);
+			return;
+		};
+
+		let radius = brush.radius.clamp(
+			ModifyTerrainBrush::MIN_RADIUS,
+			ModifyTerrainBrush::MAX_RADIUS,
 		);
 
-		let collider = {
-			let collision = chunk.read_collision_immediately();
+		// clamp passes NaN through, and we don't want to try and iterate over an infinite sphere either
+		if !radius.is_finite()
+			|| !brush
+				.center
+				.position
+				.iter()
+				.all(|coordinate| coordinate.is_finite())
+		{
+			throttled_warn!(
+				"sector::non_finite_brush",
+				Duration::from_secs(10),
+				player_id:% = player;
+				"Player {player} sent a terrain brush with a non-finite center or radius"
+			);
+			return;
+		}
 
-			match collision.vertices.is_empty() {
-				true => None,
-				false => Some(sector.physics.insert_rigid_body_collider(
-					// It hurts to have to call clone here.
-					*rigid_body,
-					ColliderBuilder::trimesh(collision.vertices.clone(), collision.indices.clone()),
-				)),
+		// Checked up front so a bad request doesn't load or generate chunks for nothing
+		if !brush
+			.center
+			.chunk(Level::new(0))
+			.is_sane(self.shared.world_radius_chunks, self.shared.max_level)
+		{
+			throttled_warn!(
+				"sector::invalid_brush",
+				Duration::from_secs(10),
+				player_id:% = player;
+				"Player {player} sent a terrain brush centered at {}, outside of the world",
+				brush.center.position
+			);
+			return;
+		}
+
+		// Only the near edge of the brush has to be within reach, the client keeps a gap between it and the player
+		let center = brush.center.to_world(&voxject.location);
+		let distance = (center.0 - eye).norm() - radius;
+
+		if distance > REACH + REACH_SLACK {
+			throttled_warn!(
+				"sector::brush_out_of_reach",
+				Duration::from_secs(10),
+				player_id:% = player;
+				"Player {player} tried to modify terrain {distance:.1}m away, further than {REACH}m"
+			);
+			return;
+		}
+
+		let journaled = self.terrain_journal.is_enabled();
+		let mut changes = vec![];
+
+		self.modify_cells(brush.center, radius, |coordinates, data, cells| {
+			let before = journaled.then(|| CellValues::copy(data));
+
+			terrain::apply_brush(data, cells, radius, brush.material, brush.mode);
+
+			if let Some(before) = before {
+				changes.push((coordinates, before, CellValues::copy(data)));
 			}
+		});
+
+		let now = self.clock.now_instant();
+		self.terrain_journal.record(player, now, changes);
+
+		if self.audit.covers_brush(radius) {
+			self.audit.record(
+				player,
+				self.ticks,
+				AuditEvent::TerrainModified {
+					center: brush.center,
+					radius,
+					mode: brush.mode,
+				},
+			);
+		}
+
+		debug!(
+			"Player {player} modified terrain at {} ({:?})",
+			brush.center, brush.mode
+		);
+	}
+
+	/// Sets the cell `player` asked for, if it's one they can reach. Players that already have the chunk are sent it
+	/// again, and they rebuild the meshes of the chunks that sample it themselves, the collision of those chunks is
+	/// rebuilt here by [`Sector::modify_chunks`].
+	fn modify_voxel(
+		&mut self,
+		player: Id,
+		ModifyVoxel {
+			coordinates,
+			cell,
+			material,
+			density,
+		}: ModifyVoxel,
+	) {
+		// They may have disconnected since
+		let Some(eye) = self
+			.players
+			.iter()
+			.find(|p| p.id == player)
+			.map(|player| player.location.position)
+		else {
+			return;
 		};
 
-		let ticking_chunk = Self {
-			inner: chunk,
-			_rigid_body: rigid_body,
-			_collider: collider,
+		let Some(voxject) = self.voxjects.get(&coordinates.voxject) else {
+			debug!(
+				"Player {player} tried to modify voxject {}, which doesn't exist",
+				coordinates.voxject
+			);
+			return;
 		};
 
-		sector
-			.ticking_chunks
-			.insert(ticking_chunk.coordinates, ticking_chunk);
-	}
-}
+		// Higher levels are only ever generated. Checked up front so a bad request doesn't load the chunk for nothing
+		if coordinates.level != Level::new(0)
+			|| !coordinates.is_sane(self.shared.world_radius_chunks, self.shared.max_level)
+			|| Data::index(cell).is_err()
+			|| !density.is_finite()
+			|| density.abs() > ModifyVoxel::MAX_DENSITY
+		{
+			throttled_warn!(
+				"sector::invalid_voxel",
+				Duration::from_secs(10),
+				player_id:% = player;
+				"Player {player} tried to set cell {}, {}, {} of chunk {coordinates} to a density of {density}, which isn't allowed",
+				cell.x, cell.y, cell.z
+			);
+			return;
+		}
 
-impl Deref for TickingChunk {
-	type Target = Arc<Chunk>;
+		let position = CellCoordinates {
+			chunk: coordinates,
+			cell,
+		}
+		.voxject_cell()
+		.cast::<f32>();
+		let position =
+			VoxjectPosition::new(coordinates.voxject, position.into()).to_world(&voxject.location);
+		let distance = (position.0 - eye).norm();
 
-	fn deref(&self) -> &Self::Target {
-		&self.inner
-	}
-}
+		if distance > REACH + REACH_SLACK {
+			throttled_warn!(
+				"sector::voxel_out_of_reach",
+				Duration::from_secs(10),
+				player_id:% = player;
+				"Player {player} tried to modify a cell {distance:.1}m away, further than {REACH}m"
+			);
+			return;
+		}
 
-#[non_exhaustive]
-pub struct Data {
-	pub materials: Box<[Material; 4096]>,
-	pub densities: Box<[f32; 4096]>,
-}
+		let mut before = None;
 
-impl Default for Data {
-	fn default() -> Self {
-		Self {
-			materials: Box::new([Material::Nothing; 4096]),
-			densities: Box::new([0.0; 4096]),
+		self.modify_chunks([(coordinates, ())], |_, data, ()| {
+			before = data.material_at(cell).zip(data.density_at(cell));
+
+			// Already checked to be within the chunk
+			let _ = data.set_voxel(cell, material, density);
+		});
+
+		if let (Some(before), Ok(index)) = (before, Data::index(cell)) {
+			let now = self.clock.now_instant();
+			self.terrain_journal.record_cell(
+				player,
+				now,
+				coordinates,
+				index,
+				before,
+				(material, density),
+			);
 		}
+
+		debug!("Player {player} set a cell of chunk {coordinates} to {material:?}");
 	}
-}
 
-#[derive(Default)]
-#[non_exhaustive]
-pub struct Collision {
-	pub vertices: Vec<Point3<f32>>,
-	pub indices: Vec<[u32; 3]>,
-}
+	/// Undoes `player`'s terrain brush strokes from the last `duration`, newest first, as far as the journal still has
+	/// them. Cells changed again since, by anyone, are left as they are.
+	pub fn rollback(&mut self, player: Id, duration: Duration) -> RollbackReport {
+		let now = self.clock.now_instant();
+		let (strokes, chunks) = self.terrain_journal.take(player, now, duration);
 
-pub struct ClientLock {
-	chunk: Arc<Chunk>,
-	connection: Arc<ConnectionSend<ServerEnd>>,
-}
+		let mut report = RollbackReport {
+			strokes,
+			..RollbackReport::default()
+		};
 
-impl ClientLock {
-	pub fn new(
-		sector: &Arc<SharedSector>,
-		coordinates: ChunkCoordinates,
-		connection: Arc<ConnectionSend<ServerEnd>>,
-	) -> Self {
-		let chunk = sector.get_chunk(coordinates);
+		self.modify_chunks(chunks, |_, data, cells| {
+			terrain_journal::undo(data, &cells, &mut report)
+		});
 
-		let mut subscribed_clients = chunk.subscribed_clients.blocking_lock();
+		report
+	}
 
-		// is_none check to avoid duplicate chunk syncs
-		if !subscribed_clients.contains(&connection) {
-			subscribed_clients.push(connection.clone());
-			if let Some(ref data) = *chunk.try_read_data() {
-				connection.send(SyncChunk {
-					coordinates: chunk.coordinates,
-					materials: data.materials.clone(),
-					densities: data.densities.clone(),
-				});
-			}
+	/// Sends `player` to `destination`, one of [`config::Transfers::destinations`]. Where they are is recorded for the
+	/// destination to put them back there once they connect, then they're told which gateway to connect through and
+	/// dropped. Their inventory is already kept in the database, so there's nothing else of theirs to hand over.
+	pub fn transfer(&mut self, player: Id, destination: &str) -> Result<(), TransferError> {
+		let Some(gateway) = self.transfers.destinations.get(destination) else {
+			return Err(TransferError::UnknownDestination(destination.into()));
+		};
+
+		let database = self
+			.shared
+			.persistence
+			.database()
+			.ok_or(TransferError::NoDatabase)?;
+
+		let index = self
+			.players
+			.iter()
+			.position(|connected| connected.id == player)
+			.ok_or(TransferError::NotConnected)?;
+
+		let recorded = self.runtime.block_on(transfer::create(
+			database,
+			player,
+			&self.shared.name,
+			destination,
+			self.players[index].location,
+			Duration::from_secs(self.transfers.expiry),
+		))?;
+
+		if !recorded {
+			return Err(TransferError::AlreadyTransferring);
 		}
 
-		nom(subscribed_clients);
+		let mut player = self.players.remove(index);
 
-		Self { chunk, connection }
+		info!(
+			player_id:% = player.id, destination = destination;
+			"Player {} is being transferred to {destination}", player.id
+		);
+
+		// The message is still written once the connection is dropped
+		player.send(Transfer {
+			sector: destination.into(),
+			gateway: gateway.clone(),
+		});
+
+		recording::finish(&mut player);
+
+		Ok(())
 	}
-}
 
-impl Drop for ClientLock {
-	fn drop(&mut self) {
-		self.chunk
-			.subscribed_clients
-			.blocking_lock()
-			.retain(|other| self.connection != *other);
+	/// Runs `modify` on the cells of every chunk within `radius` of `center`, then rebuilds the collision of any chunks
+	/// that changed.
+	fn modify_cells(
+		&mut self,
+		center: VoxjectPosition,
+		radius: f32,
+		mut modify: impl FnMut(ChunkCoordinates, &mut Data, &[BrushCell]),
+	) {
+		self.modify_chunks(
+			terrain::sphere_cells(center, radius),
+			|coordinates, data, cells| modify(coordinates, data, &cells),
+		);
 	}
-}
 
-pub struct TickLock(Arc<Chunk>);
+	/// Runs `modify` on each chunk in `chunks` in order, along with whatever is paired with it, then rebuilds the
+	/// collision of every chunk that changed.
+	fn modify_chunks<T>(
+		&mut self,
+		chunks: impl IntoIterator<Item = (ChunkCoordinates, T)>,
+		mut modify: impl FnMut(ChunkCoordinates, &mut Data, T),
+	) {
+		let mut stale_collision = HashSet::new();
 
-impl TickLock {
-	pub fn new(sector: &Arc<SharedSector>, coordinates: ChunkCoordinates) -> Self {
-		let chunk = sector.get_chunk(coordinates);
+		for (coordinates, value) in chunks {
+			let chunk = self.get_chunk(coordinates);
 
-		if chunk.tick_lock_count.fetch_add(1, Relaxed) == 0 {
-			let _ = sector.send(Event::TickLockChunk(chunk.coordinates));
-			chunk.clone().trigger_collision_mesh_rebuild();
+			chunk.modify_data(|data| modify(coordinates, data, value));
+
+			self.dirty_chunks.insert(coordinates, chunk);
+
+			// A chunk's collision is built from it and the chunks on it's positive side, so the chunks on the negative
+			// side need rebuilding too
+			for x in -1..=0 {
+				for y in -1..=0 {
+					for z in -1..=0 {
+						stale_collision.extend(coordinates.checked_add(vector![x, y, z]));
+					}
+				}
+			}
 		}
 
-		Self(chunk)
-	}
-}
+		self.wake_structures_in(&stale_collision);
 
-impl Drop for TickLock {
-	fn drop(&mut self) {
-		if self.0.tick_lock_count.fetch_sub(1, Relaxed) == 1 {
-			if let Some(sector) = Weak::upgrade(&self.0.sector) {
-				let _ = sector.send(Event::TickReleaseChunk(self.0.coordinates));
+		for coordinates in stale_collision {
+			match self.ticking_chunks.remove(&coordinates) {
+				Some(ticking_chunk) => {
+					let chunk = ticking_chunk.unregister(&mut self.physics);
+					chunk.invalidate_collision();
+					TickingChunk::register(self, chunk);
+				}
+				None => {
+					if let Some(chunk) = self
+						.chunks
+						.get(&coordinates)
+						.and_then(|chunk| chunk.upgrade())
+					{
+						chunk.invalidate_collision();
+					}
+				}
 			}
 		}
 	}
+
+	/// Tick locks the chunks around every awake dynamic structure, so that they have collision to land on even when no
+	/// players are nearby, and indexes the structure by those chunks so that changes to them can wake it later.
+	/// Sleeping structures release their locks but stay indexed. Structures that would need too many chunks, or are
+	/// furthest from players when too many are awake, are put to sleep.
+	fn update_structures(&mut self) {
+		self.limit_awake_structures();
+
+		for structure in &self.structures {
+			let Some(rigid_body) = self.physics.get_rigid_body(*structure.rigid_body) else {
+				continue;
+			};
+
+			let mut wanted = HashSet::with_hasher(FxBuildHasher);
+
+			if rigid_body.is_dynamic() && !rigid_body.is_sleeping() {
+				match structure_locks::overlapped_chunks(
+					self.shared.voxjects.values(),
+					&structure.aabb(&self.physics),
+				) {
+					Some(chunks) => {
+						self.structure_index.update(structure.id, chunks.clone());
+						wanted = chunks;
+					}
+					None => {
+						warn!(
+							"Structure {} would need more than {MAX_CHUNKS_PER_STRUCTURE} chunks tick locked, putting it to sleep",
+							structure.id
+						);
+
+						if let Some(rigid_body) =
+							self.physics.get_rigid_body_mut(*structure.rigid_body)
+						{
+							rigid_body.sleep();
+						}
+					}
+				}
+			}
+
+			if wanted.is_empty() && !self.structure_locks.contains_key(&structure.id) {
+				continue;
+			}
+
+			let locks = self.structure_locks.entry(structure.id).or_default();
+			TickLock::update(locks, wanted, &self.shared);
+		}
+
+		self.structure_locks.retain(|_, locks| !locks.is_empty());
+	}
+
+	/// Puts the awake dynamic structures furthest from any player to sleep, until no more than
+	/// [`Sector::max_awake_structures`] are awake.
+	fn limit_awake_structures(&mut self) {
+		let Some(max_awake_structures) = self.max_awake_structures else {
+			return;
+		};
+
+		let mut awake = self
+			.structures
+			.iter()
+			.filter_map(|structure| {
+				let rigid_body = self.physics.get_rigid_body(*structure.rigid_body)?;
+
+				if !rigid_body.is_dynamic() || rigid_body.is_sleeping() {
+					return None;
+				}
+
+				let distance = self
+					.players
+					.iter()
+					.map(|player| {
+						(player.location.position.coords - rigid_body.translation()).norm_squared()
+					})
+					.min_by(f32::total_cmp)
+					.unwrap_or(f32::INFINITY);
+
+				Some((distance, *structure.rigid_body))
+			})
+			.collect::<Vec<_>>();
+
+		if awake.len() <= max_awake_structures {
+			return;
+		}
+
+		warn!(
+			"{} structures are awake, more than the limit of {max_awake_structures}, putting the {} furthest from players to sleep",
+			awake.len(),
+			awake.len() - max_awake_structures
+		);
+
+		awake.sort_unstable_by(|(a, _), (b, _)| a.total_cmp(b));
+
+		for (_, rigid_body) in &awake[max_awake_structures..] {
+			if let Some(rigid_body) = self.physics.get_rigid_body_mut(*rigid_body) {
+				rigid_body.sleep();
+			}
+		}
+	}
+
+	/// Wakes every structure indexed under any of `chunks`, for when something they may be resting on has changed.
+	fn wake_structures_in<'a>(&mut self, chunks: impl IntoIterator<Item = &'a ChunkCoordinates>) {
+		let woken = self.structure_index.structures_in(chunks);
+
+		if woken.is_empty() {
+			return;
+		}
+
+		for structure in &self.structures {
+			if !woken.contains(&structure.id) {
+				continue;
+			}
+
+			if let Some(rigid_body) = self.physics.get_rigid_body_mut(*structure.rigid_body) {
+				rigid_body.wake_up(true);
+			}
+		}
+
+		debug!("Woke {} structures", woken.len());
+	}
+
+	fn send_ambient(&self) {
+		let ambient = self.ambient.build_sync();
+
+		for player in &self.players {
+			player.send(ambient);
+		}
+	}
+
+	pub fn permission(&self, player: Id) -> Permission {
+		match self.admins.contains(&player) {
+			true => Permission::Admin,
+			false => Permission::Player,
+		}
+	}
+
+	fn send_chat_message(&mut self, player: Id, text: Box<str>) {
+		let message = self.chat.push(player, text);
+
+		for recipient in &self.players {
+			recipient.send(message.clone());
+		}
+
+		let capacity = self.chat.capacity();
+		if capacity == 0 {
+			return;
+		}
+
+		let sector = self.shared.clone();
+		self.runtime.spawn(async move {
+			let Some(database) = sector.persistence.database() else {
+				return;
+			};
+
+			if let Err(error) = chat::write(database, &sector.name, capacity, &message).await {
+				warn!("Unable to save chat message {}: {error}", message.sequence);
+			}
+		});
+	}
+
+	/// Removes a chat message from the history, and tells players to stop showing it. Returns `false` if it isn't in
+	/// the history.
+	pub fn retract_chat_message(&mut self, sequence: u64) -> bool {
+		if !self.chat.retract(sequence) {
+			return false;
+		}
+
+		for player in &self.players {
+			player.send(ChatRetract { sequence });
+		}
+
+		let sector = self.shared.clone();
+		self.runtime.spawn(async move {
+			let Some(database) = sector.persistence.database() else {
+				return;
+			};
+
+			if let Err(error) = chat::retract(database, &sector.name, sequence).await {
+				warn!("Unable to retract chat message {sequence}: {error}");
+			}
+		});
+
+		true
+	}
+
+	/// Loads `player`'s inventory in the background, it's sent to them with [`Event::InventoryLoaded`].
+	fn load_inventory(&self, player: Id) {
+		let capacity = self.inventory_capacity;
+
+		let sector = self.shared.clone();
+		self.runtime.spawn(async move {
+			let inventory = Inventory::new(player, capacity, &sector.persistence)
+				.load()
+				.await;
+
+			let _ = sector.send(Event::InventoryLoaded { player, inventory });
+		});
+	}
+
+	/// Carries out whatever `player` using a block does, if they can reach it, see [`Interactions`].
+	fn interact_block(
+		&mut self,
+		player: Id,
+		InteractBlock {
+			structure,
+			position,
+		}: InteractBlock,
+	) {
+		// They may have disconnected since
+		let Some(eye) = self
+			.players
+			.iter()
+			.find(|p| p.id == player)
+			.map(|player| player.location.position)
+		else {
+			return;
+		};
+
+		let Some(index) = self.structures.iter().position(|s| s.id == structure) else {
+			debug!("Player {player} tried to use a block of structure {structure}, which doesn't exist");
+			return;
+		};
+
+		let Some(block) = self.structures[index].get_block(&position) else {
+			debug!("Player {player} tried to use a block of structure {structure} at {position:?}, which doesn't exist");
+			return;
+		};
+
+		let Some(interaction) = self.interactions.get(block.typ) else {
+			throttled_warn!(
+				"sector::interact_not_interactable",
+				Duration::from_secs(10),
+				player_id:% = player;
+				"Player {player} tried to use a {:?} block, which doesn't do anything",
+				block.typ
+			);
+			return;
+		};
+
+		if let Err(unreachable) =
+			interaction::check_reach(&self.physics, &eye, &self.structures[index], &position)
+		{
+			throttled_warn!(
+				"sector::interact_unreachable",
+				Duration::from_secs(10),
+				player_id:% = player;
+				"Player {player} tried to use a block of structure {structure} they can't reach, {unreachable}"
+			);
+			return;
+		}
+
+		match interaction.interact(block.state) {
+			Effect::SetState(state) => {
+				self.structures[index].set_block_state(&position, state);
+
+				let message = SetBlockState {
+					structure,
+					position,
+					state,
+				};
+
+				for player in &self.players {
+					match player.interest.contains(structure) {
+						true => player.send(message),
+						false => self.interest_suppressed += 1,
+					}
+				}
+			}
+			Effect::OpenContainer => {
+				let inventory = self.containers.build_sync(structure, position);
+
+				if let Some(player) = self.players.iter().find(|p| p.id == player) {
+					player.send(SyncContainer {
+						structure,
+						position,
+						inventory,
+					});
+				}
+			}
+		}
+	}
+
+	pub fn send_system_message(&self, player: Id, text: LocalizedText, severity: Severity) {
+		if let Some(player) = self.players.iter().find(|p| p.id == player) {
+			player.send(UiEvent::SystemMessage { text, severity });
+		}
+	}
+
+	/// Starts recording `player`, see [`Recorder`].
+	pub fn start_recorder(&self, player: Id) -> Result<Recorder, RecordingError> {
+		let recorder = Recorder::start(&self.recording, &self.name, player, self.tick_rate)?;
+
+		info!(
+			player_id:% = player;
+			"Recording player {player} to {}",
+			recorder.path().display()
+		);
+
+		Ok(recorder)
+	}
+
+	/// The state of `player` that recordings are checked against.
+	pub fn checkpoint(&self, player: &Player) -> Checkpoint {
+		let (lock_hash, locks) = recording::hash_locks(
+			&self.shared,
+			player
+				.client_locks
+				.iter()
+				.map(|lock| &lock.chunk.coordinates)
+				.chain(player.sync_queue.pending()),
+			player.tick_locks.iter().map(|lock| &lock.0.coordinates),
+		);
+
+		Checkpoint {
+			tick: self.ticks,
+			location: player.location,
+			lock_hash,
+			locks,
+			structures: self.structure_counts.of(player.id) as u32,
+		}
+	}
+
+	/// Writes a checkpoint for each player being recorded whose checkpoint is due, and stops any recordings which are
+	/// stopping.
+	fn record_checkpoints(&mut self) {
+		for index in 0..self.players.len() {
+			let player = &self.players[index];
+
+			if !player
+				.recorder
+				.as_ref()
+				.is_some_and(|recorder| recorder.checkpoint_due(self.ticks))
+			{
+				continue;
+			}
+
+			let checkpoint = self.checkpoint(player);
+			let player = &mut self.players[index];
+
+			recording::record(player, |recorder| recorder.checkpoint(checkpoint));
+
+			if player
+				.recorder
+				.as_ref()
+				.is_some_and(|recorder| recorder.is_stopping())
+			{
+				recording::finish(player);
+			}
+		}
+	}
+
+	pub fn process_players(&mut self) {
+		self.players.retain_mut(|player| {
+			let connected = player.connection.is_connected();
+
+			if !connected {
+				info!(player_id:% = player.id; "Player {} disconnected", player.id);
+
+				if !player.telemetry.is_empty() {
+					info!(player_id:% = player.id; "Player {} telemetry: {}", player.id, player.telemetry);
+				}
+
+				recording::finish(player);
+			}
+
+			connected
+		});
+
+		// Structures allowed this tick, which won't be added to the sector until the next
+		let mut pending_structures = StructureCounts::default();
+		let now = self.clock.now_instant();
+		let mut kicked = vec![];
+
+		for player in self.players.iter_mut() {
+			while let Ok(message) = player.try_recv() {
+				// Only cloned while recording, so that recording costs nothing otherwise
+				let recorded = player
+					.recorder
+					.as_ref()
+					.filter(|recorder| recorder.is_started())
+					.map(|_| message.clone());
+
+				if player.idle.message(&message, &player.location, now) {
+					info!(player_id:% = player.id; "Player {} is back", player.id);
+
+					// Moving updates the locks anyway, from where they've moved to
+					if !matches!(message, Serverbound::PlayerLocation(_)) {
+						player.update_locks(&self.shared);
+					}
+				}
+
+				match message {
+					Serverbound::PlayerLocation(mut location) => {
+						// TODO: Check that this makes sense, we don't want players to just teleport :foxple:
+						if let Some(position) = self
+							.world_radius
+							.and_then(|radius| past_border(location.position, radius))
+						{
+							location.position = position;
+
+							player.send(Teleport { position });
+							player.send(UiEvent::SystemMessage {
+								text: LocalizedText::new("server.outside_world_border"),
+								severity: Severity::Warning,
+							});
+						}
+
+						player.location = location;
+						player.located = true;
+						player.update_locks(&self.shared);
+					}
+					Serverbound::GiveTestItem => {
+						// How not to handle database queries: execute them blocking on the main thread
+						let inventory = Inventory::new(
+							player.id,
+							self.inventory_capacity,
+							&self.shared.persistence,
+						);
+
+						let (item, quantity) = (Item::TestOre, 1);
+						let added = inventory
+							.add(item, quantity)
+							.expect("database is fucked, probably");
+
+						// Item entities don't exist yet, so there's nowhere for the rest to go
+						if added < quantity {
+							player.send(UiEvent::SystemMessage {
+								text: LocalizedText::new("server.inventory_full")
+									.with("quantity", quantity - added)
+									.with("item", item),
+								severity: Severity::Warning,
+							});
+						}
+
+						player.inventory_synced = true;
+						player.send(inventory.build_sync());
+
+						if added > 0 {
+							self.audit.record(
+								player.id,
+								self.ticks,
+								AuditEvent::ItemsGranted {
+									item,
+									quantity: added,
+								},
+							);
+						}
+					}
+					Serverbound::CreateStructure(create_structure) => {
+						let allowed = match player.try_place(now) {
+							false => Err(ActionDenied::PlacementRateLimited),
+							true => self.structure_counts.check(
+								&pending_structures,
+								&self.structure_limits,
+								player.id,
+							),
+						};
+
+						if let Err(denied) = allowed {
+							debug!(player_id:% = player.id; "Player {} was denied a structure: {denied:?}", player.id);
+							player.send(UiEvent::ActionDenied(denied));
+							continue;
+						}
+
+						pending_structures.add(player.id);
+
+						let structure =
+							Structure::new(&mut self.physics, player.id, create_structure);
+
+						if recorded.is_some() {
+							let id = structure.id;
+							recording::record(player, |recorder| recorder.structure_created(id));
+						}
+
+						let _ = self.shared.sender.send(Event::CreateStructure(structure));
+					}
+					Serverbound::DeleteStructure(DeleteStructure { structure }) => {
+						let _ = self.shared.sender.send(Event::DeleteStructure {
+							player: player.id,
+							structure,
+						});
+					}
+					Serverbound::ModifyTerrainBrush(brush) => {
+						if !player.try_place(now) {
+							player.send(UiEvent::ActionDenied(ActionDenied::PlacementRateLimited));
+							continue;
+						}
+
+						let _ = self.shared.sender.send(Event::ModifyTerrain {
+							player: player.id,
+							brush,
+						});
+					}
+					Serverbound::ModifyVoxel(modify) => {
+						if !player.try_place(now) {
+							player.send(UiEvent::ActionDenied(ActionDenied::PlacementRateLimited));
+							continue;
+						}
+
+						let _ = self.shared.sender.send(Event::ModifyVoxel {
+							player: player.id,
+							modify,
+						});
+					}
+					Serverbound::SetAmbientPaused(SetAmbientPaused { paused }) => {
+						let _ = self.shared.sender.send(Event::SetAmbientPaused {
+							player: player.id,
+							paused,
+						});
+					}
+					Serverbound::SendChatMessage(SendChatMessage { text }) => {
+						let text = text.trim();
+
+						if text.is_empty() {
+							continue;
+						}
+
+						if text.chars().count() > SendChatMessage::MAX_LENGTH {
+							player.send(UiEvent::SystemMessage {
+								text: LocalizedText::new("server.chat_too_long")
+									.with("max", SendChatMessage::MAX_LENGTH),
+								severity: Severity::Warning,
+							});
+							continue;
+						}
+
+						let _ = self.shared.sender.send(Event::ChatMessage {
+							player: player.id,
+							text: text.into(),
+						});
+					}
+					Serverbound::ClientTelemetry(telemetry) => {
+						match player.telemetry.record(telemetry) {
+							Ok(()) => debug!(
+								player_id:% = player.id;
+								"Player {} reported {telemetry:?}", player.id
+							),
+							Err(error) => debug!(
+								player_id:% = player.id;
+								"Ignored telemetry from player {}: {error}", player.id
+							),
+						}
+					}
+					Serverbound::Ping(Ping { sequence }) => player.send(Pong { sequence }),
+					Serverbound::HaveChunks(HaveChunks { mut entries }) => {
+						let count = entries.len();
+						entries.retain(|(coordinates, _)| {
+							coordinates
+								.is_sane(self.shared.world_radius_chunks, self.shared.max_level)
+								&& self.shared.voxjects.contains_key(&coordinates.voxject)
+						});
+
+						if entries.len() < count {
+							throttled_warn!(
+								"sector::have_chunks_outside_world",
+								Duration::from_secs(10),
+								player_id:% = player.id;
+								"Player {} claimed to have {} chunks outside of the world cached",
+								player.id, count - entries.len()
+							);
+						}
+
+						debug!(
+							player_id:% = player.id;
+							"Player {} has {} more chunks cached", player.id, entries.len()
+						);
+
+						let space =
+							HaveChunks::MAX_ENTRIES.saturating_sub(player.cached_chunks.len());
+						player.cached_chunks.extend(entries.into_iter().take(space));
+					}
+					Serverbound::InteractBlock(interact) => {
+						let _ = self.shared.sender.send(Event::InteractBlock {
+							player: player.id,
+							interact,
+						});
+					}
+					Serverbound::RequestChunk(RequestChunk(coordinates)) => {
+						if !coordinates
+							.is_sane(self.shared.world_radius_chunks, self.shared.max_level)
+						{
+							throttled_warn!(
+								"sector::request_chunk_outside_world",
+								Duration::from_secs(10),
+								player_id:% = player.id;
+								"Player {} requested chunk {coordinates}, which is outside of the world",
+								player.id
+							);
+							continue;
+						}
+
+						if let Some(lock) = player
+							.client_locks
+							.iter()
+							.find(|lock| lock.coordinates() == coordinates)
+						{
+							lock.resync();
+						}
+					}
+					Serverbound::ViewHint(hint) => {
+						if !hint.is_valid() {
+							throttled_warn!(
+								"sector::invalid_view_hint",
+								Duration::from_secs(10),
+								player_id:% = player.id;
+								"Player {} sent a view hint with a non-unit forward or out of range fov_cos",
+								player.id
+							);
+							continue;
+						}
+
+						player.view_hint = Some(hint);
+
+						// Nothing is locked until the player's location is known
+						if player.located {
+							player.update_locks(&self.shared);
+						}
+					}
+				}
+
+				// Anything rejected above has already moved on to the next message
+				if let Some(message) = recorded {
+					let tick = self.ticks;
+					recording::record(player, |recorder| recorder.message(tick, message));
+				}
+			}
+
+			match player.idle.tick(now, &player.location, &self.afk) {
+				Some(IdleTransition::WentAway) => {
+					info!(player_id:% = player.id; "Player {} is away", player.id);
+
+					player.update_locks(&self.shared);
+					player.send(UiEvent::SystemMessage {
+						text: LocalizedText::new("server.afk"),
+						severity: Severity::Info,
+					});
+				}
+				Some(IdleTransition::Kick) => {
+					info!(player_id:% = player.id; "Player {} was disconnected for being away too long", player.id);
+
+					// The message is still written once the connection is dropped
+					player.send(Disconnect {
+						reason: DisconnectReason::Idle,
+					});
+
+					kicked.push(player.id);
+					continue;
+				}
+				None => {}
+			}
+
+			if player.sync_queue.is_empty() {
+				continue;
+			}
+
+			for _ in 0..SyncQueue::budget(self.tick_rate) {
+				let Some(coordinates) = player.sync_queue.pop() else {
+					break;
+				};
+
+				player.client_locks.push(ClientLock::new(
+					&self.shared,
+					coordinates,
+					player.connection.sender(),
+					player.cached_chunks.remove(&coordinates),
+				));
+			}
+
+			if player.sync_queue.is_empty() {
+				debug!("Player {} has client locked all queued chunks", player.id);
+			}
+		}
+
+		if !kicked.is_empty() {
+			self.idle_kicks += kicked.len() as u64;
+
+			self.players.retain_mut(|player| {
+				let keep = !kicked.contains(&player.id);
+
+				if !keep {
+					recording::finish(player);
+				}
+
+				keep
+			});
+		}
+	}
+}
+
+/// [`Event`]s are sent to [`Sector`]s and are processed at the start of the next tick.
+pub enum Event {
+	/// A player has connected, with where they should arrive if they've been transferred from another sector.
+	PlayerConnected(Id, Connection<ServerEnd>, Option<Location>),
+	TickLockChunk(ChunkCoordinates),
+	TickReleaseChunk(ChunkCoordinates),
+	CreateStructure(Structure),
+	DeleteStructure {
+		player: Id,
+		structure: Id,
+	},
+	ModifyTerrain {
+		player: Id,
+		brush: ModifyTerrainBrush,
+	},
+	ModifyVoxel {
+		player: Id,
+		modify: ModifyVoxel,
+	},
+	SetAmbientPaused {
+		player: Id,
+		paused: bool,
+	},
+	InteractBlock {
+		player: Id,
+		interact: InteractBlock,
+	},
+	/// A player's inventory has been loaded after they connected, see [`Sector::load_inventory`].
+	InventoryLoaded {
+		player: Id,
+		inventory: Result<SyncInventory, sqlx::Error>,
+	},
+	/// A chat message, or a command if it starts with `/`.
+	ChatMessage {
+		player: Id,
+		text: Box<str>,
+	},
+	/// Saves all modified state, `reply` is sent a report once the save has been written.
+	Save {
+		reply: Option<oneshot::Sender<SaveReport>>,
+	},
+	SaveFinished(Result<SaveReport, sqlx::Error>),
+	SnapshotFinished(Result<SnapshotReport, SnapshotError>),
+	/// Saves and then stops the sector.
+	Shutdown,
+	/// The latest report from each gateway, read every [`GatewayStats::INTERVAL`].
+	///
+	/// [`GatewayStats::INTERVAL`]: solarscape_shared::message::backend::GatewayStats::INTERVAL
+	GatewayStats(Vec<GatewayReport>),
+}
+
+/// Where `position` is moved back to on the world border, [`None`] if it's within `radius` of the sector's origin.
+fn past_border(position: Point3<f32>, radius: f32) -> Option<Point3<f32>> {
+	let distance = position.coords.norm();
+	(distance > radius).then(|| Point3::from(position.coords * (radius / distance)))
+}
+
+/// A [`SharedSector`] allows accessing shared information about a [`Sector`], as well as sending events to be
+/// processed at the start of the next tick. It does not allow directly accessing the [`Sector`]'s internal state
+/// however.
+pub struct SharedSector {
+	pub name: Box<str>,
+
+	pub persistence: Persistence,
+	pub runtime: Handle,
+	sender: Sender<Event>,
+	/// Where the sector gets the time from, see [`Clock`].
+	pub clock: Arc<dyn Clock>,
+
+	pub voxjects: HashMap<Id, Voxject>,
+	/// Level 0 chunks either side of each voxject's origin that chunks are sent to players within, covering
+	/// [`config::Sector::world_radius`], see [`ChunkCoordinates::is_sane`].
+	pub world_radius_chunks: i64,
+	/// See [`config::Sector::max_level`], chunks above it aren't sent and are refused when asked for.
+	pub max_level: Level,
+	chunks: DashMap<ChunkCoordinates, Weak<Chunk>>,
+	/// Chunks which have been saved to the database, and so should be loaded rather than generated, along with how many
+	/// times they've been saved.
+	pub saved_chunks: DashMap<ChunkCoordinates, i64>,
+	/// [`None`] if the cache is disabled.
+	generation_cache: Option<Arc<GenerationCache>>,
+	pub generation_queue: Arc<GenerationQueue>,
+
+	/// Players connected as of the last tick, for reporting to gateways, see [`SectorStatus`].
+	///
+	/// [`SectorStatus`]: solarscape_shared::message::backend::SectorStatus
+	pub player_count: AtomicU32,
+	/// See [`config::Sector::max_players`], `0` if there's no limit.
+	pub max_players: u32,
+}
+
+impl SharedSector {
+	/// Sends an event to the [`Sector`] to be processed at the start of the next tick. The event is returned if the
+	/// event could not be sent.
+	pub fn send(&self, event: Event) -> Result<(), Event> {
+		self.sender.send(event).map_err(|error| error.0)
+	}
+
+	/// Generates the chunk at `coordinates`, or reads it from the [`GenerationCache`] if it's already been generated.
+	fn generate(&self, coordinates: &ChunkCoordinates) -> Data {
+		let voxject = &self.voxjects[&coordinates.voxject];
+
+		let Some(cache) = &self.generation_cache else {
+			return (voxject.generator)(coordinates);
+		};
+
+		if let Some(data) = cache.read(voxject.generator_hash, coordinates) {
+			return data;
+		}
+
+		let data = (voxject.generator)(coordinates);
+
+		let (cache, generator_hash, coordinates) =
+			(cache.clone(), voxject.generator_hash, *coordinates);
+		let bytes = generation_cache::encode(&data);
+
+		self.runtime
+			.spawn_blocking(move || cache.write(generator_hash, &coordinates, &bytes));
+
+		data
+	}
+
+	/// Where `voxject` is, or the origin if there's no such voxject.
+	pub fn voxject_location(&self, voxject: Id) -> Isometry3<f32> {
+		self.voxjects
+			.get(&voxject)
+			.map_or_else(Isometry3::identity, |voxject| voxject.location)
+	}
+
+	pub fn get_chunk(self: &Arc<Self>, coordinates: ChunkCoordinates) -> Arc<Chunk> {
+		self.chunks
+			.get(&coordinates)
+			.as_deref()
+			.and_then(Weak::upgrade)
+			.unwrap_or_else(|| {
+				let chunk = Chunk::new(self, coordinates);
+				self.chunks.insert(coordinates, Arc::downgrade(&chunk));
+				chunk
+			})
+	}
+
+	/// Adds up the chunk data and collision meshes of every loaded chunk, for checking [`MEMORY`] against. Returns
+	/// [`None`] if a chunk was locked.
+	#[cfg(debug)]
+	pub fn recount_memory(&self) -> Option<(usize, usize)> {
+		// Dropping a chunk removes it from the map, so they can't be dropped while iterating over it
+		let chunks = self
+			.chunks
+			.iter()
+			.filter_map(|chunk| chunk.upgrade())
+			.collect::<Vec<_>>();
+
+		let mut data_bytes = 0;
+		let mut collision_bytes = 0;
+
+		for chunk in &chunks {
+			if chunk.data.try_read().ok()?.is_some() {
+				data_bytes += Data::SIZE;
+			}
+
+			if let Some(collision) = &*chunk.collision.try_read().ok()? {
+				collision_bytes += collision.heap_size();
+			}
+		}
+
+		Some((data_bytes, collision_bytes))
+	}
+}
+
+impl Deref for Sector {
+	type Target = Arc<SharedSector>;
+
+	fn deref(&self) -> &Self::Target {
+		&self.shared
+	}
+}
+
+pub struct Voxject {
+	pub id: Id,
+	pub name: Box<str>,
+	/// Voxjects don't move yet, so this is always the origin, but nothing should rely on that.
+	pub location: Isometry3<f32>,
+	pub generator: Generator,
+	/// Identifies [`Voxject::generator`]'s output, for the [`GenerationCache`].
+	pub generator_hash: u64,
+	pub lod: LodCurve,
+}
+
+impl Voxject {
+	/// `sector_lod` is used unless the voxject has it's own.
+	pub fn new(
+		config::Voxject { name, lod }: config::Voxject,
+		sector_lod: &config::Lod,
+	) -> (Id, Self) {
+		let id = Id::new();
+		let voxject = Self {
+			id,
+			name,
+			location: Isometry3::identity(),
+			generator: sphere_generator,
+			generator_hash: sphere_generator_hash(),
+			lod: LodCurve::new(lod.as_ref().unwrap_or(sector_lod)),
+		};
+		(id, voxject)
+	}
+}
+
+/// Collision rebuilds that were skipped, see [`CollisionBuildStats`].
+pub static COLLISION_BUILDS: CollisionBuildStats = CollisionBuildStats::new();
+
+/// Counts of background collision rebuilds that didn't finish, because the chunk stopped needing them.
+pub struct CollisionBuildStats {
+	/// Rebuilds given up on, before or part way through, because the chunk's last tick lock was released.
+	pub cancelled: AtomicU64,
+	/// Rebuilds that found the chunk already dropped by the time they ran.
+	pub abandoned: AtomicU64,
+}
+
+impl CollisionBuildStats {
+	const fn new() -> Self {
+		Self {
+			cancelled: AtomicU64::new(0),
+			abandoned: AtomicU64::new(0),
+		}
+	}
+}
+
+#[non_exhaustive]
+pub struct Chunk {
+	pub sector: Weak<SharedSector>,
+	pub coordinates: ChunkCoordinates,
+
+	// Copy-on-write, so that the lock is only held long enough to clone or replace the list, and never while sending.
+	subscribed_clients: Mutex<Arc<Vec<Arc<Subscriber>>>>,
+
+	// Multiple tick locks may exist, we need to avoid removing a chunk from the ticking list if its tick locked
+	// elsewhere.
+	tick_lock_count: AtomicUsize,
+	/// Set when the last tick lock is released, so that a queued or in-flight collision rebuild for it gives up. Cleared
+	/// again when the chunk is next tick locked.
+	collision_cancelled: AtomicBool,
+
+	data: RwLock<Option<Data>>,
+	collision: RwLock<Option<Collision>>,
+
+	/// Incremented every time the chunk's data is modified, while the data write lock is still held.
+	generation: AtomicU64,
+}
+
+pub type DataTryReadGuard<'a> = RwLockReadGuard<'a, Option<Data>>;
+pub type DataReadGuard<'a> = RwLockReadGuard<'a, Data>;
+
+pub type CollisionReadGuard<'a> = RwLockReadGuard<'a, Collision>;
+
+impl Chunk {
+	/// How many times to try copying a consistent snapshot of a chunk's dependencies when building collision.
+	const MAX_COLLISION_ATTEMPTS: u32 = 4;
+
+	fn new(sector: &Arc<SharedSector>, coordinates: ChunkCoordinates) -> Arc<Self> {
+		let return_chunk = Arc::new(Self {
+			sector: Arc::downgrade(sector),
+			coordinates,
+
+			subscribed_clients: Mutex::default(),
+
+			tick_lock_count: AtomicUsize::new(0),
+			collision_cancelled: AtomicBool::new(false),
+
+			data: RwLock::default(),
+			collision: RwLock::default(),
+
+			generation: AtomicU64::new(0),
+		});
+
+		sector.generation_queue.admit(Arc::downgrade(&return_chunk));
+
+		return_chunk
+	}
+
+	/// Generates the chunk's data if nothing has yet, for the [`GenerationQueue`].
+	pub fn generate_in_background(&self) {
+		let data = self.data.blocking_write();
+		let _ = self.generate_data(data);
+	}
+
+	fn generate_data<'a>(
+		&'a self,
+		mut data: RwLockWriteGuard<'a, Option<Data>>,
+	) -> RwLockReadGuard<'a, Option<Data>> {
+		// Another thread may synchronously generate chunks instead of waiting if the chunk is needed immediately. So
+		// if that has happened, don't re-generate the chunk.
+		if data.is_some() {
+			return data.downgrade();
+		}
+
+		let sector = self
+			.sector
+			.upgrade()
+			.expect("Chunk should not be used after Sector has been dropped");
+
+		*data = Some(
+			save::load_chunk(&sector, &self.coordinates)
+				.unwrap_or_else(|| sector.generate(&self.coordinates)),
+		);
+
+		let data = data.downgrade();
+
+		let message = data
+			.as_ref()
+			.unwrap()
+			.build_sync(self.coordinates, self.generation());
+
+		// Clients that subscribed after the data was set may have already synced it themselves
+		self.subscribers()
+			.iter()
+			.filter(|subscriber| !subscriber.synced.swap(true, Relaxed))
+			.for_each(|subscriber| subscriber.sync(&message));
+
+		data
+	}
+
+	pub fn generation(&self) -> u64 {
+		self.generation.load(Acquire)
+	}
+
+	fn subscribers(&self) -> Arc<Vec<Arc<Subscriber>>> {
+		self.subscribed_clients
+			.lock()
+			.expect("subscribed_clients should not be poisoned")
+			.clone()
+	}
+
+	/// Builds the collision mesh if it hasn't been built already. If `cancellable` the build gives up part way through,
+	/// leaving the collision unset, once the chunk's last tick lock is released, and `None` is returned.
+	fn generate_collision<'a>(
+		self: &'a Arc<Self>,
+		mut collision: RwLockWriteGuard<'a, Option<Collision>>,
+		cancellable: bool,
+	) -> Option<RwLockReadGuard<'a, Option<Collision>>> {
+		if collision.is_some() {
+			return Some(collision.downgrade());
+		}
+
+		let cancelled = || cancellable && self.collision_cancelled.load(Relaxed);
+
+		let sector = self
+			.sector
+			.upgrade()
+			.expect("Chunk should not be used after Sector has been dropped");
+
+		// Chunks at the very edge of the world have nothing on their positive side to build collision with
+		let Some(dependencies) = self.coordinates.mesh_dependencies() else {
+			*collision = Some(Collision::default());
+			return Some(collision.downgrade());
+		};
+
+		let chunks = dependencies.map(|coordinates| sector.get_chunk(coordinates));
+
+		// Each chunk is only locked while it's data is copied, so a chunk can be modified after we've copied it but
+		// before we've copied the rest, leaving us with half of a modification. Generations let us notice and copy
+		// again. If chunks keep being modified we give up and build from what we have, anything modifying them
+		// should be invalidating this collision anyway.
+		let mut cells = CollisionCells::default();
+
+		for attempt in 1..=Self::MAX_COLLISION_ATTEMPTS {
+			let generations = chunks.each_ref().map(|chunk| chunk.generation());
+
+			for (index, chunk) in chunks.iter().enumerate() {
+				cells.copy_chunk(index, &chunk.read_data_immediately());
+			}
+
+			let changed = chunks
+				.iter()
+				.zip(generations)
+				.any(|(chunk, generation)| chunk.generation() != generation);
+
+			if !changed {
+				break;
+			}
+
+			debug!(
+				"Chunks were modified while building collision for {:?} (attempt {attempt} of {})",
+				self.coordinates,
+				Self::MAX_COLLISION_ATTEMPTS
+			);
+		}
+
+		*collision = Some(cells.triangulate(&cancelled)?);
+		Some(collision.downgrade())
+	}
+
+	pub fn read_data_immediately(&self) -> DataReadGuard {
+		{
+			let data = self.data.blocking_read();
+
+			if data.is_some() {
+				return RwLockReadGuard::map(data, |v| v.as_ref().unwrap());
+			}
+		}
+
+		let data = self.generate_data(self.data.blocking_write());
+		RwLockReadGuard::map(data, |v| v.as_ref().unwrap())
+	}
+
+	pub fn read_collision_immediately<'a>(self: &'a Arc<Chunk>) -> CollisionReadGuard<'a> {
+		{
+			let collision = self.collision.blocking_read();
+
+			if collision.is_some() {
+				return RwLockReadGuard::map(collision, |v| v.as_ref().unwrap());
+			}
+		}
+
+		let collision = self
+			.generate_collision(self.collision.blocking_write(), false)
+			.expect("collision should not be cancelled when not cancellable");
+		RwLockReadGuard::map(collision, |v| v.as_ref().unwrap())
+	}
+
+	/// Modifies the chunk's data, generating it first if needed, then syncs the changes to subscribed clients. The
+	/// collision mesh is not updated, see [`Chunk::invalidate_collision`].
+	pub fn modify_data(&self, modify: impl FnOnce(&mut Data)) {
+		let mut data = self.data.blocking_write();
+
+		if data.is_none() {
+			nom(self.generate_data(data));
+			data = self.data.blocking_write();
+		}
+
+		let (data, generation) = {
+			modify(data.as_mut().expect("data should have just been generated"));
+			let generation = self.generation.fetch_add(1, AcqRel) + 1;
+			(data.downgrade(), generation)
+		};
+
+		let message = data
+			.as_ref()
+			.expect("data should have just been generated")
+			.build_sync(self.coordinates, generation);
+
+		// The data read guard is still held, so this can't be reordered with a new subscriber's initial sync
+		for subscriber in self.subscribers().iter() {
+			subscriber.synced.store(true, Relaxed);
+			subscriber.connection.send(message.clone());
+		}
+	}
+
+	/// Discards the collision mesh so that it is rebuilt from the current data next time it is needed.
+	pub fn invalidate_collision(&self) {
+		*self.collision.blocking_write() = None;
+	}
+
+	pub fn try_read_data(&self) -> DataTryReadGuard {
+		self.data.blocking_read()
+	}
+
+	pub fn trigger_collision_mesh_rebuild(self: &Arc<Self>) {
+		// Only a weak reference is held while the job is queued, so that a chunk released in the meantime can still be
+		// dropped, and saved, without waiting for the pool to get to it.
+		let chunk = Arc::downgrade(self);
+
+		rayon::spawn(move || {
+			let Some(chunk) = chunk.upgrade() else {
+				COLLISION_BUILDS.abandoned.fetch_add(1, Relaxed);
+				return;
+			};
+
+			if chunk.collision_cancelled.load(Relaxed) {
+				COLLISION_BUILDS.cancelled.fetch_add(1, Relaxed);
+				return;
+			}
+
+			let collision = chunk.collision.blocking_write();
+
+			if chunk.generate_collision(collision, true).is_none() {
+				COLLISION_BUILDS.cancelled.fetch_add(1, Relaxed);
+			}
+		});
+	}
+}
+
+impl Drop for Chunk {
+	fn drop(&mut self) {
+		if let Some(sector) = Weak::upgrade(&self.sector) {
+			sector.chunks.remove(&self.coordinates);
+		}
+	}
+}
+
+/// A wrapper around [`Chunk`] that stores extra information needed to allow the chunk to tick, and should not be
+/// accessible outside of the sector thread.
+struct TickingChunk {
+	inner: Arc<Chunk>,
+	rigid_body: AutoCleanup<RigidBodyHandle>,
+	_collider: Option<AutoCleanup<ColliderHandle>>,
+}
+
+impl TickingChunk {
+	fn register(sector: &mut Sector, chunk: Arc<Chunk>) {
+		let voxject_location = sector.voxject_location(chunk.coordinates.voxject);
+		let rigid_body = sector.physics.insert_rigid_body(
+			RigidBodyBuilder::fixed().position(chunk.coordinates.world_location(&voxject_location)),
+		);
+
+		let collider = {
+			let collision = chunk.read_collision_immediately();
+
+			trimesh::collider(&collision.vertices, &collision.indices).map(|collider| {
+				sector.physics.insert_collider_with_owner(
+					*rigid_body,
+					collider,
+					CollisionGroup::Terrain,
+					ColliderOwner::Chunk(chunk.coordinates),
+				)
+			})
+		};
+
+		let ticking_chunk = Self {
+			inner: chunk,
+			rigid_body,
+			_collider: collider,
+		};
+
+		sector
+			.ticking_chunks
+			.insert(ticking_chunk.coordinates, ticking_chunk);
+	}
+
+	/// Removes the chunk's rigid body and collider immediately, so that it can be registered again without them
+	/// briefly overlapping.
+	fn unregister(self, physics: &mut Physics) -> Arc<Chunk> {
+		physics.remove_now(*self.rigid_body);
+		self.inner.clone()
+	}
+}
+
+impl Deref for TickingChunk {
+	type Target = Arc<Chunk>;
+
+	fn deref(&self) -> &Self::Target {
+		&self.inner
+	}
+}
+
+#[non_exhaustive]
+pub struct Data {
+	pub materials: Box<[Material; CELLS_PER_CHUNK]>,
+	pub densities: Box<[f32; CELLS_PER_CHUNK]>,
+
+	_memory: Tracked,
+}
+
+impl Data {
+	/// Bytes allocated for every chunk's data.
+	pub const SIZE: usize =
+		size_of::<[Material; CELLS_PER_CHUNK]>() + size_of::<[f32; CELLS_PER_CHUNK]>();
+
+	/// Copies the data into a [`SyncChunk`], which can then be cloned cheaply for every subscriber.
+	pub fn build_sync(&self, coordinates: ChunkCoordinates, generation: u64) -> SyncChunk {
+		SyncChunk {
+			coordinates,
+			generation,
+			materials: Arc::new(*self.materials),
+			densities: Arc::new(*self.densities),
+		}
+	}
+
+	/// Material of the cell at `cell`, [`None`] if it's outside of the chunk.
+	pub fn material_at(&self, cell: Vector3<u8>) -> Option<Material> {
+		Some(self.materials[Self::index(cell).ok()?])
+	}
+
+	/// Density of the cell at `cell`, [`None`] if it's outside of the chunk.
+	pub fn density_at(&self, cell: Vector3<u8>) -> Option<f32> {
+		Some(self.densities[Self::index(cell).ok()?])
+	}
+
+	/// Replaces the material and density of the cell at `cell`. Doesn't mark the chunk as modified, that's up to the
+	/// caller.
+	pub fn set_voxel(
+		&mut self,
+		cell: Vector3<u8>,
+		material: Material,
+		density: f32,
+	) -> Result<(), OutsideChunk> {
+		let index = Self::index(cell)?;
+		self.materials[index] = material;
+		self.densities[index] = density;
+		Ok(())
+	}
+
+	/// Every cell's position, material, and density, in the order they're stored.
+	pub fn iter_voxels(&self) -> impl Iterator<Item = (Vector3<u8>, Material, f32)> + '_ {
+		self.materials
+			.iter()
+			.zip(self.densities.iter())
+			.enumerate()
+			.map(|(index, (material, density))| {
+				let cell = vector![
+					index / (CHUNK_SIZE * CHUNK_SIZE),
+					index / CHUNK_SIZE % CHUNK_SIZE,
+					index % CHUNK_SIZE
+				];
+
+				(cell.map(|axis| axis as u8), *material, *density)
+			})
+	}
+
+	/// Index of the cell at `cell` in [`Data::materials`] and [`Data::densities`].
+	pub fn index(cell: Vector3<u8>) -> Result<usize, OutsideChunk> {
+		if cell.iter().any(|axis| usize::from(*axis) >= CHUNK_SIZE) {
+			return Err(OutsideChunk(cell));
+		}
+
+		let cell = cell.map(usize::from);
+		Ok(cell_index(cell.x, cell.y, cell.z))
+	}
+}
+
+/// A cell position that isn't within a chunk, each axis has to be less than [`CHUNK_SIZE`].
+#[derive(Clone, Copy, Debug, Error, PartialEq)]
+#[error("cell {}, {}, {} is outside of the chunk", .0.x, .0.y, .0.z)]
+pub struct OutsideChunk(pub Vector3<u8>);
+
+impl Default for Data {
+	fn default() -> Self {
+		Self {
+			materials: Box::new([Material::Nothing; CELLS_PER_CHUNK]),
+			densities: Box::new([0.0; CELLS_PER_CHUNK]),
+
+			_memory: Tracked::new(Category::ChunkData, Self::SIZE),
+		}
+	}
+}
+
+#[non_exhaustive]
+pub struct Collision {
+	pub vertices: Vec<Point3<f32>>,
+	pub indices: Vec<[u32; 3]>,
+
+	memory: Tracked,
+}
+
+impl Default for Collision {
+	fn default() -> Self {
+		Self {
+			vertices: vec![],
+			indices: vec![],
+
+			memory: Tracked::new(Category::CollisionMeshes, 0),
+		}
+	}
+}
+
+impl Collision {
+	/// Bytes allocated for the vertices and indices.
+	pub fn heap_size(&self) -> usize {
+		self.vertices.capacity() * size_of::<Point3<f32>>()
+			+ self.indices.capacity() * size_of::<[u32; 3]>()
+	}
+
+	/// Triangulates a chunk's collision mesh, `dependencies` are the chunk's data and the data of the 7 chunks on it's
+	/// positive side, as given by [`ChunkCoordinates::mesh_dependencies`].
+	pub fn build(dependencies: [&Data; 8]) -> Self {
+		let mut cells = CollisionCells::default();
+
+		for (index, data) in dependencies.into_iter().enumerate() {
+			cells.copy_chunk(index, data);
+		}
+
+		cells
+			.triangulate(&|| false)
+			.expect("triangulation should not be cancelled")
+	}
+}
+
+/// The [`CHUNK_SAMPLE_SIZE`] cubed cells a collision mesh is triangulated from, copied out of the chunk and it's
+/// dependencies so that they don't have to stay locked while triangulating.
+struct CollisionCells {
+	densities: [f32; SAMPLES_PER_CHUNK],
+	materials: [Material; SAMPLES_PER_CHUNK],
+}
+
+impl Default for CollisionCells {
+	fn default() -> Self {
+		Self {
+			densities: [0.0; SAMPLES_PER_CHUNK],
+			materials: [Material::Nothing; SAMPLES_PER_CHUNK],
+		}
+	}
+}
+
+impl CollisionCells {
+	/// Copies the cells that come from dependency `index`, in [`ChunkCoordinates::mesh_dependencies`] order.
+	fn copy_chunk(&mut self, index: usize, data: &Data) {
+		let [x_range, y_range, z_range] =
+			[index & 0b100, index & 0b010, index & 0b001].map(|offset| match offset {
+				0 => 0..CHUNK_SIZE,
+				_ => CHUNK_SIZE..CHUNK_SAMPLE_SIZE,
+			});
+
+		for x in x_range {
+			for y in y_range.clone() {
+				for z in z_range.clone() {
+					let index = sample_index(x, y, z);
+					let chunk_cell_index =
+						cell_index(x % CHUNK_SIZE, y % CHUNK_SIZE, z % CHUNK_SIZE);
+
+					self.densities[index] = data.densities[chunk_cell_index];
+					self.materials[index] = data.materials[chunk_cell_index];
+				}
+			}
+		}
+	}
+
+	/// Triangulates the cells, returning `None` if `cancelled` returns true, which is checked before each row.
+	fn triangulate(&self, cancelled: &dyn Fn() -> bool) -> Option<Collision> {
+		let Self {
+			densities,
+			materials,
+		} = self;
+
+		let mut collision = Collision::default();
+
+		for x in 0..CHUNK_SIZE {
+			for y in 0..CHUNK_SIZE {
+				if cancelled() {
+					return None;
+				}
+
+				for z in 0..CHUNK_SIZE {
+					let indexes = [
+						(x, y, z + 1),
+						(x + 1, y, z + 1),
+						(x + 1, y, z),
+						(x, y, z),
+						(x, y + 1, z + 1),
+						(x + 1, y + 1, z + 1),
+						(x + 1, y + 1, z),
+						(x, y + 1, z),
+					]
+					.map(|(x, y, z)| sample_index(x, y, z));
+
+					let densities = indexes.map(|index| densities[index]);
+					let materials = indexes.map(|index| materials[index]);
+
+					#[allow(clippy::identity_op)]
+							#[rustfmt::skip]
+							let case_index = (!matches!(materials[0], Material::Nothing) as usize) << 0
+								| (!matches!(materials[1], Material::Nothing) as usize) << 1
+								| (!matches!(materials[2], Material::Nothing) as usize) << 2
+								| (!matches!(materials[3], Material::Nothing) as usize) << 3
+								| (!matches!(materials[4], Material::Nothing) as usize) << 4
+								| (!matches!(materials[5], Material::Nothing) as usize) << 5
+								| (!matches!(materials[6], Material::Nothing) as usize) << 6
+								| (!matches!(materials[7], Material::Nothing) as usize) << 7;
+
+					let EdgeData {
+						count,
+						edge_indices,
+					} = CELL_EDGE_MAP[case_index];
+
+					for edge_indices in edge_indices.chunks(3).take(count as usize) {
+						let vertices = edge_indices
+							.iter()
+							.map(|edge_index| {
+								let (a_index, b_index) = EDGE_CORNER_MAP[*edge_index as usize];
+
+								let a_density = densities[a_index];
+								let b_density = densities[b_index];
+
+								let weight = if a_density == b_density {
+									0.5
+								} else {
+									(0.0 - a_density) / (b_density - a_density)
+								};
+
+								let a = CORNERS[a_index];
+								let b = CORNERS[b_index];
+
+								let vertex = a + weight * (b - a);
+
+								point![x as f32, y as f32, z as f32] + vertex
+							})
+							.collect::<Vec<_>>();
+
+						collision.vertices.extend_from_slice(&vertices);
+					}
+				}
+			}
+		}
+
+		collision.indices = (0..collision.vertices.len() as u32)
+			.collect::<Vec<_>>()
+			.chunks_exact(3)
+			.map(|chunk| [chunk[0], chunk[1], chunk[2]])
+			.collect();
+
+		let heap_size = collision.heap_size();
+		collision.memory.set(heap_size);
+
+		Some(collision)
+	}
+}
+
+struct Subscriber {
+	connection: Arc<ConnectionSend<ServerEnd>>,
+
+	// Subscribing can race with the chunk's data being generated, whichever of them sets this first sends the initial
+	// sync, so that the client gets it exactly once.
+	synced: AtomicBool,
+
+	/// Content hash of the copy the client has cached, see [`Player::cached_chunks`].
+	///
+	/// [`Player::cached_chunks`]: crate::player::Player::cached_chunks
+	cached: Option<u64>,
+}
+
+impl Subscriber {
+	/// Sends the initial sync, or just tells the client to use it's cached copy if that has the same data.
+	fn sync(&self, message: &SyncChunk) {
+		match self.cached == Some(message.content_hash()) {
+			true => self.connection.send(ChunkUnchanged {
+				coordinates: message.coordinates,
+				generation: message.generation,
+				content_hash: message.content_hash(),
+			}),
+			false => self.connection.send(message.clone()),
+		}
+	}
+}
+
+pub struct ClientLock {
+	chunk: Arc<Chunk>,
+	subscriber: Arc<Subscriber>,
+}
+
+impl ClientLock {
+	pub fn new(
+		sector: &Arc<SharedSector>,
+		coordinates: ChunkCoordinates,
+		connection: Arc<ConnectionSend<ServerEnd>>,
+		cached: Option<u64>,
+	) -> Self {
+		let chunk = sector.get_chunk(coordinates);
+
+		let subscriber = {
+			let mut subscribed_clients = chunk
+				.subscribed_clients
+				.lock()
+				.expect("subscribed_clients should not be poisoned");
+
+			// An existing subscriber has already been synced, or will be, so it's synced flag stops a duplicate sync
+			match subscribed_clients
+				.iter()
+				.find(|subscriber| subscriber.connection == connection)
+			{
+				Some(subscriber) => subscriber.clone(),
+				None => {
+					let subscriber = Arc::new(Subscriber {
+						connection,
+						synced: AtomicBool::new(false),
+						cached,
+					});
+
+					Arc::make_mut(&mut subscribed_clients).push(subscriber.clone());
+					subscriber
+				}
+			}
+		};
+
+		if let Some(ref data) = *chunk.try_read_data() {
+			if !subscriber.synced.swap(true, Relaxed) {
+				subscriber.sync(&data.build_sync(chunk.coordinates, chunk.generation()));
+			}
+		}
+
+		Self { chunk, subscriber }
+	}
+
+	/// Sends the chunk's data again, regardless of what the client has cached. Does nothing if the data hasn't been
+	/// generated yet, as the initial sync is still to come.
+	pub fn resync(&self) {
+		if let Some(ref data) = *self.chunk.try_read_data() {
+			self.subscriber
+				.connection
+				.send(data.build_sync(self.chunk.coordinates, self.chunk.generation()));
+		}
+	}
+
+	pub fn coordinates(&self) -> ChunkCoordinates {
+		self.chunk.coordinates
+	}
+
+	/// Whether the chunk has been sent to the client, it hasn't if it's still being generated.
+	pub fn is_synced(&self) -> bool {
+		self.subscriber.synced.load(Relaxed)
+	}
+}
+
+impl Drop for ClientLock {
+	fn drop(&mut self) {
+		let mut subscribed_clients = self
+			.chunk
+			.subscribed_clients
+			.lock()
+			.expect("subscribed_clients should not be poisoned");
+
+		Arc::make_mut(&mut subscribed_clients)
+			.retain(|other| self.subscriber.connection != other.connection);
+	}
+}
+
+pub struct TickLock(Arc<Chunk>);
+
+impl TickLock {
+	pub fn new(sector: &Arc<SharedSector>, coordinates: ChunkCoordinates) -> Self {
+		let chunk = sector.get_chunk(coordinates);
+
+		if chunk.tick_lock_count.fetch_add(1, Relaxed) == 0 {
+			chunk.collision_cancelled.store(false, Relaxed);
+			let _ = sector.send(Event::TickLockChunk(chunk.coordinates));
+			chunk.trigger_collision_mesh_rebuild();
+		}
+
+		Self(chunk)
+	}
+
+	/// Replaces `locks` with locks on the `wanted` chunks, keeping any that are already held.
+	pub fn update(
+		locks: &mut Vec<TickLock>,
+		mut wanted: HashSet<ChunkCoordinates, FxBuildHasher>,
+		sector: &Arc<SharedSector>,
+	) {
+		// Retain will remove any chunks that aren't wanted, remove will remove any chunks from the wanted list that are
+		// already locked
+		locks.retain(|lock| wanted.remove(&lock.0.coordinates));
+
+		for coordinates in wanted {
+			locks.push(TickLock::new(sector, coordinates));
+		}
+	}
+}
+
+impl Drop for TickLock {
+	fn drop(&mut self) {
+		if self.0.tick_lock_count.fetch_sub(1, Relaxed) == 1 {
+			self.0.collision_cancelled.store(true, Relaxed);
+
+			if let Some(sector) = Weak::upgrade(&self.0.sector) {
+				let _ = sector.send(Event::TickReleaseChunk(self.0.coordinates));
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+	use nalgebra::UnitQuaternion;
+	use rapier3d::geometry::Collider;
+	use solarscape_shared::{
+		clock::{self, MockClock},
+		connection::ClientEnd,
+		message::{
+			clientbound::{ChatHistory, Clientbound, InventorySlot, SyncStructure},
+			serverbound::{BrushMode, CreateStructure, ViewHint},
+		},
+	};
+	use std::thread;
+	use tokio::runtime::Runtime;
+
+	const LAST: u8 = CHUNK_SIZE as u8 - 1;
+
+	/// Slow enough that subscribers can pile up while the chunk is being generated.
+	fn slow_generator(_: &ChunkCoordinates) -> Data {
+		thread::sleep(Duration::from_millis(20));
+		Data::default()
+	}
+
+	/// A sector with just the one voxject, and nothing else going on, along with the coordinates of a chunk in it.
+	pub(crate) fn shared_sector(
+		runtime: &Runtime,
+		generator: Generator,
+	) -> (Arc<SharedSector>, ChunkCoordinates) {
+		let id = Id::new();
+		let voxject = Voxject {
+			id,
+			name: "test".into(),
+			location: Isometry3::identity(),
+			generator,
+			generator_hash: 0,
+			lod: LodCurve::new(&config::Lod::Preset(config::LodPreset::Near)),
+		};
+
+		let sector = Arc::new(SharedSector {
+			name: "test".into(),
+
+			persistence: Persistence::memory(),
+			runtime: runtime.handle().clone(),
+			sender: channel().0,
+			clock: clock::system(),
+
+			voxjects: HashMap::from([(id, voxject)]),
+			world_radius_chunks: 16,
+			max_level: Level::new(0),
+			chunks: DashMap::new(),
+			saved_chunks: DashMap::new(),
+			generation_cache: None,
+			generation_queue: Arc::new(GenerationQueue::new(0)),
+
+			player_count: AtomicU32::new(0),
+			max_players: 0,
+		});
+
+		(
+			sector,
+			ChunkCoordinates::new(id, Vector3::zeros(), Level::new(0)),
+		)
+	}
+
+	/// Generations of every sync the client has been sent so far.
+	fn syncs(client: &mut Connection<ClientEnd>) -> Vec<u64> {
+		let mut generations = vec![];
+
+		while let Ok(message) = client.try_recv() {
+			match message {
+				Clientbound::SyncChunk(sync) => generations.push(sync.generation),
+				Clientbound::ChunkUnchanged(unchanged) => generations.push(unchanged.generation),
+				_ => {}
+			}
+		}
+
+		generations
+	}
+
+	/// A sector with one voxject and default config, run by hand with `clock`. A runtime has to be entered.
+	fn sector(clock: &Arc<MockClock>) -> Sector {
+		let config = hocon::de::from_str("name: test\nvoxjects: [{ name: test }]").unwrap();
+		Sector::new(Persistence::memory(), clock.clone(), config).unwrap()
+	}
+
+	fn tick(sector: &mut Sector, clock: &MockClock) {
+		let delta = sector.tick_duration();
+		clock.advance(delta);
+		sector.tick(delta.as_secs_f32());
+	}
+
+	/// Somewhere well above the voxject.
+	fn location() -> Location {
+		Location {
+			position: point![0.0, 1000.0, 0.0],
+			rotation: UnitQuaternion::identity(),
+		}
+	}
+
+	/// Connects `player` at [`location`], returning their end of the connection.
+	fn connect(sector: &mut Sector, clock: &MockClock, player: Id) -> Connection<ClientEnd> {
+		let (client, server) = Connection::pair();
+		let _ = sector.send(Event::PlayerConnected(player, server, None));
+		tick(sector, clock);
+
+		client.send(Serverbound::PlayerLocation(location()));
+		tick(sector, clock);
+
+		client
+	}
+
+	fn messages(client: &mut Connection<ClientEnd>) -> Vec<Clientbound> {
+		std::iter::from_fn(|| client.try_recv().ok()).collect()
+	}
+
+	fn system_messages(client: &mut Connection<ClientEnd>) -> Vec<Box<str>> {
+		messages(client)
+			.into_iter()
+			.filter_map(|message| match message {
+				Clientbound::UiEvent(UiEvent::SystemMessage { text, .. }) => Some(text.key),
+				_ => None,
+			})
+			.collect()
+	}
+
+	fn removed_structures(messages: &[Clientbound]) -> Vec<Id> {
+		messages
+			.iter()
+			.filter_map(|message| match message {
+				Clientbound::RemoveStructure(RemoveStructure(structure)) => Some(*structure),
+				_ => None,
+			})
+			.collect()
+	}
+
+	/// Has `client` create a structure, returning it's id once it exists.
+	fn create_structure(
+		sector: &mut Sector,
+		clock: &MockClock,
+		client: &Connection<ClientEnd>,
+	) -> Id {
+		let existing = sector.structures.len();
+
+		client.send(Serverbound::CreateStructure(CreateStructure {
+			location: location(),
+			block: BlockType::Block,
+		}));
+
+		// Created from the message on one tick, and added from the event on the next
+		tick(sector, clock);
+		tick(sector, clock);
+
+		assert_eq!(sector.structures.len(), existing + 1);
+		sector.structures.last().unwrap().id
+	}
+
+	fn delete_structure(
+		sector: &mut Sector,
+		clock: &MockClock,
+		client: &Connection<ClientEnd>,
+		structure: Id,
+	) {
+		client.send(Serverbound::DeleteStructure(DeleteStructure { structure }));
+		tick(sector, clock);
+		tick(sector, clock);
+	}
+
+	#[test]
+	fn only_owners_can_delete_structures() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let (owner, other) = (Id::new(), Id::new());
+		let mut owner_client = connect(&mut sector, &clock, owner);
+		let mut other_client = connect(&mut sector, &clock, other);
+
+		let structure = create_structure(&mut sector, &clock, &owner_client);
+		assert_eq!(sector.structures.last().unwrap().owner, owner);
+		messages(&mut owner_client);
+		messages(&mut other_client);
+
+		delete_structure(&mut sector, &clock, &other_client, structure);
+
+		assert!(sector.structures.iter().any(|s| s.id == structure));
+		assert_eq!(
+			system_messages(&mut other_client),
+			["server.not_structure_owner".into()]
+		);
+		assert!(removed_structures(&messages(&mut owner_client)).is_empty());
+
+		delete_structure(&mut sector, &clock, &owner_client, structure);
+
+		assert!(!sector.structures.iter().any(|s| s.id == structure));
+		assert_eq!(
+			removed_structures(&messages(&mut owner_client)),
+			[structure]
+		);
+		assert_eq!(
+			removed_structures(&messages(&mut other_client)),
+			[structure]
+		);
+	}
+
+	#[test]
+	fn awake_structures_tick_lock_the_chunks_they_overlap() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let client = connect(&mut sector, &clock, Id::new());
+		let structure = create_structure(&mut sector, &clock, &client);
+		tick(&mut sector, &clock);
+
+		let rigid_body = *sector.structures.last().unwrap().rigid_body;
+		assert!(sector
+			.physics
+			.get_rigid_body(rigid_body)
+			.unwrap()
+			.is_dynamic());
+
+		let locked: HashSet<_> = sector.structure_locks[&structure]
+			.iter()
+			.map(|lock| lock.0.coordinates)
+			.collect();
+		let position =
+			VoxjectPosition::new(*sector.voxjects.keys().next().unwrap(), location().position);
+		assert!(locked.contains(&position.chunk(Level::new(0))));
+		assert!(locked.iter().all(|chunk| chunk.level == Level::new(0)));
+
+		// Sleeping structures don't need collision, so they let go of their chunks
+		sector
+			.physics
+			.get_rigid_body_mut(rigid_body)
+			.unwrap()
+			.sleep();
+		tick(&mut sector, &clock);
+
+		assert!(!sector.structure_locks.contains_key(&structure));
+	}
+
+	#[test]
+	fn players_past_the_border_are_teleported_back() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let player = Id::new();
+		let mut client = connect(&mut sector, &clock, player);
+		sector.world_radius = Some(100.0);
+		messages(&mut client);
+
+		let teleports = |messages: &[Clientbound]| -> Vec<Point3<f32>> {
+			messages
+				.iter()
+				.filter_map(|message| match message {
+					Clientbound::Teleport(Teleport { position }) => Some(*position),
+					_ => None,
+				})
+				.collect()
+		};
+
+		let warned = |messages: &[Clientbound]| {
+			messages.iter().any(|message| {
+				matches!(
+					message,
+					Clientbound::UiEvent(UiEvent::SystemMessage { text, .. })
+						if &*text.key == "server.outside_world_border"
+				)
+			})
+		};
+
+		client.send(Serverbound::PlayerLocation(Location {
+			position: point![0.0, 50.0, 0.0],
+			rotation: UnitQuaternion::identity(),
+		}));
+		tick(&mut sector, &clock);
+
+		let inside = messages(&mut client);
+		assert!(teleports(&inside).is_empty());
+		assert!(!warned(&inside));
+
+		client.send(Serverbound::PlayerLocation(location()));
+		tick(&mut sector, &clock);
+
+		let outside = messages(&mut client);
+		assert_eq!(teleports(&outside), [point![0.0, 100.0, 0.0]]);
+		assert!(warned(&outside));
+
+		let player = sector.players.iter().find(|p| p.id == player).unwrap();
+		assert_eq!(player.location.position, point![0.0, 100.0, 0.0]);
+	}
+
+	/// Sets the first cell of the chunk at the origin of the sector's voxject.
+	fn modify_voxel(sector: &Sector, density: f32) -> ModifyVoxel {
+		ModifyVoxel {
+			coordinates: ChunkCoordinates::new(
+				*sector.voxjects.keys().next().unwrap(),
+				Vector3::zeros(),
+				Level::new(0),
+			),
+			cell: Vector3::zeros(),
+			material: Material::Stone,
+			density,
+		}
+	}
+
+	fn placements_denied(messages: &[Clientbound]) -> usize {
+		messages
+			.iter()
+			.filter(|message| {
+				matches!(
+					message,
+					Clientbound::UiEvent(UiEvent::ActionDenied(ActionDenied::PlacementRateLimited))
+				)
+			})
+			.count()
+	}
+
+	#[test]
+	fn terrain_edits_are_rate_limited_with_placements() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let mut client = connect(&mut sector, &clock, Id::new());
+		messages(&mut client);
+
+		let burst = sector.structure_limits.placement_burst as usize;
+		let modify = modify_voxel(&sector, 1.0);
+
+		for _ in 0..burst {
+			client.send(Serverbound::ModifyVoxel(modify));
+		}
+
+		tick(&mut sector, &clock);
+		assert_eq!(placements_denied(&messages(&mut client)), 0);
+
+		client.send(Serverbound::ModifyVoxel(modify));
+		client.send(Serverbound::ModifyTerrainBrush(ModifyTerrainBrush {
+			center: VoxjectPosition::new(modify.coordinates.voxject, Point3::origin()),
+			radius: 1.0,
+			material: Material::Stone,
+			mode: BrushMode::Add,
+		}));
+		tick(&mut sector, &clock);
+
+		assert_eq!(placements_denied(&messages(&mut client)), 2);
+
+		clock.advance(Duration::from_secs(1));
+		client.send(Serverbound::ModifyVoxel(modify));
+		tick(&mut sector, &clock);
+
+		assert_eq!(placements_denied(&messages(&mut client)), 0);
+	}
+
+	#[test]
+	fn voxel_densities_must_be_in_range() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let id = Id::new();
+		nom(connect(&mut sector, &clock, id));
+
+		// Right next to the cell, so that it's within reach
+		let origin = sector
+			.voxjects
+			.values()
+			.next()
+			.unwrap()
+			.location
+			.translation
+			.vector;
+		let player = sector.players.iter_mut().find(|p| p.id == id).unwrap();
+		player.location.position = origin.into();
+		sector.dirty_chunks.clear();
+
+		let max = ModifyVoxel::MAX_DENSITY;
+
+		for density in [f32::NAN, f32::INFINITY, max + 1.0, -max - 1.0] {
+			sector.modify_voxel(id, modify_voxel(&sector, density));
+			assert!(sector.dirty_chunks.is_empty(), "{density} was allowed");
+		}
+
+		for density in [max, -max, 0.5] {
+			let modify = modify_voxel(&sector, density);
+			sector.modify_voxel(id, modify);
+
+			let chunk = sector.dirty_chunks.remove(&modify.coordinates).unwrap();
+			let data = chunk.data.try_read().unwrap();
+			assert_eq!(
+				data.as_ref().unwrap().density_at(modify.cell),
+				Some(density)
+			);
+		}
+	}
+
+	#[test]
+	fn saves_write_dirty_chunks_and_reply() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let coordinates = modify_voxel(&sector, 1.0).coordinates;
+		sector.modify_chunks([(coordinates, ())], |_, data, ()| {
+			data.set_voxel(Vector3::zeros(), Material::Stone, 1.0)
+				.unwrap();
+		});
+		assert!(sector.dirty_chunks.contains_key(&coordinates));
+
+		let (reply, mut receiver) = oneshot::channel();
+		let _ = sector
+			.shared
+			.sender
+			.send(Event::Save { reply: Some(reply) });
+		tick(&mut sector, &clock);
+
+		assert!(sector.dirty_chunks.is_empty());
+		assert!(sector.save_queue.is_in_progress());
+
+		// Written in the background, and finished on a later tick
+		let report = loop {
+			match receiver.try_recv() {
+				Ok(report) => break report,
+				Err(_) => {
+					thread::sleep(Duration::from_millis(1));
+					tick(&mut sector, &clock);
+				}
+			}
+		};
+
+		// Without a database there's nowhere to write them
+		assert_eq!(report.chunks, 0);
+		assert!(!sector.save_queue.is_in_progress());
+	}
+
+	#[test]
+	fn only_admins_can_pause_the_ambient_cycle() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let (admin, player) = (Id::new(), Id::new());
+		sector.admins.insert(admin);
+		let mut admin_client = connect(&mut sector, &clock, admin);
+		let mut player_client = connect(&mut sector, &clock, player);
+		messages(&mut admin_client);
+		messages(&mut player_client);
+
+		player_client.send(Serverbound::SetAmbientPaused(SetAmbientPaused {
+			paused: true,
+		}));
+		tick(&mut sector, &clock);
+		tick(&mut sector, &clock);
+
+		assert!(!sector.ambient.is_paused());
+		assert_eq!(
+			system_messages(&mut player_client),
+			["server.ambient_admin_only".into()]
+		);
+
+		admin_client.send(Serverbound::SetAmbientPaused(SetAmbientPaused {
+			paused: true,
+		}));
+		tick(&mut sector, &clock);
+		tick(&mut sector, &clock);
+
+		assert!(sector.ambient.is_paused());
+
+		// Everyone is told, so that their cycle stops too
+		let paused = |client: &mut Connection<ClientEnd>| {
+			messages(client).iter().any(
+				|message| matches!(message, Clientbound::Ambient(ambient) if ambient.rate == 0.0),
+			)
+		};
+
+		assert!(paused(&mut admin_client));
+		assert!(paused(&mut player_client));
+	}
+
+	#[test]
+	fn deleting_a_missing_structure_is_refused() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let player = Id::new();
+		let mut client = connect(&mut sector, &clock, player);
+
+		let structure = create_structure(&mut sector, &clock, &client);
+		delete_structure(&mut sector, &clock, &client, structure);
+		messages(&mut client);
+
+		// Already gone, such as when deleted twice before the first was handled
+		delete_structure(&mut sector, &clock, &client, structure);
+		assert_eq!(
+			system_messages(&mut client),
+			["server.structure_gone".into()]
+		);
+	}
+
+	#[test]
+	fn subscribers_during_generation_are_synced_exactly_once() {
+		let runtime = Runtime::new().unwrap();
+		let (sector, coordinates) = shared_sector(&runtime, slow_generator);
+
+		// Starts generating in the background
+		let chunk = sector.get_chunk(coordinates);
+
+		let subscribers = thread::scope(|scope| {
+			let handles = (0..32)
+				.map(|index| {
+					let sector = &sector;
+
+					scope.spawn(move || {
+						// Spread out so some subscribe before the data is set, some after, and some right as it is
+						thread::sleep(Duration::from_millis(index));
+
+						let (client, server) = Connection::pair();
+						let lock = ClientLock::new(sector, coordinates, server.sender(), None);
+						(client, lock)
+					})
+				})
+				.collect::<Vec<_>>();
+
+			handles
+				.into_iter()
+				.map(|handle| handle.join().unwrap())
+				.collect::<Vec<_>>()
+		});
+
+		nom(chunk.read_data_immediately());
+
+		for (mut client, lock) in subscribers {
+			assert!(lock.is_synced());
+			assert_eq!(syncs(&mut client), [0]);
+		}
+	}
+
+	#[test]
+	fn subscriptions_survive_churn_and_broadcasts() {
+		let runtime = Runtime::new().unwrap();
+		let (sector, coordinates) = shared_sector(&runtime, slow_generator);
+		let chunk = sector.get_chunk(coordinates);
+
+		let clients = thread::scope(|scope| {
+			let modify = scope.spawn(|| {
+				for _ in 0..100 {
+					chunk.modify_data(|_| {});
+					thread::yield_now();
+				}
+			});
+
+			let handles = (0..16)
+				.map(|_| {
+					let sector = &sector;
+
+					scope.spawn(move || {
+						let (mut client, server) = Connection::pair();
+						let mut syncs_seen = vec![];
+
+						for _ in 0..50 {
+							let lock = ClientLock::new(sector, coordinates, server.sender(), None);
+							thread::yield_now();
+							nom(lock);
+							syncs_seen.extend(syncs(&mut client));
+						}
+
+						let lock = ClientLock::new(sector, coordinates, server.sender(), None);
+						(client, lock, syncs_seen)
+					})
+				})
+				.collect::<Vec<_>>();
+
+			modify.join().unwrap();
+
+			handles
+				.into_iter()
+				.map(|handle| handle.join().unwrap())
+				.collect::<Vec<_>>()
+		});
+
+		// Unsubscribing leaves nothing behind
+		assert_eq!(chunk.subscribers().len(), clients.len());
+
+		chunk.modify_data(|_| {});
+		let last = chunk.generation();
+		assert_eq!(last, 101);
+
+		for (mut client, lock, mut generations) in clients {
+			assert!(lock.is_synced());
+			generations.extend(syncs(&mut client));
+
+			// Never sent something older than what was sent before
+			assert!(generations.is_sorted(), "{generations:?}");
+			assert_eq!(generations.last(), Some(&last));
+		}
+	}
+
+	#[test]
+	fn first_cell_is_set_and_read() {
+		let mut data = Data::default();
+
+		assert_eq!(
+			data.set_voxel(vector![0, 0, 0], Material::Stone, 1.5),
+			Ok(())
+		);
+
+		assert_eq!(data.material_at(vector![0, 0, 0]), Some(Material::Stone));
+		assert_eq!(data.density_at(vector![0, 0, 0]), Some(1.5));
+		assert_eq!(Data::index(vector![0, 0, 0]), Ok(0));
+		assert_eq!(
+			(data.materials[0], data.densities[0]),
+			(Material::Stone, 1.5)
+		);
+
+		// Neighbours on each axis are untouched
+		for cell in [vector![1, 0, 0], vector![0, 1, 0], vector![0, 0, 1]] {
+			assert_eq!(data.material_at(cell), Some(Material::Nothing));
+			assert_eq!(data.density_at(cell), Some(0.0));
+		}
+	}
+
+	#[test]
+	fn last_cell_is_set_and_read() {
+		let mut data = Data::default();
+		let last = vector![LAST, LAST, LAST];
+
+		assert_eq!(data.set_voxel(last, Material::Corium, -2.0), Ok(()));
+
+		assert_eq!(data.material_at(last), Some(Material::Corium));
+		assert_eq!(data.density_at(last), Some(-2.0));
+		assert_eq!(Data::index(last), Ok(CELLS_PER_CHUNK - 1));
+		assert_eq!(
+			(
+				data.materials[CELLS_PER_CHUNK - 1],
+				data.densities[CELLS_PER_CHUNK - 1]
+			),
+			(Material::Corium, -2.0)
+		);
+	}
+
+	#[test]
+	fn cells_outside_the_chunk_are_rejected() {
+		let mut data = Data::default();
+
+		for cell in [
+			vector![CHUNK_SIZE as u8, 0, 0],
+			vector![0, CHUNK_SIZE as u8, 0],
+			vector![0, 0, CHUNK_SIZE as u8],
+			vector![u8::MAX, u8::MAX, u8::MAX],
+		] {
+			assert_eq!(
+				data.set_voxel(cell, Material::Stone, 1.0),
+				Err(OutsideChunk(cell))
+			);
+			assert_eq!(data.material_at(cell), None);
+			assert_eq!(data.density_at(cell), None);
+			assert_eq!(Data::index(cell), Err(OutsideChunk(cell)));
+		}
+
+		// Rather than wrapping around onto a cell that is in the chunk
+		assert!(data
+			.iter_voxels()
+			.all(|(_, material, density)| { material == Material::Nothing && density == 0.0 }));
+	}
+
+	#[test]
+	fn iter_voxels_visits_every_cell_in_storage_order() {
+		let mut data = Data::default();
+		data.set_voxel(vector![0, 0, 0], Material::Stone, 1.0)
+			.unwrap();
+		data.set_voxel(vector![LAST, LAST, LAST], Material::Ground, 2.0)
+			.unwrap();
+		data.set_voxel(vector![1, 2, 3], Material::Corium, 3.0)
+			.unwrap();
+
+		let voxels = data.iter_voxels().collect::<Vec<_>>();
+
+		assert_eq!(voxels.len(), CELLS_PER_CHUNK);
+		assert_eq!(voxels[0], (vector![0, 0, 0], Material::Stone, 1.0));
+		assert_eq!(
+			voxels[CELLS_PER_CHUNK - 1],
+			(vector![LAST, LAST, LAST], Material::Ground, 2.0)
+		);
+
+		for (index, (cell, material, density)) in voxels.into_iter().enumerate() {
+			assert_eq!(Data::index(cell), Ok(index));
+			assert_eq!(data.material_at(cell), Some(material));
+			assert_eq!(data.density_at(cell), Some(density));
+		}
+	}
+
+	/// A chunk with every cell set by `cell`, given the cell's coordinates.
+	fn data(cell: impl Fn(Vector3<u8>) -> (Material, f32)) -> Data {
+		let mut data = Data::default();
+
+		for x in 0..=LAST {
+			for y in 0..=LAST {
+				for z in 0..=LAST {
+					let (material, density) = cell(vector![x, y, z]);
+					data.set_voxel(vector![x, y, z], material, density).unwrap();
+				}
+			}
+		}
+
+		data
+	}
+
+	/// Triangulates a chunk surrounded by copies of itself, then builds a collider from it, the whole way a chunk's
+	/// collider is made. Returns the unsanitized triangle count along with the collider.
+	fn collider(data: &Data) -> (usize, Option<Collider>) {
+		let collision = Collision::build([data; 8]);
+		let collider = trimesh::collider(&collision.vertices, &collision.indices);
+
+		(
+			collision.indices.len(),
+			collider.map(|collider| collider.build()),
+		)
+	}
+
+	fn triangles(collider: &Collider) -> usize {
+		collider.shape().as_trimesh().unwrap().indices().len()
+	}
+
+	#[test]
+	fn all_zero_chunk_has_no_collider() {
+		let (raw, collider) = collider(&Data::default());
+
+		assert_eq!(raw, 0);
+		assert!(collider.is_none());
+	}
+
+	#[test]
+	fn solid_chunk_at_iso_value_has_no_collider() {
+		let (raw, collider) = collider(&data(|_| (Material::Stone, 0.0)));
+
+		assert_eq!(raw, 0);
+		assert!(collider.is_none());
+	}
+
+	#[test]
+	fn flat_field_at_iso_value_has_collider() {
+		// Every density exactly 0, so vertices sit half way along each edge
+		let (raw, collider) = collider(&data(|cell| match cell.y < 8 {
+			true => (Material::Stone, 0.0),
+			false => (Material::Nothing, 0.0),
+		}));
+
+		let collider = collider.expect("a flat surface should collide");
+		assert!(raw > 0);
+		assert_eq!(triangles(&collider), raw);
+	}
+
+	#[test]
+	fn surface_made_only_of_iso_corners_has_no_collider() {
+		// Every vertex lands on the solid corner, so every triangle has no area at all
+		let (raw, collider) = collider(&data(|cell| {
+			match cell.iter().all(|coordinate| coordinate % 4 == 0) {
+				true => (Material::Stone, 0.0),
+				false => (Material::Nothing, -1.0),
+			}
+		}));
+
+		assert!(raw > 0);
+		assert!(collider.is_none());
+	}
+
+	#[test]
+	fn iso_corners_are_dropped_from_surface() {
+		// A surface with solid cells exactly at the iso value floating above it, which only make zero area triangles
+		let (raw, collider) = collider(&data(|cell| match cell.y {
+			0..=6 => (Material::Stone, 1.0),
+			9 if cell.x % 4 == 0 && cell.z % 4 == 0 => (Material::Stone, 0.0),
+			_ => (Material::Nothing, -1.0),
+		}));
+
+		let collider = collider.expect("the rest of the surface should collide");
+		assert!(triangles(&collider) < raw);
+		assert!(triangles(&collider) > 0);
+	}
+
+	fn structures_denied(messages: &[Clientbound]) -> Vec<ActionDenied> {
+		messages
+			.iter()
+			.filter_map(|message| match message {
+				Clientbound::UiEvent(UiEvent::ActionDenied(denied)) => Some(*denied),
+				_ => None,
+			})
+			.collect()
+	}
+
+	#[test]
+	fn structure_limits_count_pending_and_deleted_structures() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+		sector.structure_limits.per_player = 2;
+		sector.structure_limits.per_sector = 3;
+
+		let (a, b) = (Id::new(), Id::new());
+		let mut a_client = connect(&mut sector, &clock, a);
+		let mut b_client = connect(&mut sector, &clock, b);
+		messages(&mut a_client);
+		messages(&mut b_client);
+
+		// All in one tick, before any of them have been added to the sector
+		for _ in 0..3 {
+			a_client.send(Serverbound::CreateStructure(CreateStructure {
+				location: location(),
+				block: BlockType::Block,
+			}));
+		}
+		tick(&mut sector, &clock);
+		tick(&mut sector, &clock);
+
+		assert_eq!(sector.structures.len(), 2);
+		assert_eq!(
+			structures_denied(&messages(&mut a_client)),
+			[ActionDenied::PlayerStructureLimit { max: 2 }]
+		);
+
+		create_structure(&mut sector, &clock, &b_client);
+		b_client.send(Serverbound::CreateStructure(CreateStructure {
+			location: location(),
+			block: BlockType::Block,
+		}));
+		tick(&mut sector, &clock);
+		tick(&mut sector, &clock);
+
+		assert_eq!(sector.structures.len(), 3);
+		assert_eq!(
+			structures_denied(&messages(&mut b_client)),
+			[ActionDenied::SectorStructureLimit { max: 3 }]
+		);
+
+		// Deleting frees up room for the owner again
+		let owned = sector
+			.structures
+			.iter()
+			.find(|structure| structure.owner == a)
+			.unwrap()
+			.id;
+		delete_structure(&mut sector, &clock, &a_client, owned);
+		create_structure(&mut sector, &clock, &a_client);
+
+		assert_eq!(structures_denied(&messages(&mut a_client)), []);
+	}
+
+	#[test]
+	fn structures_move_the_same_at_any_tick_rate() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+
+		let moved = |tick_rate: u32| {
+			let clock = Arc::new(MockClock::new());
+			let config = hocon::de::from_str(&format!(
+				"name: test\ntick_rate: {tick_rate}\nvoxjects: [{{ name: test }}]"
+			))
+			.unwrap();
+			let mut sector = Sector::new(Persistence::memory(), clock.clone(), config).unwrap();
+
+			let client = connect(&mut sector, &clock, Id::new());
+			let structure = create_structure(&mut sector, &clock, &client);
+			let rigid_body = *sector
+				.structures
+				.iter()
+				.find(|s| s.id == structure)
+				.unwrap()
+				.rigid_body;
+
+			// Damped, so that the result depends on the step size if physics isn't stepped by the tick duration
+			let body = sector.physics.get_rigid_body_mut(rigid_body).unwrap();
+			let start = *body.translation();
+			body.set_linear_damping(0.5);
+			body.set_linvel(vector![0.0, -10.0, 0.0], true);
+
+			for _ in 0..tick_rate {
+				tick(&mut sector, &clock);
+			}
+
+			sector
+				.physics
+				.get_rigid_body(rigid_body)
+				.unwrap()
+				.translation()
+				- start
+		};
+
+		let (slow, fast) = (moved(20), moved(60));
+
+		assert!(slow.y < -5.0, "{slow}");
+		assert!(
+			(slow - fast).norm() < 0.05,
+			"{slow} at 20Hz, {fast} at 60Hz"
+		);
+	}
+
+	#[test]
+	fn restoring_keeps_chunks_saved_since_the_snapshot() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let voxject = sector.voxjects.values().next().unwrap().id;
+		let saved = |x| ChunkCoordinates::new(voxject, vector![x, 0, 0], Level::new(0));
+		sector.saved_chunks.insert(saved(1), 2);
+		sector.saved_chunks.insert(saved(2), 2);
+
+		let chunk = |voxject: &str, x, generation| SnapshotChunk {
+			voxject: voxject.into(),
+			position: vector![x, 0, 0],
+			generation,
+			materials: vec![],
+			densities: vec![],
+		};
+
+		let (structure, player) = (Id::new(), Id::new());
+		let report = sector
+			.restore(Snapshot {
+				sector: "test".into(),
+				chunks: vec![
+					// Never saved
+					chunk("test", 0, 0),
+					// Saved once more since the snapshot
+					chunk("test", 1, 1),
+					// Not saved since the snapshot
+					chunk("test", 2, 2),
+					// The voxject has since been removed
+					chunk("removed", 0, 0),
+				],
+				structures: vec![SyncStructure {
+					id: structure,
+					owner: player,
+					location: location(),
+					linear_velocity: vector![0.0, -3.0, 0.0],
+					angular_velocity: Vector3::zeros(),
+					sleeping: false,
+					blocks: [(vector![0, 0, 0], BlockType::Block)].into_iter().collect(),
+					block_states: Default::default(),
+				}],
+				players: vec![(player, location())],
+				ambient_phase: 0.25,
+				ambient_paused: true,
+			})
+			.unwrap();
+
+		assert_eq!(
+			(
+				report.chunks,
+				report.skipped_chunks,
+				report.structures,
+				report.players
+			),
+			(2, 2, 1, 1)
+		);
+
+		let restored = sector
+			.structures
+			.iter()
+			.find(|s| s.id == structure)
+			.unwrap();
+		assert_eq!(restored.owner, player);
+		let rigid_body = sector.physics.get_rigid_body(*restored.rigid_body).unwrap();
+		assert_eq!(*rigid_body.linvel(), vector![0.0, -3.0, 0.0]);
+
+		assert_eq!(sector.ambient.phase(), 0.25);
+		assert!(sector.ambient.is_paused());
+		assert!(sector.restored_locations.contains_key(&player));
+	}
+
+	#[test]
+	fn snapshots_of_other_sectors_are_refused() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let result = sector.restore(Snapshot {
+			sector: "other".into(),
+			chunks: vec![],
+			structures: vec![],
+			players: vec![],
+			ambient_phase: 0.0,
+			ambient_paused: false,
+		});
+
+		assert!(matches!(result, Err(SnapshotError::WrongSector(sector)) if &*sector == "other"));
+	}
+
+	#[test]
+	fn explosives_damage_and_push_structures_nearby() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let mut add = |offset: f32, block: BlockType| {
+			let mut location = location();
+			location.position.x += offset;
+
+			let structure = Structure::new_from_sync(
+				&mut sector.physics,
+				SyncStructure {
+					id: Id::new(),
+					owner: Id::new(),
+					location,
+					linear_velocity: Vector3::zeros(),
+					angular_velocity: Vector3::zeros(),
+					sleeping: false,
+					blocks: [(Vector3::zeros(), block)].into_iter().collect(),
+					block_states: Default::default(),
+				},
+			);
+
+			let id = structure.id;
+			sector.add_structure(structure);
+			id
+		};
+
+		let explosive = add(0.0, BlockType::Explosive);
+		let near = add(2.0, BlockType::Block);
+		let far = add(5.0, BlockType::Block);
+
+		let find = |sector: &Sector, id: Id| {
+			sector
+				.structures
+				.iter()
+				.find(|s| s.id == id)
+				.map(|s| *s.rigid_body)
+		};
+
+		let fuse = (Explosion::FUSE.as_secs_f32() * sector.tick_rate as f32).round() as u32;
+
+		for _ in 1..fuse {
+			tick(&mut sector, &clock);
+		}
+
+		assert!(
+			find(&sector, explosive).is_some(),
+			"exploded before the fuse ran out"
+		);
+
+		tick(&mut sector, &clock);
+
+		assert!(find(&sector, explosive).is_none());
+		assert!(
+			find(&sector, near).is_none(),
+			"close enough to be destroyed"
+		);
+
+		let far = find(&sector, far).expect("far enough to survive");
+		let velocity = *sector.physics.get_rigid_body(far).unwrap().linvel();
+		assert!(
+			velocity.x > 0.0,
+			"pushed away from the explosion, {velocity}"
+		);
+
+		let health = sector
+			.structures
+			.iter()
+			.flat_map(|s| s.iter_blocks())
+			.map(|(_, block)| block.health);
+		assert!(
+			health.eq([BlockType::Block.max_health() - Explosion::EXPLOSIVE_BLOCK.damage_at(5.0)])
+		);
+	}
+
+	#[test]
+	fn players_are_sent_the_world_radius() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+
+		for (config, radius) in [("", None), ("world_radius: 250\n", Some(250.0))] {
+			let clock = Arc::new(MockClock::new());
+			let config =
+				hocon::de::from_str(&format!("name: test\n{config}voxjects: [{{ name: test }}]"))
+					.unwrap();
+			let mut sector = Sector::new(Persistence::memory(), clock.clone(), config).unwrap();
+
+			let mut client = connect(&mut sector, &clock, Id::new());
+
+			let Some(Clientbound::Sync(sync)) = messages(&mut client).into_iter().next() else {
+				panic!("the first message should be a sync");
+			};
+			assert_eq!(sync.world_radius, radius);
+		}
+	}
+
+	#[test]
+	fn sectors_tick_by_their_clock() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let sector = sector(&clock);
+
+		let shared = sector.shared.clone();
+		let tick_duration = sector.tick_duration();
+		let running = thread::spawn(move || sector.run());
+
+		// The first tick runs straight away, then it waits for the clock before handling the shutdown
+		let _ = shared.send(Event::Shutdown);
+		thread::sleep(Duration::from_millis(100));
+		assert!(!running.is_finished());
+
+		for _ in 0..100 {
+			clock.advance(tick_duration);
+			thread::sleep(Duration::from_millis(10));
+
+			if running.is_finished() {
+				return running.join().unwrap();
+			}
+		}
+
+		panic!("the sector didn't shut down");
+	}
+
+	#[test]
+	fn joining_players_get_chat_history_then_live_messages() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let chat = |sector: &Sector, player, text: &str| {
+			let _ = sector.send(Event::ChatMessage {
+				player,
+				text: text.into(),
+			});
+		};
+
+		let speaker = Id::new();
+		let mut early = connect(&mut sector, &clock, speaker);
+		chat(&sector, speaker, "zero");
+		chat(&sector, speaker, "one");
+		tick(&mut sector, &clock);
+
+		// Said in the same tick as the join, after it
+		let (mut client, server) = Connection::pair();
+		let _ = sector.send(Event::PlayerConnected(Id::new(), server, None));
+		chat(&sector, speaker, "two");
+		tick(&mut sector, &clock);
+		tick(&mut sector, &clock);
+		chat(&sector, speaker, "three");
+		tick(&mut sector, &clock);
+
+		let mut history = vec![];
+		let mut live = vec![];
+
+		for message in messages(&mut client) {
+			match message {
+				Clientbound::ChatHistory(ChatHistory(messages)) => {
+					assert!(live.is_empty(), "history should come before live messages");
+					history.extend(messages.into_iter().map(|message| message.sequence));
+				}
+				Clientbound::ChatMessage(message) => live.push(message.sequence),
+				_ => {}
+			}
+		}
+
+		assert_eq!(history, [0, 1]);
+		assert_eq!(live, [2, 3]);
+
+		// Retracted for everyone, and gone from the history the next player gets
+		messages(&mut early);
+		assert!(sector.retract_chat_message(1));
+		assert!(messages(&mut early).iter().any(|message| matches!(
+			message,
+			Clientbound::ChatRetract(ChatRetract { sequence: 1 })
+		)));
+
+		let mut late = connect(&mut sector, &clock, Id::new());
+		let history = messages(&mut late)
+			.into_iter()
+			.find_map(|message| match message {
+				Clientbound::ChatHistory(ChatHistory(messages)) => Some(messages),
+				_ => None,
+			});
+		let history = history
+			.unwrap()
+			.iter()
+			.map(|message| message.sequence)
+			.collect::<Vec<_>>();
+		assert_eq!(history, [0, 2, 3]);
+	}
+
+	/// Runs `f` on a pool with a single thread, so that collision rebuilds it triggers are queued until it returns.
+	/// Returns once they've all run.
+	fn with_queued_rebuilds(f: impl FnOnce() + Send) {
+		let pool = rayon::ThreadPoolBuilder::new()
+			.num_threads(1)
+			.build()
+			.unwrap();
+
+		pool.install(f);
+
+		// The pool's only thread runs anything queued from within it before anything injected from outside
+		pool.install(|| {});
+	}
+
+	#[test]
+	fn released_chunks_are_dropped_with_a_rebuild_queued() {
+		let runtime = Runtime::new().unwrap();
+		let (sector, coordinates) = shared_sector(&runtime, |_| Default::default());
+
+		let chunk = Mutex::new(None);
+		with_queued_rebuilds(|| {
+			let lock = TickLock::new(&sector, coordinates);
+			*chunk.lock().unwrap() = Some(Arc::downgrade(&lock.0));
+			drop(lock);
+
+			// Nothing is waiting on the pool to let go of it
+			assert!(chunk.lock().unwrap().as_ref().unwrap().upgrade().is_none());
+		});
+	}
+
+	#[test]
+	fn released_chunks_skip_queued_rebuilds() {
+		let runtime = Runtime::new().unwrap();
+		let (sector, coordinates) = shared_sector(&runtime, |_| Default::default());
+
+		// Loaded up front, so that building collision doesn't wait on generation
+		let chunks = coordinates
+			.mesh_dependencies()
+			.unwrap()
+			.map(|coordinates| sector.get_chunk(coordinates));
+		for chunk in &chunks {
+			nom(chunk.read_data_immediately());
+		}
+
+		let chunk = sector.get_chunk(coordinates);
+		with_queued_rebuilds(|| drop(TickLock::new(&sector, coordinates)));
+		assert!(chunk.collision.try_read().unwrap().is_none());
+
+		// Whereas a chunk that's still locked gets it's collision
+		let lock = Mutex::new(None);
+		with_queued_rebuilds(|| *lock.lock().unwrap() = Some(TickLock::new(&sector, coordinates)));
+		assert!(chunk.collision.try_read().unwrap().is_some());
+	}
+
+	#[test]
+	fn triangulation_gives_up_once_cancelled() {
+		let cells = CollisionCells::default();
+		let checks = AtomicUsize::new(0);
+
+		let cancelled = || checks.fetch_add(1, Relaxed) == 3;
+		assert!(cells.triangulate(&cancelled).is_none());
+		assert_eq!(checks.load(Relaxed), 4);
+
+		assert!(cells.triangulate(&|| false).is_some());
+	}
+
+	#[test]
+	fn players_past_the_limit_are_turned_away() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let config =
+			hocon::de::from_str("name: test\nmax_players: 1\nvoxjects: [{ name: test }]").unwrap();
+		let mut sector = Sector::new(Persistence::memory(), clock.clone(), config).unwrap();
+
+		let _first = connect(&mut sector, &clock, Id::new());
+		assert_eq!(sector.player_count.load(Relaxed), 1);
+
+		let mut second = connect(&mut sector, &clock, Id::new());
+
+		assert!(messages(&mut second).iter().any(|message| matches!(
+			message,
+			Clientbound::Disconnect(Disconnect {
+				reason: DisconnectReason::SectorFull {
+					players: 1,
+					capacity: 1
+				}
+			})
+		)));
+		assert_eq!(sector.players.len(), 1);
+		assert_eq!(sector.player_count.load(Relaxed), 1);
+	}
+
+	#[test]
+	fn pings_are_answered_on_the_next_tick() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let mut client = connect(&mut sector, &clock, Id::new());
+		messages(&mut client);
+
+		client.send(Ping { sequence: 7 });
+		tick(&mut sector, &clock);
+
+		let pongs = messages(&mut client)
+			.into_iter()
+			.filter_map(|message| match message {
+				Clientbound::Pong(Pong { sequence }) => Some(sequence),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		assert_eq!(pongs, [7]);
+	}
+
+	#[test]
+	fn arriving_players_are_put_where_they_left() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let player = Id::new();
+		let arrival = Location {
+			position: Point3::new(0.0, 2000.0, 0.0),
+			rotation: UnitQuaternion::from_euler_angles(0.0, 1.0, 0.0),
+		};
+
+		// Where they'd be put back from a snapshot, the transfer is more recent
+		sector.restored_locations.insert(player, location());
+
+		let (mut client, server) = Connection::pair();
+		let _ = sector.send(Event::PlayerConnected(player, server, Some(arrival)));
+		tick(&mut sector, &clock);
+
+		assert_eq!(sector.players[0].location.position, arrival.position);
+		assert_eq!(sector.players[0].location.rotation, arrival.rotation);
+		assert!(!sector.restored_locations.contains_key(&player));
+		assert!(messages(&mut client).iter().any(|message| matches!(
+			message,
+			Clientbound::Teleport(Teleport { position }) if *position == arrival.position
+		)));
+	}
+
+	#[test]
+	fn transfers_need_a_known_destination_and_a_database() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let config = hocon::de::from_str(
+			"name: test\ntransfers: { destinations: { other: \"http://gateway\" } }\nvoxjects: [{ name: test }]",
+		)
+		.unwrap();
+		let mut sector = Sector::new(Persistence::memory(), clock.clone(), config).unwrap();
+
+		let player = Id::new();
+		let _client = connect(&mut sector, &clock, player);
+
+		assert!(matches!(
+			sector.transfer(player, "nowhere"),
+			Err(TransferError::UnknownDestination(destination)) if &*destination == "nowhere"
+		));
+		assert!(matches!(
+			sector.transfer(player, "other"),
+			Err(TransferError::NoDatabase)
+		));
+
+		// Nothing was recorded, so they stay
+		assert_eq!(sector.players.len(), 1);
+	}
+
+	#[test]
+	fn idle_players_are_shrunk_then_kicked() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let config = hocon::de::from_str(
+			"name: test\nafk: { timeout: 1, kick_after: 2 }\nvoxjects: [{ name: test }]",
+		)
+		.unwrap();
+		let mut sector = Sector::new(Persistence::memory(), clock.clone(), config).unwrap();
+
+		let mut client = connect(&mut sector, &clock, Id::new());
+		let locks = |sector: &Sector| sector.players[0].compute_locks(&sector.shared).0.len();
+		let active_locks = locks(&sector);
+		messages(&mut client);
+
+		// Pings are sent by the client on it's own
+		clock.advance(Duration::from_secs(1));
+		client.send(Ping { sequence: 0 });
+		tick(&mut sector, &clock);
+
+		assert!(sector.players[0].idle.is_away());
+		assert!(locks(&sector) < active_locks);
+		assert!(system_messages(&mut client)
+			.iter()
+			.any(|key| &**key == "server.afk"));
+
+		client.send(Serverbound::SendChatMessage(SendChatMessage {
+			text: "back".into(),
+		}));
+		tick(&mut sector, &clock);
+
+		assert!(!sector.players[0].idle.is_away());
+		assert_eq!(locks(&sector), active_locks);
+
+		clock.advance(Duration::from_secs(1));
+		tick(&mut sector, &clock);
+		assert!(sector.players[0].idle.is_away());
+		messages(&mut client);
+
+		clock.advance(Duration::from_secs(2));
+		tick(&mut sector, &clock);
+
+		assert!(sector.players.is_empty());
+		assert_eq!(sector.idle_kicks, 1);
+		assert!(messages(&mut client).iter().any(|message| matches!(
+			message,
+			Clientbound::Disconnect(Disconnect {
+				reason: DisconnectReason::Idle
+			})
+		)));
+	}
+
+	#[test]
+	fn cached_chunks_are_only_sent_again_if_they_changed() {
+		let runtime = Runtime::new().unwrap();
+		let (sector, coordinates) = shared_sector(&runtime, slow_generator);
+		let chunk = sector.get_chunk(coordinates);
+
+		let sent = |client: &mut Connection<ClientEnd>| {
+			messages(client)
+				.into_iter()
+				.filter_map(|message| match message {
+					Clientbound::SyncChunk(sync) => Some((false, sync.content_hash())),
+					Clientbound::ChunkUnchanged(unchanged) => Some((true, unchanged.content_hash)),
+					_ => None,
+				})
+				.collect::<Vec<_>>()
+		};
+
+		let hash = Data::default().build_sync(coordinates, 0).content_hash();
+
+		// Subscribed while the chunk is still being generated, so it's Chunk's sync that checks the cached copy
+		let (mut early, server) = Connection::pair();
+		let early_lock = ClientLock::new(&sector, coordinates, server.sender(), Some(hash));
+		let (mut early_miss, server) = Connection::pair();
+		let early_miss_lock =
+			ClientLock::new(&sector, coordinates, server.sender(), Some(hash ^ 1));
+
+		nom(chunk.read_data_immediately());
+		assert_eq!(sent(&mut early), [(true, hash)]);
+		assert_eq!(sent(&mut early_miss), [(false, hash)]);
+
+		let (mut hit, server) = Connection::pair();
+		let hit_lock = ClientLock::new(&sector, coordinates, server.sender(), Some(hash));
+		assert_eq!(sent(&mut hit), [(true, hash)]);
+
+		let (mut miss, server) = Connection::pair();
+		let _miss_lock = ClientLock::new(&sector, coordinates, server.sender(), Some(hash ^ 1));
+		assert_eq!(sent(&mut miss), [(false, hash)]);
+
+		let (mut uncached, server) = Connection::pair();
+		let _uncached_lock = ClientLock::new(&sector, coordinates, server.sender(), None);
+		assert_eq!(sent(&mut uncached), [(false, hash)]);
+
+		// The client couldn't load it's cached copy after all
+		hit_lock.resync();
+		assert_eq!(sent(&mut hit), [(false, hash)]);
+
+		nom((early_lock, early_miss_lock));
+	}
+
+	#[test]
+	fn players_only_have_so_many_chunks_remembered() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let client = connect(&mut sector, &clock, Id::new());
+		let voxject = *sector.shared.voxjects.keys().next().unwrap();
+
+		// Far enough away that none of them are client locked, which would use them up
+		let far_away = (0..HaveChunks::MAX_ENTRIES as i32 + HaveChunks::BATCH_SIZE as i32)
+			.map(|x| {
+				(
+					ChunkCoordinates::new(voxject, vector![x, 1 << 20, 0], Level::new(0)),
+					1,
+				)
+			})
+			.collect::<Vec<_>>();
+
+		for entries in far_away.chunks(HaveChunks::BATCH_SIZE) {
+			client.send(Serverbound::HaveChunks(HaveChunks {
+				entries: entries.to_vec(),
+			}));
+		}
+		tick(&mut sector, &clock);
+
+		let cached = &sector.players[0].cached_chunks;
+		assert_eq!(cached.len(), HaveChunks::MAX_ENTRIES);
+
+		// The first are kept, the rest ignored
+		assert!(cached.contains_key(&far_away[0].0));
+		assert!(!cached.contains_key(&far_away.last().unwrap().0));
+	}
+
+	#[test]
+	fn using_blocks_switches_them_and_opens_containers() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let mut add = |offset: f32, block: BlockType| {
+			let mut location = location();
+			location.position.x += offset;
+
+			let structure = Structure::new_from_sync(
+				&mut sector.physics,
+				SyncStructure {
+					id: Id::new(),
+					owner: Id::new(),
+					location,
+					linear_velocity: Vector3::zeros(),
+					angular_velocity: Vector3::zeros(),
+					sleeping: true,
+					blocks: [(Vector3::zeros(), block)].into_iter().collect(),
+					block_states: Default::default(),
+				},
+			);
+
+			let id = structure.id;
+			sector.add_structure(structure);
+			id
+		};
+
+		let switch = add(3.0, BlockType::Switch);
+		let container = add(-3.0, BlockType::Container);
+		let far_switch = add(50.0, BlockType::Switch);
+
+		let player = Id::new();
+		let mut client = connect(&mut sector, &clock, player);
+		let mut other = connect(&mut sector, &clock, Id::new());
+		tick(&mut sector, &clock);
+		messages(&mut client);
+		messages(&mut other);
+
+		let mut interact = |sector: &mut Sector, structure: Id| {
+			client.send(Serverbound::InteractBlock(InteractBlock {
+				structure,
+				position: Vector3::zeros(),
+			}));
+
+			// Sent as an event on one tick, and carried out on the next
+			tick(sector, &clock);
+			tick(sector, &clock);
+
+			messages(&mut client)
+		};
+
+		let state = |sector: &Sector, structure: Id| {
+			let structure = sector.structures.iter().find(|s| s.id == structure);
+			structure
+				.unwrap()
+				.get_block(&Vector3::zeros())
+				.unwrap()
+				.state
+		};
+
+		let switched = |messages: &[Clientbound]| {
+			messages
+				.iter()
+				.filter_map(|message| match message {
+					Clientbound::SetBlockState(SetBlockState {
+						structure, state, ..
+					}) => Some((*structure, *state)),
+					_ => None,
+				})
+				.collect::<Vec<_>>()
+		};
+
+		// Everyone that has the switch sees it change
+		let sent = interact(&mut sector, switch);
+		assert_eq!(state(&sector, switch), 1);
+		assert_eq!(switched(&sent), [(switch, 1)]);
+		assert_eq!(switched(&messages(&mut other)), [(switch, 1)]);
+
+		let sent = interact(&mut sector, switch);
+		assert_eq!(state(&sector, switch), 0);
+		assert_eq!(switched(&sent), [(switch, 0)]);
+
+		// Out of reach
+		let sent = interact(&mut sector, far_switch);
+		assert_eq!(state(&sector, far_switch), 0);
+		assert_eq!(switched(&sent), []);
+
+		// Only whoever opened the container is sent what's in it
+		let sent = interact(&mut sector, container);
+		assert!(sent.iter().any(|message| matches!(
+			message,
+			Clientbound::SyncContainer(SyncContainer { structure, .. }) if *structure == container
+		)));
+		assert!(!messages(&mut other)
+			.iter()
+			.any(|message| matches!(message, Clientbound::SyncContainer(_))));
+		assert_eq!(state(&sector, container), 0);
+	}
+
+	#[test]
+	fn structures_are_sent_nearest_first_before_the_join_completes() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let budget = Interest::budget(sector.tick_rate);
+
+		// Added furthest first, so that they'd be sent in the wrong order if they weren't sorted
+		let mut nearest_first = (1..=budget * 2 + 3)
+			.rev()
+			.map(|distance| {
+				let mut location = location();
+				location.position.x += distance as f32 * 2.0;
+
+				let structure = Structure::new_from_sync(
+					&mut sector.physics,
+					SyncStructure {
+						id: Id::new(),
+						owner: Id::new(),
+						location,
+						linear_velocity: Vector3::zeros(),
+						angular_velocity: Vector3::zeros(),
+						sleeping: true,
+						blocks: [(Vector3::zeros(), BlockType::Block)].into_iter().collect(),
+						block_states: Default::default(),
+					},
+				);
+
+				let id = structure.id;
+				sector.add_structure(structure);
+				id
+			})
+			.collect::<Vec<_>>();
+		nearest_first.reverse();
+
+		let mut sent = vec![];
+		let mut complete = false;
+
+		let mut receive = |client: &mut Connection<ClientEnd>, sent: &mut Vec<Id>| {
+			for message in messages(client) {
+				match message {
+					Clientbound::SyncStructure(structure) => sent.push(structure.id),
+					Clientbound::JoinComplete(_) => {
+						assert!(!complete, "the join was completed twice");
+						complete = true;
+					}
+					_ => {}
+				}
+			}
+
+			complete
+		};
+
+		let (mut client, server) = Connection::pair();
+		let _ = sector.send(Event::PlayerConnected(Id::new(), server, None));
+		tick(&mut sector, &clock);
+		tick(&mut sector, &clock);
+
+		// Nothing is sent until it's known where they are
+		assert!(!receive(&mut client, &mut sent));
+		assert_eq!(sent, []);
+
+		client.send(Serverbound::PlayerLocation(location()));
+
+		for batch in nearest_first.chunks(budget) {
+			tick(&mut sector, &clock);
+			let complete = receive(&mut client, &mut sent);
+
+			// Whichever are sent on the same tick arrive together, so only the order between ticks matters
+			let received = sent[sent.len() - batch.len()..]
+				.iter()
+				.collect::<HashSet<_>>();
+			assert_eq!(received, batch.iter().collect());
+			assert_eq!(
+				sector.players[0].structure_backlog,
+				nearest_first.len() - sent.len()
+			);
+			assert!(!complete || sent.len() == nearest_first.len());
+		}
+
+		assert_eq!(sent.len(), nearest_first.len());
+
+		// Then once the level 0 chunks around them have been generated and sent
+		for _ in 0..10_000 {
+			if receive(&mut client, &mut sent) {
+				break;
+			}
+
+			thread::sleep(Duration::from_millis(1));
+			tick(&mut sector, &clock);
+		}
+
+		assert!(receive(&mut client, &mut sent));
+		assert!(!sector.players[0].joining);
+		assert_eq!(sent.len(), nearest_first.len());
+	}
+
+	#[test]
+	fn inventories_are_loaded_after_joining_and_only_sent_once() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let inventories = |client: &mut Connection<ClientEnd>| {
+			messages(client)
+				.into_iter()
+				.filter_map(|message| match message {
+					Clientbound::SyncInventory(inventory) => Some(inventory.used()),
+					_ => None,
+				})
+				.collect::<Vec<_>>()
+		};
+
+		let player = Id::new();
+		let mut client = connect(&mut sector, &clock, player);
+
+		let mut sent = vec![];
+		for _ in 0..1000 {
+			sent.extend(inventories(&mut client));
+			if !sent.is_empty() {
+				break;
+			}
+
+			thread::sleep(Duration::from_millis(1));
+			tick(&mut sector, &clock);
+		}
+
+		assert_eq!(sent, [0]);
+		assert!(sector.players[0].inventory_synced);
+
+		// A load finishing after the inventory has already been sent is out of date
+		let stale = SyncInventory {
+			slots: vec![InventorySlot {
+				item: Item::TestOre,
+				quantity: 5,
+			}],
+			capacity: InventoryCapacity::default(),
+		};
+		let _ = sector.send(Event::InventoryLoaded {
+			player,
+			inventory: Ok(stale),
+		});
+		tick(&mut sector, &clock);
+		tick(&mut sector, &clock);
+
+		assert!(inventories(&mut client).is_empty());
+	}
+
+	#[test]
+	fn only_valid_view_hints_are_kept() {
+		let runtime = Runtime::new().unwrap();
+		let _runtime = runtime.enter();
+		let clock = Arc::new(MockClock::new());
+		let mut sector = sector(&clock);
+
+		let client = connect(&mut sector, &clock, Id::new());
+
+		let hint = |sector: &mut Sector, hint: ViewHint| {
+			client.send(Serverbound::ViewHint(hint));
+			tick(sector, &clock);
+			sector.players[0].view_hint
+		};
+
+		let valid = ViewHint::new(-Vector3::z(), 1.0, 1.0);
+		assert_eq!(hint(&mut sector, valid), Some(valid));
+
+		// Rejected, leaving the last valid one in place
+		let stretched = ViewHint {
+			forward: -Vector3::z() * 2.0,
+			..valid
+		};
+		assert_eq!(hint(&mut sector, stretched), Some(valid));
+
+		let too_wide = ViewHint {
+			fov_cos: -2.0,
+			..valid
+		};
+		assert_eq!(hint(&mut sector, too_wide), Some(valid));
+	}
 }
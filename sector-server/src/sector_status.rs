@@ -0,0 +1,22 @@
+use solarscape_shared::message::backend::SectorStatus;
+use sqlx::{query, PgPool};
+
+/// Writes the sector's row in the `sector_status` table, replacing the previous report.
+pub async fn report(
+	database: &PgPool,
+	sector: &str,
+	SectorStatus { players, capacity }: SectorStatus,
+) -> Result<(), sqlx::Error> {
+	query!(
+		"INSERT INTO sector_status(sector, reported, players, capacity) VALUES ($1, NOW(), $2, $3)
+			ON CONFLICT (sector) DO UPDATE
+			SET reported = EXCLUDED.reported, players = EXCLUDED.players, capacity = EXCLUDED.capacity",
+		sector,
+		players.min(i32::MAX as u32) as i32,
+		capacity.min(i32::MAX as u32) as i32,
+	)
+	.execute(database)
+	.await?;
+
+	Ok(())
+}
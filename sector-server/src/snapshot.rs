@@ -0,0 +1,484 @@
+use crate::save::ChunkSnapshot;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use solarscape_shared::{
+	data::{world::Location, Id},
+	message::{clientbound::SyncStructure, PROTOCOL_VERSION},
+};
+use std::{
+	fmt::{self, Display, Formatter},
+	fs::{self, File},
+	io::{self, BufReader, BufWriter, Write},
+	path::Path,
+	time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// Identifies a file as a sector snapshot.
+const MAGIC: [u8; 8] = *b"SSNAPSHT";
+
+/// Bumped whenever [`Snapshot`] changes, snapshots from any other version are rejected rather than misread.
+pub const SCHEMA_VERSION: u32 = 1;
+
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// Everything about a running sector that isn't in the database, so that it can be handed to a new process with
+/// `--restore`. Written uncompressed as a [`Header`], followed by the snapshot itself compressed with zstd.
+#[derive(Deserialize, Serialize)]
+pub struct Snapshot {
+	pub sector: Box<str>,
+
+	/// Chunks modified since they were last saved.
+	pub chunks: Vec<SnapshotChunk>,
+	pub structures: Vec<SyncStructure>,
+	/// Where each connected player was, they're put back there when they reconnect.
+	pub players: Vec<(Id, Location)>,
+
+	pub ambient_phase: f32,
+	pub ambient_paused: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+struct Header {
+	magic: [u8; 8],
+	schema_version: u32,
+	protocol_version: u32,
+}
+
+/// Voxject ids aren't stable across restarts, so chunks are identified by voxject name, like in the database.
+#[derive(Deserialize, Serialize)]
+pub struct SnapshotChunk {
+	pub voxject: Box<str>,
+	pub position: Vector3<i32>,
+	/// How many times the chunk had been saved when the snapshot was taken, see [`snapshot_wins`].
+	pub generation: i64,
+
+	pub materials: Vec<u8>,
+	pub densities: Vec<f32>,
+}
+
+impl SnapshotChunk {
+	pub fn new(voxject: Box<str>, generation: i64, chunk: ChunkSnapshot) -> Self {
+		Self {
+			voxject,
+			position: chunk.coordinates.coordinates,
+			generation,
+			materials: chunk.materials,
+			densities: chunk.densities,
+		}
+	}
+}
+
+/// Whether a chunk from a snapshot should replace the database's copy. `snapshot` is how many times the chunk had been
+/// saved when the snapshot was taken, and `database` is how many times it has been saved now, [`None`] if it never
+/// has. If the database's copy has been saved since, it was saved by the old process after the snapshot was taken, so
+/// it's at least as new as the snapshot's.
+pub fn snapshot_wins(snapshot: i64, database: Option<i64>) -> bool {
+	snapshot >= database.unwrap_or(0)
+}
+
+/// Summary of a written snapshot.
+pub struct SnapshotReport {
+	pub chunks: usize,
+	pub structures: usize,
+	pub players: usize,
+	pub bytes: u64,
+	pub duration: Duration,
+}
+
+impl Display for SnapshotReport {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		write!(
+			formatter,
+			"{} chunks, {} structures, and {} players, {} KiB in {:.0?}",
+			self.chunks,
+			self.structures,
+			self.players,
+			self.bytes / 1024,
+			self.duration
+		)
+	}
+}
+
+/// Summary of a restored snapshot.
+#[derive(Default)]
+pub struct RestoreReport {
+	pub chunks: usize,
+	/// Chunks which were saved since the snapshot was taken, or whose voxject no longer exists.
+	pub skipped_chunks: usize,
+	pub structures: usize,
+	pub players: usize,
+}
+
+impl Display for RestoreReport {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		write!(
+			formatter,
+			"{} chunks ({} skipped), {} structures, and {} player locations",
+			self.chunks, self.skipped_chunks, self.structures, self.players
+		)
+	}
+}
+
+/// Snapshots are only taken while no save is in progress, so that the generation recorded for each chunk is the one
+/// its last save actually wrote. Only one snapshot is written at a time, snapshots requested while one is being
+/// written are taken once it finishes.
+#[derive(Default)]
+pub struct SnapshotQueue {
+	waiting: Vec<Id>,
+	writing: Option<Vec<Id>>,
+}
+
+impl SnapshotQueue {
+	/// `player` is told once the snapshot has been written.
+	pub fn request(&mut self, player: Id) {
+		self.waiting.push(player);
+	}
+
+	/// Returns `true` if a snapshot should be taken now, call again whenever a save or snapshot finishes.
+	pub fn start(&mut self, save_in_progress: bool) -> bool {
+		if save_in_progress || self.writing.is_some() || self.waiting.is_empty() {
+			return false;
+		}
+
+		self.writing = Some(self.waiting.drain(..).collect());
+		true
+	}
+
+	/// Returns the players who requested the snapshot that was just written.
+	pub fn finish(&mut self) -> Vec<Id> {
+		self.writing.take().unwrap_or_default()
+	}
+
+	pub fn is_busy(&self) -> bool {
+		self.writing.is_some() || !self.waiting.is_empty()
+	}
+}
+
+/// Writes `snapshot` to `path`, replacing it only once the whole snapshot has been written.
+pub fn write(path: &Path, snapshot: &Snapshot) -> Result<SnapshotReport, SnapshotError> {
+	let start_time = Instant::now();
+
+	let partial_path = path.with_extension("partial");
+	let mut file = BufWriter::new(File::create(&partial_path)?);
+
+	bincode::serialize_into(
+		&mut file,
+		&Header {
+			magic: MAGIC,
+			schema_version: SCHEMA_VERSION,
+			protocol_version: PROTOCOL_VERSION,
+		},
+	)?;
+
+	let mut encoder = zstd::Encoder::new(file, COMPRESSION_LEVEL)?;
+	bincode::serialize_into(&mut encoder, snapshot)?;
+
+	let mut file = encoder.finish()?;
+	file.flush()?;
+	file.get_ref().sync_all()?;
+
+	fs::rename(&partial_path, path)?;
+
+	Ok(SnapshotReport {
+		chunks: snapshot.chunks.len(),
+		structures: snapshot.structures.len(),
+		players: snapshot.players.len(),
+		bytes: fs::metadata(path)?.len(),
+		duration: Instant::now() - start_time,
+	})
+}
+
+/// Reads a snapshot written by [`write`], rejecting snapshots from other versions.
+pub fn read(path: &Path) -> Result<Snapshot, SnapshotError> {
+	let mut file = BufReader::new(File::open(path)?);
+
+	let header: Header =
+		bincode::deserialize_from(&mut file).map_err(|_| SnapshotError::NotASnapshot)?;
+
+	if header.magic != MAGIC {
+		return Err(SnapshotError::NotASnapshot);
+	}
+
+	if header.schema_version != SCHEMA_VERSION {
+		return Err(SnapshotError::SchemaVersion(header.schema_version));
+	}
+
+	if header.protocol_version != PROTOCOL_VERSION {
+		return Err(SnapshotError::ProtocolVersion(header.protocol_version));
+	}
+
+	Ok(bincode::deserialize_from(zstd::Decoder::with_buffer(
+		file,
+	)?)?)
+}
+
+#[derive(Debug, Error)]
+pub enum SnapshotError {
+	#[error(transparent)]
+	Io(#[from] io::Error),
+
+	#[error("not a sector snapshot")]
+	NotASnapshot,
+
+	#[error("snapshot is schema version {0}, but this server only reads version {SCHEMA_VERSION}")]
+	SchemaVersion(u32),
+
+	#[error(
+		"snapshot is from protocol version {0}, but this server is protocol version {PROTOCOL_VERSION}"
+	)]
+	ProtocolVersion(u32),
+
+	#[error("snapshot is corrupt: {0}")]
+	Corrupt(#[from] bincode::Error),
+
+	#[error("snapshot is of sector `{0}`, not this one")]
+	WrongSector(Box<str>),
+
+	#[error("unable to save restored chunks: {0}")]
+	Database(#[from] sqlx::Error),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::{point, vector, UnitQuaternion};
+	use solarscape_shared::data::world::BlockType;
+	use std::{env, path::PathBuf};
+
+	/// A snapshot file in the temporary directory, deleted when dropped.
+	struct TempFile(PathBuf);
+
+	impl TempFile {
+		fn new() -> Self {
+			Self(env::temp_dir().join(format!("solarscape-snapshot-{}.bin", Id::new())))
+		}
+	}
+
+	impl Drop for TempFile {
+		fn drop(&mut self) {
+			let _ = fs::remove_file(&self.0);
+			let _ = fs::remove_file(self.0.with_extension("partial"));
+		}
+	}
+
+	fn snapshot() -> Snapshot {
+		let (structure, player) = (Id::new(), Id::new());
+
+		Snapshot {
+			sector: "test".into(),
+
+			chunks: vec![SnapshotChunk {
+				voxject: "planet".into(),
+				position: vector![1, -2, 3],
+				generation: 4,
+				materials: vec![0b1101; 8],
+				densities: vec![0.5, -0.5, 1.0, -1.0, 0.0, 2.0, -2.0, 0.25],
+			}],
+			structures: vec![SyncStructure {
+				id: structure,
+				owner: player,
+				location: Location {
+					position: point![1.0, 2.0, 3.0],
+					rotation: UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3),
+				},
+				linear_velocity: vector![0.0, -9.5, 1.0],
+				angular_velocity: vector![0.5, 0.0, 0.0],
+				sleeping: false,
+				blocks: [(vector![0, 1, 0], BlockType::Explosive)]
+					.into_iter()
+					.collect(),
+				block_states: [(vector![0, 1, 0], 3)].into_iter().collect(),
+			}],
+			players: vec![(
+				player,
+				Location {
+					position: point![-4.0, 5.0, 6.0],
+					rotation: UnitQuaternion::identity(),
+				},
+			)],
+
+			ambient_phase: 0.75,
+			ambient_paused: true,
+		}
+	}
+
+	/// Writes a header as [`write`] would, but with the given versions, followed by `snapshot`.
+	fn write_with_header(path: &Path, header: Header, snapshot: &Snapshot) {
+		let mut file = File::create(path).unwrap();
+		bincode::serialize_into(&mut file, &header).unwrap();
+
+		let mut encoder = zstd::Encoder::new(file, COMPRESSION_LEVEL).unwrap();
+		bincode::serialize_into(&mut encoder, snapshot).unwrap();
+		encoder.finish().unwrap();
+	}
+
+	#[test]
+	fn snapshots_round_trip() {
+		let file = TempFile::new();
+		let written = snapshot();
+
+		let report = write(&file.0, &written).unwrap();
+		assert_eq!(
+			(report.chunks, report.structures, report.players),
+			(1, 1, 1)
+		);
+		assert_eq!(report.bytes, fs::metadata(&file.0).unwrap().len());
+		assert!(!file.0.with_extension("partial").exists());
+
+		let read = read(&file.0).unwrap();
+
+		assert_eq!(read.sector, written.sector);
+		assert_eq!((read.ambient_phase, read.ambient_paused), (0.75, true));
+
+		let (chunk, original) = (&read.chunks[0], &written.chunks[0]);
+		assert_eq!(
+			(&*chunk.voxject, chunk.position, chunk.generation),
+			("planet", original.position, 4)
+		);
+		assert_eq!(chunk.materials, original.materials);
+		assert_eq!(chunk.densities, original.densities);
+
+		let (structure, original) = (&read.structures[0], &written.structures[0]);
+		assert_eq!(
+			(structure.id, structure.owner),
+			(original.id, original.owner)
+		);
+		assert_eq!(structure.location.position, original.location.position);
+		assert_eq!(structure.location.rotation, original.location.rotation);
+		assert_eq!(structure.linear_velocity, original.linear_velocity);
+		assert_eq!(structure.angular_velocity, original.angular_velocity);
+		assert_eq!(structure.blocks, original.blocks);
+		assert_eq!(structure.block_states, original.block_states);
+
+		assert_eq!(read.players[0].0, written.players[0].0);
+		assert_eq!(read.players[0].1.position, written.players[0].1.position);
+	}
+
+	#[test]
+	fn other_versions_are_rejected() {
+		let file = TempFile::new();
+
+		write_with_header(
+			&file.0,
+			Header {
+				magic: MAGIC,
+				schema_version: SCHEMA_VERSION + 1,
+				protocol_version: PROTOCOL_VERSION,
+			},
+			&snapshot(),
+		);
+		let error = read(&file.0).err().unwrap();
+		assert!(
+			matches!(error, SnapshotError::SchemaVersion(version) if version == SCHEMA_VERSION + 1)
+		);
+		assert_eq!(
+			error.to_string(),
+			format!(
+				"snapshot is schema version {}, but this server only reads version {SCHEMA_VERSION}",
+				SCHEMA_VERSION + 1
+			)
+		);
+
+		write_with_header(
+			&file.0,
+			Header {
+				magic: MAGIC,
+				schema_version: SCHEMA_VERSION,
+				protocol_version: PROTOCOL_VERSION - 1,
+			},
+			&snapshot(),
+		);
+		let error = read(&file.0).err().unwrap();
+		assert!(
+			matches!(error, SnapshotError::ProtocolVersion(version) if version == PROTOCOL_VERSION - 1)
+		);
+		assert_eq!(
+			error.to_string(),
+			format!(
+				"snapshot is from protocol version {}, but this server is protocol version {PROTOCOL_VERSION}",
+				PROTOCOL_VERSION - 1
+			)
+		);
+	}
+
+	#[test]
+	fn other_files_are_rejected() {
+		let file = TempFile::new();
+
+		fs::write(&file.0, b"SS").unwrap();
+		assert!(matches!(read(&file.0), Err(SnapshotError::NotASnapshot)));
+
+		write_with_header(
+			&file.0,
+			Header {
+				magic: *b"NOTSNAPS",
+				schema_version: SCHEMA_VERSION,
+				protocol_version: PROTOCOL_VERSION,
+			},
+			&snapshot(),
+		);
+		assert!(matches!(read(&file.0), Err(SnapshotError::NotASnapshot)));
+
+		// A valid header followed by something that isn't a snapshot
+		let mut bytes = bincode::serialize(&Header {
+			magic: MAGIC,
+			schema_version: SCHEMA_VERSION,
+			protocol_version: PROTOCOL_VERSION,
+		})
+		.unwrap();
+		bytes.extend(zstd::encode_all(&b"\xFF\xFF\xFF"[..], COMPRESSION_LEVEL).unwrap());
+		fs::write(&file.0, bytes).unwrap();
+		assert!(matches!(read(&file.0), Err(SnapshotError::Corrupt(_))));
+
+		fs::remove_file(&file.0).unwrap();
+		assert!(matches!(read(&file.0), Err(SnapshotError::Io(_))));
+	}
+
+	#[test]
+	fn snapshots_win_unless_saved_since() {
+		for (snapshot, database, wins) in [
+			// Never saved
+			(0, None, true),
+			// Not saved since the snapshot was taken
+			(0, Some(0), true),
+			(3, Some(3), true),
+			// Saved by the old process after the snapshot was taken
+			(0, Some(1), false),
+			(3, Some(4), false),
+			// Only if the database was reset, the snapshot is all that's left
+			(3, None, true),
+			(3, Some(1), true),
+		] {
+			assert_eq!(
+				snapshot_wins(snapshot, database),
+				wins,
+				"{snapshot} against {database:?}"
+			);
+		}
+	}
+
+	#[test]
+	fn snapshots_wait_for_saves_and_each_other() {
+		let (a, b) = (Id::new(), Id::new());
+		let mut queue = SnapshotQueue::default();
+
+		assert!(!queue.start(false));
+		assert!(!queue.is_busy());
+
+		queue.request(a);
+		assert!(queue.is_busy());
+		assert!(!queue.start(true));
+
+		assert!(queue.start(false));
+		queue.request(b);
+		assert!(!queue.start(false));
+
+		assert_eq!(queue.finish(), [a]);
+		assert!(queue.start(false));
+		assert_eq!(queue.finish(), [b]);
+
+		assert!(!queue.is_busy());
+		assert_eq!(queue.finish(), []);
+	}
+}
@@ -0,0 +1,68 @@
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::data::{world::ChunkCoordinates, Id};
+use std::collections::{HashMap, HashSet};
+
+/// Which structures are in or near each level 0 chunk, so that anything changing a chunk can wake the structures
+/// that might be resting on it. Structures are only moved in the index while they're awake, sleeping structures stay
+/// where they were when they fell asleep.
+#[derive(Default)]
+pub struct StructureIndex {
+	chunks: HashMap<ChunkCoordinates, HashSet<Id, FxBuildHasher>, FxBuildHasher>,
+	structures: HashMap<Id, HashSet<ChunkCoordinates, FxBuildHasher>, FxBuildHasher>,
+}
+
+impl StructureIndex {
+	/// Replaces the chunks `structure` is indexed under.
+	pub fn update(&mut self, structure: Id, chunks: HashSet<ChunkCoordinates, FxBuildHasher>) {
+		let previous = self.structures.remove(&structure).unwrap_or_default();
+
+		for coordinates in previous.difference(&chunks) {
+			self.remove_from_chunk(structure, coordinates);
+		}
+
+		for coordinates in chunks.difference(&previous) {
+			self.chunks
+				.entry(*coordinates)
+				.or_default()
+				.insert(structure);
+		}
+
+		if !chunks.is_empty() {
+			self.structures.insert(structure, chunks);
+		}
+	}
+
+	pub fn remove(&mut self, structure: Id) {
+		for coordinates in self.structures.remove(&structure).unwrap_or_default() {
+			self.remove_from_chunk(structure, &coordinates);
+		}
+	}
+
+	/// Structures indexed under any of `chunks`, without duplicates.
+	pub fn structures_in<'a>(
+		&self,
+		chunks: impl IntoIterator<Item = &'a ChunkCoordinates>,
+	) -> HashSet<Id, FxBuildHasher> {
+		chunks
+			.into_iter()
+			.filter_map(|coordinates| self.chunks.get(coordinates))
+			.flatten()
+			.copied()
+			.collect()
+	}
+
+	/// Chunks `structure` is indexed under.
+	pub fn chunks_of(&self, structure: Id) -> Option<&HashSet<ChunkCoordinates, FxBuildHasher>> {
+		self.structures.get(&structure)
+	}
+
+	fn remove_from_chunk(&mut self, structure: Id, coordinates: &ChunkCoordinates) {
+		if let Some(structures) = self.chunks.get_mut(coordinates) {
+			structures.remove(&structure);
+
+			if structures.is_empty() {
+				self.chunks.remove(coordinates);
+			}
+		}
+	}
+}
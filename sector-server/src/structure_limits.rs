@@ -0,0 +1,216 @@
+use crate::config::StructureLimits;
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::{data::Id, message::clientbound::ActionDenied, structure::Structure};
+use std::{collections::HashMap, time::Instant};
+
+/// How many structures each player owns, kept up to date as structures are created and deleted so that limits can be
+/// checked without counting every structure.
+#[derive(Default, PartialEq)]
+pub struct StructureCounts {
+	owners: HashMap<Id, usize, FxBuildHasher>,
+	total: usize,
+}
+
+impl StructureCounts {
+	pub fn of(&self, owner: Id) -> usize {
+		self.owners.get(&owner).copied().unwrap_or(0)
+	}
+
+	pub fn total(&self) -> usize {
+		self.total
+	}
+
+	pub fn add(&mut self, owner: Id) {
+		*self.owners.entry(owner).or_default() += 1;
+		self.total += 1;
+	}
+
+	pub fn remove(&mut self, owner: Id) {
+		if let Some(count) = self.owners.get_mut(&owner) {
+			*count -= 1;
+			self.total -= 1;
+
+			if *count == 0 {
+				self.owners.remove(&owner);
+			}
+		}
+	}
+
+	/// Recounts from `structures`, returning `false` if the counts had drifted from them.
+	pub fn reconcile<'s>(&mut self, structures: impl IntoIterator<Item = &'s Structure>) -> bool {
+		let mut counted = Self::default();
+
+		for structure in structures {
+			counted.add(structure.owner);
+		}
+
+		let matched = *self == counted;
+		*self = counted;
+		matched
+	}
+
+	/// Whether `owner` may create another structure, given `pending` structures which have been allowed but not yet
+	/// added to the sector.
+	pub fn check(
+		&self,
+		pending: &Self,
+		limits: &StructureLimits,
+		owner: Id,
+	) -> Result<(), ActionDenied> {
+		let max = limits.per_player;
+		if max != 0 && self.of(owner) + pending.of(owner) >= max {
+			return Err(ActionDenied::PlayerStructureLimit { max });
+		}
+
+		let max = limits.per_sector;
+		if max != 0 && self.total() + pending.total() >= max {
+			return Err(ActionDenied::SectorStructureLimit { max });
+		}
+
+		Ok(())
+	}
+}
+
+/// Allows bursts of up to `capacity` actions, refilling at `rate` per second.
+pub struct TokenBucket {
+	capacity: f32,
+	rate: f32,
+
+	tokens: f32,
+	last_refill: Instant,
+}
+
+impl TokenBucket {
+	/// Starts full.
+	pub fn new(rate: f32, capacity: f32, now: Instant) -> Self {
+		Self {
+			capacity,
+			rate,
+
+			tokens: capacity,
+			last_refill: now,
+		}
+	}
+
+	/// Takes a token if there is one.
+	pub fn try_take(&mut self, now: Instant) -> bool {
+		let elapsed = now
+			.saturating_duration_since(self.last_refill)
+			.as_secs_f32();
+		self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+		self.last_refill = now;
+
+		match self.tokens >= 1.0 {
+			true => {
+				self.tokens -= 1.0;
+				true
+			}
+			false => false,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	fn limits(per_player: usize, per_sector: usize) -> StructureLimits {
+		StructureLimits {
+			per_player,
+			per_sector,
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn counts_follow_additions_and_removals() {
+		let (a, b) = (Id::new(), Id::new());
+		let mut counts = StructureCounts::default();
+
+		counts.add(a);
+		counts.add(a);
+		counts.add(b);
+		counts.remove(b);
+
+		assert_eq!((counts.of(a), counts.of(b), counts.total()), (2, 0, 2));
+
+		// Removing a structure nobody is counted for is ignored rather than underflowing
+		counts.remove(b);
+		counts.remove(Id::new());
+		assert_eq!(counts.total(), 2);
+
+		// Owners with nothing left are forgotten, so they compare equal to never having been added
+		counts.remove(a);
+		counts.remove(a);
+		assert!(counts == StructureCounts::default());
+	}
+
+	#[test]
+	fn limits_include_pending_structures() {
+		let (a, b) = (Id::new(), Id::new());
+		let (mut counts, mut pending) = (StructureCounts::default(), StructureCounts::default());
+		let limits = limits(2, 3);
+
+		counts.add(a);
+		assert_eq!(counts.check(&pending, &limits, a), Ok(()));
+
+		pending.add(a);
+		assert_eq!(
+			counts.check(&pending, &limits, a),
+			Err(ActionDenied::PlayerStructureLimit { max: 2 })
+		);
+		assert_eq!(counts.check(&pending, &limits, b), Ok(()));
+
+		pending.add(b);
+		assert_eq!(
+			counts.check(&pending, &limits, b),
+			Err(ActionDenied::SectorStructureLimit { max: 3 })
+		);
+	}
+
+	#[test]
+	fn zero_is_unlimited() {
+		let a = Id::new();
+		let mut counts = StructureCounts::default();
+
+		for _ in 0..100 {
+			counts.add(a);
+		}
+
+		assert_eq!(
+			counts.check(&StructureCounts::default(), &limits(0, 0), a),
+			Ok(())
+		);
+		assert_eq!(
+			counts.check(&StructureCounts::default(), &limits(0, 100), a),
+			Err(ActionDenied::SectorStructureLimit { max: 100 })
+		);
+	}
+
+	#[test]
+	fn buckets_allow_a_burst_then_refill() {
+		let start = Instant::now();
+		let mut bucket = TokenBucket::new(5.0, 3.0, start);
+
+		assert_eq!((0..4).filter(|_| bucket.try_take(start)).count(), 3);
+
+		// 5 per second is one every 200ms
+		assert!(!bucket.try_take(start + Duration::from_millis(100)));
+		assert!(bucket.try_take(start + Duration::from_millis(200)));
+		assert!(!bucket.try_take(start + Duration::from_millis(200)));
+	}
+
+	#[test]
+	fn buckets_refill_no_further_than_capacity() {
+		let start = Instant::now();
+		let mut bucket = TokenBucket::new(5.0, 3.0, start);
+		let later = start + Duration::from_secs(60);
+
+		assert_eq!((0..10).filter(|_| bucket.try_take(later)).count(), 3);
+
+		// Time going backwards doesn't take tokens away either
+		let mut bucket = TokenBucket::new(5.0, 3.0, later);
+		assert!(bucket.try_take(start));
+	}
+}
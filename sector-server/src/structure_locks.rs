@@ -0,0 +1,184 @@
+use crate::sector::Voxject;
+use nalgebra::vector;
+use rapier3d::parry::bounding_volume::{Aabb, BoundingVolume};
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::data::world::{ChunkCoordinates, Level, VoxjectPosition};
+use std::collections::HashSet;
+
+/// Most chunks a single structure may tick lock, a structure needing more than this is put to sleep instead.
+pub const MAX_CHUNKS_PER_STRUCTURE: usize = 64;
+
+/// Structures are given collision a little before they reach a chunk, rather than only once they're inside of it.
+const PADDING: f32 = 1.0;
+
+/// Level 0 chunks in each voxject which `aabb` overlaps, once padded. Returns [`None`] if that's more than
+/// [`MAX_CHUNKS_PER_STRUCTURE`].
+pub fn overlapped_chunks<'v>(
+	voxjects: impl Iterator<Item = &'v Voxject>,
+	aabb: &Aabb,
+) -> Option<HashSet<ChunkCoordinates, FxBuildHasher>> {
+	let aabb = aabb.loosened(PADDING);
+
+	let mut chunks = HashSet::with_hasher(FxBuildHasher);
+
+	for voxject in voxjects {
+		// Bounds of the structure's bounds once in the voxject's frame, which may be larger if the voxject is rotated
+		let aabb = aabb.transform_by(&voxject.location.inverse());
+
+		let min = VoxjectPosition::new(voxject.id, aabb.mins).chunk(Level::new(0));
+		let max = VoxjectPosition::new(voxject.id, aabb.maxs).chunk(Level::new(0));
+
+		// Saturating, a structure that has been flung far enough could overflow this
+		let count = max
+			.coordinates
+			.iter()
+			.zip(min.coordinates.iter())
+			.map(|(max, min)| (max.abs_diff(*min) as usize).saturating_add(1))
+			.fold(1, usize::saturating_mul);
+
+		if chunks.len().saturating_add(count) > MAX_CHUNKS_PER_STRUCTURE {
+			return None;
+		}
+
+		for x in min.x..=max.x {
+			for y in min.y..=max.y {
+				for z in min.z..=max.z {
+					chunks.insert(ChunkCoordinates::new(
+						voxject.id,
+						vector![x, y, z],
+						Level::new(0),
+					));
+				}
+			}
+		}
+	}
+
+	Some(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::config;
+	use nalgebra::{point, Isometry3, Point3, UnitQuaternion, Vector3};
+	use std::f32::consts::FRAC_PI_4;
+
+	fn voxject(location: Isometry3<f32>) -> Voxject {
+		let config = config::Voxject {
+			name: "test".into(),
+			lod: None,
+		};
+
+		let (_, mut voxject) = Voxject::new(config, &config::Lod::default());
+		voxject.location = location;
+		voxject
+	}
+
+	fn aabb(mins: Point3<f32>, maxs: Point3<f32>) -> Aabb {
+		Aabb::new(mins, maxs)
+	}
+
+	fn coordinates(chunks: &HashSet<ChunkCoordinates, FxBuildHasher>) -> Vec<Vector3<i32>> {
+		let mut coordinates: Vec<_> = chunks.iter().map(|chunk| chunk.coordinates).collect();
+		coordinates.sort_by_key(|coordinates| (coordinates.x, coordinates.y, coordinates.z));
+		coordinates
+	}
+
+	#[test]
+	fn structures_inside_a_chunk_lock_just_that_chunk() {
+		let voxject = voxject(Isometry3::identity());
+		let chunks = overlapped_chunks(
+			[&voxject].into_iter(),
+			&aabb(point![4.0, 4.0, 4.0], point![12.0, 12.0, 12.0]),
+		)
+		.unwrap();
+
+		assert_eq!(coordinates(&chunks), [vector![0, 0, 0]]);
+		assert!(chunks
+			.iter()
+			.all(|chunk| chunk.voxject == voxject.id && chunk.level == Level::new(0)));
+	}
+
+	#[test]
+	fn padding_reaches_into_neighbouring_chunks() {
+		let voxject = voxject(Isometry3::identity());
+
+		// Within PADDING of the chunk below on x, but not of any other
+		let chunks = overlapped_chunks(
+			[&voxject].into_iter(),
+			&aabb(point![0.5, 4.0, 4.0], point![12.0, 12.0, 12.0]),
+		)
+		.unwrap();
+
+		assert_eq!(coordinates(&chunks), [vector![-1, 0, 0], vector![0, 0, 0]]);
+	}
+
+	#[test]
+	fn structures_straddling_the_origin_lock_every_octant() {
+		let voxject = voxject(Isometry3::identity());
+		let chunks = overlapped_chunks(
+			[&voxject].into_iter(),
+			&aabb(point![-4.0, -4.0, -4.0], point![4.0, 4.0, 4.0]),
+		)
+		.unwrap();
+
+		assert_eq!(chunks.len(), 8);
+		assert!(chunks
+			.iter()
+			.all(|chunk| chunk.coordinates.iter().all(|axis| (-1..=0).contains(axis))));
+	}
+
+	#[test]
+	fn bounds_are_taken_in_each_voxjects_frame() {
+		let moved = voxject(Isometry3::translation(160.0, 0.0, 0.0));
+		let chunks = overlapped_chunks(
+			[&moved].into_iter(),
+			&aabb(point![164.0, 4.0, 4.0], point![172.0, 12.0, 12.0]),
+		)
+		.unwrap();
+
+		assert_eq!(coordinates(&chunks), [vector![0, 0, 0]]);
+
+		// A rotated voxject sees the bounds as larger, so locks more than the same bounds would without rotation
+		let rotated = voxject(Isometry3::from_parts(
+			Vector3::zeros().into(),
+			UnitQuaternion::from_axis_angle(&Vector3::y_axis(), FRAC_PI_4),
+		));
+		let bounds = aabb(point![4.0, 4.0, 4.0], point![12.0, 12.0, 12.0]);
+
+		assert!(
+			overlapped_chunks([&rotated].into_iter(), &bounds)
+				.unwrap()
+				.len() > 1
+		);
+
+		// Every voxject gets it's own chunks
+		let both = overlapped_chunks([&rotated, &moved].into_iter(), &bounds).unwrap();
+		assert!(both.iter().any(|chunk| chunk.voxject == moved.id));
+		assert!(both.iter().any(|chunk| chunk.voxject == rotated.id));
+	}
+
+	#[test]
+	fn large_structures_are_refused() {
+		let voxject = voxject(Isometry3::identity());
+
+		// 4 by 4 by 4 chunks once padded, exactly the limit
+		let limit = aabb(point![2.0, 2.0, 2.0], point![62.0, 62.0, 62.0]);
+		assert_eq!(
+			overlapped_chunks([&voxject].into_iter(), &limit)
+				.unwrap()
+				.len(),
+			MAX_CHUNKS_PER_STRUCTURE
+		);
+
+		let over = aabb(point![2.0, 2.0, 2.0], point![62.0, 62.0, 66.0]);
+		assert!(overlapped_chunks([&voxject].into_iter(), &over).is_none());
+
+		// Far too large to count without overflowing
+		let huge = aabb(
+			Point3::from(Vector3::repeat(-1e30)),
+			Point3::from(Vector3::repeat(1e30)),
+		);
+		assert!(overlapped_chunks([&voxject].into_iter(), &huge).is_none());
+	}
+}
@@ -0,0 +1,456 @@
+use crate::sector::SharedSector;
+use nalgebra::{Isometry3, Vector3};
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::{
+	data::world::{ChunkCoordinates, Location, WorldPosition},
+	message::serverbound::ViewHint,
+};
+use std::collections::HashSet;
+
+/// Chunks waiting to be client locked for a player, ordered so that the chunks the player most likely cares about are
+/// synced first. Only [`SyncQueue::budget`] chunks should be taken from the queue each tick.
+pub struct SyncQueue {
+	// Sorted so that the chunk to sync next is last, and can just be popped off the end
+	pending: Vec<ChunkCoordinates>,
+	pending_set: HashSet<ChunkCoordinates, FxBuildHasher>,
+
+	// What the player could see when the queue was last sorted
+	sorted_view: View,
+}
+
+impl SyncQueue {
+	/// How many chunks may be synced to each player per second.
+	const CHUNKS_PER_SECOND: u32 = 1920;
+
+	/// How far the player has to turn before the queue is sorted again, the same as how far the client's view has to
+	/// change before it sends another [`ViewHint`].
+	const RESORT_ANGLE: f32 = ViewHint::RESEND_ANGLE;
+
+	/// How many chunks should be taken from the queue each tick at `tick_rate`.
+	pub fn budget(tick_rate: u32) -> u32 {
+		(Self::CHUNKS_PER_SECOND / tick_rate).max(1)
+	}
+
+	pub fn new() -> Self {
+		Self {
+			pending: vec![],
+			pending_set: HashSet::with_hasher(FxBuildHasher),
+			sorted_view: View {
+				forward: -Vector3::z(),
+				fov_cos: None,
+			},
+		}
+	}
+
+	/// Replaces the queued chunks. Sorting is skipped if the chunks haven't changed, the player hasn't turned by more
+	/// than [`SyncQueue::RESORT_ANGLE`], and their field of view is the same, as most location updates won't change
+	/// anything.
+	pub fn update(
+		&mut self,
+		chunks: HashSet<ChunkCoordinates, FxBuildHasher>,
+		location: &Location,
+		view: View,
+		sector: &SharedSector,
+	) {
+		if chunks == self.pending_set
+			&& view.fov_cos == self.sorted_view.fov_cos
+			&& view.forward.angle(&self.sorted_view.forward) < Self::RESORT_ANGLE
+		{
+			return;
+		}
+
+		let mut pending = chunks
+			.iter()
+			.map(|coordinates| {
+				(
+					priority(
+						coordinates,
+						&sector.voxject_location(coordinates.voxject),
+						WorldPosition(location.position),
+						view,
+					),
+					*coordinates,
+				)
+			})
+			.collect::<Vec<_>>();
+
+		// Highest priority value last, so that the lowest is popped first
+		pending.sort_unstable_by(|(a, _), (b, _)| b.total_cmp(a));
+
+		self.pending = pending
+			.into_iter()
+			.map(|(_, coordinates)| coordinates)
+			.collect();
+		self.pending_set = chunks;
+		self.sorted_view = view;
+	}
+
+	pub fn pop(&mut self) -> Option<ChunkCoordinates> {
+		let coordinates = self.pending.pop()?;
+		self.pending_set.remove(&coordinates);
+		Some(coordinates)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.pending.is_empty()
+	}
+
+	/// Every chunk waiting to be synced, in no particular order.
+	pub fn pending(&self) -> impl Iterator<Item = &ChunkCoordinates> {
+		self.pending_set.iter()
+	}
+}
+
+/// What a player can see, for [`priority`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct View {
+	/// Unit vector the player is looking along.
+	pub forward: Vector3<f32>,
+	/// Cosine of the angle between `forward` and the edge of the view, [`None`] if it's not known.
+	pub fov_cos: Option<f32>,
+}
+
+impl View {
+	/// How far the player's facing may be from their [`ViewHint`] before the hint is considered stale. Twice the angle
+	/// the client turns before sending another, to allow for it arriving a little after the location that needed it.
+	const STALE_ANGLE: f32 = ViewHint::RESEND_ANGLE * 2.0;
+
+	/// The player's view from `hint`, or just which way they're facing if they haven't sent one or it's stale.
+	pub fn new(location: &Location, hint: Option<&ViewHint>) -> Self {
+		let facing = location.rotation.inverse_transform_vector(&-Vector3::z());
+
+		match hint {
+			Some(hint) if hint.forward.angle(&facing) <= Self::STALE_ANGLE => Self {
+				forward: hint.forward,
+				fov_cos: Some(hint.fov_cos),
+			},
+			_ => Self {
+				forward: facing,
+				fov_cos: None,
+			},
+		}
+	}
+}
+
+/// Lower values should be synced first.
+///
+/// Distance is measured in chunks on the chunk's own level, and chunks behind the player count as up to 3 times as far
+/// away. If the player's field of view is known instead, chunks even partly within it count as exactly as far away as
+/// they are, and those outside of it count as up to 4 times as far away. Coarser levels are preferred, as the client
+/// can fill in missing detail from them while the finer chunks load.
+pub fn priority(
+	coordinates: &ChunkCoordinates,
+	voxject_location: &Isometry3<f32>,
+	position: WorldPosition,
+	view: View,
+) -> f32 {
+	const BEHIND_WEIGHT: f32 = 2.0;
+	const OUT_OF_VIEW_WEIGHT: f32 = 1.0;
+	const LEVEL_WEIGHT: f32 = 1.0;
+
+	// Distance from a chunk's center to it's corners, in chunks
+	const CHUNK_RADIUS: f32 = 0.866;
+
+	let chunk_size = coordinates.size();
+	let center = coordinates.center().to_world(voxject_location);
+
+	let offset = center.0 - position.0;
+	let distance = offset.norm() / chunk_size;
+
+	// 1 if the chunk is directly ahead, -1 if directly behind
+	let alignment = offset
+		.try_normalize(f32::EPSILON)
+		.map_or(1.0, |direction| direction.dot(&view.forward));
+
+	let behind = BEHIND_WEIGHT * (1.0 - alignment) / 2.0;
+
+	let weight = match view.fov_cos {
+		None => 1.0 + behind,
+		Some(fov_cos) => {
+			// Widened by how large the chunk looks, so that chunks straddling the edge of the view count as in it
+			let apparent_radius = (CHUNK_RADIUS / distance).min(1.0).asin();
+
+			match alignment.clamp(-1.0, 1.0).acos() <= fov_cos.acos() + apparent_radius {
+				true => 1.0,
+				false => 1.0 + OUT_OF_VIEW_WEIGHT + behind,
+			}
+		}
+	};
+
+	let distance = distance * weight;
+
+	distance - *coordinates.level as f32 * LEVEL_WEIGHT
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{generation::sphere_generator, sector::tests::shared_sector};
+	use nalgebra::{point, vector, UnitQuaternion};
+	use solarscape_shared::data::{world::Level, Id};
+	use tokio::runtime::Runtime;
+
+	/// Looking along -Z, without a known field of view.
+	const FORWARD: View = View {
+		forward: vector![0.0, 0.0, -1.0],
+		fov_cos: None,
+	};
+
+	fn chunk(voxject: Id, x: i32, z: i32, level: u8) -> ChunkCoordinates {
+		ChunkCoordinates::new(voxject, vector![x, 0, z], Level::new(level))
+	}
+
+	/// Priority of `coordinates` for a player in the middle of the chunk at the origin.
+	fn priority_of(coordinates: &ChunkCoordinates, view: View) -> f32 {
+		priority(
+			coordinates,
+			&Isometry3::identity(),
+			WorldPosition(point![8.0, 8.0, 8.0]),
+			view,
+		)
+	}
+
+	#[test]
+	fn nearer_chunks_come_first() {
+		let voxject = Id::new();
+
+		assert!(
+			priority_of(&chunk(voxject, 0, -2, 0), FORWARD)
+				< priority_of(&chunk(voxject, 0, -4, 0), FORWARD)
+		);
+		assert!(
+			priority_of(&chunk(voxject, 3, 0, 0), FORWARD)
+				< priority_of(&chunk(voxject, 6, 0, 0), FORWARD)
+		);
+	}
+
+	#[test]
+	fn chunks_ahead_come_before_chunks_behind() {
+		let voxject = Id::new();
+		let ahead = priority_of(&chunk(voxject, 0, -4, 0), FORWARD);
+		let side = priority_of(&chunk(voxject, 4, 0, 0), FORWARD);
+		let behind = priority_of(&chunk(voxject, 0, 4, 0), FORWARD);
+
+		assert!(ahead < side);
+		assert!(side < behind);
+
+		// Behind counts as 3 times as far, the distances are the same otherwise
+		assert!((behind / ahead - 3.0).abs() < 1e-3);
+	}
+
+	#[test]
+	fn coarser_levels_come_first() {
+		let voxject = Id::new();
+
+		// Both are the same distance away in chunks on their own level
+		let fine = priority_of(&chunk(voxject, 0, -4, 0), FORWARD);
+		let coarse = priority_of(&chunk(voxject, 0, -4, 1), FORWARD);
+
+		assert!(coarse < fine);
+	}
+
+	#[test]
+	fn chunks_in_view_count_as_their_distance() {
+		let voxject = Id::new();
+		let view = View {
+			forward: FORWARD.forward,
+			fov_cos: Some(f32::cos(30.0_f32.to_radians())),
+		};
+
+		let ahead = chunk(voxject, 0, -8, 0);
+		let aside = chunk(voxject, 8, -1, 0);
+
+		let distance = |coordinates: &ChunkCoordinates| {
+			(coordinates.center().position - point![8.0, 8.0, 8.0]).norm() / coordinates.size()
+		};
+
+		assert!((priority_of(&ahead, view) - distance(&ahead)).abs() < 1e-3);
+		assert!(priority_of(&aside, view) > distance(&aside) * 2.0);
+	}
+
+	#[test]
+	fn queue_is_resorted_when_the_player_turns() {
+		let runtime = Runtime::new().unwrap();
+		let (sector, coordinates) = shared_sector(&runtime, sphere_generator);
+		let voxject = coordinates.voxject;
+
+		let (ahead, behind) = (chunk(voxject, 0, -4, 0), chunk(voxject, 0, 4, 0));
+		let chunks = HashSet::from_iter([ahead, behind]);
+
+		let facing = |degrees: f32| Location {
+			position: point![8.0, 8.0, 8.0],
+			rotation: UnitQuaternion::from_axis_angle(&Vector3::y_axis(), degrees.to_radians()),
+		};
+
+		let view = |location: &Location| View::new(location, None);
+
+		let mut queue = SyncQueue::new();
+		queue.update(chunks.clone(), &facing(0.0), view(&facing(0.0)), &sector);
+		assert_eq!(queue.pending.last(), Some(&ahead));
+
+		// Not far enough to bother sorting again
+		let slight = facing(10.0);
+		queue.update(chunks.clone(), &slight, view(&slight), &sector);
+		assert_eq!(queue.sorted_view, view(&facing(0.0)));
+
+		let around = facing(180.0);
+		queue.update(chunks.clone(), &around, view(&around), &sector);
+		assert_eq!(queue.sorted_view, view(&around));
+
+		assert_eq!(queue.pop(), Some(behind));
+		assert_eq!(queue.pending().collect::<Vec<_>>(), [&ahead]);
+		assert_eq!(queue.pop(), Some(ahead));
+		assert_eq!(queue.pop(), None);
+		assert!(queue.is_empty());
+	}
+
+	#[test]
+	fn budget_is_spread_over_the_tick_rate() {
+		assert_eq!(SyncQueue::budget(20), 96);
+		assert_eq!(SyncQueue::budget(SyncQueue::CHUNKS_PER_SECOND * 2), 1);
+	}
+
+	#[test]
+	fn view_priorities() {
+		let voxject = Id::new();
+		let hinted = View {
+			forward: FORWARD.forward,
+			fov_cos: Some(f32::cos(30.0_f32.to_radians())),
+		};
+
+		// How many times further than it is each chunk counts as
+		for (x, z, view, weight) in [
+			(0, -8, FORWARD, 1.0),
+			(8, 0, FORWARD, 2.0),
+			(0, 8, FORWARD, 3.0),
+			(0, -8, hinted, 1.0),
+			// Just past the edge of the view, but it's corners are in it
+			(3, -5, hinted, 1.0),
+			(4, -4, hinted, 2.0 + (1.0 - 0.5_f32.sqrt())),
+			(8, 0, hinted, 3.0),
+			(0, 8, hinted, 4.0),
+		] {
+			let coordinates = chunk(voxject, x, z, 0);
+			let distance =
+				(coordinates.center().position - point![8.0, 8.0, 8.0]).norm() / coordinates.size();
+
+			let priority = priority_of(&coordinates, view);
+			assert!(
+				(priority / distance - weight).abs() < 1e-3,
+				"{x} {z} {view:?}: {} rather than {weight}",
+				priority / distance
+			);
+		}
+
+		// The chunk the player is in comes first whichever way they look
+		assert_eq!(priority_of(&chunk(voxject, 0, 0, 0), hinted), 0.0);
+		assert_eq!(priority_of(&chunk(voxject, 0, 0, 0), FORWARD), 0.0);
+	}
+
+	#[test]
+	fn stale_view_hints_are_ignored() {
+		let location = Location {
+			position: point![8.0, 8.0, 8.0],
+			rotation: UnitQuaternion::identity(),
+		};
+
+		let turned = |degrees: f32| {
+			let rotation =
+				UnitQuaternion::from_axis_angle(&Vector3::y_axis(), degrees.to_radians());
+			ViewHint::new(rotation * -Vector3::z(), 1.0, 1.0)
+		};
+
+		assert_eq!(View::new(&location, None), FORWARD);
+
+		// Arriving a little after the location it was for
+		let hint = turned(30.0);
+		assert_eq!(
+			View::new(&location, Some(&hint)),
+			View {
+				forward: hint.forward,
+				fov_cos: Some(hint.fov_cos),
+			}
+		);
+
+		assert_eq!(View::new(&location, Some(&turned(50.0))), FORWARD);
+	}
+
+	#[test]
+	fn queue_is_resorted_when_the_view_widens() {
+		let runtime = Runtime::new().unwrap();
+		let (sector, coordinates) = shared_sector(&runtime, sphere_generator);
+		let voxject = coordinates.voxject;
+
+		let chunks = HashSet::from_iter([chunk(voxject, 0, -4, 0), chunk(voxject, 4, -1, 0)]);
+		let location = Location {
+			position: point![8.0, 8.0, 8.0],
+			rotation: UnitQuaternion::identity(),
+		};
+
+		let view = |fov_cos: f32| View {
+			fov_cos: Some(fov_cos),
+			..FORWARD
+		};
+
+		let mut queue = SyncQueue::new();
+		queue.update(chunks.clone(), &location, view(0.9), &sector);
+		assert_eq!(queue.sorted_view, view(0.9));
+
+		queue.update(chunks.clone(), &location, view(0.1), &sector);
+		assert_eq!(queue.sorted_view, view(0.1));
+	}
+
+	/// How many chunks are synced before everything the player can see has been, with and without their hint, for a
+	/// typical 70 degree 16:9 view.
+	#[test]
+	fn view_hints_sync_what_is_visible_sooner() {
+		let runtime = Runtime::new().unwrap();
+		let (sector, coordinates) = shared_sector(&runtime, sphere_generator);
+		let voxject = coordinates.voxject;
+
+		let location = Location {
+			position: point![8.0, 8.0, 8.0],
+			rotation: UnitQuaternion::identity(),
+		};
+		let hint = ViewHint::new(-Vector3::z(), 70.0_f32.to_radians(), 16.0 / 9.0);
+
+		let chunks = (-6..=6)
+			.flat_map(|x| (-6..=6).flat_map(move |y| (-6..=6).map(move |z| vector![x, y, z])))
+			.map(|position| ChunkCoordinates::new(voxject, position, Level::new(0)))
+			.collect::<HashSet<_, FxBuildHasher>>();
+
+		let visible = chunks
+			.iter()
+			.filter(|coordinates| {
+				let offset = coordinates.center().position - location.position;
+				offset.norm() == 0.0 || offset.normalize().dot(&hint.forward) >= hint.fov_cos
+			})
+			.count();
+
+		let synced_before_visible = |view: View| {
+			let mut queue = SyncQueue::new();
+			queue.update(chunks.clone(), &location, view, &sector);
+
+			let mut remaining = visible;
+			let mut synced = 0;
+			while remaining > 0 {
+				let coordinates = queue.pop().unwrap();
+				let offset = coordinates.center().position - location.position;
+				if offset.norm() == 0.0 || offset.normalize().dot(&hint.forward) >= hint.fov_cos {
+					remaining -= 1;
+				}
+				synced += 1;
+			}
+
+			synced
+		};
+
+		let with_hint = synced_before_visible(View::new(&location, Some(&hint)));
+		let without_hint = synced_before_visible(View::new(&location, None));
+
+		assert!(
+			with_hint * 3 < without_hint * 2,
+			"{with_hint} with the hint, {without_hint} without, for {visible} visible"
+		);
+	}
+}
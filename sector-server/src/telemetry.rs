@@ -0,0 +1,187 @@
+use solarscape_shared::message::serverbound::ClientTelemetry;
+use std::{
+	fmt::{self, Display, Formatter},
+	time::{Duration, Instant},
+};
+use thiserror::Error;
+
+/// Everything a player has reported with [`ClientTelemetry`] this session, which is logged when they disconnect. None
+/// of it is saved.
+#[derive(Default)]
+pub struct TelemetrySummary {
+	reports: u32,
+	fps_avg_total: f64,
+	lowest_fps_p1: f32,
+	highest_frame_time_p99_ms: f32,
+	most_mesh_jobs_pending: u32,
+	adapter_summary_hash: u64,
+
+	last_report: Option<Instant>,
+}
+
+impl TelemetrySummary {
+	const MAX_FPS: f32 = 10_000.0;
+	const MAX_FRAME_TIME_MS: f32 = 60_000.0;
+	const MAX_MESH_JOBS_PENDING: u32 = 1_000_000;
+
+	/// Clients should only report every [`ClientTelemetry::INTERVAL`], this leaves some room for a late report followed
+	/// by one on time.
+	const MIN_INTERVAL: Duration = Duration::from_secs(ClientTelemetry::INTERVAL.as_secs() / 2);
+
+	/// Adds a report to the summary, unless it's invalid or came too soon after the last one.
+	pub fn record(&mut self, telemetry: ClientTelemetry) -> Result<(), TelemetryError> {
+		if self
+			.last_report
+			.is_some_and(|last_report| last_report.elapsed() < Self::MIN_INTERVAL)
+		{
+			return Err(TelemetryError::TooSoon);
+		}
+
+		let ClientTelemetry {
+			fps_avg,
+			fps_p1,
+			frame_time_p99_ms,
+			mesh_jobs_pending,
+			adapter_summary_hash,
+		} = telemetry;
+
+		for (field, value, max) in [
+			("fps_avg", fps_avg, Self::MAX_FPS),
+			("fps_p1", fps_p1, Self::MAX_FPS),
+			(
+				"frame_time_p99_ms",
+				frame_time_p99_ms,
+				Self::MAX_FRAME_TIME_MS,
+			),
+		] {
+			if !(0.0..=max).contains(&value) {
+				return Err(TelemetryError::OutOfRange(field));
+			}
+		}
+
+		if mesh_jobs_pending > Self::MAX_MESH_JOBS_PENDING {
+			return Err(TelemetryError::OutOfRange("mesh_jobs_pending"));
+		}
+
+		self.lowest_fps_p1 = match self.reports {
+			0 => fps_p1,
+			_ => self.lowest_fps_p1.min(fps_p1),
+		};
+
+		self.reports += 1;
+		self.fps_avg_total += fps_avg as f64;
+		self.highest_frame_time_p99_ms = self.highest_frame_time_p99_ms.max(frame_time_p99_ms);
+		self.most_mesh_jobs_pending = self.most_mesh_jobs_pending.max(mesh_jobs_pending);
+		self.adapter_summary_hash = adapter_summary_hash;
+
+		self.last_report = Some(Instant::now());
+
+		Ok(())
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.reports == 0
+	}
+}
+
+impl Display for TelemetrySummary {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		write!(
+			formatter,
+			"{} reports, {:.0} FPS average, {:.0} FPS lowest 1% low, {:.1}ms highest 99th percentile frame time, up to {} \
+			chunks waiting to mesh, adapter {:016x}",
+			self.reports,
+			self.fps_avg_total / self.reports.max(1) as f64,
+			self.lowest_fps_p1,
+			self.highest_frame_time_p99_ms,
+			self.most_mesh_jobs_pending,
+			self.adapter_summary_hash
+		)
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum TelemetryError {
+	#[error("sent too soon after the previous report")]
+	TooSoon,
+
+	#[error("`{0}` is out of range")]
+	OutOfRange(&'static str),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn telemetry(fps_avg: f32, fps_p1: f32, frame_time_p99_ms: f32) -> ClientTelemetry {
+		ClientTelemetry {
+			fps_avg,
+			fps_p1,
+			frame_time_p99_ms,
+			mesh_jobs_pending: 3,
+			adapter_summary_hash: 0xabcd,
+		}
+	}
+
+	#[test]
+	fn reports_are_summarized() {
+		let mut summary = TelemetrySummary::default();
+		assert!(summary.is_empty());
+
+		summary.record(telemetry(60.0, 30.0, 20.0)).unwrap();
+		summary.last_report = None;
+		summary
+			.record(ClientTelemetry {
+				mesh_jobs_pending: 1,
+				..telemetry(120.0, 40.0, 10.0)
+			})
+			.unwrap();
+
+		assert!(!summary.is_empty());
+		assert_eq!(
+			summary.to_string(),
+			"2 reports, 90 FPS average, 30 FPS lowest 1% low, 20.0ms highest 99th percentile frame time, up to 3 \
+			chunks waiting to mesh, adapter 000000000000abcd"
+		);
+	}
+
+	#[test]
+	fn reports_can_only_be_sent_once_an_interval() {
+		let mut summary = TelemetrySummary::default();
+
+		summary.record(telemetry(60.0, 30.0, 20.0)).unwrap();
+		assert!(matches!(
+			summary.record(telemetry(60.0, 30.0, 20.0)),
+			Err(TelemetryError::TooSoon)
+		));
+		assert_eq!(summary.reports, 1);
+	}
+
+	#[test]
+	fn out_of_range_reports_are_rejected() {
+		let cases = [
+			(telemetry(-1.0, 30.0, 20.0), "fps_avg"),
+			(telemetry(f32::NAN, 30.0, 20.0), "fps_avg"),
+			(telemetry(60.0, 1e9, 20.0), "fps_p1"),
+			(telemetry(60.0, 30.0, f32::INFINITY), "frame_time_p99_ms"),
+			(
+				ClientTelemetry {
+					mesh_jobs_pending: u32::MAX,
+					..telemetry(60.0, 30.0, 20.0)
+				},
+				"mesh_jobs_pending",
+			),
+		];
+
+		for (telemetry, field) in cases {
+			let mut summary = TelemetrySummary::default();
+
+			assert!(matches!(
+				summary.record(telemetry),
+				Err(TelemetryError::OutOfRange(rejected)) if rejected == field
+			));
+			assert!(summary.is_empty());
+			assert!(summary.last_report.is_none());
+		}
+	}
+}
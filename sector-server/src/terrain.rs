@@ -0,0 +1,230 @@
+use crate::sector::Data;
+use nalgebra::{Point3, Vector3};
+use rustc_hash::FxBuildHasher;
+use solarscape_shared::{
+	data::world::{CellCoordinates, ChunkCoordinates, Level, Material, VoxjectPosition},
+	message::serverbound::BrushMode,
+};
+use std::collections::HashMap;
+
+/// Cells just outside of the brush still need their densities updated, otherwise the surface won't line up with the
+/// edge of the sphere.
+const FALLOFF_MARGIN: f32 = 1.0;
+
+/// A cell affected by a brush, and how far it is from the brush's center.
+#[derive(Clone, Copy)]
+pub struct BrushCell {
+	/// Position within the chunk, see [`CellCoordinates::cell`].
+	pub cell: Vector3<u8>,
+	pub distance: f32,
+}
+
+/// Maps a sphere to the level 0 chunks it overlaps, and the cells within each of those chunks.
+pub fn sphere_cells(
+	center: VoxjectPosition,
+	radius: f32,
+) -> HashMap<ChunkCoordinates, Vec<BrushCell>, FxBuildHasher> {
+	let VoxjectPosition {
+		voxject,
+		position: center,
+	} = center;
+	let reach = radius + FALLOFF_MARGIN;
+
+	let min = center.map(|coordinate| (coordinate - reach).floor() as i32);
+	let max = center.map(|coordinate| (coordinate + reach).ceil() as i32);
+
+	let mut chunks = HashMap::<_, Vec<_>, _>::with_hasher(FxBuildHasher);
+
+	for x in min.x..=max.x {
+		for y in min.y..=max.y {
+			for z in min.z..=max.z {
+				let cell = Point3::new(x, y, z);
+				let distance = (cell.cast() - center).norm();
+
+				if distance > reach {
+					continue;
+				}
+
+				let cell = CellCoordinates::from_voxject_cell(voxject, cell.coords, Level::new(0));
+
+				chunks.entry(cell.chunk).or_default().push(BrushCell {
+					cell: cell.cell,
+					distance,
+				});
+			}
+		}
+	}
+
+	chunks
+}
+
+/// Applies a brush to the cells of a single chunk, as given by [`sphere_cells`].
+///
+/// Densities are treated as a distance field, so adding takes the union of the terrain and the sphere, and removing
+/// subtracts the sphere from the terrain. This gives a smooth surface at the edge of the brush.
+pub fn apply_brush(
+	data: &mut Data,
+	cells: &[BrushCell],
+	radius: f32,
+	material: Material,
+	mode: BrushMode,
+) {
+	for BrushCell { cell, distance } in cells {
+		// sphere_cells only gives cells within the chunk
+		let (Some(mut cell_material), Some(mut density)) =
+			(data.material_at(*cell), data.density_at(*cell))
+		else {
+			continue;
+		};
+
+		match mode {
+			BrushMode::Add => {
+				density = density.max(radius - distance);
+
+				if density > 0.0 && matches!(cell_material, Material::Nothing) {
+					cell_material = material;
+				}
+			}
+			BrushMode::Remove => {
+				density = density.min(distance - radius);
+
+				if density <= 0.0 {
+					cell_material = Material::Nothing;
+				}
+			}
+		}
+
+		let _ = data.set_voxel(*cell, cell_material, density);
+	}
+}
+
+/// Lowers the density of each cell by `erosion` of it's distance from the center, wearing the terrain away rather than
+/// cutting a clean sphere out of it like [`apply_brush`].
+pub fn erode(data: &mut Data, cells: &[BrushCell], erosion: impl Fn(f32) -> f32) {
+	for BrushCell { cell, distance } in cells {
+		// sphere_cells only gives cells within the chunk
+		let (Some(material), Some(density)) = (data.material_at(*cell), data.density_at(*cell))
+		else {
+			continue;
+		};
+
+		let density = density - erosion(*distance);
+
+		let material = match density <= 0.0 {
+			true => Material::Nothing,
+			false => material,
+		};
+
+		let _ = data.set_voxel(*cell, material, density);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::{point, vector};
+	use solarscape_shared::{consts::CHUNK_SIZE, data::Id};
+	use std::collections::HashSet;
+
+	const LAST: u8 = CHUNK_SIZE as u8 - 1;
+
+	fn sphere(
+		center: Point3<f32>,
+		radius: f32,
+	) -> HashMap<ChunkCoordinates, Vec<BrushCell>, FxBuildHasher> {
+		sphere_cells(VoxjectPosition::new(Id::new(), center), radius)
+	}
+
+	#[test]
+	fn sphere_within_a_chunk_stays_in_it() {
+		let chunks = sphere(point![8.0, 8.0, 8.0], 2.0);
+
+		assert_eq!(chunks.len(), 1);
+		let chunk = chunks.keys().next().unwrap();
+		assert_eq!(chunk.coordinates, vector![0, 0, 0]);
+		assert_eq!(chunk.level, Level::new(0));
+	}
+
+	#[test]
+	fn sphere_across_the_origin_is_split_between_chunks() {
+		let chunks = sphere(point![0.0, 0.0, 0.0], 2.0);
+
+		let coordinates = chunks
+			.keys()
+			.map(|chunk| chunk.coordinates)
+			.collect::<HashSet<_>>();
+		let expected = (0..8)
+			.map(|corner| vector![-(corner & 1), -((corner >> 1) & 1), -((corner >> 2) & 1)])
+			.collect::<HashSet<_>>();
+		assert_eq!(coordinates, expected);
+
+		// Cells below 0 wrap around to the far side of the chunk below, rather than going negative
+		let below = &chunks[&ChunkCoordinates::new(
+			chunks.keys().next().unwrap().voxject,
+			vector![-1, -1, -1],
+			Level::new(0),
+		)];
+		assert!(below
+			.iter()
+			.any(|cell| cell.cell == vector![LAST, LAST, LAST]));
+	}
+
+	#[test]
+	fn sphere_covers_every_cell_within_reach_once() {
+		let center = point![0.0, 0.0, 0.0];
+		let radius = 2.0;
+		let chunks = sphere(center, radius);
+
+		let mut seen = HashSet::new();
+
+		for (chunk, cells) in &chunks {
+			for brush_cell in cells {
+				assert!(brush_cell
+					.cell
+					.iter()
+					.all(|&axis| (axis as usize) < CHUNK_SIZE));
+
+				let position = CellCoordinates {
+					chunk: *chunk,
+					cell: brush_cell.cell,
+				}
+				.voxject_cell();
+
+				let distance = (position.cast::<f32>() - center.coords).norm();
+				assert_eq!(brush_cell.distance, distance);
+				assert!(distance <= radius + FALLOFF_MARGIN);
+				assert!(seen.insert(position), "{position:?} is in the sphere twice");
+			}
+		}
+
+		// Points of the integer grid within 3 of the origin
+		assert_eq!(seen.len(), 123);
+	}
+
+	#[test]
+	fn erosion_wears_terrain_away_by_distance() {
+		let mut data = Data::default();
+		let (near, far, empty) = (vector![1, 1, 1], vector![4, 1, 1], vector![6, 1, 1]);
+
+		for cell in [near, far] {
+			data.set_voxel(cell, Material::Stone, 2.0).unwrap();
+		}
+
+		let cells = [near, far, empty].map(|cell| BrushCell {
+			cell,
+			distance: cell.x as f32,
+		});
+
+		erode(&mut data, &cells, |distance| 3.0 - distance / 2.0);
+
+		// Eroded right through, so it becomes empty
+		assert_eq!(data.material_at(near), Some(Material::Nothing));
+		assert_eq!(data.density_at(near), Some(-0.5));
+
+		assert_eq!(data.material_at(far), Some(Material::Stone));
+		assert_eq!(data.density_at(far), Some(1.0));
+
+		assert_eq!(data.material_at(empty), Some(Material::Nothing));
+		assert_eq!(data.density_at(empty), Some(-0.0));
+	}
+}
@@ -0,0 +1,595 @@
+use crate::{config, sector::Data};
+use solarscape_shared::{
+	consts::CELLS_PER_CHUNK,
+	data::{
+		world::{ChunkCoordinates, Material},
+		Id,
+	},
+};
+use std::{
+	collections::{HashMap, VecDeque},
+	time::{Duration, Instant},
+};
+
+/// Bytes each changed cell takes in a [`Stroke`] at most, the index is usually less.
+const MAX_CELL_SIZE: usize = 3 + 2 * (1 + 4);
+
+/// Each player's recent terrain brush strokes, with what every changed cell was before and after, so that an admin can
+/// undo a griefer's work with `/rollback`. Strokes older than [`config::TerrainJournal::window`], or past
+/// [`config::TerrainJournal::max_size`] for the player, are forgotten.
+pub struct TerrainJournal {
+	/// [`None`] if the journal is disabled.
+	window: Option<Duration>,
+	/// Bytes per player, `0` if there's no limit.
+	max_size: usize,
+	players: HashMap<Id, PlayerJournal>,
+}
+
+#[derive(Default)]
+struct PlayerJournal {
+	strokes: VecDeque<Stroke>,
+	/// Bytes across every stroke.
+	size: usize,
+}
+
+/// A chunk's changed cells, see [`encode`].
+type ChunkCells = (ChunkCoordinates, Box<[u8]>);
+
+/// The cells one brush stroke changed, oldest first in a [`PlayerJournal`].
+struct Stroke {
+	time: Instant,
+	chunks: Vec<ChunkCells>,
+}
+
+impl Stroke {
+	fn size(&self) -> usize {
+		self.chunks.iter().map(|(_, cells)| cells.len()).sum()
+	}
+}
+
+/// What undoing some strokes did, see [`Sector::rollback`].
+///
+/// [`Sector::rollback`]: crate::sector::Sector::rollback
+#[derive(Default)]
+pub struct RollbackReport {
+	pub strokes: usize,
+	pub restored: usize,
+	/// Cells that were skipped because they had been changed again since, by anyone.
+	pub conflicts: usize,
+}
+
+impl TerrainJournal {
+	pub fn new(config::TerrainJournal { window, max_size }: config::TerrainJournal) -> Self {
+		Self {
+			window: match window {
+				0 => None,
+				seconds => Some(Duration::from_secs(seconds)),
+			},
+			max_size: max_size * 1024 * 1024,
+			players: HashMap::new(),
+		}
+	}
+
+	pub fn is_enabled(&self) -> bool {
+		self.window.is_some()
+	}
+
+	/// Records a stroke by `player` that changed the chunks in `chunks`, each with it's cells from before and after the
+	/// stroke. Cells that didn't change aren't kept.
+	pub fn record(
+		&mut self,
+		player: Id,
+		now: Instant,
+		chunks: Vec<(ChunkCoordinates, CellValues, CellValues)>,
+	) {
+		if !self.is_enabled() {
+			return;
+		}
+
+		let stroke = Stroke {
+			time: now,
+			chunks: chunks
+				.into_iter()
+				.filter_map(|(coordinates, before, after)| {
+					let cells = encode(&before, &after);
+					(!cells.is_empty()).then_some((coordinates, cells))
+				})
+				.collect(),
+		};
+
+		self.push(player, stroke);
+		self.evict(now);
+	}
+
+	/// Records a stroke by `player` that changed the single cell at `index` in the chunk at `coordinates`, without
+	/// copying the whole chunk like [`TerrainJournal::record`] needs.
+	pub fn record_cell(
+		&mut self,
+		player: Id,
+		now: Instant,
+		coordinates: ChunkCoordinates,
+		index: usize,
+		before: (Material, f32),
+		after: (Material, f32),
+	) {
+		if !self.is_enabled() || is_unchanged(before, after) {
+			return;
+		}
+
+		let mut cells = vec![];
+		push_cell(&mut cells, index, before, after);
+
+		let stroke = Stroke {
+			time: now,
+			chunks: vec![(coordinates, cells.into_boxed_slice())],
+		};
+
+		self.push(player, stroke);
+		self.evict(now);
+	}
+
+	fn push(&mut self, player: Id, stroke: Stroke) {
+		if !stroke.chunks.is_empty() {
+			let journal = self.players.entry(player).or_default();
+			journal.size += stroke.size();
+			journal.strokes.push_back(stroke);
+		}
+	}
+
+	/// Forgets strokes which are too old, or past a player's size limit.
+	pub fn evict(&mut self, now: Instant) {
+		let Some(window) = self.window else {
+			return;
+		};
+
+		let max_size = self.max_size;
+
+		self.players.retain(|_, journal| {
+			while let Some(stroke) = journal.strokes.front() {
+				let expired = now.saturating_duration_since(stroke.time) > window;
+				let oversized = max_size > 0 && journal.size > max_size;
+
+				if !expired && !oversized {
+					break;
+				}
+
+				journal.size -= stroke.size();
+				journal.strokes.pop_front();
+			}
+
+			!journal.strokes.is_empty()
+		});
+	}
+
+	/// Removes `player`'s strokes from within `duration` of `now`, returning how many there were and the chunks to undo
+	/// them in, newest stroke first. Undo each chunk in order, passing it's data and cells to [`undo`].
+	pub fn take(
+		&mut self,
+		player: Id,
+		now: Instant,
+		duration: Duration,
+	) -> (usize, Vec<ChunkCells>) {
+		let Some(journal) = self.players.get_mut(&player) else {
+			return (0, vec![]);
+		};
+
+		let mut strokes = 0;
+		let mut chunks = vec![];
+
+		while let Some(stroke) = journal.strokes.back() {
+			if now.saturating_duration_since(stroke.time) > duration {
+				break;
+			}
+
+			journal.size -= stroke.size();
+			chunks.extend(journal.strokes.pop_back().unwrap().chunks);
+			strokes += 1;
+		}
+
+		(strokes, chunks)
+	}
+}
+
+/// A chunk's cells, as copied out of [`Data`] before and after a stroke, see [`CellValues::copy`].
+pub struct CellValues {
+	pub materials: Box<[Material; CELLS_PER_CHUNK]>,
+	pub densities: Box<[f32; CELLS_PER_CHUNK]>,
+}
+
+impl CellValues {
+	pub fn copy(data: &Data) -> Self {
+		Self {
+			materials: data.materials.clone(),
+			densities: data.densities.clone(),
+		}
+	}
+}
+
+/// Encodes the cells that differ between `before` and `after`. Each cell is it's index as a varint delta from the last
+/// cell's, then it's material and density before, then after.
+fn encode(before: &CellValues, after: &CellValues) -> Box<[u8]> {
+	let mut bytes = vec![];
+	let mut last_index = 0;
+
+	for index in 0..CELLS_PER_CHUNK {
+		let cell_before = (before.materials[index], before.densities[index]);
+		let cell_after = (after.materials[index], after.densities[index]);
+
+		if is_unchanged(cell_before, cell_after) {
+			continue;
+		}
+
+		push_cell(&mut bytes, index - last_index, cell_before, cell_after);
+		last_index = index;
+	}
+
+	bytes.into_boxed_slice()
+}
+
+fn is_unchanged(
+	(material_before, density_before): (Material, f32),
+	(material_after, density_after): (Material, f32),
+) -> bool {
+	material_before == material_after && density_before.to_bits() == density_after.to_bits()
+}
+
+/// Appends a cell, `delta` after the last one, as described by [`encode`].
+fn push_cell(
+	bytes: &mut Vec<u8>,
+	mut delta: usize,
+	before: (Material, f32),
+	after: (Material, f32),
+) {
+	bytes.reserve(MAX_CELL_SIZE);
+
+	loop {
+		let byte = (delta & 0x7F) as u8;
+		delta >>= 7;
+
+		match delta {
+			0 => {
+				bytes.push(byte);
+				break;
+			}
+			_ => bytes.push(byte | 0x80),
+		}
+	}
+
+	for (material, density) in [before, after] {
+		bytes.push(material as u8);
+		bytes.extend(density.to_le_bytes());
+	}
+}
+
+/// Puts back each cell in `cells`, from [`TerrainJournal::take`], to how it was before it's stroke. Cells that aren't
+/// how the stroke left them have been changed since, so they're left alone and counted as conflicts.
+pub fn undo(data: &mut Data, cells: &[u8], report: &mut RollbackReport) {
+	let mut bytes = cells.iter().copied();
+	let mut index = 0;
+
+	// Only ever read back what encode wrote, so running out part way through a cell can't happen
+	while let Some(mut byte) = bytes.next() {
+		let mut delta = 0;
+		let mut shift = 0;
+
+		loop {
+			delta |= ((byte & 0x7F) as usize) << shift;
+			shift += 7;
+
+			if byte & 0x80 == 0 {
+				break;
+			}
+
+			byte = bytes.next().expect("journal cell should be complete");
+		}
+
+		index += delta;
+
+		let mut read = || {
+			let material = bytes.next().expect("journal cell should be complete");
+			let density = [(); 4].map(|_| bytes.next().expect("journal cell should be complete"));
+
+			(
+				Material::try_from(material).expect("journal should only contain valid materials"),
+				f32::from_le_bytes(density),
+			)
+		};
+
+		let (material_before, density_before) = read();
+		let (material_after, density_after) = read();
+
+		if data.materials[index] != material_after
+			|| data.densities[index].to_bits() != density_after.to_bits()
+		{
+			report.conflicts += 1;
+			continue;
+		}
+
+		data.materials[index] = material_before;
+		data.densities[index] = density_before;
+		report.restored += 1;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::vector;
+	use solarscape_shared::data::world::Level;
+
+	fn journal(window: u64) -> TerrainJournal {
+		TerrainJournal::new(config::TerrainJournal {
+			window,
+			max_size: 0,
+		})
+	}
+
+	fn chunk(x: i32) -> ChunkCoordinates {
+		ChunkCoordinates::new("1".parse().unwrap(), vector![x, 0, 0], Level::new(0))
+	}
+
+	fn player(id: u64) -> Id {
+		id.to_string().parse().unwrap()
+	}
+
+	/// `data` with each of `cells` set, returning the values from before and after.
+	fn change(data: &mut Data, cells: &[(usize, Material, f32)]) -> (CellValues, CellValues) {
+		let before = CellValues::copy(data);
+
+		for (index, material, density) in cells {
+			data.materials[*index] = *material;
+			data.densities[*index] = *density;
+		}
+
+		(before, CellValues::copy(data))
+	}
+
+	/// Undoes every chunk `take` gives back, which have to all be for the same chunk as `data`.
+	fn undo_all(data: &mut Data, chunks: Vec<ChunkCells>) -> RollbackReport {
+		let mut report = RollbackReport::default();
+
+		for (_, cells) in chunks {
+			undo(data, &cells, &mut report);
+		}
+
+		report
+	}
+
+	#[test]
+	fn only_changed_cells_are_encoded() {
+		let mut data = Data::default();
+		let (before, after) = change(
+			&mut data,
+			&[(0, Material::Stone, 1.0), (1, Material::Nothing, 0.0)],
+		);
+
+		// Cell 1 was set to what it already was
+		let cells = encode(&before, &after);
+		assert_eq!(cells.len(), 1 + 2 * 5);
+		assert_eq!(cells[0], 0);
+	}
+
+	#[test]
+	fn indices_are_delta_encoded_varints() {
+		let mut data = Data::default();
+		let (before, after) = change(
+			&mut data,
+			&[
+				(5, Material::Stone, 1.0),
+				(6, Material::Stone, 1.0),
+				(CELLS_PER_CHUNK - 1, Material::Stone, 1.0),
+			],
+		);
+
+		let cells = encode(&before, &after);
+
+		// 5, then 1, then 4089 which needs a second byte
+		assert_eq!(cells.len(), 4 + 3 * 2 * 5);
+		assert_eq!(cells[0], 5);
+		assert_eq!(cells[11], 1);
+		assert_eq!(
+			&cells[22..24],
+			&[0x80 | (4089 & 0x7F) as u8, (4089 >> 7) as u8]
+		);
+		assert!(cells.len() <= 3 * MAX_CELL_SIZE);
+	}
+
+	#[test]
+	fn negative_zero_is_a_change() {
+		let mut data = Data::default();
+		let (before, after) = change(&mut data, &[(0, Material::Nothing, -0.0)]);
+
+		assert!(!encode(&before, &after).is_empty());
+	}
+
+	#[test]
+	fn rollback_restores_cells_newest_stroke_first() {
+		let mut journal = journal(60);
+		let now = Instant::now();
+		let mut data = Data::default();
+
+		let (first_before, first_after) = change(
+			&mut data,
+			&[(0, Material::Stone, 1.0), (9, Material::Ground, 2.0)],
+		);
+		journal.record(player(1), now, vec![(chunk(0), first_before, first_after)]);
+
+		let (second_before, second_after) = change(&mut data, &[(0, Material::Corium, 3.0)]);
+		journal.record(
+			player(1),
+			now,
+			vec![(chunk(0), second_before, second_after)],
+		);
+
+		let (strokes, chunks) = journal.take(player(1), now, Duration::from_secs(60));
+		assert_eq!(strokes, 2);
+
+		let report = undo_all(&mut data, chunks);
+		assert_eq!((report.restored, report.conflicts), (3, 0));
+
+		assert_eq!(data.materials[0], Material::Nothing);
+		assert_eq!(data.densities[0], 0.0);
+		assert_eq!(data.materials[9], Material::Nothing);
+
+		// Taken strokes are gone
+		assert_eq!(journal.take(player(1), now, Duration::from_secs(60)).0, 0);
+	}
+
+	#[test]
+	fn cells_changed_since_are_conflicts() {
+		let mut journal = journal(60);
+		let now = Instant::now();
+		let mut data = Data::default();
+
+		let (before, after) = change(
+			&mut data,
+			&[(0, Material::Stone, 1.0), (1, Material::Stone, 1.0)],
+		);
+		journal.record(player(1), now, vec![(chunk(0), before, after)]);
+
+		// Someone else changed one of the griefer's cells afterwards, their change is kept
+		let (before, after) = change(&mut data, &[(1, Material::Ground, 5.0)]);
+		journal.record(player(2), now, vec![(chunk(0), before, after)]);
+
+		let (_, chunks) = journal.take(player(1), now, Duration::from_secs(60));
+		let report = undo_all(&mut data, chunks);
+
+		assert_eq!((report.restored, report.conflicts), (1, 1));
+		assert_eq!(data.materials[0], Material::Nothing);
+		assert_eq!(
+			(data.materials[1], data.densities[1]),
+			(Material::Ground, 5.0)
+		);
+
+		// Other players' strokes aren't touched
+		assert_eq!(journal.take(player(2), now, Duration::from_secs(60)).0, 1);
+	}
+
+	#[test]
+	fn take_only_goes_back_as_far_as_asked() {
+		let mut journal = journal(600);
+		let start = Instant::now();
+		let mut data = Data::default();
+
+		for (seconds, index) in [(0, 0), (100, 1), (200, 2)] {
+			let (before, after) = change(&mut data, &[(index, Material::Stone, 1.0)]);
+			journal.record(
+				player(1),
+				start + Duration::from_secs(seconds),
+				vec![(chunk(0), before, after)],
+			);
+		}
+
+		let now = start + Duration::from_secs(200);
+		let (strokes, chunks) = journal.take(player(1), now, Duration::from_secs(150));
+		assert_eq!(strokes, 2);
+
+		undo_all(&mut data, chunks);
+		assert_eq!(data.materials[0], Material::Stone);
+		assert_eq!(data.materials[1], Material::Nothing);
+		assert_eq!(data.materials[2], Material::Nothing);
+	}
+
+	#[test]
+	fn strokes_older_than_the_window_are_evicted() {
+		let mut journal = journal(60);
+		let start = Instant::now();
+		let mut data = Data::default();
+
+		let (before, after) = change(&mut data, &[(0, Material::Stone, 1.0)]);
+		journal.record(player(1), start, vec![(chunk(0), before, after)]);
+
+		journal.evict(start + Duration::from_secs(60));
+		assert!(journal.players.contains_key(&player(1)));
+
+		journal.evict(start + Duration::from_secs(61));
+		assert!(journal.players.is_empty());
+	}
+
+	#[test]
+	fn oldest_strokes_are_evicted_past_the_size_limit() {
+		let mut journal = journal(60);
+		let now = Instant::now();
+		let mut data = Data::default();
+
+		// Each stroke is one cell in each of two chunks
+		let stroke_size = 2 * (1 + 2 * 5);
+		journal.max_size = 2 * stroke_size;
+
+		for index in 0..3 {
+			let (before_0, after_0) = change(&mut data, &[(index, Material::Stone, 1.0)]);
+			let (before_1, after_1) = change(&mut data, &[(index + 10, Material::Stone, 1.0)]);
+
+			journal.record(
+				player(1),
+				now,
+				vec![(chunk(0), before_0, after_0), (chunk(1), before_1, after_1)],
+			);
+		}
+
+		let player_journal = &journal.players[&player(1)];
+		assert_eq!(player_journal.strokes.len(), 2);
+		assert_eq!(player_journal.size, 2 * stroke_size);
+
+		let (_, chunks) = journal.take(player(1), now, Duration::from_secs(60));
+		assert_eq!(chunks.len(), 4);
+		assert!(chunks
+			.iter()
+			.all(|(coordinates, _)| [chunk(0), chunk(1)].contains(coordinates)));
+	}
+
+	#[test]
+	fn single_cells_are_recorded_like_strokes() {
+		let mut journal = journal(60);
+		let now = Instant::now();
+		let mut data = Data::default();
+
+		data.materials[42] = Material::Stone;
+		data.densities[42] = 1.0;
+		journal.record_cell(
+			player(1),
+			now,
+			chunk(0),
+			42,
+			(Material::Nothing, 0.0),
+			(Material::Stone, 1.0),
+		);
+
+		// Unchanged cells aren't worth a stroke
+		journal.record_cell(
+			player(1),
+			now,
+			chunk(0),
+			7,
+			(Material::Stone, 1.0),
+			(Material::Stone, 1.0),
+		);
+
+		let (strokes, chunks) = journal.take(player(1), now, Duration::from_secs(60));
+		assert_eq!(strokes, 1);
+
+		let report = undo_all(&mut data, chunks);
+		assert_eq!((report.restored, report.conflicts), (1, 0));
+		assert_eq!(data.materials[42], Material::Nothing);
+	}
+
+	#[test]
+	fn disabled_journal_records_nothing() {
+		let mut journal = journal(0);
+		let mut data = Data::default();
+
+		let (before, after) = change(&mut data, &[(0, Material::Stone, 1.0)]);
+		journal.record(player(1), Instant::now(), vec![(chunk(0), before, after)]);
+		journal.record_cell(
+			player(1),
+			Instant::now(),
+			chunk(0),
+			0,
+			(Material::Nothing, 0.0),
+			(Material::Stone, 1.0),
+		);
+
+		assert!(!journal.is_enabled());
+		assert!(journal.players.is_empty());
+	}
+}
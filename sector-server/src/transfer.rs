@@ -0,0 +1,286 @@
+use nalgebra::{Point3, Quaternion, UnitQuaternion};
+use solarscape_shared::data::{world::Location, Id};
+use sqlx::{query, PgPool};
+use std::time::Duration;
+use thiserror::Error;
+
+/// Why a player couldn't be sent to another sector, see [`Sector::transfer`].
+///
+/// [`Sector::transfer`]: crate::sector::Sector::transfer
+#[derive(Debug, Error)]
+pub enum TransferError {
+	#[error("{0} isn't a sector players can be sent to from here")]
+	UnknownDestination(Box<str>),
+
+	#[error("transfers need a database")]
+	NoDatabase,
+
+	#[error("the player isn't in this sector")]
+	NotConnected,
+
+	#[error("the player is already on their way to another sector")]
+	AlreadyTransferring,
+
+	#[error("unable to record the transfer: {0}")]
+	Database(#[from] sqlx::Error),
+}
+
+/// Records that `player` is leaving `source` for `destination` from `location`, giving them `expiry` to get there.
+/// Returns `false` without recording anything if they're already on their way somewhere, unless that transfer has
+/// expired.
+pub async fn create(
+	database: &PgPool,
+	player: Id,
+	source: &str,
+	destination: &str,
+	Location { position, rotation }: Location,
+	expiry: Duration,
+) -> Result<bool, sqlx::Error> {
+	let result = query!(
+		"INSERT INTO player_transfers (player_id, source, destination, position, rotation, expires)
+			VALUES ($1, $2, $3, $4, $5, NOW() + make_interval(secs => $6))
+			ON CONFLICT (player_id) DO UPDATE SET
+				source = EXCLUDED.source,
+				destination = EXCLUDED.destination,
+				position = EXCLUDED.position,
+				rotation = EXCLUDED.rotation,
+				created = NOW(),
+				expires = EXCLUDED.expires
+			WHERE player_transfers.expires < NOW()",
+		player as _,
+		source,
+		destination,
+		position.coords.as_slice(),
+		rotation.coords.as_slice(),
+		expiry.as_secs_f64(),
+	)
+	.execute(database)
+	.await?;
+
+	Ok(result.rows_affected() == 1)
+}
+
+/// Consumes `player`'s transfer to `sector`, returning where they should arrive. [`None`] if they aren't on their way
+/// here, or took so long that the transfer expired, either way they spawn as normal.
+pub async fn take(
+	database: &PgPool,
+	player: Id,
+	sector: &str,
+) -> Result<Option<Location>, sqlx::Error> {
+	let row = query!(
+		r#"DELETE FROM player_transfers WHERE player_id = $1 AND destination = $2
+			RETURNING position, rotation, expires > NOW() AS "fresh!""#,
+		player as _,
+		sector,
+	)
+	.fetch_optional(database)
+	.await?;
+
+	Ok(row
+		.filter(|row| row.fresh)
+		.and_then(|row| location(&row.position, &row.rotation)))
+}
+
+/// Deletes transfers that expired without the player arriving, returning how many there were. Any sector may clean up
+/// after any other, as the destination might never come up to do it itself.
+pub async fn expire(database: &PgPool) -> Result<u64, sqlx::Error> {
+	let result = query!("DELETE FROM player_transfers WHERE expires < NOW()")
+		.execute(database)
+		.await?;
+
+	Ok(result.rows_affected())
+}
+
+/// The table has check constraints on the lengths, so this should only be [`None`] if someone has been editing rows.
+fn location(position: &[f32], rotation: &[f32]) -> Option<Location> {
+	let [x, y, z] = *position else {
+		return None;
+	};
+
+	let [i, j, k, w] = *rotation else {
+		return None;
+	};
+
+	Some(Location {
+		position: Point3::new(x, y, z),
+		rotation: UnitQuaternion::from_quaternion(Quaternion::new(w, i, j, k)),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{env, thread};
+
+	/// Where CI and devenv put the database, `.env` is what the query macros check against when the variable isn't set.
+	fn database_url() -> String {
+		env::var("DATABASE_URL").unwrap_or_else(|_| {
+			include_str!("../../.env")
+				.lines()
+				.find_map(|line| line.strip_prefix("DATABASE_URL="))
+				.expect(".env should set DATABASE_URL")
+				.to_owned()
+		})
+	}
+
+	/// A player of their own, so tests can run alongside each other against the same database.
+	async fn player(database: &PgPool) -> Id {
+		let id = Id::new();
+
+		query!("INSERT INTO inventories(id) VALUES ($1)", id as _)
+			.execute(database)
+			.await
+			.unwrap();
+
+		query!(
+			"INSERT INTO players(id, username, email, password) VALUES ($1, $2, $3, '')",
+			id as _,
+			format!("transfer-{id}"),
+			format!("transfer-{id}@test"),
+		)
+		.execute(database)
+		.await
+		.unwrap();
+
+		id
+	}
+
+	async fn remove(database: &PgPool, player: Id) {
+		query!("DELETE FROM players WHERE id = $1", player as _)
+			.execute(database)
+			.await
+			.unwrap();
+
+		query!("DELETE FROM inventories WHERE id = $1", player as _)
+			.execute(database)
+			.await
+			.unwrap();
+	}
+
+	fn somewhere() -> Location {
+		Location {
+			position: Point3::new(1.0, -2.0, 3.5),
+			rotation: UnitQuaternion::from_euler_angles(0.1, 0.2, 0.3),
+		}
+	}
+
+	#[tokio::test]
+	async fn transfers_are_taken_once_by_their_destination() {
+		let database = PgPool::connect(&database_url()).await.unwrap();
+		let player = player(&database).await;
+		let expiry = Duration::from_secs(60);
+
+		assert!(
+			create(&database, player, "here", "there", somewhere(), expiry)
+				.await
+				.unwrap()
+		);
+		assert!(
+			!create(&database, player, "here", "elsewhere", somewhere(), expiry)
+				.await
+				.unwrap()
+		);
+
+		assert!(take(&database, player, "elsewhere")
+			.await
+			.unwrap()
+			.is_none());
+
+		let arrival = take(&database, player, "there").await.unwrap().unwrap();
+		assert_eq!(arrival.position, somewhere().position);
+		assert!(arrival.rotation.angle_to(&somewhere().rotation) < 1e-6);
+
+		assert!(take(&database, player, "there").await.unwrap().is_none());
+
+		remove(&database, player).await;
+	}
+
+	#[tokio::test]
+	async fn expired_transfers_are_ignored_replaced_and_cleaned_up() {
+		let database = PgPool::connect(&database_url()).await.unwrap();
+		let player = player(&database).await;
+
+		assert!(create(
+			&database,
+			player,
+			"here",
+			"there",
+			somewhere(),
+			Duration::ZERO
+		)
+		.await
+		.unwrap());
+		thread::sleep(Duration::from_millis(10));
+
+		// Taking an expired transfer still consumes it, they just spawn as normal
+		assert!(take(&database, player, "there").await.unwrap().is_none());
+		assert!(create(
+			&database,
+			player,
+			"here",
+			"there",
+			somewhere(),
+			Duration::ZERO
+		)
+		.await
+		.unwrap());
+		thread::sleep(Duration::from_millis(10));
+
+		// Expired transfers don't block new ones
+		let expiry = Duration::from_secs(60);
+		assert!(
+			create(&database, player, "here", "elsewhere", somewhere(), expiry)
+				.await
+				.unwrap()
+		);
+		assert!(take(&database, player, "elsewhere")
+			.await
+			.unwrap()
+			.is_some());
+
+		assert!(create(
+			&database,
+			player,
+			"here",
+			"there",
+			somewhere(),
+			Duration::ZERO
+		)
+		.await
+		.unwrap());
+		thread::sleep(Duration::from_millis(10));
+
+		// Other tests may leave expired transfers behind too, so this can only be a lower bound
+		assert!(expire(&database).await.unwrap() >= 1);
+		let remaining = query!(
+			"SELECT COUNT(*) AS \"count!\" FROM player_transfers WHERE player_id = $1",
+			player as _
+		)
+		.fetch_one(&database)
+		.await
+		.unwrap();
+		assert_eq!(remaining.count, 0);
+
+		remove(&database, player).await;
+	}
+
+	#[test]
+	fn locations_need_three_positions_and_four_rotations() {
+		let upright = location(&[1.0, 2.0, 3.0], &[0.0, 0.0, 0.0, 1.0]).unwrap();
+		assert_eq!(upright.position, Point3::new(1.0, 2.0, 3.0));
+		assert_eq!(upright.rotation, UnitQuaternion::identity());
+
+		// i, j, k, then w, a half turn around x
+		let turned = location(&[0.0; 3], &[1.0, 0.0, 0.0, 0.0]).unwrap();
+		assert!((turned.rotation.angle() - std::f32::consts::PI).abs() < 1e-6);
+		assert_eq!(
+			turned.rotation.axis().unwrap().into_inner(),
+			nalgebra::Vector3::x()
+		);
+
+		assert!(location(&[1.0, 2.0], &[0.0, 0.0, 0.0, 1.0]).is_none());
+		assert!(location(&[1.0, 2.0, 3.0, 4.0], &[0.0, 0.0, 0.0, 1.0]).is_none());
+		assert!(location(&[1.0, 2.0, 3.0], &[0.0, 0.0, 1.0]).is_none());
+		assert!(location(&[1.0, 2.0, 3.0], &[]).is_none());
+	}
+}
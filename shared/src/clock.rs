@@ -0,0 +1,157 @@
+use std::{
+	future::{pending, Future},
+	pin::Pin,
+	sync::Arc,
+	time::{Duration, Instant, SystemTime},
+};
+use tokio::{sync::watch, time::sleep_until};
+
+/// Anything that waits on or compares against the time should get it from a clock rather than asking the system
+/// directly, so that it can be run against a [`MockClock`] instead of waiting in real time.
+pub trait Clock: Send + Sync + 'static {
+	fn now_instant(&self) -> Instant;
+
+	/// The current date and time, for anything stored or compared against the database.
+	fn now_utc(&self) -> SystemTime;
+
+	/// Completes once [`Clock::now_instant`] reaches `deadline`.
+	fn sleep_until(&self, deadline: Instant) -> Sleep;
+
+	fn sleep(&self, duration: Duration) -> Sleep {
+		self.sleep_until(self.now_instant() + duration)
+	}
+}
+
+/// Returned by [`Clock::sleep_until`]. Boxed so that [`Clock`] can be used as a trait object, it's also [`Unpin`] so
+/// it can be awaited by reference in `select!` and replaced to reset it.
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send>>;
+
+/// The default clock shared by anything which wasn't given one.
+pub fn system() -> Arc<dyn Clock> {
+	Arc::new(SystemClock)
+}
+
+/// The system's clocks, sleeping with tokio's timer.
+#[derive(Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+	fn now_instant(&self) -> Instant {
+		Instant::now()
+	}
+
+	fn now_utc(&self) -> SystemTime {
+		SystemTime::now()
+	}
+
+	fn sleep_until(&self, deadline: Instant) -> Sleep {
+		Box::pin(sleep_until(deadline.into()))
+	}
+}
+
+/// A clock that only moves when [`MockClock::advance`] is called, which wakes any sleeps it passes straight away.
+/// Starts at the system's time when it's created.
+pub struct MockClock {
+	start: Instant,
+	start_utc: SystemTime,
+	elapsed: watch::Sender<Duration>,
+}
+
+impl MockClock {
+	pub fn new() -> Self {
+		Self {
+			start: Instant::now(),
+			start_utc: SystemTime::now(),
+			elapsed: watch::Sender::new(Duration::ZERO),
+		}
+	}
+
+	pub fn advance(&self, duration: Duration) {
+		self.elapsed.send_modify(|elapsed| *elapsed += duration);
+	}
+}
+
+impl Default for MockClock {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Clock for MockClock {
+	fn now_instant(&self) -> Instant {
+		self.start + *self.elapsed.borrow()
+	}
+
+	fn now_utc(&self) -> SystemTime {
+		self.start_utc + *self.elapsed.borrow()
+	}
+
+	fn sleep_until(&self, deadline: Instant) -> Sleep {
+		let start = self.start;
+		let mut elapsed = self.elapsed.subscribe();
+
+		Box::pin(async move {
+			// The clock can't move again once it's dropped, so the sleep never finishes
+			if elapsed
+				.wait_for(|elapsed| start + *elapsed >= deadline)
+				.await
+				.is_err()
+			{
+				pending::<()>().await;
+			}
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::task::{Context, Waker};
+
+	fn is_done(sleep: &mut Sleep) -> bool {
+		sleep
+			.as_mut()
+			.poll(&mut Context::from_waker(Waker::noop()))
+			.is_ready()
+	}
+
+	#[test]
+	fn mock_clocks_only_move_when_advanced() {
+		let clock = MockClock::new();
+		let (instant, utc) = (clock.now_instant(), clock.now_utc());
+
+		assert_eq!(clock.now_instant(), instant);
+
+		clock.advance(Duration::from_secs(90));
+		assert_eq!(clock.now_instant() - instant, Duration::from_secs(90));
+		assert_eq!(
+			clock.now_utc().duration_since(utc).unwrap(),
+			Duration::from_secs(90)
+		);
+	}
+
+	#[test]
+	fn mock_sleeps_finish_once_advanced_past() {
+		let clock = MockClock::new();
+		let mut sleep = clock.sleep(Duration::from_secs(10));
+		let mut elapsed = clock.sleep_until(clock.now_instant());
+
+		assert!(is_done(&mut elapsed));
+		assert!(!is_done(&mut sleep));
+
+		clock.advance(Duration::from_secs(9));
+		assert!(!is_done(&mut sleep));
+
+		clock.advance(Duration::from_secs(1));
+		assert!(is_done(&mut sleep));
+	}
+
+	#[test]
+	fn mock_sleeps_never_finish_once_the_clock_is_gone() {
+		let clock = MockClock::new();
+		let mut sleep = clock.sleep(Duration::from_secs(1));
+		drop(clock);
+
+		assert!(!is_done(&mut sleep));
+	}
+}
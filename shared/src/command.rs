@@ -0,0 +1,78 @@
+use thiserror::Error;
+
+/// Splits `text` into arguments on whitespace. Double quotes group words into a single argument, and inside quotes `\`
+/// makes the character after it literal, so `\"` is a quote. Used by both the sector's commands and the client's
+/// console, so that they're typed the same way.
+pub fn tokenize(text: &str) -> Result<Vec<String>, UnclosedQuote> {
+	let mut tokens = vec![];
+
+	// Some once a token has started, so that "" is an empty argument rather than nothing
+	let mut token: Option<String> = None;
+	let mut quoted = false;
+
+	let mut characters = text.chars();
+
+	while let Some(character) = characters.next() {
+		match character {
+			'"' => {
+				quoted = !quoted;
+				token.get_or_insert_with(String::new);
+			}
+			'\\' if quoted => match characters.next() {
+				Some(character) => token.get_or_insert_with(String::new).push(character),
+				None => return Err(UnclosedQuote),
+			},
+			character if character.is_whitespace() && !quoted => tokens.extend(token.take()),
+			character => token.get_or_insert_with(String::new).push(character),
+		}
+	}
+
+	if quoted {
+		return Err(UnclosedQuote);
+	}
+
+	tokens.extend(token);
+
+	Ok(tokens)
+}
+
+#[derive(Debug, Error)]
+#[error("Unclosed quote")]
+pub struct UnclosedQuote;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn tokens(text: &str) -> Vec<String> {
+		tokenize(text).unwrap()
+	}
+
+	#[test]
+	fn splits_on_whitespace() {
+		assert_eq!(tokens("tp  someone\t1 "), ["tp", "someone", "1"]);
+		assert!(tokens("   ").is_empty());
+	}
+
+	#[test]
+	fn quotes_group_words() {
+		assert_eq!(
+			tokens(r#"say "hello there" friend"#),
+			["say", "hello there", "friend"]
+		);
+		assert_eq!(tokens(r#"a"b c"d"#), ["ab cd"]);
+		assert_eq!(tokens(r#"name "" end"#), ["name", "", "end"]);
+	}
+
+	#[test]
+	fn backslashes_escape_inside_quotes() {
+		assert_eq!(tokens(r#""a \"quote\"" \\"#), [r#"a "quote""#, r"\\"]);
+		assert_eq!(tokens(r#""back\\slash""#), [r"back\slash"]);
+	}
+
+	#[test]
+	fn unclosed_quotes_are_refused() {
+		assert!(tokenize(r#"say "hello"#).is_err());
+		assert!(tokenize(r#"say "ends with \"#).is_err());
+	}
+}
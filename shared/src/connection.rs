@@ -1,20 +1,41 @@
-use crate::message::{clientbound::Clientbound, serverbound::Serverbound};
+use crate::{
+	clock::{self, Clock},
+	message::{clientbound::Clientbound, serverbound::Serverbound},
+};
 use chacha20poly1305::{AeadInPlace, ChaCha20Poly1305};
-use log::warn;
+use log::{info, warn};
 use serde::{de::DeserializeOwned, Serialize};
-use std::{io, marker::PhantomData, ops::Deref, sync::Arc, time::Duration};
+use socket2::{SockRef, TcpKeepalive};
+use std::{
+	io::{self, ErrorKind},
+	marker::PhantomData,
+	ops::Deref,
+	sync::{
+		atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed},
+		Arc,
+	},
+	time::{Duration, Instant},
+};
 use thiserror::Error;
 use tokio::{
-	io::{AsyncReadExt, AsyncWriteExt, BufStream},
+	io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufStream},
 	net::TcpStream,
-	pin, select,
+	select,
 	sync::mpsc::{
 		error::TryRecvError, unbounded_channel as channel, UnboundedReceiver as Receiver,
 		UnboundedSender as Sender,
 	},
-	time::sleep,
+	task::spawn_blocking,
 };
 
+/// Messages are framed with a u16 length prefix, so this is the largest a message can be once encrypted. A length of 0
+/// is reserved for keep-alives.
+pub const MAX_MESSAGE_LENGTH: usize = u16::MAX as usize;
+
+/// Messages at least this large once encrypted are decrypted and deserialized on a blocking thread, so that a burst
+/// of chunks doesn't hold up the runtime's other tasks.
+const BLOCKING_DECODE_LENGTH: usize = 8 * 1024;
+
 pub trait ConnectionSide: Default + Send + 'static {
 	type I: DeserializeOwned + Send;
 	type O: Serialize + Send;
@@ -105,6 +126,23 @@ impl ConnectionSide for ServerEnd {
 	}
 }
 
+/// How often a keep-alive is sent when nothing else has been, and how long the peer may stay silent before the
+/// connection is given up on. The timeout should be comfortably longer than the peer's interval.
+#[derive(Clone, Copy, Debug)]
+pub struct KeepAlive {
+	pub interval: Duration,
+	pub timeout: Duration,
+}
+
+impl Default for KeepAlive {
+	fn default() -> Self {
+		Self {
+			interval: Duration::from_secs(10),
+			timeout: Duration::from_secs(20),
+		}
+	}
+}
+
 pub struct Connection<E: ConnectionSide> {
 	sender: Arc<ConnectionSend<E>>,
 	incoming: Receiver<E::I>,
@@ -112,18 +150,106 @@ pub struct Connection<E: ConnectionSide> {
 
 pub struct ConnectionSend<E: ConnectionSide> {
 	outgoing: Sender<E::O>,
+	/// [`None`] for connections made with [`Connection::pair`], which can't degrade.
+	stats: Option<Arc<ConnectionStats>>,
+}
+
+/// How a connection over the network is doing, updated as it reads and writes.
+pub struct ConnectionStats {
+	start: Instant,
+	/// Milliseconds after `start` that anything, keep-alives included, was last read from the peer.
+	last_received: AtomicU64,
+	/// Messages sent but not yet written to the stream.
+	queued: AtomicUsize,
+}
+
+impl ConnectionStats {
+	fn new(now: Instant) -> Self {
+		Self {
+			start: now,
+			last_received: AtomicU64::new(0),
+			queued: AtomicUsize::new(0),
+		}
+	}
+
+	/// When anything was last read from the peer, keep-alives included. The connection being made counts.
+	pub fn last_received(&self) -> Instant {
+		self.start + Duration::from_millis(self.last_received.load(Relaxed))
+	}
+
+	fn received(&self, now: Instant) {
+		let elapsed = now.saturating_duration_since(self.start).as_millis();
+		self.last_received.store(elapsed as u64, Relaxed);
+	}
+
+	/// Messages sent but not yet written to the stream, roughly how far behind the connection is.
+	pub fn queued(&self) -> usize {
+		self.queued.load(Relaxed)
+	}
+}
+
+impl Connection<ClientEnd> {
+	/// Creates a connected pair of connections which never leave the process, for a sector running inside the client.
+	/// Messages are passed along as they are, without being serialized or encrypted.
+	pub fn pair() -> (Self, Connection<ServerEnd>) {
+		let (client_outgoing, server_incoming) = channel();
+		let (server_outgoing, client_incoming) = channel();
+
+		(
+			Self {
+				sender: Arc::new(ConnectionSend {
+					outgoing: client_outgoing,
+					stats: None,
+				}),
+				incoming: client_incoming,
+			},
+			Connection {
+				sender: Arc::new(ConnectionSend {
+					outgoing: server_outgoing,
+					stats: None,
+				}),
+				incoming: server_incoming,
+			},
+		)
+	}
 }
 
 impl<E: ConnectionSide> Connection<E> {
-	pub fn new(stream: TcpStream, cipher: ChaCha20Poly1305) -> Self {
-		let stream = BufStream::new(stream);
+	pub fn new(stream: TcpStream, cipher: ChaCha20Poly1305, keep_alive: KeepAlive) -> Self {
+		Self::with_clock(stream, cipher, keep_alive, clock::system())
+	}
+
+	/// Like [`Connection::new`], but keep-alives and timeouts go by `clock`.
+	pub fn with_clock(
+		stream: TcpStream,
+		cipher: ChaCha20Poly1305,
+		keep_alive: KeepAlive,
+		clock: Arc<dyn Clock>,
+	) -> Self {
+		if let Err(error) = Self::configure_socket(&stream, keep_alive) {
+			warn!("Unable to configure connection socket: {error}");
+		}
+
+		Self::spawn(BufStream::new(stream), cipher, keep_alive, clock)
+	}
 
+	/// Starts handling the connection over `stream`, which has already been set up.
+	fn spawn<S: AsyncRead + AsyncWrite + Send + Unpin + 'static>(
+		stream: S,
+		cipher: ChaCha20Poly1305,
+		keep_alive: KeepAlive,
+		clock: Arc<dyn Clock>,
+	) -> Self {
 		let (send_incoming, recv_incoming) = channel();
 		let (send_outgoing, recv_outgoing) = channel();
+		let stats = Arc::new(ConnectionStats::new(clock.now_instant()));
 
 		tokio::spawn(Self::handle_connection(
 			stream,
 			cipher,
+			keep_alive,
+			clock,
+			stats.clone(),
 			send_incoming,
 			recv_outgoing,
 		));
@@ -131,6 +257,7 @@ impl<E: ConnectionSide> Connection<E> {
 		Self {
 			sender: Arc::new(ConnectionSend {
 				outgoing: send_outgoing,
+				stats: Some(stats),
 			}),
 			incoming: recv_incoming,
 		}
@@ -148,25 +275,73 @@ impl<E: ConnectionSide> Connection<E> {
 		self.incoming.try_recv()
 	}
 
-	async fn handle_connection(
-		mut stream: BufStream<TcpStream>,
+	/// Messages are small and latency matters more than throughput, so Nagle's algorithm is disabled. The OS's own
+	/// keep-alive is enabled as well, and on Linux unacknowledged writes time out too, so that a peer which vanished
+	/// without closing the connection is noticed while we're still sending to it.
+	fn configure_socket(stream: &TcpStream, keep_alive: KeepAlive) -> io::Result<()> {
+		stream.set_nodelay(true)?;
+
+		let socket = SockRef::from(stream);
+
+		socket.set_tcp_keepalive(
+			&TcpKeepalive::new()
+				.with_time(keep_alive.interval)
+				.with_interval(keep_alive.interval),
+		)?;
+
+		#[cfg(target_os = "linux")]
+		socket.set_tcp_user_timeout(Some(keep_alive.timeout))?;
+
+		Ok(())
+	}
+
+	async fn handle_connection<S: AsyncRead + AsyncWrite + Unpin>(
+		mut stream: S,
 		cipher: ChaCha20Poly1305,
+		keep_alive: KeepAlive,
+		clock: Arc<dyn Clock>,
+		stats: Arc<ConnectionStats>,
 		incoming: Sender<E::I>,
 		outgoing: Receiver<E::O>,
 	) {
-		match Self::connection_loop(&mut stream, cipher, incoming, outgoing).await {
+		let (send_frames, recv_frames) = channel();
+
+		// Frames are decoded separately from the connection loop, so however long decoding takes, keep-alives are still
+		// written and read on time
+		let result = select! {
+			result = Self::connection_loop(
+				&mut stream,
+				cipher.clone(),
+				keep_alive,
+				&*clock,
+				&stats,
+				send_frames,
+				outgoing,
+			) => result,
+			result = Self::decode_loop(cipher, recv_frames, incoming) => result,
+		};
+
+		match result {
 			Ok(_) => {}
-			Err(error) => warn!("Error occurred in connection: {error}"),
+			Err(ConnectionError::PeerGone(error)) => info!("Connection closed by peer: {error}"),
+			Err(error) => crate::throttled_warn!(
+				"connection::error",
+				Duration::from_secs(10),
+				"Error occurred in connection: {error}"
+			),
 		}
 
 		// We're shutting down the stream either way, don't care
 		let _ = stream.shutdown().await;
 	}
 
-	async fn connection_loop(
-		stream: &mut BufStream<TcpStream>,
+	async fn connection_loop<S: AsyncRead + AsyncWrite + Unpin>(
+		stream: &mut S,
 		cipher: ChaCha20Poly1305,
-		incoming: Sender<E::I>,
+		keep_alive_timing: KeepAlive,
+		clock: &dyn Clock,
+		stats: &ConnectionStats,
+		frames: Sender<Vec<u8>>,
 		mut outgoing: Receiver<E::O>,
 	) -> Result<Closed, ConnectionError> {
 		let mut nonce_counter = NonceCounter::<E>::default();
@@ -176,12 +351,10 @@ impl<E: ConnectionSide> Connection<E> {
 		// a byte is cancellation safe.
 		let mut length_first_byte = None;
 
-		// The `sleep` is not cancellation safe, we can work around this by pinning them, this means they never get
-		// cancelled.
-		pin! {
-			let keep_alive = sleep(Duration::from_secs(10));
-			let time_out = sleep(Duration::from_secs(20));
-		};
+		// Sleeps are not cancellation safe, so they're kept outside of the loop and only polled by reference, this means
+		// they never get cancelled.
+		let mut keep_alive = clock.sleep(keep_alive_timing.interval);
+		let mut time_out = clock.sleep(keep_alive_timing.timeout);
 
 		loop {
 			select! {
@@ -194,21 +367,27 @@ impl<E: ConnectionSide> Connection<E> {
 					stream.write_u16_le(0).await?;
 					stream.flush().await?;
 
-					keep_alive.set(sleep(Duration::from_secs(10)));
+					keep_alive = clock.sleep(keep_alive_timing.interval);
 				},
 
 				message = outgoing.recv() => match message {
 					Some(message) => {
+						stats.queued.fetch_sub(1, Relaxed);
+
 						let mut buffer = bincode::serialize(&message)?;
 
 						let nonce = E::next(&mut nonce_counter);
 						cipher.encrypt_in_place((&nonce).into(), b"", &mut buffer)?;
 
-						stream.write_u16_le(buffer.len() as u16).await?;
+						// Truncating the length would desync the stream, so give up on the connection instead
+						let length = u16::try_from(buffer.len())
+							.map_err(|_| ConnectionError::MessageTooLarge(buffer.len()))?;
+
+						stream.write_u16_le(length).await?;
 						stream.write_all(&buffer).await?;
 						stream.flush().await?;
 
-						keep_alive.set(sleep(Duration::from_secs(10)));
+						keep_alive = clock.sleep(keep_alive_timing.interval);
 					},
 
 					None => return Ok(Closed),
@@ -231,23 +410,57 @@ impl<E: ConnectionSide> Connection<E> {
 								let mut buffer = vec![0; length as usize];
 								stream.read_exact(&mut buffer).await?;
 
-								let nonce = E::peer_next(&mut nonce_counter);
-								cipher.decrypt_in_place((&nonce).into(), b"", &mut buffer)?;
-
-								let message = bincode::deserialize(&buffer)?;
-
-								if incoming.send(message).is_err() {
+								if frames.send(buffer).is_err() {
 									return Ok(Closed);
 								}
 							}
 
-							time_out.set(sleep(Duration::from_secs(20)));
+							time_out = clock.sleep(keep_alive_timing.timeout);
+							stats.received(clock.now_instant());
 						}
 					}
 				},
 			}
 		}
 	}
+
+	/// Decrypts and deserializes frames in the order they were read.
+	async fn decode_loop(
+		cipher: ChaCha20Poly1305,
+		mut frames: Receiver<Vec<u8>>,
+		incoming: Sender<E::I>,
+	) -> Result<Closed, ConnectionError> {
+		let mut nonce_counter = NonceCounter::<E>::default();
+
+		while let Some(mut buffer) = frames.recv().await {
+			let nonce = E::peer_next(&mut nonce_counter);
+
+			let message = match buffer.len() >= BLOCKING_DECODE_LENGTH {
+				true => {
+					let cipher = cipher.clone();
+					spawn_blocking(move || Self::decode(&cipher, &nonce, &mut buffer))
+						.await
+						.expect("decoding a message shouldn't panic")?
+				}
+				false => Self::decode(&cipher, &nonce, &mut buffer)?,
+			};
+
+			if incoming.send(message).is_err() {
+				return Ok(Closed);
+			}
+		}
+
+		Ok(Closed)
+	}
+
+	fn decode(
+		cipher: &ChaCha20Poly1305,
+		nonce: &[u8; 12],
+		buffer: &mut Vec<u8>,
+	) -> Result<E::I, ConnectionError> {
+		cipher.decrypt_in_place(nonce.into(), b"", buffer)?;
+		Ok(bincode::deserialize(buffer)?)
+	}
 }
 
 impl<E: ConnectionSide> ConnectionSend<E> {
@@ -256,8 +469,17 @@ impl<E: ConnectionSide> ConnectionSend<E> {
 	}
 
 	pub fn send(&self, message: impl Into<E::O>) {
+		if let Some(stats) = &self.stats {
+			stats.queued.fetch_add(1, Relaxed);
+		}
+
 		let _ = self.outgoing.send(message.into());
 	}
+
+	/// [`None`] for connections made with [`Connection::pair`].
+	pub fn stats(&self) -> Option<&ConnectionStats> {
+		self.stats.as_deref()
+	}
 }
 
 impl<E: ConnectionSide> Deref for Connection<E> {
@@ -284,12 +506,34 @@ enum ConnectionError {
 	#[error("timed out")]
 	TimedOut,
 
-	Io(#[from] io::Error),
+	/// The peer closed or reset the connection, or it was otherwise lost underneath us.
+	#[error("peer is gone: {0}")]
+	PeerGone(io::Error),
+
+	Io(io::Error),
 
 	Bincode(#[from] bincode::Error),
 
 	#[error("encryption error")]
 	Encryption,
+
+	#[error("message of {0} bytes exceeds the maximum of {MAX_MESSAGE_LENGTH} bytes")]
+	MessageTooLarge(usize),
+}
+
+impl From<io::Error> for ConnectionError {
+	fn from(error: io::Error) -> Self {
+		match error.kind() {
+			ErrorKind::ConnectionReset
+			| ErrorKind::ConnectionAborted
+			| ErrorKind::BrokenPipe
+			| ErrorKind::NotConnected
+			| ErrorKind::UnexpectedEof
+			// What a TCP user timeout surfaces as
+			| ErrorKind::TimedOut => Self::PeerGone(error),
+			_ => Self::Io(error),
+		}
+	}
 }
 
 impl From<chacha20poly1305::Error> for ConnectionError {
@@ -297,3 +541,432 @@ impl From<chacha20poly1305::Error> for ConnectionError {
 		Self::Encryption
 	}
 }
+
+/// Parses a pre-shared key, which is written as exactly 64 hex digits. Surrounding whitespace is ignored, so that keys
+/// can be read straight from a file.
+pub fn parse_preshared_key(text: &str) -> Result<[u8; 32], KeyParseError> {
+	let text = text.trim();
+
+	if let Some(invalid) = text
+		.chars()
+		.find(|character| !character.is_ascii_hexdigit())
+	{
+		return Err(KeyParseError::InvalidDigit(invalid));
+	}
+
+	// Only ASCII is left, so this is the number of digits
+	if text.len() != 64 {
+		return Err(KeyParseError::WrongLength(text.len()));
+	}
+
+	let mut key = [0; 32];
+
+	for (byte, digits) in key.iter_mut().zip(text.as_bytes().chunks_exact(2)) {
+		let digits = std::str::from_utf8(digits).expect("hex digits should be valid UTF-8");
+		*byte = u8::from_str_radix(digits, 16).expect("hex digits should parse");
+	}
+
+	Ok(key)
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum KeyParseError {
+	#[error("key must be 64 hex digits, but it has {0}")]
+	WrongLength(usize),
+
+	#[error("key contains `{0}`, which isn't a hex digit")]
+	InvalidDigit(char),
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::{
+		clock::MockClock,
+		message::{
+			clientbound::{ChatHistory, ChatMessage, Pong},
+			fingerprint::{clientbound_samples, serverbound_samples},
+		},
+	};
+	use chacha20poly1305::KeyInit;
+	use serde::{Deserialize, Deserializer};
+	use tokio::{
+		io::{duplex, DuplexStream},
+		task::yield_now,
+		time::timeout,
+	};
+
+	/// How long [`Slow`] takes to deserialize.
+	const SLOW_DECODE: Duration = Duration::from_millis(500);
+
+	/// Takes [`SLOW_DECODE`] to deserialize, like a big chunk on a slow machine would.
+	struct Slow;
+
+	impl<'de> Deserialize<'de> for Slow {
+		fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+			Vec::<u8>::deserialize(deserializer)?;
+			std::thread::sleep(SLOW_DECODE);
+			Ok(Self)
+		}
+	}
+
+	/// A server end that receives [`Slow`] messages.
+	#[derive(Default)]
+	struct SlowEnd;
+
+	impl ConnectionSide for SlowEnd {
+		type I = Slow;
+		type O = ();
+
+		fn next(counter: &mut NonceCounter<Self>) -> [u8; 12] {
+			counter.server_next()
+		}
+
+		fn peer_next(counter: &mut NonceCounter<Self>) -> [u8; 12] {
+			counter.client_next()
+		}
+	}
+
+	fn cipher() -> ChaCha20Poly1305 {
+		ChaCha20Poly1305::new(&[7; 32].into())
+	}
+
+	fn connected() -> (Connection<ClientEnd>, Connection<ServerEnd>) {
+		let (client, server) = duplex(4 * MAX_MESSAGE_LENGTH);
+		let cipher = cipher();
+
+		(
+			Connection::spawn(
+				client,
+				cipher.clone(),
+				KeepAlive::default(),
+				clock::system(),
+			),
+			Connection::spawn(server, cipher, KeepAlive::default(), clock::system()),
+		)
+	}
+
+	/// A server end whose peer is played by hand, through the returned stream.
+	async fn raw_peer(
+		keep_alive: KeepAlive,
+		clock: Arc<dyn Clock>,
+	) -> (Connection<ServerEnd>, DuplexStream) {
+		let (server, peer) = duplex(4 * MAX_MESSAGE_LENGTH);
+		let connection = Connection::spawn(server, cipher(), keep_alive, clock);
+
+		// Let the connection start, so it's keep-alive and timeout go from now and not after the clock is advanced
+		yield_now().await;
+
+		(connection, peer)
+	}
+
+	/// Reads the next frame the peer was sent, returning it's length, so 0 is a keep-alive.
+	async fn next_frame(peer: &mut DuplexStream) -> u16 {
+		timeout(Duration::from_secs(5), async {
+			let length = peer.read_u16_le().await.expect("peer should read a length");
+			let mut buffer = vec![0; length as usize];
+			peer.read_exact(&mut buffer)
+				.await
+				.expect("peer should read the frame");
+			length
+		})
+		.await
+		.expect("peer should be sent a frame")
+	}
+
+	async fn recv<E: ConnectionSide>(connection: &mut Connection<E>) -> Option<E::I> {
+		timeout(Duration::from_secs(5), connection.recv())
+			.await
+			.expect("connection should receive or close")
+	}
+
+	#[tokio::test]
+	async fn messages_round_trip() {
+		let (mut client, mut server) = connected();
+
+		for message in clientbound_samples() {
+			let expected = bincode::serialize(&message).unwrap();
+			server.send(message);
+
+			let received = recv(&mut client).await.expect("client should receive");
+			assert_eq!(bincode::serialize(&received).unwrap(), expected);
+		}
+
+		for message in serverbound_samples() {
+			let expected = bincode::serialize(&message).unwrap();
+			client.send(message);
+
+			let received = recv(&mut server).await.expect("server should receive");
+			assert_eq!(bincode::serialize(&received).unwrap(), expected);
+		}
+	}
+
+	#[tokio::test]
+	async fn oversized_message_closes_connection() {
+		let (mut client, server) = connected();
+
+		let message = ChatMessage {
+			sequence: 0,
+			player: "1".parse().unwrap(),
+			text: "x".repeat(1024).into(),
+		};
+
+		// Just over the limit, which a length prefix can't hold
+		server.send(ChatHistory(vec![message; 64]));
+
+		assert!(recv(&mut client).await.is_none());
+		assert!(!server.is_connected());
+	}
+
+	#[tokio::test]
+	async fn message_at_limit_is_sent() {
+		let (mut client, server) = connected();
+
+		// A chat message's overhead: variant, sequence, player, and text length
+		let overhead = 4 + 8 + 8 + 8;
+		let text = "x".repeat(MAX_MESSAGE_LENGTH - 16 - overhead);
+
+		server.send(ChatMessage {
+			sequence: 0,
+			player: "1".parse().unwrap(),
+			text: text.clone().into(),
+		});
+
+		match recv(&mut client).await {
+			Some(Clientbound::ChatMessage(message)) => assert_eq!(*message.text, text),
+			_ => panic!("client should receive the chat message"),
+		}
+	}
+
+	#[tokio::test]
+	async fn silent_peer_times_out() {
+		let clock = Arc::new(MockClock::new());
+		let (mut connection, mut peer) = raw_peer(KeepAlive::default(), clock.clone()).await;
+
+		// Keep-alives are still sent to a quiet peer, it's just not sending any back
+		clock.advance(Duration::from_secs(15));
+		assert_eq!(next_frame(&mut peer).await, 0);
+		assert!(connection.is_connected());
+
+		clock.advance(Duration::from_secs(6));
+		assert!(recv(&mut connection).await.is_none());
+		assert!(!connection.is_connected());
+	}
+
+	#[tokio::test]
+	async fn peer_keep_alives_hold_off_timeout() {
+		let clock = Arc::new(MockClock::new());
+		let (connection, mut peer) = raw_peer(KeepAlive::default(), clock.clone()).await;
+
+		for _ in 0..4 {
+			clock.advance(Duration::from_secs(15));
+			peer.write_u16_le(0).await.unwrap();
+			assert_eq!(next_frame(&mut peer).await, 0);
+		}
+
+		assert!(connection.is_connected());
+	}
+
+	#[tokio::test]
+	async fn traffic_holds_off_keep_alives() {
+		let clock = Arc::new(MockClock::new());
+		let keep_alive = KeepAlive {
+			interval: Duration::from_secs(10),
+			timeout: Duration::from_secs(60),
+		};
+		let (connection, mut peer) = raw_peer(keep_alive, clock.clone()).await;
+
+		// Every message goes out before a keep-alive would be due, and pushes the next one back
+		for _ in 0..5 {
+			clock.advance(Duration::from_secs(6));
+			connection.send(ChatHistory(vec![]));
+			assert_ne!(
+				next_frame(&mut peer).await,
+				0,
+				"keep-alive sent between messages"
+			);
+		}
+
+		// Once it goes quiet they're sent again
+		clock.advance(Duration::from_secs(10));
+		assert_eq!(next_frame(&mut peer).await, 0);
+	}
+
+	#[test]
+	fn lost_connections_are_peer_gone() {
+		let gone = [
+			ErrorKind::ConnectionReset,
+			ErrorKind::ConnectionAborted,
+			ErrorKind::BrokenPipe,
+			ErrorKind::NotConnected,
+			ErrorKind::UnexpectedEof,
+			ErrorKind::TimedOut,
+		];
+
+		for kind in gone {
+			let error = ConnectionError::from(io::Error::from(kind));
+			assert!(matches!(error, ConnectionError::PeerGone(_)), "{kind:?}");
+		}
+
+		for kind in [
+			ErrorKind::InvalidData,
+			ErrorKind::PermissionDenied,
+			ErrorKind::Other,
+		] {
+			let error = ConnectionError::from(io::Error::from(kind));
+			assert!(matches!(error, ConnectionError::Io(_)), "{kind:?}");
+		}
+	}
+
+	#[tokio::test]
+	async fn write_to_vanished_peer_is_peer_gone() {
+		let (mut stream, peer) = duplex(MAX_MESSAGE_LENGTH);
+		drop(peer);
+
+		let (frames, _recv_frames) = channel();
+		let (outgoing, recv_outgoing) = channel::<Clientbound>();
+
+		for _ in 0..8 {
+			outgoing.send(ChatHistory(vec![]).into()).unwrap();
+		}
+
+		let clock = clock::system();
+		let stats = ConnectionStats::new(clock.now_instant());
+
+		let result = Connection::<ServerEnd>::connection_loop(
+			&mut stream,
+			cipher(),
+			KeepAlive::default(),
+			&*clock,
+			&stats,
+			frames,
+			recv_outgoing,
+		)
+		.await;
+
+		assert!(matches!(result, Err(ConnectionError::PeerGone(_))));
+	}
+
+	#[tokio::test]
+	async fn slow_decoding_doesnt_hold_up_keep_alives() {
+		let keep_alive = KeepAlive {
+			interval: Duration::from_millis(50),
+			timeout: Duration::from_secs(5),
+		};
+		let (stream, mut peer) = duplex(4 * MAX_MESSAGE_LENGTH);
+		let mut connection =
+			Connection::<SlowEnd>::spawn(stream, cipher(), keep_alive, clock::system());
+
+		// Big enough to be decoded on a blocking thread
+		let mut nonce_counter = NonceCounter::<ClientEnd>::default();
+		for _ in 0..2 {
+			let mut buffer = bincode::serialize(&vec![0_u8; BLOCKING_DECODE_LENGTH]).unwrap();
+			let nonce = ClientEnd::next(&mut nonce_counter);
+			cipher()
+				.encrypt_in_place((&nonce).into(), b"", &mut buffer)
+				.unwrap();
+
+			peer.write_u16_le(buffer.len() as u16).await.unwrap();
+			peer.write_all(&buffer).await.unwrap();
+		}
+
+		// Keep-alives should keep coming on schedule the whole time both are being decoded
+		let start = Instant::now();
+		let mut last = start;
+
+		while start.elapsed() < 2 * SLOW_DECODE {
+			assert_eq!(next_frame(&mut peer).await, 0);
+
+			let gap = last.elapsed();
+			assert!(
+				gap < SLOW_DECODE / 2,
+				"keep-alive came {gap:?} after the last"
+			);
+			last = Instant::now();
+		}
+
+		for _ in 0..2 {
+			assert!(recv(&mut connection).await.is_some());
+		}
+	}
+
+	#[test]
+	fn preshared_keys_are_64_hex_digits() {
+		let text = format!(
+			"  {}\n",
+			"0123456789abcdefABCDEF".repeat(3).get(..64).unwrap()
+		);
+		let key = parse_preshared_key(&text).unwrap();
+
+		assert_eq!(key[..4], [0x01, 0x23, 0x45, 0x67]);
+		assert_eq!(key[5..11], [0xab, 0xcd, 0xef, 0xab, 0xcd, 0xef]);
+		assert_eq!(parse_preshared_key(&"f".repeat(64)), Ok([0xff; 32]));
+	}
+
+	#[test]
+	fn malformed_preshared_keys_are_refused() {
+		assert_eq!(
+			parse_preshared_key(&"0".repeat(63)),
+			Err(KeyParseError::WrongLength(63))
+		);
+		assert_eq!(
+			parse_preshared_key(&"0".repeat(66)),
+			Err(KeyParseError::WrongLength(66))
+		);
+		assert_eq!(parse_preshared_key(""), Err(KeyParseError::WrongLength(0)));
+
+		// Checked before the length, so that a typo isn't reported as the wrong length
+		assert_eq!(
+			parse_preshared_key(&format!("{}g", "0".repeat(63))),
+			Err(KeyParseError::InvalidDigit('g'))
+		);
+		assert_eq!(
+			parse_preshared_key("0x00"),
+			Err(KeyParseError::InvalidDigit('x'))
+		);
+		assert_eq!(
+			parse_preshared_key(&"é".repeat(32)),
+			Err(KeyParseError::InvalidDigit('é'))
+		);
+	}
+
+	#[tokio::test]
+	async fn stats_track_when_the_peer_was_last_heard_from() {
+		let clock = Arc::new(MockClock::new());
+		let start = clock.now_instant();
+		let (connection, mut peer) = raw_peer(KeepAlive::default(), clock.clone()).await;
+		let sender = connection.sender();
+		let stats = sender.stats().unwrap();
+
+		assert_eq!(stats.last_received(), start);
+
+		// Keep-alives count
+		clock.advance(Duration::from_secs(5));
+		peer.write_u16_le(0).await.unwrap();
+
+		timeout(Duration::from_secs(5), async {
+			while stats.last_received() == start {
+				yield_now().await;
+			}
+		})
+		.await
+		.expect("the keep-alive should be read");
+
+		assert_eq!(stats.last_received(), start + Duration::from_secs(5));
+	}
+
+	#[tokio::test]
+	async fn sent_messages_are_queued_until_written() {
+		let (connection, mut peer) = raw_peer(KeepAlive::default(), clock::system()).await;
+		let sender = connection.sender();
+
+		sender.send(Pong { sequence: 1 });
+		assert!(sender.stats().unwrap().queued() <= 1);
+
+		next_frame(&mut peer).await;
+		assert_eq!(sender.stats().unwrap().queued(), 0);
+
+		let (client, _server) = Connection::<ClientEnd>::pair();
+		assert!(client.sender().stats().is_none());
+	}
+}
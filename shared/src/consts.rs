@@ -0,0 +1,121 @@
+/// Cells along each axis of a chunk, at every level.
+pub const CHUNK_SIZE: usize = 16;
+
+/// Samples along each axis of what a chunk is triangulated from. One more than [`CHUNK_SIZE`], as the cells on a
+/// chunk's far faces also need the corners they share with the next chunk over.
+pub const CHUNK_SAMPLE_SIZE: usize = CHUNK_SIZE + 1;
+
+/// Cells in a chunk, the length of it's materials and densities.
+pub const CELLS_PER_CHUNK: usize = CHUNK_SIZE.pow(3);
+
+/// Samples a chunk is triangulated from, see [`CHUNK_SAMPLE_SIZE`].
+pub const SAMPLES_PER_CHUNK: usize = CHUNK_SAMPLE_SIZE.pow(3);
+
+/// Index of the cell at `x`, `y`, `z` in a chunk's materials and densities, each axis must be less than
+/// [`CHUNK_SIZE`].
+pub const fn cell_index(x: usize, y: usize, z: usize) -> usize {
+	debug_assert!(x < CHUNK_SIZE && y < CHUNK_SIZE && z < CHUNK_SIZE);
+	(x * CHUNK_SIZE + y) * CHUNK_SIZE + z
+}
+
+/// Index of the sample at `x`, `y`, `z` in what a chunk is triangulated from, each axis must be less than
+/// [`CHUNK_SAMPLE_SIZE`].
+pub const fn sample_index(x: usize, y: usize, z: usize) -> usize {
+	debug_assert!(x < CHUNK_SAMPLE_SIZE && y < CHUNK_SAMPLE_SIZE && z < CHUNK_SAMPLE_SIZE);
+	(x * CHUNK_SAMPLE_SIZE + y) * CHUNK_SAMPLE_SIZE + z
+}
+
+/// Which of a chunk's [`ChunkCoordinates::mesh_dependencies`] the sample at `x`, `y`, `z` comes from, each axis must be
+/// less than twice [`CHUNK_SIZE`].
+///
+/// [`ChunkCoordinates::mesh_dependencies`]: crate::data::world::ChunkCoordinates::mesh_dependencies
+pub const fn dependency_index(x: usize, y: usize, z: usize) -> usize {
+	debug_assert!(x < CHUNK_SIZE * 2 && y < CHUNK_SIZE * 2 && z < CHUNK_SIZE * 2);
+	(x / CHUNK_SIZE) << 2 | (y / CHUNK_SIZE) << 1 | (z / CHUNK_SIZE)
+}
+
+/// Furthest a player can be from the center of a block or terrain cell they aim at, in meters. The client only targets
+/// things within reach, and the sector checks [`InteractBlock`] against it.
+///
+/// [`InteractBlock`]: crate::message::serverbound::InteractBlock
+pub const REACH: f32 = 8.0;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::collections::HashSet;
+
+	/// Every `x`, `y`, `z` below `size` on each axis.
+	fn cube(size: usize) -> impl Iterator<Item = (usize, usize, usize)> {
+		(0..size).flat_map(move |x| (0..size).flat_map(move |y| (0..size).map(move |z| (x, y, z))))
+	}
+
+	#[test]
+	fn sizes_are_what_they_were() {
+		assert_eq!(CHUNK_SIZE, 16);
+		assert_eq!(CHUNK_SAMPLE_SIZE, 17);
+		assert_eq!(CELLS_PER_CHUNK, 4096);
+		assert_eq!(SAMPLES_PER_CHUNK, 17 * 17 * 17);
+	}
+
+	#[test]
+	fn cell_index_is_the_old_shifts() {
+		for (x, y, z) in cube(CHUNK_SIZE) {
+			assert_eq!(cell_index(x, y, z), x << 8 | y << 4 | z, "{x} {y} {z}");
+		}
+	}
+
+	#[test]
+	fn sample_index_is_the_old_multiplication() {
+		for (x, y, z) in cube(CHUNK_SAMPLE_SIZE) {
+			assert_eq!(
+				sample_index(x, y, z),
+				(x * 289) + (y * 17) + z,
+				"{x} {y} {z}"
+			);
+		}
+	}
+
+	#[test]
+	fn dependency_index_is_the_old_masks() {
+		for (x, y, z) in cube(CHUNK_SIZE * 2) {
+			assert_eq!(
+				dependency_index(x, y, z),
+				((x & 0x10) >> 2) | ((y & 0x10) >> 3) | ((z & 0x10) >> 4),
+				"{x} {y} {z}"
+			);
+
+			// Which cell of that dependency, as the masks used to pick it
+			assert_eq!(
+				cell_index(x % CHUNK_SIZE, y % CHUNK_SIZE, z % CHUNK_SIZE),
+				(x & 0x0F) << 8 | (y & 0x0F) << 4 | z & 0x0F,
+				"{x} {y} {z}"
+			);
+		}
+	}
+
+	#[test]
+	fn indices_cover_every_slot_once() {
+		let cells = cube(CHUNK_SIZE)
+			.map(|(x, y, z)| cell_index(x, y, z))
+			.collect::<HashSet<_>>();
+		assert_eq!(cells, (0..CELLS_PER_CHUNK).collect());
+
+		let samples = cube(CHUNK_SAMPLE_SIZE)
+			.map(|(x, y, z)| sample_index(x, y, z))
+			.collect::<HashSet<_>>();
+		assert_eq!(samples, (0..SAMPLES_PER_CHUNK).collect());
+
+		let dependencies = cube(CHUNK_SIZE * 2)
+			.map(|(x, y, z)| dependency_index(x, y, z))
+			.collect::<HashSet<_>>();
+		assert_eq!(dependencies, (0..8).collect());
+	}
+
+	#[test]
+	#[cfg(debug_assertions)]
+	#[should_panic]
+	fn cells_past_the_chunk_are_caught() {
+		cell_index(0, CHUNK_SIZE, 0);
+	}
+}
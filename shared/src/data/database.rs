@@ -0,0 +1,45 @@
+use super::Id;
+use sqlx::{
+	encode::IsNull,
+	error::BoxDynError,
+	postgres::{PgHasArrayType, PgTypeInfo},
+	Database, Decode, Encode, Type, TypeInfo,
+};
+
+impl<D: Database> Type<D> for Id
+where
+	i64: Type<D>,
+{
+	fn type_info() -> D::TypeInfo {
+		<i64 as Type<D>>::type_info()
+	}
+
+	fn compatible(ty: &<D>::TypeInfo) -> bool {
+		ty.type_compatible(&<i64 as Type<D>>::type_info())
+	}
+}
+
+/// Allows binding slices of ids, such as with `UNNEST`.
+impl PgHasArrayType for Id {
+	fn array_type_info() -> PgTypeInfo {
+		<i64 as PgHasArrayType>::array_type_info()
+	}
+}
+
+impl<'r, D: Database> Decode<'r, D> for Id
+where
+	i64: Decode<'r, D>,
+{
+	fn decode(value: <D>::ValueRef<'r>) -> Result<Self, BoxDynError> {
+		<i64 as Decode<D>>::decode(value).map(|value| Self(value as u64))
+	}
+}
+
+impl<'r, D: Database> Encode<'r, D> for Id
+where
+	i64: Encode<'r, D>,
+{
+	fn encode_by_ref(&self, buffer: &mut <D>::ArgumentBuffer<'r>) -> Result<IsNull, BoxDynError> {
+		<i64 as Encode<D>>::encode_by_ref(&(self.0 as i64), buffer)
+	}
+}
@@ -0,0 +1,102 @@
+use super::Id;
+use std::{
+	cell::RefCell,
+	sync::{
+		atomic::{AtomicU8, Ordering::Relaxed},
+		Mutex,
+	},
+};
+use time::{macros::datetime, OffsetDateTime};
+
+const SOLARSCAPE_EPOCH: OffsetDateTime = datetime!(2024-01-01 00:00 UTC);
+
+static THREAD_ID_COUNTER: AtomicU8 = AtomicU8::new(0);
+
+/// Thread ids given back by threads that have exited, along with where their counter was up to, so that threads which
+/// come and go don't run out of the 32 there are.
+static FREE_THREAD_IDS: Mutex<Vec<(u8, u16)>> = Mutex::new(vec![]);
+
+/// A thread's part of every [`Id`] it generates, given back to [`FREE_THREAD_IDS`] once the thread exits.
+struct Generator {
+	thread_id: u8,
+	counter: u16,
+}
+
+impl Generator {
+	fn new() -> Self {
+		// Carrying on from the previous thread's counter keeps ids unique, as if the same thread were generating them
+		if let Some((thread_id, counter)) = FREE_THREAD_IDS.lock().unwrap().pop() {
+			return Self { thread_id, counter };
+		}
+
+		let thread_id = THREAD_ID_COUNTER.fetch_add(1, Relaxed);
+		assert!(thread_id < u8::pow(2, 5));
+
+		Self {
+			thread_id,
+			counter: 0,
+		}
+	}
+}
+
+impl Drop for Generator {
+	fn drop(&mut self) {
+		if let Ok(mut free) = FREE_THREAD_IDS.lock() {
+			free.push((self.thread_id, self.counter));
+		}
+	}
+}
+
+thread_local! {
+	static GENERATOR: RefCell<Generator> = RefCell::new(Generator::new());
+}
+
+impl Id {
+	pub fn new() -> Self {
+		let timestamp =
+			((OffsetDateTime::now_utc() - SOLARSCAPE_EPOCH).whole_seconds() as u64) << 22;
+
+		GENERATOR.with_borrow_mut(|generator| {
+			let thread_id = (generator.thread_id as u64) << 12;
+			let counter = generator.counter as u64;
+
+			generator.counter += 1;
+			if generator.counter == u16::pow(2, 12) {
+				generator.counter = 0
+			}
+
+			Id(timestamp | thread_id | counter)
+		})
+	}
+}
+
+/// Generates a new id, the same as [`Id::new`].
+impl Default for Id {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{collections::HashSet, thread};
+
+	#[test]
+	fn exited_threads_give_their_id_back() {
+		let mut ids = HashSet::new();
+
+		// Far more threads than there are thread ids, but only a few at once
+		for _ in 0..64 {
+			let generated = thread::spawn(|| (0..8).map(|_| Id::new()).collect::<Vec<_>>())
+				.join()
+				.unwrap();
+
+			for id in generated {
+				assert!(ids.insert(id), "{id:?} was generated twice");
+			}
+		}
+
+		assert_eq!(ids.len(), 64 * 8);
+	}
+}
@@ -1,5 +1,9 @@
-use crate::data::Id;
-use nalgebra::{vector, Point3, UnitQuaternion, Vector3};
+use crate::{
+	consts::{cell_index, CELLS_PER_CHUNK, CHUNK_SIZE},
+	data::Id,
+	hash::fnv1a,
+};
+use nalgebra::{vector, Isometry3, Point3, Translation3, UnitQuaternion, Vector3};
 use serde::{de::Error, Deserialize, Deserializer, Serialize};
 use std::{
 	fmt::{self, Display, Formatter},
@@ -10,6 +14,11 @@ use thiserror::Error;
 
 pub const LEVELS: u8 = 28;
 
+/// Level 0 chunks either side of a voxject's origin that any chunk can be within, as far as every cell of the chunk
+/// still has coordinates an [`i32`] can hold, see [`CellCoordinates::voxject_cell`]. Used as
+/// [`ChunkCoordinates::is_sane`]'s bound when a sector's world has no border.
+pub const UNBOUNDED_CHUNK_RADIUS: i64 = (i32::MAX / CHUNK_SIZE as i32) as i64;
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, Serialize)]
 #[repr(transparent)]
 #[serde(transparent)]
@@ -62,33 +71,151 @@ impl ChunkCoordinates {
 		}
 	}
 
+	/// Offsets of a 2x2x2 block of chunks from it's lowest corner, ordered x then y then z.
+	#[rustfmt::skip]
+	const BLOCK_OFFSETS: [Vector3<i32>; 8] = [
+		vector![0, 0, 0],
+		vector![0, 0, 1],
+		vector![0, 1, 0],
+		vector![0, 1, 1],
+		vector![1, 0, 0],
+		vector![1, 0, 1],
+		vector![1, 1, 0],
+		vector![1, 1, 1],
+	];
+
+	/// Returns [`None`] if any of the coordinates would overflow.
+	pub fn checked_add(&self, offset: Vector3<i32>) -> Option<Self> {
+		Some(Self::new(
+			self.voxject,
+			vector![
+				self.x.checked_add(offset.x)?,
+				self.y.checked_add(offset.y)?,
+				self.z.checked_add(offset.z)?
+			],
+			self.level,
+		))
+	}
+
 	/// # Panics
 	/// If [`level`] is 27 as upleveled grid coordinates would be on level 28, which is out of bounds.
 	pub fn upleveled(&self) -> Self {
-		assert!(*self.level < LEVELS - 1);
-		Self::new(
-			self.voxject,
-			self.coordinates.map(|coordinate| coordinate >> 1),
-			Level::new(*self.level + 1),
-		)
+		self.try_upleveled()
+			.expect("level 27 chunks can not be upleveled")
+	}
+
+	/// Returns [`None`] if [`level`] is 27 as upleveled grid coordinates would be on level 28, which is out of bounds.
+	pub fn try_upleveled(&self) -> Option<Self> {
+		match *self.level < LEVELS - 1 {
+			true => Some(Self::new(
+				self.voxject,
+				self.coordinates.map(|coordinate| coordinate >> 1),
+				Level::new(*self.level + 1),
+			)),
+			false => None,
+		}
 	}
 
 	/// # Panics
-	/// If [`level`] is 0 as downleveled grid coordinates would be on level -1, which is out of bounds.
+	/// If [`level`] is 0 as downleveled grid coordinates would be on level -1, which is out of bounds, or if the
+	/// downleveled coordinates would overflow.
 	pub fn downleveled(&self) -> Self {
-		assert_ne!(*self.level, 0);
-		Self::new(
+		self.try_downleveled()
+			.expect("level 0 chunks can not be downleveled")
+	}
+
+	/// Returns [`None`] if [`level`] is 0 as downleveled grid coordinates would be on level -1, which is out of bounds,
+	/// or if the downleveled coordinates would overflow.
+	pub fn try_downleveled(&self) -> Option<Self> {
+		if *self.level == 0 {
+			return None;
+		}
+
+		Some(Self::new(
 			self.voxject,
-			self.coordinates.map(|coordinate| coordinate << 1),
+			vector![
+				self.x.checked_mul(2)?,
+				self.y.checked_mul(2)?,
+				self.z.checked_mul(2)?
+			],
 			Level::new(*self.level - 1),
-		)
+		))
+	}
+
+	/// The chunk on the level above which contains this chunk, same as [`ChunkCoordinates::try_upleveled`].
+	pub fn parent(&self) -> Option<Self> {
+		self.try_upleveled()
+	}
+
+	/// The 8 chunks on the level below which make up this chunk, ordered x then y then z.
+	pub fn children(&self) -> Option<[Self; 8]> {
+		self.try_downleveled()?.mesh_dependencies()
+	}
+
+	/// This chunk and the 7 chunks on it's positive side, which are needed to build it's mesh or collision. Ordered x
+	/// then y then z, so the first is always this chunk.
+	pub fn mesh_dependencies(&self) -> Option<[Self; 8]> {
+		let dependencies = Self::BLOCK_OFFSETS.map(|offset| self.checked_add(offset));
+
+		match dependencies.iter().all(Option::is_some) {
+			true => Some(dependencies.map(Option::unwrap)),
+			false => None,
+		}
 	}
 
-	/// Returns the Chunk's translation relative to the Voxject.
-	pub fn voxject_relative_translation(&self) -> Vector3<f32> {
+	/// Whether these could be the coordinates of a chunk in a world which is `world_radius_chunks` level 0 chunks across
+	/// either side of each voxject's origin, the bound shrinking with each level as chunks grow, and which has no chunks
+	/// above `max_level`. Coordinates come from the other end of a connection, which could send anything, and should be
+	/// checked with this before they're used.
+	///
+	/// Level 27 chunks are never sane, nothing is ever synced at that level as there's no level above to uplevel to.
+	pub fn is_sane(&self, world_radius_chunks: i64, max_level: Level) -> bool {
+		if *self.level > *max_level || *self.level >= LEVELS - 1 {
+			return false;
+		}
+
+		// Rounded up, so that a chunk partly within the world is still in it
+		let size = 1i64 << *self.level;
+		let bound = (world_radius_chunks.clamp(0, UNBOUNDED_CHUNK_RADIUS) + size - 1) / size;
+
 		self.coordinates
-			.map(|coordinate| coordinate << *self.level)
-			.cast() * 16.0
+			.iter()
+			.all(|coordinate| (-bound..bound).contains(&i64::from(*coordinate)))
+	}
+
+	/// Whether `finer` is this chunk, or is within this chunk on a lower level.
+	pub fn contains(&self, finer: &Self) -> bool {
+		self.voxject == finer.voxject
+			&& *finer.level <= *self.level
+			&& finer
+				.coordinates
+				.map(|coordinate| coordinate >> (*self.level - *finer.level))
+				== self.coordinates
+	}
+
+	/// Width of the chunk, [`CHUNK_SIZE`] on level 0 and doubling with each level.
+	pub fn size(&self) -> f32 {
+		((CHUNK_SIZE as u64) << *self.level) as f32
+	}
+
+	/// The chunk's lowest corner.
+	pub fn origin(&self) -> VoxjectPosition {
+		VoxjectPosition::new(
+			self.voxject,
+			Point3::from(self.coordinates.cast() * self.size()),
+		)
+	}
+
+	pub fn center(&self) -> VoxjectPosition {
+		let mut origin = self.origin();
+		origin.position += Vector3::repeat(self.size() / 2.0);
+		origin
+	}
+
+	/// Where the chunk's [`ChunkCoordinates::origin`] is in the world, with the voxject's rotation, given the voxject's
+	/// location. Chunk meshes and collision are built relative to this.
+	pub fn world_location(&self, voxject_location: &Isometry3<f32>) -> Isometry3<f32> {
+		voxject_location * Translation3::from(self.origin().position.coords)
 	}
 
 	/// Returns a list of the Chunk's surrounding chunks. These are both the Chunk's dependents and dependencies.
@@ -188,13 +315,143 @@ impl Deref for ChunkCoordinates {
 	}
 }
 
+/// One of the [`CHUNK_SIZE`] cubed cells of a chunk, which a material and density are stored for.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct CellCoordinates {
+	pub chunk: ChunkCoordinates,
+	/// Position within the chunk, each axis is less than [`CHUNK_SIZE`].
+	pub cell: Vector3<u8>,
+}
+
+impl CellCoordinates {
+	/// The cell at `cell`, counted in cells from the voxject's origin on `level` rather than from a chunk's.
+	pub fn from_voxject_cell(voxject: Id, cell: Vector3<i32>, level: Level) -> Self {
+		Self {
+			chunk: ChunkCoordinates::new(
+				voxject,
+				cell.map(|coordinate| coordinate.div_euclid(CHUNK_SIZE as i32)),
+				level,
+			),
+			cell: cell.map(|coordinate| coordinate.rem_euclid(CHUNK_SIZE as i32) as u8),
+		}
+	}
+
+	/// Inverse of [`CellCoordinates::from_voxject_cell`]. Only chunks which are [`ChunkCoordinates::is_sane`] are
+	/// guaranteed not to overflow.
+	pub fn voxject_cell(&self) -> Vector3<i32> {
+		self.chunk.coordinates * CHUNK_SIZE as i32 + self.cell.cast()
+	}
+
+	/// Index of this cell in the chunk's materials and densities.
+	pub fn index(&self) -> usize {
+		let cell = self.cell.map(usize::from);
+		cell_index(cell.x, cell.y, cell.z)
+	}
+
+	/// The 8 cells at the corners of the volume between this cell and the next on each axis, which is what a mesh is
+	/// built from. Ordered x then y then z, so the first is always this cell. Any but the first may be in a different
+	/// chunk.
+	pub fn corners(&self) -> [Self; 8] {
+		let cell = self.voxject_cell();
+
+		ChunkCoordinates::BLOCK_OFFSETS.map(|offset| {
+			Self::from_voxject_cell(self.chunk.voxject, cell + offset, self.chunk.level)
+		})
+	}
+}
+
+impl Display for CellCoordinates {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		write!(
+			formatter,
+			"{} / {}, {}, {}",
+			self.chunk, self.cell.x, self.cell.y, self.cell.z
+		)
+	}
+}
+
+/// A position in the sector, the frame which players, structures, and physics use. See [`VoxjectPosition`] for
+/// positions in terrain.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[repr(transparent)]
+#[serde(transparent)]
+pub struct WorldPosition(pub Point3<f32>);
+
+impl WorldPosition {
+	/// This position relative to `voxject`, which is at `voxject_location`.
+	pub fn relative_to(self, voxject: Id, voxject_location: &Isometry3<f32>) -> VoxjectPosition {
+		VoxjectPosition::new(voxject, voxject_location.inverse_transform_point(&self.0))
+	}
+}
+
+impl Display for WorldPosition {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		write!(formatter, "{}, {}, {}", self.0.x, self.0.y, self.0.z)
+	}
+}
+
+/// A position relative to a voxject, the frame which it's chunks and cells are laid out in. This is only the same as a
+/// [`WorldPosition`] while the voxject is at the origin, so convert between them rather than using one as the other.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct VoxjectPosition {
+	pub voxject: Id,
+	pub position: Point3<f32>,
+}
+
+impl VoxjectPosition {
+	pub const fn new(voxject: Id, position: Point3<f32>) -> Self {
+		Self { voxject, position }
+	}
+
+	/// Inverse of [`WorldPosition::relative_to`].
+	pub fn to_world(self, voxject_location: &Isometry3<f32>) -> WorldPosition {
+		WorldPosition(voxject_location.transform_point(&self.position))
+	}
+
+	/// The chunk on `level` containing this position.
+	pub fn chunk(&self, level: Level) -> ChunkCoordinates {
+		let chunk_size = ((CHUNK_SIZE as u64) << *level) as f32;
+
+		ChunkCoordinates::new(
+			self.voxject,
+			self.position
+				.coords
+				.map(|coordinate| (coordinate / chunk_size).floor() as i32),
+			level,
+		)
+	}
+
+	/// The cell on `level` containing this position. Cells are 1 unit wide on level 0, doubling with each level.
+	pub fn cell(&self, level: Level) -> CellCoordinates {
+		let cell_size = (1u64 << *level) as f32;
+
+		CellCoordinates::from_voxject_cell(
+			self.voxject,
+			self.position
+				.coords
+				.map(|coordinate| (coordinate / cell_size).floor() as i32),
+			level,
+		)
+	}
+}
+
+impl Display for VoxjectPosition {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		write!(
+			formatter,
+			"{} / {}, {}, {}",
+			self.voxject, self.position.x, self.position.y, self.position.z
+		)
+	}
+}
+
 #[derive(Clone, Copy, Default, Deserialize, Serialize)]
 pub struct Location {
 	pub position: Point3<f32>,
 	pub rotation: UnitQuaternion<f32>,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 #[repr(u8)]
 pub enum Material {
 	Corium = 0b1100,
@@ -204,13 +461,42 @@ pub enum Material {
 	Nothing = 0b1111,
 }
 
+impl TryFrom<u8> for Material {
+	type Error = NotFound;
+
+	fn try_from(value: u8) -> Result<Self, Self::Error> {
+		Ok(match value {
+			0b1100 => Self::Corium,
+			0b1101 => Self::Stone,
+			0b1110 => Self::Ground,
+			0b1111 => Self::Nothing,
+			_ => Err(NotFound)?,
+		})
+	}
+}
+
+/// Identifies a chunk's data, so that a copy can be checked against the original without sending it, and corrupt
+/// copies can be caught. Uses [`fnv1a`], which is fine as a client claiming to have data it doesn't only gets the wrong
+/// terrain itself.
+pub fn content_hash(
+	materials: &[Material; CELLS_PER_CHUNK],
+	densities: &[f32; CELLS_PER_CHUNK],
+) -> u64 {
+	let materials = materials.iter().map(|material| *material as u8);
+	let densities = densities.iter().flat_map(|density| density.to_le_bytes());
+
+	fnv1a(materials.chain(densities))
+}
+
 #[cfg_attr(feature = "backend", derive(sqlx::Type))]
-#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum Item {
 	TestOre,
 }
 
 impl Item {
+	pub const ALL: &'static [Self] = &[Self::TestOre];
+
 	pub const fn name(&self) -> &'static str {
 		match self {
 			Self::TestOre => "test_ore",
@@ -230,15 +516,73 @@ impl Item {
 	}
 }
 
+/// Anything about a block that can change after it's placed, which clients are sent so that they can show it, like
+/// whether a [`BlockType::Switch`] is on. What it means depends on the block's type, every block is placed with 0.
+pub type BlockState = u8;
+
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
 pub enum BlockType {
 	Block,
+	/// Explodes a few seconds after being placed.
+	Explosive,
+	/// Turned on and off by using it, it's state is 1 while it's on.
+	Switch,
+	/// Holds a few items, which are shown to whoever uses it.
+	Container,
 
 	TestBlock = 0xFF,
 }
 
 impl BlockType {
-	pub const ALL: &'static [Self] = &[Self::Block, Self::TestBlock];
+	pub const ALL: &'static [Self] = &[
+		Self::Block,
+		Self::Explosive,
+		Self::Switch,
+		Self::Container,
+		Self::TestBlock,
+	];
+
+	pub const fn display_name(&self) -> &'static str {
+		match self {
+			Self::Block => "Block",
+			Self::Explosive => "Explosive",
+			Self::Switch => "Switch",
+			Self::Container => "Container",
+			Self::TestBlock => "Test Block",
+		}
+	}
+
+	/// Whether anything happens when the block is used, see [`InteractBlock`]. The sector server has an interaction
+	/// registered for each of these.
+	///
+	/// [`InteractBlock`]: crate::message::serverbound::InteractBlock
+	pub const fn is_interactable(&self) -> bool {
+		matches!(self, Self::Switch | Self::Container)
+	}
+
+	/// How much damage the block can take before it's destroyed.
+	pub const fn max_health(&self) -> f32 {
+		match self {
+			Self::Block => 100.0,
+			Self::Explosive => 20.0,
+			Self::Switch => 50.0,
+			Self::Container => 100.0,
+			Self::TestBlock => 100.0,
+		}
+	}
+
+	/// Which cell of the structure block texture atlas holds this block's texture in `state`. Cells are numbered left
+	/// to right, then top to bottom. Cell 0 is the missing texture.
+	pub const fn atlas_cell(&self, state: BlockState) -> u32 {
+		match self {
+			Self::Block => 1,
+			Self::TestBlock => 2,
+			Self::Explosive => 3,
+			Self::Switch if state == 0 => 4,
+			Self::Switch => 5,
+			Self::Container => 6,
+		}
+	}
 }
 
 impl FromStr for BlockType {
@@ -247,6 +591,9 @@ impl FromStr for BlockType {
 	fn from_str(s: &str) -> Result<Self, Self::Err> {
 		Ok(match s {
 			"Block" => Self::Block,
+			"Explosive" => Self::Explosive,
+			"Switch" => Self::Switch,
+			"Container" => Self::Container,
 			"TestBlock" => Self::TestBlock,
 			_ => Err(NotFound)?,
 		})
@@ -256,3 +603,402 @@ impl FromStr for BlockType {
 #[derive(Debug, Error)]
 #[error("not found")]
 pub struct NotFound;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const TOP: Level = Level::new(LEVELS - 2);
+
+	fn chunk(coordinates: Vector3<i32>, level: u8) -> ChunkCoordinates {
+		ChunkCoordinates::new("1".parse().unwrap(), coordinates, Level::new(level))
+	}
+
+	#[test]
+	fn sane_chunks_are_within_the_radius() {
+		for (coordinate, sane) in [(-11, false), (-10, true), (0, true), (9, true), (10, false)] {
+			for axis in 0..3 {
+				let mut coordinates = vector![0, 0, 0];
+				coordinates[axis] = coordinate;
+
+				assert_eq!(
+					chunk(coordinates, 0).is_sane(10, TOP),
+					sane,
+					"{coordinates:?}"
+				);
+			}
+		}
+
+		assert!(!chunk(vector![0, 0, 0], 0).is_sane(0, TOP));
+		assert!(!chunk(vector![0, 0, 0], 0).is_sane(-5, TOP));
+	}
+
+	#[test]
+	fn bound_shrinks_with_each_level_rounding_up() {
+		// A radius of 10 level 0 chunks is 5 level 1 chunks, then 2.5 level 2 chunks which is rounded up to 3
+		for (level, bound) in [(0, 10), (1, 5), (2, 3), (3, 2), (4, 1), (20, 1)] {
+			assert!(chunk(vector![bound - 1, -bound, 0], level).is_sane(10, TOP));
+			assert!(!chunk(vector![bound, 0, 0], level).is_sane(10, TOP));
+			assert!(!chunk(vector![0, -bound - 1, 0], level).is_sane(10, TOP));
+		}
+	}
+
+	#[test]
+	fn levels_above_the_max_level_are_not_sane() {
+		assert!(chunk(vector![0, 0, 0], 4).is_sane(10, Level::new(4)));
+		assert!(!chunk(vector![0, 0, 0], 5).is_sane(10, Level::new(4)));
+
+		// Nothing is synced on the top level, whatever the sector says
+		assert!(!chunk(vector![0, 0, 0], LEVELS - 1).is_sane(10, Level::new(LEVELS - 1)));
+	}
+
+	#[test]
+	fn unbounded_chunks_have_cells_an_i32_can_hold() {
+		let last = CHUNK_SIZE as u8 - 1;
+		let bound = UNBOUNDED_CHUNK_RADIUS as i32;
+
+		for radius in [UNBOUNDED_CHUNK_RADIUS, i64::MAX] {
+			assert!(!chunk(vector![bound, 0, 0], 0).is_sane(radius, TOP));
+			assert!(!chunk(vector![i32::MIN, i32::MAX, 0], 0).is_sane(radius, TOP));
+		}
+
+		for coordinates in [
+			vector![bound - 1, bound - 1, bound - 1],
+			vector![-bound, -bound, -bound],
+		] {
+			let chunk = chunk(coordinates, 0);
+			assert!(chunk.is_sane(UNBOUNDED_CHUNK_RADIUS, TOP));
+
+			for cell in [vector![0, 0, 0], vector![last, last, last]] {
+				// Panics on overflow in a debug build
+				CellCoordinates { chunk, cell }.voxject_cell();
+			}
+		}
+	}
+
+	#[test]
+	fn extreme_coordinates_are_never_sane_or_panic() {
+		let extremes = [i32::MIN, i32::MIN + 1, -1, 0, 1, i32::MAX - 1, i32::MAX];
+
+		for level in 0..LEVELS {
+			for x in extremes {
+				for y in extremes {
+					let chunk = chunk(vector![x, y, i32::MIN], level);
+
+					for radius in [i64::MIN, -1, 0, 1, UNBOUNDED_CHUNK_RADIUS, i64::MAX] {
+						assert!(!chunk.is_sane(radius, TOP));
+					}
+				}
+			}
+		}
+	}
+
+	const EXTREMES: [i32; 9] = [
+		i32::MIN,
+		i32::MIN + 1,
+		i32::MIN / 2 - 1,
+		i32::MIN / 2,
+		0,
+		1,
+		i32::MAX / 2,
+		i32::MAX / 2 + 1,
+		i32::MAX,
+	];
+
+	/// Every combination of [`EXTREMES`] on one axis, with the others held at `0`.
+	fn extreme_coordinates() -> impl Iterator<Item = Vector3<i32>> {
+		(0..3).flat_map(|axis| {
+			EXTREMES.into_iter().map(move |coordinate| {
+				let mut coordinates = vector![0, 0, 0];
+				coordinates[axis] = coordinate;
+				coordinates
+			})
+		})
+	}
+
+	#[test]
+	fn checked_add_overflows_at_extremes() {
+		for axis in 0..3 {
+			let mut one = vector![0, 0, 0];
+			one[axis] = 1;
+
+			let mut max = vector![0, 0, 0];
+			max[axis] = i32::MAX;
+			let mut min = vector![0, 0, 0];
+			min[axis] = i32::MIN;
+
+			assert_eq!(chunk(max, 3).checked_add(one), None);
+			assert_eq!(chunk(min, 3).checked_add(-one), None);
+			assert_eq!(chunk(max, 3).checked_add(min), Some(chunk(-one, 3)));
+			assert_eq!(chunk(max - one, 3).checked_add(one), Some(chunk(max, 3)));
+			assert_eq!(chunk(min, 3).checked_add(max), Some(chunk(-one, 3)));
+		}
+	}
+
+	#[test]
+	fn upleveling_stops_at_the_top_level() {
+		for coordinates in extreme_coordinates() {
+			for level in 0..LEVELS - 1 {
+				assert_eq!(
+					chunk(coordinates, level).try_upleveled(),
+					Some(chunk(coordinates.map(|c| c.div_euclid(2)), level + 1))
+				);
+			}
+
+			assert_eq!(chunk(coordinates, LEVELS - 1).try_upleveled(), None);
+		}
+
+		assert_eq!(
+			chunk(vector![i32::MIN, -1, i32::MAX], 26).upleveled(),
+			chunk(vector![i32::MIN / 2, -1, i32::MAX / 2], 27)
+		);
+	}
+
+	#[test]
+	#[should_panic]
+	fn upleveling_the_top_level_panics() {
+		chunk(vector![0, 0, 0], LEVELS - 1).upleveled();
+	}
+
+	#[test]
+	fn downleveling_stops_at_level_0_and_overflow() {
+		for coordinates in extreme_coordinates() {
+			assert_eq!(chunk(coordinates, 0).try_downleveled(), None);
+
+			let fits = coordinates
+				.iter()
+				.all(|coordinate| (i32::MIN / 2..=i32::MAX / 2).contains(coordinate));
+
+			for level in 1..LEVELS {
+				let downleveled = chunk(coordinates, level).try_downleveled();
+
+				match fits {
+					true => assert_eq!(downleveled, Some(chunk(coordinates * 2, level - 1))),
+					false => assert_eq!(downleveled, None, "{coordinates:?}"),
+				}
+			}
+		}
+
+		assert_eq!(
+			chunk(vector![i32::MAX / 2, i32::MIN / 2, 0], 27).downleveled(),
+			chunk(vector![i32::MAX - 1, i32::MIN, 0], 26)
+		);
+	}
+
+	#[test]
+	#[should_panic]
+	fn downleveling_level_0_panics() {
+		chunk(vector![0, 0, 0], 0).downleveled();
+	}
+
+	#[test]
+	#[should_panic]
+	fn downleveling_past_i32_panics() {
+		chunk(vector![i32::MAX / 2 + 1, 0, 0], 1).downleveled();
+	}
+
+	#[test]
+	fn downleveling_then_upleveling_round_trips() {
+		for coordinates in extreme_coordinates() {
+			for level in 1..LEVELS {
+				let chunk = chunk(coordinates, level);
+
+				if let Some(downleveled) = chunk.try_downleveled() {
+					assert_eq!(downleveled.parent(), Some(chunk));
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn mesh_dependencies_stop_at_i32_max() {
+		for coordinates in extreme_coordinates() {
+			for level in [0, 26, 27] {
+				let chunk = chunk(coordinates, level);
+				let dependencies = chunk.mesh_dependencies();
+
+				match coordinates.iter().any(|coordinate| *coordinate == i32::MAX) {
+					true => assert_eq!(dependencies, None),
+					false => {
+						let dependencies = dependencies.unwrap();
+						assert_eq!(dependencies[0], chunk);
+						assert_eq!(dependencies[7].coordinates, coordinates.add_scalar(1));
+						assert!(dependencies
+							.iter()
+							.all(|dependency| dependency.level == chunk.level));
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn children_make_up_their_parent() {
+		for coordinates in extreme_coordinates() {
+			assert_eq!(chunk(coordinates, 0).children(), None);
+
+			for level in [1, 26, 27] {
+				let parent = chunk(coordinates, level);
+				let children = parent.children();
+
+				// Anything that can be downleveled also has room for the other children on it's positive side
+				assert_eq!(children.is_some(), parent.try_downleveled().is_some());
+
+				for child in children.into_iter().flatten() {
+					assert_eq!(*child.level, level - 1);
+					assert_eq!(child.parent(), Some(parent));
+					assert!(parent.contains(&child));
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn contains_matches_repeated_upleveling() {
+		let levels = [0, 1, 25, 26, 27];
+
+		for coordinates in extreme_coordinates() {
+			for finer_level in levels {
+				let finer = chunk(coordinates, finer_level);
+
+				for level in levels {
+					let mut coarser = Some(finer);
+					for _ in finer_level..level {
+						coarser = coarser.and_then(|chunk| chunk.try_upleveled());
+					}
+
+					match level < finer_level {
+						true => {
+							assert!(!chunk(coordinates, level).contains(&finer));
+							assert!(!chunk(coordinates.map(|c| c >> 1), level).contains(&finer));
+						}
+						false => {
+							let coarser = coarser.unwrap();
+							assert!(coarser.contains(&finer), "{coarser:?} {finer:?}");
+
+							if let Some(neighbour) = coarser.checked_add(vector![1, 0, 0]) {
+								assert!(!neighbour.contains(&finer));
+							}
+						}
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn level_27_contains_across_every_level() {
+		// 27 levels up, an i32 of level 0 chunks is only 32 level 27 chunks across
+		let top = |x| chunk(vector![x, 0, 0], 27);
+		let bottom = |x| chunk(vector![x, 0, 0], 0);
+
+		assert!(top(0).contains(&bottom(0)));
+		assert!(top(0).contains(&bottom((1 << 27) - 1)));
+		assert!(!top(0).contains(&bottom(1 << 27)));
+		assert!(top(1).contains(&bottom(1 << 27)));
+		assert!(top(-1).contains(&bottom(-1)));
+		assert!(!top(0).contains(&bottom(-1)));
+		assert!(top(-16).contains(&bottom(i32::MIN)));
+		assert!(top(15).contains(&bottom(i32::MAX)));
+		assert!(!top(16).contains(&bottom(i32::MAX)));
+		assert!(!bottom(0).contains(&top(0)));
+	}
+
+	#[test]
+	fn contains_only_within_the_same_voxject() {
+		let chunk = chunk(vector![0, 0, 0], 3);
+		let other = ChunkCoordinates::new("2".parse().unwrap(), vector![0, 0, 0], Level::new(3));
+
+		assert!(chunk.contains(&chunk));
+		assert!(!chunk.contains(&other));
+	}
+
+	fn cell(cell: Vector3<i32>, level: u8) -> CellCoordinates {
+		CellCoordinates::from_voxject_cell("1".parse().unwrap(), cell, Level::new(level))
+	}
+
+	#[test]
+	fn cells_are_found_within_their_chunk() {
+		for (voxject_cell, chunk_coordinates, cell_coordinates) in [
+			(vector![0, 0, 0], vector![0, 0, 0], vector![0, 0, 0]),
+			(vector![15, 16, -1], vector![0, 1, -1], vector![15, 0, 15]),
+			(vector![-16, -17, 33], vector![-1, -2, 2], vector![0, 15, 1]),
+		] {
+			let cell = cell(voxject_cell, 4);
+
+			assert_eq!(cell.chunk, chunk(chunk_coordinates, 4));
+			assert_eq!(cell.cell, cell_coordinates);
+			assert_eq!(cell.voxject_cell(), voxject_cell);
+		}
+	}
+
+	#[test]
+	fn cell_indices_match_the_chunk_arrays() {
+		for (x, y, z) in [
+			(0, 0, 0),
+			(0, 0, 1),
+			(0, 1, 0),
+			(1, 0, 0),
+			(15, 15, 15),
+			(3, 9, 12),
+		] {
+			assert_eq!(
+				cell(vector![x, y, z], 0).index(),
+				(x << 8 | y << 4 | z) as usize
+			);
+		}
+	}
+
+	#[test]
+	fn corners_reach_into_neighbouring_chunks() {
+		let corners = cell(vector![15, 0, 15], 0).corners();
+
+		assert_eq!(corners[0], cell(vector![15, 0, 15], 0));
+		assert_eq!(corners[1].chunk, chunk(vector![0, 0, 1], 0));
+		assert_eq!(corners[1].cell, vector![15, 0, 0]);
+		assert_eq!(corners[2], cell(vector![15, 1, 15], 0));
+		assert_eq!(corners[4].chunk, chunk(vector![1, 0, 0], 0));
+		assert_eq!(corners[7].chunk, chunk(vector![1, 0, 1], 0));
+		assert_eq!(corners[7].cell, vector![0, 1, 0]);
+	}
+
+	#[test]
+	fn positions_are_in_the_cell_below_them() {
+		let position = VoxjectPosition::new("1".parse().unwrap(), Point3::new(-0.5, 17.9, 40.0));
+
+		assert_eq!(position.cell(Level::new(0)), cell(vector![-1, 17, 40], 0));
+		assert_eq!(position.cell(Level::new(2)), cell(vector![-1, 4, 10], 2));
+
+		for level in [0, 2, 5] {
+			let level = Level::new(level);
+			assert_eq!(position.cell(level).chunk, position.chunk(level));
+		}
+	}
+
+	#[test]
+	fn switches_show_their_state() {
+		assert_eq!(BlockType::Switch.atlas_cell(0), 4);
+		assert_eq!(BlockType::Switch.atlas_cell(1), 5);
+
+		// Blocks without states look the same in any of them
+		for block in [BlockType::Block, BlockType::Explosive, BlockType::Container] {
+			assert_eq!(block.atlas_cell(0), block.atlas_cell(1), "{block:?}");
+		}
+
+		// Cell 0 is the missing texture
+		for block in BlockType::ALL {
+			assert_ne!(block.atlas_cell(0), 0, "{block:?}");
+		}
+	}
+
+	#[test]
+	fn block_types_parse_from_their_names() {
+		for block in BlockType::ALL {
+			assert_eq!(format!("{block:?}").parse::<BlockType>().ok(), Some(*block));
+		}
+
+		assert!(BlockType::Switch.is_interactable());
+		assert!(BlockType::Container.is_interactable());
+		assert!(!BlockType::Block.is_interactable());
+	}
+}
@@ -0,0 +1,55 @@
+use crate::{
+	consts::{cell_index, CELLS_PER_CHUNK, CHUNK_SIZE},
+	data::world::{ChunkCoordinates, Material},
+};
+use nalgebra::{vector, zero, Vector3};
+
+/// Generates a chunk of the standard sphere voxject, a ground crust over stone, with a corium core.
+pub fn sphere(
+	coordinates: &ChunkCoordinates,
+	materials: &mut [Material; CELLS_PER_CHUNK],
+	densities: &mut [f32; CELLS_PER_CHUNK],
+) {
+	sphere_chunk(
+		coordinates,
+		32.0,
+		|distance| {
+			if distance >= 32.0 {
+				Material::Nothing
+			} else if distance >= 30.0 {
+				Material::Ground
+			} else if distance >= 16.0 {
+				Material::Stone
+			} else {
+				Material::Corium
+			}
+		},
+		materials,
+		densities,
+	)
+}
+
+fn sphere_chunk(
+	coordinates: &ChunkCoordinates,
+	radius: f32,
+	material_map: impl Fn(f32) -> Material,
+	materials: &mut [Material; CELLS_PER_CHUNK],
+	densities: &mut [f32; CELLS_PER_CHUNK],
+) {
+	let level_radius = radius / f32::powi(2.0, *coordinates.level as i32);
+	let chunk_origin_level_coordinates =
+		coordinates.cast() * f32::powi(16.0, *coordinates.level as i32 + 1);
+
+	for x in 0..CHUNK_SIZE {
+		for y in 0..CHUNK_SIZE {
+			for z in 0..CHUNK_SIZE {
+				let index = cell_index(x, y, z);
+				let level_coordinates =
+					chunk_origin_level_coordinates + vector![x as f32, y as f32, z as f32];
+				let distance = level_coordinates.metric_distance(&zero::<Vector3<_>>()) - 32.0;
+				densities[index] = level_radius - distance;
+				materials[index] = material_map(distance);
+			}
+		}
+	}
+}
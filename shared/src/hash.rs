@@ -0,0 +1,22 @@
+const OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const PRIME: u64 = 0x100000001b3;
+
+/// 64 bit FNV-1a of `bytes`. Unlike [`std::hash::DefaultHasher`] this is the same across runs, builds, and platforms, so
+/// it's fine to store or send. It isn't collision resistant, so nothing adversarial should rely on it.
+pub fn fnv1a(bytes: impl IntoIterator<Item = u8>) -> u64 {
+	bytes.into_iter().fold(OFFSET_BASIS, |hash, byte| {
+		(hash ^ u64::from(byte)).wrapping_mul(PRIME)
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn matches_reference_values() {
+		assert_eq!(fnv1a([]), 0xcbf29ce484222325);
+		assert_eq!(fnv1a(*b"a"), 0xaf63dc4c8601ec8c);
+		assert_eq!(fnv1a(*b"foobar"), 0x85944171f73967e8);
+	}
+}
@@ -1,8 +1,22 @@
+pub mod clock;
+
+pub mod command;
+
+pub mod consts;
+
 #[cfg(feature = "world")]
 pub mod connection;
 
 pub mod data;
 
+#[cfg(feature = "world")]
+pub mod generation;
+
+pub mod hash;
+
+#[cfg(feature = "backend")]
+pub mod logging;
+
 #[cfg(feature = "world")]
 pub mod physics;
 
@@ -10,15 +24,32 @@ pub mod physics;
 pub mod structure;
 
 pub mod message {
+	/// Bumped whenever a message changes in a way that older builds would misread, messages are encoded with bincode
+	/// so this includes adding, removing, or reordering variants and fields. The tests check that it was, against the
+	/// fingerprint recorded in `message/protocol_fingerprint`. Clients send it in their handshake, and sectors refuse
+	/// clients on any other version.
+	pub const PROTOCOL_VERSION: u32 = 16;
+
 	#[cfg(feature = "backend")]
 	pub mod backend;
 
 	#[cfg(feature = "world")]
 	pub mod clientbound;
 
+	#[cfg(all(test, feature = "world"))]
+	mod conformance;
+
+	#[cfg(all(test, feature = "world"))]
+	pub(crate) mod fingerprint;
+
 	#[cfg(feature = "world")]
 	pub mod serverbound;
 }
 
+pub mod throttle;
+
 #[cfg(feature = "world")]
 pub mod triangulation_table;
+
+#[cfg(feature = "world")]
+pub mod trimesh;
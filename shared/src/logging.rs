@@ -0,0 +1,81 @@
+use clap::ValueEnum;
+use env_logger::{fmt::Formatter, Env};
+use log::{
+	kv::{self, Key, VisitSource},
+	Record,
+};
+use serde_json::{Map, Value};
+use std::io::{self, Write};
+
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum LogFormat {
+	/// Human readable, the same as env_logger's default format
+	#[default]
+	Text,
+
+	/// One JSON object per line, intended for log aggregation
+	Json,
+}
+
+/// Installs the logger for backend applications, `RUST_LOG` filtering works the same regardless of `format`.
+pub fn init(format: LogFormat, default_filter: &str) {
+	let mut builder =
+		env_logger::Builder::from_env(Env::default().default_filter_or(default_filter));
+
+	match format {
+		// Fields are only included in JSON logs, text logs stay exactly as they were before fields were added
+		LogFormat::Text => builder.format_key_values(env_logger::fmt::hidden_kv_format),
+		LogFormat::Json => builder.format(write_json),
+	};
+
+	builder.init();
+}
+
+fn write_json(formatter: &mut Formatter, record: &Record) -> io::Result<()> {
+	let mut object = Map::new();
+
+	object.insert(
+		"timestamp".into(),
+		formatter.timestamp_millis().to_string().into(),
+	);
+	object.insert("level".into(), record.level().as_str().into());
+	object.insert("target".into(), record.target().into());
+	object.insert("message".into(), record.args().to_string().into());
+
+	// Visiting can only fail if we return an error, which we never do
+	let _ = record.key_values().visit(&mut Fields(&mut object));
+
+	serde_json::to_writer(&mut *formatter, &object)?;
+	writeln!(formatter)
+}
+
+/// Merges log fields into a JSON object. Fields with the same name as one of the standard keys are prefixed with
+/// `field.` rather than replacing it.
+struct Fields<'a>(&'a mut Map<String, Value>);
+
+impl<'kvs> VisitSource<'kvs> for Fields<'_> {
+	fn visit_pair(&mut self, key: Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+		let value = if let Some(value) = value.to_bool() {
+			value.into()
+		} else if let Some(value) = value.to_u64() {
+			value.into()
+		} else if let Some(value) = value.to_i64() {
+			value.into()
+		} else if let Some(value) = value.to_f64() {
+			// NaN and infinity aren't valid JSON, so fall back to a string for those
+			serde_json::Number::from_f64(value)
+				.map_or_else(|| value.to_string().into(), Value::Number)
+		} else {
+			value.to_string().into()
+		};
+
+		let key = match self.0.contains_key(key.as_str()) {
+			true => format!("field.{key}"),
+			false => key.to_string(),
+		};
+
+		self.0.insert(key, value);
+
+		Ok(())
+	}
+}
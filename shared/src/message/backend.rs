@@ -1,8 +1,86 @@
 use crate::data::Id;
 use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, time::Duration};
 
 #[derive(Deserialize, Serialize)]
 pub struct AllowConnection {
 	pub id: Id,
+
+	/// Identifies the key in logs, so that a connection can be traced without revealing the key.
+	pub key_id: Id,
 	pub key: [u8; 32],
 }
+
+/// Counters a gateway writes to the `gateway_stats` table, so that sector servers can show whether login problems are
+/// on the gateway's side. Counts only cover the period since the gateway's previous report. Missing fields default to
+/// zero, so that gateways and sector servers from different versions can still read each other's reports.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct GatewayStats {
+	/// How long the counts cover, in seconds.
+	pub period: u64,
+
+	pub token_requests: u64,
+	pub token_successes: u64,
+	pub token_failures: TokenFailures,
+
+	/// Connect requests, by the sector the player was sent to.
+	pub connect_requests: BTreeMap<Box<str>, u64>,
+}
+
+impl GatewayStats {
+	/// How often gateways report.
+	pub const INTERVAL: Duration = Duration::from_secs(30);
+
+	/// How long since a gateway's last report before it's considered to have stopped reporting.
+	pub const STALE_AFTER: Duration = Duration::from_secs(120);
+}
+
+/// Why token requests were refused.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct TokenFailures {
+	pub account_does_not_exist: u64,
+	pub incorrect_password: u64,
+	pub internal: u64,
+}
+
+/// What a sector server writes to the `sector_status` table, so that gateways can turn players away from a full sector
+/// rather than sending them to be rejected.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SectorStatus {
+	pub players: u32,
+	/// Most players the sector allows at once, `0` if there's no limit.
+	pub capacity: u32,
+}
+
+impl SectorStatus {
+	/// How often sector servers report.
+	pub const INTERVAL: Duration = Duration::from_secs(10);
+
+	/// How long since a sector's last report before gateways stop trusting it, and let players through regardless.
+	pub const STALE_AFTER: Duration = Duration::from_secs(60);
+
+	/// How many more players the sector has room for, [`None`] if there's no limit.
+	pub fn room(&self) -> Option<u32> {
+		match self.capacity {
+			0 => None,
+			capacity => Some(capacity.saturating_sub(self.players)),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn room_is_what_is_left_of_the_capacity() {
+		let status = |players, capacity| SectorStatus { players, capacity };
+
+		assert_eq!(status(3, 10).room(), Some(7));
+		assert_eq!(status(10, 10).room(), Some(0));
+		assert_eq!(status(12, 10).room(), Some(0));
+		assert_eq!(status(1000, 0).room(), None);
+	}
+}
@@ -1,30 +1,64 @@
-use crate::data::{
-	world::{BlockType, ChunkCoordinates, Item, Location, Material},
-	Id,
+use crate::{
+	consts::CELLS_PER_CHUNK,
+	data::{
+		world::{self, BlockState, BlockType, ChunkCoordinates, Item, Level, Location, Material},
+		Id,
+	},
 };
-use nalgebra::Vector3;
+use nalgebra::{Point3, Vector3};
 use rustc_hash::FxBuildHasher;
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
-use std::collections::HashMap;
+use std::{
+	collections::HashMap,
+	fmt::{self, Display, Formatter},
+	sync::Arc,
+};
 
 #[derive(Clone, Deserialize, Serialize)]
 pub enum Clientbound {
 	Sync(Sync),
+	JoinComplete(JoinComplete),
 	SyncInventory(SyncInventory),
 	SyncChunk(SyncChunk),
 	RemoveChunk(RemoveChunk),
+	ChunkUnchanged(ChunkUnchanged),
 	SyncStructure(SyncStructure),
+	RemoveStructure(RemoveStructure),
+	SetBlockState(SetBlockState),
+	SyncContainer(SyncContainer),
+	UiEvent(UiEvent),
+	Ambient(Ambient),
+	ChatMessage(ChatMessage),
+	ChatHistory(ChatHistory),
+	ChatRetract(ChatRetract),
+	Teleport(Teleport),
+	Disconnect(Disconnect),
+	Pong(Pong),
+	Transfer(Transfer),
 }
 
+/// The first message sent after connecting, with only what the sector is. Everything in it follows on it's own, the
+/// player's inventory as a [`SyncInventory`], nearby structures as they come into their interest radius, and chunks
+/// once their location is known, finished with [`JoinComplete`].
 #[derive(Clone, Deserialize, Serialize)]
 pub struct Sync {
 	pub name: Box<str>,
+	/// Ticks per second the sector runs at.
+	pub tick_rate: u32,
+	/// Distance from the sector's origin that players are shown a border at, [`None`] if there's no border.
+	pub world_radius: Option<f32>,
+	/// Level 0 chunks either side of each voxject's origin that the sector can send chunks within, see
+	/// [`ChunkCoordinates::is_sane`].
+	pub world_radius_chunks: i64,
+	/// Highest level the sector sends chunks at, see [`ChunkCoordinates::is_sane`].
+	pub max_level: Level,
 
 	pub voxjects: Vec<Voxject>,
-	pub structures: Vec<SyncStructure>,
 
-	pub inventory: Vec<InventorySlot>,
+	/// Keyframes of the sector's ambient cycle, the current point in the cycle is sent separately with [`Ambient`].
+	pub ambient_keyframes: Vec<AmbientKeyframe>,
+	pub ambient: Ambient,
 }
 
 #[derive(Clone, Deserialize, Serialize)]
@@ -46,8 +80,63 @@ impl From<Sync> for Clientbound {
 	}
 }
 
+/// Sent once a joining player has been sent the level 0 chunks and structures around them, after their first location.
+/// The client shows that it's still loading until then.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct JoinComplete;
+
+impl From<JoinComplete> for Clientbound {
+	fn from(value: JoinComplete) -> Self {
+		Self::JoinComplete(value)
+	}
+}
+
 #[derive(Clone, Deserialize, Serialize)]
-pub struct SyncInventory(pub Vec<InventorySlot>);
+pub struct SyncInventory {
+	pub slots: Vec<InventorySlot>,
+	pub capacity: InventoryCapacity,
+}
+
+impl SyncInventory {
+	/// Total quantity of every item in the inventory.
+	pub fn used(&self) -> i64 {
+		self.slots.iter().map(|slot| slot.quantity).sum()
+	}
+
+	/// How many of `quantity` `item` fit into the inventory. A new item needs a free stack, and everything counts
+	/// towards the total quantity.
+	pub fn addable(&self, item: Item, quantity: i64) -> i64 {
+		let has_stack = self.slots.iter().any(|slot| slot.item == item);
+
+		if !has_stack && self.slots.len() >= self.capacity.max_stacks {
+			return 0;
+		}
+
+		quantity
+			.min(self.capacity.max_quantity - self.used())
+			.max(0)
+	}
+}
+
+/// Limits on how much an inventory can hold, enforced by the server.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct InventoryCapacity {
+	/// Maximum number of different items.
+	pub max_stacks: usize,
+
+	/// Maximum total quantity across all items.
+	pub max_quantity: i64,
+}
+
+impl Default for InventoryCapacity {
+	fn default() -> Self {
+		Self {
+			max_stacks: 40,
+			max_quantity: 1000,
+		}
+	}
+}
 
 impl From<SyncInventory> for Clientbound {
 	fn from(value: SyncInventory) -> Self {
@@ -60,11 +149,23 @@ impl From<SyncInventory> for Clientbound {
 pub struct SyncChunk {
 	pub coordinates: ChunkCoordinates,
 
-	#[serde_as(as = "Box<[_; 4096]>")]
-	pub materials: Box<[Material; 4096]>,
+	/// Incremented every time the chunk is modified. Messages can arrive out of order, so a sync older than the one
+	/// the client already has should be ignored.
+	pub generation: u64,
 
-	#[serde_as(as = "Box<[_; 4096]>")]
-	pub densities: Box<[f32; 4096]>,
+	// Shared so that sending the same sync to many players doesn't copy the arrays for each of them
+	#[serde_as(as = "Arc<[_; CELLS_PER_CHUNK]>")]
+	pub materials: Arc<[Material; CELLS_PER_CHUNK]>,
+
+	#[serde_as(as = "Arc<[_; CELLS_PER_CHUNK]>")]
+	pub densities: Arc<[f32; CELLS_PER_CHUNK]>,
+}
+
+impl SyncChunk {
+	/// See [`world::content_hash`].
+	pub fn content_hash(&self) -> u64 {
+		world::content_hash(&self.materials, &self.densities)
+	}
 }
 
 impl From<SyncChunk> for Clientbound {
@@ -82,14 +183,40 @@ impl From<RemoveChunk> for Clientbound {
 	}
 }
 
+/// Sent instead of a [`SyncChunk`] when the client already has the chunk cached with the same content hash, see
+/// [`HaveChunks`]. The client loads it from it's cache instead, or asks for it with [`RequestChunk`] if it can't.
+///
+/// [`HaveChunks`]: crate::message::serverbound::HaveChunks
+/// [`RequestChunk`]: crate::message::serverbound::RequestChunk
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct ChunkUnchanged {
+	pub coordinates: ChunkCoordinates,
+	/// See [`SyncChunk::generation`].
+	pub generation: u64,
+	pub content_hash: u64,
+}
+
+impl From<ChunkUnchanged> for Clientbound {
+	fn from(value: ChunkUnchanged) -> Self {
+		Self::ChunkUnchanged(value)
+	}
+}
+
 /// Initial sync of a [Structure](crate::structure::Structure) when the Player logs in, the Structure is created, or
 /// the Structure comes into view. This is not used for subsequent updates to the Structure.
 #[derive(Clone, Deserialize, Serialize)]
 pub struct SyncStructure {
 	pub id: Id,
+	pub owner: Id,
 	pub location: Location,
+	pub linear_velocity: Vector3<f32>,
+	pub angular_velocity: Vector3<f32>,
+	/// Structures resting on each other would jolt if they were woken when synced.
+	pub sleeping: bool,
 
 	pub blocks: HashMap<Vector3<i16>, BlockType, FxBuildHasher>,
+	/// State of each block that isn't in state 0.
+	pub block_states: HashMap<Vector3<i16>, BlockState, FxBuildHasher>,
 }
 
 impl From<SyncStructure> for Clientbound {
@@ -97,3 +224,379 @@ impl From<SyncStructure> for Clientbound {
 		Self::SyncStructure(value)
 	}
 }
+
+/// A block's state changed, sent to every player that has it's structure.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct SetBlockState {
+	pub structure: Id,
+	pub position: Vector3<i16>,
+	pub state: BlockState,
+}
+
+impl From<SetBlockState> for Clientbound {
+	fn from(value: SetBlockState) -> Self {
+		Self::SetBlockState(value)
+	}
+}
+
+/// What a [`BlockType::Container`] holds, sent only to the player that used it, who should be shown it.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SyncContainer {
+	pub structure: Id,
+	pub position: Vector3<i16>,
+	pub inventory: SyncInventory,
+}
+
+impl From<SyncContainer> for Clientbound {
+	fn from(value: SyncContainer) -> Self {
+		Self::SyncContainer(value)
+	}
+}
+
+/// The [Structure](crate::structure::Structure) was deleted and should no longer exist.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct RemoveStructure(pub Id);
+
+impl From<RemoveStructure> for Clientbound {
+	fn from(value: RemoveStructure) -> Self {
+		Self::RemoveStructure(value)
+	}
+}
+
+/// Lets the server drive parts of the client's UI.
+#[derive(Clone, Deserialize, Serialize)]
+pub enum UiEvent {
+	/// Shown briefly as a toast, then disappears on it's own.
+	SystemMessage {
+		text: LocalizedText,
+		severity: Severity,
+	},
+	OpenInventory,
+	CloseInventory,
+	/// Shown as a window which must be dismissed by the player.
+	Notice {
+		title: Box<str>,
+		body: Box<str>,
+	},
+	/// Something the player tried to do was refused, shown as a toast.
+	ActionDenied(ActionDenied),
+}
+
+/// Text the client words itself, so that it can be translated. `key` is looked up in the client's language files and
+/// each `{name}` in the string is replaced by the matching param. A client that doesn't know the key, because the
+/// server is newer, shows the key and params instead.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct LocalizedText {
+	pub key: Box<str>,
+	pub params: Vec<(Box<str>, Param)>,
+}
+
+impl LocalizedText {
+	/// Every key sent by the sector server or singleplayer. [`LocalizedText::new`] only accepts these, and the client
+	/// checks it's English language file has all of them.
+	pub const KEYS: &'static [&'static str] = &[
+		"server.structure_gone",
+		"server.not_structure_owner",
+		"server.ambient_admin_only",
+		"server.snapshot_written",
+		"server.snapshot_failed",
+		"server.inventory_full",
+		"server.chat_too_long",
+		"server.command_reply",
+		"server.command_failed",
+		"server.outside_world_border",
+		"server.afk",
+		"singleplayer.terrain_unavailable",
+		"singleplayer.commands_unavailable",
+		"singleplayer.interaction_unavailable",
+	];
+
+	pub fn new(key: &'static str) -> Self {
+		debug_assert!(
+			Self::KEYS.contains(&key),
+			"{key} should be listed in LocalizedText::KEYS"
+		);
+
+		Self {
+			key: key.into(),
+			params: vec![],
+		}
+	}
+
+	pub fn with(mut self, name: &'static str, value: impl Into<Param>) -> Self {
+		self.params.push((name.into(), value.into()));
+		self
+	}
+}
+
+/// A value filled in to a [`LocalizedText`].
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Param {
+	/// Shown as it is, for names and for anything typed by a person, such as an admin's reason for a kick.
+	Text(Box<str>),
+	Integer(i64),
+	Item(Item),
+}
+
+impl Display for Param {
+	fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Text(text) => formatter.write_str(text),
+			Self::Integer(integer) => write!(formatter, "{integer}"),
+			Self::Item(item) => formatter.write_str(item.display_name()),
+		}
+	}
+}
+
+impl From<&str> for Param {
+	fn from(value: &str) -> Self {
+		Self::Text(value.into())
+	}
+}
+
+impl From<String> for Param {
+	fn from(value: String) -> Self {
+		Self::Text(value.into())
+	}
+}
+
+impl From<usize> for Param {
+	fn from(value: usize) -> Self {
+		Self::Integer(value as i64)
+	}
+}
+
+impl From<i64> for Param {
+	fn from(value: i64) -> Self {
+		Self::Integer(value)
+	}
+}
+
+impl From<Item> for Param {
+	fn from(value: Item) -> Self {
+		Self::Item(value)
+	}
+}
+
+/// Why the server refused something the player tried to do. Unlike a system message, the client decides how to word
+/// it, so that it can be translated.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ActionDenied {
+	/// The player already owns as many structures as they're allowed.
+	PlayerStructureLimit { max: usize },
+	/// The sector already has as many structures as it allows.
+	SectorStructureLimit { max: usize },
+	/// Structures are being placed, or terrain modified, faster than allowed.
+	PlacementRateLimited,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum Severity {
+	Info,
+	Warning,
+	Error,
+}
+
+impl From<UiEvent> for Clientbound {
+	fn from(value: UiEvent) -> Self {
+		Self::UiEvent(value)
+	}
+}
+
+/// Where the sector currently is in it's ambient (day/night) cycle. Sent periodically, and whenever the cycle is paused
+/// or resumed.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Ambient {
+	/// From 0 to 1, 0 is sunrise, 0.25 is midday, 0.5 is sunset, and 0.75 is midnight.
+	pub phase: f32,
+
+	/// How much the phase advances each second, so that the client can keep the cycle moving between updates. 0 while
+	/// the cycle is paused.
+	pub rate: f32,
+}
+
+impl From<Ambient> for Clientbound {
+	fn from(value: Ambient) -> Self {
+		Self::Ambient(value)
+	}
+}
+
+/// Lighting at one point in the ambient cycle, the client interpolates between keyframes. Colors are linear RGB.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct AmbientKeyframe {
+	/// From 0 to 1, see [`Ambient::phase`].
+	pub phase: f32,
+
+	/// Color of the sky behind everything.
+	pub sky: [f32; 3],
+
+	/// Color of the sun's light, the brightness is included in the color.
+	pub sun: [f32; 3],
+
+	/// Light which reaches every surface, including those facing away from the sun.
+	pub ambient: [f32; 3],
+}
+
+/// A chat message from a player.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ChatMessage {
+	/// Counts up from 0 for each message sent in the sector, so that clients can tell whether they've already seen a
+	/// message.
+	pub sequence: u64,
+	pub player: Id,
+	pub text: Box<str>,
+}
+
+impl From<ChatMessage> for Clientbound {
+	fn from(value: ChatMessage) -> Self {
+		Self::ChatMessage(value)
+	}
+}
+
+/// The sector's most recent chat messages, oldest first. Sent when the player joins, before any [`ChatMessage`].
+#[derive(Clone, Deserialize, Serialize)]
+pub struct ChatHistory(pub Vec<ChatMessage>);
+
+impl From<ChatHistory> for Clientbound {
+	fn from(value: ChatHistory) -> Self {
+		Self::ChatHistory(value)
+	}
+}
+
+/// The [`ChatMessage`] was retracted by an admin and should no longer be shown.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct ChatRetract {
+	pub sequence: u64,
+}
+
+impl From<ChatRetract> for Clientbound {
+	fn from(value: ChatRetract) -> Self {
+		Self::ChatRetract(value)
+	}
+}
+
+/// Moves the player, keeping the direction they're facing.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct Teleport {
+	pub position: Point3<f32>,
+}
+
+impl From<Teleport> for Clientbound {
+	fn from(value: Teleport) -> Self {
+		Self::Teleport(value)
+	}
+}
+
+/// Sent just before the server closes the connection, so that the client can tell the player why rather than trying to
+/// reconnect straight away.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct Disconnect {
+	pub reason: DisconnectReason,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub enum DisconnectReason {
+	/// The sector already has as many players as it allows.
+	SectorFull { players: u32, capacity: u32 },
+	/// The player was away for longer than the sector allows.
+	Idle,
+}
+
+impl From<Disconnect> for Clientbound {
+	fn from(value: Disconnect) -> Self {
+		Self::Disconnect(value)
+	}
+}
+
+/// The reply to a [`Ping`], with it's sequence.
+///
+/// [`Ping`]: crate::message::serverbound::Ping
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct Pong {
+	pub sequence: u32,
+}
+
+impl From<Pong> for Clientbound {
+	fn from(value: Pong) -> Self {
+		Self::Pong(value)
+	}
+}
+
+/// Sent just before the server closes the connection to send the player to another sector. The client should connect
+/// through `gateway` rather than the gateway it came from, the destination already knows to expect the player.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Transfer {
+	pub sector: Box<str>,
+	/// API endpoint of the gateway that admits players to `sector`.
+	pub gateway: Box<str>,
+}
+
+impl From<Transfer> for Clientbound {
+	fn from(value: Transfer) -> Self {
+		Self::Transfer(value)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn inventory(quantity: Option<i64>, max_stacks: usize, max_quantity: i64) -> SyncInventory {
+		SyncInventory {
+			slots: quantity
+				.map(|quantity| InventorySlot {
+					item: Item::TestOre,
+					quantity,
+				})
+				.into_iter()
+				.collect(),
+			capacity: InventoryCapacity {
+				max_stacks,
+				max_quantity,
+			},
+		}
+	}
+
+	#[test]
+	fn everything_fits_in_empty_inventory() {
+		assert_eq!(inventory(None, 1, 100).addable(Item::TestOre, 40), 40);
+		assert_eq!(inventory(None, 1, 100).addable(Item::TestOre, 100), 100);
+	}
+
+	#[test]
+	fn only_part_fits_over_max_quantity() {
+		assert_eq!(inventory(None, 1, 100).addable(Item::TestOre, 150), 100);
+		assert_eq!(inventory(Some(60), 1, 100).addable(Item::TestOre, 50), 40);
+	}
+
+	#[test]
+	fn existing_stack_near_limit() {
+		assert_eq!(inventory(Some(99), 1, 100).addable(Item::TestOre, 1), 1);
+		assert_eq!(inventory(Some(99), 1, 100).addable(Item::TestOre, 5), 1);
+		assert_eq!(inventory(Some(100), 1, 100).addable(Item::TestOre, 5), 0);
+	}
+
+	#[test]
+	fn existing_stack_doesnt_need_a_free_stack() {
+		assert_eq!(inventory(Some(10), 1, 100).addable(Item::TestOre, 5), 5);
+	}
+
+	#[test]
+	fn new_item_needs_a_free_stack() {
+		assert_eq!(inventory(None, 0, 100).addable(Item::TestOre, 5), 0);
+	}
+
+	#[test]
+	fn never_negative() {
+		// Capacity can be lowered in config after an inventory is already fuller than it allows
+		assert_eq!(inventory(Some(150), 1, 100).addable(Item::TestOre, 5), 0);
+		assert_eq!(inventory(None, 1, 100).addable(Item::TestOre, 0), 0);
+		assert_eq!(inventory(None, 1, 100).addable(Item::TestOre, -5), 0);
+	}
+
+	#[test]
+	fn used_sums_every_stack() {
+		assert_eq!(inventory(None, 1, 100).used(), 0);
+		assert_eq!(inventory(Some(42), 1, 100).used(), 42);
+	}
+}
@@ -0,0 +1,222 @@
+use crate::{
+	connection::MAX_MESSAGE_LENGTH,
+	data::{
+		world::{ChunkCoordinates, Level, Material, LEVELS},
+		Id,
+	},
+	message::{
+		clientbound::{ChatHistory, ChatMessage, Clientbound, SyncChunk},
+		fingerprint::{
+			clientbound_name, clientbound_samples, serverbound_name, serverbound_samples,
+		},
+		serverbound::{HaveChunks, ModifyVoxel, RequestChunk, SendChatMessage, Serverbound},
+		PROTOCOL_VERSION,
+	},
+};
+use nalgebra::vector;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{collections::HashMap, env, fs, path::PathBuf, sync::Arc};
+
+/// Bytes encryption adds to every message, Poly1305's tag.
+const TAG_LENGTH: usize = 16;
+
+/// Variants whose size grows with the sector rather than being capped by a constant, so the samples here can't be the
+/// largest they get. The sector server has to keep them within [`MAX_MESSAGE_LENGTH`] itself, they aren't split up.
+const UNBOUNDED: &[&str] = &[
+	"Sync",
+	"SyncInventory",
+	"SyncStructure",
+	"SyncContainer",
+	"ChatHistory",
+];
+
+fn id(id: u64) -> Id {
+	id.to_string().parse().expect("any u64 is an id")
+}
+
+/// The furthest chunk from the origin on the highest level anything is synced at.
+fn extreme_chunk(sign: i32) -> ChunkCoordinates {
+	ChunkCoordinates::new(
+		id(u64::MAX),
+		vector![i32::MAX, i32::MIN, i32::MAX].map(|coordinate| coordinate.saturating_mul(sign)),
+		Level::new(LEVELS - 2),
+	)
+}
+
+/// The longest text [`SendChatMessage::MAX_LENGTH`] allows, every character as wide as UTF-8 gets.
+fn longest_text() -> Box<str> {
+	"\u{10FFFF}".repeat(SendChatMessage::MAX_LENGTH).into()
+}
+
+/// Boundary cases for [`Clientbound`], the largest each variant can be where that's bounded, and extreme values.
+fn clientbound_boundaries() -> Vec<Clientbound> {
+	let materials = [Material::Corium, Material::Stone, Material::Ground];
+
+	vec![
+		Clientbound::SyncChunk(SyncChunk {
+			coordinates: extreme_chunk(1),
+			generation: u64::MAX,
+			materials: Arc::new(std::array::from_fn(|index| materials[index % 3])),
+			densities: Arc::new(std::array::from_fn(|index| {
+				[f32::MIN, -0.0, f32::MAX, f32::EPSILON][index % 4]
+			})),
+		}),
+		Clientbound::ChatMessage(ChatMessage {
+			sequence: u64::MAX,
+			player: id(u64::MAX),
+			text: longest_text(),
+		}),
+		Clientbound::ChatHistory(ChatHistory(vec![])),
+	]
+}
+
+/// Boundary cases for [`Serverbound`], the largest each variant can be where that's bounded, and extreme values.
+fn serverbound_boundaries() -> Vec<Serverbound> {
+	vec![
+		Serverbound::SendChatMessage(SendChatMessage {
+			text: longest_text(),
+		}),
+		Serverbound::HaveChunks(HaveChunks {
+			entries: (0..HaveChunks::BATCH_SIZE)
+				.map(|index| (extreme_chunk(1 - 2 * (index as i32 % 2)), u64::MAX))
+				.collect(),
+		}),
+		Serverbound::RequestChunk(RequestChunk(extreme_chunk(-1))),
+		Serverbound::ModifyVoxel(ModifyVoxel {
+			coordinates: extreme_chunk(1),
+			cell: vector![u8::MAX, 0, u8::MAX],
+			material: Material::Corium,
+			density: f32::NEG_INFINITY,
+		}),
+	]
+}
+
+/// Names each message after it's variant, numbered in order among messages of the same variant.
+fn name_messages<T>(messages: Vec<T>, name: fn(&T) -> &'static str) -> Vec<(String, T)> {
+	let mut counts = HashMap::<_, usize>::new();
+
+	messages
+		.into_iter()
+		.map(|message| {
+			let count = counts.entry(name(&message)).or_default();
+			*count += 1;
+			(format!("{}-{count}", name(&message)), message)
+		})
+		.collect()
+}
+
+fn clientbound_messages() -> Vec<(String, Clientbound)> {
+	let mut messages = clientbound_samples();
+	messages.extend(clientbound_boundaries());
+	name_messages(messages, clientbound_name)
+}
+
+fn serverbound_messages() -> Vec<(String, Serverbound)> {
+	let mut messages = serverbound_samples();
+	messages.extend(serverbound_boundaries());
+	name_messages(messages, serverbound_name)
+}
+
+/// Encoded the same way a connection does before encrypting.
+fn encode(message: &impl Serialize) -> Vec<u8> {
+	bincode::serialize(message).expect("messages should serialize")
+}
+
+/// Compares each message's encoding against the fixture of the same name in `shared/tests/fixtures/<directory>`, and
+/// checks that decoding the fixture gets the same message back, so that a client and sector server on the same
+/// [`PROTOCOL_VERSION`] always agree. Every problem is collected, so one run lists everything that changed. Run the
+/// tests with `SOLARSCAPE_UPDATE_FIXTURES=1` to write the fixtures instead, after bumping [`PROTOCOL_VERSION`].
+fn check_fixtures<T: Serialize + DeserializeOwned>(directory: &str, messages: Vec<(String, T)>) {
+	let directory = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+		.join("tests/fixtures")
+		.join(directory);
+
+	if env::var_os("SOLARSCAPE_UPDATE_FIXTURES").is_some() {
+		let _ = fs::remove_dir_all(&directory);
+		fs::create_dir_all(&directory).expect("fixtures directory should be writable");
+
+		for (name, message) in &messages {
+			fs::write(directory.join(format!("{name}.bin")), encode(message))
+				.expect("fixture should be writable");
+		}
+
+		return;
+	}
+
+	let mut problems = vec![];
+
+	for (name, message) in &messages {
+		let encoded = encode(message);
+
+		let Ok(fixture) = fs::read(directory.join(format!("{name}.bin"))) else {
+			problems.push(format!("{name} has no fixture"));
+			continue;
+		};
+
+		if encoded != fixture {
+			problems.push(format!("{name} is encoded differently to it's fixture"));
+			continue;
+		}
+
+		match bincode::deserialize::<T>(&fixture) {
+			Ok(decoded) if encode(&decoded) == fixture => {}
+			Ok(_) => problems.push(format!("{name} isn't the same once decoded")),
+			Err(error) => problems.push(format!("{name}'s fixture doesn't decode: {error}")),
+		}
+	}
+
+	let expected = messages
+		.iter()
+		.map(|(name, _)| format!("{name}.bin"))
+		.collect::<Vec<_>>();
+
+	for entry in fs::read_dir(&directory).into_iter().flatten().flatten() {
+		let file_name = entry.file_name().to_string_lossy().into_owned();
+
+		if !expected.contains(&file_name) {
+			problems.push(format!(
+				"{file_name} is a fixture for a message that no longer exists"
+			));
+		}
+	}
+
+	assert!(
+		problems.is_empty(),
+		"{directory:?} doesn't match protocol version {PROTOCOL_VERSION}:\n\t{}\nIf this was intended, bump \
+		 PROTOCOL_VERSION and run the tests again with SOLARSCAPE_UPDATE_FIXTURES=1",
+		problems.join("\n\t"),
+	);
+}
+
+#[test]
+fn clientbound_matches_fixtures() {
+	check_fixtures("clientbound", clientbound_messages());
+}
+
+#[test]
+fn serverbound_matches_fixtures() {
+	check_fixtures("serverbound", serverbound_messages());
+}
+
+#[test]
+fn messages_fit_in_a_frame() {
+	let clientbound = clientbound_messages()
+		.into_iter()
+		.map(|(name, message)| (name, clientbound_name(&message), encode(&message).len()));
+	let serverbound = serverbound_messages()
+		.into_iter()
+		.map(|(name, message)| (name, serverbound_name(&message), encode(&message).len()));
+
+	for (name, variant, length) in clientbound.chain(serverbound) {
+		let length = length + TAG_LENGTH;
+
+		assert!(
+			length <= MAX_MESSAGE_LENGTH,
+			"{name} is {length} bytes once encrypted, more than the {MAX_MESSAGE_LENGTH} a frame can hold{}",
+			match UNBOUNDED.contains(&variant) {
+				true => ", and it's size isn't bounded, so the sector server needs to keep it smaller",
+				false => "",
+			}
+		);
+	}
+}
@@ -0,0 +1,432 @@
+use crate::{
+	data::{
+		world::{BlockType, ChunkCoordinates, Item, Level, Location, Material, VoxjectPosition},
+		Id,
+	},
+	hash::fnv1a,
+	message::{
+		clientbound::{
+			ActionDenied, Ambient, AmbientKeyframe, ChatHistory, ChatMessage, ChatRetract,
+			ChunkUnchanged, Clientbound, Disconnect, DisconnectReason, InventoryCapacity,
+			InventorySlot, JoinComplete, LocalizedText, Pong, RemoveChunk, RemoveStructure,
+			SetBlockState, Severity, Sync, SyncChunk, SyncContainer, SyncInventory, SyncStructure,
+			Teleport, Transfer, UiEvent, Voxject,
+		},
+		serverbound::{
+			BrushMode, ClientTelemetry, CreateStructure, DeleteStructure, HaveChunks,
+			InteractBlock, ModifyTerrainBrush, ModifyVoxel, Ping, RequestChunk, SendChatMessage,
+			Serverbound, SetAmbientPaused, ViewHint,
+		},
+		PROTOCOL_VERSION,
+	},
+};
+use nalgebra::{point, vector, Quaternion, UnitQuaternion};
+use std::{collections::HashMap, sync::Arc};
+
+/// [`PROTOCOL_VERSION`] and [`fingerprint`] as of the last time the protocol changed, as `<version> <fingerprint>`.
+const RECORDED: &str = include_str!("protocol_fingerprint");
+
+/// Every [`Clientbound`] variant in order, see [`clientbound_name`].
+const CLIENTBOUND: [&str; 19] = [
+	"Sync",
+	"JoinComplete",
+	"SyncInventory",
+	"SyncChunk",
+	"RemoveChunk",
+	"ChunkUnchanged",
+	"SyncStructure",
+	"RemoveStructure",
+	"SetBlockState",
+	"SyncContainer",
+	"UiEvent",
+	"Ambient",
+	"ChatMessage",
+	"ChatHistory",
+	"ChatRetract",
+	"Teleport",
+	"Disconnect",
+	"Pong",
+	"Transfer",
+];
+
+/// Every [`Serverbound`] variant in order, see [`serverbound_name`].
+const SERVERBOUND: [&str; 14] = [
+	"PlayerLocation",
+	"GiveTestItem",
+	"CreateStructure",
+	"DeleteStructure",
+	"ModifyTerrainBrush",
+	"SetAmbientPaused",
+	"ClientTelemetry",
+	"SendChatMessage",
+	"Ping",
+	"HaveChunks",
+	"RequestChunk",
+	"InteractBlock",
+	"ViewHint",
+	"ModifyVoxel",
+];
+
+/// Name of `message`'s variant. There's no wildcard, so adding a variant doesn't compile until it's added here, and to
+/// [`CLIENTBOUND`], whose length has to be changed along with it.
+pub(crate) fn clientbound_name(message: &Clientbound) -> &'static str {
+	match message {
+		Clientbound::Sync(_) => "Sync",
+		Clientbound::JoinComplete(_) => "JoinComplete",
+		Clientbound::SyncInventory(_) => "SyncInventory",
+		Clientbound::SyncChunk(_) => "SyncChunk",
+		Clientbound::RemoveChunk(_) => "RemoveChunk",
+		Clientbound::ChunkUnchanged(_) => "ChunkUnchanged",
+		Clientbound::SyncStructure(_) => "SyncStructure",
+		Clientbound::RemoveStructure(_) => "RemoveStructure",
+		Clientbound::SetBlockState(_) => "SetBlockState",
+		Clientbound::SyncContainer(_) => "SyncContainer",
+		Clientbound::UiEvent(_) => "UiEvent",
+		Clientbound::Ambient(_) => "Ambient",
+		Clientbound::ChatMessage(_) => "ChatMessage",
+		Clientbound::ChatHistory(_) => "ChatHistory",
+		Clientbound::ChatRetract(_) => "ChatRetract",
+		Clientbound::Teleport(_) => "Teleport",
+		Clientbound::Disconnect(_) => "Disconnect",
+		Clientbound::Pong(_) => "Pong",
+		Clientbound::Transfer(_) => "Transfer",
+	}
+}
+
+/// Same as [`clientbound_name`], for [`SERVERBOUND`].
+pub(crate) fn serverbound_name(message: &Serverbound) -> &'static str {
+	match message {
+		Serverbound::PlayerLocation(_) => "PlayerLocation",
+		Serverbound::GiveTestItem => "GiveTestItem",
+		Serverbound::CreateStructure(_) => "CreateStructure",
+		Serverbound::DeleteStructure(_) => "DeleteStructure",
+		Serverbound::ModifyTerrainBrush(_) => "ModifyTerrainBrush",
+		Serverbound::SetAmbientPaused(_) => "SetAmbientPaused",
+		Serverbound::ClientTelemetry(_) => "ClientTelemetry",
+		Serverbound::SendChatMessage(_) => "SendChatMessage",
+		Serverbound::Ping(_) => "Ping",
+		Serverbound::HaveChunks(_) => "HaveChunks",
+		Serverbound::RequestChunk(_) => "RequestChunk",
+		Serverbound::InteractBlock(_) => "InteractBlock",
+		Serverbound::ViewHint(_) => "ViewHint",
+		Serverbound::ModifyVoxel(_) => "ModifyVoxel",
+	}
+}
+
+/// Identifies the shape of the protocol, so that changing it without bumping [`PROTOCOL_VERSION`] can be caught. Made
+/// from every variant's name and the encoding of [`clientbound_samples`] and [`serverbound_samples`], which give each
+/// field a different value so that reordering fields changes it too.
+///
+/// # Panics
+/// If the samples don't cover every variant in order, which means they need updating along with the enums.
+fn fingerprint() -> u64 {
+	let clientbound = clientbound_samples();
+	let serverbound = serverbound_samples();
+
+	check_order(
+		"Clientbound",
+		&CLIENTBOUND,
+		clientbound.iter().map(clientbound_name),
+	);
+	check_order(
+		"Serverbound",
+		&SERVERBOUND,
+		serverbound.iter().map(serverbound_name),
+	);
+
+	let mut bytes = vec![];
+
+	for name in CLIENTBOUND.iter().chain(&SERVERBOUND) {
+		bytes.extend(name.as_bytes());
+		bytes.push(0);
+	}
+
+	for message in &clientbound {
+		bytes.extend(bincode::serialize(message).expect("samples should serialize"));
+	}
+
+	for message in &serverbound {
+		bytes.extend(bincode::serialize(message).expect("samples should serialize"));
+	}
+
+	fnv1a(bytes)
+}
+
+/// Checks [`fingerprint`] and [`PROTOCOL_VERSION`] against the ones recorded in `protocol_fingerprint`, next to this
+/// file. The failure says what to do about it, changing the protocol needs both a new version and a new recording.
+#[test]
+fn protocol_version_matches_recording() {
+	let fingerprint = fingerprint();
+
+	let recorded = RECORDED.split_whitespace().collect::<Vec<_>>();
+	let (recorded_version, recorded_fingerprint) = match recorded[..] {
+		[version, fingerprint] => (
+			version.parse::<u32>().ok(),
+			u64::from_str_radix(fingerprint, 16).ok(),
+		),
+		_ => (None, None),
+	};
+
+	let expected = format!("{PROTOCOL_VERSION} {fingerprint:016x}");
+
+	match (recorded_version, recorded_fingerprint) {
+		(Some(version), Some(recorded))
+			if recorded == fingerprint && version == PROTOCOL_VERSION => {}
+		(Some(version), Some(recorded))
+			if recorded != fingerprint && version == PROTOCOL_VERSION =>
+		{
+			panic!(
+				"Messages have changed since protocol version {version}, bump PROTOCOL_VERSION and write \"{} \
+				 {fingerprint:016x}\" to shared/src/message/protocol_fingerprint",
+				version + 1
+			)
+		}
+		_ => panic!(
+			"shared/src/message/protocol_fingerprint is out of date, write \"{expected}\" to it"
+		),
+	}
+}
+
+fn check_order<'a>(enum_name: &str, names: &[&str], samples: impl Iterator<Item = &'a str>) {
+	let mut samples = samples.collect::<Vec<_>>();
+	samples.dedup();
+
+	assert_eq!(
+		samples, names,
+		"{enum_name} samples should cover every variant, in order"
+	);
+}
+
+fn id(id: u64) -> Id {
+	id.to_string().parse().expect("any u64 is an id")
+}
+
+fn chunk(x: i32) -> ChunkCoordinates {
+	ChunkCoordinates::new(id(1), vector![x, x + 1, x + 2], Level::new(3))
+}
+
+fn location() -> Location {
+	Location {
+		position: point![1.0, 2.0, 3.0],
+		// Built from exact values, as trigonometry can differ between platforms
+		rotation: UnitQuaternion::new_unchecked(Quaternion::new(0.5, -0.5, 0.5, -0.5)),
+	}
+}
+
+fn inventory() -> SyncInventory {
+	SyncInventory {
+		slots: vec![InventorySlot {
+			item: Item::TestOre,
+			quantity: 4,
+		}],
+		capacity: InventoryCapacity {
+			max_stacks: 5,
+			max_quantity: 6,
+		},
+	}
+}
+
+fn text() -> LocalizedText {
+	LocalizedText::new("server.command_reply")
+		.with("a", "b")
+		.with("c", 7i64)
+		.with("d", Item::TestOre)
+}
+
+fn chat_message() -> ChatMessage {
+	ChatMessage {
+		sequence: 8,
+		player: id(9),
+		text: "chat".into(),
+	}
+}
+
+/// One of every [`Clientbound`] message, and of every variant of the enums within them, in order.
+pub(crate) fn clientbound_samples() -> Vec<Clientbound> {
+	let mut materials = [Material::Nothing; 4096];
+	materials[..4].copy_from_slice(&[
+		Material::Corium,
+		Material::Stone,
+		Material::Ground,
+		Material::Nothing,
+	]);
+
+	let keyframe = AmbientKeyframe {
+		phase: 0.5,
+		sky: [0.1, 0.2, 0.3],
+		sun: [0.4, 0.5, 0.6],
+		ambient: [0.7, 0.8, 0.9],
+	};
+
+	vec![
+		Clientbound::Sync(Sync {
+			name: "name".into(),
+			tick_rate: 10,
+			world_radius: Some(11.0),
+			world_radius_chunks: 12,
+			max_level: Level::new(13),
+			voxjects: vec![Voxject {
+				id: id(13),
+				name: "voxject".into(),
+			}],
+			ambient_keyframes: vec![keyframe],
+			ambient: Ambient {
+				phase: 0.25,
+				rate: 0.125,
+			},
+		}),
+		Clientbound::JoinComplete(JoinComplete),
+		Clientbound::SyncInventory(inventory()),
+		Clientbound::SyncChunk(SyncChunk {
+			coordinates: chunk(14),
+			generation: 15,
+			materials: Arc::new(materials),
+			densities: Arc::new([0.5; 4096]),
+		}),
+		Clientbound::RemoveChunk(RemoveChunk(chunk(16))),
+		Clientbound::ChunkUnchanged(ChunkUnchanged {
+			coordinates: chunk(17),
+			generation: 18,
+			content_hash: 19,
+		}),
+		Clientbound::SyncStructure(SyncStructure {
+			id: id(20),
+			owner: id(21),
+			location: location(),
+			linear_velocity: vector![22.0, 23.0, 24.0],
+			angular_velocity: vector![25.0, 26.0, 27.0],
+			sleeping: true,
+			// Only one block, the order of more would depend on the hash map's implementation
+			blocks: HashMap::from_iter([(vector![1, 2, 3], BlockType::Block)]),
+			block_states: HashMap::from_iter([(vector![4, 5, 6], 7)]),
+		}),
+		Clientbound::RemoveStructure(RemoveStructure(id(28))),
+		Clientbound::SetBlockState(SetBlockState {
+			structure: id(52),
+			position: vector![53, 54, 55],
+			state: 56,
+		}),
+		Clientbound::SyncContainer(SyncContainer {
+			structure: id(57),
+			position: vector![58, 59, 60],
+			inventory: inventory(),
+		}),
+		Clientbound::UiEvent(UiEvent::SystemMessage {
+			text: text(),
+			severity: Severity::Info,
+		}),
+		Clientbound::UiEvent(UiEvent::SystemMessage {
+			text: text(),
+			severity: Severity::Warning,
+		}),
+		Clientbound::UiEvent(UiEvent::SystemMessage {
+			text: text(),
+			severity: Severity::Error,
+		}),
+		Clientbound::UiEvent(UiEvent::OpenInventory),
+		Clientbound::UiEvent(UiEvent::CloseInventory),
+		Clientbound::UiEvent(UiEvent::Notice {
+			title: "title".into(),
+			body: "body".into(),
+		}),
+		Clientbound::UiEvent(UiEvent::ActionDenied(ActionDenied::PlayerStructureLimit {
+			max: 29,
+		})),
+		Clientbound::UiEvent(UiEvent::ActionDenied(ActionDenied::SectorStructureLimit {
+			max: 30,
+		})),
+		Clientbound::UiEvent(UiEvent::ActionDenied(ActionDenied::PlacementRateLimited)),
+		Clientbound::Ambient(Ambient {
+			phase: 0.75,
+			rate: 0.0625,
+		}),
+		Clientbound::ChatMessage(chat_message()),
+		Clientbound::ChatHistory(ChatHistory(vec![chat_message()])),
+		Clientbound::ChatRetract(ChatRetract { sequence: 31 }),
+		Clientbound::Teleport(Teleport {
+			position: point![32.0, 33.0, 34.0],
+		}),
+		Clientbound::Disconnect(Disconnect {
+			reason: DisconnectReason::SectorFull {
+				players: 35,
+				capacity: 36,
+			},
+		}),
+		Clientbound::Disconnect(Disconnect {
+			reason: DisconnectReason::Idle,
+		}),
+		Clientbound::Pong(Pong { sequence: 37 }),
+		Clientbound::Transfer(Transfer {
+			sector: "sector".into(),
+			gateway: "gateway".into(),
+		}),
+	]
+}
+
+/// One of every [`Serverbound`] message, and of every variant of the enums within them, in order.
+pub(crate) fn serverbound_samples() -> Vec<Serverbound> {
+	let brush = |mode| ModifyTerrainBrush {
+		center: VoxjectPosition::new(id(38), point![39.0, 40.0, 41.0]),
+		radius: 4.5,
+		material: Material::Stone,
+		mode,
+	};
+
+	vec![
+		Serverbound::PlayerLocation(location()),
+		Serverbound::GiveTestItem,
+		Serverbound::CreateStructure(CreateStructure {
+			location: location(),
+			block: BlockType::Block,
+		}),
+		Serverbound::CreateStructure(CreateStructure {
+			location: location(),
+			block: BlockType::Explosive,
+		}),
+		Serverbound::CreateStructure(CreateStructure {
+			location: location(),
+			block: BlockType::Switch,
+		}),
+		Serverbound::CreateStructure(CreateStructure {
+			location: location(),
+			block: BlockType::Container,
+		}),
+		Serverbound::CreateStructure(CreateStructure {
+			location: location(),
+			block: BlockType::TestBlock,
+		}),
+		Serverbound::DeleteStructure(DeleteStructure { structure: id(42) }),
+		Serverbound::ModifyTerrainBrush(brush(BrushMode::Add)),
+		Serverbound::ModifyTerrainBrush(brush(BrushMode::Remove)),
+		Serverbound::SetAmbientPaused(SetAmbientPaused { paused: true }),
+		Serverbound::ClientTelemetry(ClientTelemetry {
+			fps_avg: 43.0,
+			fps_p1: 44.0,
+			frame_time_p99_ms: 45.0,
+			mesh_jobs_pending: 46,
+			adapter_summary_hash: 47,
+		}),
+		Serverbound::SendChatMessage(SendChatMessage {
+			text: "chat".into(),
+		}),
+		Serverbound::Ping(Ping { sequence: 48 }),
+		Serverbound::HaveChunks(HaveChunks {
+			entries: vec![(chunk(49), 50)],
+		}),
+		Serverbound::RequestChunk(RequestChunk(chunk(51))),
+		Serverbound::InteractBlock(InteractBlock {
+			structure: id(61),
+			position: vector![62, 63, 64],
+		}),
+		Serverbound::ViewHint(ViewHint {
+			forward: vector![0.0, 0.6, -0.8],
+			fov_cos: 0.65,
+		}),
+		Serverbound::ModifyVoxel(ModifyVoxel {
+			coordinates: chunk(65),
+			cell: vector![1, 2, 3],
+			material: Material::Ground,
+			density: 66.0,
+		}),
+	]
+}
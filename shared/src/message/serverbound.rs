@@ -1,11 +1,27 @@
-use crate::data::world::{BlockType, Location};
+use crate::data::{
+	world::{BlockType, ChunkCoordinates, Location, Material, VoxjectPosition},
+	Id,
+};
+use nalgebra::Vector3;
 use serde::{Deserialize, Serialize};
+use std::{f32::consts::PI, time::Duration};
 
-#[derive(Clone, Copy, Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub enum Serverbound {
 	PlayerLocation(Location),
 	GiveTestItem,
 	CreateStructure(CreateStructure),
+	DeleteStructure(DeleteStructure),
+	ModifyTerrainBrush(ModifyTerrainBrush),
+	SetAmbientPaused(SetAmbientPaused),
+	ClientTelemetry(ClientTelemetry),
+	SendChatMessage(SendChatMessage),
+	Ping(Ping),
+	HaveChunks(HaveChunks),
+	RequestChunk(RequestChunk),
+	InteractBlock(InteractBlock),
+	ViewHint(ViewHint),
+	ModifyVoxel(ModifyVoxel),
 }
 
 impl From<Location> for Serverbound {
@@ -27,3 +43,304 @@ impl From<CreateStructure> for Serverbound {
 		Self::CreateStructure(value)
 	}
 }
+
+/// Delete a [Structure](crate::structure::Structure), only the Structure's owner is allowed to do this.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct DeleteStructure {
+	pub structure: Id,
+}
+
+impl From<DeleteStructure> for Serverbound {
+	fn from(value: DeleteStructure) -> Self {
+		Self::DeleteStructure(value)
+	}
+}
+
+/// Add or remove terrain within a sphere. The [Material] is only used when adding terrain.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct ModifyTerrainBrush {
+	pub center: VoxjectPosition,
+	pub radius: f32,
+
+	pub material: Material,
+	pub mode: BrushMode,
+}
+
+impl ModifyTerrainBrush {
+	/// The server will clamp the radius to this range.
+	pub const MIN_RADIUS: f32 = 0.5;
+	pub const MAX_RADIUS: f32 = 8.0;
+}
+
+impl From<ModifyTerrainBrush> for Serverbound {
+	fn from(value: ModifyTerrainBrush) -> Self {
+		Self::ModifyTerrainBrush(value)
+	}
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+pub enum BrushMode {
+	Add,
+	Remove,
+}
+
+/// Pause or resume the sector's ambient cycle, for debugging lighting. Only admins are allowed to do this.
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct SetAmbientPaused {
+	pub paused: bool,
+}
+
+impl From<SetAmbientPaused> for Serverbound {
+	fn from(value: SetAmbientPaused) -> Self {
+		Self::SetAmbientPaused(value)
+	}
+}
+
+/// Anonymous client performance, only sent if the player has opted in to sharing it.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct ClientTelemetry {
+	pub fps_avg: f32,
+	/// Average FPS of the slowest 1% of frames.
+	pub fps_p1: f32,
+	pub frame_time_p99_ms: f32,
+
+	/// Chunks waiting for more data before they can be meshed.
+	pub mesh_jobs_pending: u32,
+
+	/// Hash of the graphics adapter's name, backend, and driver, so reports from the same hardware can be grouped
+	/// without sending what it is.
+	pub adapter_summary_hash: u64,
+}
+
+impl ClientTelemetry {
+	/// How often the client sends a report, the server ignores reports that come any faster.
+	pub const INTERVAL: Duration = Duration::from_secs(60);
+}
+
+impl From<ClientTelemetry> for Serverbound {
+	fn from(value: ClientTelemetry) -> Self {
+		Self::ClientTelemetry(value)
+	}
+}
+
+/// Sends a message to everyone in the sector, or runs a command if it starts with `/`.
+#[derive(Clone, Deserialize, Serialize)]
+pub struct SendChatMessage {
+	pub text: Box<str>,
+}
+
+impl SendChatMessage {
+	/// Longest message the server accepts, in characters.
+	pub const MAX_LENGTH: usize = 256;
+}
+
+impl From<SendChatMessage> for Serverbound {
+	fn from(value: SendChatMessage) -> Self {
+		Self::SendChatMessage(value)
+	}
+}
+
+/// Asks for a [`Pong`] with the same sequence, for measuring latency. The server replies while processing the tick
+/// after it arrives, so the time until the reply includes up to a tick of waiting.
+///
+/// [`Pong`]: crate::message::clientbound::Pong
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct Ping {
+	pub sequence: u32,
+}
+
+impl From<Ping> for Serverbound {
+	fn from(value: Ping) -> Self {
+		Self::Ping(value)
+	}
+}
+
+/// Chunks the client has cached from an earlier visit to the sector, with their content hashes. Sent in batches when
+/// joining, chunks the client is sent afterwards that still have the same content hash are sent as a
+/// [`ChunkUnchanged`] rather than all of their data.
+///
+/// [`ChunkUnchanged`]: crate::message::clientbound::ChunkUnchanged
+#[derive(Clone, Deserialize, Serialize)]
+pub struct HaveChunks {
+	pub entries: Vec<(ChunkCoordinates, u64)>,
+}
+
+impl HaveChunks {
+	/// Most entries sent in one message, so that it stays well within [`MAX_MESSAGE_LENGTH`].
+	///
+	/// [`MAX_MESSAGE_LENGTH`]: crate::connection::MAX_MESSAGE_LENGTH
+	pub const BATCH_SIZE: usize = 1024;
+
+	/// Most entries the server keeps for each player, any more are ignored.
+	pub const MAX_ENTRIES: usize = 65536;
+}
+
+impl From<HaveChunks> for Serverbound {
+	fn from(value: HaveChunks) -> Self {
+		Self::HaveChunks(value)
+	}
+}
+
+/// Asks for a chunk to be synced again, for when the client was sent a [`ChunkUnchanged`] but couldn't load it from
+/// it's cache after all. Ignored unless the client is already being sent the chunk.
+///
+/// [`ChunkUnchanged`]: crate::message::clientbound::ChunkUnchanged
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct RequestChunk(pub ChunkCoordinates);
+
+impl From<RequestChunk> for Serverbound {
+	fn from(value: RequestChunk) -> Self {
+		Self::RequestChunk(value)
+	}
+}
+
+/// Uses a block, one which [`BlockType::is_interactable`]. The server checks that the player is within
+/// [`REACH`] of it and can see it first.
+///
+/// [`REACH`]: crate::consts::REACH
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct InteractBlock {
+	pub structure: Id,
+	pub position: Vector3<i16>,
+}
+
+impl From<InteractBlock> for Serverbound {
+	fn from(value: InteractBlock) -> Self {
+		Self::InteractBlock(value)
+	}
+}
+
+/// Which way the player is looking and how much they can see, so that the chunks in view can be synced before the
+/// rest. The sector only knows the player's [`Location`], not their field of view or aspect ratio. Sent again whenever
+/// [`ViewHint::differs`] from the last one sent.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ViewHint {
+	/// Unit vector the player is looking along.
+	pub forward: Vector3<f32>,
+	/// Cosine of the angle between `forward` and the corners of the view, anything closer to `forward` is visible.
+	pub fov_cos: f32,
+}
+
+impl ViewHint {
+	/// How far the view has to turn or widen before it's sent again.
+	pub const RESEND_ANGLE: f32 = 20.0 * PI / 180.0;
+
+	/// How far from unit length `forward` may be before the server rejects the hint.
+	const LENGTH_TOLERANCE: f32 = 1e-3;
+
+	/// The view looking along `forward`, with a vertical field of view of `fovy` radians and an `aspect` ratio of width
+	/// to height.
+	pub fn new(forward: Vector3<f32>, fovy: f32, aspect: f32) -> Self {
+		let half_diagonal = ((fovy / 2.0).tan() * (1.0 + aspect * aspect).sqrt()).atan();
+
+		Self {
+			forward: forward.normalize(),
+			fov_cos: half_diagonal.cos(),
+		}
+	}
+
+	/// Whether `forward` is unit length and `fov_cos` is a cosine.
+	pub fn is_valid(&self) -> bool {
+		self.forward.iter().all(|axis| axis.is_finite())
+			&& (self.forward.norm() - 1.0).abs() <= Self::LENGTH_TOLERANCE
+			&& (-1.0..=1.0).contains(&self.fov_cos)
+	}
+
+	/// Whether `other` has turned or widened by more than [`ViewHint::RESEND_ANGLE`] from this one.
+	pub fn differs(&self, other: &ViewHint) -> bool {
+		let widened = (self.fov_cos.acos() - other.fov_cos.acos()).abs();
+
+		self.forward.angle(&other.forward) > Self::RESEND_ANGLE || widened > Self::RESEND_ANGLE
+	}
+}
+
+impl From<ViewHint> for Serverbound {
+	fn from(value: ViewHint) -> Self {
+		Self::ViewHint(value)
+	}
+}
+
+/// Sets a single cell of a level 0 chunk, unlike [`ModifyTerrainBrush`] which blends a whole sphere. The server checks
+/// that the cell is within [`REACH`] of the player.
+///
+/// [`REACH`]: crate::consts::REACH
+#[derive(Clone, Copy, Deserialize, Serialize)]
+pub struct ModifyVoxel {
+	pub coordinates: ChunkCoordinates,
+	/// Position within the chunk, each axis must be less than [`CHUNK_SIZE`].
+	///
+	/// [`CHUNK_SIZE`]: crate::consts::CHUNK_SIZE
+	pub cell: Vector3<u8>,
+	pub material: Material,
+	/// Must be within [`ModifyVoxel::MAX_DENSITY`] of `0`.
+	pub density: f32,
+}
+
+impl ModifyVoxel {
+	/// Densities are roughly distances from the surface in cells, the server refuses any further from it than this.
+	pub const MAX_DENSITY: f32 = 16.0;
+}
+
+impl From<ModifyVoxel> for Serverbound {
+	fn from(value: ModifyVoxel) -> Self {
+		Self::ModifyVoxel(value)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::vector;
+
+	fn hint(forward: Vector3<f32>, fov_cos: f32) -> ViewHint {
+		ViewHint { forward, fov_cos }
+	}
+
+	#[test]
+	fn view_hints_use_the_half_diagonal() {
+		let hint = ViewHint::new(vector![0.0, 0.0, -2.0], PI / 2.0, 1.0);
+
+		assert_eq!(hint.forward, vector![0.0, 0.0, -1.0]);
+
+		// The corners of a square 90 degree view are atan(sqrt(2)) from the middle
+		assert!((hint.fov_cos - 1.0 / 3.0_f32.sqrt()).abs() < 1e-5);
+
+		// Wider views see further from the middle
+		assert!(ViewHint::new(-Vector3::z(), PI / 2.0, 2.0).fov_cos < hint.fov_cos);
+	}
+
+	#[test]
+	fn view_hints_are_validated() {
+		let forward = -Vector3::z();
+
+		for (hint, valid) in [
+			(hint(forward, 0.5), true),
+			(hint(forward, -1.0), true),
+			(hint(forward, 1.0), true),
+			(hint(forward * 1.0005, 0.5), true),
+			(hint(forward * 1.01, 0.5), false),
+			(hint(Vector3::zeros(), 0.5), false),
+			(hint(vector![f32::NAN, 0.0, 0.0], 0.5), false),
+			(hint(vector![f32::INFINITY, 0.0, 0.0], 0.5), false),
+			(hint(forward, 1.01), false),
+			(hint(forward, -1.01), false),
+			(hint(forward, f32::NAN), false),
+		] {
+			assert_eq!(hint.is_valid(), valid, "{hint:?}");
+		}
+	}
+
+	#[test]
+	fn view_hints_differ_once_turned_or_widened_enough() {
+		let angle = |degrees: f32| degrees.to_radians();
+		let facing = |degrees: f32| vector![angle(degrees).sin(), 0.0, -angle(degrees).cos()];
+		let sent = hint(facing(0.0), angle(40.0).cos());
+
+		assert!(!sent.differs(&sent));
+		assert!(!sent.differs(&hint(facing(15.0), sent.fov_cos)));
+		assert!(sent.differs(&hint(facing(25.0), sent.fov_cos)));
+		assert!(!sent.differs(&hint(sent.forward, angle(55.0).cos())));
+		assert!(sent.differs(&hint(sent.forward, angle(65.0).cos())));
+		assert!(sent.differs(&hint(sent.forward, angle(15.0).cos())));
+	}
+}
@@ -1,13 +1,23 @@
-use nalgebra::Vector3;
+use crate::data::{world::ChunkCoordinates, Id};
+use nalgebra::{Point3, Vector3};
 use rapier3d::{
 	dynamics::{
 		CCDSolver, ImpulseJointHandle, ImpulseJointSet, IntegrationParameters, IslandManager,
 		MultibodyJointHandle, MultibodyJointSet, RigidBody, RigidBodyHandle, RigidBodySet,
 	},
-	geometry::{Collider, ColliderHandle, ColliderSet, DefaultBroadPhase, NarrowPhase},
-	pipeline::PhysicsPipeline,
+	geometry::{
+		Collider, ColliderHandle, ColliderSet, DefaultBroadPhase, Group, InteractionGroups,
+		NarrowPhase, Ray,
+	},
+	math::Isometry,
+	parry::{query::ShapeCastOptions, shape::Shape},
+	pipeline::{PhysicsPipeline, QueryFilter, QueryPipeline},
+};
+use rustc_hash::FxBuildHasher;
+use std::{
+	collections::HashMap,
+	ops::{Deref, DerefMut},
 };
-use std::ops::{Deref, DerefMut};
 use tokio::sync::mpsc::{
 	unbounded_channel as channel, UnboundedReceiver as Receiver, UnboundedSender as Sender,
 };
@@ -26,6 +36,11 @@ pub struct Physics {
 	impulse_joints: ImpulseJointSet,
 	multibody_joints: MultibodyJointSet,
 	ccd_solver: CCDSolver,
+
+	/// Kept up to date by [`Physics::tick`], or [`Physics::update_queries`] between ticks.
+	query_pipeline: QueryPipeline,
+	/// What each collider inserted with [`Physics::insert_collider_with_owner`] belongs to.
+	collider_owners: HashMap<ColliderHandle, ColliderOwner, FxBuildHasher>,
 }
 
 impl Physics {
@@ -46,6 +61,9 @@ impl Physics {
 			impulse_joints: ImpulseJointSet::default(),
 			multibody_joints: MultibodyJointSet::default(),
 			ccd_solver: CCDSolver::default(),
+
+			query_pipeline: QueryPipeline::default(),
+			collider_owners: HashMap::with_hasher(FxBuildHasher),
 		}
 	}
 
@@ -56,28 +74,7 @@ impl Physics {
 		// TryRecvError::Empty - There are no more messages, at which point we will break from the loop and continue on
 		// TryRecvError::Disconnected - This is impossible as we also hold a Sender
 		while let Ok(handle_drop) = self.handle_drop_receiver.try_recv() {
-			match handle_drop {
-				HandleDrop::Collider(handle) => {
-					self.colliders
-						.remove(handle, &mut self.islands, &mut self.rigid_bodies, false);
-				}
-				HandleDrop::RigidBody(handle) => {
-					self.rigid_bodies.remove(
-						handle,
-						&mut self.islands,
-						&mut self.colliders,
-						&mut self.impulse_joints,
-						&mut self.multibody_joints,
-						true,
-					);
-				}
-				HandleDrop::ImpulseJoint(handle) => {
-					self.impulse_joints.remove(handle, false);
-				}
-				HandleDrop::MultibodyJoint(handle) => {
-					self.multibody_joints.remove(handle, false);
-				}
-			}
+			self.remove(handle_drop);
 		}
 
 		self.pipeline.step(
@@ -91,19 +88,233 @@ impl Physics {
 			&mut self.impulse_joints,
 			&mut self.multibody_joints,
 			&mut self.ccd_solver,
-			None,
+			Some(&mut self.query_pipeline),
 			&(),
 			&(),
 		);
 	}
 
+	/// Brings queries up to date with colliders added or moved since the last [`Physics::tick`]. Queries otherwise see
+	/// colliders as they were at the end of the last tick, colliders removed since are never hit.
+	pub fn update_queries(&mut self) {
+		self.query_pipeline.update(&self.colliders);
+	}
+
+	/// Removes whatever `handle` points to immediately, rather than waiting for the next [`Physics::tick`] like
+	/// dropping an [`AutoCleanup`] does. Returns `false` if it was already removed.
+	///
+	/// It's fine for the [`AutoCleanup`] that owned `handle` to be dropped afterwards, handles are generational so it
+	/// won't remove anything that has since reused the slot.
+	#[allow(private_bounds)]
+	pub fn remove_now<T: Into<HandleDrop>>(&mut self, handle: T) -> bool {
+		self.remove(handle.into())
+	}
+
+	#[allow(private_bounds)]
+	pub fn contains<T: Into<HandleDrop>>(&self, handle: T) -> bool {
+		match handle.into() {
+			HandleDrop::Collider(handle) => self.colliders.contains(handle),
+			HandleDrop::RigidBody(handle) => self.rigid_bodies.contains(handle),
+			HandleDrop::ImpulseJoint(handle) => self.impulse_joints.contains(handle),
+			HandleDrop::MultibodyJoint(handle) => self.multibody_joints.get(handle).is_some(),
+		}
+	}
+
+	/// What `collider` belongs to, if that was given when it was inserted.
+	pub fn collider_owner(&self, collider: ColliderHandle) -> Option<ColliderOwner> {
+		self.collider_owners.get(&collider).copied()
+	}
+
+	/// Runs `query` with a filter only passing colliders matching `mask`.
+	fn filtered<T>(&self, mask: QueryMask, query: impl FnOnce(QueryFilter) -> T) -> T {
+		let predicate = |_, collider: &Collider| mask.matches(collider);
+		query(QueryFilter::new().predicate(&predicate))
+	}
+
+	/// Whether `shape` placed at `position` would overlap any collider matching `mask`.
+	pub fn intersects_shape(
+		&self,
+		position: &Isometry<f32>,
+		shape: &dyn Shape,
+		mask: QueryMask,
+	) -> bool {
+		self.filtered(mask, |filter| {
+			self.query_pipeline
+				.intersection_with_shape(
+					&self.rigid_bodies,
+					&self.colliders,
+					position,
+					shape,
+					filter,
+				)
+				.is_some()
+		})
+	}
+
+	/// Every collider matching `mask` which `shape` placed at `position` would overlap, in no particular order.
+	pub fn intersections_with_shape(
+		&self,
+		position: &Isometry<f32>,
+		shape: &dyn Shape,
+		mask: QueryMask,
+	) -> Vec<QueryHit> {
+		let mut hits = vec![];
+
+		self.filtered(mask, |filter| {
+			self.query_pipeline.intersections_with_shape(
+				&self.rigid_bodies,
+				&self.colliders,
+				position,
+				shape,
+				filter,
+				|collider| {
+					hits.push(QueryHit {
+						collider,
+						owner: self.collider_owner(collider),
+					});
+					true
+				},
+			)
+		});
+
+		hits
+	}
+
+	/// Finds the closest collider matching `mask` that `ray` hits within `max_distance`. A ray starting inside of a
+	/// collider hits it straight away.
+	pub fn cast_ray(&self, ray: &Ray, max_distance: f32, mask: QueryMask) -> Option<RayHit> {
+		let (collider, intersection) = self.filtered(mask, |filter| {
+			self.query_pipeline.cast_ray_and_get_normal(
+				&self.rigid_bodies,
+				&self.colliders,
+				ray,
+				max_distance,
+				true,
+				filter,
+			)
+		})?;
+
+		Some(RayHit {
+			hit: QueryHit {
+				collider,
+				owner: self.collider_owner(collider),
+			},
+			distance: intersection.time_of_impact,
+			normal: intersection.normal,
+		})
+	}
+
+	/// Sweeps `shape` from `position` along `direction`, finding the first collider matching `mask` that it would hit
+	/// within `max_distance`. A shape which starts overlapping a collider hits it straight away.
+	pub fn cast_shape(
+		&self,
+		position: &Isometry<f32>,
+		direction: &Vector3<f32>,
+		shape: &dyn Shape,
+		max_distance: f32,
+		mask: QueryMask,
+	) -> Option<ShapeHit> {
+		let options = ShapeCastOptions {
+			max_time_of_impact: max_distance,
+			stop_at_penetration: true,
+			compute_impact_geometry_on_penetration: true,
+			..ShapeCastOptions::default()
+		};
+
+		let (collider, shape_hit) = self.filtered(mask, |filter| {
+			self.query_pipeline.cast_shape(
+				&self.rigid_bodies,
+				&self.colliders,
+				position,
+				&direction.normalize(),
+				shape,
+				options,
+				filter,
+			)
+		})?;
+
+		Some(ShapeHit {
+			hit: QueryHit {
+				collider,
+				owner: self.collider_owner(collider),
+			},
+			distance: shape_hit.time_of_impact,
+			point: shape_hit.witness1,
+			normal: *shape_hit.normal1,
+		})
+	}
+
+	/// Finds the closest point to `point` on any collider matching `mask`. Colliders are treated as solid, so a point
+	/// inside of one is its own projection.
+	pub fn project_point(&self, point: &Point3<f32>, mask: QueryMask) -> Option<PointHit> {
+		let (collider, projection) = self.filtered(mask, |filter| {
+			self.query_pipeline.project_point(
+				&self.rigid_bodies,
+				&self.colliders,
+				point,
+				true,
+				filter,
+			)
+		})?;
+
+		Some(PointHit {
+			hit: QueryHit {
+				collider,
+				owner: self.collider_owner(collider),
+			},
+			point: projection.point,
+			is_inside: projection.is_inside,
+		})
+	}
+
+	fn remove(&mut self, handle_drop: HandleDrop) -> bool {
+		// Handles may be removed twice, once by remove_now and again when their AutoCleanup is dropped
+		if !self.contains(handle_drop) {
+			return false;
+		}
+
+		match handle_drop {
+			HandleDrop::Collider(handle) => {
+				self.colliders
+					.remove(handle, &mut self.islands, &mut self.rigid_bodies, false);
+				self.collider_owners.remove(&handle);
+			}
+			HandleDrop::RigidBody(handle) => {
+				let rigid_body = self.rigid_bodies.remove(
+					handle,
+					&mut self.islands,
+					&mut self.colliders,
+					&mut self.impulse_joints,
+					&mut self.multibody_joints,
+					true,
+				);
+
+				// Attached colliders are removed along with the rigid body
+				for collider in rigid_body
+					.iter()
+					.flat_map(|rigid_body| rigid_body.colliders())
+				{
+					self.collider_owners.remove(collider);
+				}
+			}
+			HandleDrop::ImpulseJoint(handle) => {
+				self.impulse_joints.remove(handle, false);
+			}
+			HandleDrop::MultibodyJoint(handle) => {
+				self.multibody_joints.remove(handle, false);
+			}
+		}
+
+		true
+	}
+
 	pub fn insert_rigid_body(
 		&mut self,
 		rigid_body: impl Into<RigidBody>,
 	) -> AutoCleanup<RigidBodyHandle> {
 		AutoCleanup {
 			handle: self.rigid_bodies.insert(rigid_body),
-			handle_drop_sender: self.handle_drop_sender.clone(),
+			handle_drop_sender: Some(self.handle_drop_sender.clone()),
 		}
 	}
 
@@ -111,22 +322,175 @@ impl Physics {
 		self.rigid_bodies.get(rigid_body)
 	}
 
-	pub fn insert_rigid_body_collider(
+	pub fn get_rigid_body_mut(&mut self, rigid_body: RigidBodyHandle) -> Option<&mut RigidBody> {
+		self.rigid_bodies.get_mut(rigid_body)
+	}
+
+	pub fn get_collider(&self, collider: ColliderHandle) -> Option<&Collider> {
+		self.colliders.get(collider)
+	}
+
+	/// Attaches `collider` to a rigid body as a member of `group`, replacing any groups it was built with.
+	pub fn insert_collider_with_group(
 		&mut self,
 		rigid_body_handle: RigidBodyHandle,
 		collider: impl Into<Collider>,
+		group: CollisionGroup,
 	) -> AutoCleanup<ColliderHandle> {
+		let mut collider = collider.into();
+		collider.set_collision_groups(group.interaction_groups());
+
 		AutoCleanup {
 			handle: self.colliders.insert_with_parent(
 				collider,
 				rigid_body_handle,
 				&mut self.rigid_bodies,
 			),
-			handle_drop_sender: self.handle_drop_sender.clone(),
+			handle_drop_sender: Some(self.handle_drop_sender.clone()),
+		}
+	}
+
+	/// Like [`Physics::insert_collider_with_group`], but also records what the collider belongs to, so that queries
+	/// which hit it can say what was hit.
+	pub fn insert_collider_with_owner(
+		&mut self,
+		rigid_body_handle: RigidBodyHandle,
+		collider: impl Into<Collider>,
+		group: CollisionGroup,
+		owner: ColliderOwner,
+	) -> AutoCleanup<ColliderHandle> {
+		let collider = self.insert_collider_with_group(rigid_body_handle, collider, group);
+		self.collider_owners.insert(*collider, owner);
+		collider
+	}
+}
+
+/// What a collider is part of, so that query results can be turned back into whatever was hit.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ColliderOwner {
+	Chunk(ChunkCoordinates),
+	/// A structure, and the position of the block within it.
+	StructureBlock(Id, Vector3<i16>),
+	Player(Id),
+}
+
+/// A collider hit by a query.
+#[derive(Clone, Copy, Debug)]
+pub struct QueryHit {
+	pub collider: ColliderHandle,
+	/// [`None`] if the collider was inserted without an owner.
+	pub owner: Option<ColliderOwner>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct RayHit {
+	pub hit: QueryHit,
+	/// How far along the ray the collider was hit.
+	pub distance: f32,
+	/// Surface normal where the collider was hit.
+	pub normal: Vector3<f32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct ShapeHit {
+	pub hit: QueryHit,
+	/// How far the shape moved before touching the collider.
+	pub distance: f32,
+	/// Where the collider was touched.
+	pub point: Point3<f32>,
+	/// Surface normal of the collider where it was touched.
+	pub normal: Vector3<f32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct PointHit {
+	pub hit: QueryHit,
+	/// The closest point on the collider.
+	pub point: Point3<f32>,
+	/// Whether the queried point was inside of the collider.
+	pub is_inside: bool,
+}
+
+/// What a collider belongs to, which decides what it collides with and lets queries pick which colliders they hit.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum CollisionGroup {
+	Terrain,
+	Structure,
+	Player,
+	/// Colliders which only exist to be queried, like a placement preview. These don't collide with anything.
+	Preview,
+}
+
+impl CollisionGroup {
+	pub const ALL: &'static [Self] = &[Self::Terrain, Self::Structure, Self::Player, Self::Preview];
+
+	/// Groups which physically block each other.
+	pub const SOLID: &'static [Self] = &[Self::Terrain, Self::Structure, Self::Player];
+
+	pub const fn group(self) -> Group {
+		match self {
+			Self::Terrain => Group::GROUP_1,
+			Self::Structure => Group::GROUP_2,
+			Self::Player => Group::GROUP_3,
+			Self::Preview => Group::GROUP_4,
+		}
+	}
+
+	/// Groups colliders in this group collide with. Terrain doesn't move, so it doesn't need to collide with itself.
+	pub fn collides_with(self) -> Group {
+		match self {
+			Self::Terrain => Self::mask(&[Self::Structure, Self::Player]),
+			Self::Structure | Self::Player => Self::mask(Self::SOLID),
+			Self::Preview => Group::NONE,
+		}
+	}
+
+	pub fn interaction_groups(self) -> InteractionGroups {
+		InteractionGroups::new(self.group(), self.collides_with())
+	}
+
+	/// Combines `groups` into a single [`Group`].
+	pub fn mask(groups: &[Self]) -> Group {
+		groups
+			.iter()
+			.fold(Group::NONE, |mask, group| mask | group.group())
+	}
+}
+
+/// Which colliders a query hits.
+#[derive(Clone, Copy, Debug)]
+pub struct QueryMask {
+	groups: Group,
+	exclude_rigid_body: Option<RigidBodyHandle>,
+}
+
+impl QueryMask {
+	/// Hits colliders in any of `groups`.
+	pub fn new(groups: &[CollisionGroup]) -> Self {
+		Self {
+			groups: CollisionGroup::mask(groups),
+			exclude_rigid_body: None,
 		}
 	}
+
+	/// Skips colliders attached to `rigid_body`, such as the querying player's own body.
+	pub fn excluding(self, rigid_body: RigidBodyHandle) -> Self {
+		Self {
+			exclude_rigid_body: Some(rigid_body),
+			..self
+		}
+	}
+
+	fn matches(&self, collider: &Collider) -> bool {
+		collider
+			.collision_groups()
+			.memberships
+			.intersects(self.groups)
+			&& (self.exclude_rigid_body.is_none() || collider.parent() != self.exclude_rigid_body)
+	}
 }
 
+#[derive(Clone, Copy)]
 enum HandleDrop {
 	Collider(ColliderHandle),
 	RigidBody(RigidBodyHandle),
@@ -161,7 +525,18 @@ impl From<MultibodyJointHandle> for HandleDrop {
 #[allow(private_bounds)] // Don't really want to expose HandleDrop
 pub struct AutoCleanup<T: Into<HandleDrop> + Copy> {
 	pub handle: T,
-	handle_drop_sender: Sender<HandleDrop>,
+
+	// Only None once detached
+	handle_drop_sender: Option<Sender<HandleDrop>>,
+}
+
+#[allow(private_bounds)]
+impl<T: Into<HandleDrop> + Copy> AutoCleanup<T> {
+	/// Disarms the automatic cleanup, returning the raw handle. Removing it is now the caller's responsibility.
+	pub fn detach(mut self) -> T {
+		self.handle_drop_sender = None;
+		self.handle
+	}
 }
 
 impl<T: Into<HandleDrop> + Copy> Deref for AutoCleanup<T> {
@@ -180,7 +555,439 @@ impl<T: Into<HandleDrop> + Copy> DerefMut for AutoCleanup<T> {
 
 impl<T: Into<HandleDrop> + Copy> Drop for AutoCleanup<T> {
 	fn drop(&mut self) {
-		// If this is an error, then the Physics and whatever this handle was pointing to has already been dropped
-		let _ = self.handle_drop_sender.send(self.handle.into());
+		if let Some(handle_drop_sender) = &self.handle_drop_sender {
+			// If this is an error, then the Physics and whatever this handle was pointing to has already been dropped
+			let _ = handle_drop_sender.send(self.handle.into());
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::data::world::Level;
+	use rapier3d::{
+		dynamics::RigidBodyBuilder,
+		geometry::{Ball, ColliderBuilder},
+	};
+
+	fn body(physics: &mut Physics) -> AutoCleanup<RigidBodyHandle> {
+		physics.insert_rigid_body(RigidBodyBuilder::fixed())
+	}
+
+	fn id(id: u64) -> Id {
+		id.to_string().parse().unwrap()
+	}
+
+	fn assert_near(actual: f32, expected: f32) {
+		assert!((actual - expected).abs() < 1e-4, "{actual} != {expected}");
+	}
+
+	fn chunk() -> ColliderOwner {
+		ColliderOwner::Chunk(ChunkCoordinates::new(
+			id(2),
+			Vector3::zeros(),
+			Level::new(0),
+		))
+	}
+
+	/// A 2m cuboid of terrain at the origin and a 1m radius ball belonging to a player at 10m along x.
+	fn world() -> (Physics, [ColliderHandle; 2]) {
+		let mut physics = Physics::new();
+		let rigid_body = body(&mut physics).detach();
+
+		let cuboid = physics
+			.insert_collider_with_owner(
+				rigid_body,
+				ColliderBuilder::cuboid(1.0, 1.0, 1.0),
+				CollisionGroup::Terrain,
+				chunk(),
+			)
+			.detach();
+		let ball = physics
+			.insert_collider_with_owner(
+				rigid_body,
+				ColliderBuilder::ball(1.0).translation(Vector3::new(10.0, 0.0, 0.0)),
+				CollisionGroup::Player,
+				ColliderOwner::Player(id(1)),
+			)
+			.detach();
+
+		physics.update_queries();
+		(physics, [cuboid, ball])
+	}
+
+	fn everything() -> QueryMask {
+		QueryMask::new(CollisionGroup::ALL)
+	}
+
+	#[test]
+	fn intersections_with_shape_finds_every_overlap() {
+		let (physics, [cuboid, ball]) = world();
+
+		let hits = physics.intersections_with_shape(
+			&Isometry::translation(1.2, 0.0, 0.0),
+			&Ball::new(0.5),
+			everything(),
+		);
+		assert_eq!(hits.len(), 1);
+		assert_eq!(hits[0].collider, cuboid);
+		assert_eq!(hits[0].owner, Some(chunk()));
+
+		let mut hits = physics.intersections_with_shape(
+			&Isometry::translation(5.0, 0.0, 0.0),
+			&Ball::new(5.0),
+			everything(),
+		);
+		hits.sort_by_key(|hit| hit.collider == ball);
+		assert_eq!(hits.len(), 2);
+		assert_eq!(hits[0].owner, Some(chunk()));
+		assert_eq!(hits[1].collider, ball);
+		assert_eq!(hits[1].owner, Some(ColliderOwner::Player(id(1))));
+
+		let hits = physics.intersections_with_shape(
+			&Isometry::translation(5.0, 0.0, 0.0),
+			&Ball::new(1.0),
+			everything(),
+		);
+		assert!(hits.is_empty());
+	}
+
+	#[test]
+	fn cast_ray_hits_the_closest_collider() {
+		let (physics, [cuboid, ball]) = world();
+
+		let ray = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vector3::x());
+		let hit = physics.cast_ray(&ray, 100.0, everything()).unwrap();
+		assert_eq!(hit.hit.collider, cuboid);
+		assert_eq!(hit.hit.owner, Some(chunk()));
+		assert_near(hit.distance, 4.0);
+		assert_eq!(hit.normal, -Vector3::x());
+
+		let ray = Ray::new(Point3::new(5.0, 0.0, 0.0), Vector3::x());
+		let hit = physics.cast_ray(&ray, 100.0, everything()).unwrap();
+		assert_eq!(hit.hit.collider, ball);
+		assert_eq!(hit.hit.owner, Some(ColliderOwner::Player(id(1))));
+		assert_near(hit.distance, 4.0);
+
+		assert!(physics.cast_ray(&ray, 3.9, everything()).is_none());
+
+		// Starting inside hits straight away
+		let ray = Ray::new(Point3::new(0.5, 0.0, 0.0), Vector3::x());
+		let hit = physics.cast_ray(&ray, 100.0, everything()).unwrap();
+		assert_eq!(hit.hit.collider, cuboid);
+		assert_near(hit.distance, 0.0);
+	}
+
+	#[test]
+	fn cast_shape_stops_at_the_first_collider() {
+		let (physics, [cuboid, ball]) = world();
+
+		let hit = physics
+			.cast_shape(
+				&Isometry::translation(5.0, 0.0, 0.0),
+				&Vector3::new(2.0, 0.0, 0.0),
+				&Ball::new(0.5),
+				100.0,
+				everything(),
+			)
+			.unwrap();
+		assert_eq!(hit.hit.collider, ball);
+		assert_eq!(hit.hit.owner, Some(ColliderOwner::Player(id(1))));
+		assert_near(hit.distance, 3.5);
+		assert_near(hit.point.x, 9.0);
+		assert_near(hit.normal.x, -1.0);
+
+		let hit = physics
+			.cast_shape(
+				&Isometry::translation(5.0, 0.0, 0.0),
+				&-Vector3::x(),
+				&Ball::new(0.5),
+				100.0,
+				everything(),
+			)
+			.unwrap();
+		assert_eq!(hit.hit.collider, cuboid);
+		assert_eq!(hit.hit.owner, Some(chunk()));
+		assert_near(hit.distance, 3.5);
+		assert_near(hit.point.x, 1.0);
+
+		assert!(physics
+			.cast_shape(
+				&Isometry::translation(5.0, 0.0, 0.0),
+				&Vector3::y(),
+				&Ball::new(0.5),
+				100.0,
+				everything(),
+			)
+			.is_none());
+	}
+
+	#[test]
+	fn project_point_finds_the_closest_surface() {
+		let (physics, [cuboid, ball]) = world();
+
+		let hit = physics
+			.project_point(&Point3::new(6.0, 0.0, 0.0), everything())
+			.unwrap();
+		assert_eq!(hit.hit.collider, ball);
+		assert_eq!(hit.hit.owner, Some(ColliderOwner::Player(id(1))));
+		assert_near(hit.point.x, 9.0);
+		assert!(!hit.is_inside);
+
+		let hit = physics
+			.project_point(&Point3::new(3.0, 0.0, 0.0), everything())
+			.unwrap();
+		assert_eq!(hit.hit.collider, cuboid);
+		assert_eq!(hit.hit.owner, Some(chunk()));
+		assert_near(hit.point.x, 1.0);
+		assert!(!hit.is_inside);
+
+		let point = Point3::new(0.5, 0.25, 0.0);
+		let hit = physics.project_point(&point, everything()).unwrap();
+		assert_eq!(hit.hit.collider, cuboid);
+		assert_eq!(hit.point, point);
+		assert!(hit.is_inside);
+	}
+
+	#[test]
+	fn colliders_without_owners_are_still_hit() {
+		let mut physics = Physics::new();
+		let rigid_body = body(&mut physics).detach();
+		let collider = physics
+			.insert_collider_with_group(
+				rigid_body,
+				ColliderBuilder::cuboid(1.0, 1.0, 1.0),
+				CollisionGroup::Structure,
+			)
+			.detach();
+		physics.update_queries();
+
+		let ray = Ray::new(Point3::new(-5.0, 0.0, 0.0), Vector3::x());
+		let hit = physics.cast_ray(&ray, 100.0, everything()).unwrap();
+		assert_eq!(hit.hit.collider, collider);
+		assert_eq!(hit.hit.owner, None);
+	}
+
+	#[test]
+	fn removing_a_collider_forgets_its_owner() {
+		let (mut physics, [cuboid, ball]) = world();
+
+		assert!(physics.remove_now(ball));
+		assert_eq!(physics.collider_owner(ball), None);
+		assert_eq!(physics.collider_owner(cuboid), Some(chunk()));
+	}
+
+	/// What placement and interaction queries use.
+	fn placement() -> QueryMask {
+		QueryMask::new(&[CollisionGroup::Terrain, CollisionGroup::Structure])
+	}
+
+	#[test]
+	fn groups_are_distinct() {
+		for (index, group) in CollisionGroup::ALL.iter().enumerate() {
+			for other in &CollisionGroup::ALL[index + 1..] {
+				assert!(
+					!group.group().intersects(other.group()),
+					"{group:?} {other:?}"
+				);
+			}
+		}
+
+		assert_eq!(CollisionGroup::mask(&[]), Group::NONE);
+		assert_eq!(
+			CollisionGroup::mask(&[CollisionGroup::Terrain, CollisionGroup::Player]),
+			Group::GROUP_1 | Group::GROUP_3
+		);
+		assert_eq!(
+			CollisionGroup::mask(CollisionGroup::ALL),
+			Group::GROUP_1 | Group::GROUP_2 | Group::GROUP_3 | Group::GROUP_4
+		);
+	}
+
+	#[test]
+	fn solid_groups_collide_both_ways() {
+		for &group in CollisionGroup::ALL {
+			for &other in CollisionGroup::ALL {
+				let collides = group.interaction_groups().test(other.interaction_groups());
+
+				let expected = CollisionGroup::SOLID.contains(&group)
+					&& CollisionGroup::SOLID.contains(&other)
+					&& !(group == CollisionGroup::Terrain && other == CollisionGroup::Terrain);
+
+				assert_eq!(collides, expected, "{group:?} {other:?}");
+			}
+		}
+	}
+
+	#[test]
+	fn preview_collides_with_nothing() {
+		let groups = CollisionGroup::Preview.interaction_groups();
+
+		assert_eq!(groups.memberships, Group::GROUP_4);
+		assert_eq!(groups.filter, Group::NONE);
+	}
+
+	#[test]
+	fn query_masks_match_memberships() {
+		let mut physics = Physics::new();
+		let rigid_body = body(&mut physics).detach();
+
+		for &group in CollisionGroup::ALL {
+			let collider = physics
+				.insert_collider_with_group(rigid_body, ColliderBuilder::ball(1.0), group)
+				.detach();
+			let collider = physics.get_collider(collider).unwrap();
+
+			assert!(QueryMask::new(&[group]).matches(collider));
+			assert!(QueryMask::new(CollisionGroup::ALL).matches(collider));
+			assert_eq!(
+				placement().matches(collider),
+				matches!(group, CollisionGroup::Terrain | CollisionGroup::Structure)
+			);
+			assert!(!QueryMask::new(&[]).matches(collider));
+			assert!(!QueryMask::new(CollisionGroup::ALL)
+				.excluding(rigid_body)
+				.matches(collider));
+		}
+	}
+
+	#[test]
+	fn placement_rays_pass_through_players() {
+		let (physics, [cuboid, ball]) = world();
+
+		// From the far side of the player, through it, and on into the terrain
+		let ray = Ray::new(Point3::new(15.0, 0.0, 0.0), -Vector3::x());
+
+		let hit = physics.cast_ray(&ray, 100.0, everything()).unwrap();
+		assert_eq!(hit.hit.collider, ball);
+
+		let hit = physics.cast_ray(&ray, 100.0, placement()).unwrap();
+		assert_eq!(hit.hit.collider, cuboid);
+		assert_near(hit.distance, 14.0);
+
+		// Starting inside of the player
+		let ray = Ray::new(Point3::new(10.0, 0.0, 0.0), -Vector3::x());
+		let hit = physics.cast_ray(&ray, 100.0, placement()).unwrap();
+		assert_eq!(hit.hit.collider, cuboid);
+
+		assert!(physics
+			.intersections_with_shape(
+				&Isometry::translation(10.0, 0.0, 0.0),
+				&Ball::new(0.5),
+				placement(),
+			)
+			.is_empty());
+	}
+
+	#[test]
+	fn excluded_rigid_bodies_are_skipped() {
+		let (mut physics, [cuboid, _]) = world();
+
+		let player = body(&mut physics).detach();
+		physics
+			.insert_collider_with_group(
+				player,
+				ColliderBuilder::ball(1.0).translation(Vector3::new(5.0, 0.0, 0.0)),
+				CollisionGroup::Structure,
+			)
+			.detach();
+		physics.update_queries();
+
+		let ray = Ray::new(Point3::new(5.0, 0.0, 0.0), -Vector3::x());
+		let hit = physics
+			.cast_ray(&ray, 100.0, placement().excluding(player))
+			.unwrap();
+		assert_eq!(hit.hit.collider, cuboid);
+	}
+
+	#[test]
+	fn dropped_handles_are_removed_next_tick() {
+		let mut physics = Physics::new();
+		let rigid_body = body(&mut physics);
+		let handle = *rigid_body;
+
+		drop(rigid_body);
+		assert!(physics.contains(handle));
+
+		physics.tick(1.0 / 60.0);
+		assert!(!physics.contains(handle));
+	}
+
+	#[test]
+	fn dropped_rigid_bodies_take_their_colliders_and_owners() {
+		let mut physics = Physics::new();
+		let rigid_body = body(&mut physics);
+		let collider = physics
+			.insert_collider_with_owner(
+				*rigid_body,
+				ColliderBuilder::ball(1.0),
+				CollisionGroup::Player,
+				ColliderOwner::Player(Id::default()),
+			)
+			.detach();
+
+		drop(rigid_body);
+		physics.tick(1.0 / 60.0);
+
+		assert!(!physics.contains(collider));
+		assert_eq!(physics.collider_owner(collider), None);
+	}
+
+	#[test]
+	fn reinserting_before_the_tick_keeps_the_new_handle() {
+		let mut physics = Physics::new();
+		let old = body(&mut physics);
+		let old_handle = *old;
+
+		drop(old);
+		let new = body(&mut physics);
+
+		physics.tick(1.0 / 60.0);
+		assert!(!physics.contains(old_handle));
+		assert!(physics.contains(*new));
+	}
+
+	#[test]
+	fn stale_drops_dont_remove_reused_slots() {
+		let mut physics = Physics::new();
+		let old = body(&mut physics);
+
+		assert!(physics.remove_now(*old));
+		let new = body(&mut physics);
+		// The arena hands the freed slot straight back out, only the generation differs
+		assert_eq!(old.into_raw_parts().0, new.into_raw_parts().0);
+
+		drop(old);
+		physics.tick(1.0 / 60.0);
+		assert!(physics.contains(*new));
+	}
+
+	#[test]
+	fn remove_now_after_drop_is_queued() {
+		let mut physics = Physics::new();
+		let rigid_body = body(&mut physics);
+		let handle = *rigid_body;
+
+		drop(rigid_body);
+		assert!(physics.remove_now(handle));
+		assert!(!physics.contains(handle));
+		assert!(!physics.remove_now(handle));
+
+		// The queued drop finds nothing left to remove
+		physics.tick(1.0 / 60.0);
+		assert!(!physics.contains(handle));
+	}
+
+	#[test]
+	fn detached_handles_are_never_removed_automatically() {
+		let mut physics = Physics::new();
+		let handle = body(&mut physics).detach();
+
+		physics.tick(1.0 / 60.0);
+		assert!(physics.contains(handle));
+
+		assert!(physics.remove_now(handle));
+		assert!(!physics.contains(handle));
 	}
 }
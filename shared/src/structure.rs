@@ -1,69 +1,51 @@
 use crate::{
 	data::{
-		world::{BlockType, Location},
+		world::{BlockState, BlockType, Location},
 		Id,
 	},
 	message::clientbound::SyncStructure,
-	physics::{AutoCleanup, Physics},
+	physics::{AutoCleanup, ColliderOwner, CollisionGroup, Physics},
 };
-use nalgebra::{vector, Isometry3, Point3, Vector3};
+use nalgebra::{vector, Isometry3, Matrix3, Point3, Vector3};
 use rapier3d::{
 	dynamics::{RigidBodyBuilder, RigidBodyHandle},
 	geometry::{ColliderBuilder, ColliderHandle},
+	parry::bounding_volume::{Aabb, BoundingVolume},
 };
 use rustc_hash::FxBuildHasher;
 use std::collections::HashMap;
+use thiserror::Error;
 
-#[cfg(feature = "backend")]
-use crate::message::serverbound::CreateStructure;
+#[cfg(feature = "authority")]
+mod authority;
 
+// Field order matters here, the rigid body is dropped before the blocks, so the rigid body's removal (which also
+// removes it's colliders) is queued before the colliders are. Rapier's handles are generational, so the collider
+// removals that follow are no-ops rather than removing something else.
 pub struct Structure {
 	pub id: Id,
+	pub owner: Id,
 	pub rigid_body: AutoCleanup<RigidBodyHandle>,
 
 	blocks: HashMap<Vector3<i16>, Block, FxBuildHasher>,
 }
 
 impl Structure {
-	#[cfg(feature = "backend")]
-	pub fn new(
-		physics: &mut Physics,
-		CreateStructure { location, block }: CreateStructure,
-	) -> Self {
-		let (x, y, z) = location.rotation.euler_angles();
-
-		let rigid_body = physics.insert_rigid_body(
-			RigidBodyBuilder::dynamic()
-				.translation(location.position.coords)
-				.rotation(vector![x, y, z]),
-		);
-
-		let mut blocks = HashMap::with_capacity_and_hasher(1, FxBuildHasher);
-		blocks.insert(
-			nalgebra::vector![0, 0, 0],
-			Block {
-				typ: block,
-				_collider: physics.insert_rigid_body_collider(
-					*rigid_body,
-					ColliderBuilder::cuboid(0.5, 0.5, 0.5),
-				),
-			},
-		);
-
-		Self {
-			id: Id::new(),
-			rigid_body,
-
-			blocks,
-		}
+	pub fn heap_size(&self) -> usize {
+		self.blocks.capacity() * size_of::<(Vector3<i16>, Block)>()
 	}
 
 	pub fn new_from_sync(
 		physics: &mut Physics,
 		SyncStructure {
 			id,
+			owner,
 			location,
+			linear_velocity,
+			angular_velocity,
+			sleeping,
 			blocks,
+			block_states,
 		}: SyncStructure,
 	) -> Self {
 		let (x, y, z) = location.rotation.euler_angles();
@@ -71,7 +53,10 @@ impl Structure {
 		let rigid_body = physics.insert_rigid_body(
 			RigidBodyBuilder::dynamic()
 				.translation(location.position.coords)
-				.rotation(vector![x, y, z]),
+				.rotation(vector![x, y, z])
+				.linvel(linear_velocity)
+				.angvel(angular_velocity)
+				.sleeping(sleeping),
 		);
 
 		let blocks = blocks
@@ -81,10 +66,9 @@ impl Structure {
 					position,
 					Block {
 						typ,
-						_collider: physics.insert_rigid_body_collider(
-							*rigid_body,
-							ColliderBuilder::cuboid(0.5, 0.5, 0.5),
-						),
+						state: block_states.get(&position).copied().unwrap_or(0),
+						health: typ.max_health(),
+						_collider: insert_block_collider(physics, *rigid_body, id, position),
 					},
 				)
 			})
@@ -92,6 +76,7 @@ impl Structure {
 
 		Self {
 			id,
+			owner,
 			rigid_body,
 			blocks,
 		}
@@ -111,12 +96,22 @@ impl Structure {
 
 		SyncStructure {
 			id: self.id,
+			owner: self.owner,
 			location,
+			linear_velocity: *rigid_body.linvel(),
+			angular_velocity: *rigid_body.angvel(),
+			sleeping: rigid_body.is_sleeping(),
 			blocks: self
 				.blocks
 				.iter()
 				.map(|(position, block)| (*position, block.typ))
 				.collect(),
+			block_states: self
+				.blocks
+				.iter()
+				.filter(|(_, block)| block.state != 0)
+				.map(|(position, block)| (*position, block.state))
+				.collect(),
 		}
 	}
 
@@ -127,6 +122,29 @@ impl Structure {
 			.position()
 	}
 
+	/// World space bounding box of every block in the structure.
+	pub fn aabb(&self, physics: &Physics) -> Aabb {
+		let (mins, maxs) = self.blocks.keys().fold(
+			(
+				Point3::from(Vector3::repeat(f32::MAX)),
+				Point3::from(Vector3::repeat(f32::MIN)),
+			),
+			|(mins, maxs), position| {
+				let position = Point3::from(position.cast::<f32>());
+				(mins.inf(&position), maxs.sup(&position))
+			},
+		);
+
+		// Block positions are their centers
+		Aabb::new(mins, maxs)
+			.loosened(0.5)
+			.transform_by(self.get_location(physics))
+	}
+
+	pub fn get_block(&self, position: &Vector3<i16>) -> Option<&Block> {
+		self.blocks.get(position)
+	}
+
 	pub fn iter_blocks(&self) -> impl Iterator<Item = (&Vector3<i16>, &Block)> {
 		self.blocks.iter()
 	}
@@ -134,9 +152,204 @@ impl Structure {
 	pub fn num_blocks(&self) -> usize {
 		self.blocks.len()
 	}
+
+	/// Changes the state of the block at `position`, returning whether there is one.
+	pub fn set_block_state(&mut self, position: &Vector3<i16>, state: BlockState) -> bool {
+		let Some(block) = self.blocks.get_mut(position) else {
+			return false;
+		};
+
+		block.state = state;
+		true
+	}
+
+	/// Takes `amount` off of the block's health, removing it once it has none left. Returns [`None`] if there's no such
+	/// block, otherwise whether it was destroyed. A structure left with no blocks should be removed.
+	pub fn damage_block(&mut self, position: &Vector3<i16>, amount: f32) -> Option<bool> {
+		let block = self.blocks.get_mut(position)?;
+		block.health -= amount;
+
+		if block.health > 0.0 {
+			return Some(false);
+		}
+
+		self.blocks.remove(position);
+		Some(true)
+	}
+}
+
+fn insert_block_collider(
+	physics: &mut Physics,
+	rigid_body: RigidBodyHandle,
+	id: Id,
+	position: Vector3<i16>,
+) -> AutoCleanup<ColliderHandle> {
+	physics.insert_collider_with_owner(
+		rigid_body,
+		ColliderBuilder::cuboid(0.5, 0.5, 0.5).translation(position.cast()),
+		CollisionGroup::Structure,
+		ColliderOwner::StructureBlock(id, position),
+	)
+}
+
+/// Maps block positions in one structure's grid to the grid of another, one of the 24 rotations which keep a cube
+/// axis aligned, followed by a whole number of blocks of translation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LatticeTransform {
+	/// Each row and column has exactly one non-zero entry, which is 1 or -1, and the determinant is 1.
+	pub rotation: Matrix3<i16>,
+	pub translation: Vector3<i16>,
+}
+
+impl LatticeTransform {
+	/// How far each entry of the rotation matrix may be from -1, 0 or 1, a little under 3 degrees.
+	pub const ROTATION_TOLERANCE: f32 = 0.05;
+
+	/// How far, in blocks, the translation may be from a whole number of blocks.
+	pub const TRANSLATION_TOLERANCE: f32 = 0.05;
+
+	/// Finds the transform taking positions in the grid of a structure at `from` to the grid of a structure at `to`.
+	pub fn between(to: &Isometry3<f32>, from: &Isometry3<f32>) -> Result<Self, MergeError> {
+		let relative = to.inv_mul(from);
+
+		let matrix = relative.rotation.to_rotation_matrix().into_inner();
+		let rotation = matrix.map(|entry| entry.round());
+
+		if (matrix - rotation).amax() > Self::ROTATION_TOLERANCE {
+			return Err(MergeError::NotAxisAligned);
+		}
+
+		let rotation = rotation.map(|entry| entry as i16);
+
+		// Rounding a matrix which isn't close to a rotation could give something else, such as all zeros
+		let is_permutation = (0..3).all(|index| {
+			rotation.row(index).abs().sum() == 1 && rotation.column(index).abs().sum() == 1
+		});
+
+		if !is_permutation || rotation.cast::<f32>().determinant() != 1.0 {
+			return Err(MergeError::NotAxisAligned);
+		}
+
+		let translation = relative.translation.vector;
+		let rounded = translation.map(|entry| entry.round());
+
+		if (translation - rounded).amax() > Self::TRANSLATION_TOLERANCE {
+			return Err(MergeError::OffGrid);
+		}
+
+		if rounded.amax() > i16::MAX as f32 {
+			return Err(MergeError::OutOfRange);
+		}
+
+		Ok(Self {
+			rotation,
+			translation: rounded.map(|entry| entry as i16),
+		})
+	}
+
+	/// Returns [`None`] if the position would end up outside of the range a block position can hold.
+	pub fn apply(&self, position: &Vector3<i16>) -> Option<Vector3<i16>> {
+		let rotated = self.rotation.cast::<i32>() * position.cast::<i32>();
+		let translated = rotated + self.translation.cast::<i32>();
+
+		Some(Vector3::new(
+			i16::try_from(translated.x).ok()?,
+			i16::try_from(translated.y).ok()?,
+			i16::try_from(translated.z).ok()?,
+		))
+	}
+}
+
+#[derive(Debug, Error)]
+pub enum MergeError {
+	#[error("structures aren't rotated a multiple of 90 degrees from each other")]
+	NotAxisAligned,
+
+	#[error("structures aren't a whole number of blocks apart")]
+	OffGrid,
+
+	#[error("structures are too far apart for their blocks to share a grid")]
+	OutOfRange,
+
+	#[error("structures have blocks in the same place")]
+	Overlapping,
 }
 
 pub struct Block {
 	pub typ: BlockType,
+	pub state: BlockState,
+	/// Only tracked by the server, clients always see blocks at full health.
+	pub health: f32,
 	_collider: AutoCleanup<ColliderHandle>,
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn structure(physics: &mut Physics) -> Structure {
+		Structure::new_from_sync(
+			physics,
+			SyncStructure {
+				id: Id::new(),
+				owner: Id::new(),
+				location: Location::default(),
+				linear_velocity: Vector3::zeros(),
+				angular_velocity: Vector3::zeros(),
+				sleeping: false,
+				blocks: [
+					(vector![0, 0, 0], BlockType::Block),
+					(vector![1, 0, 0], BlockType::Explosive),
+				]
+				.into_iter()
+				.collect(),
+				block_states: Default::default(),
+			},
+		)
+	}
+
+	#[test]
+	fn blocks_are_destroyed_once_out_of_health() {
+		let mut physics = Physics::new();
+		let mut structure = structure(&mut physics);
+
+		assert_eq!(structure.damage_block(&vector![0, 0, 0], 60.0), Some(false));
+		assert_eq!(structure.get_block(&vector![0, 0, 0]).unwrap().health, 40.0);
+		assert_eq!(structure.damage_block(&vector![0, 0, 0], 40.0), Some(true));
+		assert!(structure.get_block(&vector![0, 0, 0]).is_none());
+
+		// Explosives are fragile
+		assert_eq!(structure.damage_block(&vector![1, 0, 0], 20.0), Some(true));
+		assert_eq!(structure.num_blocks(), 0);
+	}
+
+	#[test]
+	fn damaging_a_missing_block_does_nothing() {
+		let mut physics = Physics::new();
+		let mut structure = structure(&mut physics);
+
+		assert_eq!(structure.damage_block(&vector![0, 5, 0], 1000.0), None);
+		assert_eq!(structure.num_blocks(), 2);
+	}
+
+	#[test]
+	fn block_states_survive_being_synced() {
+		let mut physics = Physics::new();
+		let mut structure = structure(&mut physics);
+
+		assert!(structure.set_block_state(&vector![1, 0, 0], 3));
+		assert!(!structure.set_block_state(&vector![0, 5, 0], 3));
+
+		// Only blocks that aren't in state 0 are sent
+		let sync = structure.build_sync(&physics);
+		assert_eq!(sync.block_states.len(), 1);
+		assert_eq!(sync.block_states[&vector![1, 0, 0]], 3);
+
+		let synced = Structure::new_from_sync(&mut physics, sync);
+		assert_eq!(synced.get_block(&vector![0, 0, 0]).unwrap().state, 0);
+		assert_eq!(synced.get_block(&vector![1, 0, 0]).unwrap().state, 3);
+
+		structure.set_block_state(&vector![1, 0, 0], 0);
+		assert!(structure.build_sync(&physics).block_states.is_empty());
+	}
+}
@@ -0,0 +1,106 @@
+use super::{insert_block_collider, Block, LatticeTransform, MergeError, Structure};
+use crate::{data::Id, message::serverbound::CreateStructure, physics::Physics};
+use nalgebra::vector;
+use rapier3d::dynamics::RigidBodyBuilder;
+use rustc_hash::FxBuildHasher;
+use std::collections::HashMap;
+
+impl Structure {
+	pub fn new(
+		physics: &mut Physics,
+		owner: Id,
+		CreateStructure { location, block }: CreateStructure,
+	) -> Self {
+		let (x, y, z) = location.rotation.euler_angles();
+
+		let id = Id::new();
+
+		let rigid_body = physics.insert_rigid_body(
+			RigidBodyBuilder::dynamic()
+				.translation(location.position.coords)
+				.rotation(vector![x, y, z]),
+		);
+
+		let position = nalgebra::vector![0, 0, 0];
+
+		let mut blocks = HashMap::with_capacity_and_hasher(1, FxBuildHasher);
+		blocks.insert(
+			position,
+			Block {
+				typ: block,
+				state: 0,
+				health: block.max_health(),
+				_collider: insert_block_collider(physics, *rigid_body, id, position),
+			},
+		);
+
+		Self {
+			id,
+			owner,
+			rigid_body,
+
+			blocks,
+		}
+	}
+
+	/// Bytes allocated for the structure's blocks, not including their colliders.
+	/// Moves every block of `other` into this structure, keeping them where they are in the world. This structure keeps
+	/// it's id and owner, `other` is removed. If `other`'s blocks don't line up with this structure's grid, or would
+	/// overlap it's blocks, nothing changes and `other` is given back.
+	///
+	/// The caller is responsible for resyncing this structure and telling clients `other` was removed.
+	pub fn absorb(
+		&mut self,
+		physics: &mut Physics,
+		other: Structure,
+	) -> Result<(), (Structure, MergeError)> {
+		let transform = match LatticeTransform::between(
+			self.get_location(physics),
+			other.get_location(physics),
+		) {
+			Ok(transform) => transform,
+			Err(error) => return Err((other, error)),
+		};
+
+		let mut positions = HashMap::with_capacity_and_hasher(other.blocks.len(), FxBuildHasher);
+		for position in other.blocks.keys() {
+			let Some(rebased) = transform.apply(position) else {
+				return Err((other, MergeError::OutOfRange));
+			};
+
+			if self.blocks.contains_key(&rebased) {
+				return Err((other, MergeError::Overlapping));
+			}
+
+			positions.insert(*position, rebased);
+		}
+
+		let Structure {
+			rigid_body, blocks, ..
+		} = other;
+
+		// Removes other's colliders along with it, they're rebuilt on this structure's rigid body below
+		physics.remove_now(*rigid_body);
+
+		for (
+			position,
+			Block {
+				typ, state, health, ..
+			},
+		) in blocks
+		{
+			let position = positions[&position];
+			self.blocks.insert(
+				position,
+				Block {
+					typ,
+					state,
+					health,
+					_collider: insert_block_collider(physics, *self.rigid_body, self.id, position),
+				},
+			);
+		}
+
+		Ok(())
+	}
+}
@@ -0,0 +1,284 @@
+use crate::clock::{self, Clock};
+use dashmap::{mapref::entry::Entry, DashMap};
+use log::warn;
+use rustc_hash::FxBuildHasher;
+use std::{
+	sync::{Arc, LazyLock, Mutex},
+	time::{Duration, Instant},
+};
+
+#[doc(hidden)]
+pub use log;
+
+/// Shared by every [`throttled_warn!`].
+///
+/// [`throttled_warn!`]: crate::throttled_warn
+pub static THROTTLE: LazyLock<Throttle> = LazyLock::new(|| Throttle::new(clock::system()));
+
+/// Keeps track of how often lines that something outside of our control could cause over and over, like a client
+/// flooding invalid messages, have been logged. Each key is logged the first time it comes up, then at most once per
+/// interval, with how many were suppressed in between.
+pub struct Throttle {
+	keys: DashMap<&'static str, Key, FxBuildHasher>,
+	clock: Arc<dyn Clock>,
+	last_evicted: Mutex<Instant>,
+}
+
+struct Key {
+	last_logged: Instant,
+	interval: Duration,
+	/// Since [`Key::last_logged`].
+	suppressed: u64,
+}
+
+impl Throttle {
+	/// How often keys that haven't been logged for longer than their interval are forgotten, they'd be logged straight
+	/// away the next time they come up anyway.
+	const EVICT_INTERVAL: Duration = Duration::from_secs(60);
+
+	pub fn new(clock: Arc<dyn Clock>) -> Self {
+		Self {
+			keys: DashMap::with_hasher(FxBuildHasher),
+			last_evicted: Mutex::new(clock.now_instant()),
+			clock,
+		}
+	}
+
+	/// Whether a line under `key` should be logged now, and if so how many were suppressed since it last was.
+	pub fn check(&self, key: &'static str, interval: Duration) -> Option<u64> {
+		let now = self.clock.now_instant();
+
+		let suppressed = match self.keys.entry(key) {
+			Entry::Occupied(mut entry) => {
+				let entry = entry.get_mut();
+
+				if now.duration_since(entry.last_logged) < entry.interval {
+					entry.suppressed += 1;
+					return None;
+				}
+
+				entry.last_logged = now;
+				entry.interval = interval;
+				std::mem::take(&mut entry.suppressed)
+			}
+			Entry::Vacant(entry) => {
+				entry.insert(Key {
+					last_logged: now,
+					interval,
+					suppressed: 0,
+				});
+				0
+			}
+		};
+
+		// Only ever done while logging, which is already rare, so suppressed lines stay cheap
+		self.evict(now);
+
+		Some(suppressed)
+	}
+
+	/// Logs how many lines every key has suppressed since it was last logged and forgets them all, for shutting down.
+	pub fn flush(&self) {
+		self.keys.retain(|key, entry| {
+			report(key, entry.suppressed);
+			false
+		});
+	}
+
+	/// Forgets keys that haven't been logged for longer than their interval, logging anything they suppressed. Does
+	/// nothing if it's been less than [`Throttle::EVICT_INTERVAL`] since it last did.
+	fn evict(&self, now: Instant) {
+		{
+			let Ok(mut last_evicted) = self.last_evicted.try_lock() else {
+				return;
+			};
+
+			if now.duration_since(*last_evicted) < Self::EVICT_INTERVAL {
+				return;
+			}
+
+			*last_evicted = now;
+		}
+
+		self.keys.retain(|key, entry| {
+			let stale = now.duration_since(entry.last_logged) >= entry.interval;
+
+			if stale {
+				report(key, entry.suppressed);
+			}
+
+			!stale
+		});
+	}
+}
+
+fn report(key: &str, suppressed: u64) {
+	if suppressed > 0 {
+		warn!(
+			throttle_key = key, suppressed = suppressed;
+			"Suppressed {suppressed} more \"{key}\" warnings"
+		);
+	}
+}
+
+/// [`log::warn!`], but only logged the first time `key` comes up and then at most once every `interval`, see
+/// [`Throttle`]. Lines logged after some were suppressed say how many, and have a `suppressed` field. Nothing is
+/// formatted for suppressed lines.
+///
+/// ```ignore
+/// throttled_warn!("sector::tick_overrun", Duration::from_secs(10), "Tick took {duration:?}");
+/// ```
+#[macro_export]
+macro_rules! throttled_warn {
+	($key:expr, $interval:expr, $($field:ident $(:$capture:tt)? = $value:expr),+; $($arg:tt)+) => {
+		if $crate::throttle::log::log_enabled!($crate::throttle::log::Level::Warn) {
+			match $crate::throttle::THROTTLE.check($key, $interval) {
+				None => {}
+				Some(0) => $crate::throttle::log::warn!($($field $(:$capture)? = $value),+; $($arg)+),
+				Some(suppressed) => $crate::throttle::log::warn!(
+					$($field $(:$capture)? = $value,)+ suppressed = suppressed;
+					"{} (suppressed {suppressed} similar)", format_args!($($arg)+)
+				),
+			}
+		}
+	};
+	($key:expr, $interval:expr, $($arg:tt)+) => {
+		if $crate::throttle::log::log_enabled!($crate::throttle::log::Level::Warn) {
+			match $crate::throttle::THROTTLE.check($key, $interval) {
+				None => {}
+				Some(0) => $crate::throttle::log::warn!($($arg)+),
+				Some(suppressed) => $crate::throttle::log::warn!(
+					suppressed = suppressed;
+					"{} (suppressed {suppressed} similar)", format_args!($($arg)+)
+				),
+			}
+		}
+	};
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::clock::MockClock;
+
+	const SECOND: Duration = Duration::from_secs(1);
+
+	fn throttle() -> (Throttle, Arc<MockClock>) {
+		let clock = Arc::new(MockClock::new());
+		(Throttle::new(clock.clone()), clock)
+	}
+
+	#[test]
+	fn first_line_is_logged() {
+		let (throttle, _) = throttle();
+
+		assert_eq!(throttle.check("a", SECOND), Some(0));
+	}
+
+	#[test]
+	fn lines_within_interval_are_suppressed() {
+		let (throttle, clock) = throttle();
+
+		assert_eq!(throttle.check("a", SECOND), Some(0));
+		assert_eq!(throttle.check("a", SECOND), None);
+
+		clock.advance(SECOND - Duration::from_millis(1));
+		assert_eq!(throttle.check("a", SECOND), None);
+
+		clock.advance(Duration::from_millis(1));
+		assert_eq!(throttle.check("a", SECOND), Some(2));
+	}
+
+	#[test]
+	fn suppressed_count_resets_once_logged() {
+		let (throttle, clock) = throttle();
+
+		throttle.check("a", SECOND);
+		for _ in 0..5 {
+			assert_eq!(throttle.check("a", SECOND), None);
+		}
+
+		clock.advance(SECOND);
+		assert_eq!(throttle.check("a", SECOND), Some(5));
+
+		clock.advance(SECOND);
+		assert_eq!(throttle.check("a", SECOND), Some(0));
+	}
+
+	#[test]
+	fn keys_are_independent() {
+		let (throttle, _) = throttle();
+
+		assert_eq!(throttle.check("a", SECOND), Some(0));
+		assert_eq!(throttle.check("b", SECOND), Some(0));
+		assert_eq!(throttle.check("a", SECOND), None);
+		assert_eq!(throttle.check("b", SECOND), None);
+	}
+
+	#[test]
+	fn new_interval_applies_after_logging() {
+		let (throttle, clock) = throttle();
+
+		throttle.check("a", SECOND);
+
+		// Still held to the interval it was last logged with
+		assert_eq!(throttle.check("a", SECOND * 10), None);
+
+		clock.advance(SECOND);
+		assert_eq!(throttle.check("a", SECOND * 10), Some(1));
+
+		clock.advance(SECOND * 5);
+		assert_eq!(throttle.check("a", SECOND), None);
+
+		clock.advance(SECOND * 5);
+		assert_eq!(throttle.check("a", SECOND), Some(1));
+	}
+
+	#[test]
+	fn stale_keys_are_evicted() {
+		let (throttle, clock) = throttle();
+
+		throttle.check("stale", SECOND);
+		throttle.check("fresh", Throttle::EVICT_INTERVAL * 2);
+		assert_eq!(throttle.keys.len(), 2);
+
+		// Eviction only happens alongside logging
+		clock.advance(Throttle::EVICT_INTERVAL);
+		assert_eq!(throttle.check("other", SECOND), Some(0));
+
+		assert!(!throttle.keys.contains_key("stale"));
+		assert!(throttle.keys.contains_key("fresh"));
+		assert!(throttle.keys.contains_key("other"));
+
+		// Forgotten, so it's logged straight away with nothing suppressed
+		assert_eq!(throttle.check("stale", SECOND), Some(0));
+	}
+
+	#[test]
+	fn eviction_waits_for_its_interval() {
+		let (throttle, clock) = throttle();
+
+		throttle.check("stale", SECOND);
+
+		clock.advance(Throttle::EVICT_INTERVAL - SECOND);
+		throttle.check("other", SECOND);
+		assert!(throttle.keys.contains_key("stale"));
+
+		clock.advance(SECOND);
+		throttle.check("another", SECOND);
+		assert!(!throttle.keys.contains_key("stale"));
+	}
+
+	#[test]
+	fn flush_forgets_everything() {
+		let (throttle, _) = throttle();
+
+		throttle.check("a", SECOND);
+		throttle.check("a", SECOND);
+		throttle.check("b", SECOND);
+
+		throttle.flush();
+		assert!(throttle.keys.is_empty());
+		assert_eq!(throttle.check("a", SECOND), Some(0));
+	}
+}
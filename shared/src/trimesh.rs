@@ -0,0 +1,245 @@
+use log::debug;
+use nalgebra::Point3;
+use rapier3d::geometry::{ColliderBuilder, SharedShape, TriMesh, TriMeshFlags};
+use rustc_hash::FxBuildHasher;
+use std::{collections::HashMap, time::Duration};
+
+/// Triangles with less area than this, in square meters, are dropped as degenerate.
+pub const AREA_EPSILON: f32 = 1e-6;
+
+/// Vertices closer together than this, in meters, are welded into one.
+pub const WELD_DISTANCE: f32 = 1e-4;
+
+/// Processing Rapier does on top of [`sanitize`], which only fails with flags this doesn't use, but is still checked.
+const FLAGS: TriMeshFlags =
+	TriMeshFlags::DELETE_DEGENERATE_TRIANGLES.union(TriMeshFlags::DELETE_DUPLICATE_TRIANGLES);
+
+/// Cleans up marching cubes output so that it can be used as a collider. Marching cubes emits zero area triangles
+/// when a corner's density is exactly 0, and vertices that are only almost the same after interpolation, both of which
+/// Rapier can choke on. Coincident vertices are welded, then any triangle that is out of range, has a non-finite
+/// vertex, or has less than [`AREA_EPSILON`] area is dropped, along with vertices no triangle uses.
+pub fn sanitize(
+	vertices: &[Point3<f32>],
+	indices: &[[u32; 3]],
+) -> (Vec<Point3<f32>>, Vec<[u32; 3]>) {
+	let mut welded = HashMap::with_hasher(FxBuildHasher);
+	let mut sanitized_vertices = vec![];
+	let mut sanitized_indices = vec![];
+
+	for triangle in indices {
+		let Some(corners) = triangle
+			.iter()
+			.map(|index| vertices.get(*index as usize).copied())
+			.collect::<Option<Vec<_>>>()
+		else {
+			continue;
+		};
+
+		if !corners
+			.iter()
+			.all(|corner| corner.iter().all(|c| c.is_finite()))
+		{
+			continue;
+		}
+
+		let area = (corners[1] - corners[0])
+			.cross(&(corners[2] - corners[0]))
+			.norm() / 2.0;
+
+		if area < AREA_EPSILON {
+			continue;
+		}
+
+		let keys = [0, 1, 2].map(|corner| {
+			corners[corner]
+				.coords
+				.map(|c| (c / WELD_DISTANCE).round() as i64)
+		});
+
+		// Welding can collapse a thin triangle which still had enough area before, checked before any of it's vertices
+		// are added so that they aren't left unused
+		if keys[0] == keys[1] || keys[1] == keys[2] || keys[0] == keys[2] {
+			continue;
+		}
+
+		let triangle = [0, 1, 2].map(|corner| {
+			*welded.entry(keys[corner]).or_insert_with(|| {
+				sanitized_vertices.push(corners[corner]);
+				sanitized_vertices.len() as u32 - 1
+			})
+		});
+
+		sanitized_indices.push(triangle);
+	}
+
+	(sanitized_vertices, sanitized_indices)
+}
+
+/// Builds a trimesh collider from marching cubes output, see [`sanitize`]. Returns [`None`] if there's nothing left
+/// to collide with, as Rapier panics on a trimesh without triangles.
+pub fn collider(vertices: &[Point3<f32>], indices: &[[u32; 3]]) -> Option<ColliderBuilder> {
+	let (vertices, indices) = sanitize(vertices, indices);
+
+	if indices.is_empty() {
+		return None;
+	}
+
+	let mut trimesh = TriMesh::new(vertices.clone(), indices.clone());
+
+	if let Err(error) = trimesh.set_flags(FLAGS) {
+		crate::throttled_warn!(
+			"trimesh::rejected",
+			Duration::from_secs(10),
+			"Rapier rejected a sanitized trimesh ({error:?}), using it without processing instead"
+		);
+		trimesh = TriMesh::new(vertices, indices);
+	}
+
+	// Rapier's own processing may remove triangles that sanitizing kept
+	if trimesh.indices().is_empty() {
+		debug!("No triangles left after Rapier processed a trimesh, skipping its collider");
+		return None;
+	}
+
+	Some(ColliderBuilder::new(SharedShape::new(trimesh)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use nalgebra::point;
+
+	fn triangle_area(vertices: &[Point3<f32>], triangle: &[u32; 3]) -> f32 {
+		let [a, b, c] = triangle.map(|index| vertices[index as usize]);
+		(b - a).cross(&(c - a)).norm() / 2.0
+	}
+
+	/// Every triangle is in range with enough area, and every vertex is used by one.
+	fn assert_clean(vertices: &[Point3<f32>], indices: &[[u32; 3]]) {
+		let mut used = vec![false; vertices.len()];
+
+		for triangle in indices {
+			assert!(triangle
+				.iter()
+				.all(|index| (*index as usize) < vertices.len()));
+			assert!(triangle_area(vertices, triangle) >= AREA_EPSILON);
+
+			for index in triangle {
+				used[*index as usize] = true;
+			}
+		}
+
+		assert!(used.into_iter().all(|used| used), "unused vertices left");
+	}
+
+	#[test]
+	fn good_triangles_are_kept() {
+		let vertices = [
+			point![0.0, 0.0, 0.0],
+			point![1.0, 0.0, 0.0],
+			point![0.0, 1.0, 0.0],
+			point![1.0, 1.0, 0.0],
+		];
+		let indices = [[0, 1, 2], [1, 3, 2]];
+
+		let (sanitized_vertices, sanitized_indices) = sanitize(&vertices, &indices);
+
+		assert_eq!(sanitized_vertices, vertices);
+		assert_eq!(sanitized_indices, indices);
+	}
+
+	#[test]
+	fn coincident_vertices_are_welded() {
+		// Marching cubes emits every triangle's vertices separately, so shared corners only almost match
+		let vertices = [
+			point![0.0, 0.0, 0.0],
+			point![1.0, 0.0, 0.0],
+			point![0.0, 1.0, 0.0],
+			point![1.0, 0.00001, 0.0],
+			point![1.0, 1.0, 0.0],
+			point![0.0, 0.99999, 0.0],
+		];
+
+		let (vertices, indices) = sanitize(&vertices, &[[0, 1, 2], [3, 4, 5]]);
+
+		assert_eq!(vertices.len(), 4);
+		assert_eq!(indices, [[0, 1, 2], [1, 3, 2]]);
+		assert_clean(&vertices, &indices);
+	}
+
+	#[test]
+	fn degenerate_triangles_are_dropped() {
+		let vertices = [
+			point![0.0, 0.0, 0.0],
+			point![1.0, 0.0, 0.0],
+			point![0.0, 1.0, 0.0],
+			// Collinear
+			point![2.0, 0.0, 0.0],
+			// Not finite
+			point![f32::NAN, 0.0, 0.0],
+			point![0.0, f32::INFINITY, 0.0],
+		];
+
+		let indices = [
+			[0, 0, 0],
+			[0, 1, 1],
+			[0, 1, 3],
+			[0, 1, 4],
+			[0, 5, 1],
+			// Out of range
+			[0, 1, 6],
+			[0, 1, 2],
+		];
+
+		let (vertices, indices) = sanitize(&vertices, &indices);
+
+		assert_eq!(indices, [[0, 1, 2]]);
+		assert_clean(&vertices, &indices);
+	}
+
+	#[test]
+	fn collapsed_triangles_leave_no_vertices_behind() {
+		// Thin enough to collapse once welded, but still above the area epsilon before
+		let vertices = [
+			point![0.0, 0.0, 0.0],
+			point![0.0, WELD_DISTANCE * 0.4, 0.0],
+			point![100.0, 0.0, 0.0],
+			point![0.0, 0.0, 5.0],
+			point![1.0, 0.0, 5.0],
+			point![0.0, 1.0, 5.0],
+		];
+
+		assert!(triangle_area(&vertices, &[0, 1, 2]) >= AREA_EPSILON);
+
+		let (sanitized_vertices, indices) = sanitize(&vertices, &[[0, 1, 2], [3, 4, 5]]);
+
+		assert_eq!(sanitized_vertices, vertices[3..]);
+		assert_eq!(indices, [[0, 1, 2]]);
+		assert_clean(&sanitized_vertices, &indices);
+	}
+
+	#[test]
+	fn all_zero_has_no_collider() {
+		let vertices = [Point3::origin(); 6];
+
+		assert!(collider(&vertices, &[[0, 1, 2], [3, 4, 5]]).is_none());
+		assert!(collider(&[], &[]).is_none());
+	}
+
+	#[test]
+	fn collider_is_built_from_what_survives() {
+		let vertices = [
+			point![0.0, 0.0, 0.0],
+			point![1.0, 0.0, 0.0],
+			point![0.0, 1.0, 0.0],
+			point![0.0, 0.0, 0.0],
+		];
+
+		let collider = collider(&vertices, &[[0, 1, 3], [0, 1, 2]])
+			.expect("a good triangle is left")
+			.build();
+
+		let trimesh = collider.shape().as_trimesh().unwrap();
+		assert_eq!(trimesh.indices().len(), 1);
+	}
+}